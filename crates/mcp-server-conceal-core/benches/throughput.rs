@@ -0,0 +1,106 @@
+//! Regex-only throughput of the detection and replacement hot path
+//!
+//! Run with `cargo bench -p mcp-server-conceal-core --features bridge`. The
+//! documented goal is 100 MB/min (about 1.6 MiB/s) of MCP traffic through a
+//! regex-only pipeline; criterion reports the measured rate per benchmark.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use mcp_server_conceal_core::config::DetectionMode;
+use mcp_server_conceal_core::{Config, Direction, OllamaConfig, Pipeline, RegexDetectionEngine};
+use serde_json::json;
+use std::path::PathBuf;
+
+const CORPUS_BYTES: usize = 1 << 20;
+
+const PROSE: &str = "The deployment finished without errors and all health checks \
+    passed on the first attempt. Latency stayed within the agreed budget while \
+    the cache warmed up, and no alerts fired during the rollout window.";
+
+/// Tool results shaped like real MCP responses, one message per line. With
+/// `pii`, every other content block carries an email, a phone number and an SSN.
+fn corpus(pii: bool) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut size = 0;
+    let mut id = 0;
+    while size < CORPUS_BYTES {
+        let content: Vec<_> = (0..8)
+            .map(|block| {
+                let text = if pii && block % 2 == 0 {
+                    format!(
+                        "{} Escalate to user{}@example.com or call 555-{:03}-{:04}; SSN on file is {:03}-{:02}-{:04}.",
+                        PROSE, id % 500, id % 1000, id % 10000, 100 + id % 800, 10 + id % 90, id % 10000
+                    )
+                } else {
+                    PROSE.to_string()
+                };
+                json!({ "type": "text", "text": text })
+            })
+            .collect();
+        let line = json!({ "jsonrpc": "2.0", "id": id, "result": { "content": content, "isError": false } }).to_string();
+        size += line.len() + 1;
+        lines.push(line);
+        id += 1;
+    }
+    lines
+}
+
+// The pattern set from the README's sample configuration
+const PATTERNS: &[(&str, &str)] = &[
+    ("email", r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b"),
+    ("phone", r"\b(?:\+?1[-\.\s]?)?(?:\(?[0-9]{3}\)?[-\.\s]?)?[0-9]{3}[-\.\s]?[0-9]{4}\b"),
+    ("ssn", r"\b\d{3}-\d{2}-\d{4}\b"),
+    ("credit_card", r"\b\d{4}[-\s]?\d{4}[-\s]?\d{4}[-\s]?\d{4}\b"),
+    ("ip_address", r"\b(?:(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)\.){3}(?:25[0-5]|2[0-4][0-9]|[01]?[0-9][0-9]?)\b"),
+    ("url", r"https?://[^\s/$.?#].[^\s]*"),
+];
+
+fn regex_only_config() -> Config {
+    let mut config = Config::default();
+    config.detection.mode = DetectionMode::Regex;
+    config.detection.patterns = PATTERNS.iter().map(|(name, pattern)| (name.to_string(), (*pattern).into())).collect();
+    config.mapping.database_path = PathBuf::from(":memory:");
+    config
+}
+
+fn bench_pipeline(c: &mut Criterion) {
+    let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+    let mut group = c.benchmark_group("pipeline_regex_only");
+    group.sample_size(10);
+
+    for (name, pii) in [("clean", false), ("with_pii", true)] {
+        let lines = corpus(pii);
+        let bytes: usize = lines.iter().map(|line| line.len() + 1).sum();
+        let mut pipeline = Pipeline::new(&regex_only_config(), OllamaConfig::default(), Direction::Response).unwrap();
+        // Mappings already exist in a running proxy; measure the steady state
+        runtime.block_on(async {
+            for line in &lines {
+                pipeline.process_line(line).await.unwrap();
+            }
+        });
+
+        group.throughput(Throughput::Bytes(bytes as u64));
+        group.bench_function(name, |b| {
+            b.iter(|| runtime.block_on(async {
+                for line in &lines {
+                    black_box(pipeline.process_line(line).await.unwrap());
+                }
+            }))
+        });
+    }
+    group.finish();
+}
+
+fn bench_detection(c: &mut Criterion) {
+    let engine = RegexDetectionEngine::new(&regex_only_config().detection).unwrap();
+    let mut group = c.benchmark_group("detect_in_text");
+
+    for (name, pii) in [("clean", false), ("with_pii", true)] {
+        let text = corpus(pii).join("\n");
+        group.throughput(Throughput::Bytes(text.len() as u64));
+        group.bench_function(name, |b| b.iter(|| black_box(engine.detect_in_text(&text))));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_pipeline, bench_detection);
+criterion_main!(benches);
@@ -0,0 +1,695 @@
+//! Runtime admin channel
+//!
+//! With `[admin] socket` set, a running proxy accepts control commands on a
+//! unix socket: live statistics, clearing the LLM cache, flushing mappings,
+//! reloading the configuration and toggling detect-only mode. Requests and
+//! replies are one JSON object per line; `mcp-server-conceal ctl` sends them.
+//!
+//! With `[admin] metrics` set, the same statistics are served over HTTP at
+//! `/metrics` in the Prometheus text format, for scraping.
+//!
+//! Pipelines run in their own tasks, so commands reach them through
+//! [`RuntimeControl`], which they check before each message. The same path
+//! reloads the configuration when its file changes or on SIGHUP.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tracing::{debug, info, warn};
+
+use crate::audit::AuditLog;
+use crate::config::{Config, ConfigSource, DetectionMode, MappingConfig};
+use crate::degradation::DegradationReport;
+use crate::listener::{Connection, ListenAddress, Listener};
+use crate::mapping::{MappingStore, RuntimeCounters};
+
+/// Longest request line the metrics endpoint reads
+const METRICS_REQUEST_LINE_BYTES: u64 = 8 * 1024;
+const METRICS_READ_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AdminConfig {
+    /// Control socket of the running proxy; relative paths live in the data
+    /// directory. Unset, there is no admin channel.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket: Option<PathBuf>,
+    /// `host:port` serving the runtime counters at `/metrics`. Unset, there
+    /// is no metrics endpoint.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metrics: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReloadConfig {
+    /// Reload the configuration file when it changes
+    #[serde(default = "default_watch")]
+    pub watch: bool,
+    /// How often the file is checked for changes
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+impl Default for ReloadConfig {
+    fn default() -> Self {
+        Self {
+            watch: default_watch(),
+            poll_interval_ms: default_poll_interval_ms(),
+        }
+    }
+}
+
+fn default_watch() -> bool {
+    true
+}
+
+fn default_poll_interval_ms() -> u64 {
+    2000
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "snake_case")]
+pub enum AdminCommand {
+    /// Mapping, cache and runtime statistics
+    Stats,
+    /// Delete cached LLM detections
+    ClearCache,
+    /// Delete all mappings; values seen again get new fakes
+    FlushMappings,
+    /// Re-read the configuration file and apply its detection settings
+    Reload,
+    /// Detect and count entities but forward messages unchanged
+    DetectOnly { enabled: bool },
+}
+
+impl AdminCommand {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AdminCommand::Stats => "stats",
+            AdminCommand::ClearCache => "clear_cache",
+            AdminCommand::FlushMappings => "flush_mappings",
+            AdminCommand::Reload => "reload",
+            AdminCommand::DetectOnly { .. } => "detect_only",
+        }
+    }
+}
+
+/// Settings changed at runtime, shared by the proxy's pipelines.
+#[derive(Clone, Default)]
+pub struct RuntimeControl(Arc<ControlValues>);
+
+#[derive(Default)]
+struct ControlValues {
+    detect_only: AtomicBool,
+    /// Moves on when mapping stores must be reopened, dropping what they
+    /// keep in memory about rows deleted under them
+    store_generation: AtomicU64,
+    /// Configuration to apply, with its generation
+    config: Mutex<(u64, Option<Arc<Config>>)>,
+    /// Configuration the proxy was started with, which reloads are checked against
+    started: Option<Arc<Config>>,
+    /// Whether the proxy started an LLM client
+    llm_enabled: bool,
+}
+
+impl RuntimeControl {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Control of a proxy started with `config`, with or without an LLM client.
+    pub fn started_with(config: &Config, llm_enabled: bool) -> Self {
+        Self(Arc::new(ControlValues {
+            started: Some(Arc::new(config.clone())),
+            llm_enabled,
+            ..ControlValues::default()
+        }))
+    }
+
+    pub fn detect_only(&self) -> bool {
+        self.0.detect_only.load(Ordering::Relaxed)
+    }
+
+    pub fn set_detect_only(&self, enabled: bool) {
+        self.0.detect_only.store(enabled, Ordering::Relaxed);
+    }
+
+    pub fn store_generation(&self) -> u64 {
+        self.0.store_generation.load(Ordering::Acquire)
+    }
+
+    pub fn invalidate_stores(&self) {
+        self.0.store_generation.fetch_add(1, Ordering::AcqRel);
+    }
+
+    pub fn config_generation(&self) -> u64 {
+        self.0.config.lock().unwrap_or_else(|e| e.into_inner()).0
+    }
+
+    /// The configuration pipelines are to apply, with its generation.
+    pub fn config(&self) -> (u64, Option<Arc<Config>>) {
+        self.0.config.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn publish_config(&self, config: Config) {
+        let mut current = self.0.config.lock().unwrap_or_else(|e| e.into_inner());
+        *current = (current.0 + 1, Some(Arc::new(config)));
+    }
+}
+
+/// What admin commands act on.
+pub struct AdminContext {
+    pub control: RuntimeControl,
+    /// Mapping configuration of each namespace the proxy writes to; the
+    /// client sessions started below them are found in the database
+    pub namespaces: Vec<MappingConfig>,
+    pub counters: RuntimeCounters,
+    pub degradation: DegradationReport,
+    /// What `reload` reads; without a file there is nothing to reload
+    pub config_source: Option<ConfigSource>,
+    pub audit_log: AuditLog,
+}
+
+impl AdminContext {
+    pub fn handle(&self, command: &AdminCommand) -> Result<Value> {
+        self.audit_log.record("admin_command", json!({ "command": command.name() }));
+        match command {
+            AdminCommand::Stats => self.stats(),
+            AdminCommand::ClearCache => {
+                let mut cleared = 0;
+                for mapping in self.namespaces_with_sessions()? {
+                    cleared += MappingStore::new(mapping)?.clear_llm_cache()?;
+                }
+                self.control.invalidate_stores();
+                info!("Admin: cleared {} LLM cache entries", cleared);
+                Ok(json!({ "cleared": cleared }))
+            }
+            AdminCommand::FlushMappings => {
+                let mut flushed = 0;
+                for mapping in self.namespaces_with_sessions()? {
+                    flushed += MappingStore::new(mapping)?.clear_all_mappings()?;
+                }
+                self.control.invalidate_stores();
+                info!("Admin: flushed {} mappings", flushed);
+                Ok(json!({ "flushed": flushed }))
+            }
+            AdminCommand::Reload => {
+                let source = self.config_source.as_ref()
+                    .ok_or_else(|| anyhow::anyhow!("The proxy was started without a configuration file"))?;
+                let generation = reload(source, &self.control, &self.audit_log, "admin")?;
+                Ok(json!({ "reloaded": source.path, "generation": generation }))
+            }
+            AdminCommand::DetectOnly { enabled } => {
+                self.control.set_detect_only(*enabled);
+                warn!("Admin: detect-only mode {}", if *enabled { "on, messages are forwarded unchanged" } else { "off" });
+                Ok(json!({ "detect_only": enabled }))
+            }
+        }
+    }
+
+    /// The configured namespaces, each followed by its client sessions.
+    fn namespaces_with_sessions(&self) -> Result<Vec<MappingConfig>> {
+        let mut all = Vec::new();
+        for mapping in &self.namespaces {
+            let sessions = MappingStore::new(mapping.clone())?.session_namespaces()?;
+            all.push(mapping.clone());
+            all.extend(sessions.into_iter().map(|namespace| MappingConfig { namespace: Some(namespace), ..mapping.clone() }));
+        }
+        Ok(all)
+    }
+
+    fn stats(&self) -> Result<Value> {
+        let mut namespaces = Vec::new();
+        for mapping in self.namespaces_with_sessions()? {
+            let stats = MappingStore::new(mapping.clone())?.get_statistics()?;
+            namespaces.push(json!({
+                "namespace": mapping.namespace.clone().unwrap_or_default(),
+                "total_mappings": stats.total_mappings,
+                "total_cache_entries": stats.total_cache_entries,
+                "cache_hits": stats.cache_hits,
+                "mappings_by_type": stats.mappings_by_type,
+            }));
+        }
+        Ok(json!({
+            "namespaces": namespaces,
+            "runtime": self.counters.snapshot(),
+            "degradation": self.degradation.to_json(),
+            "detect_only": self.control.detect_only(),
+            "config_generation": self.control.config_generation(),
+        }))
+    }
+
+    /// The runtime counters in the Prometheus text exposition format.
+    pub fn metrics(&self) -> String {
+        let runtime = self.counters.snapshot();
+        let mut metrics = String::new();
+        let mut family = |name: &str, help: &str, samples: &[(&str, u64)]| {
+            metrics.push_str(&format!("# HELP {} {}\n# TYPE {} counter\n", name, help, name));
+            for (labels, value) in samples {
+                metrics.push_str(&format!("{}{} {}\n", name, labels, value));
+            }
+        };
+        family("mcp_conceal_mapping_lookups_total", "Detected values looked up in the mapping store",
+               &[(r#"{result="hit"}"#, runtime.mapping_hits), (r#"{result="miss"}"#, runtime.mapping_misses)]);
+        family("mcp_conceal_llm_cache_lookups_total", "Texts looked up in the LLM cache",
+               &[(r#"{result="hit"}"#, runtime.llm_cache_hits), (r#"{result="miss"}"#, runtime.llm_cache_misses)]);
+        family("mcp_conceal_entities_anonymized_total", "Entities replaced",
+               &[(r#"{direction="request"}"#, runtime.request.entities_anonymized), (r#"{direction="response"}"#, runtime.response.entities_anonymized)]);
+        family("mcp_conceal_bytes_processed_total", "Message bytes read",
+               &[(r#"{direction="request"}"#, runtime.request.bytes_processed), (r#"{direction="response"}"#, runtime.response.bytes_processed)]);
+        family("mcp_conceal_messages_total", "Messages processed", &[("", self.degradation.messages())]);
+        family("mcp_conceal_degraded_messages_total", "Messages not processed as configured",
+               &[("", self.degradation.degraded_messages())]);
+        metrics
+    }
+}
+
+/// Reads and validates the configuration file and hands it to the pipelines,
+/// which apply it before their next message. An invalid file changes nothing,
+/// and the file itself is never rewritten. Returns the new configuration
+/// generation.
+pub fn reload(source: &ConfigSource, control: &RuntimeControl, audit_log: &AuditLog, trigger: &str) -> Result<u64> {
+    let path = &source.path;
+    let config = source.reload()?;
+    config.validate()?;
+    let restart_needed = match &control.0.started {
+        Some(started) => check_reload(started, control.0.llm_enabled, &config)?,
+        None => Vec::new(),
+    };
+    for section in &restart_needed {
+        warn!("Reloaded configuration changes [{}], which only takes effect after a restart", section);
+    }
+    control.publish_config(config);
+    let generation = control.config_generation();
+    info!("Reloaded configuration from {} ({})", path.display(), trigger);
+    audit_log.record("config_reloaded", json!({
+        "path": path,
+        "trigger": trigger,
+        "generation": generation,
+        "restart_needed": restart_needed,
+    }));
+    Ok(generation)
+}
+
+/// Sections read once at startup: the mapping database and the bypass
+/// tokens signed with its passphrase, the LLM client, the approval gate,
+/// schema hints and the rest of what `Pipeline::apply_config` leaves alone.
+fn startup_sections(config: &Config) -> [(&'static str, Value); 9] {
+    [
+        ("mapping", json!(config.mapping)),
+        ("llm", json!(config.llm)),
+        ("approval", json!(config.approval)),
+        ("detection.schema_hints", json!(config.detection.schema_hints)),
+        ("audit", json!(config.audit)),
+        ("canary", json!(config.canary)),
+        ("quarantine", json!(config.quarantine)),
+        ("i18n", json!(config.i18n)),
+        ("language", json!(config.language)),
+    ]
+}
+
+/// Refuses a reload the running proxy cannot apply, and lists the sections
+/// it changes that wait for a restart.
+fn check_reload(started: &Config, llm_enabled: bool, reloaded: &Config) -> Result<Vec<&'static str>> {
+    let uses_llm = |config: &Config| !matches!(config.detection.mode, DetectionMode::Regex);
+    if uses_llm(reloaded) && !(uses_llm(started) && llm_enabled) {
+        return Err(anyhow::anyhow!(
+            "Detection mode {:?} needs the LLM client, which this proxy did not start; restart it to switch modes",
+            reloaded.detection.mode
+        ));
+    }
+    Ok(startup_sections(started).into_iter()
+        .zip(startup_sections(reloaded))
+        .filter(|((_, before), (_, after))| before != after)
+        .map(|((section, _), _)| section)
+        .collect())
+}
+
+/// Reloads the configuration whenever its file changes and, on unix, on
+/// SIGHUP, until the task is aborted.
+pub fn watch_config(
+    source: ConfigSource,
+    reload_config: &ReloadConfig,
+    control: RuntimeControl,
+    audit_log: AuditLog,
+) -> tokio::task::JoinHandle<()> {
+    let interval = reload_config.watch.then(|| Duration::from_millis(reload_config.poll_interval_ms.max(100)));
+    // Taken before the task runs, so an edit made right after this call is seen
+    let mut seen = file_version(&source.path);
+    tokio::spawn(async move {
+        let path = source.path.clone();
+        let mut hangup = hangup_signal();
+        loop {
+            let trigger = tokio::select! {
+                _ = sleep_or_pending(interval) => {
+                    let version = file_version(&path);
+                    if version == seen {
+                        continue;
+                    }
+                    seen = version;
+                    "file changed"
+                }
+                Some(()) = next_hangup(&mut hangup) => "SIGHUP",
+            };
+            if let Err(e) = reload(&source, &control, &audit_log, trigger) {
+                warn!("Keeping the running configuration, {} could not be reloaded: {}", path.display(), e);
+            }
+        }
+    })
+}
+
+/// Modification time and size, so an edit within the clock's resolution is still seen.
+fn file_version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+async fn sleep_or_pending(interval: Option<Duration>) {
+    match interval {
+        Some(interval) => tokio::time::sleep(interval).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(unix)]
+type Hangup = Option<tokio::signal::unix::Signal>;
+#[cfg(not(unix))]
+type Hangup = ();
+
+#[cfg(unix)]
+fn hangup_signal() -> Hangup {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    signal(SignalKind::hangup())
+        .map_err(|e| warn!("Cannot listen for SIGHUP: {}", e))
+        .ok()
+}
+
+#[cfg(not(unix))]
+fn hangup_signal() -> Hangup {}
+
+#[cfg(unix)]
+async fn next_hangup(hangup: &mut Hangup) -> Option<()> {
+    match hangup {
+        Some(signal) => signal.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(not(unix))]
+async fn next_hangup(_hangup: &mut Hangup) -> Option<()> {
+    std::future::pending().await
+}
+
+/// Binds the admin socket and answers commands on it until the task is aborted.
+pub async fn serve(path: &Path, context: Arc<AdminContext>) -> Result<tokio::task::JoinHandle<()>> {
+    let listener = Listener::bind(&ListenAddress::Unix(path.to_path_buf()), None).await?;
+    info!("  Admin socket: {}", path.display());
+
+    Ok(tokio::spawn(async move {
+        loop {
+            let Connection { reader, mut writer, .. } = match listener.accept().await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    warn!("Failed to accept an admin connection: {}", e);
+                    continue;
+                }
+            };
+            let context = context.clone();
+            tokio::spawn(async move {
+                let mut lines = BufReader::new(reader).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    let reply = match serde_json::from_str::<AdminCommand>(&line) {
+                        Ok(command) => {
+                            debug!("Admin command: {}", command.name());
+                            match context.handle(&command) {
+                                Ok(result) => json!({ "ok": true, "result": result }),
+                                Err(e) => json!({ "ok": false, "error": e.to_string() }),
+                            }
+                        }
+                        Err(e) => json!({ "ok": false, "error": format!("Invalid command: {}", e) }),
+                    };
+                    let mut reply = reply.to_string();
+                    reply.push('\n');
+                    if writer.write_all(reply.as_bytes()).await.is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+    }))
+}
+
+/// Serves `/metrics` on `address` until the task is aborted.
+pub async fn serve_metrics(address: &str, context: Arc<AdminContext>) -> Result<tokio::task::JoinHandle<()>> {
+    let listener = tokio::net::TcpListener::bind(address).await
+        .map_err(|e| anyhow::anyhow!("Failed to bind the metrics endpoint {}: {}", address, e))?;
+    info!("  Metrics: http://{}/metrics", listener.local_addr()?);
+    Ok(serve_metrics_on(listener, context))
+}
+
+fn serve_metrics_on(listener: tokio::net::TcpListener, context: Arc<AdminContext>) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let mut stream = match listener.accept().await {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    warn!("Failed to accept a metrics connection: {}", e);
+                    continue;
+                }
+            };
+            let context = context.clone();
+            tokio::spawn(async move {
+                // Only the request line matters; the rest of the request is ignored
+                let mut request_line = String::new();
+                let mut reader = BufReader::new(&mut stream).take(METRICS_REQUEST_LINE_BYTES);
+                if !matches!(tokio::time::timeout(METRICS_READ_TIMEOUT, reader.read_line(&mut request_line)).await, Ok(Ok(_))) {
+                    return;
+                }
+                let (status, body) = match request_line.split_whitespace().take(2).collect::<Vec<_>>()[..] {
+                    ["GET", "/metrics"] => ("200 OK", context.metrics()),
+                    _ => ("404 Not Found", String::new()),
+                };
+                let response = format!("HTTP/1.1 {}\r\ncontent-type: text/plain; version=0.0.4\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                                       status, body.len(), body);
+                let _ = stream.write_all(response.as_bytes()).await;
+            });
+        }
+    })
+}
+
+/// Sends one command to a running proxy and returns its result.
+#[cfg(unix)]
+pub async fn send(path: &Path, command: &AdminCommand) -> Result<Value> {
+    let stream = tokio::net::UnixStream::connect(path).await
+        .map_err(|e| anyhow::anyhow!("Cannot reach the proxy's admin socket {}: {}", path.display(), e))?;
+    let (reader, mut writer) = stream.into_split();
+    let mut request = serde_json::to_string(command)?;
+    request.push('\n');
+    writer.write_all(request.as_bytes()).await?;
+
+    let reply = BufReader::new(reader).lines().next_line().await?
+        .ok_or_else(|| anyhow::anyhow!("The proxy closed the admin connection without replying"))?;
+    let reply: Value = serde_json::from_str(&reply)?;
+    match reply.get("ok").and_then(Value::as_bool) {
+        Some(true) => Ok(reply.get("result").cloned().unwrap_or(Value::Null)),
+        _ => Err(anyhow::anyhow!("{}", reply.get("error").and_then(Value::as_str).unwrap_or("The proxy rejected the command"))),
+    }
+}
+
+#[cfg(not(unix))]
+pub async fn send(_path: &Path, _command: &AdminCommand) -> Result<Value> {
+    Err(anyhow::anyhow!("The admin channel needs unix domain sockets"))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_commands_round_trip_over_the_socket() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mapping = MappingConfig {
+            database_path: dir.path().join("mappings.db"),
+            ..MappingConfig::default()
+        };
+        let control = RuntimeControl::new();
+        let context = AdminContext {
+            control: control.clone(),
+            namespaces: vec![mapping],
+            counters: RuntimeCounters::default(),
+            degradation: DegradationReport::new(),
+            config_source: None,
+            audit_log: AuditLog::disabled(),
+        };
+        let socket = dir.path().join("admin.sock");
+        let server = serve(&socket, Arc::new(context)).await.unwrap();
+
+        let stats = send(&socket, &AdminCommand::Stats).await.unwrap();
+        assert_eq!(stats["namespaces"][0]["total_mappings"], 0);
+        assert_eq!(stats["detect_only"], false);
+
+        send(&socket, &AdminCommand::DetectOnly { enabled: true }).await.unwrap();
+        assert!(control.detect_only());
+
+        let generation = control.store_generation();
+        assert_eq!(send(&socket, &AdminCommand::FlushMappings).await.unwrap()["flushed"], 0);
+        assert_eq!(control.store_generation(), generation + 1);
+
+        assert!(send(&socket, &AdminCommand::Reload).await.is_err());
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn test_metrics_are_served_for_scraping() {
+        use tokio::io::AsyncReadExt;
+
+        let counters = RuntimeCounters::default();
+        counters.record_mapping_lookup(true);
+        counters.record_mapping_lookup(false);
+        counters.record_bytes(crate::pipeline::Direction::Response, 120);
+        let context = AdminContext {
+            control: RuntimeControl::new(),
+            namespaces: Vec::new(),
+            counters,
+            degradation: DegradationReport::new(),
+            config_source: None,
+            audit_log: AuditLog::disabled(),
+        };
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let server = serve_metrics_on(listener, Arc::new(context));
+
+        let get = |path: &'static str| async move {
+            let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+            stream.write_all(format!("GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).as_bytes()).await.unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).await.unwrap();
+            response
+        };
+        let metrics = get("/metrics").await;
+        assert!(metrics.starts_with("HTTP/1.1 200 OK"));
+        assert!(metrics.contains("# TYPE mcp_conceal_mapping_lookups_total counter"));
+        assert!(metrics.contains("mcp_conceal_mapping_lookups_total{result=\"hit\"} 1\n"));
+        assert!(metrics.contains("mcp_conceal_bytes_processed_total{direction=\"response\"} 120\n"));
+        assert!(metrics.contains("mcp_conceal_degraded_messages_total 0\n"));
+        assert!(get("/").await.starts_with("HTTP/1.1 404"));
+        server.abort();
+    }
+
+    #[test]
+    fn test_commands_reach_session_namespaces() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mapping = MappingConfig {
+            database_path: dir.path().join("mappings.db"),
+            namespace: Some("github".to_string()),
+            keep_sessions: true,
+            ..MappingConfig::default()
+        };
+        let scope = crate::session::SessionScope::new(mapping.clone());
+        let session = scope.start();
+        scope.open_store().unwrap().0.store_mapping(&crate::config::AnonymizedEntity {
+            entity_type: "email".to_string(),
+            original_value: "jane@acme.com".to_string(),
+            fake_value: "user1@example.com".to_string(),
+            mapping_id: uuid::Uuid::new_v4().to_string(),
+        }).unwrap();
+        let context = AdminContext {
+            control: RuntimeControl::new(),
+            namespaces: vec![mapping],
+            counters: RuntimeCounters::default(),
+            degradation: DegradationReport::new(),
+            config_source: None,
+            audit_log: AuditLog::disabled(),
+        };
+
+        let stats = context.handle(&AdminCommand::Stats).unwrap();
+        assert_eq!(stats["namespaces"][1]["namespace"], session);
+        assert_eq!(stats["namespaces"][1]["total_mappings"], 1);
+        assert_eq!(context.handle(&AdminCommand::FlushMappings).unwrap()["flushed"], 1);
+        assert_eq!(context.handle(&AdminCommand::Stats).unwrap()["namespaces"].as_array().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_config_file_changes_are_reloaded() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        let mut config = Config::default();
+        config.mapping.database_path = dir.path().join("mappings.db");
+        config.to_file(&path).unwrap();
+
+        let control = RuntimeControl::new();
+        let watcher = watch_config(ConfigSource { path: path.clone(), profile: None, preset: None }, &ReloadConfig { watch: true, poll_interval_ms: 100 }, control.clone(), AuditLog::disabled());
+
+        config.detection.patterns.insert("employee_id".to_string(), r"\bEMP-\d{4}\b".into());
+        config.to_file(&path).unwrap();
+        tokio::time::timeout(Duration::from_secs(30), async {
+            while control.config_generation() == 0 {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        }).await.expect("the changed file was not reloaded");
+        watcher.abort();
+        let (generation, reloaded) = control.config();
+        assert_eq!(generation, 1);
+        assert!(reloaded.unwrap().detection.patterns.contains_key("employee_id"));
+    }
+
+    #[test]
+    fn test_reloads_that_cannot_be_applied_change_nothing() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        let source = ConfigSource { path: path.clone(), profile: None, preset: None };
+        let mut config = Config::default();
+        config.detection.mode = DetectionMode::Regex;
+        config.mapping.database_path = dir.path().join("mappings.db");
+        let control = RuntimeControl::started_with(&config, false);
+
+        // A broken file leaves the running configuration in place
+        std::fs::write(&path, "detection = [").unwrap();
+        assert!(reload(&source, &control, &AuditLog::disabled(), "test").is_err());
+        assert_eq!(control.config_generation(), 0);
+
+        // So does a switch to LLM detection without an LLM client
+        let mut llm = config.clone();
+        llm.detection.mode = DetectionMode::RegexLlm;
+        llm.to_file(&path).unwrap();
+        assert!(reload(&source, &control, &AuditLog::disabled(), "test").is_err());
+        assert_eq!(control.config_generation(), 0);
+
+        // Sections read at startup are applied, and reported, at the next restart
+        let mut moved = config.clone();
+        moved.mapping.database_path = dir.path().join("elsewhere.db");
+        moved.approval.enabled = !config.approval.enabled;
+        assert_eq!(check_reload(&config, false, &moved).unwrap(), vec!["mapping", "approval"]);
+        assert!(check_reload(&config, false, &config).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reload_does_not_rewrite_a_legacy_file() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        let mut config = Config::default();
+        config.mapping.database_path = dir.path().join("mappings.db");
+        let mut legacy: toml::Table = toml::from_str(&toml::to_string(&config).unwrap()).unwrap();
+        legacy.remove("config_version");
+        let contents = toml::to_string(&legacy).unwrap();
+        std::fs::write(&path, &contents).unwrap();
+
+        let control = RuntimeControl::new();
+        let source = ConfigSource { path: path.clone(), profile: None, preset: None };
+        assert_eq!(reload(&source, &control, &AuditLog::disabled(), "test").unwrap(), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), contents);
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_commands_are_tagged_json() {
+        let command: AdminCommand = serde_json::from_str(r#"{"command":"detect_only","enabled":false}"#).unwrap();
+        assert_eq!(command, AdminCommand::DetectOnly { enabled: false });
+        assert_eq!(serde_json::to_string(&AdminCommand::ClearCache).unwrap(), r#"{"command":"clear_cache"}"#);
+    }
+}
@@ -0,0 +1,186 @@
+//! Dry-run review of what the proxy would change
+//!
+//! Before enforcing anonymization on live traffic, reviewers can run captured
+//! messages through `mcp-server-conceal scan`. Every replacement is wrapped in
+//! a visible marker, `⟦email→kim@example.net⟧`, or shown in a side-by-side
+//! HTML page with the original next to it. The scan uses a throwaway
+//! in-memory mapping store, so it never touches the live database and its
+//! fakes can differ from the ones the proxy would pick.
+
+use crate::config::{base_entity_type, Config};
+use crate::i18n::Catalog;
+use crate::ollama::OllamaConfig;
+use crate::pipeline::{Direction, Pipeline};
+use anyhow::Result;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+const MARKER_OPEN: char = '⟦';
+const MARKER_ARROW: char = '→';
+const MARKER_CLOSE: char = '⟧';
+
+/// Review marker for one replacement.
+pub fn marker(entity_type: &str, fake_value: &str) -> String {
+    let base_type = base_entity_type(entity_type);
+    format!("{}{}{}{}{}", MARKER_OPEN, base_type, MARKER_ARROW, fake_value, MARKER_CLOSE)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanFormat {
+    /// Each input line with replacements marked inline
+    Annotated,
+    /// Self-contained HTML page, original and annotated side by side
+    Html,
+}
+
+impl FromStr for ScanFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "annotated" | "text" => Ok(ScanFormat::Annotated),
+            "html" => Ok(ScanFormat::Html),
+            other => Err(anyhow::anyhow!("Unknown scan format '{}' (supported: annotated, html)", other)),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ScanReport {
+    pub output: String,
+    pub lines: usize,
+    pub changed_lines: usize,
+}
+
+/// Runs each line of `input` through the response pipeline, as the proxy
+/// would for server output. JSON lines are processed as messages (protocol
+/// messages stay untouched, as on the wire); other lines as plain text.
+pub async fn scan(config: &Config, ollama_config: OllamaConfig, input: &str, format: ScanFormat) -> Result<ScanReport> {
+    let mut config = config.clone();
+    config.mapping.database_path = PathBuf::from(":memory:");
+    let mut pipeline = Pipeline::new(&config, ollama_config, Direction::Response)?.with_annotations(true);
+
+    let mut report = ScanReport::default();
+    let mut rows = Vec::new();
+    for line in input.lines().filter(|line| !line.trim().is_empty()) {
+        let annotated = match serde_json::from_str::<serde_json::Value>(line) {
+            Ok(_) => pipeline.process_line(line).await?,
+            Err(_) => pipeline.sanitize_text(line).await?,
+        };
+        report.lines += 1;
+        if annotated != line {
+            report.changed_lines += 1;
+        }
+        rows.push((line.to_string(), annotated.into_owned()));
+    }
+
+    report.output = match format {
+        ScanFormat::Annotated => rows.iter().map(|(_, annotated)| format!("{}\n", annotated)).collect(),
+        ScanFormat::Html => render_html(&rows, &Catalog::new(&config.i18n)?),
+    };
+    Ok(report)
+}
+
+fn render_html(rows: &[(String, String)], catalog: &Catalog) -> String {
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html lang=\"{}\"><head><meta charset=\"utf-8\"><title>{}</title>\n",
+        escape_html(catalog.lang()),
+        escape_html(catalog.text("scan_title")),
+    );
+    html.push_str(concat!(
+        "<style>body{font-family:sans-serif}table{border-collapse:collapse;width:100%}",
+        "td,th{border:1px solid #ccc;padding:4px;vertical-align:top;text-align:left}",
+        "td{font-family:monospace;white-space:pre-wrap;word-break:break-all}",
+        "tr.unchanged{color:#888}mark{background:#fde68a}mark small{color:#92400e}</style>\n",
+        "</head><body>\n<table>\n",
+    ));
+    html.push_str(&format!(
+        "<tr><th>#</th><th>{}</th><th>{}</th></tr>\n",
+        escape_html(catalog.text("scan_column_original")),
+        escape_html(catalog.text("scan_column_anonymized")),
+    ));
+    for (index, (original, annotated)) in rows.iter().enumerate() {
+        let class = if original == annotated { "unchanged" } else { "changed" };
+        html.push_str(&format!(
+            "<tr class=\"{}\"><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            class,
+            index + 1,
+            escape_html(original),
+            highlight_markers(annotated),
+        ));
+    }
+    html.push_str("</table>\n</body></html>\n");
+    html
+}
+
+// Markers become `<mark>` elements labelled with the entity type
+fn highlight_markers(annotated: &str) -> String {
+    let mut html = String::with_capacity(annotated.len());
+    let mut rest = annotated;
+    while let Some(open) = rest.find(MARKER_OPEN) {
+        let Some(close) = rest[open..].find(MARKER_CLOSE).map(|i| open + i) else {
+            break;
+        };
+        let Some((entity_type, fake)) = rest[open + MARKER_OPEN.len_utf8()..close].split_once(MARKER_ARROW) else {
+            break;
+        };
+        html.push_str(&escape_html(&rest[..open]));
+        html.push_str(&format!("<mark><small>{}</small> {}</mark>", escape_html(entity_type), escape_html(fake)));
+        rest = &rest[close + MARKER_CLOSE.len_utf8()..];
+    }
+    html.push_str(&escape_html(rest));
+    html
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DetectionMode;
+
+    #[tokio::test]
+    async fn test_scan_marks_replacements_without_touching_the_database() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.detection.mode = DetectionMode::Regex;
+        config.mapping.database_path = temp_dir.path().join("mappings.db");
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text","text":"mail jane@acme.com"}]}}"#, "\n",
+            "plain text for bob@globex.com\n",
+            "nothing here\n",
+        );
+
+        let report = scan(&config, OllamaConfig::default(), input, ScanFormat::Annotated).await.unwrap();
+        let lines: Vec<&str> = report.output.lines().collect();
+        assert_eq!((report.lines, report.changed_lines), (3, 2));
+        assert!(lines[0].contains("mail ⟦email→") && !lines[0].contains("jane@acme.com"), "{}", lines[0]);
+        assert!(lines[1].starts_with("plain text for ⟦email→"), "{}", lines[1]);
+        assert_eq!(lines[2], "nothing here");
+        assert!(!config.mapping.database_path.exists());
+    }
+
+    #[test]
+    fn test_html_escapes_and_highlights() {
+        let html = render_html(&[(
+            "<b>jane@acme.com</b>".to_string(),
+            format!("<b>{}</b>", marker("email@user.email", "kim@example.net")),
+        )], &Catalog::default());
+        assert!(html.contains("<td>&lt;b&gt;jane@acme.com&lt;/b&gt;</td>"));
+        assert!(html.contains("&lt;b&gt;<mark><small>email</small> kim@example.net</mark>&lt;/b&gt;"));
+        assert!(html.contains("<th>Anonymized</th>"));
+    }
+
+    #[test]
+    fn test_html_report_uses_configured_locale() {
+        let catalog = Catalog::new(&crate::i18n::I18nConfig { locale: "fr_FR".to_string(), catalog: None }).unwrap();
+        let html = render_html(&[], &catalog);
+        assert!(html.contains("<html lang=\"fr-FR\">"));
+        assert!(html.contains("<th>Anonymisé</th>"));
+    }
+}
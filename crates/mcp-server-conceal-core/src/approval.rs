@@ -0,0 +1,346 @@
+//! Operator approval of first-seen critical entities
+//!
+//! In high-stakes setups an anonymization rule that misfires on a private key
+//! or an SSN should not go unnoticed. With approvals enabled, a message in
+//! which a critical entity type is seen for the first time (no mapping exists
+//! for the value yet) is held: the proxy writes a pending request to the
+//! approval directory and waits until an operator runs
+//! `mcp-server-conceal ctl approve <id>` or `ctl deny <id>`, or until the
+//! timeout applies the configured default.
+//!
+//! Decisions are HMAC-signed with a key derived from the mapping passphrase,
+//! so only someone holding it can release a message. Pending requests carry
+//! entity types and the method only, never values.
+
+use crate::config::{base_entity_type, Config};
+use crate::crypto;
+use crate::i18n::Catalog;
+use crate::pipeline::Direction;
+use crate::secrets::{self, Passphrase};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+const PENDING_FILE_EXTENSION: &str = "pending";
+const DECISION_FILE_EXTENSION: &str = "decision";
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+const SIGNING_KEY_CONTEXT: &[u8] = b"mcp-server-conceal approval decisions v1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ApprovalDecision {
+    /// Forward the anonymized message
+    Approve,
+    /// Withhold the message and forget the new mappings
+    Deny,
+}
+
+impl ApprovalDecision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApprovalDecision::Approve => "approve",
+            ApprovalDecision::Deny => "deny",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "approve" => Some(ApprovalDecision::Approve),
+            "deny" => Some(ApprovalDecision::Deny),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApprovalConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Types whose first sighting holds the message
+    #[serde(default = "default_entity_types")]
+    pub entity_types: Vec<String>,
+    /// How long a message is held before `on_timeout` applies
+    #[serde(default = "default_timeout_seconds")]
+    pub timeout_seconds: u64,
+    #[serde(default = "default_on_timeout")]
+    pub on_timeout: ApprovalDecision,
+    /// Where pending requests and decisions are exchanged; relative paths
+    /// live in the data directory
+    #[serde(default = "default_approval_dir")]
+    pub dir: PathBuf,
+}
+
+impl Default for ApprovalConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            entity_types: default_entity_types(),
+            timeout_seconds: default_timeout_seconds(),
+            on_timeout: default_on_timeout(),
+            dir: default_approval_dir(),
+        }
+    }
+}
+
+fn default_entity_types() -> Vec<String> {
+    ["private_key", "ssn"].into_iter().map(String::from).collect()
+}
+
+fn default_timeout_seconds() -> u64 {
+    300
+}
+
+fn default_on_timeout() -> ApprovalDecision {
+    ApprovalDecision::Deny
+}
+
+fn default_approval_dir() -> PathBuf {
+    PathBuf::from("approvals")
+}
+
+impl ApprovalConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.enabled && self.timeout_seconds == 0 {
+            return Err(anyhow::anyhow!("approval.timeout_seconds must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// Whether a first-seen entity of this type holds its message.
+    pub fn requires_approval(&self, entity_type: &str) -> bool {
+        let base_type = base_entity_type(entity_type);
+        self.entity_types.iter().any(|t| t.eq_ignore_ascii_case(base_type))
+    }
+}
+
+/// A held message, as shown to operators.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingApproval {
+    pub id: String,
+    pub direction: String,
+    /// First-seen critical types in the message
+    pub entity_types: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    pub requested_at: u64,
+    pub expires_at: u64,
+}
+
+/// How a held message was released.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Resolution {
+    pub decision: ApprovalDecision,
+    /// No operator decided in time; `decision` is the configured default
+    pub timed_out: bool,
+}
+
+/// Derives the decision signing key from the mapping passphrase.
+pub fn signing_key(passphrase: &Passphrase) -> Vec<u8> {
+    crypto::derive_key(passphrase, SIGNING_KEY_CONTEXT).to_vec()
+}
+
+fn sign(key: &[u8], id: &str, decision: ApprovalDecision) -> String {
+    crypto::keyed_hash(key, format!("{}:{}", id, decision.as_str()).as_bytes())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+fn file_path(dir: &Path, id: &str, extension: &str) -> PathBuf {
+    dir.join(format!("{}.{}", id, extension))
+}
+
+/// Requests still waiting for a decision. Requests left behind by a proxy
+/// that stopped before its timeout are deleted.
+pub fn list_pending(dir: &Path) -> Vec<PendingApproval> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut pending = Vec::new();
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().and_then(|e| e.to_str()) != Some(PENDING_FILE_EXTENSION) {
+            continue;
+        }
+        let parsed = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| Ok(serde_json::from_str::<PendingApproval>(&contents)?));
+        match parsed {
+            Ok(request) if request.expires_at <= now_secs() => {
+                debug!("Removing stale approval request {}", request.id);
+                std::fs::remove_file(&path).ok();
+            }
+            Ok(request) => pending.push(request),
+            Err(e) => warn!("Ignoring approval request {}: {}", path.display(), e),
+        }
+    }
+    pending.sort_by_key(|request| request.requested_at);
+    pending
+}
+
+/// Records an operator's decision for a pending request. Returns false when
+/// no such request is waiting.
+pub fn decide(dir: &Path, key: &[u8], id: &str, decision: ApprovalDecision) -> Result<bool> {
+    if !list_pending(dir).iter().any(|request| request.id == id) {
+        return Ok(false);
+    }
+    let contents = format!("{}.{}", decision.as_str(), sign(key, id, decision));
+    std::fs::write(file_path(dir, id, DECISION_FILE_EXTENSION), contents)?;
+    Ok(true)
+}
+
+/// Holds messages for operator approval on behalf of a pipeline.
+#[derive(Clone)]
+pub struct ApprovalGate {
+    config: ApprovalConfig,
+    key: Vec<u8>,
+    /// Error message sent to the client in place of a withheld response
+    withheld_message: String,
+    /// Error message sent to the sender of a withheld request
+    withheld_request_message: String,
+}
+
+impl ApprovalGate {
+    pub fn new(config: &ApprovalConfig, key: Vec<u8>) -> Self {
+        let catalog = Catalog::default();
+        Self {
+            config: config.clone(),
+            key,
+            withheld_message: catalog.text("approval_withheld").to_string(),
+            withheld_request_message: catalog.text("approval_withheld_request").to_string(),
+        }
+    }
+
+    /// The configured gate, if approvals are enabled. The mapping passphrase
+    /// is required.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        if !config.approval.enabled {
+            return Ok(None);
+        }
+        let passphrase = secrets::require_passphrase(&config.mapping)
+            .map_err(|e| anyhow::anyhow!("Operator approvals need the mapping passphrase: {}", e))?;
+        std::fs::create_dir_all(&config.approval.dir)?;
+        let mut gate = Self::new(&config.approval, signing_key(&passphrase));
+        let catalog = Catalog::new(&config.i18n)?;
+        gate.withheld_message = catalog.text("approval_withheld").to_string();
+        gate.withheld_request_message = catalog.text("approval_withheld_request").to_string();
+        Ok(Some(gate))
+    }
+
+    pub fn config(&self) -> &ApprovalConfig {
+        &self.config
+    }
+
+    pub fn withheld_message(&self) -> &str {
+        &self.withheld_message
+    }
+
+    pub fn withheld_request_message(&self) -> &str {
+        &self.withheld_request_message
+    }
+
+    /// Writes a pending request for a held message.
+    pub fn submit(&self, direction: Direction, entity_types: Vec<String>, method: Option<String>) -> Result<PendingApproval> {
+        let requested_at = now_secs();
+        let request = PendingApproval {
+            id: Uuid::new_v4().to_string(),
+            direction: direction.as_str().to_string(),
+            entity_types,
+            method,
+            requested_at,
+            expires_at: requested_at + self.config.timeout_seconds,
+        };
+        std::fs::create_dir_all(&self.config.dir)?;
+        std::fs::write(
+            file_path(&self.config.dir, &request.id, PENDING_FILE_EXTENSION),
+            serde_json::to_string_pretty(&request)?,
+        )?;
+        Ok(request)
+    }
+
+    /// Waits for an operator decision on `request`, or for its timeout.
+    pub async fn wait(&self, request: &PendingApproval) -> Resolution {
+        let deadline = tokio::time::Instant::now() + Duration::from_secs(self.config.timeout_seconds);
+        let resolution = loop {
+            if let Some(decision) = self.read_decision(&request.id) {
+                break Resolution { decision, timed_out: false };
+            }
+            if tokio::time::Instant::now() >= deadline {
+                break Resolution { decision: self.config.on_timeout, timed_out: true };
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        };
+
+        std::fs::remove_file(file_path(&self.config.dir, &request.id, PENDING_FILE_EXTENSION)).ok();
+        std::fs::remove_file(file_path(&self.config.dir, &request.id, DECISION_FILE_EXTENSION)).ok();
+        resolution
+    }
+
+    // Forged or corrupt decisions are discarded, so the request keeps waiting
+    fn read_decision(&self, id: &str) -> Option<ApprovalDecision> {
+        let path = file_path(&self.config.dir, id, DECISION_FILE_EXTENSION);
+        let contents = std::fs::read_to_string(&path).ok()?;
+        let decision = contents.trim().split_once('.').and_then(|(name, signature)| {
+            ApprovalDecision::from_name(name).filter(|decision| sign(&self.key, id, *decision) == signature)
+        });
+        if decision.is_none() {
+            warn!("Discarding approval decision {} with an invalid signature", path.display());
+            std::fs::remove_file(&path).ok();
+        }
+        decision
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const KEY: &[u8] = b"test-signing-key";
+
+    fn gate(dir: &Path, timeout_seconds: u64) -> ApprovalGate {
+        let config = ApprovalConfig {
+            enabled: true,
+            timeout_seconds,
+            dir: dir.to_path_buf(),
+            ..ApprovalConfig::default()
+        };
+        ApprovalGate::new(&config, KEY.to_vec())
+    }
+
+    #[tokio::test]
+    async fn test_signed_decision_releases_the_message() {
+        let temp_dir = TempDir::new().unwrap();
+        let gate = gate(temp_dir.path(), 60);
+        let request = gate.submit(Direction::Response, vec!["ssn".to_string()], None).unwrap();
+        assert_eq!(list_pending(temp_dir.path()), vec![request.clone()]);
+
+        // A decision signed with another key is ignored
+        assert!(decide(temp_dir.path(), b"other-key", &request.id, ApprovalDecision::Approve).unwrap());
+        assert_eq!(gate.read_decision(&request.id), None);
+
+        assert!(decide(temp_dir.path(), KEY, &request.id, ApprovalDecision::Approve).unwrap());
+        let resolution = gate.wait(&request).await;
+        assert_eq!(resolution, Resolution { decision: ApprovalDecision::Approve, timed_out: false });
+        assert!(list_pending(temp_dir.path()).is_empty());
+        assert!(!decide(temp_dir.path(), KEY, &request.id, ApprovalDecision::Deny).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_timeout_applies_default_policy() {
+        let temp_dir = TempDir::new().unwrap();
+        let gate = gate(temp_dir.path(), 1);
+        let request = gate.submit(Direction::Request, vec!["private_key".to_string()], Some("tools/call".to_string())).unwrap();
+
+        let resolution = gate.wait(&request).await;
+        assert_eq!(resolution, Resolution { decision: ApprovalDecision::Deny, timed_out: true });
+        assert!(!temp_dir.path().join(format!("{}.pending", request.id)).exists());
+
+        assert!(gate.config().requires_approval("SSN@hr") && !gate.config().requires_approval("email"));
+    }
+}
@@ -0,0 +1,100 @@
+//! Append-only audit trail
+//!
+//! Security-relevant events (policy bypasses, expired mappings, degraded
+//! processing) are written as JSON lines to `audit.path`. Without a configured
+//! path, events go to the regular log under the `audit` target. Inside a
+//! proxy session, each event carries the id of the session manifest.
+
+use crate::config::AuditConfig;
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+#[derive(Clone, Default)]
+pub struct AuditLog {
+    file: Option<Arc<Mutex<File>>>,
+    session_id: Option<Arc<str>>,
+}
+
+impl AuditLog {
+    pub fn new(config: &AuditConfig) -> Result<Self> {
+        let file = match &config.path {
+            Some(path) => {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                let file = OpenOptions::new().create(true).append(true).open(path)
+                    .map_err(|e| anyhow::anyhow!("Failed to open audit log {}: {}", path.display(), e))?;
+                Some(Arc::new(Mutex::new(file)))
+            }
+            None => None,
+        };
+        Ok(Self { file, session_id: None })
+    }
+
+    /// Tags every event with the id of the session manifest.
+    pub fn with_session_id(mut self, session_id: &str) -> Self {
+        self.session_id = Some(Arc::from(session_id));
+        self
+    }
+
+    /// An audit log that only writes to the regular log output.
+    pub fn disabled() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&self, event: &str, details: Value) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let mut entry = json!({ "timestamp": timestamp, "event": event });
+        if let Some(session_id) = &self.session_id {
+            entry["session_id"] = json!(session_id.as_ref());
+        }
+        if let (Some(entry_obj), Value::Object(details)) = (entry.as_object_mut(), details) {
+            entry_obj.extend(details);
+        }
+
+        match &self.file {
+            Some(file) => {
+                let result = file.lock()
+                    .map_err(|_| anyhow::anyhow!("audit log lock poisoned"))
+                    .and_then(|mut f| Ok(writeln!(f, "{}", entry)?));
+                if let Err(e) = result {
+                    warn!("Failed to write audit event '{}': {}", event, e);
+                }
+            }
+            None => info!(target: "audit", "{}", entry),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_records_json_lines() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("audit").join("audit.jsonl");
+        let audit = AuditLog::new(&AuditConfig { path: Some(path.clone()), ..AuditConfig::default() }).unwrap();
+
+        audit.record("bypass_issued", json!({ "token_id": "abc" }));
+        audit.with_session_id("session-1").record("bypass_applied", json!({ "token_id": "abc", "direction": "response" }));
+
+        let contents = std::fs::read_to_string(path).unwrap();
+        let events: Vec<Value> = contents.lines().map(|l| serde_json::from_str(l).unwrap()).collect();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0]["event"], "bypass_issued");
+        assert_eq!(events[1]["direction"], "response");
+        assert!(events[1]["timestamp"].as_u64().is_some());
+        assert_eq!((events[0].get("session_id"), &events[1]["session_id"]), (None, &json!("session-1")));
+    }
+}
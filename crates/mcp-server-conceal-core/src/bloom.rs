@@ -0,0 +1,97 @@
+//! Bloom filter of values the mapping store has seen
+//!
+//! Most strings the proxy looks up have never been mapped before. The filter
+//! answers "definitely not stored" for them from memory, so only the (rare)
+//! possible hits go to SQLite. A false positive costs one query that finds
+//! nothing; there are no false negatives for values stored through the same
+//! store.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// Target false-positive rate at full capacity.
+const FALSE_POSITIVE_RATE: f64 = 0.01;
+
+#[derive(Debug, Clone)]
+pub struct BloomFilter {
+    bits: Vec<u64>,
+    bit_count: u64,
+    hash_count: u32,
+    capacity: usize,
+    len: usize,
+}
+
+impl BloomFilter {
+    /// An empty filter sized for `capacity` entries at a 1% false-positive rate.
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        let ln2 = std::f64::consts::LN_2;
+        let bit_count = (-(capacity as f64) * FALSE_POSITIVE_RATE.ln() / (ln2 * ln2)).ceil().max(64.0) as u64;
+        let hash_count = ((bit_count as f64 / capacity as f64) * ln2).round().clamp(1.0, 16.0) as u32;
+        Self {
+            bits: vec![0; bit_count.div_ceil(64) as usize],
+            bit_count,
+            hash_count,
+            capacity,
+            len: 0,
+        }
+    }
+
+    pub fn insert(&mut self, entity_type: &str, key: &str) {
+        for bit in self.bit_positions(entity_type, key) {
+            self.bits[(bit / 64) as usize] |= 1 << (bit % 64);
+        }
+        self.len += 1;
+    }
+
+    /// False means the value was never inserted; true means it may have been.
+    pub fn may_contain(&self, entity_type: &str, key: &str) -> bool {
+        self.bit_positions(entity_type, key)
+            .all(|bit| self.bits[(bit / 64) as usize] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Whether more entries were inserted than the filter was sized for, so
+    /// its false-positive rate is above target.
+    pub fn is_saturated(&self) -> bool {
+        self.len > self.capacity
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    // Double hashing: k positions from two independent hashes
+    fn bit_positions(&self, entity_type: &str, key: &str) -> impl Iterator<Item = u64> {
+        let hash_with = |seed: u8| {
+            let mut hasher = DefaultHasher::new();
+            seed.hash(&mut hasher);
+            entity_type.hash(&mut hasher);
+            key.hash(&mut hasher);
+            hasher.finish()
+        };
+        let (h1, h2) = (hash_with(0), hash_with(1) | 1);
+        let bit_count = self.bit_count;
+        (0..u64::from(self.hash_count)).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % bit_count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_false_negatives_and_few_false_positives() {
+        let mut filter = BloomFilter::with_capacity(1000);
+        for n in 0..1000 {
+            filter.insert("email", &format!("hash-{}", n));
+        }
+        assert!((0..1000).all(|n| filter.may_contain("email", &format!("hash-{}", n))));
+        assert!(!filter.is_saturated());
+
+        let false_positives = (0..10_000).filter(|n| filter.may_contain("email", &format!("other-{}", n))).count();
+        assert!(false_positives < 300, "{} false positives", false_positives);
+
+        filter.insert("email", "one more");
+        assert!(filter.is_saturated());
+    }
+}
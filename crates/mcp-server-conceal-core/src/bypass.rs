@@ -0,0 +1,319 @@
+//! Signed, time-boxed bypass tokens for debugging
+//!
+//! `mcp-server-conceal ctl bypass --tool <name> --ttl 10m` issues a token that
+//! lets traffic for one tool or method through unprocessed until it expires.
+//! Tokens are HMAC-signed with a key derived from the mapping passphrase and
+//! dropped into the bypass directory, where a running proxy picks them up.
+//! Issuing and applying a token is recorded in the audit trail, so a forgotten
+//! bypass expires on its own and leaves evidence behind.
+
+use crate::config::Config;
+use crate::crypto;
+use crate::protocol::RequestInfo;
+use crate::secrets::Passphrase;
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+use uuid::Uuid;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Longest bypass an operator can issue.
+pub const MAX_BYPASS_TTL: Duration = Duration::from_secs(24 * 60 * 60);
+
+const TOKEN_FILE_EXTENSION: &str = "token";
+const REFRESH_INTERVAL: Duration = Duration::from_secs(2);
+const SIGNING_KEY_CONTEXT: &[u8] = b"mcp-server-conceal bypass tokens v1";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BypassScope {
+    Tool(String),
+    Method(String),
+}
+
+impl BypassScope {
+    pub fn matches(&self, request: &RequestInfo) -> bool {
+        match self {
+            BypassScope::Tool(tool) => request.tool.as_deref() == Some(tool.as_str()),
+            BypassScope::Method(method) => request.method == *method,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BypassToken {
+    pub id: String,
+    pub scope: BypassScope,
+    pub issued_at: u64,
+    pub expires_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl BypassToken {
+    pub fn is_expired(&self) -> bool {
+        now_secs() >= self.expires_at
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SignedBypassToken {
+    pub token: BypassToken,
+    payload: String,
+    signature: String,
+}
+
+impl SignedBypassToken {
+    pub fn issue(key: &[u8], scope: BypassScope, ttl: Duration, reason: Option<String>) -> Result<Self> {
+        if ttl.is_zero() || ttl > MAX_BYPASS_TTL {
+            return Err(anyhow::anyhow!(
+                "Bypass TTL must be between 1s and {}h",
+                MAX_BYPASS_TTL.as_secs() / 3600
+            ));
+        }
+
+        let issued_at = now_secs();
+        let token = BypassToken {
+            id: Uuid::new_v4().to_string(),
+            scope,
+            issued_at,
+            expires_at: issued_at + ttl.as_secs(),
+            reason,
+        };
+        let payload = hex::encode(serde_json::to_vec(&token)?);
+        let signature = sign(key, &payload);
+        Ok(Self { token, payload, signature })
+    }
+
+    /// `<hex payload>.<hex signature>`
+    pub fn encode(&self) -> String {
+        format!("{}.{}", self.payload, self.signature)
+    }
+
+    /// Parses and verifies a token; tampered or foreign tokens are rejected.
+    pub fn decode(encoded: &str, key: &[u8]) -> Result<Self> {
+        let (payload, signature) = encoded.trim().split_once('.')
+            .ok_or_else(|| anyhow::anyhow!("Malformed bypass token"))?;
+
+        let mut mac = HmacSha256::new_from_slice(key).expect("HMAC accepts any key length");
+        mac.update(payload.as_bytes());
+        mac.verify_slice(&hex::decode(signature)?)
+            .map_err(|_| anyhow::anyhow!("Bypass token signature is invalid"))?;
+
+        let token: BypassToken = serde_json::from_slice(&hex::decode(payload)?)?;
+        Ok(Self {
+            token,
+            payload: payload.to_string(),
+            signature: signature.to_string(),
+        })
+    }
+}
+
+/// Derives the token signing key from the mapping passphrase.
+pub fn signing_key(passphrase: &Passphrase) -> Vec<u8> {
+    crypto::derive_key(passphrase, SIGNING_KEY_CONTEXT).to_vec()
+}
+
+fn sign(key: &[u8], payload: &str) -> String {
+    crypto::keyed_hash(key, payload.as_bytes())
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+/// Parses durations like `90s`, `10m`, `2h` (a bare number is seconds).
+pub fn parse_ttl(value: &str) -> Result<Duration> {
+    let value = value.trim();
+    let split = value.find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let amount: u64 = number.parse()
+        .map_err(|_| anyhow::anyhow!("Invalid duration '{}'", value))?;
+    let multiplier = match unit {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(anyhow::anyhow!("Invalid duration unit in '{}' (use s, m, h or d)", value)),
+    };
+    let seconds = amount.checked_mul(multiplier)
+        .ok_or_else(|| anyhow::anyhow!("Duration '{}' is too long", value))?;
+    Ok(Duration::from_secs(seconds))
+}
+
+/// Directory a running proxy watches for bypass tokens.
+pub fn default_bypass_dir() -> Result<PathBuf> {
+    Ok(Config::get_app_dirs()?.data_dir().join("bypass"))
+}
+
+pub fn write_token(dir: &Path, token: &SignedBypassToken) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let path = dir.join(format!("{}.{}", token.token.id, TOKEN_FILE_EXTENSION));
+    std::fs::write(&path, token.encode())?;
+    Ok(path)
+}
+
+/// Deletes the token file of `id`, which must be a token id as issued, so
+/// the path cannot leave `dir`.
+pub fn revoke_token(dir: &Path, id: &str) -> Result<bool> {
+    let id = Uuid::try_parse(id)
+        .map_err(|_| anyhow::anyhow!("'{}' is not a bypass token id", id))?;
+    let path = dir.join(format!("{}.{}", id.hyphenated(), TOKEN_FILE_EXTENSION));
+    match std::fs::remove_file(path) {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Reads every valid token in `dir`, deleting expired ones.
+pub fn load_tokens(dir: &Path, key: &[u8]) -> Vec<BypassToken> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut tokens = Vec::new();
+    for path in entries.flatten().map(|e| e.path()) {
+        if path.extension().and_then(|e| e.to_str()) != Some(TOKEN_FILE_EXTENSION) {
+            continue;
+        }
+        let decoded = std::fs::read_to_string(&path)
+            .map_err(anyhow::Error::from)
+            .and_then(|contents| SignedBypassToken::decode(&contents, key));
+        match decoded {
+            Ok(signed) if signed.token.is_expired() => {
+                debug!("Removing expired bypass token {}", signed.token.id);
+                std::fs::remove_file(&path).ok();
+            }
+            Ok(signed) => tokens.push(signed.token),
+            Err(e) => warn!("Ignoring bypass token {}: {}", path.display(), e),
+        }
+    }
+    tokens
+}
+
+/// Cached view of the bypass directory used on the hot path.
+#[derive(Clone)]
+pub struct BypassRegistry {
+    dir: PathBuf,
+    key: Vec<u8>,
+    tokens: Vec<BypassToken>,
+    last_refresh: Option<Instant>,
+}
+
+impl BypassRegistry {
+    pub fn new(dir: PathBuf, key: Vec<u8>) -> Self {
+        Self { dir, key, tokens: Vec::new(), last_refresh: None }
+    }
+
+    /// Returns the active token covering `request`, if any.
+    pub fn find(&mut self, request: &RequestInfo) -> Option<BypassToken> {
+        if self.last_refresh.is_none_or(|t| t.elapsed() >= REFRESH_INTERVAL) {
+            self.tokens = load_tokens(&self.dir, &self.key);
+            self.last_refresh = Some(Instant::now());
+        }
+        self.tokens.iter()
+            .find(|t| !t.is_expired() && t.scope.matches(request))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const KEY: &[u8] = b"test-signing-key";
+
+    fn tool_request(tool: &str) -> RequestInfo {
+        RequestInfo { method: "tools/call".to_string(), tool: Some(tool.to_string()) }
+    }
+
+    #[test]
+    fn test_parse_ttl() {
+        assert_eq!(parse_ttl("10m").unwrap(), Duration::from_secs(600));
+        assert_eq!(parse_ttl("45").unwrap(), Duration::from_secs(45));
+        assert_eq!(parse_ttl("2h").unwrap(), Duration::from_secs(7200));
+        assert!(parse_ttl("10 minutes").is_err());
+        assert!(parse_ttl("m").is_err());
+        assert!(parse_ttl("18446744073709551615d").is_err());
+    }
+
+    #[test]
+    fn test_token_roundtrip_and_tamper_detection() {
+        let signed = SignedBypassToken::issue(
+            KEY,
+            BypassScope::Tool("query_db".to_string()),
+            Duration::from_secs(600),
+            Some("debugging #42".to_string()),
+        ).unwrap();
+
+        let decoded = SignedBypassToken::decode(&signed.encode(), KEY).unwrap();
+        assert_eq!(decoded.token, signed.token);
+
+        assert!(SignedBypassToken::decode(&signed.encode(), b"other-key").is_err());
+
+        let forged_payload = hex::encode(
+            serde_json::to_vec(&BypassToken { expires_at: u64::MAX, ..signed.token.clone() }).unwrap(),
+        );
+        let forged = format!("{}.{}", forged_payload, signed.encode().split_once('.').unwrap().1);
+        assert!(SignedBypassToken::decode(&forged, KEY).is_err());
+    }
+
+    #[test]
+    fn test_ttl_is_bounded() {
+        let scope = BypassScope::Method("tools/call".to_string());
+        assert!(SignedBypassToken::issue(KEY, scope.clone(), Duration::ZERO, None).is_err());
+        assert!(SignedBypassToken::issue(KEY, scope, MAX_BYPASS_TTL * 2, None).is_err());
+    }
+
+    #[test]
+    fn test_registry_matches_scope_and_revocation() {
+        let temp_dir = TempDir::new().unwrap();
+        let signed = SignedBypassToken::issue(
+            KEY,
+            BypassScope::Tool("query_db".to_string()),
+            Duration::from_secs(600),
+            None,
+        ).unwrap();
+        write_token(temp_dir.path(), &signed).unwrap();
+
+        let mut registry = BypassRegistry::new(temp_dir.path().to_path_buf(), KEY.to_vec());
+        assert_eq!(registry.find(&tool_request("query_db")).unwrap().id, signed.token.id);
+        assert!(registry.find(&tool_request("send_email")).is_none());
+
+        assert!(revoke_token(temp_dir.path(), &signed.token.id).unwrap());
+        assert!(load_tokens(temp_dir.path(), KEY).is_empty());
+
+        // Only token ids are accepted, so nothing outside the directory is removed
+        let outside = temp_dir.path().join("config.token");
+        std::fs::write(&outside, "").unwrap();
+        let nested = temp_dir.path().join("bypass");
+        assert!(revoke_token(&nested, "../config").is_err());
+        assert!(outside.exists());
+    }
+
+    #[test]
+    fn test_expired_tokens_are_removed() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut signed = SignedBypassToken::issue(
+            KEY,
+            BypassScope::Method("tools/call".to_string()),
+            Duration::from_secs(1),
+            None,
+        ).unwrap();
+        signed.token.expires_at = signed.token.issued_at;
+        signed.payload = hex::encode(serde_json::to_vec(&signed.token).unwrap());
+        signed.signature = sign(KEY, &signed.payload);
+        let path = write_token(temp_dir.path(), &signed).unwrap();
+
+        assert!(load_tokens(temp_dir.path(), KEY).is_empty());
+        assert!(!path.exists());
+    }
+}
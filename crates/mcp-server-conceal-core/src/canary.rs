@@ -0,0 +1,244 @@
+//! Canary tokens in anonymized responses
+//!
+//! Anonymized data can turn up again somewhere else: pasted into a ticket,
+//! kept in a model provider's logs, or in a training set. With `[canary]
+//! enabled = true`, every response the proxy changed carries a canary of the
+//! session, a string like `ref-3fa2c1d97b0e44aa`, so a copy of the data shows
+//! it went through this proxy. The session is the proxy's run, or with
+//! `mapping.reset_on_initialize` each client session from its `initialize`:
+//!
+//! - `placement = "field"` sets `field` (a dotted path, `_meta.trace_id` by
+//!   default) in the response's `result`; MCP clients ignore unknown `_meta`
+//!   keys. A value the server already put there is left alone.
+//! - `placement = "text"` appends the canary to the last text block of the
+//!   result's content, where it survives copy and paste. Blocks holding JSON
+//!   are skipped, since the canary would break it; results without another
+//!   text block get the field instead.
+//!
+//! The canary is `prefix`, eight random hex digits, and eight more that sign
+//! them with a key derived from the mapping passphrase. `canary-check` finds
+//! the canaries of the same passphrase in any text without storing anything;
+//! each session's canary is also recorded as a `canary_issued` audit event,
+//! which ties it to the session manifest.
+
+use crate::crypto;
+use crate::secrets::Passphrase;
+use anyhow::Result;
+use rand::Rng;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+const KEY_CONTEXT: &[u8] = b"mcp-server-conceal canaries v1";
+/// Hex digits of the random part, and of the signature
+const PART_DIGITS: usize = 8;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CanaryPlacement {
+    /// A metadata field of the result
+    #[default]
+    Field,
+    /// The end of the last text block
+    Text,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CanaryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub placement: CanaryPlacement,
+    /// Dotted path below `result` set with `placement = "field"`
+    #[serde(default = "default_field")]
+    pub field: String,
+    /// Start of every canary; `canary-check` needs the same one
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+}
+
+impl Default for CanaryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            placement: CanaryPlacement::default(),
+            field: default_field(),
+            prefix: default_prefix(),
+        }
+    }
+}
+
+fn default_field() -> String {
+    "_meta.trace_id".to_string()
+}
+
+fn default_prefix() -> String {
+    "ref-".to_string()
+}
+
+impl CanaryConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.field.split('.').any(str::is_empty) {
+            return Err(anyhow::anyhow!("canary.field must be a dotted path of keys, e.g. \"_meta.trace_id\""));
+        }
+        if self.prefix.chars().any(|c| c.is_whitespace()) {
+            return Err(anyhow::anyhow!("canary.prefix cannot contain whitespace"));
+        }
+        Ok(())
+    }
+}
+
+/// Derives the canary signing key from the mapping passphrase.
+pub fn signing_key(passphrase: &Passphrase) -> Vec<u8> {
+    crypto::derive_key(passphrase, KEY_CONTEXT).to_vec()
+}
+
+fn signature(key: &[u8], prefix: &str, random: &str) -> String {
+    crypto::keyed_hash(key, format!("{}{}", prefix, random).as_bytes())[..PART_DIGITS].to_string()
+}
+
+/// The canary of one session and where it goes.
+#[derive(Debug, Clone)]
+pub struct Canary {
+    token: String,
+    placement: CanaryPlacement,
+    field: Vec<String>,
+    prefix: String,
+    key: Vec<u8>,
+}
+
+impl Canary {
+    pub fn issue(config: &CanaryConfig, key: &[u8]) -> Self {
+        let random = hex::encode(rand::thread_rng().gen::<[u8; PART_DIGITS / 2]>());
+        Self {
+            token: format!("{}{}{}", config.prefix, random, signature(key, &config.prefix, &random)),
+            placement: config.placement,
+            field: config.field.split('.').map(str::to_string).collect(),
+            prefix: config.prefix.clone(),
+            key: key.to_vec(),
+        }
+    }
+
+    /// The canary of the client session kept in `namespace`. Both pipelines
+    /// of a proxy derive the same one without sharing it.
+    pub fn for_session(&self, namespace: &str) -> Self {
+        let random = crypto::keyed_hash(&self.key, format!("session {}", namespace).as_bytes())[..PART_DIGITS].to_string();
+        Self {
+            token: format!("{}{}{}", self.prefix, random, signature(&self.key, &self.prefix, &random)),
+            ..self.clone()
+        }
+    }
+
+    pub fn token(&self) -> &str {
+        &self.token
+    }
+
+    /// Adds the canary to a response; other messages are left alone.
+    pub fn inject(&self, message: &mut Value) {
+        let Some(result) = message.get_mut("result").filter(|result| result.is_object()) else {
+            return;
+        };
+        if self.placement == CanaryPlacement::Text {
+            let last_text = result.get_mut("content")
+                .and_then(Value::as_array_mut)
+                .and_then(|blocks| blocks.iter_mut().rev().find(|block| block["type"] == "text" && !holds_json(&block["text"])))
+                .and_then(|block| block.get_mut("text"));
+            if let Some(Value::String(text)) = last_text {
+                text.push(' ');
+                text.push_str(&self.token);
+                return;
+            }
+        }
+        let (last, parents) = self.field.split_last().expect("canary.field is validated");
+        let mut target = result;
+        for key in parents {
+            let Some(object) = target.as_object_mut() else {
+                return;
+            };
+            target = object.entry(key.clone()).or_insert_with(|| Value::Object(Default::default()));
+        }
+        if let Some(object) = target.as_object_mut() {
+            object.entry(last.clone()).or_insert_with(|| Value::String(self.token.clone()));
+        }
+    }
+}
+
+/// Whether a text block carries a JSON document, which a client may parse.
+fn holds_json(text: &Value) -> bool {
+    text.as_str().is_some_and(|text| serde_json::from_str::<Value>(text).is_ok())
+}
+
+/// Canaries of `key` in `text`, in order of appearance.
+pub fn find(text: &str, prefix: &str, key: &[u8]) -> Vec<String> {
+    let pattern = format!(r"{}([0-9a-f]{{{digits}}})([0-9a-f]{{{digits}}})\b", regex::escape(prefix), digits = PART_DIGITS);
+    let canaries = Regex::new(&pattern).expect("canary pattern is valid");
+    canaries.captures_iter(text)
+        .filter(|captures| signature(key, prefix, &captures[1]) == captures[2])
+        .map(|captures| captures[0].to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn key(passphrase: &str) -> Vec<u8> {
+        signing_key(&Passphrase::new(passphrase))
+    }
+
+    #[test]
+    fn test_canaries_are_found_with_their_key_only() {
+        let canary = Canary::issue(&CanaryConfig::default(), &key("correct horse"));
+        assert!(canary.token().starts_with("ref-") && canary.token().len() == 20);
+
+        let text = format!("copied from a chat: see {}, or ref-0123456789abcdef", canary.token());
+        assert_eq!(find(&text, "ref-", &key("correct horse")), [canary.token()]);
+        assert!(find(&text, "ref-", &key("battery staple")).is_empty());
+        assert!(find(&text, "id-", &key("correct horse")).is_empty());
+    }
+
+    #[test]
+    fn test_canaries_go_in_a_field_or_the_last_text_block() {
+        let canary = Canary::issue(&CanaryConfig::default(), &key("correct horse"));
+        let mut response = json!({"jsonrpc": "2.0", "id": 1, "result": {"_meta": {"x": 1}, "content": []}});
+        canary.inject(&mut response);
+        assert_eq!(response["result"]["_meta"], json!({"x": 1, "trace_id": canary.token()}));
+
+        let config = CanaryConfig { placement: CanaryPlacement::Text, ..CanaryConfig::default() };
+        let canary = Canary::issue(&config, &key("correct horse"));
+        let mut response = json!({"id": 2, "result": {"content": [{"type": "text", "text": "a"}, {"type": "text", "text": "b"}]}});
+        canary.inject(&mut response);
+        assert_eq!(response["result"]["content"][1]["text"], format!("b {}", canary.token()));
+        assert_eq!(response["result"]["content"][0]["text"], "a");
+
+        let mut request = json!({"id": 3, "method": "tools/call", "params": {}});
+        canary.inject(&mut request);
+        assert_eq!(request, json!({"id": 3, "method": "tools/call", "params": {}}));
+    }
+
+    #[test]
+    fn test_canaries_leave_json_and_existing_fields_alone() {
+        let config = CanaryConfig { placement: CanaryPlacement::Text, ..CanaryConfig::default() };
+        let canary = Canary::issue(&config, &key("correct horse"));
+        let mut response = json!({"id": 1, "result": {"content": [{"type": "text", "text": "a"}, {"type": "text", "text": "{\"rows\": []}"}]}});
+        canary.inject(&mut response);
+        assert_eq!(response["result"]["content"][1]["text"], "{\"rows\": []}");
+        assert_eq!(response["result"]["content"][0]["text"], format!("a {}", canary.token()));
+
+        let mut response = json!({"id": 2, "result": {"content": [{"type": "text", "text": "[1, 2]"}], "_meta": {"trace_id": "abc"}}});
+        canary.inject(&mut response);
+        assert_eq!(response["result"]["content"][0]["text"], "[1, 2]");
+        assert_eq!(response["result"]["_meta"]["trace_id"], "abc");
+    }
+
+    #[test]
+    fn test_sessions_get_canaries_of_their_own() {
+        let canary = Canary::issue(&CanaryConfig::default(), &key("correct horse"));
+        let first = canary.for_session("session-1");
+        assert_eq!(first.token(), canary.for_session("session-1").token());
+        assert_ne!(first.token(), canary.for_session("session-2").token());
+        assert_ne!(first.token(), canary.token());
+        assert_eq!(find(first.token(), "ref-", &key("correct horse")), [first.token()]);
+    }
+}
@@ -0,0 +1,198 @@
+//! Fault injection for exercising degradation policies
+//!
+//! Builds with the `chaos` cargo feature read a handful of environment
+//! variables at startup and inject failures into a running proxy: lines from
+//! the server are dropped or corrupted, and LLM calls are delayed. This lets
+//! users and CI check that fail-open forwarding, message timeouts and the
+//! regex fallback behave as configured, without a broken server or model.
+//!
+//! Without the feature no variable is read and every hook is a no-op.
+
+use anyhow::Result;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Probability of dropping a line the server writes to stdout
+pub const DROP_STDOUT_ENV_VAR: &str = "MCP_CONCEAL_CHAOS_DROP_STDOUT";
+/// Probability of truncating a message before it is processed, in either direction
+pub const CORRUPT_JSON_ENV_VAR: &str = "MCP_CONCEAL_CHAOS_CORRUPT_JSON";
+/// Added to LLM calls, in milliseconds
+pub const LLM_DELAY_MS_ENV_VAR: &str = "MCP_CONCEAL_CHAOS_LLM_DELAY_MS";
+/// Probability of delaying an LLM call; 1 when unset
+pub const LLM_DELAY_RATE_ENV_VAR: &str = "MCP_CONCEAL_CHAOS_LLM_DELAY_RATE";
+/// Seed for reproducible runs
+pub const SEED_ENV_VAR: &str = "MCP_CONCEAL_CHAOS_SEED";
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ChaosConfig {
+    pub drop_stdout_rate: f64,
+    pub corrupt_json_rate: f64,
+    pub llm_delay: Duration,
+    pub llm_delay_rate: f64,
+    pub seed: Option<u64>,
+}
+
+impl ChaosConfig {
+    pub fn from_env() -> Result<Self> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self> {
+        let rate = |name: &str, default: f64| -> Result<f64> {
+            let Some(value) = var(name) else {
+                return Ok(default);
+            };
+            value.trim().parse::<f64>().ok()
+                .filter(|rate| (0.0..=1.0).contains(rate))
+                .ok_or_else(|| anyhow::anyhow!("{} must be a probability between 0 and 1, got '{}'", name, value))
+        };
+        let number = |name: &str| -> Result<Option<u64>> {
+            var(name)
+                .map(|value| value.trim().parse::<u64>()
+                    .map_err(|_| anyhow::anyhow!("{} must be a non-negative integer, got '{}'", name, value)))
+                .transpose()
+        };
+
+        Ok(Self {
+            drop_stdout_rate: rate(DROP_STDOUT_ENV_VAR, 0.0)?,
+            corrupt_json_rate: rate(CORRUPT_JSON_ENV_VAR, 0.0)?,
+            llm_delay: Duration::from_millis(number(LLM_DELAY_MS_ENV_VAR)?.unwrap_or(0)),
+            llm_delay_rate: rate(LLM_DELAY_RATE_ENV_VAR, 1.0)?,
+            seed: number(SEED_ENV_VAR)?,
+        })
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.drop_stdout_rate > 0.0
+            || self.corrupt_json_rate > 0.0
+            || (!self.llm_delay.is_zero() && self.llm_delay_rate > 0.0)
+    }
+}
+
+pub struct ChaosInjector {
+    config: ChaosConfig,
+    rng: Mutex<StdRng>,
+}
+
+impl ChaosInjector {
+    pub fn new(config: ChaosConfig) -> Self {
+        let rng = match config.seed {
+            Some(seed) => StdRng::seed_from_u64(seed),
+            None => StdRng::from_entropy(),
+        };
+        Self { config, rng: Mutex::new(rng) }
+    }
+
+    pub fn config(&self) -> &ChaosConfig {
+        &self.config
+    }
+
+    fn roll(&self, rate: f64) -> bool {
+        rate > 0.0 && self.rng.lock().map(|mut rng| rng.gen_bool(rate)).unwrap_or(false)
+    }
+
+    /// Whether to drop a line the server wrote to stdout.
+    pub fn drop_stdout_line(&self) -> bool {
+        self.roll(self.config.drop_stdout_rate)
+    }
+
+    /// A truncated copy of `line`, which no longer parses as JSON, or None
+    /// to leave it intact.
+    pub fn corrupt(&self, line: &str) -> Option<String> {
+        let trimmed = line.trim_end();
+        if trimmed.is_empty() || !self.roll(self.config.corrupt_json_rate) {
+            return None;
+        }
+        let mut cut = trimmed.len() / 2;
+        while !trimmed.is_char_boundary(cut) {
+            cut -= 1;
+        }
+        Some(format!("{}\n", &trimmed[..cut]))
+    }
+
+    /// Stalls an LLM call as a slow backend would.
+    pub async fn delay_llm_call(&self) {
+        if !self.config.llm_delay.is_zero() && self.roll(self.config.llm_delay_rate) {
+            tokio::time::sleep(self.config.llm_delay).await;
+        }
+    }
+}
+
+/// The process-wide injector, configured from the environment on first use.
+/// None unless built with the `chaos` feature and a fault is configured.
+#[cfg(feature = "chaos")]
+pub fn injector() -> Option<&'static ChaosInjector> {
+    static INJECTOR: std::sync::OnceLock<Option<ChaosInjector>> = std::sync::OnceLock::new();
+    INJECTOR
+        .get_or_init(|| match ChaosConfig::from_env() {
+            Ok(config) if config.is_active() => {
+                tracing::warn!("Fault injection active: {:?}", config);
+                Some(ChaosInjector::new(config))
+            }
+            Ok(_) => None,
+            Err(e) => {
+                tracing::error!("Ignoring fault injection settings: {}", e);
+                None
+            }
+        })
+        .as_ref()
+}
+
+#[cfg(not(feature = "chaos"))]
+pub fn injector() -> Option<&'static ChaosInjector> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn config(vars: &[(&str, &str)]) -> Result<ChaosConfig> {
+        let vars: HashMap<String, String> = vars.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        ChaosConfig::from_vars(|name| vars.get(name).cloned())
+    }
+
+    #[test]
+    fn test_settings_come_from_env_vars() {
+        assert!(!config(&[]).unwrap().is_active());
+        assert!(!config(&[(LLM_DELAY_RATE_ENV_VAR, "0.5")]).unwrap().is_active());
+
+        let parsed = config(&[(DROP_STDOUT_ENV_VAR, "0.25"), (LLM_DELAY_MS_ENV_VAR, "1500"), (SEED_ENV_VAR, "7")]).unwrap();
+        assert_eq!(parsed, ChaosConfig {
+            drop_stdout_rate: 0.25,
+            corrupt_json_rate: 0.0,
+            llm_delay: Duration::from_millis(1500),
+            llm_delay_rate: 1.0,
+            seed: Some(7),
+        });
+        assert!(parsed.is_active());
+
+        assert!(config(&[(CORRUPT_JSON_ENV_VAR, "1.5")]).is_err());
+        assert!(config(&[(LLM_DELAY_MS_ENV_VAR, "soon")]).is_err());
+    }
+
+    #[test]
+    fn test_faults_follow_their_rates() {
+        let always = ChaosInjector::new(ChaosConfig {
+            drop_stdout_rate: 1.0,
+            corrupt_json_rate: 1.0,
+            seed: Some(1),
+            ..ChaosConfig::default()
+        });
+        assert!(always.drop_stdout_line());
+        let corrupted = always.corrupt("{\"jsonrpc\":\"2.0\",\"result\":\"é\"}\n").unwrap();
+        assert!(corrupted.ends_with('\n'));
+        assert!(serde_json::from_str::<serde_json::Value>(&corrupted).is_err());
+
+        let never = ChaosInjector::new(ChaosConfig { seed: Some(1), ..ChaosConfig::default() });
+        assert!(!never.drop_stdout_line());
+        assert_eq!(never.corrupt("{}"), None);
+
+        let half = ChaosInjector::new(ChaosConfig { drop_stdout_rate: 0.5, seed: Some(42), ..ChaosConfig::default() });
+        let dropped = (0..1000).filter(|_| half.drop_stdout_line()).count();
+        assert!((400..600).contains(&dropped), "{} dropped", dropped);
+    }
+}
@@ -0,0 +1,182 @@
+//! Splitting long texts for LLM detection
+//!
+//! Text beyond the model's context window is silently dropped by Ollama, so
+//! PII near the end of a long tool result would never be seen. Long texts are
+//! split into overlapping chunks, preferably at whitespace, each chunk is
+//! detected on its own, and entity offsets are mapped back onto the original
+//! string. The overlap lets a value cut by one chunk boundary appear whole in
+//! the neighbouring chunk; duplicates from the overlap are merged.
+
+use crate::config::DetectedEntity;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmChunkingConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// Longest text sent in one request, in bytes
+    #[serde(default = "default_max_chars")]
+    pub max_chars: usize,
+    /// Bytes shared by consecutive chunks; capped at half of `max_chars`
+    #[serde(default = "default_overlap_chars")]
+    pub overlap_chars: usize,
+}
+
+impl Default for LlmChunkingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_enabled(),
+            max_chars: default_max_chars(),
+            overlap_chars: default_overlap_chars(),
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_max_chars() -> usize {
+    4000
+}
+
+fn default_overlap_chars() -> usize {
+    200
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk<'a> {
+    /// Byte offset of the chunk in the original text
+    pub offset: usize,
+    pub text: &'a str,
+}
+
+/// Splits `text` into chunks of at most `max_chars` bytes (never inside a
+/// character) that overlap by about `overlap_chars`. Short texts come back as
+/// a single chunk.
+pub fn split(text: &str, max_chars: usize, overlap_chars: usize) -> Vec<Chunk<'_>> {
+    let max_chars = max_chars.max(16);
+    let overlap_chars = overlap_chars.min(max_chars / 2);
+    let mut chunks = Vec::new();
+    let mut start = 0;
+
+    loop {
+        if text.len() - start <= max_chars {
+            chunks.push(Chunk { offset: start, text: &text[start..] });
+            return chunks;
+        }
+
+        let hard_end = floor_char_boundary(text, start + max_chars);
+        // Break after the last whitespace in the second half of the window, if any
+        let end = text[start..hard_end].char_indices()
+            .rev()
+            .find(|&(i, c)| c.is_whitespace() && i >= max_chars / 2)
+            .map(|(i, c)| start + i + c.len_utf8())
+            .unwrap_or(hard_end);
+        chunks.push(Chunk { offset: start, text: &text[start..end] });
+
+        let next = ceil_char_boundary(text, end.saturating_sub(overlap_chars));
+        start = if next > start { next } else { end };
+    }
+}
+
+/// Maps per-chunk entities onto the original text and merges the duplicates
+/// produced by overlapping chunks. A value cut by a chunk boundary is replaced
+/// by the complete value found in the neighbouring chunk.
+pub fn merge(per_chunk: Vec<(Chunk<'_>, Vec<DetectedEntity>)>) -> Vec<DetectedEntity> {
+    let mut entities: Vec<DetectedEntity> = per_chunk.into_iter()
+        .flat_map(|(chunk, entities)| {
+            entities.into_iter().map(move |mut entity| {
+                entity.start += chunk.offset;
+                entity.end += chunk.offset;
+                entity
+            })
+        })
+        .collect();
+    // Longest first at each start, so a covering entity precedes what it covers
+    entities.sort_by(|a, b| a.start.cmp(&b.start).then(b.end.cmp(&a.end)));
+
+    let mut merged: Vec<DetectedEntity> = Vec::new();
+    for entity in entities {
+        let covering = merged.iter_mut().find(|kept| {
+            kept.entity_type == entity.entity_type && kept.start <= entity.start && entity.end <= kept.end
+        });
+        match covering {
+            Some(kept) => kept.confidence = kept.confidence.max(entity.confidence),
+            None => merged.push(entity),
+        }
+    }
+    merged
+}
+
+fn floor_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_char_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(entity_type: &str, value: &str, start: usize) -> DetectedEntity {
+        DetectedEntity {
+            entity_type: entity_type.to_string(),
+            original_value: value.to_string(),
+            start,
+            end: start + value.len(),
+            confidence: 0.9,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_split_covers_text_with_overlap() {
+        let text = "word ".repeat(100);
+        let chunks = split(&text, 64, 16);
+
+        assert!(chunks.len() > 1);
+        assert_eq!(chunks[0].offset, 0);
+        for pair in chunks.windows(2) {
+            let first_end = pair[0].offset + pair[0].text.len();
+            assert!(pair[1].offset < first_end, "chunks must overlap");
+            assert!(pair[0].text.len() <= 64);
+            assert!(pair[0].text.ends_with(' '), "chunks break at whitespace");
+        }
+        let last = chunks.last().unwrap();
+        assert_eq!(last.offset + last.text.len(), text.len());
+        assert_eq!(split("short", 64, 16), vec![Chunk { offset: 0, text: "short" }]);
+    }
+
+    #[test]
+    fn test_split_never_cuts_characters() {
+        let text = "é".repeat(100);
+        for chunk in split(&text, 33, 7) {
+            assert!(chunk.text.chars().all(|c| c == 'é'));
+            assert_eq!(&text[chunk.offset..chunk.offset + chunk.text.len()], chunk.text);
+        }
+    }
+
+    #[test]
+    fn test_merge_remaps_offsets_and_prefers_whole_values() {
+        let text = "contact jane.roe@acme.com today";
+        let first = Chunk { offset: 0, text: &text[..16] };
+        let second = Chunk { offset: 8, text: &text[8..] };
+
+        let merged = merge(vec![
+            (first, vec![entity("email", "jane.ro", 8)]),
+            (second, vec![entity("email", "jane.roe@acme.com", 0)]),
+        ]);
+
+        assert_eq!(merged.len(), 1);
+        assert_eq!(&text[merged[0].start..merged[0].end], "jane.roe@acme.com");
+    }
+}
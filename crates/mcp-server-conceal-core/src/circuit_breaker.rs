@@ -0,0 +1,170 @@
+//! Circuit breaker for the LLM backend
+//!
+//! When Ollama is down or overloaded every string would otherwise wait for a
+//! health check or request timeout. After `failure_threshold` consecutive
+//! failures the breaker opens and LLM calls are skipped (regex-only detection)
+//! for `cooldown_seconds`. It then half-opens: a single trial request decides
+//! whether to close again or start another cooldown.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CircuitBreakerConfig {
+    #[serde(default = "default_failure_threshold")]
+    pub failure_threshold: u32,
+    #[serde(default = "default_cooldown_seconds")]
+    pub cooldown_seconds: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: default_failure_threshold(),
+            cooldown_seconds: default_cooldown_seconds(),
+        }
+    }
+}
+
+fn default_failure_threshold() -> u32 {
+    3
+}
+
+fn default_cooldown_seconds() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    Closed,
+    Open,
+    HalfOpen,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    config: CircuitBreakerConfig,
+    state: CircuitState,
+    consecutive_failures: u32,
+    opened_at: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: CircuitBreakerConfig) -> Self {
+        Self {
+            config,
+            state: CircuitState::Closed,
+            consecutive_failures: 0,
+            opened_at: None,
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        self.state
+    }
+
+    /// Whether a call may go out now. Moves an expired open breaker to half-open
+    /// and grants that single trial call; further calls wait for its outcome.
+    pub fn allow_request(&mut self) -> bool {
+        self.allow_request_at(Instant::now())
+    }
+
+    fn allow_request_at(&mut self, now: Instant) -> bool {
+        match self.state {
+            CircuitState::Closed => true,
+            CircuitState::HalfOpen => false,
+            CircuitState::Open => {
+                let cooldown = Duration::from_secs(self.config.cooldown_seconds);
+                if self.opened_at.is_some_and(|opened| now.duration_since(opened) >= cooldown) {
+                    info!("LLM circuit half-open, sending trial request");
+                    self.state = CircuitState::HalfOpen;
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+
+    pub fn record_success(&mut self) {
+        if self.state != CircuitState::Closed {
+            info!("LLM circuit closed, resuming LLM detection");
+        }
+        self.state = CircuitState::Closed;
+        self.consecutive_failures = 0;
+        self.opened_at = None;
+    }
+
+    pub fn record_failure(&mut self) {
+        self.record_failure_at(Instant::now());
+    }
+
+    fn record_failure_at(&mut self, now: Instant) {
+        self.consecutive_failures += 1;
+        let should_open = match self.state {
+            CircuitState::HalfOpen => true,
+            CircuitState::Closed => self.consecutive_failures >= self.config.failure_threshold.max(1),
+            CircuitState::Open => false,
+        };
+        if should_open {
+            warn!(
+                "LLM circuit open after {} consecutive failures, using regex-only detection for {}s",
+                self.consecutive_failures, self.config.cooldown_seconds
+            );
+            self.state = CircuitState::Open;
+            self.opened_at = Some(now);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker() -> CircuitBreaker {
+        CircuitBreaker::new(CircuitBreakerConfig {
+            failure_threshold: 2,
+            cooldown_seconds: 10,
+        })
+    }
+
+    #[test]
+    fn test_opens_after_consecutive_failures() {
+        let mut breaker = breaker();
+        let now = Instant::now();
+
+        breaker.record_failure_at(now);
+        assert!(breaker.allow_request_at(now));
+        breaker.record_success();
+        breaker.record_failure_at(now);
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        breaker.record_failure_at(now);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.allow_request_at(now + Duration::from_secs(5)));
+    }
+
+    #[test]
+    fn test_half_open_trial_decides_next_state() {
+        let mut breaker = breaker();
+        let now = Instant::now();
+        breaker.record_failure_at(now);
+        breaker.record_failure_at(now);
+
+        let later = now + Duration::from_secs(10);
+        assert!(breaker.allow_request_at(later));
+        assert_eq!(breaker.state(), CircuitState::HalfOpen);
+        // Only one trial at a time
+        assert!(!breaker.allow_request_at(later));
+
+        breaker.record_failure_at(later);
+        assert_eq!(breaker.state(), CircuitState::Open);
+        assert!(!breaker.allow_request_at(later + Duration::from_secs(9)));
+
+        assert!(breaker.allow_request_at(later + Duration::from_secs(10)));
+        breaker.record_success();
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.allow_request_at(later + Duration::from_secs(10)));
+    }
+}
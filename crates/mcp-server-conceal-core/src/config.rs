@@ -1,25 +1,479 @@
 //! Configuration management for mcp-server-conceal
 
+use crate::admin::{AdminConfig, ReloadConfig};
+use crate::approval::ApprovalConfig;
+use crate::i18n::I18nConfig;
+use crate::canary::CanaryConfig;
+use crate::chunking::LlmChunkingConfig;
+use crate::circuit_breaker::CircuitBreakerConfig;
+use crate::config_format::ConfigFormat;
+use crate::context::ContextConfig;
+use crate::config_migration::{self, CURRENT_CONFIG_VERSION};
+use crate::dates::DateShiftConfig;
+use crate::discovery::DiscoveryConfig;
+use crate::fingerprint::TargetTrustConfig;
+use crate::gateway::{self, TargetConfig};
+use crate::geo::GeoFakerConfig;
+use crate::hashing::HashAlgorithm;
+use crate::ip::IpFakerConfig;
+use crate::json_limits::JsonLimits;
+use crate::json_path::ScalarRules;
+use crate::outage_queue::OutageQueueConfig;
+use crate::pattern_packs;
+use crate::phone::{PhoneConfig, PhoneDetector};
+use crate::presets::{self, Preset};
+use crate::provider::LlmProvider;
+use crate::quarantine::QuarantineConfig;
+use crate::redis_cache::RedisCacheConfig;
+use crate::schema_hints::SchemaHintsConfig;
+use crate::scheduler::LlmConcurrencyConfig;
+use crate::identifiers::IdentifierConfig;
+use crate::identity::IdentityConfig;
+use crate::language::LanguageConfig;
+use crate::leak_check::LeakCheckConfig;
+use crate::log_file::LoggingConfig;
+use crate::telemetry::TelemetryConfig;
+use crate::urls::UrlConfig;
+use crate::validators::{self, Validator};
+use crate::verification::LlmVerificationConfig;
 use anyhow::Result;
 use directories::ProjectDirs;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
+    #[serde(default)]
+    pub config_version: u32,
+    /// Settings the file leaves out are taken from this preset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub preset: Option<Preset>,
     pub detection: DetectionConfig,
     pub faker: FakerConfig,
     pub mapping: MappingConfig,
     pub llm: Option<LlmConfig>,
+    #[serde(default)]
+    pub audit: AuditConfig,
+    #[serde(default)]
+    pub discovery: DiscoveryConfig,
+    #[serde(default)]
+    pub target_trust: TargetTrustConfig,
+    #[serde(default)]
+    pub processing: ProcessingConfig,
+    #[serde(default)]
+    pub quarantine: QuarantineConfig,
+    #[serde(default)]
+    pub approval: ApprovalConfig,
+    #[serde(default)]
+    pub leak_check: LeakCheckConfig,
+    #[serde(default)]
+    pub canary: CanaryConfig,
+    #[serde(default)]
+    pub i18n: I18nConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub reload: ReloadConfig,
+    #[serde(default)]
+    pub telemetry: TelemetryConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub language: LanguageConfig,
+    /// Target servers of gateway mode
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub targets: Vec<TargetConfig>,
+    /// Named overrides selected with `--profile`; each is laid over the
+    /// rest of the file, table by table
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profile: BTreeMap<String, toml::Table>,
+}
+
+/// Where a configuration was read from and how, so it can be read again.
+#[derive(Debug, Clone)]
+pub struct ConfigSource {
+    pub path: PathBuf,
+    pub profile: Option<String>,
+    pub preset: Option<Preset>,
+}
+
+impl ConfigSource {
+    pub fn load(&self) -> Result<Config> {
+        Config::load(&self.path, self.profile.as_deref(), self.preset)
+    }
+
+    /// Loads the file again for a running proxy, without migrating it on disk.
+    pub fn reload(&self) -> Result<Config> {
+        Config::from_value(config_migration::read_migrated(&self.path)?, self.profile.as_deref(), self.preset)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DetectionConfig {
     pub mode: DetectionMode,
     pub enabled: bool,
-    pub patterns: HashMap<String, String>,
+    /// Entity type → regex, or a table with the regex and its flags
+    pub patterns: HashMap<String, PatternConfig>,
+    /// Built-in pattern sets added to `patterns`; see `pattern_packs`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pattern_packs: Vec<String>,
     pub confidence_threshold: f64,
+    /// JSON key name (case-insensitive) → entity type assumed for its string value
+    #[serde(default = "default_key_hints")]
+    pub key_hints: HashMap<String, String>,
+    #[serde(default)]
+    pub overlap: OverlapConfig,
+    #[serde(default)]
+    pub stitching: StitchingConfig,
+    #[serde(default)]
+    pub names: NameDetectionConfig,
+    #[serde(default)]
+    pub entropy: EntropyConfig,
+    #[serde(default)]
+    pub key_material: KeyMaterialConfig,
+    /// Confidence boosts and demotions from words near a pattern match
+    #[serde(default)]
+    pub context: ContextConfig,
+    /// International phone numbers, validated per region; see `phone`
+    #[serde(default)]
+    pub phone: PhoneConfig,
+    /// Scanning of URL path segments and query values; see `urls`
+    #[serde(default)]
+    pub urls: UrlConfig,
+    /// UUIDs, git hashes and numeric ids never reported; see `identifiers`
+    #[serde(default)]
+    pub identifiers: IdentifierConfig,
+    /// Term lists always detected, in every mode; see `gazetteer`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dictionaries: Vec<DictionaryConfig>,
+    #[serde(default)]
+    pub scalars: ScalarFieldsConfig,
+    #[serde(default)]
+    pub nested_json: NestedJsonConfig,
+    #[serde(default)]
+    pub base64: Base64Config,
+    #[serde(default)]
+    pub schema_hints: SchemaHintsConfig,
+}
+
+/// Scanning of base64-encoded text in fields such as MCP resource blobs.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Base64Config {
+    #[serde(default)]
+    pub enabled: bool,
+    /// JSON keys (case-insensitive) whose string values may be base64
+    #[serde(default = "default_base64_keys")]
+    pub keys: Vec<String>,
+    /// Larger payloads are left as they are
+    #[serde(default = "default_base64_max_decoded_bytes")]
+    pub max_decoded_bytes: usize,
+}
+
+impl Default for Base64Config {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            keys: default_base64_keys(),
+            max_decoded_bytes: default_base64_max_decoded_bytes(),
+        }
+    }
+}
+
+fn default_base64_keys() -> Vec<String> {
+    vec!["blob".to_string(), "data".to_string()]
+}
+
+fn default_base64_max_decoded_bytes() -> usize {
+    1024 * 1024
+}
+
+/// Scanning of JSON documents serialized into string values.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NestedJsonConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Levels of documents-in-strings followed below the message itself
+    #[serde(default = "default_nested_json_max_depth")]
+    pub max_depth: usize,
+}
+
+impl Default for NestedJsonConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_depth: default_nested_json_max_depth(),
+        }
+    }
+}
+
+fn default_nested_json_max_depth() -> usize {
+    2
+}
+
+/// JSON path rules for non-string fields; by default only strings are scanned.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ScalarFieldsConfig {
+    /// Integer fields converted to text, scanned, and replaced with numbers
+    #[serde(default)]
+    pub numbers: Vec<String>,
+    /// Boolean fields replaced with a random boolean
+    #[serde(default)]
+    pub booleans: Vec<String>,
+}
+
+/// Priority rules used when detected spans overlap.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct OverlapConfig {
+    /// Entity types in descending priority; they win over longer or more confident spans.
+    #[serde(default)]
+    pub type_precedence: Vec<String>,
+}
+
+/// Joining of adjacent text chunks (array elements, content blocks) before detection.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StitchingConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Maximum number of adjacent chunks examined together
+    #[serde(default = "default_stitching_window")]
+    pub window: usize,
+    /// Inserted between joined chunks. The default newline keeps word-boundary
+    /// patterns from matching across fields; use "" for streams split mid-token.
+    #[serde(default = "default_stitching_separator")]
+    pub separator: String,
+}
+
+impl Default for StitchingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window: default_stitching_window(),
+            separator: default_stitching_separator(),
+        }
+    }
+}
+
+fn default_stitching_window() -> usize {
+    4
+}
+
+fn default_stitching_separator() -> String {
+    "\n".to_string()
+}
+
+/// LLM-free person-name detection based on honorifics and a first-name gazetteer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NameDetectionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_honorifics")]
+    pub honorifics: Vec<String>,
+    /// Given names added to the built-in gazetteer
+    #[serde(default)]
+    pub first_names: Vec<String>,
+}
+
+impl Default for NameDetectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            honorifics: default_honorifics(),
+            first_names: Vec::new(),
+        }
+    }
+}
+
+fn default_honorifics() -> Vec<String> {
+    ["Mr", "Mrs", "Ms", "Miss", "Mx", "Dr", "Prof", "Sir", "Dame"]
+        .iter()
+        .map(|h| h.to_string())
+        .collect()
+}
+
+/// A detection pattern: a bare regex, or a table with the regex, its flags,
+/// confidence and validator.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum PatternConfig {
+    Regex(String),
+    Structured(PatternSpec),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PatternSpec {
+    pub regex: String,
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub case_insensitive: bool,
+    /// `^` and `$` match at line boundaries
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub multi_line: bool,
+    /// Off, `\w`, `\d` and `\b` only know ASCII
+    #[serde(default = "default_unicode")]
+    pub unicode: bool,
+    /// Overrides the entity type's default confidence
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub confidence: Option<f64>,
+    /// Overrides the entity type's default validator; see `validators`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub validator: Option<Validator>,
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn default_unicode() -> bool {
+    true
+}
+
+impl PatternConfig {
+    pub fn regex(&self) -> &str {
+        match self {
+            PatternConfig::Regex(regex) => regex,
+            PatternConfig::Structured(spec) => &spec.regex,
+        }
+    }
+
+    /// The regex with its flags written inline, so it compiles the same on
+    /// its own and in a `RegexSet` with other patterns.
+    pub fn expression(&self) -> String {
+        let PatternConfig::Structured(spec) = self else {
+            return self.regex().to_string();
+        };
+        let mut flags = String::new();
+        if spec.case_insensitive {
+            flags.push('i');
+        }
+        if spec.multi_line {
+            flags.push('m');
+        }
+        if !spec.unicode {
+            flags.push_str("-u");
+        }
+        if flags.is_empty() {
+            spec.regex.clone()
+        } else {
+            format!("(?{}){}", flags, spec.regex)
+        }
+    }
+
+    /// Confidence and validator of matches reported as `entity_type`.
+    pub fn scoring(&self, entity_type: &str) -> (f64, Option<Validator>) {
+        let (confidence, validator) = validators::defaults_for(entity_type);
+        match self {
+            PatternConfig::Regex(_) => (confidence, validator),
+            PatternConfig::Structured(spec) => (spec.confidence.unwrap_or(confidence), spec.validator.or(validator)),
+        }
+    }
+}
+
+impl From<String> for PatternConfig {
+    fn from(regex: String) -> Self {
+        PatternConfig::Regex(regex)
+    }
+}
+
+impl From<&str> for PatternConfig {
+    fn from(regex: &str) -> Self {
+        PatternConfig::Regex(regex.to_string())
+    }
+}
+
+/// A file of known-sensitive terms, one per line.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DictionaryConfig {
+    /// Relative paths live in the data directory
+    pub path: PathBuf,
+    pub entity_type: String,
+    /// By default ASCII letters match regardless of case
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
+/// Detection of PEM private keys, certificates and SSH public keys, which
+/// are replaced whole; see `key_material`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyMaterialConfig {
+    #[serde(default = "default_key_material_enabled")]
+    pub enabled: bool,
+}
+
+impl Default for KeyMaterialConfig {
+    fn default() -> Self {
+        Self { enabled: default_key_material_enabled() }
+    }
+}
+
+fn default_key_material_enabled() -> bool {
+    true
+}
+
+/// Detection of random-looking tokens (API keys, passwords, session tokens)
+/// that no pattern describes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EntropyConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_entropy_min_length")]
+    pub min_length: usize,
+    /// Longer tokens are encoded data rather than credentials
+    #[serde(default = "default_entropy_max_length")]
+    pub max_length: usize,
+    /// Shannon entropy, in bits per character, a token must reach
+    #[serde(default = "default_entropy_min_bits")]
+    pub min_entropy: f64,
+    #[serde(default = "default_entropy_entity_type")]
+    pub entity_type: String,
+    /// Regexes for benign high-entropy formats; a token matching one is left alone
+    #[serde(default = "default_entropy_allowlist")]
+    pub allowlist: Vec<String>,
+}
+
+impl Default for EntropyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_length: default_entropy_min_length(),
+            max_length: default_entropy_max_length(),
+            min_entropy: default_entropy_min_bits(),
+            entity_type: default_entropy_entity_type(),
+            allowlist: default_entropy_allowlist(),
+        }
+    }
+}
+
+fn default_entropy_min_length() -> usize {
+    20
+}
+
+fn default_entropy_max_length() -> usize {
+    256
+}
+
+fn default_entropy_min_bits() -> f64 {
+    3.8
+}
+
+fn default_entropy_entity_type() -> String {
+    "secret".to_string()
+}
+
+/// UUIDs and git/SHA-256 object ids.
+fn default_entropy_allowlist() -> Vec<String> {
+    vec![
+        r"^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$".to_string(),
+        r"^(?:[0-9a-f]{40}|[0-9a-f]{64})$".to_string(),
+    ]
+}
+
+pub fn default_key_hints() -> HashMap<String, String> {
+    ["email", "phone", "ssn", "name"]
+        .iter()
+        .map(|key| (key.to_string(), key.to_string()))
+        .collect()
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -36,13 +490,282 @@ pub struct FakerConfig {
     pub locale: String,
     pub seed: Option<u64>,
     pub consistency: bool,
+    /// Replacement strategy per entity type; unlisted types get realistic fakes
+    #[serde(default)]
+    pub strategies: HashMap<String, AnonymizationStrategy>,
+    /// Subnet preservation for the `ip` strategy
+    #[serde(default)]
+    pub ip: IpFakerConfig,
+    #[serde(default)]
+    pub email: EmailFakerConfig,
+    /// Offset range for the `date_shift` strategy
+    #[serde(default)]
+    pub date_shift: DateShiftConfig,
+    /// Distance and precision for the `geo_*` strategies
+    #[serde(default)]
+    pub geo: GeoFakerConfig,
+    /// One fake person per real person; see `identity`
+    #[serde(default)]
+    pub identity: IdentityConfig,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EmailFakerConfig {
+    /// What fake email addresses do with the original domain
+    #[serde(default)]
+    pub domains: EmailDomainMode,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailDomainMode {
+    /// Random `example.*` domain per address
+    #[default]
+    Fake,
+    /// Original domain, only the user part is faked
+    Keep,
+    /// Each real domain consistently replaced by one fake domain
+    Map,
+}
+
+/// How a detected value is replaced.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AnonymizationStrategy {
+    /// Realistic fake value of the same type
+    #[default]
+    Fake,
+    /// Every letter and digit but the last four replaced with `*`
+    Mask,
+    /// Fixed `[REDACTED]` token
+    Redact,
+    /// Truncated SHA-256 of the value
+    Hash,
+    /// Numbered placeholder such as `<<EMAIL_1>>`, numbered from the mapping store
+    #[serde(alias = "placeholder")]
+    Tokenize,
+    /// Digits encrypted in place with FF3-1; reversible with the mapping passphrase, never stored
+    Fpe,
+    /// IP address in the same private/public block, with subnets mapped consistently
+    Ip,
+    /// Date moved by one random offset per mapping database, keeping intervals
+    #[serde(rename = "date_shift")]
+    DateShift,
+    /// Coordinates moved by a random distance up to `faker.geo.jitter_km`
+    #[serde(rename = "geo_jitter")]
+    GeoJitter,
+    /// Coordinates replaced with the centre of the nearest large city
+    #[serde(rename = "geo_city")]
+    GeoCity,
+    /// Coordinates cut to `faker.geo.precision` decimal places
+    #[serde(rename = "geo_truncate")]
+    GeoTruncate,
+    /// Coarser value of the same shape, e.g. a ZIP code cut to its first three digits
+    Generalize,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MappingConfig {
     pub database_path: PathBuf,
+    /// Keeps this proxy's mappings and LLM cache apart from other proxied
+    /// servers sharing the database; `--profile` sets it, and unset is the
+    /// default, empty namespace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub namespace: Option<String>,
     pub encryption: bool,
     pub retention_days: Option<u32>,
+    #[serde(default)]
+    pub passphrase_source: PassphraseSource,
+    #[serde(default = "default_keyring_account")]
+    pub keyring_account: String,
+    /// Honour `ctl bypass` tokens, which are signed with the mapping passphrase
+    #[serde(default)]
+    pub bypass_tokens: bool,
+    /// Warn when retention cleanup removes mappings used within this many days (0 disables)
+    #[serde(default = "default_expiry_warning_days")]
+    pub expiry_warning_days: u32,
+    /// How original values are hashed into mapping keys; unset means
+    /// hmac-sha256, keyed with the mapping passphrase under encryption and
+    /// with the database's key file otherwise
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hash_algorithm: Option<HashAlgorithm>,
+    /// Refuse hash algorithms that are not FIPS 140 approved
+    #[serde(default)]
+    pub fips_mode: bool,
+    /// Keep an in-memory Bloom filter of mapped values, so lookups of values
+    /// never seen before skip the database
+    #[serde(default = "default_bloom_filter")]
+    pub bloom_filter: bool,
+    /// Shared Redis cache in front of this database
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redis: Option<RedisCacheConfig>,
+    #[serde(default)]
+    pub llm_cache_ttl: LlmCacheTtlConfig,
+    #[serde(default)]
+    pub memory_cache: MemoryCacheConfig,
+    /// Most LLM cache entries kept per namespace; the least recently used
+    /// are evicted beyond it
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cache_entries: Option<u64>,
+    /// Most bytes of cached texts and results kept per namespace
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cache_bytes: Option<u64>,
+    /// How long a write waits for another connection's lock before failing
+    #[serde(default = "default_busy_timeout_ms")]
+    pub busy_timeout_ms: u64,
+    /// Start a fresh namespace below `namespace` at every MCP `initialize`,
+    /// so each client session gets its own pseudonyms
+    #[serde(default)]
+    pub reset_on_initialize: bool,
+    /// Keep a session's mappings when it ends instead of clearing its
+    /// namespace; `retention_days` still removes them
+    #[serde(default)]
+    pub keep_sessions: bool,
+    /// Put the originals back for fakes found in the arguments of a client's
+    /// tool calls, so tools act on the real values; needs `encryption`
+    #[serde(default)]
+    pub restore_in_requests: bool,
+}
+
+fn default_expiry_warning_days() -> u32 {
+    7
+}
+
+fn default_bloom_filter() -> bool {
+    true
+}
+
+fn default_busy_timeout_ms() -> u64 {
+    5000
+}
+
+/// Lifetime of LLM cache entries, adapted to how often each one is hit. A
+/// new entry lives `min_ttl_hours`; every hit doubles its lifetime, up to
+/// `max_ttl_days`. One-off texts drop out quickly while recurring ones stay.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmCacheTtlConfig {
+    /// Off keeps entries until `retention_days`, as before
+    #[serde(default = "default_adaptive_ttl")]
+    pub adaptive: bool,
+    /// Lifetime of an entry that has not been hit yet
+    #[serde(default = "default_min_ttl_hours")]
+    pub min_ttl_hours: u64,
+    /// Longest lifetime a frequently hit entry is extended to
+    #[serde(default = "default_max_ttl_days")]
+    pub max_ttl_days: u64,
+}
+
+impl Default for LlmCacheTtlConfig {
+    fn default() -> Self {
+        Self {
+            adaptive: default_adaptive_ttl(),
+            min_ttl_hours: default_min_ttl_hours(),
+            max_ttl_days: default_max_ttl_days(),
+        }
+    }
+}
+
+fn default_adaptive_ttl() -> bool {
+    true
+}
+
+fn default_min_ttl_hours() -> u64 {
+    24
+}
+
+fn default_max_ttl_days() -> u64 {
+    30
+}
+
+impl LlmCacheTtlConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.adaptive && self.min_ttl_hours == 0 {
+            return Err(anyhow::anyhow!("mapping.llm_cache_ttl.min_ttl_hours must be at least 1"));
+        }
+        if self.adaptive && self.max_ttl_days * 24 < self.min_ttl_hours {
+            return Err(anyhow::anyhow!("mapping.llm_cache_ttl.max_ttl_days must not be shorter than min_ttl_hours"));
+        }
+        Ok(())
+    }
+
+    /// Initial lifetime of an entry, unset without adaptive TTLs.
+    pub fn min_ttl_secs(&self) -> Option<u64> {
+        self.adaptive.then_some(self.min_ttl_hours * 60 * 60)
+    }
+
+    pub fn max_ttl_secs(&self) -> u64 {
+        self.max_ttl_days * 24 * 60 * 60
+    }
+}
+
+/// Sizes of the in-process LRU caches in front of SQLite; 0 disables one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MemoryCacheConfig {
+    /// Most recently used mappings kept in memory
+    #[serde(default = "default_memory_cache_mappings")]
+    pub mappings: usize,
+    /// Most recently used LLM results kept in memory
+    #[serde(default = "default_memory_cache_llm_results")]
+    pub llm_results: usize,
+}
+
+impl Default for MemoryCacheConfig {
+    fn default() -> Self {
+        Self {
+            mappings: default_memory_cache_mappings(),
+            llm_results: default_memory_cache_llm_results(),
+        }
+    }
+}
+
+fn default_memory_cache_mappings() -> usize {
+    10_000
+}
+
+fn default_memory_cache_llm_results() -> usize {
+    1_000
+}
+
+/// Where the mapping encryption/HMAC passphrase is read from.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PassphraseSource {
+    /// OS credential store (Keychain, Secret Service, Windows Credential Manager)
+    #[default]
+    Keyring,
+    /// `MCP_CONCEAL_PASSPHRASE` environment variable, for headless CI runs
+    Env,
+}
+
+fn default_keyring_account() -> String {
+    "mapping-passphrase".to_string()
+}
+
+impl Default for MappingConfig {
+    fn default() -> Self {
+        Self {
+            database_path: PathBuf::from("mappings.db"),
+            namespace: None,
+            encryption: false,
+            retention_days: Some(90),
+            passphrase_source: PassphraseSource::default(),
+            keyring_account: default_keyring_account(),
+            bypass_tokens: false,
+            expiry_warning_days: default_expiry_warning_days(),
+            hash_algorithm: None,
+            fips_mode: false,
+            bloom_filter: true,
+            redis: None,
+            llm_cache_ttl: LlmCacheTtlConfig::default(),
+            memory_cache: MemoryCacheConfig::default(),
+            max_cache_entries: None,
+            max_cache_bytes: None,
+            busy_timeout_ms: default_busy_timeout_ms(),
+            reset_on_initialize: false,
+            keep_sessions: false,
+            restore_in_requests: false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -52,6 +775,262 @@ pub struct LlmConfig {
     pub endpoint: String,
     pub timeout_seconds: u64,
     pub prompt_template: Option<String>,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    #[serde(default)]
+    pub concurrency: LlmConcurrencyConfig,
+    #[serde(default)]
+    pub batching: LlmBatchConfig,
+    #[serde(default)]
+    pub chunking: LlmChunkingConfig,
+    #[serde(default)]
+    pub verification: LlmVerificationConfig,
+    #[serde(default)]
+    pub output_format: LlmOutputFormat,
+    /// Pull a missing model at startup instead of failing
+    #[serde(default)]
+    pub auto_pull: bool,
+    #[serde(default)]
+    pub outage_queue: OutageQueueConfig,
+    #[serde(default)]
+    pub provider: LlmProvider,
+    /// Allow sending text to an endpoint that is not on this host
+    #[serde(default)]
+    pub allow_remote: bool,
+    /// Environment variable holding the hosted provider's API key
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+}
+
+/// How Ollama is asked to constrain its replies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmOutputFormat {
+    /// Free-form text; JSON is extracted from the reply
+    None,
+    /// `format: "json"`, supported by all Ollama versions with JSON mode
+    #[default]
+    Json,
+    /// `format: <JSON schema>`, requires Ollama 0.5 or later
+    Schema,
+}
+
+/// Packing of several short strings from one message into a single LLM request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmBatchConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Most texts sent in one request
+    #[serde(default = "default_batch_max_items")]
+    pub max_items: usize,
+    /// Most characters sent in one request; longer texts are sent on their own
+    #[serde(default = "default_batch_max_chars")]
+    pub max_chars: usize,
+}
+
+impl Default for LlmBatchConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_items: default_batch_max_items(),
+            max_chars: default_batch_max_chars(),
+        }
+    }
+}
+
+fn default_batch_max_items() -> usize {
+    16
+}
+
+fn default_batch_max_chars() -> usize {
+    4000
+}
+
+/// Limits applied to each message the proxy processes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessingConfig {
+    /// Longest a single message may take to process before it is forwarded
+    /// unprocessed; 0 disables the limit
+    #[serde(default = "default_message_timeout_seconds")]
+    pub message_timeout_seconds: u64,
+    /// Time LLM detection may add to one message before the rest of it is
+    /// detected with regex only; 0 disables the budget
+    #[serde(default)]
+    pub latency_budget_ms: u64,
+    /// How long the target gets to exit after stdin closes or a signal is
+    /// forwarded to it, and to finish writing its output, before it is killed
+    #[serde(default = "default_shutdown_grace_seconds")]
+    pub shutdown_grace_seconds: u64,
+    /// How long a gateway waits for every target to answer `initialize` or
+    /// `tools/list` before answering with the targets that did
+    #[serde(default = "default_fan_out_timeout_seconds")]
+    pub fan_out_timeout_seconds: u64,
+    /// What reaches the other side when a message cannot be fully processed
+    #[serde(default)]
+    pub on_error: OnError,
+    /// Longest line read from either side, newline excluded; longer lines are
+    /// never buffered whole
+    #[serde(default = "default_max_line_bytes")]
+    pub max_line_bytes: usize,
+    #[serde(default)]
+    pub on_oversized_line: OversizedLine,
+    /// How messages are delimited on stdio
+    #[serde(default)]
+    pub framing: Framing,
+    /// Deepest nesting of arrays and objects processed; deeper messages are
+    /// handled according to `on_error`
+    #[serde(default = "default_max_json_depth")]
+    pub max_json_depth: usize,
+    /// Most JSON values in one message
+    #[serde(default = "default_max_json_nodes")]
+    pub max_json_nodes: usize,
+}
+
+/// Delimiting of messages on a stream. Messages are written in the framing
+/// they were read in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Framing {
+    /// Decide from the first bytes of each stream: a header means
+    /// `content_length`, anything else `newline`
+    #[default]
+    Auto,
+    /// One JSON-RPC message per line
+    Newline,
+    /// `Content-Length: <n>` header, blank line, then `n` bytes of body, as in LSP
+    ContentLength,
+}
+
+impl Framing {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Framing::Auto => "auto",
+            Framing::Newline => "newline",
+            Framing::ContentLength => "content_length",
+        }
+    }
+}
+
+/// Handling of a line longer than `max_line_bytes`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizedLine {
+    /// Discard the line
+    #[default]
+    Drop,
+    /// Forward the first `max_line_bytes`, concealed as plain text, and discard the rest
+    Truncate,
+    /// Stream the line through unprocessed
+    Forward,
+}
+
+/// Handling of a message that failed processing, timed out, or had strings
+/// left unprocessed after an error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OnError {
+    /// Forward the original message
+    #[default]
+    Forward,
+    /// Drop the message; a response becomes a JSON-RPC error
+    Block,
+    /// Forward the message with every string below the JSON-RPC envelope redacted
+    RedactAll,
+}
+
+impl OversizedLine {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OversizedLine::Drop => "drop",
+            OversizedLine::Truncate => "truncate",
+            OversizedLine::Forward => "forward",
+        }
+    }
+}
+
+impl OnError {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OnError::Forward => "forward",
+            OnError::Block => "block",
+            OnError::RedactAll => "redact_all",
+        }
+    }
+}
+
+impl Default for ProcessingConfig {
+    fn default() -> Self {
+        Self {
+            message_timeout_seconds: default_message_timeout_seconds(),
+            latency_budget_ms: 0,
+            shutdown_grace_seconds: default_shutdown_grace_seconds(),
+            fan_out_timeout_seconds: default_fan_out_timeout_seconds(),
+            on_error: OnError::default(),
+            max_line_bytes: default_max_line_bytes(),
+            on_oversized_line: OversizedLine::default(),
+            framing: Framing::default(),
+            max_json_depth: default_max_json_depth(),
+            max_json_nodes: default_max_json_nodes(),
+        }
+    }
+}
+
+impl ProcessingConfig {
+    pub fn message_timeout(&self) -> Option<Duration> {
+        (self.message_timeout_seconds > 0).then(|| Duration::from_secs(self.message_timeout_seconds))
+    }
+
+    pub fn latency_budget(&self) -> Option<Duration> {
+        (self.latency_budget_ms > 0).then(|| Duration::from_millis(self.latency_budget_ms))
+    }
+
+    pub fn shutdown_grace(&self) -> Duration {
+        Duration::from_secs(self.shutdown_grace_seconds)
+    }
+
+    pub fn fan_out_timeout(&self) -> Duration {
+        Duration::from_secs(self.fan_out_timeout_seconds)
+    }
+
+    pub fn json_limits(&self) -> JsonLimits {
+        JsonLimits { max_depth: self.max_json_depth, max_nodes: self.max_json_nodes }
+    }
+}
+
+fn default_message_timeout_seconds() -> u64 {
+    300
+}
+
+fn default_shutdown_grace_seconds() -> u64 {
+    5
+}
+
+fn default_fan_out_timeout_seconds() -> u64 {
+    60
+}
+
+fn default_max_line_bytes() -> usize {
+    64 * 1024 * 1024
+}
+
+fn default_max_json_depth() -> usize {
+    128
+}
+
+fn default_max_json_nodes() -> usize {
+    1_000_000
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct AuditConfig {
+    /// JSON-lines file for audit events; relative paths live in the data directory.
+    /// When unset, events are written to the regular log.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+    /// Directory for per-session manifests; relative paths live in the data
+    /// directory. When unset, the manifest is only recorded as an audit event.
+    #[serde(default)]
+    pub manifest_dir: Option<PathBuf>,
 }
 
 impl Default for Config {
@@ -59,34 +1038,81 @@ impl Default for Config {
         let mut patterns = HashMap::new();
         patterns.insert(
             "email".to_string(),
-            r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b".to_string(),
+            r"\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Z|a-z]{2,}\b".into(),
         );
         // Add other common patterns here as needed
         
         Self {
+            config_version: CURRENT_CONFIG_VERSION,
+            preset: None,
             detection: DetectionConfig {
                 mode: DetectionMode::RegexLlm,
                 enabled: true,
                 patterns,
+                pattern_packs: Vec::new(),
                 confidence_threshold: 0.8,
+                key_hints: default_key_hints(),
+                overlap: OverlapConfig::default(),
+                stitching: StitchingConfig::default(),
+                names: NameDetectionConfig::default(),
+                entropy: EntropyConfig::default(),
+                key_material: KeyMaterialConfig::default(),
+                context: ContextConfig::default(),
+                phone: PhoneConfig::default(),
+                urls: UrlConfig::default(),
+                identifiers: IdentifierConfig::default(),
+                dictionaries: Vec::new(),
+                scalars: ScalarFieldsConfig::default(),
+                nested_json: NestedJsonConfig::default(),
+                base64: Base64Config::default(),
+                schema_hints: SchemaHintsConfig::default(),
             },
             faker: FakerConfig {
                 locale: "en_US".to_string(),
                 seed: Some(12345),
                 consistency: true,
+                strategies: HashMap::new(),
+                ip: IpFakerConfig::default(),
+                email: EmailFakerConfig::default(),
+                date_shift: DateShiftConfig::default(),
+                geo: GeoFakerConfig::default(),
+                identity: IdentityConfig::default(),
             },
-            mapping: MappingConfig {
-                database_path: PathBuf::from("mappings.db"),
-                encryption: false,
-                retention_days: Some(90),
-            },
+            mapping: MappingConfig::default(),
             llm: Some(LlmConfig {
                 enabled: true,
                 model: "llama3.2:3b".to_string(),
                 endpoint: "http://localhost:11434".to_string(),
                 timeout_seconds: 300,
                 prompt_template: None,
+                circuit_breaker: CircuitBreakerConfig::default(),
+                concurrency: LlmConcurrencyConfig::default(),
+                batching: LlmBatchConfig::default(),
+                chunking: LlmChunkingConfig::default(),
+                verification: LlmVerificationConfig::default(),
+                output_format: LlmOutputFormat::default(),
+                auto_pull: false,
+                outage_queue: OutageQueueConfig::default(),
+                provider: LlmProvider::default(),
+                allow_remote: false,
+                api_key_env: None,
             }),
+            audit: AuditConfig::default(),
+            discovery: DiscoveryConfig::default(),
+            target_trust: TargetTrustConfig::default(),
+            processing: ProcessingConfig::default(),
+            quarantine: QuarantineConfig::default(),
+            approval: ApprovalConfig::default(),
+            leak_check: LeakCheckConfig::default(),
+            canary: CanaryConfig::default(),
+            i18n: I18nConfig::default(),
+            admin: AdminConfig::default(),
+            reload: ReloadConfig::default(),
+            telemetry: TelemetryConfig::default(),
+            logging: LoggingConfig::default(),
+            language: LanguageConfig::default(),
+            targets: Vec::new(),
+            profile: BTreeMap::new(),
         }
     }
 }
@@ -106,13 +1132,64 @@ impl Config {
             std::fs::create_dir_all(data_dir)?;
             self.mapping.database_path = data_dir.join(&self.mapping.database_path);
         }
+
+        if let Some(audit_path) = self.audit.path.as_mut().filter(|p| p.is_relative()) {
+            *audit_path = project_dirs.data_dir().join(&*audit_path);
+        }
+
+        if let Some(manifest_dir) = self.audit.manifest_dir.as_mut().filter(|p| p.is_relative()) {
+            *manifest_dir = project_dirs.data_dir().join(&*manifest_dir);
+        }
+
+        if self.discovery.path.is_relative() {
+            self.discovery.path = project_dirs.data_dir().join(&self.discovery.path);
+        }
+
+        if let Some(llm) = self.llm.as_mut().filter(|llm| llm.outage_queue.path.is_relative()) {
+            llm.outage_queue.path = project_dirs.data_dir().join(&llm.outage_queue.path);
+        }
+
+        if self.quarantine.path.is_relative() {
+            self.quarantine.path = project_dirs.data_dir().join(&self.quarantine.path);
+        }
+
+        if self.approval.dir.is_relative() {
+            self.approval.dir = project_dirs.data_dir().join(&self.approval.dir);
+        }
+
+        if let Some(socket) = self.admin.socket.as_mut().filter(|p| p.is_relative()) {
+            *socket = project_dirs.data_dir().join(&*socket);
+        }
+
+        for dictionary in self.detection.dictionaries.iter_mut().filter(|d| d.path.is_relative()) {
+            dictionary.path = project_dirs.data_dir().join(&dictionary.path);
+        }
+
+        if let Some(log_file) = self.logging.file.as_mut().filter(|p| p.is_relative()) {
+            *log_file = project_dirs.data_dir().join(&*log_file);
+        }
         
         Ok(())
     }
 
     pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self> {
-        let contents = std::fs::read_to_string(path)?;
-        let mut config: Self = toml::from_str(&contents)?;
+        Self::load(path, None, None)
+    }
+
+    /// Loads `path` with `[profile.<name>]` applied, over `preset` or else the
+    /// preset the file names. A file that defines no profiles is loaded as it
+    /// is, since `--profile` also names the mapping namespace; one that does
+    /// must define the requested profile.
+    pub fn load<P: AsRef<std::path::Path>>(path: P, profile: Option<&str>, preset: Option<Preset>) -> Result<Self> {
+        Self::from_value(config_migration::load_and_migrate(path)?, profile, preset)
+    }
+
+    fn from_value(mut value: toml::Value, profile: Option<&str>, preset: Option<Preset>) -> Result<Self> {
+        if let Some(name) = profile {
+            apply_profile(&mut value, name)?;
+        }
+        presets::apply(&mut value, preset)?;
+        let mut config: Self = value.try_into()?;
         config.resolve_paths()?;
         Ok(config)
     }
@@ -125,20 +1202,73 @@ impl Config {
     }
 
     pub fn to_file<P: AsRef<std::path::Path>>(&self, path: P) -> Result<()> {
-        let contents = toml::to_string_pretty(self)?;
+        let path = path.as_ref();
+        let contents = ConfigFormat::from_path(path).render(self)?;
         std::fs::write(path, contents)?;
         Ok(())
     }
 
     pub fn validate(&self) -> Result<()> {
-        for (name, pattern) in &self.detection.patterns {
-            regex::Regex::new(pattern)
+        for (name, pattern) in &pattern_packs::with_packs(&self.detection.pattern_packs, &self.detection.patterns)? {
+            regex::Regex::new(&pattern.expression())
                 .map_err(|e| anyhow::anyhow!("Invalid regex pattern for '{}': {}", name, e))?;
+            if let PatternConfig::Structured(PatternSpec { confidence: Some(confidence), .. }) = pattern {
+                if !(0.0..=1.0).contains(confidence) {
+                    return Err(anyhow::anyhow!("Confidence of pattern '{}' must be between 0.0 and 1.0", name));
+                }
+            }
         }
 
         if !(0.0..=1.0).contains(&self.detection.confidence_threshold) {
             return Err(anyhow::anyhow!("Confidence threshold must be between 0.0 and 1.0"));
         }
+
+        ScalarRules::new(&self.detection.scalars)?;
+        if self.detection.phone.enabled {
+            PhoneDetector::new(&self.detection.phone)?;
+        }
+        self.faker.ip.validate()?;
+        self.faker.date_shift.validate()?;
+        self.faker.geo.validate()?;
+        self.quarantine.validate()?;
+        self.approval.validate()?;
+        self.canary.validate()?;
+        self.mapping.llm_cache_ttl.validate()?;
+        gateway::validate_targets(&self.targets)?;
+
+        if self.mapping.restore_in_requests && !self.mapping.encryption {
+            return Err(anyhow::anyhow!("mapping.restore_in_requests needs mapping.encryption = true, which stores the originals"));
+        }
+
+        if let Some(algorithm) = self.mapping.hash_algorithm.filter(|a| self.mapping.fips_mode && !a.is_fips_approved()) {
+            return Err(anyhow::anyhow!(
+                "Hash algorithm {} is not FIPS approved; use sha256, sha512 or an hmac variant with fips_mode",
+                algorithm
+            ));
+        }
+
+        // serde_json refuses documents nested deeper than 128 levels anyway
+        if !(1..=128).contains(&self.processing.max_json_depth) || self.processing.max_json_nodes == 0 {
+            return Err(anyhow::anyhow!("processing.max_json_depth must be between 1 and 128, and max_json_nodes at least 1"));
+        }
+
+        if self.leak_check.min_length == 0 {
+            return Err(anyhow::anyhow!("leak_check.min_length must be at least 1"));
+        }
+
+        if self.detection.stitching.window < 2 {
+            return Err(anyhow::anyhow!("Stitching window must be at least 2"));
+        }
+
+        if !(0.0..=1.0).contains(&self.discovery.sample_rate) {
+            return Err(anyhow::anyhow!("Discovery sample rate must be between 0.0 and 1.0"));
+        }
+
+        if let Some(chunking) = self.llm.as_ref().map(|llm| &llm.chunking).filter(|c| c.enabled) {
+            if chunking.overlap_chars >= chunking.max_chars {
+                return Err(anyhow::anyhow!("LLM chunk overlap must be smaller than max_chars"));
+            }
+        }
         
         if let Some(parent) = self.mapping.database_path.parent() {
             if !parent.exists() {
@@ -150,13 +1280,21 @@ impl Config {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct DetectedEntity {
     pub entity_type: String,
     pub original_value: String,
     pub start: usize,
     pub end: usize,
     pub confidence: f64,
+    /// Region parsed from the value, such as a phone number's country (`GB`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+}
+
+/// Entity type without its key path: `email@customer.email` → `email`.
+pub fn base_entity_type(entity_type: &str) -> &str {
+    entity_type.split('@').next().unwrap_or(entity_type)
 }
 
 #[derive(Debug, Clone)]
@@ -167,11 +1305,90 @@ pub struct AnonymizedEntity {
     pub mapping_id: String,
 }
 
+fn apply_profile(value: &mut toml::Value, name: &str) -> Result<()> {
+    let Some(profiles) = value.get("profile").and_then(|p| p.as_table()) else {
+        return Ok(());
+    };
+    let overrides = profiles.get(name).cloned().ok_or_else(|| {
+        let available: Vec<&str> = profiles.keys().map(String::as_str).collect();
+        anyhow::anyhow!("Profile '{}' is not defined in the configuration (available: {})", name, available.join(", "))
+    })?;
+    merge_tables(value, overrides);
+    Ok(())
+}
+
+/// Tables are merged key by key; any other value replaces the one below.
+pub(crate) fn merge_tables(base: &mut toml::Value, overrides: toml::Value) {
+    match (base, overrides) {
+        (toml::Value::Table(base), toml::Value::Table(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_tables(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::NamedTempFile;
 
+    #[test]
+    fn test_patterns_take_a_structured_form() {
+        let detection: DetectionConfig = toml::from_str(r#"
+            mode = "regex"
+            enabled = true
+            confidence_threshold = 0.8
+            [patterns]
+            email = '\S+@\S+'
+            ticket = { regex = 'tkt-\d+', case_insensitive = true, unicode = false, confidence = 0.9, validator = "none" }
+        "#).unwrap();
+        assert_eq!(detection.patterns["email"].expression(), r"\S+@\S+");
+        assert_eq!(detection.patterns["email"].scoring("email"), (0.95, Some(Validator::Email)));
+        assert_eq!(detection.patterns["ticket"].expression(), r"(?i-u)tkt-\d+");
+        assert_eq!(detection.patterns["ticket"].scoring("ticket"), (0.9, Some(Validator::None)));
+        assert!(toml::from_str::<DetectionConfig>("mode = 'regex'\nenabled = true\nconfidence_threshold = 0.8\n[patterns]\nx = { regex = 'x', validator = 'crc' }").is_err());
+        // A misspelled flag is an error, not a pattern without it
+        assert!(toml::from_str::<DetectionConfig>("mode = 'regex'\nenabled = true\nconfidence_threshold = 0.8\n[patterns]\nx = { regex = 'x', case_insensitve = true }").is_err());
+    }
+
+    #[test]
+    fn test_profiles_override_the_base_configuration() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        let mut config = Config::default();
+        config.mapping.database_path = dir.path().join("mappings.db");
+        config.detection.patterns.insert("ticket".to_string(), r"\bTKT-\d+\b".into());
+        let mut github: toml::Table = toml::from_str(&format!(
+            "[detection.patterns]\ngithub_token = 'ghp_[A-Za-z0-9]{{36}}'\n[faker]\nlocale = 'de_DE'\n[mapping]\ndatabase_path = '{}'",
+            dir.path().join("github.db").display()
+        )).unwrap();
+        github.insert("targets".to_string(), toml::Value::Array(Vec::new()));
+        config.profile.insert("github".to_string(), github);
+        config.profile.insert("crm".to_string(), toml::Table::new());
+        config.to_file(&path).unwrap();
+
+        let loaded = Config::load(&path, Some("github"), None).unwrap();
+        assert!(loaded.detection.patterns.contains_key("github_token"));
+        assert!(loaded.detection.patterns.contains_key("ticket"));
+        assert!(loaded.detection.patterns.contains_key("email"));
+        assert_eq!(loaded.faker.locale, "de_DE");
+        assert_eq!(loaded.mapping.database_path, dir.path().join("github.db"));
+
+        let base = Config::from_file(&path).unwrap();
+        assert!(!base.detection.patterns.contains_key("github_token"));
+        assert_eq!(base.faker.locale, "en_US");
+
+        let err = Config::load(&path, Some("gitlab"), None).unwrap_err();
+        assert!(err.to_string().contains("available: crm, github"));
+    }
+
     #[test]
     fn test_config_default() {
         let config = Config::default();
@@ -190,7 +1407,7 @@ mod tests {
         
         config.validate().unwrap();
         
-        config.detection.patterns.insert("invalid".to_string(), "[".to_string());
+        config.detection.patterns.insert("invalid".to_string(), "[".into());
         assert!(config.validate().is_err());
         
         config = Config::default();
@@ -220,6 +1437,7 @@ mod tests {
             start: 10,
             end: 25,
             confidence: 0.95,
+            ..Default::default()
         };
         
         assert_eq!(entity.entity_type, "email");
@@ -0,0 +1,111 @@
+//! Configuration file formats
+//!
+//! Configuration is TOML by default, but MCP hosts often template JSON, so
+//! `.json`, `.yaml` and `.yml` files are read and written in their own format.
+//! Every format is parsed into the same TOML document, so migrations and
+//! profiles work the same whichever one a file uses.
+
+use anyhow::Result;
+use serde::Serialize;
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl ConfigFormat {
+    /// The format of `path`, by extension; anything unrecognised is TOML.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase).as_deref() {
+            Some("json") => ConfigFormat::Json,
+            Some("yaml" | "yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConfigFormat::Toml => "TOML",
+            ConfigFormat::Json => "JSON",
+            ConfigFormat::Yaml => "YAML",
+        }
+    }
+
+    pub fn parse(&self, contents: &str) -> Result<toml::Value> {
+        let document = match self {
+            ConfigFormat::Toml => return Ok(toml::from_str(contents)?),
+            ConfigFormat::Json => serde_json::from_str(contents)
+                .map_err(|e| anyhow::anyhow!("Invalid JSON configuration: {}", e))?,
+            ConfigFormat::Yaml => serde_yaml_ng::from_str(contents)
+                .map_err(|e| anyhow::anyhow!("Invalid YAML configuration: {}", e))?,
+        };
+        toml::Value::try_from(without_nulls(document))
+            .map_err(|e| anyhow::anyhow!("Unsupported {} configuration: {}", self.as_str(), e))
+    }
+
+    pub fn render<T: Serialize>(&self, value: &T) -> Result<String> {
+        Ok(match self {
+            ConfigFormat::Toml => toml::to_string_pretty(value)?,
+            ConfigFormat::Json => serde_json::to_string_pretty(value)? + "\n",
+            ConfigFormat::Yaml => serde_yaml_ng::to_string(value)?,
+        })
+    }
+}
+
+/// TOML has no null; a null field means the same as leaving it out.
+fn without_nulls(value: serde_json::Value) -> serde_json::Value {
+    match value {
+        serde_json::Value::Object(map) => map.into_iter()
+            .filter(|(_, v)| !v.is_null())
+            .map(|(k, v)| (k, without_nulls(v)))
+            .collect(),
+        serde_json::Value::Array(items) => items.into_iter().map(without_nulls).collect(),
+        other => other,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+
+    #[test]
+    fn test_formats_are_chosen_by_extension() {
+        assert_eq!(ConfigFormat::from_path(Path::new("conceal.json")), ConfigFormat::Json);
+        assert_eq!(ConfigFormat::from_path(Path::new("conceal.YML")), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path(Path::new("conceal.yaml")), ConfigFormat::Yaml);
+        assert_eq!(ConfigFormat::from_path(Path::new("conceal.toml")), ConfigFormat::Toml);
+        assert_eq!(ConfigFormat::from_path(Path::new("conceal")), ConfigFormat::Toml);
+    }
+
+    #[test]
+    fn test_json_and_yaml_files_load_like_toml() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let database = dir.path().join("mappings.db");
+        let json = dir.path().join("conceal.json");
+        std::fs::write(&json, format!(r#"{{
+            "detection": {{ "mode": "regex", "enabled": true, "confidence_threshold": 0.8,
+                            "patterns": {{ "ticket": "\\bTKT-\\d+\\b" }} }},
+            "faker": {{ "locale": "en_US", "seed": 7, "consistency": true }},
+            "mapping": {{ "database_path": {:?}, "encryption": false, "retention_days": null }},
+            "llm": null
+        }}"#, database.display().to_string())).unwrap();
+        let config = Config::from_file(&json).unwrap();
+        assert!(config.detection.patterns.contains_key("ticket"));
+        assert_eq!(config.faker.seed, Some(7));
+        assert!(config.llm.is_none());
+        // Migration wrote the version back in the file's own format
+        let rewritten: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+        assert!(rewritten["config_version"].is_number());
+
+        let yaml = dir.path().join("conceal.yaml");
+        config.to_file(&yaml).unwrap();
+        assert!(std::fs::read_to_string(&yaml).unwrap().contains("ticket:"));
+        let reloaded = Config::from_file(&yaml).unwrap();
+        assert_eq!(reloaded.detection.patterns, config.detection.patterns);
+        assert_eq!(reloaded.mapping.database_path, database);
+    }
+}
@@ -0,0 +1,387 @@
+//! Versioned configuration schema and startup migration of legacy config files
+//!
+//! Each release that renames, moves or drops a configuration field bumps
+//! `CURRENT_CONFIG_VERSION` and registers a migration step here. Old files are
+//! upgraded in place on startup (after writing a backup) and every deprecated
+//! field that was touched is explained in the logs. TOML files are edited key
+//! by key, so comments, order and formatting the migration did not touch are
+//! kept.
+
+use crate::config_format::ConfigFormat;
+use crate::provider;
+use anyhow::Result;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use toml::value::Table;
+use toml::Value;
+use toml_edit::{DocumentMut, Item, TableLike};
+use tracing::{info, warn};
+
+/// Schema version written by this release.
+pub const CURRENT_CONFIG_VERSION: u32 = 2;
+
+const VERSION_KEY: &str = "config_version";
+
+/// A step fails when it cannot upgrade a file without a decision only the
+/// user can make.
+type MigrationStep = fn(&mut Table, &mut MigrationReport) -> Result<()>;
+
+/// Ordered migration steps. Entry `i` upgrades a file from version `i` to `i + 1`.
+const MIGRATIONS: &[MigrationStep] = &[migrate_v0_to_v1, migrate_v1_to_v2];
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MigrationReport {
+    pub from_version: u32,
+    pub to_version: u32,
+    pub notices: Vec<String>,
+}
+
+impl MigrationReport {
+    pub fn is_migrated(&self) -> bool {
+        self.from_version != self.to_version
+    }
+}
+
+/// Upgrades a parsed configuration document to `CURRENT_CONFIG_VERSION`.
+pub fn migrate_value(value: &mut Value) -> Result<MigrationReport> {
+    let table = value
+        .as_table_mut()
+        .ok_or_else(|| anyhow::anyhow!("Configuration root must be a table"))?;
+
+    let from_version = match table.get(VERSION_KEY) {
+        None => 0,
+        Some(Value::Integer(v)) if *v >= 0 => *v as u32,
+        Some(other) => {
+            return Err(anyhow::anyhow!("Invalid '{}' value: {}", VERSION_KEY, other));
+        }
+    };
+
+    if from_version > CURRENT_CONFIG_VERSION {
+        return Err(anyhow::anyhow!(
+            "Configuration version {} is newer than this release supports ({}); please upgrade mcp-server-conceal",
+            from_version,
+            CURRENT_CONFIG_VERSION
+        ));
+    }
+
+    let mut report = MigrationReport {
+        from_version,
+        to_version: from_version,
+        notices: Vec::new(),
+    };
+
+    for step in &MIGRATIONS[from_version as usize..] {
+        step(table, &mut report)?;
+        report.to_version += 1;
+    }
+
+    table.insert(VERSION_KEY.to_string(), Value::Integer(report.to_version as i64));
+    Ok(report)
+}
+
+/// Reads a config file and migrates it in memory, leaving the file as it is.
+/// Used when a running proxy reloads its configuration, which must not
+/// rewrite a file the user may be editing.
+pub fn read_migrated<P: AsRef<Path>>(path: P) -> Result<Value> {
+    let path = path.as_ref();
+    let mut value = ConfigFormat::from_path(path).parse(&std::fs::read_to_string(path)?)?;
+    let report = migrate_value(&mut value)?;
+    if report.is_migrated() {
+        info!(
+            "Configuration {} is at version {}; migrated in memory, it is rewritten at the next start",
+            path.display(),
+            report.from_version
+        );
+    }
+    Ok(value)
+}
+
+/// Reads a config file, migrates it if needed and, when anything changed,
+/// writes a backup of the original next to it before rewriting it in place.
+pub fn load_and_migrate<P: AsRef<Path>>(path: P) -> Result<Value> {
+    let path = path.as_ref();
+    let contents = std::fs::read_to_string(path)?;
+    let format = ConfigFormat::from_path(path);
+    let mut value = format.parse(&contents)?;
+    let original = value.clone();
+    let report = migrate_value(&mut value)?;
+
+    if report.is_migrated() {
+        info!(
+            "Migrating configuration {} from version {} to {}",
+            path.display(),
+            report.from_version,
+            report.to_version
+        );
+        for notice in &report.notices {
+            warn!("Config migration: {}", notice);
+        }
+
+        // Rendered before anything is written: a file that cannot be
+        // rendered must not be replaced with an empty one
+        let rendered = match format {
+            ConfigFormat::Toml => edit_toml(&contents, &original, &value)?,
+            ConfigFormat::Json | ConfigFormat::Yaml => format.render(&value)?,
+        };
+        let write_result = write_backup(path, report.from_version, &contents)
+            .and_then(|backup_path| std::fs::write(path, rendered).map(|_| backup_path));
+
+        match write_result {
+            Ok(backup_path) => info!(
+                "Upgraded configuration written in place (backup: {})",
+                backup_path.display()
+            ),
+            // Read-only config locations are common (e.g. managed deployments);
+            // the migrated config is still used for this run.
+            Err(e) => warn!(
+                "Could not rewrite migrated configuration {}: {}. Using migrated settings in memory only",
+                path.display(),
+                e
+            ),
+        }
+    }
+
+    Ok(value)
+}
+
+/// `contents` of `path` as it was before the migration, in `<file>.v<N>.bak`
+/// or, when a backup of that version is already there, `<file>.v<N>.<n>.bak`.
+/// An existing backup is never overwritten.
+fn write_backup(path: &Path, from_version: u32, contents: &str) -> std::io::Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| "config".to_string());
+    for attempt in 0u32.. {
+        let backup_path = match attempt {
+            0 => path.with_file_name(format!("{}.v{}.bak", file_name, from_version)),
+            n => path.with_file_name(format!("{}.v{}.{}.bak", file_name, from_version, n)),
+        };
+        match std::fs::OpenOptions::new().write(true).create_new(true).open(&backup_path) {
+            Ok(mut file) => return file.write_all(contents.as_bytes()).map(|_| backup_path),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!("backup names are exhausted")
+}
+
+/// `contents` with the keys the migration from `before` to `after` added,
+/// changed or removed edited in place.
+fn edit_toml(contents: &str, before: &Value, after: &Value) -> Result<String> {
+    let mut document: DocumentMut = contents.parse()
+        .map_err(|e| anyhow::anyhow!("Cannot edit configuration in place: {}", e))?;
+    let (Some(before), Some(after)) = (before.as_table(), after.as_table()) else {
+        return Err(anyhow::anyhow!("Configuration root must be a table"));
+    };
+    apply_changes(document.as_table_mut(), before, after)?;
+    Ok(document.to_string())
+}
+
+fn apply_changes(document: &mut dyn TableLike, before: &Table, after: &Table) -> Result<()> {
+    for key in before.keys().filter(|key| !after.contains_key(*key)) {
+        document.remove(key);
+    }
+    for (key, value) in after {
+        match (before.get(key), value, document.get_mut(key).and_then(Item::as_table_like_mut)) {
+            (Some(old), _, _) if old == value => {}
+            (Some(Value::Table(old)), Value::Table(new), Some(table)) => apply_changes(table, old, new)?,
+            _ => {
+                document.insert(key, toml_item(value)?);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `value` as a `toml_edit` item, a table for a table and a value otherwise.
+fn toml_item(value: &Value) -> Result<Item> {
+    let mut wrapper = Table::new();
+    wrapper.insert("item".to_string(), value.clone());
+    let mut document: DocumentMut = toml::to_string(&wrapper)?.parse()?;
+    Ok(document.remove("item").unwrap_or_default())
+}
+
+fn section<'a>(table: &'a mut Table, name: &str) -> Option<&'a mut Table> {
+    table.get_mut(name).and_then(Value::as_table_mut)
+}
+
+fn insert_default(table: &mut Table, section_name: &str, key: &str, value: Value, report: &mut MigrationReport) {
+    if let Some(section) = section(table, section_name) {
+        if !section.contains_key(key) {
+            report.notices.push(format!(
+                "'{}.{}' was missing and has been set to its previous implicit default ({})",
+                section_name, key, value
+            ));
+            section.insert(key.to_string(), value);
+        }
+    }
+}
+
+/// Version 0 covers every file written before the schema was versioned,
+/// including the example configuration from the original README.
+fn migrate_v0_to_v1(table: &mut Table, report: &mut MigrationReport) -> Result<()> {
+    insert_default(table, "detection", "enabled", Value::Boolean(true), report);
+    insert_default(table, "detection", "patterns", Value::Table(Table::new()), report);
+    insert_default(table, "faker", "consistency", Value::Boolean(true), report);
+    insert_default(table, "mapping", "encryption", Value::Boolean(false), report);
+    insert_default(table, "llm", "enabled", Value::Boolean(true), report);
+
+    if table.remove("llm_cache").is_some() {
+        report.notices.push(
+            "'[llm_cache]' is deprecated and has been removed: the LLM cache always lives in the mapping database \
+             ('mapping.database_path') and its lifetime follows 'mapping.retention_days'"
+                .to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// Version 2 refuses non-local LLM endpoints unless `llm.allow_remote` is set.
+/// A file that already points at a remote endpoint is not upgraded until the
+/// user opts in, since the migration cannot decide where text may go.
+fn migrate_v1_to_v2(table: &mut Table, _report: &mut MigrationReport) -> Result<()> {
+    let Some(llm) = section(table, "llm") else {
+        return Ok(());
+    };
+    let remote_endpoint = llm.get("endpoint")
+        .and_then(Value::as_str)
+        .filter(|endpoint| !provider::is_local_endpoint(endpoint));
+
+    match remote_endpoint {
+        Some(endpoint) if !llm.contains_key("allow_remote") => Err(anyhow::anyhow!(
+            "'llm.endpoint' ({}) is not on this host, and remote LLM endpoints now require an explicit opt-in. \
+             Add 'allow_remote = true' under [llm] to keep sending detection text there, \
+             or point 'llm.endpoint' at a local model",
+            endpoint
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use tempfile::TempDir;
+
+    const LEGACY_CONFIG: &str = r#"
+[detection]
+mode = "regex_llm"
+enabled = true
+confidence_threshold = 0.8
+
+[detection.patterns]
+email = "\\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\\.[A-Z|a-z]{2,}\\b"
+
+[faker]
+locale = "en_US"
+seed = 12345
+consistency = true
+
+[mapping]
+database_path = "mappings.db"
+retention_days = 90
+
+[llm]
+model = "llama3.2:3b"
+endpoint = "http://localhost:11434"
+timeout_seconds = 180
+
+[llm_cache]
+enabled = true
+database_path = "llm_cache.db"
+max_text_length = 2000
+"#;
+
+    #[test]
+    fn test_migrate_legacy_value() {
+        let mut value: Value = toml::from_str(LEGACY_CONFIG).unwrap();
+        let report = migrate_value(&mut value).unwrap();
+
+        assert_eq!(report.from_version, 0);
+        assert_eq!(report.to_version, CURRENT_CONFIG_VERSION);
+        assert!(report.is_migrated());
+        assert!(report.notices.iter().any(|n| n.contains("llm_cache")));
+        assert!(report.notices.iter().any(|n| n.contains("mapping.encryption")));
+
+        let table = value.as_table().unwrap();
+        assert!(!table.contains_key("llm_cache"));
+        assert_eq!(table["config_version"].as_integer(), Some(CURRENT_CONFIG_VERSION as i64));
+        assert_eq!(table["llm"]["enabled"].as_bool(), Some(true));
+    }
+
+    #[test]
+    fn test_remote_endpoint_needs_an_explicit_opt_in() {
+        let mut value: Value = toml::from_str("config_version = 1\n[llm]\nendpoint = \"http://gpu-box:11434\"").unwrap();
+        let error = migrate_value(&mut value).unwrap_err().to_string();
+        assert!(error.contains("gpu-box") && error.contains("allow_remote = true"), "{}", error);
+        assert!(value["llm"].get("allow_remote").is_none());
+
+        let mut value: Value = toml::from_str("config_version = 1\n[llm]\nendpoint = \"http://gpu-box:11434\"\nallow_remote = true").unwrap();
+        assert_eq!(migrate_value(&mut value).unwrap().to_version, CURRENT_CONFIG_VERSION);
+
+        let mut local: Value = toml::from_str(LEGACY_CONFIG).unwrap();
+        migrate_value(&mut local).unwrap();
+        assert!(local["llm"].get("allow_remote").is_none());
+    }
+
+    #[test]
+    fn test_current_version_is_untouched() {
+        let mut value = Value::try_from(Config::default()).unwrap();
+        let before = value.clone();
+        let report = migrate_value(&mut value).unwrap();
+
+        assert!(!report.is_migrated());
+        assert!(report.notices.is_empty());
+        assert_eq!(value, before);
+    }
+
+    #[test]
+    fn test_newer_version_is_rejected() {
+        let mut value: Value = toml::from_str("config_version = 999").unwrap();
+        assert!(migrate_value(&mut value).is_err());
+    }
+
+    #[test]
+    fn test_migration_rewrites_file_with_backup() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("mcp-server-conceal.toml");
+        std::fs::write(&path, LEGACY_CONFIG).unwrap();
+
+        let config = Config::from_file(&path).unwrap();
+        assert_eq!(config.config_version, CURRENT_CONFIG_VERSION);
+        assert!(!config.mapping.encryption);
+
+        let backup = temp_dir.path().join("mcp-server-conceal.toml.v0.bak");
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), LEGACY_CONFIG);
+
+        let rewritten = std::fs::read_to_string(&path).unwrap();
+        assert!(rewritten.contains(&format!("config_version = {}", CURRENT_CONFIG_VERSION)));
+        assert!(!rewritten.contains("llm_cache"));
+    }
+
+    #[test]
+    fn test_migration_keeps_comments_and_order_and_earlier_backups() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("mcp-server-conceal.toml");
+        let commented = LEGACY_CONFIG
+            .replace("[faker]\n", "# Fakes stay stable across runs\n[faker]\n")
+            .replace("seed = 12345", "seed = 12345      # the team's seed");
+        std::fs::write(&path, &commented).unwrap();
+        let earlier = temp_dir.path().join("mcp-server-conceal.toml.v0.bak");
+        std::fs::write(&earlier, "earlier backup").unwrap();
+
+        Config::from_file(&path).unwrap();
+        let rewritten = std::fs::read_to_string(&path).unwrap();
+        assert!(rewritten.contains("# Fakes stay stable across runs\n[faker]\n"), "{}", rewritten);
+        assert!(rewritten.contains("seed = 12345      # the team's seed"), "{}", rewritten);
+        assert!(rewritten.contains("[mapping]\ndatabase_path = \"mappings.db\"\nretention_days = 90\nencryption = false\n"), "{}", rewritten);
+        assert!(rewritten.find("[detection]") < rewritten.find("[faker]"));
+        assert!(!rewritten.contains("llm_cache"));
+
+        assert_eq!(std::fs::read_to_string(&earlier).unwrap(), "earlier backup");
+        let backup = temp_dir.path().join("mcp-server-conceal.toml.v0.1.bak");
+        assert_eq!(std::fs::read_to_string(backup).unwrap(), commented);
+    }
+}
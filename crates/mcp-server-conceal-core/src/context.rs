@@ -0,0 +1,183 @@
+//! Confidence adjustment from the words around a match
+//!
+//! `\d{3}-\d{2}-\d{4}` matches social security numbers and order numbers
+//! alike; the text around the match tells them apart. With
+//! `[detection.context]` enabled, a pattern match with one of its type's
+//! `keywords` ("SSN", "social security") within `window` characters before or
+//! after it gains `boost` confidence, and one with a `negative_keywords` word
+//! ("order", "invoice") nearby loses `demote`, which usually puts it below
+//! `confidence_threshold`. A positive keyword wins when both are nearby:
+//! "invoice for Jane Roe, SSN 123-45-6789" still holds an SSN. Keywords
+//! match case-insensitively as whole words.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContextConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Characters searched on each side of a match
+    #[serde(default = "default_window")]
+    pub window: usize,
+    #[serde(default = "default_boost")]
+    pub boost: f64,
+    #[serde(default = "default_demote")]
+    pub demote: f64,
+    /// Entity type → words that confirm a match
+    #[serde(default = "default_keywords")]
+    pub keywords: HashMap<String, Vec<String>>,
+    /// Entity type → words that suggest a look-alike
+    #[serde(default = "default_negative_keywords")]
+    pub negative_keywords: HashMap<String, Vec<String>>,
+}
+
+impl Default for ContextConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window: default_window(),
+            boost: default_boost(),
+            demote: default_demote(),
+            keywords: default_keywords(),
+            negative_keywords: default_negative_keywords(),
+        }
+    }
+}
+
+fn default_window() -> usize {
+    40
+}
+
+fn default_boost() -> f64 {
+    0.1
+}
+
+fn default_demote() -> f64 {
+    0.3
+}
+
+fn word_lists(lists: &[(&str, &[&str])]) -> HashMap<String, Vec<String>> {
+    lists.iter()
+        .map(|(entity_type, words)| (entity_type.to_string(), words.iter().map(|w| w.to_string()).collect()))
+        .collect()
+}
+
+fn default_keywords() -> HashMap<String, Vec<String>> {
+    word_lists(&[
+        ("ssn", &["ssn", "social security", "taxpayer"]),
+        ("credit_card", &["card", "card number", "credit", "visa", "mastercard", "amex", "cc"]),
+        ("phone", &["phone", "tel", "telephone", "mobile", "cell", "call", "fax"]),
+        ("email", &["email", "e-mail", "mail"]),
+        ("ip_address", &["ip", "address", "host"]),
+        ("coordinates", &["lat", "latitude", "lon", "lng", "longitude", "gps", "location", "coordinates", "position"]),
+        ("zip_code", &["zip", "zip code", "postal", "address"]),
+        ("postal_code", &["postcode", "postal", "post code", "plz", "cp", "address"]),
+        ("mac_address", &["mac", "hwaddr", "ether", "ethernet", "bssid", "hardware"]),
+    ])
+}
+
+fn default_negative_keywords() -> HashMap<String, Vec<String>> {
+    word_lists(&[
+        ("ssn", &["order", "invoice", "tracking", "part", "serial", "sku"]),
+        ("credit_card", &["order", "invoice", "tracking", "serial"]),
+        ("phone", &["order", "invoice", "tracking", "serial", "version"]),
+    ])
+}
+
+/// Adjusts the confidence of pattern matches by their surroundings.
+#[derive(Debug, Clone)]
+pub struct ContextScorer {
+    window: usize,
+    boost: f64,
+    demote: f64,
+    /// Lowercased keywords, per entity type
+    keywords: HashMap<String, Vec<String>>,
+    negative_keywords: HashMap<String, Vec<String>>,
+}
+
+impl ContextScorer {
+    pub fn new(config: &ContextConfig) -> Self {
+        let lowercase = |lists: &HashMap<String, Vec<String>>| lists.iter()
+            .map(|(entity_type, words)| (entity_type.clone(), words.iter().map(|w| w.to_lowercase()).collect()))
+            .collect();
+        Self {
+            window: config.window,
+            boost: config.boost,
+            demote: config.demote,
+            keywords: lowercase(&config.keywords),
+            negative_keywords: lowercase(&config.negative_keywords),
+        }
+    }
+
+    /// Highest confidence a match of `entity_type` can reach.
+    pub fn ceiling(&self, entity_type: &str, confidence: f64) -> f64 {
+        if self.keywords.contains_key(entity_type) {
+            (confidence + self.boost).min(1.0)
+        } else {
+            confidence
+        }
+    }
+
+    /// Confidence of the match at `start..end` of `text`, given the words
+    /// around it. A positive keyword outweighs a negative one, since missing
+    /// a real SSN costs more than replacing an order number.
+    pub fn adjust(&self, entity_type: &str, text: &str, start: usize, end: usize, confidence: f64) -> f64 {
+        let near = |lists: &HashMap<String, Vec<String>>| lists.get(entity_type)
+            .is_some_and(|words| self.near(text, start, end, words));
+        if near(&self.keywords) {
+            (confidence + self.boost).min(1.0)
+        } else if near(&self.negative_keywords) {
+            (confidence - self.demote).max(0.0)
+        } else {
+            confidence
+        }
+    }
+
+    fn near(&self, text: &str, start: usize, end: usize, words: &[String]) -> bool {
+        let from = text[..start].char_indices().rev().take(self.window).last().map_or(start, |(i, _)| i);
+        let to = text[end..].char_indices().nth(self.window).map_or(text.len(), |(i, _)| end + i);
+        let (before, after) = (text[from..start].to_lowercase(), text[end..to].to_lowercase());
+        words.iter().any(|word| contains_word(&before, word) || contains_word(&after, word))
+    }
+}
+
+fn contains_word(text: &str, word: &str) -> bool {
+    text.match_indices(word).any(|(i, _)| {
+        let before = text[..i].chars().next_back();
+        let after = text[i + word.len()..].chars().next();
+        !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn score(scorer: &ContextScorer, entity_type: &str, text: &str, value: &str) -> f64 {
+        let start = text.find(value).unwrap();
+        scorer.adjust(entity_type, text, start, start + value.len(), 0.8)
+    }
+
+    #[test]
+    fn test_nearby_words_adjust_confidence() {
+        let scorer = ContextScorer::new(&ContextConfig { enabled: true, ..ContextConfig::default() });
+        assert!((score(&scorer, "ssn", "Employee SSN: 123-45-6789", "123-45-6789") - 0.9).abs() < 1e-9);
+        assert!((score(&scorer, "ssn", "Order 123-45-6789 shipped", "123-45-6789") - 0.5).abs() < 1e-9);
+        assert!((score(&scorer, "ssn", "Reference 123-45-6789", "123-45-6789") - 0.8).abs() < 1e-9);
+        // Whole words only: "ordered" is not "order"
+        assert!((score(&scorer, "ssn", "She ordered 123-45-6789", "123-45-6789") - 0.8).abs() < 1e-9);
+        assert!((score(&scorer, "employee_id", "SSN EMP-1", "EMP-1") - 0.8).abs() < 1e-9);
+        // A positive keyword wins over a negative one
+        assert!((score(&scorer, "ssn", "Invoice for Jane, SSN 123-45-6789", "123-45-6789") - 0.9).abs() < 1e-9);
+        assert!((score(&scorer, "credit_card", "Order paid with card 4111 1111 1111 1111", "4111 1111 1111 1111") - 0.9).abs() < 1e-9);
+        assert!((scorer.ceiling("ssn", 0.95) - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_words_outside_the_window_are_ignored() {
+        let scorer = ContextScorer::new(&ContextConfig { enabled: true, window: 10, ..ContextConfig::default() });
+        let text = "Order placed long ago, then later: 123-45-6789";
+        assert!((score(&scorer, "ssn", text, "123-45-6789") - 0.8).abs() < 1e-9);
+    }
+}
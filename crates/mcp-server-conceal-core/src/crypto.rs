@@ -0,0 +1,64 @@
+//! Keys and encryption derived from the mapping passphrase
+//!
+//! Every feature that protects data at rest derives its own key from the
+//! mapping passphrase with a distinct context string, so a key recovered from
+//! one store says nothing about another.
+
+use crate::secrets::Passphrase;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Derives a 256-bit key for one purpose: `HMAC-SHA256(passphrase, context)`.
+pub fn derive_key(passphrase: &Passphrase, context: &[u8]) -> [u8; 32] {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(passphrase.expose().as_bytes())
+        .expect("HMAC accepts any key length");
+    mac.update(context);
+    mac.finalize().into_bytes().into()
+}
+
+/// Hex HMAC-SHA256 of `data`; lets equal values be matched without storing them.
+pub fn keyed_hash(key: &[u8], data: &[u8]) -> String {
+    let mut mac = <HmacSha256 as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+    mac.update(data);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Encrypts with AES-256-GCM under a fresh random nonce. Returns `(nonce, ciphertext)`.
+pub fn encrypt(key: &[u8; 32], plaintext: &[u8]) -> Result<(Vec<u8>, Vec<u8>)> {
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let ciphertext = cipher.encrypt(&nonce, plaintext)
+        .map_err(|_| anyhow::anyhow!("Encryption failed"))?;
+    Ok((nonce.to_vec(), ciphertext))
+}
+
+/// Decrypts and authenticates data produced by [`encrypt`].
+pub fn decrypt(key: &[u8; 32], nonce: &[u8], ciphertext: &[u8]) -> Result<Vec<u8>> {
+    if nonce.len() != 12 {
+        return Err(anyhow::anyhow!("Invalid nonce length {}", nonce.len()));
+    }
+    let cipher = Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(key));
+    cipher.decrypt(Nonce::from_slice(nonce), ciphertext)
+        .map_err(|_| anyhow::anyhow!("Decryption failed: wrong passphrase or corrupted data"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encrypt_round_trip_and_wrong_key() {
+        let key = derive_key(&Passphrase::new("correct horse"), b"test v1");
+        let (nonce, ciphertext) = encrypt(&key, b"jane@acme.com").unwrap();
+        assert_eq!(decrypt(&key, &nonce, &ciphertext).unwrap(), b"jane@acme.com");
+
+        let other = derive_key(&Passphrase::new("correct horse"), b"test v2");
+        assert_ne!(key, other);
+        assert!(decrypt(&other, &nonce, &ciphertext).is_err());
+    }
+}
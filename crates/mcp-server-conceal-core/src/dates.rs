@@ -0,0 +1,194 @@
+//! Consistent date shifting
+//!
+//! The `date_shift` strategy moves every date by the same random number of
+//! days, so the intervals between events survive: an admission three days
+//! before a discharge is still three days before it. The offset is drawn
+//! once per mapping database and stored there, so both proxy directions, and
+//! later runs with `--keep-database`, shift by the same amount.
+//!
+//! Dates keep their format: `2024-03-15`, `2024/03/15`, `03/15/2024` (US
+//! order) and `15.03.2024`. Whatever follows the date, such as the time in
+//! `2024-03-15T10:30:00Z`, is kept unchanged.
+
+use anyhow::Result;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DateShiftConfig {
+    /// Largest shift in days, in either direction
+    #[serde(default = "default_max_days")]
+    pub max_days: u32,
+}
+
+impl Default for DateShiftConfig {
+    fn default() -> Self {
+        Self { max_days: default_max_days() }
+    }
+}
+
+fn default_max_days() -> u32 {
+    365
+}
+
+impl DateShiftConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_days == 0 {
+            return Err(anyhow::anyhow!("faker.date_shift.max_days must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// A non-zero offset in days within `max_days`.
+    pub fn random_offset(&self, rng: &mut impl Rng) -> i64 {
+        let days = rng.gen_range(1..=self.max_days as i64);
+        if rng.gen_bool(0.5) { days } else { -days }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Order {
+    Ymd,
+    Mdy,
+    Dmy,
+}
+
+/// A valid date at the start of a value, as written.
+struct Written<'v> {
+    year: i64,
+    month: i64,
+    day: i64,
+    order: Order,
+    separator: char,
+    parts: Vec<&'v str>,
+    /// Length of the date; the rest of the value follows it
+    len: usize,
+}
+
+fn parse(value: &str) -> Option<Written<'_>> {
+    let date_len = value.find(|c: char| !(c.is_ascii_digit() || matches!(c, '-' | '/' | '.')))
+        .unwrap_or(value.len());
+    let date = value[..date_len].trim_end_matches(['-', '/', '.']);
+
+    let separator = date.chars().find(|c| !c.is_ascii_digit())?;
+    let parts: Vec<&str> = date.split(separator).collect();
+    if parts.len() != 3 || parts.iter().any(|p| p.is_empty() || p.len() > 4) {
+        return None;
+    }
+    let order = match (parts[0].len(), parts[2].len(), separator) {
+        (4, 1..=2, _) => Order::Ymd,
+        (1..=2, 4, '.') => Order::Dmy,
+        (1..=2, 4, _) => Order::Mdy,
+        _ => return None,
+    };
+    let field = |i: usize| parts[i].parse::<i64>().ok();
+    let (year, month, day) = match order {
+        Order::Ymd => (field(0)?, field(1)?, field(2)?),
+        Order::Mdy => (field(2)?, field(0)?, field(1)?),
+        Order::Dmy => (field(2)?, field(1)?, field(0)?),
+    };
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    Some(Written { year, month, day, order, separator, parts, len: date.len() })
+}
+
+/// Shifts the date at the start of `value` by `days`, keeping its format and
+/// anything after it. `None` when `value` does not start with a valid date.
+pub fn shift(value: &str, days: i64) -> Option<String> {
+    let date = parse(value)?;
+    let (parts, separator) = (&date.parts, date.separator);
+    let (year, month, day) = civil_from_days(days_from_civil(date.year, date.month, date.day) + days);
+    // Keep zero padding where the original had it
+    let pad = |n: i64, original: &str| format!("{:0width$}", n, width = original.len().min(2));
+    let shifted = match date.order {
+        Order::Ymd => format!("{:04}{sep}{}{sep}{}", year, pad(month, parts[1]), pad(day, parts[2]), sep = separator),
+        Order::Mdy => format!("{}{sep}{}{sep}{:04}", pad(month, parts[0]), pad(day, parts[1]), year, sep = separator),
+        Order::Dmy => format!("{}{sep}{}{sep}{:04}", pad(day, parts[0]), pad(month, parts[1]), year, sep = separator),
+    };
+    Some(format!("{}{}", shifted, &value[date.len..]))
+}
+
+pub fn current_year() -> i32 {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    1970 + (secs / 31_556_952) as i32
+}
+
+/// Year of the date at the start of `value`, and the length of that date.
+pub fn year(value: &str) -> Option<(i64, usize)> {
+    parse(value).map(|date| (date.year, date.len))
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since 1970-01-01 of a proleptic Gregorian date (Howard Hinnant's algorithm).
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146_097 + day_of_era - 719_468
+}
+
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719_468;
+    let era = days.div_euclid(146_097);
+    let day_of_era = days - era * 146_097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shift_keeps_format_and_crosses_boundaries() {
+        assert_eq!(shift("2024-02-28", 1).as_deref(), Some("2024-02-29"));
+        assert_eq!(shift("2023-02-28", 1).as_deref(), Some("2023-03-01"));
+        assert_eq!(shift("2024-12-31T23:59:00Z", 1).as_deref(), Some("2025-01-01T23:59:00Z"));
+        assert_eq!(shift("03/01/2024", -1).as_deref(), Some("02/29/2024"));
+        assert_eq!(shift("1.3.2024", -1).as_deref(), Some("29.2.2024"));
+        assert_eq!(shift("2024/01/15 08:00", -400).as_deref(), Some("2022/12/11 08:00"));
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+
+        assert_eq!(shift("2024-02-30", 1), None);
+        assert_eq!(shift("13/01/2024", 1), None);
+        assert_eq!(shift("v1.2.3", 1), None);
+    }
+
+    #[test]
+    fn test_intervals_survive_the_shift() {
+        let config = DateShiftConfig::default();
+        let offset = config.random_offset(&mut rand::thread_rng());
+        assert!(offset != 0 && offset.abs() <= 365);
+
+        let admitted = shift("2024-03-15", offset).unwrap();
+        let discharged = shift("2024-03-18", offset).unwrap();
+        let day = |date: &str| {
+            let parts: Vec<i64> = date.split('-').map(|p| p.parse().unwrap()).collect();
+            days_from_civil(parts[0], parts[1], parts[2])
+        };
+        assert_eq!(day(&discharged) - day(&admitted), 3);
+    }
+}
@@ -0,0 +1,141 @@
+//! Masked per-message diffs for debugging detection rules
+//!
+//! With `--debug-diff`, every message the proxy changes is logged as a small
+//! unified-style diff: one hunk per changed field, keyed by its JSON path,
+//! with the old and new values replaced by keyed hashes, followed by the
+//! entity types that fired. Hashes use a random per-process key, so equal
+//! values can be matched within a session but not looked up offline.
+
+use crate::crypto;
+use rand::RngCore;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// Diff state for one pipeline: the hashing key and the rules fired by the
+/// message being processed.
+pub struct DebugDiff {
+    key: [u8; 32],
+    fired: Vec<String>,
+}
+
+impl Default for DebugDiff {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DebugDiff {
+    pub fn new() -> Self {
+        let mut key = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut key);
+        Self { key, fired: Vec::new() }
+    }
+
+    /// Records an entity type applied to the current message.
+    pub fn record_rule(&mut self, entity_type: &str) {
+        self.fired.push(entity_type.to_string());
+    }
+
+    /// Renders the diff for one message and resets the fired rules.
+    pub fn render(&mut self, label: &str, original: &Value, processed: &Value) -> String {
+        let mut lines = vec![format!("--- original {}", label), format!("+++ processed {}", label)];
+        self.diff_value("$", original, processed, &mut lines);
+
+        let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+        for rule in self.fired.drain(..) {
+            *counts.entry(rule).or_default() += 1;
+        }
+        let fired: Vec<String> = counts.into_iter().map(|(rule, n)| format!("{} ({})", rule, n)).collect();
+        lines.push(format!("# rules fired: {}", if fired.is_empty() { "none".to_string() } else { fired.join(", ") }));
+        lines.join("\n")
+    }
+
+    /// Drops rules recorded for a message that turned out unchanged.
+    pub fn reset(&mut self) {
+        self.fired.clear();
+    }
+
+    fn diff_value(&self, path: &str, original: &Value, processed: &Value, lines: &mut Vec<String>) {
+        match (original, processed) {
+            (Value::Object(a), Value::Object(b)) => {
+                for (key, value) in a {
+                    let child = format!("{}.{}", path, key);
+                    match b.get(key) {
+                        Some(other) => self.diff_value(&child, value, other, lines),
+                        None => self.hunk(&child, Some(value), None, lines),
+                    }
+                }
+                for (key, value) in b.iter().filter(|(key, _)| !a.contains_key(*key)) {
+                    self.hunk(&format!("{}.{}", path, key), None, Some(value), lines);
+                }
+            }
+            (Value::Array(a), Value::Array(b)) => {
+                for index in 0..a.len().max(b.len()) {
+                    let child = format!("{}[{}]", path, index);
+                    match (a.get(index), b.get(index)) {
+                        (Some(x), Some(y)) => self.diff_value(&child, x, y, lines),
+                        (x, y) => self.hunk(&child, x, y, lines),
+                    }
+                }
+            }
+            (a, b) if a != b => self.hunk(path, Some(a), Some(b), lines),
+            _ => {}
+        }
+    }
+
+    fn hunk(&self, path: &str, original: Option<&Value>, processed: Option<&Value>, lines: &mut Vec<String>) {
+        lines.push(format!("@@ {} @@", path));
+        if let Some(value) = original {
+            lines.push(format!("-{}", self.mask(value)));
+        }
+        if let Some(value) = processed {
+            lines.push(format!("+{}", self.mask(value)));
+        }
+    }
+
+    fn mask(&self, value: &Value) -> String {
+        let (kind, text) = match value {
+            Value::String(s) => ("string", s.clone()),
+            Value::Number(n) => ("number", n.to_string()),
+            Value::Bool(b) => ("bool", b.to_string()),
+            Value::Null => return " null".to_string(),
+            other => ("json", other.to_string()),
+        };
+        let hash = crypto::keyed_hash(&self.key, text.as_bytes());
+        format!(" {} h:{} ({} chars)", kind, &hash[..12], text.chars().count())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_diff_lists_changed_paths_without_plaintext() {
+        let mut diff = DebugDiff::new();
+        let original = json!({"result": {"content": [{"type": "text", "text": "Mail jane.roe@acme.com"}], "id": 7}});
+        let processed = json!({"result": {"content": [{"type": "text", "text": "Mail ella@example.org"}], "id": 7}});
+        diff.record_rule("email");
+        diff.record_rule("email");
+
+        let rendered = diff.render("id=1", &original, &processed);
+
+        assert!(rendered.contains("@@ $.result.content[0].text @@"));
+        assert!(!rendered.contains("$.result.id"));
+        assert!(!rendered.contains("jane.roe"));
+        assert!(!rendered.contains("ella@example.org"));
+        assert!(rendered.ends_with("# rules fired: email (2)"));
+
+        // Rules are per message
+        let rendered = diff.render("id=2", &original, &original);
+        assert!(rendered.ends_with("# rules fired: none"));
+    }
+
+    #[test]
+    fn test_equal_values_hash_equally_within_a_session() {
+        let diff = DebugDiff::new();
+        assert_eq!(diff.mask(&json!("secret")), diff.mask(&json!("secret")));
+        assert_ne!(diff.mask(&json!("secret")), DebugDiff::new().mask(&json!("secret")));
+    }
+}
@@ -0,0 +1,218 @@
+//! End-of-session account of traffic that was not concealed as configured
+//!
+//! The proxy keeps forwarding when parts of it fail: detection falls back to
+//! regex when the LLM is down, and messages that cannot be processed are
+//! forwarded unchanged. Each pipeline records which fallbacks touched each
+//! message here, and the proxy prints the totals on shutdown, so a session
+//! that looked fine can be seen to have leaked through a degraded path.
+
+use crate::i18n::Catalog;
+use crate::pipeline::Direction;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+/// A way a message was handled short of the configured detection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Degradation {
+    /// Regex-only detection while the LLM circuit was open
+    LlmCircuitOpen,
+    /// Regex-only detection because the LLM failed its health check
+    LlmUnavailable,
+    /// Regex-only detection because an LLM request failed
+    LlmFailed,
+    /// Regex-only detection for the rest of a message after it used up the
+    /// latency budget
+    LatencyBudget,
+    /// A string was left unchanged after an error processing it
+    FieldSkipped,
+    /// The whole message was forwarded unprocessed after an error
+    FailOpen,
+    /// The whole message was forwarded unprocessed after exceeding the message timeout
+    TimedOut,
+    /// The message was forwarded unprocessed under a bypass token
+    Bypassed,
+    /// The message was dropped after an error, under `on_error = "block"`
+    Withheld,
+    /// Every string was redacted after an error, under `on_error = "redact_all"`
+    RedactedAll,
+    /// The line was longer than `max_line_bytes` and dropped, truncated or
+    /// forwarded unprocessed
+    Oversized,
+    /// Original values were found in the message after replacement; see `leak_check`
+    Leaked,
+    /// A fake was detected and replaced again
+    DoubleAnonymized,
+}
+
+impl Degradation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Degradation::LlmCircuitOpen => "llm_circuit_open",
+            Degradation::LlmUnavailable => "llm_unavailable",
+            Degradation::LlmFailed => "llm_failed",
+            Degradation::LatencyBudget => "latency_budget",
+            Degradation::FieldSkipped => "field_skipped",
+            Degradation::FailOpen => "fail_open",
+            Degradation::TimedOut => "timed_out",
+            Degradation::Bypassed => "bypassed",
+            Degradation::Withheld => "withheld",
+            Degradation::RedactedAll => "redacted_all",
+            Degradation::Oversized => "oversized",
+            Degradation::Leaked => "leaked",
+            Degradation::DoubleAnonymized => "double_anonymized",
+        }
+    }
+
+    /// Whether data went through unprocessed, which `on_error` can prevent.
+    pub fn leaves_data_unprocessed(&self) -> bool {
+        matches!(self, Degradation::FieldSkipped | Degradation::FailOpen | Degradation::TimedOut)
+    }
+
+    /// Catalog key of the description shown in the summary.
+    pub fn message_key(&self) -> &'static str {
+        match self {
+            Degradation::LlmCircuitOpen => "degradation_llm_circuit_open",
+            Degradation::LlmUnavailable => "degradation_llm_unavailable",
+            Degradation::LlmFailed => "degradation_llm_failed",
+            Degradation::LatencyBudget => "degradation_latency_budget",
+            Degradation::FieldSkipped => "degradation_field_skipped",
+            Degradation::FailOpen => "degradation_fail_open",
+            Degradation::TimedOut => "degradation_timed_out",
+            Degradation::Bypassed => "degradation_bypassed",
+            Degradation::Withheld => "degradation_withheld",
+            Degradation::RedactedAll => "degradation_redacted_all",
+            Degradation::Oversized => "degradation_oversized",
+            Degradation::Leaked => "degradation_leaked",
+            Degradation::DoubleAnonymized => "degradation_double_anonymized",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Counts {
+    messages: BTreeMap<Direction, u64>,
+    degraded: BTreeMap<Direction, u64>,
+    by_kind: BTreeMap<(Degradation, Direction), u64>,
+}
+
+/// Session-wide degradation counts, shared by both proxy directions.
+#[derive(Clone, Default)]
+pub struct DegradationReport {
+    counts: Arc<Mutex<Counts>>,
+}
+
+impl DegradationReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records one message and every degradation that affected it.
+    pub fn record_message(&self, direction: Direction, degradations: &BTreeSet<Degradation>) {
+        let Ok(mut counts) = self.counts.lock() else {
+            return;
+        };
+        *counts.messages.entry(direction).or_default() += 1;
+        if degradations.is_empty() {
+            return;
+        }
+        *counts.degraded.entry(direction).or_default() += 1;
+        for kind in degradations {
+            *counts.by_kind.entry((*kind, direction)).or_default() += 1;
+        }
+    }
+
+    pub fn messages(&self) -> u64 {
+        self.counts.lock().map(|c| c.messages.values().sum()).unwrap_or(0)
+    }
+
+    pub fn degraded_messages(&self) -> u64 {
+        self.counts.lock().map(|c| c.degraded.values().sum()).unwrap_or(0)
+    }
+
+    /// Summary lines: a headline, then one line per degradation with its per-direction split.
+    pub fn summary(&self, catalog: &Catalog) -> Vec<String> {
+        let Ok(counts) = self.counts.lock() else {
+            return Vec::new();
+        };
+        let total: u64 = counts.messages.values().sum();
+        let degraded: u64 = counts.degraded.values().sum();
+        if degraded == 0 {
+            return vec![catalog.format("summary_clean", &[("total", &total)])];
+        }
+
+        let mut lines = vec![catalog.format("summary_degraded", &[("degraded", &degraded), ("total", &total)])];
+        let mut by_kind: BTreeMap<Degradation, Vec<(Direction, u64)>> = BTreeMap::new();
+        for ((kind, direction), n) in &counts.by_kind {
+            by_kind.entry(*kind).or_default().push((*direction, *n));
+        }
+        for (kind, split) in by_kind {
+            let sum: u64 = split.iter().map(|(_, n)| n).sum();
+            let split: Vec<String> = split
+                .iter()
+                .map(|(direction, n)| format!("{} {}", catalog.text(&format!("direction_{}", direction)), n))
+                .collect();
+            lines.push(format!("  {}: {} ({})", catalog.text(kind.message_key()), sum, split.join(", ")));
+        }
+        lines
+    }
+
+    /// Logs the summary; degraded sessions are logged as warnings.
+    pub fn log_summary(&self, catalog: &Catalog) {
+        let lines = self.summary(catalog);
+        if self.degraded_messages() == 0 {
+            lines.iter().for_each(|line| info!("{}", line));
+        } else {
+            lines.iter().for_each(|line| warn!("{}", line));
+        }
+    }
+
+    /// Counts for the audit trail.
+    pub fn to_json(&self) -> Value {
+        let Ok(counts) = self.counts.lock() else {
+            return Value::Null;
+        };
+        let mut by_kind = serde_json::Map::new();
+        for ((kind, direction), n) in &counts.by_kind {
+            let entry = by_kind.entry(kind.as_str()).or_insert_with(|| json!({}));
+            entry[direction.as_str()] = json!(n);
+        }
+        json!({
+            "messages": counts.messages.values().sum::<u64>(),
+            "degraded_messages": counts.degraded.values().sum::<u64>(),
+            "by_kind": by_kind,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_summary_counts_messages_once_per_kind() {
+        let report = DegradationReport::new();
+        report.record_message(Direction::Request, &BTreeSet::new());
+        report.record_message(Direction::Response, &BTreeSet::from([Degradation::LlmCircuitOpen, Degradation::FieldSkipped]));
+        report.record_message(Direction::Response, &BTreeSet::from([Degradation::LlmCircuitOpen]));
+        report.record_message(Direction::Request, &BTreeSet::from([Degradation::FailOpen]));
+
+        assert_eq!(report.messages(), 4);
+        assert_eq!(report.degraded_messages(), 3);
+
+        let summary = report.summary(&Catalog::default());
+        assert!(summary[0].contains("3 of 4 messages"));
+        assert!(summary.contains(&"  regex-only detection, LLM circuit open: 2 (response 2)".to_string()));
+        assert!(summary.contains(&"  forwarded unprocessed after an error: 1 (request 1)".to_string()));
+        assert_eq!(report.to_json()["by_kind"]["field_skipped"]["response"], 1);
+    }
+
+    #[test]
+    fn test_clean_session_summary() {
+        let report = DegradationReport::new();
+        report.record_message(Direction::Request, &BTreeSet::new());
+
+        assert_eq!(report.summary(&Catalog::default()), vec!["All 1 messages were processed with the configured detection".to_string()]);
+    }
+}
@@ -0,0 +1,519 @@
+//! Suggestions for entities the current rules miss
+//!
+//! With discovery enabled, a small sample of processed messages is sent back to
+//! the LLM with the question "which identifier-like values were not replaced?".
+//! Findings are generalized and counted in a local suggestion store:
+//! values containing digits become candidate regexes built from their shape
+//! (`EMP-004512` becomes `\bEMP-\d{6}\b`), names become dictionary candidates.
+//! `mcp-server-conceal suggest-patterns` lists them for the operator to approve
+//! or dismiss; nothing is added to the configuration automatically.
+//!
+//! Fake values inserted by the proxy are never reported as misses.
+
+use crate::config::{Config, DetectedEntity};
+use crate::config_format::ConfigFormat;
+use crate::detection::RegexDetectionEngine;
+use crate::ollama::OllamaClient;
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{debug, warn};
+
+/// Prompt asking the LLM for values that survived anonymization.
+pub const DISCOVERY_PROMPT: &str = "The TEXT below has already been anonymized: personal data was replaced \
+with realistic fake values. List values that still look like REAL identifiers or personal data that were \
+missed, such as employee or customer IDs, account numbers, names, or internal hostnames. \
+Do not list generic words, product names, or timestamps.\n\nTEXT: \"{text}\"\n\n\
+Return ONLY JSON: {\"entities\": [{\"type\": \"employee_id\", \"value\": \"EMP-004512\", \"confidence\": 0.8}]}. \
+Return {\"entities\": []} if nothing was missed.";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiscoveryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Fraction of processed messages checked, between 0.0 and 1.0
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    /// Suggestion store; relative paths live in the data directory
+    #[serde(default = "default_store_path")]
+    pub path: PathBuf,
+}
+
+impl Default for DiscoveryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sample_rate: default_sample_rate(),
+            path: default_store_path(),
+        }
+    }
+}
+
+fn default_sample_rate() -> f64 {
+    0.02
+}
+
+fn default_store_path() -> PathBuf {
+    PathBuf::from("suggestions.db")
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuggestionKind {
+    /// A regex generalized from the value's shape
+    Pattern,
+    /// A word for the name gazetteer
+    Dictionary,
+}
+
+impl SuggestionKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SuggestionKind::Pattern => "pattern",
+            SuggestionKind::Dictionary => "dictionary",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "dictionary" => SuggestionKind::Dictionary,
+            _ => SuggestionKind::Pattern,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub id: i64,
+    pub kind: SuggestionKind,
+    pub entity_type: String,
+    pub candidate: String,
+    pub occurrences: u64,
+    pub last_seen: u64,
+}
+
+/// Turns a missed value into a candidate: a shape regex if it contains digits,
+/// a dictionary word if it is a name, otherwise nothing.
+pub fn generalize(entity_type: &str, value: &str) -> Option<(SuggestionKind, String)> {
+    let value = value.trim();
+    if value.chars().count() < 3 {
+        return None;
+    }
+    if value.chars().any(|c| c.is_ascii_digit()) {
+        return Some((SuggestionKind::Pattern, shape_regex(value)));
+    }
+    let is_name = entity_type.contains("name") && value.chars().next().is_some_and(char::is_uppercase);
+    let first_word = value.split_whitespace().next()?;
+    if is_name && first_word.chars().all(char::is_alphabetic) {
+        return Some((SuggestionKind::Dictionary, first_word.to_string()));
+    }
+    None
+}
+
+/// Regex matching values of the same shape. A leading letter run is kept
+/// literally since it is usually a fixed prefix (`EMP`, `INV`).
+pub fn shape_regex(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    let mut regex = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let class = char_class(chars[i]);
+        let mut j = i;
+        while j < chars.len() && char_class(chars[j]) == class {
+            j += 1;
+        }
+        let run: String = chars[i..j].iter().collect();
+        let len = j - i;
+        match class {
+            CharClass::Digit => regex.push_str(&format!(r"\d{{{}}}", len)),
+            CharClass::Letter if i == 0 => regex.push_str(&regex::escape(&run)),
+            CharClass::Letter => {
+                let set = if run.chars().all(char::is_uppercase) {
+                    "[A-Z]"
+                } else if run.chars().all(char::is_lowercase) {
+                    "[a-z]"
+                } else {
+                    "[A-Za-z]"
+                };
+                regex.push_str(&format!("{}{{{}}}", set, len));
+            }
+            CharClass::Other => regex.push_str(&regex::escape(&run)),
+        }
+        i = j;
+    }
+
+    let starts_word = chars.first().is_some_and(|c| c.is_alphanumeric());
+    let ends_word = chars.last().is_some_and(|c| c.is_alphanumeric());
+    format!("{}{}{}", if starts_word { r"\b" } else { "" }, regex, if ends_word { r"\b" } else { "" })
+}
+
+#[derive(PartialEq, Eq, Clone, Copy)]
+enum CharClass {
+    Digit,
+    Letter,
+    Other,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_ascii_digit() {
+        CharClass::Digit
+    } else if c.is_alphabetic() {
+        CharClass::Letter
+    } else {
+        CharClass::Other
+    }
+}
+
+pub struct SuggestionStore {
+    conn: Connection,
+}
+
+impl SuggestionStore {
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn = if path == Path::new(":memory:") {
+            Connection::open_in_memory()?
+        } else {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            Connection::open(path)?
+        };
+        conn.busy_timeout(std::time::Duration::from_secs(5))?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS suggestions (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                kind TEXT NOT NULL,
+                entity_type TEXT NOT NULL,
+                candidate TEXT NOT NULL,
+                occurrences INTEGER NOT NULL DEFAULT 1,
+                first_seen INTEGER NOT NULL,
+                last_seen INTEGER NOT NULL,
+                UNIQUE(kind, entity_type, candidate)
+            )",
+            [],
+        )?;
+        Ok(Self { conn })
+    }
+
+    pub fn record(&mut self, kind: SuggestionKind, entity_type: &str, candidate: &str) -> Result<()> {
+        let now = now_secs();
+        self.conn.execute(
+            "INSERT INTO suggestions (kind, entity_type, candidate, first_seen, last_seen)
+             VALUES (?1, ?2, ?3, ?4, ?4)
+             ON CONFLICT(kind, entity_type, candidate)
+             DO UPDATE SET occurrences = occurrences + 1, last_seen = excluded.last_seen",
+            params![kind.as_str(), entity_type, candidate, now],
+        )?;
+        Ok(())
+    }
+
+    /// Suggestions seen at least `min_occurrences` times, most frequent first.
+    pub fn list(&self, min_occurrences: u64) -> Result<Vec<Suggestion>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, kind, entity_type, candidate, occurrences, last_seen FROM suggestions
+             WHERE occurrences >= ?1 ORDER BY occurrences DESC, id",
+        )?;
+        let rows = stmt.query_map(params![min_occurrences as i64], |row| {
+            Ok(Suggestion {
+                id: row.get(0)?,
+                kind: SuggestionKind::parse(&row.get::<_, String>(1)?),
+                entity_type: row.get(2)?,
+                candidate: row.get(3)?,
+                occurrences: row.get::<_, i64>(4)? as u64,
+                last_seen: row.get::<_, i64>(5)? as u64,
+            })
+        })?;
+        rows.collect::<rusqlite::Result<Vec<_>>>().map_err(Into::into)
+    }
+
+    pub fn get(&self, id: i64) -> Result<Option<Suggestion>> {
+        Ok(self.list(0)?.into_iter().find(|s| s.id == id))
+    }
+
+    pub fn remove(&mut self, id: i64) -> Result<bool> {
+        Ok(self.conn.execute("DELETE FROM suggestions WHERE id = ?1", params![id])? > 0)
+    }
+}
+
+/// Adds an approved suggestion to the configuration: patterns under
+/// `detection.patterns.<name>`, dictionary words to `detection.names.first_names`.
+/// Returns the pattern name or the word that was added.
+pub fn apply_suggestion(config: &mut Config, suggestion: &Suggestion, name: Option<&str>) -> Result<String> {
+    match suggestion.kind {
+        SuggestionKind::Pattern => {
+            regex::Regex::new(&suggestion.candidate)?;
+            let name = name.unwrap_or(&suggestion.entity_type).to_string();
+            if config.detection.patterns.contains_key(&name) {
+                return Err(anyhow::anyhow!("A pattern named '{}' already exists; choose another with --name", name));
+            }
+            config.detection.patterns.insert(name.clone(), suggestion.candidate.clone().into());
+            Ok(name)
+        }
+        SuggestionKind::Dictionary => {
+            let names = &mut config.detection.names;
+            if !names.first_names.iter().any(|n| n.eq_ignore_ascii_case(&suggestion.candidate)) {
+                names.first_names.push(suggestion.candidate.clone());
+            }
+            names.enabled = true;
+            Ok(suggestion.candidate.clone())
+        }
+    }
+}
+
+/// Writes an approved suggestion into the configuration file at `path`,
+/// leaving the rest of the file as written: comments, `preset`, profiles and
+/// unset defaults stay as they are. `effective` is the configuration as
+/// loaded, with presets and profile applied, and is used to check that the
+/// name is free and the result still valid before anything is written.
+/// Returns what [`apply_suggestion`] added.
+pub fn approve_in_file(path: &Path, effective: &Config, suggestion: &Suggestion, name: Option<&str>) -> Result<String> {
+    let mut checked = effective.clone();
+    let added = apply_suggestion(&mut checked, suggestion, name)?;
+    checked.validate()?;
+
+    let contents = std::fs::read_to_string(path)?;
+    let format = ConfigFormat::from_path(path);
+    let updated = match format {
+        ConfigFormat::Toml => {
+            let mut document: toml_edit::DocumentMut = contents.parse()
+                .map_err(|e| anyhow::anyhow!("Invalid TOML configuration: {}", e))?;
+            add_to_toml(&mut document, suggestion, name)?;
+            document.to_string()
+        }
+        ConfigFormat::Json | ConfigFormat::Yaml => {
+            let mut document: serde_json::Value = match format {
+                ConfigFormat::Json => serde_json::from_str(&contents)?,
+                _ => serde_yaml_ng::from_str(&contents)?,
+            };
+            if document.is_null() {
+                document = serde_json::json!({});
+            }
+            add_to_json(&mut document, suggestion, name)?;
+            format.render(&document)?
+        }
+    };
+    std::fs::write(path, updated)?;
+    Ok(added)
+}
+
+fn add_to_toml(document: &mut toml_edit::DocumentMut, suggestion: &Suggestion, name: Option<&str>) -> Result<()> {
+    let detection = toml_table(document.as_table_mut(), "detection")?;
+    match suggestion.kind {
+        SuggestionKind::Pattern => {
+            let name = name.unwrap_or(&suggestion.entity_type);
+            toml_table(detection, "patterns")?.insert(name, toml_edit::value(suggestion.candidate.as_str()));
+        }
+        SuggestionKind::Dictionary => {
+            let names = toml_table(detection, "names")?;
+            names.insert("enabled", toml_edit::value(true));
+            let first_names = names.entry("first_names")
+                .or_insert_with(|| toml_edit::value(toml_edit::Array::new()))
+                .as_array_mut()
+                .ok_or_else(|| anyhow::anyhow!("detection.names.first_names is not an array"))?;
+            if !first_names.iter().any(|n| n.as_str().is_some_and(|n| n.eq_ignore_ascii_case(&suggestion.candidate))) {
+                first_names.push(suggestion.candidate.as_str());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The table under `key`, created as a `[section]` header if it is missing.
+fn toml_table<'a>(parent: &'a mut toml_edit::Table, key: &str) -> Result<&'a mut toml_edit::Table> {
+    let item = parent.entry(key).or_insert_with(|| {
+        let mut table = toml_edit::Table::new();
+        table.set_implicit(true);
+        toml_edit::Item::Table(table)
+    });
+    if let Some(inline) = item.as_inline_table_mut() {
+        let table = std::mem::take(inline).into_table();
+        *item = toml_edit::Item::Table(table);
+    }
+    item.as_table_mut().ok_or_else(|| anyhow::anyhow!("'{}' in the configuration is not a table", key))
+}
+
+fn add_to_json(document: &mut serde_json::Value, suggestion: &Suggestion, name: Option<&str>) -> Result<()> {
+    let document = document.as_object_mut().ok_or_else(|| anyhow::anyhow!("The configuration is not an object"))?;
+    let detection = json_object(document, "detection")?;
+    match suggestion.kind {
+        SuggestionKind::Pattern => {
+            let name = name.unwrap_or(&suggestion.entity_type).to_string();
+            json_object(detection, "patterns")?.insert(name, suggestion.candidate.clone().into());
+        }
+        SuggestionKind::Dictionary => {
+            let names = json_object(detection, "names")?;
+            names.insert("enabled".to_string(), true.into());
+            let first_names = names.entry("first_names")
+                .or_insert_with(|| serde_json::json!([]))
+                .as_array_mut()
+                .ok_or_else(|| anyhow::anyhow!("detection.names.first_names is not an array"))?;
+            if !first_names.iter().any(|n| n.as_str().is_some_and(|n| n.eq_ignore_ascii_case(&suggestion.candidate))) {
+                first_names.push(suggestion.candidate.clone().into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The object under `key`, created if it is missing or null.
+fn json_object<'a>(parent: &'a mut serde_json::Map<String, serde_json::Value>, key: &str) -> Result<&'a mut serde_json::Map<String, serde_json::Value>> {
+    let child = parent.entry(key).or_insert_with(|| serde_json::json!({}));
+    if child.is_null() {
+        *child = serde_json::json!({});
+    }
+    child.as_object_mut().ok_or_else(|| anyhow::anyhow!("'{}' in the configuration is not an object", key))
+}
+
+/// Runs one sampled message through the discovery prompt and records what the
+/// LLM says was missed. `fakes` are the replacement values used in the message.
+pub async fn discover(
+    client: &OllamaClient,
+    detection_engine: &RegexDetectionEngine,
+    store_path: &Path,
+    texts: Vec<String>,
+    fakes: Vec<String>,
+) -> Result<usize> {
+    let mut findings: Vec<DetectedEntity> = Vec::new();
+    for text in &texts {
+        let missed = client.discover_unreplaced(text).await?;
+        // Values our own rules match would have been replaced already
+        let known = detection_engine.detect_in_text(text);
+        findings.extend(missed.into_iter().filter(|entity| {
+            !fakes.iter().any(|fake| fake.contains(&entity.original_value) || entity.original_value.contains(fake.as_str()))
+                && !known.iter().any(|k| k.start < entity.end && entity.start < k.end)
+        }));
+    }
+    if findings.is_empty() {
+        return Ok(0);
+    }
+
+    let mut store = SuggestionStore::open(store_path)?;
+    let mut recorded = 0;
+    for entity in findings {
+        if let Some((kind, candidate)) = generalize(&entity.entity_type, &entity.original_value) {
+            store.record(kind, &entity.entity_type, &candidate)?;
+            recorded += 1;
+        }
+    }
+    debug!("Discovery recorded {} suggestion(s)", recorded);
+    Ok(recorded)
+}
+
+/// Sampling state carried by a pipeline.
+pub struct Discovery {
+    pub(crate) config: DiscoveryConfig,
+    /// Replacement values used in the message being processed
+    pub(crate) fakes: Vec<String>,
+}
+
+impl Discovery {
+    pub fn new(config: &DiscoveryConfig) -> Option<Self> {
+        config.enabled.then(|| Self { config: config.clone(), fakes: Vec::new() })
+    }
+
+    pub fn should_sample(&self) -> bool {
+        rand::random::<f64>() < self.config.sample_rate
+    }
+
+    /// Checks a processed message in the background, off the forwarding path.
+    pub fn spawn(&mut self, client: OllamaClient, detection_engine: RegexDetectionEngine, texts: Vec<String>) {
+        let fakes = std::mem::take(&mut self.fakes);
+        let path = self.config.path.clone();
+        tokio::spawn(async move {
+            if let Err(e) = discover(&client, &detection_engine, &path, texts, fakes).await {
+                warn!("Entity discovery failed: {}", e);
+            }
+        });
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generalize_values() {
+        assert_eq!(shape_regex("EMP-004512"), r"\bEMP\-\d{6}\b");
+        assert_eq!(shape_regex("ab12CD"), r"\bab\d{2}[A-Z]{2}\b");
+        assert!(regex::Regex::new(&shape_regex("EMP-004512")).unwrap().is_match("id EMP-998877 ok"));
+
+        assert_eq!(generalize("person_name", "Siobhan Byrne"), Some((SuggestionKind::Dictionary, "Siobhan".to_string())));
+        assert_eq!(generalize("project", "Falcon"), None);
+        assert_eq!(generalize("id", "42"), None);
+    }
+
+    #[test]
+    fn test_store_aggregates_and_approval_updates_config() {
+        let mut store = SuggestionStore::open(Path::new(":memory:")).unwrap();
+        store.record(SuggestionKind::Pattern, "employee_id", r"\bEMP\-\d{6}\b").unwrap();
+        store.record(SuggestionKind::Pattern, "employee_id", r"\bEMP\-\d{6}\b").unwrap();
+        store.record(SuggestionKind::Dictionary, "person_name", "Siobhan").unwrap();
+
+        let frequent = store.list(2).unwrap();
+        assert_eq!(frequent.len(), 1);
+        assert_eq!(frequent[0].occurrences, 2);
+
+        let mut config = Config::default();
+        apply_suggestion(&mut config, &frequent[0], None).unwrap();
+        assert_eq!(config.detection.patterns["employee_id"].regex(), r"\bEMP\-\d{6}\b");
+        assert!(apply_suggestion(&mut config, &frequent[0], None).is_err());
+
+        let name = store.list(1).unwrap().into_iter().find(|s| s.kind == SuggestionKind::Dictionary).unwrap();
+        apply_suggestion(&mut config, &name, None).unwrap();
+        assert!(config.detection.names.enabled);
+        assert!(store.remove(name.id).unwrap());
+    }
+
+    fn suggestion(kind: SuggestionKind, entity_type: &str, candidate: &str) -> Suggestion {
+        let mut store = SuggestionStore::open(Path::new(":memory:")).unwrap();
+        store.record(kind, entity_type, candidate).unwrap();
+        store.list(1).unwrap().remove(0)
+    }
+
+    #[test]
+    fn test_approval_edits_the_file_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("conceal.toml");
+        let original = "# Support desk proxy\nconfig_version = 2\npreset = \"minimal\"\n\n[detection]\nmode = \"regex\" # no LLM here\n";
+        std::fs::write(&path, original).unwrap();
+        let effective = Config::load(&path, None, None).unwrap();
+
+        let pattern = suggestion(SuggestionKind::Pattern, "employee_id", r"\bEMP\-\d{6}\b");
+        approve_in_file(&path, &effective, &pattern, None).unwrap();
+        let name = suggestion(SuggestionKind::Dictionary, "person_name", "Siobhan");
+        approve_in_file(&path, &effective, &name, None).unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert!(written.starts_with(original), "{}", written);
+        assert!(!written.contains("[mapping]"), "defaults must not be written out: {}", written);
+        let reloaded = Config::load(&path, None, None).unwrap();
+        assert_eq!(reloaded.detection.patterns["employee_id"].regex(), r"\bEMP\-\d{6}\b");
+        assert!(reloaded.detection.names.enabled && reloaded.detection.names.first_names.contains(&"Siobhan".to_string()));
+
+        // Names taken by the preset are checked against the effective configuration
+        let taken = effective.detection.patterns.keys().next().unwrap().clone();
+        assert!(approve_in_file(&path, &effective, &pattern, Some(&taken)).is_err());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), written);
+    }
+
+    #[test]
+    fn test_approval_keeps_json_files_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("conceal.json");
+        std::fs::write(&path, r#"{"config_version": 2, "preset": "minimal", "detection": {"mode": "regex"}}"#).unwrap();
+        let effective = Config::load(&path, None, None).unwrap();
+
+        let pattern = suggestion(SuggestionKind::Pattern, "employee_id", r"\bEMP\-\d{6}\b");
+        assert_eq!(approve_in_file(&path, &effective, &pattern, Some("staff_id")).unwrap(), "staff_id");
+        let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(written, serde_json::json!({"config_version": 2, "preset": "minimal", "detection": {
+            "mode": "regex", "patterns": {"staff_id": r"\bEMP\-\d{6}\b"},
+        }}));
+    }
+}
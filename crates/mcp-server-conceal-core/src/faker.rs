@@ -1,23 +1,67 @@
 //! Fake data generation for PII anonymization
 
-use crate::config::{AnonymizedEntity, DetectedEntity, FakerConfig};
+use crate::api_keys;
+use crate::config::{base_entity_type, AnonymizationStrategy, AnonymizedEntity, Config, DetectedEntity, EmailDomainMode, FakerConfig};
+use crate::dates::{self, DateShiftConfig};
+use crate::crypto;
+use crate::fpe::FpeCipher;
+use crate::generalize;
+use crate::geo::{self, GeoFakerConfig};
+use crate::identity::{self, IdentityConfig, Persona};
+use crate::ip::{self, IpFakerConfig};
+use crate::key_material;
+use crate::language::LanguageConfig;
+use crate::phone;
+use crate::secrets;
 use anyhow::Result;
+use fake::faker::address::en::{BuildingNumber, CityName, StateAbbr, StreetName, ZipCode};
+use fake::faker::company::en::CompanyName;
 use fake::faker::internet::en::{SafeEmail, IP, DomainSuffix};
-use fake::faker::name::en::{FirstName, LastName};
+use fake::faker::name::raw::{FirstName, LastName};
+use fake::locales::{Data, AR_SA, EN, FR_FR, JA_JP, PT_BR, ZH_CN, ZH_TW};
 use fake::Fake;
 use rand::rngs::StdRng;
 use rand::{Rng, SeedableRng};
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use tracing::{debug, warn};
 use uuid::Uuid;
 
 #[derive(Clone)]
 pub struct FakerEngine {
     rng: StdRng,
-    locale: String,
-    consistency: bool,
+    locale: FakeLocale,
+    strategies: HashMap<String, AnonymizationStrategy>,
+    /// Last placeholder number per label when no mapping store is involved
+    token_counters: Arc<Mutex<HashMap<String, u64>>>,
+    /// Key for the `fpe` strategy
+    fpe: Option<Arc<FpeCipher>>,
+    /// HMAC key for the `hash` strategy; random per engine until the
+    /// passphrase's is loaded
+    hash_key: [u8; 32],
+    ip: IpFakerConfig,
+    /// Fake subnet per real subnet when no mapping store is involved
+    ip_subnets: Arc<Mutex<HashMap<String, String>>>,
+    email_domains: EmailDomainMode,
+    /// Fake domain per real domain when no mapping store is involved
+    domain_map: Arc<Mutex<HashMap<String, String>>>,
+    date_shift: DateShiftConfig,
+    /// Date offset in days when no mapping store is involved
+    date_offset: Arc<Mutex<Option<i64>>>,
+    geo: GeoFakerConfig,
+    identity: IdentityConfig,
+    /// Per-text locales by detected language, when enabled
+    languages: Option<LanguageConfig>,
+    /// Locale of the text being faked, overriding `locale`
+    text_locale: Option<FakeLocale>,
 }
 
+/// Derives the `hash` strategy's key from the mapping passphrase.
+const HASH_STRATEGY_KEY_CONTEXT: &[u8] = b"mcp-server-conceal hash strategy v1";
+
+/// Replacement used by the `redact` strategy.
+pub const REDACTED_TOKEN: &str = "[REDACTED]";
+
 impl FakerEngine {
     pub fn new(config: &FakerConfig) -> Self {
         let rng = if let Some(seed) = config.seed {
@@ -28,41 +72,383 @@ impl FakerEngine {
         
         Self {
             rng,
-            locale: config.locale.clone(),
-            consistency: config.consistency,
+            locale: FakeLocale::from_config(&config.locale),
+            strategies: config.strategies.clone(),
+            token_counters: Arc::new(Mutex::new(HashMap::new())),
+            fpe: None,
+            hash_key: rand::random(),
+            ip: config.ip.clone(),
+            ip_subnets: Arc::new(Mutex::new(HashMap::new())),
+            email_domains: config.email.domains,
+            domain_map: Arc::new(Mutex::new(HashMap::new())),
+            date_shift: config.date_shift.clone(),
+            date_offset: Arc::new(Mutex::new(None)),
+            geo: config.geo.clone(),
+            identity: config.identity.clone(),
+            languages: None,
+            text_locale: None,
+        }
+    }
+
+    /// Builds the engine for a full config, reading the mapping passphrase when
+    /// an entity type uses the `fpe` or `hash` strategy.
+    pub fn from_config(config: &Config) -> Result<Self> {
+        let mut engine = Self::new(&config.faker).with_languages(&config.language);
+        let uses = |strategy| engine.strategies.values().any(|s| *s == strategy);
+        let (fpe, hash) = (uses(AnonymizationStrategy::Fpe), uses(AnonymizationStrategy::Hash));
+        if !fpe && !hash {
+            return Ok(engine);
+        }
+        let passphrase = secrets::require_passphrase(&config.mapping).map_err(|e| {
+            anyhow::anyhow!("The {} strategy needs the mapping passphrase: {}", if fpe { "fpe" } else { "hash" }, e)
+        })?;
+        if fpe {
+            engine = engine.with_fpe(FpeCipher::from_passphrase(&passphrase));
         }
+        if hash {
+            engine = engine.with_hash_key(crypto::derive_key(&passphrase, HASH_STRATEGY_KEY_CONTEXT));
+        }
+        Ok(engine)
+    }
+
+    /// Picks the locale per text from its detected language, when `[language]`
+    /// is enabled.
+    pub fn with_languages(mut self, config: &LanguageConfig) -> Self {
+        self.languages = Some(config.clone()).filter(|config| config.enabled);
+        self
+    }
+
+    /// Fakes the next entities in the locale of the language `text` is
+    /// written in, or the configured locale when that cannot be told.
+    pub fn adapt_to(&mut self, text: &str) {
+        self.text_locale = self.languages.as_ref()
+            .and_then(|languages| languages.locale_for(text))
+            .and_then(FakeLocale::parse);
+    }
+
+    fn current_locale(&self) -> FakeLocale {
+        self.text_locale.unwrap_or(self.locale)
+    }
+
+    pub fn with_fpe(mut self, cipher: FpeCipher) -> Self {
+        self.fpe = Some(Arc::new(cipher));
+        self
+    }
+
+    pub fn with_hash_key(mut self, key: [u8; 32]) -> Self {
+        self.hash_key = key;
+        self
+    }
+
+    /// Strategy configured for an entity type, keyed by its base type.
+    pub fn strategy_for(&self, entity_type: &str) -> AnonymizationStrategy {
+        self.strategies.get(&self.extract_base_type(entity_type)).copied().unwrap_or_default()
     }
 
     pub fn anonymize_entity(&mut self, detected: &DetectedEntity) -> Result<AnonymizedEntity> {
         let entity_type = self.extract_base_type(&detected.entity_type);
         
-        let fake_value = match entity_type.as_str() {
-            "email" => self.generate_fake_email(),
-            "phone" => self.generate_fake_phone(),
-            "ssn" => self.generate_fake_ssn(),
-            "name" => self.generate_fake_name(),
-            "ip_address" => self.generate_fake_ip(),
-            "hostname" => self.generate_fake_hostname(),
-            "node_name" => self.generate_fake_node_name(),
-            _ => {
-                warn!("Unknown entity type '{}', using generic replacement", entity_type);
-                format!("REDACTED_{}", entity_type.to_uppercase())
+        let fake_value = match self.strategy_for(&entity_type) {
+            AnonymizationStrategy::Fake if entity_type == "email" => self.fake_email(&detected.original_value, None),
+            AnonymizationStrategy::Fake if entity_type == phone::PHONE_TYPE && detected.region.is_some() => {
+                let region = detected.region.as_deref().unwrap_or_default();
+                phone::fake_number(region, &detected.original_value, &mut self.rng)
+                    .unwrap_or_else(|| self.generate_fake_phone())
+            }
+            AnonymizationStrategy::Fake if entity_type == "credit_card" => self.fake_credit_card(&detected.original_value),
+            AnonymizationStrategy::Fake if entity_type == "mac_address" => self.fake_mac(&detected.original_value),
+            AnonymizationStrategy::Fake if entity_type == geo::COORDINATES_TYPE => geo::fake(&detected.original_value, &mut self.rng),
+            AnonymizationStrategy::Fake if matches!(entity_type.as_str(), "date_of_birth" | "dob") => {
+                self.fake_date_of_birth(&detected.original_value)
+            }
+            AnonymizationStrategy::Fake if api_keys::is_key_type(&entity_type) => {
+                api_keys::fake_key(&detected.original_value, &mut self.rng)
+            }
+            AnonymizationStrategy::Fake => self.generate_fake(&entity_type),
+            AnonymizationStrategy::Mask => mask_value(&detected.original_value),
+            AnonymizationStrategy::Redact => REDACTED_TOKEN.to_string(),
+            AnonymizationStrategy::Hash => hash_value(&self.hash_key, &detected.original_value),
+            AnonymizationStrategy::Tokenize => {
+                let label = placeholder_label(&entity_type);
+                let number = self.next_token_number(&label);
+                placeholder(&label, number)
+            }
+            AnonymizationStrategy::Fpe => self.encrypt_digits(&entity_type, &detected.original_value),
+            AnonymizationStrategy::Ip => {
+                let fake_subnet = self.ip_subnet(&detected.original_value)
+                    .and_then(|subnet| self.local_fake_subnet(&subnet));
+                self.fake_ip(&entity_type, &detected.original_value, fake_subnet.as_deref())
+            }
+            AnonymizationStrategy::DateShift => {
+                let offset = self.local_date_offset();
+                self.shift_date(&entity_type, &detected.original_value, offset)
+            }
+            AnonymizationStrategy::Generalize => {
+                generalize::generalize(&entity_type, &detected.original_value).unwrap_or_else(|| {
+                    warn!("No generalization for '{}' value, using a fake instead", entity_type);
+                    self.generate_fake(&entity_type)
+                })
+            }
+            AnonymizationStrategy::GeoJitter => {
+                let moved = geo::jitter(&detected.original_value, self.geo.jitter_km, &mut self.rng);
+                self.generalized_point(&entity_type, moved)
+            }
+            AnonymizationStrategy::GeoCity => {
+                let city = geo::snap_to_city(&detected.original_value);
+                self.generalized_point(&entity_type, city)
+            }
+            AnonymizationStrategy::GeoTruncate => {
+                let truncated = geo::truncate(&detected.original_value, self.geo.precision);
+                self.generalized_point(&entity_type, truncated)
             }
         };
 
+        Ok(self.anonymized(detected, &entity_type, fake_value))
+    }
+
+    /// Replaces an entity with a placeholder whose number was reserved elsewhere,
+    /// normally from the mapping store so numbering survives restarts.
+    pub fn anonymize_as_placeholder(&self, detected: &DetectedEntity, number: u64) -> AnonymizedEntity {
+        let entity_type = self.extract_base_type(&detected.entity_type);
+        let fake_value = placeholder(&placeholder_label(&entity_type), number);
+        self.anonymized(detected, &entity_type, fake_value)
+    }
+
+    /// Real subnet whose fake the `ip` strategy keeps consistent, e.g. `10.1.2.0/24`.
+    pub fn ip_subnet(&self, value: &str) -> Option<String> {
+        ip::subnet_of(value, &self.ip)
+    }
+
+    /// A fake subnet in the same private/public block as `subnet`.
+    pub fn generate_fake_subnet(&mut self, subnet: &str) -> Result<String> {
+        ip::fake_subnet(subnet, &mut self.rng)
+    }
+
+    /// Replaces an IP address inside a fake subnet chosen elsewhere, normally
+    /// from the mapping store so the subnet mapping survives restarts.
+    pub fn anonymize_ip_in_subnet(&mut self, detected: &DetectedEntity, fake_subnet: &str) -> AnonymizedEntity {
+        let entity_type = self.extract_base_type(&detected.entity_type);
+        let fake_value = self.fake_ip(&entity_type, &detected.original_value, Some(fake_subnet));
+        self.anonymized(detected, &entity_type, fake_value)
+    }
+
+    /// Random offset for the `date_shift` strategy, to be shared by every date.
+    pub fn generate_date_offset(&mut self) -> i64 {
+        self.date_shift.random_offset(&mut self.rng)
+    }
+
+    /// Shifts a date by an offset chosen elsewhere, normally from the mapping store.
+    pub fn anonymize_date_shifted(&mut self, detected: &DetectedEntity, offset: i64) -> AnonymizedEntity {
+        let entity_type = self.extract_base_type(&detected.entity_type);
+        let fake_value = self.shift_date(&entity_type, &detected.original_value, offset);
+        self.anonymized(detected, &entity_type, fake_value)
+    }
+
+    fn shift_date(&mut self, entity_type: &str, value: &str, offset: i64) -> String {
+        dates::shift(value, offset).unwrap_or_else(|| {
+            warn!("Cannot parse '{}' value as a date, using a fake instead", entity_type);
+            self.generate_fake(entity_type)
+        })
+    }
+
+    fn generalized_point(&mut self, entity_type: &str, point: Option<String>) -> String {
+        point.unwrap_or_else(|| {
+            warn!("Cannot parse '{}' value as coordinates, using a fake instead", entity_type);
+            self.generate_fake(entity_type)
+        })
+    }
+
+    // Used without a mapping store; the offset lasts for the process
+    fn local_date_offset(&mut self) -> i64 {
+        let offset = self.date_offset.lock().ok().and_then(|offset| *offset);
+        offset.unwrap_or_else(|| {
+            let offset = self.generate_date_offset();
+            if let Ok(mut stored) = self.date_offset.lock() {
+                *stored.get_or_insert(offset)
+            } else {
+                offset
+            }
+        })
+    }
+
+    /// Lower-cased domain of an email whose fake needs a consistently mapped
+    /// domain (`faker.email.domains = "map"`), for the caller to look up.
+    pub fn email_domain_to_map(&self, entity_type: &str, value: &str) -> Option<String> {
+        let mapped = self.email_domains == EmailDomainMode::Map
+            && self.extract_base_type(entity_type) == "email"
+            && self.strategy_for(entity_type) == AnonymizationStrategy::Fake;
+        mapped.then(|| email_domain(value)).flatten().map(|domain| domain.to_lowercase())
+    }
+
+    /// A fake domain under the reserved `.example` top-level domain.
+    pub fn generate_fake_domain(&mut self) -> String {
+        let (_, last) = self.first_last(EN);
+        format!("{}-{:02}.example", ascii_fold(&last), self.rng.gen_range(1..100))
+    }
+
+    /// Replaces an email address with a fake user at a fake domain chosen
+    /// elsewhere, normally from the mapping store.
+    pub fn anonymize_email_in_domain(&mut self, detected: &DetectedEntity, fake_domain: &str) -> AnonymizedEntity {
+        let entity_type = self.extract_base_type(&detected.entity_type);
+        let fake_value = self.fake_email(&detected.original_value, Some(fake_domain));
+        self.anonymized(detected, &entity_type, fake_value)
+    }
+
+    /// Identity linking settings, when enabled.
+    pub fn identity(&self) -> Option<&IdentityConfig> {
+        Some(&self.identity).filter(|config| config.enabled)
+    }
+
+    /// Replaces a name or email address of an identity cluster: the name with
+    /// the persona's fake name, the email with an address made from it. A
+    /// non-zero `attempt` numbers the address, for when the plain one is
+    /// already another address's fake.
+    pub fn anonymize_as_persona(&mut self, detected: &DetectedEntity, persona: &Persona, fake_domain: Option<&str>, attempt: u32) -> AnonymizedEntity {
+        let entity_type = self.extract_base_type(&detected.entity_type);
+        let fake_value = if entity_type == identity::EMAIL_TYPE {
+            let user: Vec<_> = persona.fake_name.split_whitespace().map(ascii_fold).filter(|part| !part.is_empty()).collect();
+            if user.is_empty() {
+                self.fake_email(&detected.original_value, fake_domain)
+            } else {
+                let domain = ["com", "net", "org"][self.rng.gen_range(0..3)];
+                let number = if attempt > 0 { (attempt + 1).to_string() } else { String::new() };
+                let generated = format!("{}{}@example.{}", user.join("."), number, domain);
+                self.email_at_domain(&detected.original_value, generated, fake_domain)
+            }
+        } else {
+            persona.fake_name.clone()
+        };
+        self.anonymized(detected, &entity_type, fake_value)
+    }
+
+    fn fake_email(&mut self, original: &str, fake_domain: Option<&str>) -> String {
+        let generated = self.generate_fake_email();
+        self.email_at_domain(original, generated, fake_domain)
+    }
+
+    /// Applies the domain mode to a generated fake address, keeping its user.
+    fn email_at_domain(&mut self, original: &str, generated: String, fake_domain: Option<&str>) -> String {
+        let Some(domain) = email_domain(original) else {
+            return generated;
+        };
+        let user = generated.split('@').next().unwrap_or_default().to_string();
+        match self.email_domains {
+            EmailDomainMode::Fake => generated,
+            EmailDomainMode::Keep => format!("{}@{}", user, domain),
+            EmailDomainMode::Map => {
+                let fake_domain = match fake_domain {
+                    Some(fake_domain) => fake_domain.to_string(),
+                    None => self.local_fake_domain(&domain.to_lowercase()),
+                };
+                format!("{}@{}", user, fake_domain)
+            }
+        }
+    }
+
+    // Used without a mapping store; domain mappings last for the process
+    fn local_fake_domain(&mut self, domain: &str) -> String {
+        if let Some(known) = self.domain_map.lock().ok().and_then(|map| map.get(domain).cloned()) {
+            return known;
+        }
+        let fake = self.generate_fake_domain();
+        if let Ok(mut map) = self.domain_map.lock() {
+            map.insert(domain.to_string(), fake.clone());
+        }
+        fake
+    }
+
+    // Used without a mapping store; subnet mappings last for the process
+    fn local_fake_subnet(&mut self, subnet: &str) -> Option<String> {
+        let known = self.ip_subnets.lock().ok()?.get(subnet).cloned();
+        if known.is_some() {
+            return known;
+        }
+        let fake = self.generate_fake_subnet(subnet).ok()?;
+        self.ip_subnets.lock().ok()?.insert(subnet.to_string(), fake.clone());
+        Some(fake)
+    }
+
+    fn fake_ip(&mut self, entity_type: &str, value: &str, fake_subnet: Option<&str>) -> String {
+        match ip::fake_address(value, fake_subnet, &mut self.rng) {
+            Some(fake) => fake,
+            None => self.generate_fake(entity_type),
+        }
+    }
+
+    fn anonymized(&self, detected: &DetectedEntity, entity_type: &str, fake_value: String) -> AnonymizedEntity {
         let mapping_id = Uuid::new_v4().to_string();
         
         debug!("Generated fake '{}' for entity type '{}': {} -> {}", 
                mapping_id, entity_type, detected.original_value, fake_value);
 
-        Ok(AnonymizedEntity {
+        AnonymizedEntity {
             entity_type: detected.entity_type.clone(),
             original_value: detected.original_value.clone(),
             fake_value,
             mapping_id,
+        }
+    }
+
+    fn generate_fake(&mut self, entity_type: &str) -> String {
+        match entity_type {
+            "email" => self.generate_fake_email(),
+            "phone" => self.generate_fake_phone(),
+            "ssn" => self.generate_fake_ssn(),
+            "name" => self.generate_fake_name(),
+            "ip_address" => self.generate_fake_ip(),
+            "hostname" => self.generate_fake_hostname(),
+            "node_name" => self.generate_fake_node_name(),
+            "address" | "street_address" | "postal_address" => self.generate_fake_address(),
+            "zip_code" | "zip" | "postal_code" | "postcode" => ZipCode().fake_with_rng(&mut self.rng),
+            "organization" | "company" => CompanyName().fake_with_rng(&mut self.rng),
+            "credit_card" => self.generate_fake_credit_card(),
+            "mac_address" => self.generate_fake_mac(),
+            geo::COORDINATES_TYPE => geo::fake("", &mut self.rng),
+            "iban" => self.generate_fake_iban(),
+            "date_of_birth" | "dob" => self.generate_fake_date_of_birth(),
+            // A fake credential would look real to whoever reads it
+            "secret" => "REDACTED_SECRET".to_string(),
+            _ => match key_material::placeholder(entity_type) {
+                Some(placeholder) => placeholder.to_string(),
+                None => {
+                    warn!("Unknown entity type '{}', using generic replacement", entity_type);
+                    format!("REDACTED_{}", entity_type.to_uppercase())
+                }
+            },
+        }
+    }
+
+    /// Whether the `fpe` strategy encrypts `value` rather than falling back to
+    /// a random fake, which has to be stored to stay consistent.
+    pub(crate) fn can_encrypt(&self, value: &str) -> bool {
+        let digits = value.bytes().filter(u8::is_ascii_digit).count();
+        self.fpe.is_some() && (crate::fpe::MIN_DIGITS..=crate::fpe::MAX_DIGITS).contains(&digits)
+    }
+
+    // Values FF3-1 cannot take (too few digits) still need a replacement
+    fn encrypt_digits(&mut self, entity_type: &str, value: &str) -> String {
+        let encrypted = match &self.fpe {
+            Some(cipher) => cipher.encrypt_value(entity_type, value),
+            None => Err(anyhow::anyhow!("no FPE key loaded")),
+        };
+        encrypted.unwrap_or_else(|e| {
+            warn!("Cannot encrypt '{}' value, using a fake instead: {}", entity_type, e);
+            self.generate_fake(entity_type)
         })
     }
 
+    // Used without a mapping store; numbering restarts with the process
+    fn next_token_number(&self, label: &str) -> u64 {
+        match self.token_counters.lock() {
+            Ok(mut counters) => {
+                let counter = counters.entry(label.to_string()).or_default();
+                *counter += 1;
+                *counter
+            }
+            Err(_) => 0,
+        }
+    }
+
     pub fn anonymize_entities(&mut self, detected_entities: Vec<DetectedEntity>) -> Result<Vec<AnonymizedEntity>> {
         detected_entities.into_iter()
             .map(|entity| self.anonymize_entity(&entity))
@@ -70,11 +456,21 @@ impl FakerEngine {
     }
 
     fn extract_base_type(&self, entity_type: &str) -> String {
-        entity_type.split('@').next().unwrap_or(entity_type).to_string()
+        base_entity_type(entity_type).to_string()
     }
 
     fn generate_fake_email(&mut self) -> String {
-        SafeEmail().fake_with_rng(&mut self.rng)
+        if self.current_locale() == FakeLocale::En {
+            return SafeEmail().fake_with_rng(&mut self.rng);
+        }
+        // Addresses are ASCII: accents are folded, and scripts that do not fold
+        // (Japanese, Chinese, Arabic) use English names
+        let (first, last) = match self.locale_first_last() {
+            (first, last) if !ascii_fold(&first).is_empty() && !ascii_fold(&last).is_empty() => (first, last),
+            _ => self.first_last(EN),
+        };
+        let domain = ["com", "net", "org"][self.rng.gen_range(0..3)];
+        format!("{}.{}@example.{}", ascii_fold(&first), ascii_fold(&last), domain)
     }
 
     fn generate_fake_phone(&mut self) -> String {
@@ -92,10 +488,33 @@ impl FakerEngine {
             self.rng.gen_range(1000..9999))
     }
 
-    fn generate_fake_name(&mut self) -> String {
-        let first: String = FirstName().fake_with_rng(&mut self.rng);
-        let last: String = LastName().fake_with_rng(&mut self.rng);
-        format!("{} {}", first, last)
+    pub fn generate_fake_name(&mut self) -> String {
+        let (first, last) = self.locale_first_last();
+        match self.current_locale() {
+            FakeLocale::Ja => format!("{} {}", last, first),
+            FakeLocale::ZhCn | FakeLocale::ZhTw => format!("{}{}", last, first),
+            _ => format!("{} {}", first, last),
+        }
+    }
+
+    fn locale_first_last(&mut self) -> (String, String) {
+        match self.current_locale() {
+            FakeLocale::En => self.first_last(EN),
+            FakeLocale::Fr => self.first_last(FR_FR),
+            FakeLocale::De => (
+                GERMAN_FIRST_NAMES[self.rng.gen_range(0..GERMAN_FIRST_NAMES.len())].to_string(),
+                GERMAN_LAST_NAMES[self.rng.gen_range(0..GERMAN_LAST_NAMES.len())].to_string(),
+            ),
+            FakeLocale::Ja => self.first_last(JA_JP),
+            FakeLocale::PtBr => self.first_last(PT_BR),
+            FakeLocale::ZhCn => self.first_last(ZH_CN),
+            FakeLocale::ZhTw => self.first_last(ZH_TW),
+            FakeLocale::Ar => self.first_last(AR_SA),
+        }
+    }
+
+    fn first_last<L: Data + Copy>(&mut self, locale: L) -> (String, String) {
+        (FirstName(locale).fake_with_rng(&mut self.rng), LastName(locale).fake_with_rng(&mut self.rng))
     }
 
     fn generate_fake_ip(&mut self) -> String {
@@ -127,6 +546,115 @@ impl FakerEngine {
         }
     }
 
+    fn generate_fake_address(&mut self) -> String {
+        let number: String = BuildingNumber().fake_with_rng(&mut self.rng);
+        let street: String = StreetName().fake_with_rng(&mut self.rng);
+        let city: String = CityName().fake_with_rng(&mut self.rng);
+        let state: String = StateAbbr().fake_with_rng(&mut self.rng);
+        let zip: String = ZipCode().fake_with_rng(&mut self.rng);
+        format!("{} {}, {}, {} {}", number, street, city, state, zip)
+    }
+
+    // Visa-shaped and Luhn-valid, so it passes client-side validation
+    fn generate_fake_credit_card(&mut self) -> String {
+        let mut digits: Vec<u32> = std::iter::once(4)
+            .chain((0..14).map(|_| self.rng.gen_range(0..10)))
+            .collect();
+        digits.push(luhn_check_digit(&digits));
+        digits.chunks(4)
+            .map(|group| group.iter().map(|d| char::from_digit(*d, 10).unwrap_or('0')).collect::<String>())
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+
+    /// A Luhn-valid number of the same network and length as `original`,
+    /// with its separators in the same places.
+    fn fake_credit_card(&mut self, original: &str) -> String {
+        let digits: Vec<u32> = original.chars().filter_map(|c| c.to_digit(10)).collect();
+        if !(12..=19).contains(&digits.len()) {
+            return self.generate_fake_credit_card();
+        }
+        let keep = network_prefix_len(&digits);
+        let fake = loop {
+            let mut fake = digits[..keep].to_vec();
+            fake.extend((keep..digits.len() - 1).map(|_| self.rng.gen_range(0..10)));
+            fake.push(luhn_check_digit(&fake));
+            if fake != digits {
+                break fake;
+            }
+        };
+        let mut fake = fake.into_iter();
+        original.chars()
+            .map(|c| match c.is_ascii_digit() {
+                true => fake.next().and_then(|d| char::from_digit(d, 10)).unwrap_or('0'),
+                false => c,
+            })
+            .collect()
+    }
+
+    // Locally administered unicast, so it cannot belong to a real vendor
+    fn generate_fake_mac(&mut self) -> String {
+        std::iter::once(0x02u8)
+            .chain((0..5).map(|_| self.rng.gen()))
+            .map(|octet| format!("{:02x}", octet))
+            .collect::<Vec<_>>()
+            .join(":")
+    }
+
+    /// The vendor OUI (first three octets) of `original` with a random device
+    /// part, in the same notation and letter case.
+    fn fake_mac(&mut self, original: &str) -> String {
+        let hex: Vec<char> = original.chars().filter(char::is_ascii_hexdigit).collect();
+        if hex.len() != 12 {
+            return self.generate_fake_mac();
+        }
+        let uppercase = original.chars().any(|c| c.is_ascii_uppercase());
+        let fake = loop {
+            let mut fake = hex[..6].to_vec();
+            fake.extend((0..6).map(|_| char::from_digit(self.rng.gen_range(0..16), 16).unwrap_or('0')));
+            if !fake.iter().zip(&hex).all(|(a, b)| a.eq_ignore_ascii_case(b)) {
+                break fake;
+            }
+        };
+        let mut fake = fake.into_iter();
+        original.chars()
+            .map(|c| match c.is_ascii_hexdigit() {
+                true => fake.next().map(|d| if uppercase { d.to_ascii_uppercase() } else { d }).unwrap_or('0'),
+                false => c,
+            })
+            .collect()
+    }
+
+    // German layout (8-digit bank code, 10-digit account) with valid check digits
+    fn generate_fake_iban(&mut self) -> String {
+        let bban: String = (0..18).map(|_| char::from(b'0' + self.rng.gen_range(0..10u8))).collect();
+        let iban = format!("DE{:02}{}", iban_check_digits("DE", &bban), bban);
+        iban.as_bytes()
+            .chunks(4)
+            .map(|group| String::from_utf8_lossy(group).into_owned())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// A random date of birth in place of the first date in `original`,
+    /// keeping the words around it ("DOB: 03/15/1971" keeps "DOB: ").
+    fn fake_date_of_birth(&mut self, original: &str) -> String {
+        let fake = self.generate_fake_date_of_birth();
+        let Some(start) = original.find(|c: char| c.is_ascii_digit()) else {
+            return fake;
+        };
+        match dates::year(&original[start..]) {
+            Some((_, len)) => format!("{}{}{}", &original[..start], fake, &original[start + len..]),
+            None => fake,
+        }
+    }
+
+    // An adult between 18 and 90, as YYYY-MM-DD
+    fn generate_fake_date_of_birth(&mut self) -> String {
+        let year = dates::current_year() - self.rng.gen_range(18..=90);
+        format!("{}-{:02}-{:02}", year, self.rng.gen_range(1..=12), self.rng.gen_range(1..=28))
+    }
+
     pub fn create_replacement_map(&mut self, detected_entities: Vec<DetectedEntity>) -> Result<HashMap<String, String>> {
         let mut replacement_map = HashMap::new();
         
@@ -139,16 +667,172 @@ impl FakerEngine {
     }
 }
 
+/// Language of generated names and email addresses, from `faker.locale`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FakeLocale {
+    En,
+    Fr,
+    De,
+    Ja,
+    PtBr,
+    ZhCn,
+    ZhTw,
+    Ar,
+}
+
+impl FakeLocale {
+    /// Parses `en_US`, `fr-FR`, `de`, `pt_BR`, `zh_TW` and similar tags by language,
+    /// and by region only where the script differs (Chinese).
+    pub fn parse(locale: &str) -> Option<Self> {
+        let tag = locale.trim().to_ascii_lowercase().replace('-', "_");
+        let (language, region) = tag.split_once('_').unwrap_or((&tag, ""));
+        match (language, region) {
+            ("en", _) => Some(FakeLocale::En),
+            ("fr", _) => Some(FakeLocale::Fr),
+            ("de", _) => Some(FakeLocale::De),
+            ("ja", _) => Some(FakeLocale::Ja),
+            ("pt", _) => Some(FakeLocale::PtBr),
+            ("zh", "tw" | "hk" | "hant") => Some(FakeLocale::ZhTw),
+            ("zh", _) => Some(FakeLocale::ZhCn),
+            ("ar", _) => Some(FakeLocale::Ar),
+            _ => None,
+        }
+    }
+
+    fn from_config(locale: &str) -> Self {
+        Self::parse(locale).unwrap_or_else(|| {
+            warn!("Unsupported faker locale '{}', generating English fakes", locale);
+            FakeLocale::En
+        })
+    }
+}
+
+// The fake crate has no German locale
+const GERMAN_FIRST_NAMES: &[&str] = &[
+    "Anna", "Ben", "Clara", "David", "Emma", "Felix", "Greta", "Hannah", "Jonas", "Julia",
+    "Karl", "Lena", "Lukas", "Marie", "Maximilian", "Mia", "Niklas", "Paul", "Sophie", "Tobias",
+];
+
+const GERMAN_LAST_NAMES: &[&str] = &[
+    "Bauer", "Becker", "Fischer", "Hoffmann", "Koch", "Krüger", "Lange", "Meyer", "Müller", "Neumann",
+    "Richter", "Schäfer", "Schmidt", "Schneider", "Schulz", "Schwarz", "Wagner", "Weber", "Wolf", "Zimmermann",
+];
+
+/// Number of leading digits that identify a card's network (IIN ranges of
+/// Visa, Mastercard, American Express, Discover, Diners Club, JCB, UnionPay).
+fn network_prefix_len(digits: &[u32]) -> usize {
+    let prefix = |n: usize| digits.iter().take(n).fold(0, |acc, d| acc * 10 + d);
+    match (prefix(2), prefix(3), prefix(4)) {
+        (_, _, 2221..=2720 | 3528..=3589 | 6011) => 4,
+        (_, 300..=305 | 644..=649, _) => 3,
+        (34 | 36..=39 | 51..=55 | 62 | 65, _, _) => 2,
+        _ => 1,
+    }
+}
+
+/// Digit that makes `digits` followed by it pass the Luhn check.
+fn luhn_check_digit(digits: &[u32]) -> u32 {
+    let sum: u32 = digits.iter().rev().enumerate()
+        .map(|(i, &d)| match i % 2 {
+            0 => if d * 2 > 9 { d * 2 - 9 } else { d * 2 },
+            _ => d,
+        })
+        .sum();
+    (10 - sum % 10) % 10
+}
+
+/// ISO 13616 check digits: 98 minus the mod-97 remainder of the rearranged IBAN.
+fn iban_check_digits(country: &str, bban: &str) -> u32 {
+    let rearranged = format!("{}{}00", bban, country);
+    let remainder = rearranged.chars().fold(0u32, |acc, c| {
+        let value = c.to_digit(36).unwrap_or(0);
+        let width = if value > 9 { 100 } else { 10 };
+        (acc * width + value) % 97
+    });
+    98 - remainder
+}
+
+fn email_domain(value: &str) -> Option<&str> {
+    value.rsplit_once('@').map(|(_, domain)| domain).filter(|domain| !domain.is_empty())
+}
+
+/// Lower-case ASCII rendering of a name for email addresses: accents are
+/// folded (`Müller` → `mueller`, `José` → `jose`), other characters dropped.
+fn ascii_fold(name: &str) -> String {
+    let mut folded = String::with_capacity(name.len());
+    for c in name.chars().flat_map(char::to_lowercase) {
+        match c {
+            'a'..='z' | '0'..='9' => folded.push(c),
+            'à' | 'á' | 'â' | 'ã' | 'å' => folded.push('a'),
+            'ä' | 'æ' => folded.push_str("ae"),
+            'ç' => folded.push('c'),
+            'è' | 'é' | 'ê' | 'ë' => folded.push('e'),
+            'ì' | 'í' | 'î' | 'ï' => folded.push('i'),
+            'ñ' => folded.push('n'),
+            'ò' | 'ó' | 'ô' | 'õ' => folded.push('o'),
+            'ö' | 'œ' => folded.push_str("oe"),
+            'ù' | 'ú' | 'û' => folded.push('u'),
+            'ü' => folded.push_str("ue"),
+            'ý' | 'ÿ' => folded.push('y'),
+            'ß' => folded.push_str("ss"),
+            _ => {}
+        }
+    }
+    folded
+}
+
+/// Placeholder label for an entity type: `name` reads as `PERSON`, other
+/// types are upper-cased (`email` → `EMAIL`).
+pub fn placeholder_label(entity_type: &str) -> String {
+    let base = base_entity_type(entity_type);
+    match base {
+        "name" | "person" => "PERSON".to_string(),
+        other => other.to_uppercase(),
+    }
+}
+
+fn placeholder(label: &str, number: u64) -> String {
+    format!("<<{}_{}>>", label, number)
+}
+
+/// Replaces every letter and digit except the last four with `*`, keeping
+/// separators so the value's shape stays recognizable.
+fn mask_value(value: &str) -> String {
+    let keep_from = value.chars().filter(|c| c.is_alphanumeric()).count().saturating_sub(4);
+    let mut seen = 0;
+    value.chars()
+        .map(|c| {
+            if !c.is_alphanumeric() {
+                return c;
+            }
+            seen += 1;
+            if seen > keep_from { c } else { '*' }
+        })
+        .collect()
+}
+
+/// Truncated HMAC-SHA256, so values cannot be found again by hashing
+/// candidates without the key.
+fn hash_value(key: &[u8; 32], value: &str) -> String {
+    format!("hmac:{}", &crypto::keyed_hash(key, value.as_bytes())[..16])
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::{DetectedEntity, FakerConfig};
+    use crate::config::{DetectedEntity, EmailFakerConfig, FakerConfig};
 
     fn create_test_config() -> FakerConfig {
         FakerConfig {
             locale: "en_US".to_string(),
             seed: Some(12345),
             consistency: true,
+            strategies: HashMap::new(),
+            ip: IpFakerConfig::default(),
+            email: EmailFakerConfig::default(),
+            date_shift: DateShiftConfig::default(),
+            geo: GeoFakerConfig::default(),
+            identity: IdentityConfig::default(),
         }
     }
 
@@ -157,8 +841,53 @@ mod tests {
         let config = create_test_config();
         let engine = FakerEngine::new(&config);
         
-        assert_eq!(engine.locale, "en_US");
-        assert!(engine.consistency);
+        assert_eq!(engine.locale, FakeLocale::En);
+    }
+
+    #[test]
+    fn test_names_and_emails_follow_locale() {
+        let mut config = create_test_config();
+        let entity = |entity_type: &str| DetectedEntity {
+            entity_type: entity_type.to_string(),
+            original_value: "x".to_string(),
+            start: 0,
+            end: 1,
+            confidence: 0.9,
+            ..Default::default()
+        };
+
+        config.locale = "de_DE".to_string();
+        let mut engine = FakerEngine::new(&config);
+        let name = engine.anonymize_entity(&entity("name")).unwrap().fake_value;
+        let (first, last) = name.split_once(' ').unwrap();
+        assert!(GERMAN_FIRST_NAMES.contains(&first) && GERMAN_LAST_NAMES.contains(&last));
+        let email = engine.anonymize_entity(&entity("email")).unwrap().fake_value;
+        assert!(email.is_ascii() && email.contains(".") && email.contains("@example."));
+
+        config.locale = "ja-JP".to_string();
+        let mut engine = FakerEngine::new(&config);
+        assert!(!engine.anonymize_entity(&entity("name")).unwrap().fake_value.is_ascii());
+        assert!(engine.anonymize_entity(&entity("email")).unwrap().fake_value.is_ascii());
+
+        assert_eq!(FakeLocale::parse("pt_BR"), Some(FakeLocale::PtBr));
+        assert_eq!(FakeLocale::parse("zh-Hant"), Some(FakeLocale::ZhTw));
+        assert_eq!(FakeLocale::parse("xx"), None);
+        assert_eq!(ascii_fold("Müller-Lüdenscheidt"), "muellerluedenscheidt");
+    }
+
+    #[test]
+    fn test_locale_follows_detected_language() {
+        let languages = LanguageConfig { enabled: true, ..LanguageConfig::default() };
+        let mut engine = FakerEngine::new(&create_test_config()).with_languages(&languages);
+
+        engine.adapt_to("Sehr geehrte Damen und Herren, anbei erhalten Sie die Rechnung für den vergangenen Monat.");
+        assert_eq!(engine.current_locale(), FakeLocale::De);
+        engine.adapt_to("Danke");
+        assert_eq!(engine.current_locale(), FakeLocale::En);
+
+        let mut engine = FakerEngine::new(&create_test_config()).with_languages(&LanguageConfig::default());
+        engine.adapt_to("Sehr geehrte Damen und Herren, anbei erhalten Sie die Rechnung für den vergangenen Monat.");
+        assert_eq!(engine.current_locale(), FakeLocale::En);
     }
 
     #[test]
@@ -172,6 +901,7 @@ mod tests {
             start: 0,
             end: 20,
             confidence: 0.95,
+            ..Default::default()
         };
         
         let anonymized = engine.anonymize_entity(&detected).unwrap();
@@ -183,6 +913,35 @@ mod tests {
         assert!(!anonymized.mapping_id.is_empty());
     }
 
+    #[test]
+    fn test_email_domains_kept_or_mapped() {
+        let mut config = create_test_config();
+        let email = |value: &str| DetectedEntity {
+            entity_type: "email".to_string(),
+            original_value: value.to_string(),
+            start: 0,
+            end: value.len(),
+            confidence: 0.95,
+            ..Default::default()
+        };
+
+        config.email.domains = EmailDomainMode::Keep;
+        let mut engine = FakerEngine::new(&config);
+        let kept = engine.anonymize_entity(&email("jane@acme.com")).unwrap().fake_value;
+        assert!(kept.ends_with("@acme.com") && !kept.starts_with("jane@"), "{}", kept);
+
+        config.email.domains = EmailDomainMode::Map;
+        let mut engine = FakerEngine::new(&config);
+        let domain_of = |fake: String| fake.split_once('@').unwrap().1.to_string();
+        let jane = domain_of(engine.anonymize_entity(&email("jane@acme.com")).unwrap().fake_value);
+        let john = domain_of(engine.anonymize_entity(&email("john@ACME.com")).unwrap().fake_value);
+        let other = domain_of(engine.anonymize_entity(&email("ann@globex.com")).unwrap().fake_value);
+        assert_eq!(jane, john);
+        assert!(jane.ends_with(".example") && jane != "acme.com");
+        assert_ne!(jane, other);
+        assert_eq!(engine.email_domain_to_map("email@customer.email", "x@Acme.com").as_deref(), Some("acme.com"));
+    }
+
     #[test]
     fn test_phone_anonymization() {
         let config = create_test_config();
@@ -194,6 +953,7 @@ mod tests {
             start: 0,
             end: 12,
             confidence: 0.9,
+            ..Default::default()
         };
         
         let anonymized = engine.anonymize_entity(&detected).unwrap();
@@ -201,6 +961,13 @@ mod tests {
         assert_eq!(anonymized.entity_type, "phone");
         assert!(anonymized.fake_value.contains('-'));
         assert_ne!(anonymized.fake_value, "555-123-4567");
+
+        let detected = DetectedEntity {
+            original_value: "+33 6 12 34 56 78".to_string(),
+            region: Some("FR".to_string()),
+            ..detected
+        };
+        assert!(engine.anonymize_entity(&detected).unwrap().fake_value.starts_with("+33 "));
     }
 
     #[test]
@@ -214,6 +981,7 @@ mod tests {
             start: 0,
             end: 11,
             confidence: 0.95,
+            ..Default::default()
         };
         
         let anonymized = engine.anonymize_entity(&detected).unwrap();
@@ -236,6 +1004,7 @@ mod tests {
             start: 0,
             end: 16,
             confidence: 0.95,
+            ..Default::default()
         };
         
         let result1 = engine1.anonymize_entity(&detected).unwrap();
@@ -253,12 +1022,12 @@ mod tests {
             DetectedEntity {
                 entity_type: "email".to_string(),
                 original_value: "john@test.com".to_string(),
-                start: 0, end: 13, confidence: 0.95,
+                start: 0, end: 13, confidence: 0.95, ..Default::default()
             },
             DetectedEntity {
                 entity_type: "phone".to_string(),
                 original_value: "555-123-4567".to_string(),
-                start: 20, end: 32, confidence: 0.9,
+                start: 20, end: 32, confidence: 0.9, ..Default::default()
             },
         ];
         
@@ -278,12 +1047,12 @@ mod tests {
             DetectedEntity {
                 entity_type: "email".to_string(),
                 original_value: "john@test.com".to_string(),
-                start: 0, end: 13, confidence: 0.95,
+                start: 0, end: 13, confidence: 0.95, ..Default::default()
             },
             DetectedEntity {
                 entity_type: "phone".to_string(),
                 original_value: "555-123-4567".to_string(),
-                start: 20, end: 32, confidence: 0.9,
+                start: 20, end: 32, confidence: 0.9, ..Default::default()
             },
         ];
         
@@ -316,6 +1085,7 @@ mod tests {
             start: 0,
             end: 8,
             confidence: 0.95,
+            ..Default::default()
         };
         
         let anonymized = engine.anonymize_entity(&detected).unwrap();
@@ -347,6 +1117,7 @@ mod tests {
             start: 0,
             end: 17,
             confidence: 0.95,
+            ..Default::default()
         };
         
         let anonymized = engine.anonymize_entity(&detected).unwrap();
@@ -376,6 +1147,7 @@ mod tests {
             start: 0,
             end: 6,
             confidence: 0.95,
+            ..Default::default()
         };
         
         let anonymized = engine.anonymize_entity(&detected).unwrap();
@@ -393,6 +1165,87 @@ mod tests {
                 anonymized.fake_value.contains("edge"));
     }
 
+    #[test]
+    fn test_card_fakes_keep_network_length_and_layout() {
+        let mut engine = FakerEngine::new(&create_test_config());
+        for (original, prefix) in [
+            ("4111 1111 1111 1111", "4"),
+            ("5555-5555-5555-4444", "55"),
+            ("378282246310005", "37"),
+            ("2221000000000009", "2221"),
+            ("6011111111111117", "6011"),
+            ("4222222222222", "4"),
+        ] {
+            let fake = engine.fake_credit_card(original);
+            assert!(fake.starts_with(prefix), "{} -> {}", original, fake);
+            assert_eq!(fake.len(), original.len());
+            assert_eq!(fake.chars().filter(|c| !c.is_ascii_digit()).collect::<String>(),
+                       original.chars().filter(|c| !c.is_ascii_digit()).collect::<String>());
+            assert_ne!(fake, original);
+            let digits: Vec<u32> = fake.chars().filter_map(|c| c.to_digit(10)).collect();
+            assert_eq!(luhn_check_digit(&digits[..digits.len() - 1]), digits[digits.len() - 1]);
+        }
+    }
+
+    #[test]
+    fn test_mac_fakes_keep_the_vendor() {
+        let mut engine = FakerEngine::new(&create_test_config());
+        for (original, oui) in [("00:1A:2B:3C:4D:5E", "00:1A:2B:"), ("f4-5c-89-aa-bb-cc", "f4-5c-89-"), ("001a.2b3c.4d5e", "001a.2b")] {
+            let fake = engine.fake_mac(original);
+            assert!(fake.starts_with(oui), "{} -> {}", original, fake);
+            assert_eq!(fake.len(), original.len());
+            assert_eq!(fake.chars().any(|c| c.is_ascii_lowercase()), original.chars().any(|c| c.is_ascii_lowercase()));
+            assert_ne!(fake, original);
+        }
+        let fake = engine.generate_fake_mac();
+        assert!(fake.starts_with("02:") && fake.len() == 17, "{}", fake);
+    }
+
+    #[test]
+    fn test_structured_fakes_are_valid() {
+        let mut engine = FakerEngine::new(&create_test_config());
+        let mut fake = |entity_type: &str| {
+            engine.anonymize_entity(&DetectedEntity {
+                entity_type: entity_type.to_string(),
+                original_value: "original".to_string(),
+                start: 0, end: 8, confidence: 0.9, ..Default::default()
+            }).unwrap().fake_value
+        };
+
+        let card: Vec<u32> = fake("credit_card").chars().filter_map(|c| c.to_digit(10)).collect();
+        assert_eq!(card.len(), 16);
+        assert_eq!(luhn_check_digit(&card[..15]), card[15]);
+
+        let iban = fake("iban").replace(' ', "");
+        assert!(iban.starts_with("DE") && iban.len() == 22, "{}", iban);
+        assert_eq!(iban_check_digits("DE", &iban[4..]), iban[2..4].parse::<u32>().unwrap());
+        assert_eq!(iban_check_digits("DE", "370400440532013000"), 89);
+
+        let dob = fake("date_of_birth");
+        let year: i32 = dob[..4].parse().unwrap();
+        assert!((dates::current_year() - 90..=dates::current_year() - 18).contains(&year), "{}", dob);
+
+        assert!(!fake("address").starts_with("REDACTED"));
+        assert!(!fake("organization").starts_with("REDACTED"));
+
+        let labelled = engine.fake_date_of_birth("born on 15.03.1971 in Lyon");
+        assert!(labelled.starts_with("born on ") && labelled.ends_with(" in Lyon"), "{}", labelled);
+        assert!(dates::year(&labelled[8..]).is_some(), "{}", labelled);
+    }
+
+    #[test]
+    fn test_api_keys_keep_their_prefix() {
+        let mut engine = FakerEngine::new(&create_test_config());
+        let detected = DetectedEntity {
+            entity_type: "api_key@env.OPENAI_API_KEY".to_string(),
+            original_value: "sk-proj-4f9Qz8LmN2xR7tYv".to_string(),
+            start: 0, end: 24, confidence: 0.9, ..Default::default()
+        };
+
+        let fake = engine.anonymize_entity(&detected).unwrap().fake_value;
+        assert!(fake.starts_with("sk-proj-") && fake.len() == 24 && fake != detected.original_value, "{}", fake);
+    }
+
     #[test]
     fn test_unknown_entity_type() {
         let config = create_test_config();
@@ -401,7 +1254,7 @@ mod tests {
         let detected = DetectedEntity {
             entity_type: "unknown_type".to_string(),
             original_value: "some_value".to_string(),
-            start: 0, end: 10, confidence: 0.8,
+            start: 0, end: 10, confidence: 0.8, ..Default::default()
         };
         
         let anonymized = engine.anonymize_entity(&detected).unwrap();
@@ -409,6 +1262,65 @@ mod tests {
         assert_eq!(anonymized.fake_value, "REDACTED_UNKNOWN_TYPE");
     }
 
+    #[test]
+    fn test_strategies_per_entity_type() {
+        let mut config = create_test_config();
+        config.strategies = HashMap::from([
+            ("ssn".to_string(), AnonymizationStrategy::Mask),
+            ("phone".to_string(), AnonymizationStrategy::Redact),
+            ("name".to_string(), AnonymizationStrategy::Hash),
+            ("email".to_string(), AnonymizationStrategy::Tokenize),
+            ("zip_code".to_string(), AnonymizationStrategy::Generalize),
+            ("age".to_string(), AnonymizationStrategy::Generalize),
+        ]);
+        let mut engine = FakerEngine::new(&config);
+        let mut replace = |entity_type: &str, value: &str| {
+            engine.anonymize_entity(&DetectedEntity {
+                entity_type: entity_type.to_string(),
+                original_value: value.to_string(),
+                start: 0, end: value.len(), confidence: 0.9, ..Default::default()
+            }).unwrap().fake_value
+        };
+
+        assert_eq!(replace("ssn", "123-45-6789"), "***-**-6789");
+        assert_eq!(replace("phone", "555-123-4567"), REDACTED_TOKEN);
+        assert_eq!(replace("name", "Jane Roe"), replace("name", "Jane Roe"));
+        assert!(replace("name", "Jane Roe").starts_with("hmac:"));
+        assert_eq!(hash_value(&[1; 32], "123-45-6789"), hash_value(&[1; 32], "123-45-6789"));
+        assert_ne!(hash_value(&[1; 32], "123-45-6789"), hash_value(&[2; 32], "123-45-6789"));
+        assert_eq!(replace("email@customer.email", "a@b.com"), "<<EMAIL_1>>");
+        assert_eq!(replace("email", "c@d.com"), "<<EMAIL_2>>");
+        assert_eq!(replace("zip_code", "CA 94105-1234"), "CA 94100-0000");
+        assert_eq!(replace("age", "aged 52"), "aged 50-59");
+        // Unlisted types keep realistic fakes
+        assert!(replace("ip_address", "10.0.0.1").contains('.'));
+    }
+
+    #[test]
+    fn test_geo_strategies_generalize_coordinates() {
+        let mut config = create_test_config();
+        config.strategies = HashMap::from([
+            ("coordinates".to_string(), AnonymizationStrategy::GeoTruncate),
+            ("home".to_string(), AnonymizationStrategy::GeoCity),
+            ("position".to_string(), AnonymizationStrategy::GeoJitter),
+        ]);
+        let mut engine = FakerEngine::new(&config);
+        let mut replace = |entity_type: &str, value: &str| {
+            engine.anonymize_entity(&DetectedEntity {
+                entity_type: entity_type.to_string(),
+                original_value: value.to_string(),
+                start: 0, end: value.len(), confidence: 0.9, ..Default::default()
+            }).unwrap().fake_value
+        };
+
+        assert_eq!(replace("coordinates", "51.500729, -0.124625"), "51.50, -0.12");
+        assert_eq!(replace("home", "51.500729, -0.124625"), "51.507400, -0.127800");
+        let moved = replace("position", "51.500729, -0.124625");
+        let point = geo::Point::parse(&moved).unwrap();
+        assert!(geo::distance_km(51.500729, -0.124625, point.lat, point.lon) <= 5.01, "{}", moved);
+        assert_eq!(replace("home", "not a place"), "REDACTED_HOME");
+    }
+
     #[test]
     fn test_localhost_ip_anonymization() {
         let config = create_test_config();
@@ -420,6 +1332,7 @@ mod tests {
             start: 0,
             end: 9,
             confidence: 0.95,
+            ..Default::default()
         };
         
         let anonymized = engine.anonymize_entity(&detected).unwrap();
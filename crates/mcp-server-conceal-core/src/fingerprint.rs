@@ -0,0 +1,266 @@
+//! Trust-on-first-use fingerprints of the target MCP server
+//!
+//! The proxy is only as trustworthy as the server it wraps. On the first run
+//! with a given target command line, the SHA-256 of the resolved executable
+//! and of the script it runs (the first argument naming an existing file, as
+//! in `node server.js`) is recorded in the data directory. Later runs compare
+//! against the record and warn or refuse to start when the files changed, so
+//! a server swapped out underneath a trusted setup does not go unnoticed.
+//! `mcp-server-conceal trust-target` accepts an intended change.
+
+use crate::config::Config;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+/// What to do when the target's files differ from the recorded fingerprint.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FingerprintPolicy {
+    /// Do not fingerprint the target
+    Off,
+    /// Log a warning and record an audit event, then start anyway
+    #[default]
+    Warn,
+    /// Refuse to start the target
+    Block,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetTrustConfig {
+    #[serde(default)]
+    pub policy: FingerprintPolicy,
+    /// Fingerprint store; relative paths live in the data directory
+    #[serde(default = "default_store_path")]
+    pub path: PathBuf,
+}
+
+impl Default for TargetTrustConfig {
+    fn default() -> Self {
+        Self {
+            policy: FingerprintPolicy::default(),
+            path: default_store_path(),
+        }
+    }
+}
+
+fn default_store_path() -> PathBuf {
+    PathBuf::from("target_fingerprints.json")
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileFingerprint {
+    pub path: PathBuf,
+    pub sha256: String,
+}
+
+/// Fingerprint of one target command line.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TargetFingerprint {
+    /// The command line as given, which identifies the target in the store
+    pub command_line: String,
+    pub files: Vec<FileFingerprint>,
+}
+
+impl TargetFingerprint {
+    /// Resolves the command through `PATH` (the target's own `PATH` when set)
+    /// and hashes it together with the script it is given, if any.
+    pub fn compute(
+        command: &str,
+        args: &[String],
+        cwd: Option<&Path>,
+        env: &HashMap<String, String>,
+    ) -> Result<Self> {
+        let executable = resolve_command(command, cwd, env)
+            .ok_or_else(|| anyhow::anyhow!("Could not find target command '{}'", command))?;
+        let script = args.iter()
+            .filter(|arg| !arg.starts_with('-'))
+            .map(|arg| relative_to(cwd, Path::new(arg)))
+            .find(|path| path.is_file());
+
+        let files = std::iter::once(executable)
+            .chain(script)
+            .map(|path| {
+                let path = path.canonicalize().unwrap_or(path);
+                Ok(FileFingerprint { sha256: hash_file(&path)?, path })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let command_line = std::iter::once(command)
+            .chain(args.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join(" ");
+        Ok(Self { command_line, files })
+    }
+}
+
+/// Result of comparing a target against the store.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TrustCheck {
+    /// Not seen before; the fingerprint has been recorded
+    FirstUse,
+    Unchanged,
+    /// Files whose hash differs from the record, or that were added or removed
+    Changed(Vec<PathBuf>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct TrustedTarget {
+    files: Vec<FileFingerprint>,
+    trusted_at: u64,
+}
+
+/// JSON file of trusted fingerprints keyed by command line.
+pub struct FingerprintStore {
+    path: PathBuf,
+}
+
+impl FingerprintStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+
+    pub fn from_config(config: &TargetTrustConfig) -> Result<Self> {
+        let path = if config.path.is_absolute() {
+            config.path.clone()
+        } else {
+            Config::get_app_dirs()?.data_dir().join(&config.path)
+        };
+        Ok(Self::new(path))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Compares `fingerprint` with the record, recording it on first use.
+    pub fn check(&self, fingerprint: &TargetFingerprint) -> Result<TrustCheck> {
+        let targets = self.load()?;
+        let Some(trusted) = targets.get(&fingerprint.command_line) else {
+            self.trust(fingerprint)?;
+            return Ok(TrustCheck::FirstUse);
+        };
+
+        let mut changed: Vec<PathBuf> = fingerprint.files.iter()
+            .filter(|file| !trusted.files.contains(file))
+            .map(|file| file.path.clone())
+            .collect();
+        changed.extend(trusted.files.iter()
+            .filter(|old| !fingerprint.files.iter().any(|file| file.path == old.path))
+            .map(|old| old.path.clone()));
+
+        if changed.is_empty() {
+            Ok(TrustCheck::Unchanged)
+        } else {
+            Ok(TrustCheck::Changed(changed))
+        }
+    }
+
+    /// Records `fingerprint` as trusted, replacing any earlier record.
+    pub fn trust(&self, fingerprint: &TargetFingerprint) -> Result<()> {
+        let mut targets = self.load()?;
+        targets.insert(fingerprint.command_line.clone(), TrustedTarget {
+            files: fingerprint.files.clone(),
+            trusted_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+        });
+
+        if let Some(parent) = self.path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp = self.path.with_extension("tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(&targets)?)?;
+        std::fs::rename(&temp, &self.path)?;
+        debug!("Trusted target '{}'", fingerprint.command_line);
+        Ok(())
+    }
+
+    fn load(&self) -> Result<BTreeMap<String, TrustedTarget>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| anyhow::anyhow!("Corrupt fingerprint store {}: {}", self.path.display(), e)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(BTreeMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+fn relative_to(cwd: Option<&Path>, path: &Path) -> PathBuf {
+    match cwd {
+        Some(cwd) if path.is_relative() => cwd.join(path),
+        _ => path.to_path_buf(),
+    }
+}
+
+fn resolve_command(command: &str, cwd: Option<&Path>, env: &HashMap<String, String>) -> Option<PathBuf> {
+    let path = Path::new(command);
+    if path.components().count() > 1 {
+        let path = relative_to(cwd, path);
+        return path.is_file().then_some(path);
+    }
+
+    let search_path = env.get("PATH").cloned().or_else(|| std::env::var("PATH").ok())?;
+    std::env::split_paths(&search_path)
+        .flat_map(|dir| [dir.join(command), dir.join(format!("{}{}", command, std::env::consts::EXE_SUFFIX))])
+        .find(|candidate| candidate.is_file())
+}
+
+fn hash_file(path: &Path) -> Result<String> {
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(hex::encode(hasher.finalize()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_resolves_command_from_target_path_and_script() {
+        let temp_dir = TempDir::new().unwrap();
+        let bin = temp_dir.path().join("bin");
+        std::fs::create_dir(&bin).unwrap();
+        std::fs::write(bin.join("node"), "#!runtime").unwrap();
+        std::fs::write(temp_dir.path().join("server.js"), "console.log(1)").unwrap();
+        let env = HashMap::from([("PATH".to_string(), bin.display().to_string())]);
+
+        let fingerprint = TargetFingerprint::compute(
+            "node",
+            &["--trace".to_string(), "server.js".to_string()],
+            Some(temp_dir.path()),
+            &env,
+        ).unwrap();
+
+        assert_eq!(fingerprint.command_line, "node --trace server.js");
+        let names: Vec<_> = fingerprint.files.iter().map(|f| f.path.file_name().unwrap().to_owned()).collect();
+        assert_eq!(names, ["node", "server.js"]);
+        assert!(TargetFingerprint::compute("no-such-server", &[], None, &env).is_err());
+    }
+
+    #[test]
+    fn test_changed_script_is_reported_until_trusted() {
+        let temp_dir = TempDir::new().unwrap();
+        let script = temp_dir.path().join("server.sh");
+        std::fs::write(&script, "echo v1").unwrap();
+        let store = FingerprintStore::new(temp_dir.path().join("fingerprints.json"));
+        let compute = || TargetFingerprint::compute(
+            script.to_str().unwrap(), &[], None, &HashMap::new(),
+        ).unwrap();
+
+        assert_eq!(store.check(&compute()).unwrap(), TrustCheck::FirstUse);
+        assert_eq!(store.check(&compute()).unwrap(), TrustCheck::Unchanged);
+
+        std::fs::write(&script, "echo swapped").unwrap();
+        let changed = compute();
+        assert_eq!(store.check(&changed).unwrap(), TrustCheck::Changed(vec![changed.files[0].path.clone()]));
+
+        store.trust(&changed).unwrap();
+        assert_eq!(store.check(&compute()).unwrap(), TrustCheck::Unchanged);
+    }
+}
@@ -0,0 +1,190 @@
+//! Format-preserving encryption (FF3-1, NIST SP 800-38G Rev. 1) of numeric identifiers
+//!
+//! The `fpe` strategy encrypts the digits of a value in place: separators stay
+//! where they were and the result has exactly as many digits as the input, so
+//! an SSN still looks like an SSN. Nothing is written to the mapping store;
+//! the key alone reverses a value, which keeps very high-cardinality
+//! identifiers such as account numbers out of SQLite. The key is derived from
+//! the mapping passphrase, and the tweak from the entity type, so the same
+//! digits encrypt differently as an SSN and as an account number.
+
+use crate::config::base_entity_type;
+use crate::crypto;
+use crate::secrets::Passphrase;
+use aes_gcm::aes::cipher::generic_array::GenericArray;
+use aes_gcm::aes::cipher::{BlockEncrypt, KeyInit};
+use aes_gcm::aes::Aes256;
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+
+const FPE_KEY_CONTEXT: &[u8] = b"mcp-server-conceal fpe v1";
+const RADIX: u128 = 10;
+const ROUNDS: u8 = 8;
+
+/// Fewest digits FF3-1 accepts for radix 10 (radix^minlen >= 1,000,000).
+pub const MIN_DIGITS: usize = 6;
+/// Most digits FF3-1 accepts for radix 10 (2 * floor(log10(2^96))).
+pub const MAX_DIGITS: usize = 56;
+
+pub struct FpeCipher {
+    aes: Aes256,
+}
+
+impl FpeCipher {
+    pub fn new(key: &[u8; 32]) -> Self {
+        // FF3 keys the block cipher with the byte-reversed key
+        let mut reversed = *key;
+        reversed.reverse();
+        Self {
+            aes: Aes256::new(GenericArray::from_slice(&reversed)),
+        }
+    }
+
+    pub fn from_passphrase(passphrase: &Passphrase) -> Self {
+        Self::new(&crypto::derive_key(passphrase, FPE_KEY_CONTEXT))
+    }
+
+    /// Encrypts the digits of `value`, leaving every other character in place.
+    pub fn encrypt_value(&self, entity_type: &str, value: &str) -> Result<String> {
+        self.transform_value(entity_type, value, true)
+    }
+
+    /// Reverses [`encrypt_value`](Self::encrypt_value) for the same entity type.
+    pub fn decrypt_value(&self, entity_type: &str, value: &str) -> Result<String> {
+        self.transform_value(entity_type, value, false)
+    }
+
+    fn transform_value(&self, entity_type: &str, value: &str, encrypt: bool) -> Result<String> {
+        let digits: Vec<u8> = value.bytes().filter(u8::is_ascii_digit).map(|b| b - b'0').collect();
+        if !(MIN_DIGITS..=MAX_DIGITS).contains(&digits.len()) {
+            return Err(anyhow::anyhow!(
+                "FPE needs {} to {} digits, '{}' value has {}",
+                MIN_DIGITS, MAX_DIGITS, entity_type, digits.len()
+            ));
+        }
+
+        let (tl, tr) = split_tweak(&entity_tweak(entity_type));
+        let mut transformed = self.rounds(tl, tr, &digits, encrypt).into_iter();
+        Ok(value.chars()
+            .map(|c| match c.is_ascii_digit() {
+                true => char::from(b'0' + transformed.next().unwrap_or_default()),
+                false => c,
+            })
+            .collect())
+    }
+
+    /// The FF3 Feistel network over decimal numerals, with the tweak already split.
+    fn rounds(&self, tl: [u8; 4], tr: [u8; 4], digits: &[u8], encrypt: bool) -> Vec<u8> {
+        let u = digits.len().div_ceil(2);
+        let v = digits.len() - u;
+        let (mut a, mut b) = (digits[..u].to_vec(), digits[u..].to_vec());
+
+        let round_input = |i: u8| if i.is_multiple_of(2) { (u, tr) } else { (v, tl) };
+        if encrypt {
+            for i in 0..ROUNDS {
+                let (m, w) = round_input(i);
+                let y = self.round_function(w, i, &b);
+                let modulus = RADIX.pow(m as u32);
+                let c = (num_rev(&a) + y % modulus) % modulus;
+                a = std::mem::replace(&mut b, str_rev(c, m));
+            }
+        } else {
+            for i in (0..ROUNDS).rev() {
+                let (m, w) = round_input(i);
+                let y = self.round_function(w, i, &a);
+                let modulus = RADIX.pow(m as u32);
+                let c = (num_rev(&b) + modulus - y % modulus) % modulus;
+                b = std::mem::replace(&mut a, str_rev(c, m));
+            }
+        }
+
+        a.extend(b);
+        a
+    }
+
+    fn round_function(&self, w: [u8; 4], round: u8, half: &[u8]) -> u128 {
+        let mut block = [0u8; 16];
+        block[..4].copy_from_slice(&w);
+        block[3] ^= round;
+        block[4..].copy_from_slice(&num_rev(half).to_be_bytes()[4..]);
+
+        block.reverse();
+        let mut block = GenericArray::from(block);
+        self.aes.encrypt_block(&mut block);
+        let mut s: [u8; 16] = block.into();
+        s.reverse();
+        u128::from_be_bytes(s)
+    }
+}
+
+/// 56-bit tweak bound to an entity type.
+fn entity_tweak(entity_type: &str) -> [u8; 7] {
+    let base_type = base_entity_type(entity_type);
+    let digest = Sha256::digest(base_type.as_bytes());
+    let mut tweak = [0u8; 7];
+    tweak.copy_from_slice(&digest[..7]);
+    tweak
+}
+
+/// Splits a 56-bit FF3-1 tweak into the 32-bit left and right round tweaks.
+fn split_tweak(tweak: &[u8; 7]) -> ([u8; 4], [u8; 4]) {
+    (
+        [tweak[0], tweak[1], tweak[2], tweak[3] & 0xF0],
+        [tweak[4], tweak[5], tweak[6], (tweak[3] & 0x0F) << 4],
+    )
+}
+
+/// NUM(REV(X)): the numeral string read least significant digit first.
+fn num_rev(digits: &[u8]) -> u128 {
+    digits.iter().rev().fold(0, |acc, &d| acc * RADIX + d as u128)
+}
+
+/// REV(STR_m(x)): `m` digits of `x`, least significant first.
+fn str_rev(mut x: u128, m: usize) -> Vec<u8> {
+    (0..m)
+        .map(|_| {
+            let digit = (x % RADIX) as u8;
+            x /= RADIX;
+            digit
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digits(s: &str) -> Vec<u8> {
+        s.bytes().map(|b| b - b'0').collect()
+    }
+
+    #[test]
+    fn test_nist_ff3_aes256_sample() {
+        // NIST FF3 sample: AES-256, radix 10, 64-bit tweak D8E7920AFA330A73
+        let key: [u8; 32] = hex::decode("EF4359D8D580AA4F7F036D6F04FC6A942B7E151628AED2A6ABF7158809CF4F3C")
+            .unwrap()
+            .try_into()
+            .unwrap();
+        let cipher = FpeCipher::new(&key);
+        let (tl, tr) = ([0xD8, 0xE7, 0x92, 0x0A], [0xFA, 0x33, 0x0A, 0x73]);
+
+        let ciphertext = cipher.rounds(tl, tr, &digits("890121234567890000"), true);
+        assert_eq!(ciphertext, digits("922011205562777495"));
+        assert_eq!(cipher.rounds(tl, tr, &ciphertext, false), digits("890121234567890000"));
+    }
+
+    #[test]
+    fn test_values_keep_format_and_round_trip() {
+        let cipher = FpeCipher::from_passphrase(&Passphrase::new("correct horse"));
+
+        let ssn = cipher.encrypt_value("ssn", "123-45-6789").unwrap();
+        assert_ne!(ssn, "123-45-6789");
+        assert_eq!(ssn.len(), 11);
+        assert_eq!((&ssn[3..4], &ssn[6..7]), ("-", "-"));
+        assert_eq!(cipher.decrypt_value("ssn", &ssn).unwrap(), "123-45-6789");
+
+        // The tweak separates entity types
+        assert_ne!(cipher.encrypt_value("account_number", "123-45-6789").unwrap(), ssn);
+        assert!(cipher.encrypt_value("ssn", "12345").is_err());
+    }
+}
@@ -0,0 +1,517 @@
+//! Gateway mode: one proxy in front of several target servers
+//!
+//! The client sees a single MCP server. `initialize` and `tools/list` are sent
+//! to every target and their answers merged, with each tool renamed to
+//! `<target>__<tool>`; a target that has not answered within the fan-out
+//! timeout is left out of that answer. Targets that page their tools get a
+//! gateway cursor holding each one's own cursor, so the next page is asked
+//! of those targets only. `tools/call` goes to the target named by the prefix,
+//! with the prefix removed. Requests a target sends on its own (sampling,
+//! roots) get gateway-wide ids so the client's answers find their way back.
+//!
+//! The `Router` only rewrites and routes messages; the proxy runs each
+//! target's traffic through that target's own pipelines.
+
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::PathBuf;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::{debug, warn};
+
+/// Between the target name and the tool name in the tools the client sees.
+pub const TOOL_SEPARATOR: &str = "__";
+
+/// Prefix of the ids of requests the gateway sends to targets on its own behalf.
+const FAN_OUT_ID_PREFIX: &str = "conceal-gateway-";
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const INTERNAL_ERROR: i64 = -32603;
+
+/// One `[[targets]]` block.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TargetConfig {
+    /// Tool prefix and mapping namespace of this target
+    pub name: String,
+    pub command: String,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default)]
+    pub env: HashMap<String, String>,
+    #[serde(default)]
+    pub cwd: Option<PathBuf>,
+}
+
+impl TargetConfig {
+    /// Mapping namespace of the target, below the configured one if any, so
+    /// the same value gets unrelated fakes on different targets.
+    pub fn namespace(&self, base: Option<&str>) -> String {
+        match base {
+            Some(base) => format!("{}/{}", base, self.name),
+            None => self.name.clone(),
+        }
+    }
+}
+
+pub fn validate_targets(targets: &[TargetConfig]) -> Result<()> {
+    let mut names = HashSet::new();
+    for target in targets {
+        if target.name.is_empty()
+            || target.name.contains(TOOL_SEPARATOR)
+            || !target.name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        {
+            return Err(anyhow::anyhow!(
+                "Target name '{}' must be letters, digits, '-' and '_', without '{}'",
+                target.name, TOOL_SEPARATOR
+            ));
+        }
+        if target.command.is_empty() {
+            return Err(anyhow::anyhow!("Target '{}' has no command", target.name));
+        }
+        if !names.insert(target.name.as_str()) {
+            return Err(anyhow::anyhow!("Target name '{}' is used twice", target.name));
+        }
+    }
+    Ok(())
+}
+
+/// Where a message goes next.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Route {
+    Client(String),
+    /// Index of the target in the configuration
+    Target(usize, String),
+}
+
+/// A client request sent to every target, waiting for their answers.
+struct FanOut {
+    client_id: Value,
+    method: String,
+    params: Value,
+    waiting: HashSet<usize>,
+    results: Vec<(usize, Value)>,
+    deadline: Instant,
+}
+
+pub struct Router {
+    targets: Vec<String>,
+    closed: HashSet<usize>,
+    next_id: u64,
+    fan_out_timeout: Duration,
+    fan_outs: HashMap<u64, FanOut>,
+    /// Fan-out request id sent to a target → fan-out it belongs to
+    fan_out_ids: HashMap<String, u64>,
+    /// Fan-out request ids answered without the target, whose late answers are dropped
+    late_ids: HashSet<String>,
+    /// Gateway id of a target's own request → target and its original id
+    target_requests: HashMap<String, (usize, Value)>,
+}
+
+impl Router {
+    pub fn new(targets: Vec<String>, fan_out_timeout: Duration) -> Self {
+        Self {
+            targets,
+            closed: HashSet::new(),
+            next_id: 0,
+            fan_out_timeout,
+            fan_outs: HashMap::new(),
+            fan_out_ids: HashMap::new(),
+            late_ids: HashSet::new(),
+            target_requests: HashMap::new(),
+        }
+    }
+
+    /// When the earliest pending fan-out runs out of time.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        self.fan_outs.values().map(|fan_out| fan_out.deadline).min()
+    }
+
+    /// Answers the fan-outs past their deadline with what the targets that
+    /// did answer said.
+    pub fn expire(&mut self, now: Instant) -> Vec<Route> {
+        let expired: Vec<u64> = self.fan_outs.iter()
+            .filter(|(_, fan_out)| fan_out.deadline <= now)
+            .map(|(key, _)| *key)
+            .collect();
+        let mut routes = Vec::new();
+        for key in expired {
+            let Some(fan_out) = self.fan_outs.get_mut(&key) else {
+                continue;
+            };
+            for target in fan_out.waiting.drain() {
+                warn!("Gateway target '{}' did not answer {} within {}s, leaving it out",
+                      self.targets[target], fan_out.method, self.fan_out_timeout.as_secs());
+            }
+            self.late_ids.extend(self.fan_out_ids.iter().filter(|(_, pending)| **pending == key).map(|(id, _)| id.clone()));
+            routes.extend(self.finish_if_complete(key));
+        }
+        routes
+    }
+
+    fn open_targets(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..self.targets.len()).filter(|index| !self.closed.contains(index))
+    }
+
+    pub fn all_closed(&self) -> bool {
+        self.closed.len() == self.targets.len()
+    }
+
+    /// Routes a line the client sent.
+    pub fn from_client(&mut self, line: &str) -> Vec<Route> {
+        let Ok(message) = serde_json::from_str::<Value>(line) else {
+            warn!("Dropping a client message that is not JSON");
+            return Vec::new();
+        };
+        let method = message.get("method").and_then(Value::as_str);
+        let id = message.get("id").filter(|id| !id.is_null()).cloned();
+
+        match (method, id) {
+            (Some("initialize" | "tools/list"), Some(id)) => self.fan_out(method.unwrap_or_default(), id, &message),
+            (Some("tools/call"), Some(id)) => self.route_tool_call(id, message),
+            (Some("ping"), Some(id)) => vec![Route::Client(json!({"jsonrpc": "2.0", "id": id, "result": {}}).to_string())],
+            (Some(method), Some(id)) => {
+                debug!("Gateway does not serve '{}'", method);
+                vec![error_response(id, METHOD_NOT_FOUND, &format!("Method '{}' is not available through the gateway", method))]
+            }
+            // Notifications concern every target
+            (Some(_), None) => self.open_targets().map(|index| Route::Target(index, line.to_string())).collect(),
+            (None, Some(id)) => self.route_client_response(id, message),
+            (None, None) => Vec::new(),
+        }
+    }
+
+    /// Routes a line a target sent, after its response pipeline.
+    pub fn from_target(&mut self, target: usize, line: &str) -> Vec<Route> {
+        let Ok(mut message) = serde_json::from_str::<Value>(line) else {
+            return vec![Route::Client(line.to_string())];
+        };
+        let has_method = message.get("method").is_some();
+        let Some(id) = message.get("id").filter(|id| !id.is_null()).cloned() else {
+            return vec![Route::Client(line.to_string())];
+        };
+
+        if has_method {
+            // A request of the target's own; its id may clash with another target's
+            let sequence = self.next_id();
+            let gateway_id = format!("{}{}{}", self.targets[target], TOOL_SEPARATOR, sequence);
+            self.target_requests.insert(gateway_id.clone(), (target, id));
+            message["id"] = Value::String(gateway_id);
+            return vec![Route::Client(message.to_string())];
+        }
+
+        if id.as_str().is_some_and(|id| self.late_ids.remove(id)) {
+            debug!("Dropping a late answer of gateway target '{}'", self.targets[target]);
+            return Vec::new();
+        }
+        match id.as_str().and_then(|id| self.fan_out_ids.remove(id)) {
+            Some(fan_out) => self.collect(fan_out, target, message),
+            None => vec![Route::Client(line.to_string())],
+        }
+    }
+
+    /// Stops waiting for a target whose output ended.
+    pub fn target_closed(&mut self, target: usize) -> Vec<Route> {
+        warn!("Gateway target '{}' closed its output", self.targets[target]);
+        self.closed.insert(target);
+        self.target_requests.retain(|_, (index, _)| *index != target);
+        let pending: Vec<u64> = self.fan_outs.iter()
+            .filter(|(_, fan_out)| fan_out.waiting.contains(&target))
+            .map(|(key, _)| *key)
+            .collect();
+        pending.into_iter()
+            .flat_map(|key| {
+                self.fan_outs.get_mut(&key).map(|fan_out| fan_out.waiting.remove(&target));
+                self.finish_if_complete(key)
+            })
+            .collect()
+    }
+
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn fan_out(&mut self, method: &str, client_id: Value, message: &Value) -> Vec<Route> {
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+        // The next page is asked only of the targets that had more
+        let cursors = match params.get("cursor").and_then(Value::as_str).map(decode_cursor) {
+            Some(Some(cursors)) => Some(cursors),
+            Some(None) => return vec![error_response(client_id, INVALID_PARAMS, "Invalid cursor")],
+            None => None,
+        };
+        let waiting: HashSet<usize> = self.open_targets()
+            .filter(|&target| cursors.as_ref().is_none_or(|cursors| cursors.contains_key(&self.targets[target])))
+            .collect();
+        if waiting.is_empty() && cursors.is_some() {
+            return vec![error_response(client_id, INVALID_PARAMS, "The cursor names no running target")];
+        }
+        let key = self.next_id();
+        let mut routes = Vec::new();
+        for &target in &waiting {
+            let id = format!("{}{}-{}", FAN_OUT_ID_PREFIX, key, target);
+            self.fan_out_ids.insert(id.clone(), key);
+            let mut request = json!({"jsonrpc": "2.0", "id": id, "method": method});
+            if !params.is_null() {
+                request["params"] = params.clone();
+            }
+            if let Some(cursor) = cursors.as_ref().and_then(|cursors| cursors.get(&self.targets[target])) {
+                request["params"]["cursor"] = Value::String(cursor.clone());
+            }
+            routes.push(Route::Target(target, request.to_string()));
+        }
+        let deadline = Instant::now() + self.fan_out_timeout;
+        self.fan_outs.insert(key, FanOut { client_id, method: method.to_string(), params, waiting, results: Vec::new(), deadline });
+        routes.extend(self.finish_if_complete(key));
+        routes
+    }
+
+    fn collect(&mut self, key: u64, target: usize, message: Value) -> Vec<Route> {
+        let Some(fan_out) = self.fan_outs.get_mut(&key) else {
+            return Vec::new();
+        };
+        fan_out.waiting.remove(&target);
+        match message.get("result") {
+            Some(result) => fan_out.results.push((target, result.clone())),
+            None => warn!("Gateway target '{}' failed {}: {}", self.targets[target], fan_out.method,
+                          message.get("error").cloned().unwrap_or_default()),
+        }
+        self.finish_if_complete(key)
+    }
+
+    fn finish_if_complete(&mut self, key: u64) -> Vec<Route> {
+        if self.fan_outs.get(&key).is_none_or(|fan_out| !fan_out.waiting.is_empty()) {
+            return Vec::new();
+        }
+        let Some(fan_out) = self.fan_outs.remove(&key) else {
+            return Vec::new();
+        };
+        self.fan_out_ids.retain(|_, pending| *pending != key);
+
+        if fan_out.results.is_empty() {
+            return vec![error_response(fan_out.client_id, INTERNAL_ERROR, &format!("No target answered {}", fan_out.method))];
+        }
+        let result = match fan_out.method.as_str() {
+            "initialize" => merge_initialize(&fan_out),
+            _ => self.merge_tools(&fan_out.results),
+        };
+        vec![Route::Client(json!({"jsonrpc": "2.0", "id": fan_out.client_id, "result": result}).to_string())]
+    }
+
+    fn merge_tools(&self, results: &[(usize, Value)]) -> Value {
+        let mut results: Vec<_> = results.iter().collect();
+        results.sort_by_key(|(target, _)| *target);
+        let cursors: BTreeMap<&str, &str> = results.iter()
+            .filter_map(|(target, result)| Some((self.targets[*target].as_str(), result.get("nextCursor")?.as_str()?)))
+            .collect();
+        let tools: Vec<Value> = results.into_iter()
+            .flat_map(|(target, result)| {
+                let prefix = &self.targets[*target];
+                result.get("tools").and_then(Value::as_array).cloned().unwrap_or_default()
+                    .into_iter()
+                    .filter_map(move |mut tool| {
+                        let name = tool.get("name")?.as_str()?;
+                        tool["name"] = Value::String(format!("{}{}{}", prefix, TOOL_SEPARATOR, name));
+                        Some(tool)
+                    })
+            })
+            .collect();
+        let mut merged = json!({ "tools": tools });
+        if !cursors.is_empty() {
+            merged["nextCursor"] = Value::String(encode_cursor(&cursors));
+        }
+        merged
+    }
+
+    fn route_tool_call(&mut self, id: Value, mut message: Value) -> Vec<Route> {
+        let name = message.pointer("/params/name").and_then(Value::as_str).unwrap_or_default();
+        let routed = name.split_once(TOOL_SEPARATOR).and_then(|(prefix, tool)| {
+            let target = self.targets.iter().position(|target| target == prefix)?;
+            Some((target, tool.to_string()))
+        });
+        match routed {
+            Some((target, _)) if self.closed.contains(&target) => {
+                vec![error_response(id, INTERNAL_ERROR, &format!("Target '{}' is no longer running", self.targets[target]))]
+            }
+            Some((target, tool)) => {
+                message["params"]["name"] = Value::String(tool);
+                vec![Route::Target(target, message.to_string())]
+            }
+            None => vec![error_response(id, INVALID_PARAMS, &format!("Unknown tool '{}'", name))],
+        }
+    }
+
+    fn route_client_response(&mut self, id: Value, mut message: Value) -> Vec<Route> {
+        let Some((target, original_id)) = id.as_str().and_then(|id| self.target_requests.remove(id)) else {
+            warn!("Dropping a client response to an unknown request");
+            return Vec::new();
+        };
+        message["id"] = original_id;
+        vec![Route::Target(target, message.to_string())]
+    }
+}
+
+/// The gateway's own answer to `initialize`: the protocol version the first
+/// target agreed to, and the tools capability, which is all it serves.
+fn merge_initialize(fan_out: &FanOut) -> Value {
+    let protocol_version = fan_out.results.iter()
+        .min_by_key(|(target, _)| *target)
+        .and_then(|(_, result)| result.get("protocolVersion").cloned())
+        .or_else(|| fan_out.params.get("protocolVersion").cloned())
+        .unwrap_or(Value::Null);
+    json!({
+        "protocolVersion": protocol_version,
+        "capabilities": { "tools": { "listChanged": true } },
+        "serverInfo": { "name": "mcp-server-conceal", "version": env!("CARGO_PKG_VERSION") },
+    })
+}
+
+/// The gateway's cursor: each paging target's own cursor, by target name.
+fn encode_cursor(cursors: &BTreeMap<&str, &str>) -> String {
+    URL_SAFE_NO_PAD.encode(serde_json::to_vec(cursors).unwrap_or_default())
+}
+
+fn decode_cursor(cursor: &str) -> Option<HashMap<String, String>> {
+    serde_json::from_slice(&URL_SAFE_NO_PAD.decode(cursor).ok()?).ok()
+}
+
+fn error_response(id: Value, code: i64, message: &str) -> Route {
+    Route::Client(json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}}).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    fn parse(route: &Route) -> (Option<usize>, Value) {
+        match route {
+            Route::Client(line) => (None, serde_json::from_str(line).unwrap()),
+            Route::Target(index, line) => (Some(*index), serde_json::from_str(line).unwrap()),
+        }
+    }
+
+    #[test]
+    fn test_tools_are_listed_with_prefixes_and_calls_routed() {
+        let mut router = Router::new(vec!["github".to_string(), "db".to_string()], TIMEOUT);
+        let sent = router.from_client(r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#);
+        assert_eq!(sent.len(), 2);
+
+        let mut answered = Vec::new();
+        for route in &sent {
+            let (target, request) = parse(route);
+            let target = target.unwrap();
+            let tool = if target == 0 { "search_issues" } else { "query" };
+            let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": {"tools": [{"name": tool}]}});
+            answered.extend(router.from_target(target, &response.to_string()));
+        }
+        let (_, listing) = parse(&answered[0]);
+        assert_eq!(listing["id"], 1);
+        assert_eq!(listing["result"]["tools"], json!([{"name": "github__search_issues"}, {"name": "db__query"}]));
+
+        let routed = router.from_client(r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"db__query","arguments":{}}}"#);
+        let (target, call) = parse(&routed[0]);
+        assert_eq!(target, Some(1));
+        assert_eq!(call["params"]["name"], "query");
+        assert_eq!(call["id"], 2);
+
+        let unknown = router.from_client(r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"query"}}"#);
+        assert_eq!(parse(&unknown[0]).1["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_target_requests_get_gateway_ids() {
+        let mut router = Router::new(vec!["a".to_string(), "b".to_string()], TIMEOUT);
+        let first = router.from_target(0, r#"{"jsonrpc":"2.0","id":1,"method":"sampling/createMessage"}"#);
+        let second = router.from_target(1, r#"{"jsonrpc":"2.0","id":1,"method":"sampling/createMessage"}"#);
+        let (first_id, second_id) = (parse(&first[0]).1["id"].clone(), parse(&second[0]).1["id"].clone());
+        assert_ne!(first_id, second_id);
+
+        let reply = router.from_client(&json!({"jsonrpc": "2.0", "id": second_id, "result": {}}).to_string());
+        assert_eq!(parse(&reply[0]), (Some(1), json!({"jsonrpc": "2.0", "id": 1, "result": {}})));
+    }
+
+    #[test]
+    fn test_fan_out_completes_when_a_target_closes() {
+        let mut router = Router::new(vec!["a".to_string(), "b".to_string()], TIMEOUT);
+        let sent = router.from_client(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-06-18"}}"#);
+        let (_, request) = parse(&sent.iter().find(|route| matches!(route, Route::Target(0, _))).unwrap().clone());
+        let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": {"protocolVersion": "2025-03-26"}});
+        assert!(router.from_target(0, &response.to_string()).is_empty());
+
+        let answered = router.target_closed(1);
+        let (_, result) = parse(&answered[0]);
+        assert_eq!(result["result"]["protocolVersion"], "2025-03-26");
+        assert_eq!(result["result"]["serverInfo"]["name"], "mcp-server-conceal");
+    }
+
+    /// Answers every fan-out request among `sent` with `answer(target, request)`.
+    fn answer_all(router: &mut Router, sent: &[Route], answer: impl Fn(usize, &Value) -> Value) -> Vec<Route> {
+        let mut answered = Vec::new();
+        for route in sent {
+            if let (Some(target), request) = parse(route) {
+                let response = json!({"jsonrpc": "2.0", "id": request["id"], "result": answer(target, &request)});
+                answered.extend(router.from_target(target, &response.to_string()));
+            }
+        }
+        answered
+    }
+
+    #[test]
+    fn test_tool_pages_are_asked_of_the_targets_that_have_more() {
+        let mut router = Router::new(vec!["a".to_string(), "b".to_string()], TIMEOUT);
+        let sent = router.from_client(r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#);
+        let answered = answer_all(&mut router, &sent, |target, _| match target {
+            0 => json!({"tools": [{"name": "one"}], "nextCursor": "page-2"}),
+            _ => json!({"tools": [{"name": "two"}]}),
+        });
+        let (_, first) = parse(&answered[0]);
+        let cursor = first["result"]["nextCursor"].as_str().unwrap().to_string();
+
+        let request = json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list", "params": {"cursor": cursor}});
+        let sent = router.from_client(&request.to_string());
+        assert_eq!(sent.len(), 1);
+        let (target, paged) = parse(&sent[0]);
+        assert_eq!((target, &paged["params"]["cursor"]), (Some(0), &json!("page-2")));
+        let answered = answer_all(&mut router, &sent, |_, _| json!({"tools": [{"name": "three"}]}));
+        let (_, second) = parse(&answered[0]);
+        assert_eq!(second["result"], json!({"tools": [{"name": "a__three"}]}));
+
+        let bogus = router.from_client(r#"{"jsonrpc":"2.0","id":3,"method":"tools/list","params":{"cursor":"nope"}}"#);
+        assert_eq!(parse(&bogus[0]).1["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_fan_out_answers_without_targets_past_the_timeout() {
+        let mut router = Router::new(vec!["a".to_string(), "b".to_string()], TIMEOUT);
+        let sent = router.from_client(r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#);
+        let answered = answer_all(&mut router, &sent[..1], |_, _| json!({"tools": [{"name": "tool"}]}));
+        assert!(answered.is_empty());
+        assert!(router.expire(Instant::now()).is_empty());
+
+        let deadline = router.next_deadline().unwrap();
+        let expired = router.expire(deadline);
+        let (_, listing) = parse(&expired[0]);
+        assert_eq!(listing["result"]["tools"].as_array().unwrap().len(), 1);
+        assert!(router.next_deadline().is_none());
+
+        // The slow target's answer no longer reaches the client
+        let (target, request) = parse(&sent[1]);
+        let late = json!({"jsonrpc": "2.0", "id": request["id"], "result": {"tools": []}});
+        assert!(router.from_target(target.unwrap(), &late.to_string()).is_empty());
+    }
+
+    #[test]
+    fn test_target_names_are_validated() {
+        let target = |name: &str| TargetConfig { name: name.to_string(), command: "server".to_string(), args: vec![], env: HashMap::new(), cwd: None };
+        assert!(validate_targets(&[target("github"), target("db-1")]).is_ok());
+        assert!(validate_targets(&[target("a__b")]).is_err());
+        assert!(validate_targets(&[target("db"), target("db")]).is_err());
+        assert!(validate_targets(&[target("")]).is_err());
+    }
+}
@@ -0,0 +1,129 @@
+//! Dictionary-based detection of known-sensitive terms
+//!
+//! Employee rosters, customer lists and project codenames are known in advance
+//! and must be caught every time, whatever regex patterns or the LLM make of
+//! them. Each `[[detection.dictionaries]]` file holds one term per line (blank
+//! lines and lines starting with `#` are skipped) reported as the file's
+//! entity type. All terms go into one Aho-Corasick automaton, so a text is
+//! scanned once however long the lists are. Terms only match as whole words,
+//! and the longest term wins where several overlap ("Acme Labs" over "Acme").
+
+use crate::config::{DetectedEntity, DictionaryConfig};
+use aho_corasick::{AhoCorasick, AhoCorasickBuilder, MatchKind};
+use anyhow::Result;
+use std::collections::HashMap;
+use tracing::info;
+
+const DICTIONARY_CONFIDENCE: f64 = 1.0;
+
+#[derive(Debug, Clone)]
+pub struct Gazetteer {
+    /// Case-insensitive terms (ASCII letters), then case-sensitive ones
+    matchers: Vec<(AhoCorasick, Vec<String>)>,
+}
+
+impl Gazetteer {
+    pub fn new(dictionaries: &[DictionaryConfig]) -> Result<Self> {
+        // Term → entity type; a term listed in several files keeps the first type
+        let mut folded: HashMap<String, String> = HashMap::new();
+        let mut exact: HashMap<String, String> = HashMap::new();
+        for dictionary in dictionaries {
+            let content = std::fs::read_to_string(&dictionary.path)
+                .map_err(|e| anyhow::anyhow!("Failed to read dictionary {}: {}", dictionary.path.display(), e))?;
+            let terms = if dictionary.case_sensitive { &mut exact } else { &mut folded };
+            let mut count = 0;
+            for term in content.lines().map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#')) {
+                let term = if dictionary.case_sensitive { term.to_string() } else { term.to_ascii_lowercase() };
+                terms.entry(term).or_insert_with(|| dictionary.entity_type.clone());
+                count += 1;
+            }
+            info!("Loaded {} '{}' terms from {}", count, dictionary.entity_type, dictionary.path.display());
+        }
+
+        let mut matchers = Vec::new();
+        for (terms, case_insensitive) in [(folded, true), (exact, false)] {
+            if terms.is_empty() {
+                continue;
+            }
+            let (terms, types): (Vec<_>, Vec<_>) = terms.into_iter().unzip();
+            let automaton = AhoCorasickBuilder::new()
+                .match_kind(MatchKind::LeftmostLongest)
+                .ascii_case_insensitive(case_insensitive)
+                .build(&terms)?;
+            matchers.push((automaton, types));
+        }
+        Ok(Self { matchers })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matchers.is_empty()
+    }
+
+    pub fn detect(&self, text: &str) -> Vec<DetectedEntity> {
+        let mut entities: Vec<DetectedEntity> = Vec::new();
+        for (automaton, types) in &self.matchers {
+            for mat in automaton.find_iter(text) {
+                if !is_whole_word(text, mat.start(), mat.end())
+                    || entities.iter().any(|e| e.start < mat.end() && mat.start() < e.end)
+                {
+                    continue;
+                }
+                entities.push(DetectedEntity {
+                    entity_type: types[mat.pattern().as_usize()].clone(),
+                    original_value: text[mat.start()..mat.end()].to_string(),
+                    start: mat.start(),
+                    end: mat.end(),
+                    confidence: DICTIONARY_CONFIDENCE,
+                    ..Default::default()
+                });
+            }
+        }
+        entities
+    }
+}
+
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+    !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dictionary(dir: &tempfile::TempDir, name: &str, entity_type: &str, case_sensitive: bool, content: &str) -> DictionaryConfig {
+        let path = dir.path().join(name);
+        std::fs::write(&path, content).unwrap();
+        DictionaryConfig { path, entity_type: entity_type.to_string(), case_sensitive }
+    }
+
+    #[test]
+    fn test_terms_match_as_whole_words_longest_first() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let gazetteer = Gazetteer::new(&[
+            dictionary(&dir, "customers.txt", "organization", false, "# customers\nAcme\nAcme Labs\n\nInitech\n"),
+            dictionary(&dir, "codenames.txt", "codename", true, "BLUEJAY\n"),
+        ]).unwrap();
+
+        let text = "ACME LABS signed; Acmeville did not. Project BLUEJAY, not bluejay, for initech.";
+        let found: Vec<_> = gazetteer.detect(text).into_iter()
+            .map(|e| (e.entity_type, e.original_value))
+            .collect();
+        assert_eq!(found.len(), 3);
+        assert!(found.contains(&("organization".to_string(), "ACME LABS".to_string())));
+        assert!(found.contains(&("organization".to_string(), "initech".to_string())));
+        assert!(found.contains(&("codename".to_string(), "BLUEJAY".to_string())));
+    }
+
+    #[test]
+    fn test_missing_dictionary_is_an_error() {
+        let config = DictionaryConfig {
+            path: "/nonexistent/roster.txt".into(),
+            entity_type: "name".to_string(),
+            case_sensitive: false,
+        };
+        assert!(Gazetteer::new(&[config]).unwrap_err().to_string().contains("roster.txt"));
+        assert!(Gazetteer::new(&[]).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,159 @@
+//! Generalization of quasi-identifiers
+//!
+//! The `generalize` strategy replaces a value with a coarser one of the same
+//! shape instead of a fake, following the HIPAA Safe Harbor method: a ZIP
+//! code keeps its first three digits and the rest are zeroed (`10001-1234`
+//! becomes `10000-0000`), and the three-digit areas with fewer than 20,000
+//! people become `000`. Postal codes of other countries keep their first
+//! three letters and digits; later digits become `0` and later letters `X`
+//! (`SW1A 1AA` becomes `SW1X 0XX`). Words around the code, such as a state
+//! before a ZIP code or a city after a postal code, are kept.
+//!
+//! Ages become ten-year ranges (`52` becomes `50-59`), and ages of 90 and
+//! over become `90+`. Birth dates keep only their year (`DOB: 1971-03-15`
+//! becomes `DOB: 1971`), and the years of people aged 90 or over become the
+//! latest such year, written `<=1935` in 2025.
+
+use crate::dates;
+
+/// Three-digit ZIP areas with 20,000 people or fewer (2000 census), which
+/// Safe Harbor replaces with `000`.
+const SPARSE_ZIP3: &[&str] = &[
+    "036", "059", "063", "102", "203", "556", "692", "790", "821", "823",
+    "830", "831", "878", "879", "884", "890", "893",
+];
+
+/// Digits and letters kept at the start of a code.
+const KEPT: usize = 3;
+
+/// Youngest age Safe Harbor pools into a single group.
+const OLDEST_AGE_GROUP: u32 = 90;
+
+/// The generalized form of `value`, or `None` when `entity_type` has no
+/// generalization or `value` does not look like one.
+pub fn generalize(entity_type: &str, value: &str) -> Option<String> {
+    match entity_type {
+        "zip_code" | "zip" => zip_code(value),
+        "postal_code" | "postcode" => postal_code(value),
+        "age" => age(value),
+        "date_of_birth" | "dob" | "birth_date" => birth_year(value, dates::current_year()),
+        _ => None,
+    }
+}
+
+/// A US ZIP or ZIP+4 code cut to its three-digit area.
+pub fn zip_code(value: &str) -> Option<String> {
+    let digits: String = value.chars().filter(char::is_ascii_digit).collect();
+    if digits.len() != 5 && digits.len() != 9 {
+        return None;
+    }
+    let sparse = SPARSE_ZIP3.contains(&&digits[..KEPT]);
+    let mut seen = 0;
+    Some(value.chars()
+        .map(|c| match c.is_ascii_digit() {
+            true => {
+                seen += 1;
+                if seen <= KEPT && !sparse { c } else { '0' }
+            }
+            false => c,
+        })
+        .collect())
+}
+
+/// A postal code with everything after its first three letters and digits
+/// blanked out.
+pub fn postal_code(value: &str) -> Option<String> {
+    let tokens: Vec<&str> = value.split_inclusive(char::is_whitespace).collect();
+    let mut in_code = vec![false; tokens.len()];
+    for (i, token) in tokens.iter().enumerate() {
+        let word = token.trim_end();
+        // Letters right after the digits belong to the code ("1234 AB"); a
+        // word before the first code token or in lower case does not
+        let trailing_letters = i > 0 && in_code[i - 1] && word.len() <= 2 && word.chars().all(|c| c.is_ascii_uppercase());
+        in_code[i] = word.chars().any(|c| c.is_ascii_digit()) || trailing_letters;
+    }
+    if !in_code.contains(&true) {
+        return None;
+    }
+    let mut seen = 0;
+    Some(tokens.iter().zip(in_code)
+        .map(|(token, in_code)| match in_code {
+            true => token.chars()
+                .map(|c| {
+                    if !c.is_ascii_alphanumeric() {
+                        return c;
+                    }
+                    seen += 1;
+                    match (seen <= KEPT, c.is_ascii_digit()) {
+                        (true, _) => c,
+                        (false, true) => '0',
+                        (false, false) => 'X',
+                    }
+                })
+                .collect(),
+            false => token.to_string(),
+        })
+        .collect())
+}
+
+/// The first number in `value` replaced with its ten-year range.
+pub fn age(value: &str) -> Option<String> {
+    let start = value.find(|c: char| c.is_ascii_digit())?;
+    let len = value[start..].find(|c: char| !c.is_ascii_digit()).unwrap_or(value.len() - start);
+    let years: u32 = value[start..start + len].parse().ok()?;
+    let range = match years {
+        years if years >= OLDEST_AGE_GROUP => format!("{}+", OLDEST_AGE_GROUP),
+        years => format!("{}-{}", years / 10 * 10, years / 10 * 10 + 9),
+    };
+    Some(format!("{}{}{}", &value[..start], range, &value[start + len..]))
+}
+
+/// The first date in `value` cut to its year, as of `this_year`. `None`
+/// unless `value` contains a full date.
+pub fn birth_year(value: &str, this_year: i32) -> Option<String> {
+    let start = value.find(|c: char| c.is_ascii_digit())?;
+    let (year, len) = dates::year(&value[start..])?;
+    let oldest = i64::from(this_year) - i64::from(OLDEST_AGE_GROUP);
+    let year = if year <= oldest { format!("<={}", oldest) } else { year.to_string() };
+    Some(format!("{}{}{}", &value[..start], year, &value[start + len..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zip_codes_keep_their_area() {
+        assert_eq!(zip_code("10001").unwrap(), "10000");
+        assert_eq!(zip_code("10001-1234").unwrap(), "10000-0000");
+        assert_eq!(zip_code("NY 10001").unwrap(), "NY 10000");
+        assert_eq!(zip_code("03601").unwrap(), "00000");
+        assert!(zip_code("1234").is_none());
+    }
+
+    #[test]
+    fn test_postal_codes_keep_their_first_characters() {
+        assert_eq!(postal_code("SW1A 1AA").unwrap(), "SW1X 0XX");
+        assert_eq!(postal_code("10115 Berlin").unwrap(), "10100 Berlin");
+        assert_eq!(postal_code("1012 AB").unwrap(), "1010 XX");
+        assert_eq!(postal_code("00-950").unwrap(), "00-900");
+        assert_eq!(postal_code("1012 AB Amsterdam").unwrap(), "1010 XX Amsterdam");
+        assert_eq!(generalize("zip", "94105").unwrap(), "94100");
+        assert!(generalize("name", "94105").is_none());
+    }
+
+    #[test]
+    fn test_ages_and_birth_dates_lose_detail() {
+        assert_eq!(age("52").unwrap(), "50-59");
+        assert_eq!(age("aged 7").unwrap(), "aged 0-9");
+        assert_eq!(age("93-year-old").unwrap(), "90+-year-old");
+        assert_eq!(age("90 years old").unwrap(), "90+ years old");
+        assert!(age("unknown").is_none());
+
+        assert_eq!(birth_year("1971-03-15", 2025).unwrap(), "1971");
+        assert_eq!(birth_year("DOB: 03/15/1971", 2025).unwrap(), "DOB: 1971");
+        assert_eq!(birth_year("born 15.03.1971 in Lyon", 2025).unwrap(), "born 1971 in Lyon");
+        assert_eq!(birth_year("1931-06-01", 2025).unwrap(), "<=1935");
+        assert!(birth_year("DOB: 1971", 2025).is_none());
+    }
+}
@@ -0,0 +1,247 @@
+//! GPS coordinate generalization
+//!
+//! A latitude/longitude pair such as `40.748817, -73.985428` pinpoints a
+//! building. The `coordinates` pattern finds signed decimal pairs, and three
+//! strategies make them coarse enough to share:
+//!
+//! - `geo_jitter` moves the point in a random direction by up to
+//!   `[faker.geo] jitter_km`, keeping the original's number of decimals.
+//! - `geo_city` replaces the point with the centre of the nearest large city.
+//! - `geo_truncate` cuts both values to `precision` decimal places (2 decimals
+//!   is about 1.1 km), which keeps nearby points together.
+//!
+//! The `fake` strategy puts the point near a random city instead. The
+//! separator between the two values is kept as written.
+
+use anyhow::Result;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+pub const COORDINATES_TYPE: &str = "coordinates";
+
+const EARTH_RADIUS_KM: f64 = 6371.0;
+const KM_PER_DEGREE: f64 = 111.32;
+
+/// Large cities, as (name, latitude, longitude) of their centre.
+const CITIES: &[(&str, f64, f64)] = &[
+    ("New York", 40.7128, -74.0060),
+    ("Los Angeles", 34.0522, -118.2437),
+    ("Chicago", 41.8781, -87.6298),
+    ("Houston", 29.7604, -95.3698),
+    ("Denver", 39.7392, -104.9903),
+    ("Seattle", 47.6062, -122.3321),
+    ("San Francisco", 37.7749, -122.4194),
+    ("Atlanta", 33.7490, -84.3880),
+    ("Miami", 25.7617, -80.1918),
+    ("Toronto", 43.6532, -79.3832),
+    ("Vancouver", 49.2827, -123.1207),
+    ("Montreal", 45.5017, -73.5673),
+    ("Mexico City", 19.4326, -99.1332),
+    ("Bogotá", 4.7110, -74.0721),
+    ("Lima", -12.0464, -77.0428),
+    ("Santiago", -33.4489, -70.6693),
+    ("Buenos Aires", -34.6037, -58.3816),
+    ("São Paulo", -23.5505, -46.6333),
+    ("Rio de Janeiro", -22.9068, -43.1729),
+    ("London", 51.5074, -0.1278),
+    ("Dublin", 53.3498, -6.2603),
+    ("Paris", 48.8566, 2.3522),
+    ("Madrid", 40.4168, -3.7038),
+    ("Lisbon", 38.7223, -9.1393),
+    ("Berlin", 52.5200, 13.4050),
+    ("Amsterdam", 52.3676, 4.9041),
+    ("Rome", 41.9028, 12.4964),
+    ("Milan", 45.4642, 9.1900),
+    ("Vienna", 48.2082, 16.3738),
+    ("Stockholm", 59.3293, 18.0686),
+    ("Warsaw", 52.2297, 21.0122),
+    ("Athens", 37.9838, 23.7275),
+    ("Istanbul", 41.0082, 28.9784),
+    ("Moscow", 55.7558, 37.6173),
+    ("Cairo", 30.0444, 31.2357),
+    ("Lagos", 6.5244, 3.3792),
+    ("Nairobi", -1.2921, 36.8219),
+    ("Johannesburg", -26.2041, 28.0473),
+    ("Dubai", 25.2048, 55.2708),
+    ("Tehran", 35.6892, 51.3890),
+    ("Mumbai", 19.0760, 72.8777),
+    ("Delhi", 28.7041, 77.1025),
+    ("Bangalore", 12.9716, 77.5946),
+    ("Bangkok", 13.7563, 100.5018),
+    ("Singapore", 1.3521, 103.8198),
+    ("Jakarta", -6.2088, 106.8456),
+    ("Manila", 14.5995, 120.9842),
+    ("Hong Kong", 22.3193, 114.1694),
+    ("Shanghai", 31.2304, 121.4737),
+    ("Beijing", 39.9042, 116.4074),
+    ("Seoul", 37.5665, 126.9780),
+    ("Tokyo", 35.6762, 139.6503),
+    ("Osaka", 34.6937, 135.5023),
+    ("Sydney", -33.8688, 151.2093),
+    ("Melbourne", -37.8136, 144.9631),
+    ("Perth", -31.9505, 115.8605),
+    ("Auckland", -36.8485, 174.7633),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GeoFakerConfig {
+    /// Largest distance `geo_jitter` moves a point, in kilometres
+    #[serde(default = "default_jitter_km")]
+    pub jitter_km: f64,
+    /// Decimal places `geo_truncate` keeps
+    #[serde(default = "default_precision")]
+    pub precision: u32,
+}
+
+impl Default for GeoFakerConfig {
+    fn default() -> Self {
+        Self {
+            jitter_km: default_jitter_km(),
+            precision: default_precision(),
+        }
+    }
+}
+
+fn default_jitter_km() -> f64 {
+    5.0
+}
+
+fn default_precision() -> u32 {
+    2
+}
+
+impl GeoFakerConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(self.jitter_km.is_finite() && self.jitter_km > 0.0) {
+            return Err(anyhow::anyhow!("faker.geo.jitter_km must be a positive number of kilometres"));
+        }
+        if self.precision > 6 {
+            return Err(anyhow::anyhow!("faker.geo.precision must be at most 6 decimal places"));
+        }
+        Ok(())
+    }
+}
+
+/// A parsed `latitude, longitude` pair and how it was written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Point<'v> {
+    pub lat: f64,
+    pub lon: f64,
+    lat_decimals: usize,
+    lon_decimals: usize,
+    separator: &'v str,
+}
+
+impl<'v> Point<'v> {
+    /// `None` unless `value` is two decimal degrees in range, separated by a comma.
+    pub fn parse(value: &'v str) -> Option<Self> {
+        let comma = value.find(',')?;
+        let lat = value[..comma].trim_end();
+        let lon_start = comma + 1 + (value[comma + 1..].len() - value[comma + 1..].trim_start().len());
+        let lon = value[lon_start..].trim_end();
+        let (lat_value, lon_value): (f64, f64) = (lat.parse().ok()?, lon.parse().ok()?);
+        if !(-90.0..=90.0).contains(&lat_value) || !(-180.0..=180.0).contains(&lon_value) {
+            return None;
+        }
+        Some(Self {
+            lat: lat_value,
+            lon: lon_value,
+            lat_decimals: decimals(lat),
+            lon_decimals: decimals(lon),
+            separator: &value[lat.len()..lon_start],
+        })
+    }
+
+    /// `lat` and `lon` written like this point.
+    fn write(&self, lat: f64, lon: f64) -> String {
+        format!("{:.*}{}{:.*}", self.lat_decimals, lat + 0.0, self.separator, self.lon_decimals, lon + 0.0)
+    }
+}
+
+fn decimals(value: &str) -> usize {
+    value.split_once('.').map_or(0, |(_, fraction)| fraction.len())
+}
+
+/// `value` moved by up to `max_km` in a random direction.
+pub fn jitter(value: &str, max_km: f64, rng: &mut impl Rng) -> Option<String> {
+    let point = Point::parse(value)?;
+    let (lat, lon) = offset(point.lat, point.lon, max_km, rng);
+    Some(point.write(lat, lon))
+}
+
+/// The centre of the city nearest to `value`.
+pub fn snap_to_city(value: &str) -> Option<String> {
+    let point = Point::parse(value)?;
+    let (_, lat, lon) = CITIES.iter()
+        .min_by(|a, b| distance_km(point.lat, point.lon, a.1, a.2).total_cmp(&distance_km(point.lat, point.lon, b.1, b.2)))?;
+    Some(point.write(*lat, *lon))
+}
+
+/// `value` cut (not rounded) to `precision` decimal places.
+pub fn truncate(value: &str, precision: u32) -> Option<String> {
+    let point = Point::parse(value)?;
+    let scale = 10f64.powi(precision as i32);
+    let cut = |degrees: f64| (degrees * scale).trunc() / scale;
+    let truncated = Point { lat_decimals: precision as usize, lon_decimals: precision as usize, ..point };
+    Some(truncated.write(cut(point.lat), cut(point.lon)))
+}
+
+/// A point within 10 km of a random city, written like `original` when it
+/// parses and as `lat, lon` with four decimals otherwise.
+pub fn fake(original: &str, rng: &mut impl Rng) -> String {
+    let (_, lat, lon) = CITIES[rng.gen_range(0..CITIES.len())];
+    let (lat, lon) = offset(lat, lon, 10.0, rng);
+    match Point::parse(original) {
+        Some(point) => point.write(lat, lon),
+        None => format!("{:.4}, {:.4}", lat, lon),
+    }
+}
+
+/// A uniformly random point within `max_km` of `lat`, `lon`.
+fn offset(lat: f64, lon: f64, max_km: f64, rng: &mut impl Rng) -> (f64, f64) {
+    let distance = max_km * rng.gen::<f64>().sqrt();
+    let bearing = rng.gen_range(0.0..std::f64::consts::TAU);
+    let new_lat = (lat + distance * bearing.cos() / KM_PER_DEGREE).clamp(-90.0, 90.0);
+    let km_per_lon_degree = (KM_PER_DEGREE * lat.to_radians().cos()).max(1e-6);
+    let new_lon = (lon + distance * bearing.sin() / km_per_lon_degree + 180.0).rem_euclid(360.0) - 180.0;
+    (new_lat, new_lon)
+}
+
+/// Great-circle distance between two points.
+pub fn distance_km(lat1: f64, lon1: f64, lat2: f64, lon2: f64) -> f64 {
+    let (dlat, dlon) = ((lat2 - lat1).to_radians(), (lon2 - lon1).to_radians());
+    let a = (dlat / 2.0).sin().powi(2) + lat1.to_radians().cos() * lat2.to_radians().cos() * (dlon / 2.0).sin().powi(2);
+    2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_points_keep_their_format() {
+        let point = Point::parse("40.748817, -73.985428").unwrap();
+        assert_eq!((point.lat, point.lon), (40.748817, -73.985428));
+        assert!(Point::parse("91.0, 10.0").is_none());
+        assert!(Point::parse("40.7").is_none());
+
+        assert_eq!(truncate("40.748817,-73.985428", 2).unwrap(), "40.74,-73.98");
+        assert_eq!(truncate("-0.004, 12.5", 2).unwrap(), "0.00, 12.50");
+        assert_eq!(snap_to_city("48.858370, 2.294481").unwrap(), "48.856600, 2.352200");
+    }
+
+    #[test]
+    fn test_jitter_stays_within_range() {
+        let mut rng = StdRng::seed_from_u64(3);
+        for _ in 0..50 {
+            let moved = jitter("40.748817, -73.985428", 5.0, &mut rng).unwrap();
+            let point = Point::parse(&moved).unwrap();
+            assert!(distance_km(40.748817, -73.985428, point.lat, point.lon) <= 5.01, "{}", moved);
+            assert_eq!(moved.len(), "40.748817, -73.985428".len());
+        }
+        let fake = fake("1.5,2.5", &mut rng);
+        assert!(Point::parse(&fake).is_some() && fake.contains(',') && !fake.contains(' '), "{}", fake);
+    }
+}
@@ -0,0 +1,310 @@
+//! Hashing of original values before they are used as mapping keys
+//!
+//! The mapping database never stores originals, only a hash of them. By
+//! default the hash is HMAC-SHA256 keyed with the mapping passphrase, so a
+//! stolen database cannot be brute-forced for low-entropy values such as
+//! SSNs; without encryption or a passphrase the key is a random one generated
+//! on first use and kept next to the database. The algorithm can be pinned for deployments with cryptographic compliance requirements:
+//! the SHA-2 and HMAC-SHA-2 options are FIPS 140 approved, BLAKE3 is faster
+//! but is not.
+//!
+//! Every mapping records the algorithm it is keyed with. After a change of
+//! algorithm, old mappings are found with their old hash and rekeyed the
+//! next time their value shows up; see `MappingStore`.
+
+use crate::config::MappingConfig;
+use crate::crypto;
+use crate::secrets;
+use anyhow::Result;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use std::fmt;
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, info, warn};
+
+const HASH_KEY_CONTEXT: &[u8] = b"mcp-server-conceal mapping hash v1";
+
+/// Name recorded for databases created before the algorithm was configurable,
+/// whose keys came from the standard library's (unstable, unkeyed) SipHash.
+pub const LEGACY_ALGORITHM: &str = "siphash";
+
+/// Name recorded for HMAC-SHA256 keyed with the database's key file rather
+/// than the mapping passphrase.
+pub const KEY_FILE_ALGORITHM: &str = "hmac-sha256-keyfile";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HashAlgorithm {
+    #[serde(rename = "sha256")]
+    Sha256,
+    #[serde(rename = "sha512")]
+    Sha512,
+    #[serde(rename = "blake3")]
+    Blake3,
+    /// Keyed with the mapping passphrase, so hashes of low-entropy values
+    /// such as SSNs cannot be brute-forced without it
+    #[serde(rename = "hmac-sha256")]
+    HmacSha256,
+    #[serde(rename = "hmac-sha512")]
+    HmacSha512,
+}
+
+impl HashAlgorithm {
+    /// Name as written in the config file and the database metadata.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Sha256 => "sha256",
+            Self::Sha512 => "sha512",
+            Self::Blake3 => "blake3",
+            Self::HmacSha256 => "hmac-sha256",
+            Self::HmacSha512 => "hmac-sha512",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Self::Sha256, Self::Sha512, Self::Blake3, Self::HmacSha256, Self::HmacSha512]
+            .into_iter()
+            .find(|algorithm| algorithm.name() == name)
+    }
+
+    pub fn is_fips_approved(self) -> bool {
+        self != Self::Blake3
+    }
+
+    pub fn is_keyed(self) -> bool {
+        matches!(self, Self::HmacSha256 | Self::HmacSha512)
+    }
+}
+
+impl fmt::Display for HashAlgorithm {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Hashes values with one algorithm (and key, for the HMAC variants).
+#[derive(Clone)]
+pub struct ValueHasher {
+    /// `None` for databases keyed by the legacy SipHash
+    algorithm: Option<HashAlgorithm>,
+    key: Option<[u8; 32]>,
+    /// Keyed with the key file instead of the passphrase
+    key_file: bool,
+}
+
+impl ValueHasher {
+    pub fn new(algorithm: HashAlgorithm, key: Option<[u8; 32]>) -> Result<Self> {
+        if algorithm.is_keyed() && key.is_none() {
+            return Err(anyhow::anyhow!("The {} hash algorithm needs a key", algorithm));
+        }
+        Ok(Self { algorithm: Some(algorithm), key, key_file: false })
+    }
+
+    /// The configured algorithm, loading the mapping passphrase for HMAC
+    /// variants. Unset means HMAC-SHA256, keyed with the passphrase if
+    /// encryption is on and one is available, and otherwise with the
+    /// database's key file, generated on first use: unkeyed hashes of SSNs or
+    /// phone numbers are found again by hashing every candidate, so plain
+    /// SHA-256 is only used when pinned. Without encryption the passphrase
+    /// source is not read.
+    pub fn from_config(config: &MappingConfig) -> Result<Self> {
+        let Some(algorithm) = config.hash_algorithm else {
+            if config.encryption {
+                match secrets::resolve_passphrase(config) {
+                    Ok(Some(passphrase)) => {
+                        return Self::new(HashAlgorithm::HmacSha256, Some(crypto::derive_key(&passphrase, HASH_KEY_CONTEXT)));
+                    }
+                    Ok(None) => debug!("No mapping passphrase is set, keying mapping hashes with the key file"),
+                    Err(e) => warn!("The mapping passphrase could not be read, keying mapping hashes with the key file: {}", e),
+                }
+            }
+            return Self::with_key_file(config, true);
+        };
+        Self::for_algorithm(algorithm, config)
+    }
+
+    /// Hasher for an algorithm name recorded in a database, to find mappings
+    /// keyed before the algorithm changed.
+    pub fn from_recorded(name: &str, config: &MappingConfig) -> Result<Self> {
+        if name == LEGACY_ALGORITHM {
+            return Ok(Self::legacy());
+        }
+        if name == KEY_FILE_ALGORITHM {
+            return Self::with_key_file(config, false);
+        }
+        let algorithm = HashAlgorithm::from_name(name)
+            .ok_or_else(|| anyhow::anyhow!("Unknown hash algorithm '{}' in mapping database", name))?;
+        Self::for_algorithm(algorithm, config)
+    }
+
+    fn for_algorithm(algorithm: HashAlgorithm, config: &MappingConfig) -> Result<Self> {
+        let key = if algorithm.is_keyed() {
+            let passphrase = secrets::require_passphrase(config).map_err(|e| {
+                anyhow::anyhow!("The {} hash algorithm needs the mapping passphrase: {}", algorithm, e)
+            })?;
+            Some(crypto::derive_key(&passphrase, HASH_KEY_CONTEXT))
+        } else {
+            None
+        };
+        Self::new(algorithm, key)
+    }
+
+    /// HMAC-SHA256 keyed with the database's key file, which is generated
+    /// if `create` is set and there is none yet.
+    fn with_key_file(config: &MappingConfig, create: bool) -> Result<Self> {
+        let key = match key_file_path(&config.database_path) {
+            // Mappings of an in-memory database do not outlive the process
+            None => rand::random::<[u8; 32]>(),
+            Some(path) => load_key_file(&path, create)?,
+        };
+        Ok(Self { algorithm: Some(HashAlgorithm::HmacSha256), key: Some(key), key_file: true })
+    }
+
+    pub(crate) fn legacy() -> Self {
+        Self { algorithm: None, key: None, key_file: false }
+    }
+
+    /// Algorithm name as recorded in the database metadata.
+    pub fn name(&self) -> &'static str {
+        match self.key_file {
+            true => KEY_FILE_ALGORITHM,
+            false => self.algorithm.map_or(LEGACY_ALGORITHM, HashAlgorithm::name),
+        }
+    }
+
+    pub fn is_fips_approved(&self) -> bool {
+        self.algorithm.is_some_and(HashAlgorithm::is_fips_approved)
+    }
+
+    pub fn hash(&self, value: &str) -> String {
+        let key = self.key.as_ref().map(|k| k.as_slice()).unwrap_or_default();
+        match self.algorithm {
+            Some(HashAlgorithm::Sha256) => hex::encode(Sha256::digest(value.as_bytes())),
+            Some(HashAlgorithm::Sha512) => hex::encode(Sha512::digest(value.as_bytes())),
+            Some(HashAlgorithm::Blake3) => blake3::hash(value.as_bytes()).to_hex().to_string(),
+            Some(HashAlgorithm::HmacSha256) => crypto::keyed_hash(key, value.as_bytes()),
+            Some(HashAlgorithm::HmacSha512) => {
+                let mut mac = <Hmac<Sha512> as Mac>::new_from_slice(key).expect("HMAC accepts any key length");
+                mac.update(value.as_bytes());
+                hex::encode(mac.finalize().into_bytes())
+            }
+            None => {
+                use std::collections::hash_map::DefaultHasher;
+                use std::hash::{Hash, Hasher};
+
+                let mut hasher = DefaultHasher::new();
+                value.hash(&mut hasher);
+                format!("{:x}", hasher.finish())
+            }
+        }
+    }
+}
+
+/// Key file of a database: `mappings.db.key` next to `mappings.db`. `None`
+/// for an in-memory database.
+pub fn key_file_path(database_path: &Path) -> Option<PathBuf> {
+    if database_path == Path::new(":memory:") {
+        return None;
+    }
+    let mut name = database_path.as_os_str().to_owned();
+    name.push(".key");
+    Some(PathBuf::from(name))
+}
+
+/// Reads the hex key in `path`, first writing a random one if there is none
+/// and `create` is set. The key is written to a temporary file and linked
+/// into place, so a proxy starting at the same time never reads half a key.
+fn load_key_file(path: &Path, create: bool) -> Result<[u8; 32]> {
+    if create && !path.exists() {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(format!(".{}.tmp", uuid::Uuid::new_v4()));
+        let temporary = PathBuf::from(temporary);
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create_new(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+        let written = options.open(&temporary)
+            .and_then(|mut file| file.write_all(hex::encode(rand::random::<[u8; 32]>()).as_bytes()).and_then(|_| file.sync_all()))
+            .and_then(|_| std::fs::hard_link(&temporary, path));
+        std::fs::remove_file(&temporary).ok();
+        match written {
+            Ok(()) => info!("Generated mapping hash key {}", path.display()),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+            Err(e) => return Err(anyhow::anyhow!("Failed to write mapping hash key {}: {}", path.display(), e)),
+        }
+    }
+    let text = std::fs::read_to_string(path).map_err(|e| {
+        anyhow::anyhow!("Failed to read mapping hash key {}: {}", path.display(), e)
+    })?;
+    hex::decode(text.trim()).ok()
+        .and_then(|bytes| <[u8; 32]>::try_from(bytes).ok())
+        .ok_or_else(|| anyhow::anyhow!("Mapping hash key {} is not 64 hex digits", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_algorithms_produce_distinct_stable_hashes() {
+        let key = Some([7u8; 32]);
+        let hashes: Vec<String> = [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha512,
+            HashAlgorithm::Blake3,
+            HashAlgorithm::HmacSha256,
+            HashAlgorithm::HmacSha512,
+        ]
+        .into_iter()
+        .map(|algorithm| ValueHasher::new(algorithm, key).unwrap().hash("jane@acme.com"))
+        .collect();
+
+        assert_eq!(
+            hashes[0],
+            hex::encode(Sha256::digest(b"jane@acme.com"))
+        );
+        assert_eq!(hashes[1].len(), 128);
+        for (i, hash) in hashes.iter().enumerate() {
+            assert!(hashes[i + 1..].iter().all(|other| other != hash));
+        }
+
+        let other_key = ValueHasher::new(HashAlgorithm::HmacSha256, Some([8u8; 32])).unwrap();
+        assert_ne!(other_key.hash("jane@acme.com"), hashes[3]);
+        assert!(ValueHasher::new(HashAlgorithm::HmacSha512, None).is_err());
+        assert!(!HashAlgorithm::Blake3.is_fips_approved());
+        assert_eq!(HashAlgorithm::from_name("hmac-sha512"), Some(HashAlgorithm::HmacSha512));
+        assert_eq!(HashAlgorithm::from_name("md5"), None);
+    }
+
+    #[test]
+    fn test_default_without_a_passphrase_is_keyed() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = MappingConfig { database_path: dir.path().join("mappings.db"), ..MappingConfig::default() };
+        let hasher = ValueHasher::from_config(&config).unwrap();
+        assert_eq!(hasher.name(), KEY_FILE_ALGORITHM);
+        assert_ne!(hasher.hash("123-45-6789"), hex::encode(Sha256::digest(b"123-45-6789")));
+
+        // The key is kept for the next start and for rekeying
+        assert_eq!(ValueHasher::from_config(&config).unwrap().hash("123-45-6789"), hasher.hash("123-45-6789"));
+        let recorded = ValueHasher::from_recorded(KEY_FILE_ALGORITHM, &config).unwrap();
+        assert_eq!(recorded.hash("123-45-6789"), hasher.hash("123-45-6789"));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = std::fs::metadata(dir.path().join("mappings.db.key")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+
+        let other = MappingConfig { database_path: dir.path().join("other.db"), ..MappingConfig::default() };
+        assert_ne!(ValueHasher::from_config(&other).unwrap().hash("123-45-6789"), hasher.hash("123-45-6789"));
+        let missing = MappingConfig { database_path: dir.path().join("missing.db"), ..MappingConfig::default() };
+        assert!(ValueHasher::from_recorded(KEY_FILE_ALGORITHM, &missing).is_err());
+    }
+}
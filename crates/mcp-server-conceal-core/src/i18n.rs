@@ -0,0 +1,466 @@
+//! Message catalogs for text shown to operators and auditors
+//!
+//! Reports (the HTML scan, the session summary), the output of the CLI's
+//! reporting commands and text the proxy injects into the MCP stream are
+//! looked up here by key, in the language of `i18n.locale`. English,
+//! German, French and Spanish are built in; an operator catalog, a TOML file
+//! of `key = "text"` lines, adds a language or rewords built-in messages.
+//! Keys missing from a catalog fall back to English.
+//!
+//! Other log lines, errors and audit events stay English so they can be searched
+//! and parsed the same way everywhere.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::PathBuf;
+use tracing::warn;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct I18nConfig {
+    /// Language tag such as `en`, `de_DE` or `fr-CA`; the language part selects the catalog
+    #[serde(default = "default_locale")]
+    pub locale: String,
+    /// Operator catalog of `key = "text"` lines, used before the built-in one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub catalog: Option<PathBuf>,
+}
+
+impl Default for I18nConfig {
+    fn default() -> Self {
+        Self { locale: default_locale(), catalog: None }
+    }
+}
+
+fn default_locale() -> String {
+    "en".to_string()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Language {
+    En,
+    De,
+    Fr,
+    Es,
+}
+
+impl Language {
+    fn parse(locale: &str) -> Option<Self> {
+        let tag = locale.trim().to_ascii_lowercase().replace('-', "_");
+        match tag.split('_').next().unwrap_or_default() {
+            "en" => Some(Language::En),
+            "de" => Some(Language::De),
+            "fr" => Some(Language::Fr),
+            "es" => Some(Language::Es),
+            _ => None,
+        }
+    }
+
+    fn messages(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            Language::En => EN,
+            Language::De => DE,
+            Language::Fr => FR,
+            Language::Es => ES,
+        }
+    }
+}
+
+/// Messages in the configured language.
+#[derive(Debug, Clone)]
+pub struct Catalog {
+    /// BCP 47 language tag, for the `lang` attribute of HTML reports
+    lang: String,
+    builtin: Language,
+    overrides: HashMap<String, String>,
+}
+
+impl Default for Catalog {
+    fn default() -> Self {
+        Self { lang: "en".to_string(), builtin: Language::En, overrides: HashMap::new() }
+    }
+}
+
+impl Catalog {
+    pub fn new(config: &I18nConfig) -> Result<Self> {
+        let overrides: HashMap<String, String> = match &config.catalog {
+            Some(path) => {
+                let contents = std::fs::read_to_string(path)
+                    .map_err(|e| anyhow::anyhow!("Failed to read message catalog {}: {}", path.display(), e))?;
+                toml::from_str(&contents)
+                    .map_err(|e| anyhow::anyhow!("Invalid message catalog {}: {}", path.display(), e))?
+            }
+            None => HashMap::new(),
+        };
+        let builtin = Language::parse(&config.locale).unwrap_or_else(|| {
+            if overrides.is_empty() {
+                warn!("No message catalog for locale '{}', using English", config.locale);
+            }
+            Language::En
+        });
+        let lang = config.locale.trim().replace('_', "-");
+        Ok(Self { lang, builtin, overrides })
+    }
+
+    pub fn lang(&self) -> &str {
+        &self.lang
+    }
+
+    /// The message for `key`, without arguments.
+    pub fn text<'a>(&'a self, key: &'a str) -> &'a str {
+        if let Some(text) = self.overrides.get(key) {
+            return text;
+        }
+        lookup(self.builtin.messages(), key)
+            .or_else(|| lookup(EN, key))
+            .unwrap_or(key)
+    }
+
+    /// The message for `key` with each `{name}` replaced by its argument.
+    pub fn format(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        let mut message = self.text(key).to_string();
+        for (name, value) in args {
+            message = message.replace(&format!("{{{}}}", name), &value.to_string());
+        }
+        message
+    }
+}
+
+fn lookup(messages: &'static [(&'static str, &'static str)], key: &str) -> Option<&'static str> {
+    messages.iter().find(|(k, _)| *k == key).map(|(_, text)| *text)
+}
+
+const EN: &[(&str, &str)] = &[
+    ("approval_withheld", "Response withheld by the privacy proxy's approval policy"),
+    ("approval_withheld_request", "Request withheld by the privacy proxy's approval policy"),
+    ("backfill_entities", "LLM entities found: {found}, not covered by regex: {missed}"),
+    ("backfill_processed", "Processed {processed} queued text(s), {remaining} remaining"),
+    ("ctl_bypass_entry", "{id}  {scope}  expires_at={expires_at}  reason={reason}"),
+    ("ctl_bypass_issued", "Issued bypass {id} for {scope}, expires in {seconds}s"),
+    ("ctl_bypass_revoked", "Revoked bypass {id}"),
+    ("ctl_decision_recorded", "Recorded '{decision}' for held message {id}"),
+    ("ctl_no_bypass_token", "No bypass token with id {id}"),
+    ("ctl_no_bypass_tokens", "No active bypass tokens"),
+    ("ctl_no_held_message", "No held message with id {id}"),
+    ("ctl_no_pending", "No messages awaiting approval"),
+    ("ctl_pending_entry", "{id}  {direction}  types={types}  method={method}  expires_at={expires_at}"),
+    ("degradation_bypassed", "forwarded unprocessed under a bypass token"),
+    ("degradation_double_anonymized", "fakes detected and replaced again"),
+    ("degradation_fail_open", "forwarded unprocessed after an error"),
+    ("degradation_field_skipped", "some strings left unprocessed after an error"),
+    ("degradation_latency_budget", "regex-only detection, over the latency budget"),
+    ("degradation_leaked", "original values left after replacement"),
+    ("degradation_llm_circuit_open", "regex-only detection, LLM circuit open"),
+    ("degradation_llm_failed", "regex-only detection, LLM request failed"),
+    ("degradation_llm_unavailable", "regex-only detection, LLM unreachable"),
+    ("degradation_oversized", "over the line size limit"),
+    ("degradation_redacted_all", "every string redacted after an error"),
+    ("degradation_timed_out", "forwarded unprocessed after the message timeout"),
+    ("degradation_withheld", "dropped after an error"),
+    ("diff_counts", "Documents: {documents}, replaced strings: {strings} ({entities} entities), replaced scalars: {scalars}"),
+    ("diff_pass", "only detected entities differ"),
+    ("direction_request", "request"),
+    ("direction_response", "response"),
+    ("error_withheld", "Response withheld: the privacy proxy could not process it"),
+    ("error_withheld_request", "Request withheld: the privacy proxy could not process it"),
+    ("export_done", "Exported {count} mapping(s) to {path}"),
+    ("import_done", "Imported {imported} mapping(s), {already_mapped} already mapped"),
+    ("import_originals_dropped", "Dropped {count} original(s): enable mapping.encryption to keep them"),
+    ("import_unusable", "Skipped {count} mapping(s) hashed with another algorithm and without an original"),
+    ("passphrase_deleted", "Deleted passphrase '{account}'"),
+    ("passphrase_generated", "Generated and stored passphrase '{account}'"),
+    ("passphrase_not_set", "Passphrase '{account}' is not set"),
+    ("passphrase_present", "Passphrase '{account}' is present"),
+    ("passphrase_stored", "Stored passphrase '{account}'"),
+    ("passphrase_was_not_set", "Passphrase '{account}' was not set"),
+    ("qa_deleted", "Deleted {count} QA sample(s)"),
+    ("qa_shown", "{shown} of {total} sample(s) shown"),
+    ("sanitize_no_logs", "No {client} logs found in {dir}"),
+    ("sanitize_summary", "Sanitized {lines} line(s), {changed} changed"),
+    ("scan_column_anonymized", "Anonymized"),
+    ("scan_column_original", "Original"),
+    ("scan_summary", "Scanned {lines} line(s), {changed} would change"),
+    ("scan_title", "mcp-server-conceal scan"),
+    ("stats_cache_entries", "LLM cache entries: {count}"),
+    ("stats_cache_reused", "reused: {reused} ({hits} hits)"),
+    ("stats_cache_ttl", "mean remaining TTL: {hours}h"),
+    ("stats_combinations", "Entity types replaced together (messages):"),
+    ("stats_mappings", "Mappings: {count}"),
+    ("stats_no_combinations", "No messages with more than one entity type recorded"),
+    ("suggest_approved_name", "Approved suggestion {id}: added '{name}' to detection.names.first_names in {path}"),
+    ("suggest_approved_pattern", "Approved suggestion {id}: added pattern '{name}' in {path}"),
+    ("suggest_dismissed", "Dismissed suggestion {id}"),
+    ("suggest_entry", "{id}  {kind}  {entity_type}  seen {count}x  {candidate}"),
+    ("suggest_none", "No suggestions seen at least {count} time(s)"),
+    ("summary_clean", "All {total} messages were processed with the configured detection"),
+    ("summary_degraded", "Session ran degraded: {degraded} of {total} messages were not fully concealed as configured"),
+    ("trust_done", "Trusted '{command}' in {path}"),
+    ("verify_config_version", "Config version: {version}"),
+    ("verify_counts", "Raw lines: {raw}, processed lines: {processed}, raw entities: {entities}"),
+    ("verify_line_counts_differ", "Line counts differ; files must contain the same messages in the same order"),
+    ("verify_pass", "no raw entities found in processed output"),
+    ("wrote_file", "Wrote {path}"),
+];
+
+const DE: &[(&str, &str)] = &[
+    ("approval_withheld", "Antwort durch die Freigaberichtlinie des Datenschutz-Proxys zurückgehalten"),
+    ("approval_withheld_request", "Anfrage durch die Freigaberichtlinie des Datenschutz-Proxys zurückgehalten"),
+    ("backfill_entities", "Vom LLM gefundene Entitäten: {found}, nicht durch Regex abgedeckt: {missed}"),
+    ("backfill_processed", "{processed} wartende(r) Text(e) verarbeitet, {remaining} verbleibend"),
+    ("ctl_bypass_entry", "{id}  {scope}  expires_at={expires_at}  reason={reason}"),
+    ("ctl_bypass_issued", "Umgehung {id} für {scope} ausgestellt, läuft in {seconds}s ab"),
+    ("ctl_bypass_revoked", "Umgehung {id} widerrufen"),
+    ("ctl_decision_recorded", "'{decision}' für zurückgehaltene Nachricht {id} erfasst"),
+    ("ctl_no_bypass_token", "Kein Umgehungs-Token mit der ID {id}"),
+    ("ctl_no_bypass_tokens", "Keine aktiven Umgehungs-Token"),
+    ("ctl_no_held_message", "Keine zurückgehaltene Nachricht mit der ID {id}"),
+    ("ctl_no_pending", "Keine Nachrichten warten auf Freigabe"),
+    ("ctl_pending_entry", "{id}  {direction}  types={types}  method={method}  expires_at={expires_at}"),
+    ("degradation_bypassed", "mit Umgehungs-Token unverarbeitet weitergeleitet"),
+    ("degradation_double_anonymized", "Ersatzwerte erkannt und erneut ersetzt"),
+    ("degradation_fail_open", "nach einem Fehler unverarbeitet weitergeleitet"),
+    ("degradation_field_skipped", "einige Zeichenketten nach einem Fehler unverarbeitet"),
+    ("degradation_latency_budget", "nur Regex-Erkennung, Latenzbudget überschritten"),
+    ("degradation_leaked", "Originalwerte nach dem Ersetzen übrig"),
+    ("degradation_llm_circuit_open", "nur Regex-Erkennung, LLM-Schaltkreis offen"),
+    ("degradation_llm_failed", "nur Regex-Erkennung, LLM-Anfrage fehlgeschlagen"),
+    ("degradation_llm_unavailable", "nur Regex-Erkennung, LLM nicht erreichbar"),
+    ("degradation_oversized", "über der Zeilengrößenbegrenzung"),
+    ("degradation_redacted_all", "nach einem Fehler alle Zeichenketten geschwärzt"),
+    ("degradation_timed_out", "nach Ablauf des Nachrichten-Timeouts unverarbeitet weitergeleitet"),
+    ("degradation_withheld", "nach einem Fehler verworfen"),
+    ("diff_counts", "Dokumente: {documents}, ersetzte Zeichenketten: {strings} ({entities} Entitäten), ersetzte Skalare: {scalars}"),
+    ("diff_pass", "nur erkannte Entitäten unterscheiden sich"),
+    ("direction_request", "Anfrage"),
+    ("direction_response", "Antwort"),
+    ("error_withheld", "Antwort zurückgehalten: Der Datenschutz-Proxy konnte sie nicht verarbeiten"),
+    ("error_withheld_request", "Anfrage zurückgehalten: Der Datenschutz-Proxy konnte sie nicht verarbeiten"),
+    ("export_done", "{count} Zuordnung(en) nach {path} exportiert"),
+    ("import_done", "{imported} Zuordnung(en) importiert, {already_mapped} bereits vorhanden"),
+    ("import_originals_dropped", "{count} Original(e) verworfen: mapping.encryption aktivieren, um sie zu behalten"),
+    ("import_unusable", "{count} Zuordnung(en) mit anderem Hash-Algorithmus und ohne Original übersprungen"),
+    ("passphrase_deleted", "Passphrase '{account}' gelöscht"),
+    ("passphrase_generated", "Passphrase '{account}' erzeugt und gespeichert"),
+    ("passphrase_not_set", "Passphrase '{account}' ist nicht gesetzt"),
+    ("passphrase_present", "Passphrase '{account}' ist vorhanden"),
+    ("passphrase_stored", "Passphrase '{account}' gespeichert"),
+    ("passphrase_was_not_set", "Passphrase '{account}' war nicht gesetzt"),
+    ("qa_deleted", "{count} QA-Stichprobe(n) gelöscht"),
+    ("qa_shown", "{shown} von {total} Stichprobe(n) angezeigt"),
+    ("sanitize_no_logs", "Keine {client}-Logs in {dir} gefunden"),
+    ("sanitize_summary", "{lines} Zeile(n) bereinigt, {changed} geändert"),
+    ("scan_column_anonymized", "Anonymisiert"),
+    ("scan_column_original", "Original"),
+    ("scan_summary", "{lines} Zeile(n) geprüft, {changed} würden geändert"),
+    ("scan_title", "mcp-server-conceal Prüfbericht"),
+    ("stats_cache_entries", "LLM-Cache-Einträge: {count}"),
+    ("stats_cache_reused", "wiederverwendet: {reused} ({hits} Treffer)"),
+    ("stats_cache_ttl", "mittlere verbleibende TTL: {hours} h"),
+    ("stats_combinations", "Gemeinsam ersetzte Entitätstypen (Nachrichten):"),
+    ("stats_mappings", "Zuordnungen: {count}"),
+    ("stats_no_combinations", "Keine Nachrichten mit mehr als einem Entitätstyp erfasst"),
+    ("suggest_approved_name", "Vorschlag {id} übernommen: '{name}' zu detection.names.first_names in {path} hinzugefügt"),
+    ("suggest_approved_pattern", "Vorschlag {id} übernommen: Muster '{name}' in {path} hinzugefügt"),
+    ("suggest_dismissed", "Vorschlag {id} verworfen"),
+    ("suggest_entry", "{id}  {kind}  {entity_type}  gesehen {count}x  {candidate}"),
+    ("suggest_none", "Keine Vorschläge, die mindestens {count}-mal gesehen wurden"),
+    ("summary_clean", "Alle {total} Nachrichten wurden mit der konfigurierten Erkennung verarbeitet"),
+    ("summary_degraded", "Sitzung lief eingeschränkt: {degraded} von {total} Nachrichten wurden nicht vollständig wie konfiguriert verborgen"),
+    ("trust_done", "'{command}' in {path} als vertrauenswürdig eingetragen"),
+    ("verify_config_version", "Konfigurationsversion: {version}"),
+    ("verify_counts", "Rohzeilen: {raw}, verarbeitete Zeilen: {processed}, Roh-Entitäten: {entities}"),
+    ("verify_line_counts_differ", "Zeilenzahlen unterscheiden sich; die Dateien müssen dieselben Nachrichten in derselben Reihenfolge enthalten"),
+    ("verify_pass", "keine Roh-Entitäten in der verarbeiteten Ausgabe gefunden"),
+    ("wrote_file", "{path} geschrieben"),
+];
+
+const FR: &[(&str, &str)] = &[
+    ("approval_withheld", "Réponse retenue par la politique d'approbation du proxy de confidentialité"),
+    ("approval_withheld_request", "Requête retenue par la politique d'approbation du proxy de confidentialité"),
+    ("backfill_entities", "Entités trouvées par le LLM : {found}, non couvertes par les regex : {missed}"),
+    ("backfill_processed", "{processed} texte(s) en attente traité(s), {remaining} restant(s)"),
+    ("ctl_bypass_entry", "{id}  {scope}  expires_at={expires_at}  reason={reason}"),
+    ("ctl_bypass_issued", "Contournement {id} émis pour {scope}, expire dans {seconds}s"),
+    ("ctl_bypass_revoked", "Contournement {id} révoqué"),
+    ("ctl_decision_recorded", "'{decision}' enregistré pour le message retenu {id}"),
+    ("ctl_no_bypass_token", "Aucun jeton de contournement avec l'identifiant {id}"),
+    ("ctl_no_bypass_tokens", "Aucun jeton de contournement actif"),
+    ("ctl_no_held_message", "Aucun message retenu avec l'identifiant {id}"),
+    ("ctl_no_pending", "Aucun message en attente d'approbation"),
+    ("ctl_pending_entry", "{id}  {direction}  types={types}  method={method}  expires_at={expires_at}"),
+    ("degradation_bypassed", "transmis sans traitement sous un jeton de contournement"),
+    ("degradation_double_anonymized", "valeurs fictives détectées et remplacées à nouveau"),
+    ("degradation_fail_open", "transmis sans traitement après une erreur"),
+    ("degradation_field_skipped", "certaines chaînes non traitées après une erreur"),
+    ("degradation_latency_budget", "détection par regex uniquement, budget de latence dépassé"),
+    ("degradation_leaked", "valeurs originales restantes après remplacement"),
+    ("degradation_llm_circuit_open", "détection par regex uniquement, circuit LLM ouvert"),
+    ("degradation_llm_failed", "détection par regex uniquement, échec de la requête LLM"),
+    ("degradation_llm_unavailable", "détection par regex uniquement, LLM injoignable"),
+    ("degradation_oversized", "au-delà de la taille de ligne maximale"),
+    ("degradation_redacted_all", "toutes les chaînes masquées après une erreur"),
+    ("degradation_timed_out", "transmis sans traitement après le délai du message"),
+    ("degradation_withheld", "abandonné après une erreur"),
+    ("diff_counts", "Documents : {documents}, chaînes remplacées : {strings} ({entities} entités), scalaires remplacés : {scalars}"),
+    ("diff_pass", "seules les entités détectées diffèrent"),
+    ("direction_request", "requête"),
+    ("direction_response", "réponse"),
+    ("error_withheld", "Réponse retenue : le proxy de confidentialité n'a pas pu la traiter"),
+    ("error_withheld_request", "Requête retenue : le proxy de confidentialité n'a pas pu la traiter"),
+    ("export_done", "{count} correspondance(s) exportée(s) vers {path}"),
+    ("import_done", "{imported} correspondance(s) importée(s), {already_mapped} déjà présente(s)"),
+    ("import_originals_dropped", "{count} original(aux) abandonné(s) : activez mapping.encryption pour les conserver"),
+    ("import_unusable", "{count} correspondance(s) hachée(s) avec un autre algorithme et sans original ignorée(s)"),
+    ("passphrase_deleted", "Phrase secrète '{account}' supprimée"),
+    ("passphrase_generated", "Phrase secrète '{account}' générée et enregistrée"),
+    ("passphrase_not_set", "La phrase secrète '{account}' n'est pas définie"),
+    ("passphrase_present", "La phrase secrète '{account}' est présente"),
+    ("passphrase_stored", "Phrase secrète '{account}' enregistrée"),
+    ("passphrase_was_not_set", "La phrase secrète '{account}' n'était pas définie"),
+    ("qa_deleted", "{count} échantillon(s) QA supprimé(s)"),
+    ("qa_shown", "{shown} échantillon(s) affiché(s) sur {total}"),
+    ("sanitize_no_logs", "Aucun journal {client} trouvé dans {dir}"),
+    ("sanitize_summary", "{lines} ligne(s) nettoyée(s), {changed} modifiée(s)"),
+    ("scan_column_anonymized", "Anonymisé"),
+    ("scan_column_original", "Original"),
+    ("scan_summary", "{lines} ligne(s) analysée(s), {changed} seraient modifiée(s)"),
+    ("scan_title", "Analyse mcp-server-conceal"),
+    ("stats_cache_entries", "Entrées du cache LLM : {count}"),
+    ("stats_cache_reused", "réutilisées : {reused} ({hits} accès)"),
+    ("stats_cache_ttl", "TTL restant moyen : {hours} h"),
+    ("stats_combinations", "Types d'entités remplacés ensemble (messages) :"),
+    ("stats_mappings", "Correspondances : {count}"),
+    ("stats_no_combinations", "Aucun message avec plus d'un type d'entité enregistré"),
+    ("suggest_approved_name", "Suggestion {id} approuvée : '{name}' ajouté à detection.names.first_names dans {path}"),
+    ("suggest_approved_pattern", "Suggestion {id} approuvée : motif '{name}' ajouté dans {path}"),
+    ("suggest_dismissed", "Suggestion {id} écartée"),
+    ("suggest_entry", "{id}  {kind}  {entity_type}  vu {count}x  {candidate}"),
+    ("suggest_none", "Aucune suggestion vue au moins {count} fois"),
+    ("summary_clean", "Les {total} messages ont été traités avec la détection configurée"),
+    ("summary_degraded", "Session dégradée : {degraded} messages sur {total} n'ont pas été entièrement masqués comme configuré"),
+    ("trust_done", "'{command}' approuvé dans {path}"),
+    ("verify_config_version", "Version de la configuration : {version}"),
+    ("verify_counts", "Lignes brutes : {raw}, lignes traitées : {processed}, entités brutes : {entities}"),
+    ("verify_line_counts_differ", "Le nombre de lignes diffère ; les fichiers doivent contenir les mêmes messages dans le même ordre"),
+    ("verify_pass", "aucune entité brute trouvée dans la sortie traitée"),
+    ("wrote_file", "{path} écrit"),
+];
+
+const ES: &[(&str, &str)] = &[
+    ("approval_withheld", "Respuesta retenida por la política de aprobación del proxy de privacidad"),
+    ("approval_withheld_request", "Solicitud retenida por la política de aprobación del proxy de privacidad"),
+    ("backfill_entities", "Entidades encontradas por el LLM: {found}, no cubiertas por regex: {missed}"),
+    ("backfill_processed", "{processed} texto(s) en cola procesado(s), {remaining} pendiente(s)"),
+    ("ctl_bypass_entry", "{id}  {scope}  expires_at={expires_at}  reason={reason}"),
+    ("ctl_bypass_issued", "Omisión {id} emitida para {scope}, caduca en {seconds}s"),
+    ("ctl_bypass_revoked", "Omisión {id} revocada"),
+    ("ctl_decision_recorded", "'{decision}' registrado para el mensaje retenido {id}"),
+    ("ctl_no_bypass_token", "No hay ningún token de omisión con el id {id}"),
+    ("ctl_no_bypass_tokens", "No hay tokens de omisión activos"),
+    ("ctl_no_held_message", "No hay ningún mensaje retenido con el id {id}"),
+    ("ctl_no_pending", "No hay mensajes pendientes de aprobación"),
+    ("ctl_pending_entry", "{id}  {direction}  types={types}  method={method}  expires_at={expires_at}"),
+    ("degradation_bypassed", "reenviado sin procesar con un token de omisión"),
+    ("degradation_double_anonymized", "valores ficticios detectados y reemplazados de nuevo"),
+    ("degradation_fail_open", "reenviado sin procesar tras un error"),
+    ("degradation_field_skipped", "algunas cadenas sin procesar tras un error"),
+    ("degradation_latency_budget", "detección solo con regex, presupuesto de latencia superado"),
+    ("degradation_leaked", "valores originales restantes tras el reemplazo"),
+    ("degradation_llm_circuit_open", "solo detección por regex, circuito del LLM abierto"),
+    ("degradation_llm_failed", "solo detección por regex, falló la solicitud al LLM"),
+    ("degradation_llm_unavailable", "solo detección por regex, LLM inaccesible"),
+    ("degradation_oversized", "por encima del tamaño máximo de línea"),
+    ("degradation_redacted_all", "todas las cadenas censuradas tras un error"),
+    ("degradation_timed_out", "reenviado sin procesar tras el tiempo límite del mensaje"),
+    ("degradation_withheld", "descartado tras un error"),
+    ("diff_counts", "Documentos: {documents}, cadenas reemplazadas: {strings} ({entities} entidades), escalares reemplazados: {scalars}"),
+    ("diff_pass", "solo difieren las entidades detectadas"),
+    ("direction_request", "solicitud"),
+    ("direction_response", "respuesta"),
+    ("error_withheld", "Respuesta retenida: el proxy de privacidad no pudo procesarla"),
+    ("error_withheld_request", "Solicitud retenida: el proxy de privacidad no pudo procesarla"),
+    ("export_done", "{count} asignación(es) exportada(s) a {path}"),
+    ("import_done", "{imported} asignación(es) importada(s), {already_mapped} ya existente(s)"),
+    ("import_originals_dropped", "{count} original(es) descartado(s): active mapping.encryption para conservarlos"),
+    ("import_unusable", "{count} asignación(es) con otro algoritmo de hash y sin original omitida(s)"),
+    ("passphrase_deleted", "Frase de contraseña '{account}' eliminada"),
+    ("passphrase_generated", "Frase de contraseña '{account}' generada y guardada"),
+    ("passphrase_not_set", "La frase de contraseña '{account}' no está definida"),
+    ("passphrase_present", "La frase de contraseña '{account}' está presente"),
+    ("passphrase_stored", "Frase de contraseña '{account}' guardada"),
+    ("passphrase_was_not_set", "La frase de contraseña '{account}' no estaba definida"),
+    ("qa_deleted", "{count} muestra(s) de QA eliminada(s)"),
+    ("qa_shown", "{shown} de {total} muestra(s) mostrada(s)"),
+    ("sanitize_no_logs", "No se encontraron registros de {client} en {dir}"),
+    ("sanitize_summary", "{lines} línea(s) saneada(s), {changed} modificada(s)"),
+    ("scan_column_anonymized", "Anonimizado"),
+    ("scan_column_original", "Original"),
+    ("scan_summary", "{lines} línea(s) analizada(s), {changed} cambiarían"),
+    ("scan_title", "Análisis de mcp-server-conceal"),
+    ("stats_cache_entries", "Entradas de la caché del LLM: {count}"),
+    ("stats_cache_reused", "reutilizadas: {reused} ({hits} aciertos)"),
+    ("stats_cache_ttl", "TTL restante medio: {hours} h"),
+    ("stats_combinations", "Tipos de entidad reemplazados juntos (mensajes):"),
+    ("stats_mappings", "Asignaciones: {count}"),
+    ("stats_no_combinations", "No se registraron mensajes con más de un tipo de entidad"),
+    ("suggest_approved_name", "Sugerencia {id} aprobada: '{name}' añadido a detection.names.first_names en {path}"),
+    ("suggest_approved_pattern", "Sugerencia {id} aprobada: patrón '{name}' añadido en {path}"),
+    ("suggest_dismissed", "Sugerencia {id} descartada"),
+    ("suggest_entry", "{id}  {kind}  {entity_type}  visto {count}x  {candidate}"),
+    ("suggest_none", "Ninguna sugerencia vista al menos {count} vez(es)"),
+    ("summary_clean", "Los {total} mensajes se procesaron con la detección configurada"),
+    ("summary_degraded", "Sesión degradada: {degraded} de {total} mensajes no se ocultaron por completo según la configuración"),
+    ("trust_done", "'{command}' marcado como de confianza en {path}"),
+    ("verify_config_version", "Versión de la configuración: {version}"),
+    ("verify_counts", "Líneas originales: {raw}, líneas procesadas: {processed}, entidades originales: {entities}"),
+    ("verify_line_counts_differ", "El número de líneas difiere; los archivos deben contener los mismos mensajes en el mismo orden"),
+    ("verify_pass", "no se encontraron entidades originales en la salida procesada"),
+    ("wrote_file", "Escrito {path}"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn placeholders(text: &str) -> Vec<&str> {
+        let mut names: Vec<&str> = text.split('{').skip(1).filter_map(|part| part.split_once('}').map(|(name, _)| name)).collect();
+        names.sort_unstable();
+        names
+    }
+
+    #[test]
+    fn test_builtin_catalogs_translate_every_message() {
+        for language in [Language::De, Language::Fr, Language::Es] {
+            let messages = language.messages();
+            assert_eq!(messages.len(), EN.len(), "{:?}", language);
+            for (key, english) in EN {
+                let translated = lookup(messages, key).unwrap_or_else(|| panic!("{:?} lacks {}", language, key));
+                assert_eq!(placeholders(translated), placeholders(english), "{:?} {}", language, key);
+            }
+        }
+    }
+
+    #[test]
+    fn test_locale_selects_catalog_and_falls_back_to_english() {
+        let catalog = Catalog::new(&I18nConfig { locale: "de_AT".to_string(), catalog: None }).unwrap();
+        assert_eq!(catalog.lang(), "de-AT");
+        assert_eq!(catalog.format("stats_mappings", &[("count", &3)]), "Zuordnungen: 3");
+
+        let catalog = Catalog::new(&I18nConfig { locale: "it".to_string(), catalog: None }).unwrap();
+        assert_eq!(catalog.text("scan_column_original"), "Original");
+        assert_eq!(catalog.text("no_such_key"), "no_such_key");
+    }
+
+    #[test]
+    fn test_operator_catalog_overrides_builtin_messages() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("it.toml");
+        std::fs::write(&path, "scan_column_original = \"Originale\"\nstats_mappings = \"Mappature: {count}\"\n").unwrap();
+        let catalog = Catalog::new(&I18nConfig { locale: "it".to_string(), catalog: Some(path) }).unwrap();
+
+        assert_eq!(catalog.text("scan_column_original"), "Originale");
+        assert_eq!(catalog.format("stats_mappings", &[("count", &7)]), "Mappature: 7");
+        assert_eq!(catalog.text("scan_column_anonymized"), "Anonymized");
+    }
+}
@@ -0,0 +1,198 @@
+//! Identifiers that are never PII
+//!
+//! Broad custom patterns such as `\b\d{6,}\b` or `\b[0-9a-f]{12,}\b` also
+//! match record ids, UUIDs and commit hashes, and faking those breaks the
+//! references a tool's output relies on. The formats listed in
+//! `[detection.identifiers] skip` are dropped from detection results,
+//! whether a pattern, a key hint or the LLM reported them:
+//!
+//! - `uuid`: 32 hex digits grouped 8-4-4-4-12, optionally in braces or after
+//!   `urn:uuid:`.
+//! - `git_sha`: a full git object id (40 or 64 lowercase hex digits), or an
+//!   abbreviated one of at least 7 with both digits and letters.
+//! - `numeric`: digits only, without separators. Built-in entity types (card
+//!   numbers, phone numbers, national ids; see `validators`) keep their
+//!   numeric matches, since their patterns are meant to find exactly those.
+//!
+//! Only a whole detected value is compared; an email address or sentence
+//! that contains a UUID is still reported. Secrets and key material are
+//! never skipped: a hex API key or a UUID-shaped token is a credential
+//! whatever it looks like.
+
+use crate::api_keys;
+use crate::config::{base_entity_type, DetectedEntity};
+use crate::key_material;
+use crate::pattern_packs;
+use crate::urls::SECRET_PARAM_TYPE;
+use crate::validators;
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IdentifierKind {
+    Uuid,
+    GitSha,
+    Numeric,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct IdentifierConfig {
+    /// Formats never reported as PII; none by default
+    #[serde(default)]
+    pub skip: Vec<IdentifierKind>,
+}
+
+impl IdentifierKind {
+    pub fn matches(self, value: &str) -> bool {
+        match self {
+            IdentifierKind::Uuid => is_uuid(value),
+            IdentifierKind::GitSha => is_git_sha(value),
+            IdentifierKind::Numeric => !value.is_empty() && value.bytes().all(|b| b.is_ascii_digit()),
+        }
+    }
+}
+
+fn is_uuid(value: &str) -> bool {
+    let value = value.strip_prefix("urn:uuid:")
+        .or_else(|| value.strip_prefix('{').and_then(|v| v.strip_suffix('}')))
+        .unwrap_or(value);
+    let groups: Vec<&str> = value.split('-').collect();
+    groups.len() == 5
+        && groups.iter().map(|g| g.len()).eq([8, 4, 4, 4, 12])
+        && groups.iter().all(|g| g.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+fn is_git_sha(value: &str) -> bool {
+    let hex = |b: u8| b.is_ascii_digit() || (b'a'..=b'f').contains(&b);
+    if !value.bytes().all(hex) {
+        return false;
+    }
+    match value.len() {
+        40 | 64 => true,
+        7..=39 => value.bytes().any(|b| b.is_ascii_digit()) && value.bytes().any(|b| b.is_ascii_alphabetic()),
+        _ => false,
+    }
+}
+
+/// Whether values of `entity_type` are credentials: secrets from key hints,
+/// URLs and the entropy detector, API keys and tokens, the `secrets` pattern
+/// pack, key material, and types named like `*_key`, `*_token`,
+/// `*_secret` or `*password`.
+pub fn is_secret_type(entity_type: &str) -> bool {
+    entity_type == SECRET_PARAM_TYPE
+        || api_keys::is_key_type(entity_type)
+        || key_material::placeholder(entity_type).is_some()
+        || pattern_packs::pack("secrets").is_some_and(|pack| pack.iter().any(|(name, _)| *name == entity_type))
+        || ["_key", "_token", "_secret", "password"].iter().any(|suffix| entity_type.ends_with(suffix))
+}
+
+/// Drops detected values that are identifiers of the configured kinds.
+#[derive(Debug, Clone, Default)]
+pub struct IdentifierFilter {
+    skip: Vec<IdentifierKind>,
+    /// Entity type of the entropy detector's secrets, when it is not `secret`
+    secret_type: Option<String>,
+}
+
+impl IdentifierFilter {
+    pub fn new(config: &IdentifierConfig) -> Self {
+        Self { skip: config.skip.clone(), secret_type: None }
+    }
+
+    /// Also treats `entity_type` as a secret type.
+    pub fn with_secret_type(mut self, entity_type: &str) -> Self {
+        self.secret_type = Some(entity_type.to_string());
+        self
+    }
+
+    /// Whether `entity` is an identifier rather than PII.
+    pub fn skips(&self, entity: &DetectedEntity) -> bool {
+        let value = entity.original_value.trim();
+        let base_type = base_entity_type(&entity.entity_type);
+        if is_secret_type(base_type) || self.secret_type.as_deref() == Some(base_type) {
+            return false;
+        }
+        self.skip.iter().any(|&kind| {
+            kind.matches(value) && !(kind == IdentifierKind::Numeric && validators::is_builtin(base_type))
+        })
+    }
+
+    pub fn retain(&self, mut entities: Vec<DetectedEntity>) -> Vec<DetectedEntity> {
+        if !self.skip.is_empty() {
+            entities.retain(|entity| {
+                let skipped = self.skips(entity);
+                if skipped {
+                    debug!("Not treating identifier-shaped '{}' match as PII", entity.entity_type);
+                }
+                !skipped
+            });
+        }
+        entities
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(entity_type: &str, value: &str) -> DetectedEntity {
+        DetectedEntity {
+            entity_type: entity_type.to_string(),
+            original_value: value.to_string(),
+            start: 0,
+            end: value.len(),
+            confidence: 0.9,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_identifier_formats() {
+        assert!(IdentifierKind::Uuid.matches("123e4567-e89b-12d3-a456-426614174000"));
+        assert!(IdentifierKind::Uuid.matches("{123E4567-E89B-12D3-A456-426614174000}"));
+        assert!(IdentifierKind::Uuid.matches("urn:uuid:123e4567-e89b-12d3-a456-426614174000"));
+        assert!(!IdentifierKind::Uuid.matches("123e4567-e89b-12d3-a456-42661417400"));
+
+        assert!(IdentifierKind::GitSha.matches("3d74cd6"));
+        assert!(IdentifierKind::GitSha.matches("9740e04c1b2a7f0e8d3c5b6a4f1e2d3c4b5a6978"));
+        assert!(!IdentifierKind::GitSha.matches("defaced"));
+        assert!(!IdentifierKind::GitSha.matches("3D74CD6"));
+
+        assert!(IdentifierKind::Numeric.matches("000123456"));
+        assert!(!IdentifierKind::Numeric.matches("123-45-6789"));
+    }
+
+    #[test]
+    fn test_filter_keeps_validated_numbers_and_other_values() {
+        let filter = IdentifierFilter::new(&IdentifierConfig {
+            skip: vec![IdentifierKind::Uuid, IdentifierKind::GitSha, IdentifierKind::Numeric],
+        });
+        let entities = filter.retain(vec![
+            entity("account_id", "48213377"),
+            entity("request_id", "123e4567-e89b-12d3-a456-426614174000"),
+            entity("credit_card", "4111111111111111"),
+            entity("email@user.email", "jane.roe@acme.com"),
+            entity("ref@commit", "3d74cd6"),
+        ]);
+        let kept: Vec<&str> = entities.iter().map(|e| e.entity_type.as_str()).collect();
+        assert_eq!(kept, ["credit_card", "email@user.email"]);
+        assert!(IdentifierFilter::default().retain(vec![entity("account_id", "48213377")]).len() == 1);
+    }
+
+    #[test]
+    fn test_secrets_are_never_skipped() {
+        let filter = IdentifierFilter::new(&IdentifierConfig {
+            skip: vec![IdentifierKind::Uuid, IdentifierKind::GitSha, IdentifierKind::Numeric],
+        }).with_secret_type("high_entropy");
+        let entities = filter.retain(vec![
+            entity("secret", "123e4567-e89b-12d3-a456-426614174000"),
+            entity("api_key@config.key", "9740e04c1b2a7f0e8d3c5b6a4f1e2d3c4b5a6978"),
+            entity("db_password", "20240117"),
+            entity("high_entropy", "3d74cd6a91"),
+            entity("commit", "3d74cd6a91"),
+        ]);
+        let kept: Vec<&str> = entities.iter().map(|e| e.entity_type.as_str()).collect();
+        assert_eq!(kept, ["secret", "api_key@config.key", "db_password", "high_entropy"]);
+    }
+}
@@ -0,0 +1,131 @@
+//! Identity clusters: one fake person per real person
+//!
+//! Faked independently, a name, its email address and its phone number become
+//! unrelated values ("Laura Chen" writing from `kirk.olson@example.org`), which
+//! reads wrong and makes the model treat them as different people. With
+//! `[faker.identity]` enabled, a string that mentions exactly one name links it
+//! with the emails and phones found alongside into a cluster in the mapping
+//! database. The cluster has one fake name, and emails first seen as part of it
+//! get fake addresses built from that name (`laura.chen@example.org`, or
+//! `laura.chen2@example.org` when that is taken by another address). A value
+//! belongs to the first cluster it was linked to; later mentions of any member,
+//! with new emails or phones, extend that cluster and reuse its fake name.
+
+use crate::config::{base_entity_type, DetectedEntity};
+use serde::{Deserialize, Serialize};
+
+/// Entity type a cluster is built around.
+pub const NAME_TYPE: &str = "name";
+/// Linked entity type whose fakes are derived from the cluster's fake name.
+pub const EMAIL_TYPE: &str = "email";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IdentityConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Entity types linked with the name mentioned alongside them
+    #[serde(default = "default_linked_types")]
+    pub linked_types: Vec<String>,
+}
+
+impl Default for IdentityConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            linked_types: default_linked_types(),
+        }
+    }
+}
+
+fn default_linked_types() -> Vec<String> {
+    vec![EMAIL_TYPE.to_string(), "phone".to_string()]
+}
+
+/// The entities of one string that describe one person.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cluster {
+    pub name: String,
+    /// `(entity type, value)` of every member, the name first
+    pub members: Vec<(String, String)>,
+}
+
+/// The fake person a cluster maps to.
+#[derive(Debug, Clone)]
+pub struct Persona {
+    pub name: String,
+    pub fake_name: String,
+}
+
+impl Persona {
+    /// Whether `entity` gets its fake from this persona: the clustered name
+    /// itself, or an email address found with it.
+    pub fn covers(&self, entity: &DetectedEntity) -> bool {
+        match base_entity_type(&entity.entity_type) {
+            NAME_TYPE => entity.original_value == self.name,
+            EMAIL_TYPE => true,
+            _ => false,
+        }
+    }
+}
+
+/// Links the entities of a string when it names exactly one person; with
+/// several names there is no telling whose email is whose.
+pub fn cluster(entities: &[DetectedEntity], config: &IdentityConfig) -> Option<Cluster> {
+    let mut names = entities.iter().filter(|e| base_entity_type(&e.entity_type) == NAME_TYPE);
+    let name = &names.next()?.original_value;
+    if names.any(|other| &other.original_value != name) {
+        return None;
+    }
+
+    let mut members = vec![(NAME_TYPE.to_string(), name.clone())];
+    for entity in entities {
+        let entity_type = base_entity_type(&entity.entity_type);
+        if config.linked_types.iter().any(|linked| linked == entity_type)
+            && !members.iter().any(|(t, v)| t == entity_type && v == &entity.original_value)
+        {
+            members.push((entity_type.to_string(), entity.original_value.clone()));
+        }
+    }
+    (members.len() > 1).then(|| Cluster { name: name.clone(), members })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(entity_type: &str, value: &str) -> DetectedEntity {
+        DetectedEntity {
+            entity_type: entity_type.to_string(),
+            original_value: value.to_string(),
+            start: 0,
+            end: value.len(),
+            confidence: 0.9,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_one_name_is_linked_with_its_contacts() {
+        let config = IdentityConfig::default();
+        let entities = [
+            entity("name", "Maria Keller"),
+            entity("email", "maria@corp.com"),
+            entity("phone", "555-123-4567"),
+            entity("ssn", "123-45-6789"),
+            entity("name", "Maria Keller"),
+        ];
+        let cluster = cluster(&entities, &config).unwrap();
+        assert_eq!(cluster.name, "Maria Keller");
+        assert_eq!(cluster.members.len(), 3);
+        assert_eq!(cluster.members[1], ("email".to_string(), "maria@corp.com".to_string()));
+    }
+
+    #[test]
+    fn test_nothing_is_linked_without_a_single_name() {
+        let config = IdentityConfig::default();
+        assert!(cluster(&[entity("email", "a@b.com"), entity("phone", "555-123-4567")], &config).is_none());
+        assert!(cluster(&[entity("name", "Maria Keller")], &config).is_none());
+        let two_people = [entity("name", "Maria Keller"), entity("name", "Jonas Weber"), entity("email", "a@b.com")];
+        assert!(cluster(&two_people, &config).is_none());
+    }
+}
@@ -219,6 +219,16 @@ async fn create_test_config() -> Result<(Config, OllamaConfig)> {
         endpoint: "http://localhost:11434".to_string(),
         model: "llama3.2:3b".to_string(),
         timeout_seconds: 300,
+        circuit_breaker: Default::default(),
+        concurrency: Default::default(),
+        batching: Default::default(),
+        chunking: Default::default(),
+        verification: Default::default(),
+        output_format: Default::default(),
+        auto_pull: false,
+        provider: Default::default(),
+        allow_remote: false,
+        api_key_env: None,
     };
     
     // Keep temp_dir alive by leaking it (acceptable for tests)
@@ -743,7 +753,6 @@ async fn test_regex_vs_ollama_detection_comparison() -> Result<()> {
         // Store results for summary
         comparison_results.push(DetectionComparisonResult {
             case_name: case_name.to_string(),
-            text: text.to_string(),
             regex_count: regex_entities.len(),
             llm_count: llm_entities.len(),
             regex_only_count: analysis.regex_only.len(),
@@ -790,9 +799,10 @@ async fn test_regex_vs_ollama_detection_comparison() -> Result<()> {
     // Per-case breakdown
     info!("\nPer-case Results:");
     for result in &comparison_results {
-        info!("  {}: R={}, L={}, R-only={}, L-only={}, Both={}", 
+        info!("  {}: R={}, L={}, R-only={}, L-only={}, Both={}, precision R={:.2} L={:.2}", 
               result.case_name, result.regex_count, result.llm_count, 
-              result.regex_only_count, result.llm_only_count, result.overlapping_count);
+              result.regex_only_count, result.llm_only_count, result.overlapping_count,
+              result.regex_precision, result.llm_precision);
     }
 
     // Assertions to validate the test
@@ -834,7 +844,6 @@ struct DetectionAnalysis {
 #[derive(Debug)]
 struct DetectionComparisonResult {
     case_name: String,
-    text: String,
     regex_count: usize,
     llm_count: usize,
     regex_only_count: usize,
@@ -1013,6 +1022,9 @@ async fn test_integrated_proxy_config() {
         target_cwd: Some(PathBuf::from("/tmp")),
         config: Config::default(),
         ollama_config: crate::OllamaConfig::default(),
+        debug_diff: false,
+        listen: None,
+        config_source: None,
     };
     
     assert_eq!(config.target_command, "echo");
@@ -1032,7 +1044,9 @@ fn test_module_exports() {
     use crate::{RegexDetectionEngine, FakerEngine, MappingStore};
     
     // These should all compile and be accessible
-    let _config = Config::default();
+    let mut _config = Config::default();
+    // Keeps the store and its key file out of the working directory
+    _config.mapping.database_path = PathBuf::from(":memory:");
     let _ollama_config = OllamaConfig::default();
     
     // Test that we can create the core engines (these were preserved in refactoring)
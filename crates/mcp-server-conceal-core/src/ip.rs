@@ -0,0 +1,320 @@
+//! Class- and subnet-preserving IP address faking
+//!
+//! The `ip` strategy keeps what an address says about the network without
+//! keeping the address: a private address stays in its private block, a
+//! public one stays public, link-local stays link-local. Loopback,
+//! unspecified and broadcast addresses identify nobody and are kept as is.
+//!
+//! With subnet preservation, every address in one real subnet maps into the
+//! same fake subnet: the network bits are replaced consistently and, for IPv4,
+//! the host bits are kept, so `10.1.2.5` and `10.1.2.9` become `10.77.3.5`
+//! and `10.77.3.9`. IPv6 host bits are randomized because interface
+//! identifiers can embed a MAC address.
+
+use anyhow::Result;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IpFakerConfig {
+    /// Prefix length whose subnets are mapped consistently for IPv4 (0 disables)
+    #[serde(default = "default_ipv4_subnet_bits")]
+    pub ipv4_subnet_bits: u8,
+    /// Prefix length whose subnets are mapped consistently for IPv6 (0 disables)
+    #[serde(default = "default_ipv6_subnet_bits")]
+    pub ipv6_subnet_bits: u8,
+}
+
+impl Default for IpFakerConfig {
+    fn default() -> Self {
+        Self {
+            ipv4_subnet_bits: default_ipv4_subnet_bits(),
+            ipv6_subnet_bits: default_ipv6_subnet_bits(),
+        }
+    }
+}
+
+fn default_ipv4_subnet_bits() -> u8 {
+    24
+}
+
+fn default_ipv6_subnet_bits() -> u8 {
+    64
+}
+
+impl IpFakerConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.ipv4_subnet_bits > 32 || self.ipv6_subnet_bits > 128 {
+            return Err(anyhow::anyhow!(
+                "faker.ip subnet bits must be at most 32 for IPv4 and 128 for IPv6"
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Addresses kept unchanged: (base, prefix length).
+const IPV4_KEPT: &[(u32, u8)] = &[
+    (0x0000_0000, 8),  // "this network"
+    (0x7F00_0000, 8),  // loopback
+    (0xFFFF_FFFF, 32), // broadcast
+];
+
+/// Special-purpose blocks a fake stays inside.
+const IPV4_BLOCKS: &[(u32, u8)] = &[
+    (0x0A00_0000, 8),  // 10.0.0.0/8 private
+    (0xAC10_0000, 12), // 172.16.0.0/12 private
+    (0xC0A8_0000, 16), // 192.168.0.0/16 private
+    (0x6440_0000, 10), // 100.64.0.0/10 carrier-grade NAT
+    (0xA9FE_0000, 16), // 169.254.0.0/16 link-local
+    (0xC000_0200, 24), // 192.0.2.0/24 documentation
+    (0xC633_6400, 24), // 198.51.100.0/24 documentation
+    (0xCB00_7100, 24), // 203.0.113.0/24 documentation
+    (0xE000_0000, 4),  // 224.0.0.0/4 multicast
+    (0xF000_0000, 4),  // 240.0.0.0/4 reserved
+];
+
+const IPV6_KEPT: &[(u128, u8)] = &[
+    (0, 128), // unspecified
+    (1, 128), // loopback
+];
+
+const IPV6_BLOCKS: &[(u128, u8)] = &[
+    (0xFE80 << 112, 10),        // link-local
+    (0xFC00 << 112, 7),         // unique local
+    (0xFF00 << 112, 8),         // multicast
+    (0x2001_0DB8 << 96, 32),    // documentation
+];
+
+/// Global unicast space public IPv6 fakes are drawn from.
+const IPV6_GLOBAL: (u128, u8) = (0x2000 << 112, 3);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Family {
+    V4,
+    V6,
+}
+
+impl Family {
+    fn width(self) -> u8 {
+        match self {
+            Family::V4 => 32,
+            Family::V6 => 128,
+        }
+    }
+
+    fn kept(self) -> Vec<(u128, u8)> {
+        match self {
+            Family::V4 => IPV4_KEPT.iter().map(|&(base, len)| (base as u128, len)).collect(),
+            Family::V6 => IPV6_KEPT.to_vec(),
+        }
+    }
+
+    fn blocks(self) -> Vec<(u128, u8)> {
+        match self {
+            Family::V4 => IPV4_BLOCKS.iter().map(|&(base, len)| (base as u128, len)).collect(),
+            Family::V6 => IPV6_BLOCKS.to_vec(),
+        }
+    }
+
+    /// The range a public address of this family is drawn from.
+    fn public_range(self) -> (u128, u8) {
+        match self {
+            Family::V4 => (0, 0),
+            Family::V6 => IPV6_GLOBAL,
+        }
+    }
+}
+
+/// Network bits of a `width`-bit address for a prefix of `len` bits.
+fn mask(len: u8, width: u8) -> u128 {
+    if len == 0 {
+        return 0;
+    }
+    (u128::MAX << (128 - len as u32)) >> (128 - width as u32)
+}
+
+fn in_range(value: u128, (base, len): (u128, u8), width: u8) -> bool {
+    value & mask(len, width) == base
+}
+
+fn split(addr: IpAddr) -> (Family, u128) {
+    match addr {
+        IpAddr::V4(v4) => (Family::V4, u32::from(v4) as u128),
+        IpAddr::V6(v6) => (Family::V6, u128::from(v6)),
+    }
+}
+
+fn join(family: Family, value: u128) -> IpAddr {
+    match family {
+        Family::V4 => IpAddr::V4(Ipv4Addr::from(value as u32)),
+        Family::V6 => IpAddr::V6(Ipv6Addr::from(value)),
+    }
+}
+
+fn is_kept(family: Family, value: u128) -> bool {
+    family.kept().into_iter().any(|range| in_range(value, range, family.width()))
+}
+
+/// The special block containing `value`, or the public range.
+fn block_of(family: Family, value: u128) -> (u128, u8) {
+    family.blocks()
+        .into_iter()
+        .find(|&range| in_range(value, range, family.width()))
+        .unwrap_or_else(|| family.public_range())
+}
+
+fn is_public(family: Family, value: u128) -> bool {
+    let width = family.width();
+    in_range(value, family.public_range(), width)
+        && !is_kept(family, value)
+        && !family.blocks().into_iter().any(|range| in_range(value, range, width))
+}
+
+/// A random address in the same block as `value`.
+fn random_in_block<R: Rng>(family: Family, value: u128, rng: &mut R) -> u128 {
+    let width = family.width();
+    let (base, len) = block_of(family, value);
+    let draw = |rng: &mut R| base | (rng.gen::<u128>() & !mask(len, width) & mask(width, width));
+    if (base, len) != family.public_range() {
+        return draw(rng);
+    }
+    // Public space has holes (private and special blocks); redraw until outside them
+    for _ in 0..64 {
+        let candidate = draw(rng);
+        if is_public(family, candidate) {
+            return candidate;
+        }
+    }
+    value
+}
+
+fn subnet_bits(family: Family, config: &IpFakerConfig) -> u8 {
+    match family {
+        Family::V4 => config.ipv4_subnet_bits.min(32),
+        Family::V6 => config.ipv6_subnet_bits.min(128),
+    }
+}
+
+/// The real subnet of `value` mapped consistently, e.g. `10.1.2.0/24`.
+///
+/// `None` when the value is not an IP address, subnet preservation is off, or
+/// the address is kept as is. The prefix never reaches into the block the
+/// address belongs to, so `192.168.4.7` with 8 bits yields `192.168.0.0/16`.
+pub fn subnet_of(value: &str, config: &IpFakerConfig) -> Option<String> {
+    let addr: IpAddr = value.trim().parse().ok()?;
+    let addr = unmap(addr);
+    let (family, bits) = split(addr);
+    let requested = subnet_bits(family, config);
+    if requested == 0 || is_kept(family, bits) {
+        return None;
+    }
+    let len = requested.max(block_of(family, bits).1);
+    let network = bits & mask(len, family.width());
+    Some(format!("{}/{}", join(family, network), len))
+}
+
+/// A fake subnet in the same block as `subnet` (as returned by [`subnet_of`]).
+pub fn fake_subnet<R: Rng>(subnet: &str, rng: &mut R) -> Result<String> {
+    let (network, len) = parse_subnet(subnet)?;
+    let (family, bits) = split(network);
+    let fake = random_in_block(family, bits, rng) & mask(len, family.width());
+    Ok(format!("{}/{}", join(family, fake), len))
+}
+
+fn parse_subnet(subnet: &str) -> Result<(IpAddr, u8)> {
+    let (network, len) = subnet.split_once('/')
+        .ok_or_else(|| anyhow::anyhow!("Invalid subnet '{}'", subnet))?;
+    Ok((network.parse()?, len.parse()?))
+}
+
+/// Fakes `value`, inside `fake_subnet` when given. Returns `None` when the
+/// value is not an IP address.
+pub fn fake_address<R: Rng>(value: &str, fake_subnet: Option<&str>, rng: &mut R) -> Option<String> {
+    let original: IpAddr = value.trim().parse().ok()?;
+    let addr = unmap(original);
+    let (family, bits) = split(addr);
+    if is_kept(family, bits) {
+        return Some(value.to_string());
+    }
+
+    let width = family.width();
+    let fake = match fake_subnet.and_then(|subnet| parse_subnet(subnet).ok()) {
+        Some((network, len)) => {
+            let (_, network) = split(network);
+            let host = match family {
+                Family::V4 => bits,
+                Family::V6 => rng.gen::<u128>(),
+            };
+            network | (host & !mask(len, width) & mask(width, width))
+        }
+        None => random_in_block(family, bits, rng),
+    };
+
+    let fake = join(family, fake);
+    Some(match (original, fake) {
+        // Keep IPv4-mapped IPv6 notation
+        (IpAddr::V6(_), IpAddr::V4(v4)) => v4.to_ipv6_mapped().to_string(),
+        _ => fake.to_string(),
+    })
+}
+
+fn unmap(addr: IpAddr) -> IpAddr {
+    match addr {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map(IpAddr::V4).unwrap_or(addr),
+        v4 => v4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn parse(value: &str) -> (Family, u128) {
+        split(value.parse().unwrap())
+    }
+
+    #[test]
+    fn test_fakes_stay_in_their_class() {
+        let mut rng = StdRng::seed_from_u64(7);
+        for _ in 0..50 {
+            let private = fake_address("172.20.1.9", None, &mut rng).unwrap();
+            assert!(in_range(parse(&private).1, (0xAC10_0000, 12), 32), "{}", private);
+
+            let public = fake_address("8.8.4.4", None, &mut rng).unwrap();
+            let (family, bits) = parse(&public);
+            assert!(is_public(family, bits), "{}", public);
+
+            let v6 = fake_address("2606:4700::1111", None, &mut rng).unwrap();
+            let (family, bits) = parse(&v6);
+            assert!(is_public(family, bits), "{}", v6);
+        }
+
+        assert_eq!(fake_address("127.0.0.1", None, &mut rng).unwrap(), "127.0.0.1");
+        assert_eq!(fake_address("::1", None, &mut rng).unwrap(), "::1");
+        assert!(fake_address("fe80::1", None, &mut rng).unwrap().starts_with("fe"));
+        assert!(fake_address("::ffff:10.0.0.1", None, &mut rng).unwrap().starts_with("::ffff:10."));
+        assert!(fake_address("not an ip", None, &mut rng).is_none());
+    }
+
+    #[test]
+    fn test_subnets_map_consistently() {
+        let config = IpFakerConfig::default();
+        let mut rng = StdRng::seed_from_u64(7);
+
+        assert_eq!(subnet_of("10.1.2.5", &config).as_deref(), Some("10.1.2.0/24"));
+        assert_eq!(subnet_of("2001:db8:1:2::5", &config).as_deref(), Some("2001:db8:1:2::/64"));
+        let wide = IpFakerConfig { ipv4_subnet_bits: 8, ..config.clone() };
+        assert_eq!(subnet_of("192.168.4.7", &wide).as_deref(), Some("192.168.0.0/16"));
+        assert_eq!(subnet_of("127.0.0.1", &config), None);
+
+        let fake = fake_subnet("10.1.2.0/24", &mut rng).unwrap();
+        assert!(fake.starts_with("10.") && fake.ends_with(".0/24"), "{}", fake);
+        let prefix = fake.trim_end_matches("0/24");
+        assert_eq!(fake_address("10.1.2.5", Some(&fake), &mut rng).unwrap(), format!("{}5", prefix));
+        assert_eq!(fake_address("10.1.2.9", Some(&fake), &mut rng).unwrap(), format!("{}9", prefix));
+    }
+}
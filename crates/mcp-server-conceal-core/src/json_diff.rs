@@ -0,0 +1,352 @@
+//! Anonymization-aware diff of a JSON document and its proxied version
+//!
+//! When a user reports that the proxy broke a tool's output, support needs to
+//! know whether anything beyond the replaced entities changed. Both documents
+//! are walked side by side: keys, array lengths and value types must match,
+//! and a changed string may only differ inside the spans detection finds in
+//! the original. Numbers and booleans may only change where scalar rules
+//! select them. Detection uses the configured regex engine and key hints, so
+//! values only an LLM would have found show up as unexplained changes.
+//!
+//! Reports hold JSON paths and offsets, never values, so they can be pasted
+//! into a ticket.
+
+use crate::config::Config;
+use crate::detection::RegexDetectionEngine;
+use crate::json_path::{JsonPathSegment, ScalarRules};
+use anyhow::Result;
+use serde_json::Value;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DifferenceKind {
+    /// A key of the original is missing from the anonymized document
+    MissingKey,
+    /// The anonymized document has a key the original lacks
+    AddedKey,
+    TypeChanged { original: &'static str, anonymized: &'static str },
+    LengthChanged { original: usize, anonymized: usize },
+    /// A number, boolean or null changed without a scalar rule selecting it
+    ValueChanged,
+    /// A string changed outside its detected entities, first at this byte
+    /// offset of the original
+    TextOutsideEntities { offset: usize },
+    /// The files hold different numbers of documents
+    DocumentCount { original: usize, anonymized: usize },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Difference {
+    /// 1-based; with one message per line, the line number
+    pub document: usize,
+    pub path: String,
+    pub kind: DifferenceKind,
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let DifferenceKind::DocumentCount { original, anonymized } = self.kind {
+            return write!(f, "{} document(s) in the original, {} in the anonymized file", original, anonymized);
+        }
+        write!(f, "document {} {}: ", self.document, self.path)?;
+        match &self.kind {
+            DifferenceKind::MissingKey => write!(f, "key missing from the anonymized document"),
+            DifferenceKind::AddedKey => write!(f, "key added by the anonymized document"),
+            DifferenceKind::TypeChanged { original, anonymized } => write!(f, "{} became {}", original, anonymized),
+            DifferenceKind::LengthChanged { original, anonymized } => {
+                write!(f, "array of {} element(s) became {}", original, anonymized)
+            }
+            DifferenceKind::ValueChanged => write!(f, "value changed without a scalar rule"),
+            DifferenceKind::TextOutsideEntities { offset } => {
+                write!(f, "text changed outside detected entities, from byte {}", offset)
+            }
+            DifferenceKind::DocumentCount { .. } => unreachable!(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct DiffReport {
+    pub documents: usize,
+    /// Strings that differ only inside detected entities
+    pub replaced_strings: usize,
+    /// Detected entity spans in those strings
+    pub replaced_entities: usize,
+    /// Numbers and booleans changed by scalar rules
+    pub replaced_scalars: usize,
+    pub differences: Vec<Difference>,
+}
+
+impl DiffReport {
+    pub fn passed(&self) -> bool {
+        self.differences.is_empty()
+    }
+}
+
+pub fn diff_files<P: AsRef<Path>>(config: &Config, original: P, anonymized: P) -> Result<DiffReport> {
+    let read = |path: &Path| {
+        std::fs::read_to_string(path).map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))
+    };
+    diff(config, &read(original.as_ref())?, &read(anonymized.as_ref())?)
+}
+
+/// Compares two files, each holding one JSON document or one per line.
+pub fn diff(config: &Config, original: &str, anonymized: &str) -> Result<DiffReport> {
+    let original = parse_documents(original).map_err(|e| anyhow::anyhow!("Original: {}", e))?;
+    let anonymized = parse_documents(anonymized).map_err(|e| anyhow::anyhow!("Anonymized: {}", e))?;
+
+    let mut differ = Differ {
+        engine: RegexDetectionEngine::new(&config.detection)?,
+        scalar_rules: ScalarRules::new(&config.detection.scalars)?,
+        path: Vec::new(),
+        document: 0,
+        report: DiffReport { documents: original.len(), ..DiffReport::default() },
+    };
+    if original.len() != anonymized.len() {
+        differ.report.differences.push(Difference {
+            document: original.len().min(anonymized.len()) + 1,
+            path: "$".to_string(),
+            kind: DifferenceKind::DocumentCount { original: original.len(), anonymized: anonymized.len() },
+        });
+    }
+    for (index, (a, b)) in original.iter().zip(&anonymized).enumerate() {
+        differ.document = index + 1;
+        differ.compare(a, b, None);
+    }
+    Ok(differ.report)
+}
+
+fn parse_documents(contents: &str) -> Result<Vec<Value>> {
+    if let Ok(value) = serde_json::from_str::<Value>(contents) {
+        return Ok(vec![value]);
+    }
+    contents.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| serde_json::from_str(line)
+            .map_err(|e| anyhow::anyhow!("line {} is not JSON: {}", index + 1, e)))
+        .collect()
+}
+
+struct Differ {
+    engine: RegexDetectionEngine,
+    scalar_rules: ScalarRules,
+    path: Vec<JsonPathSegment>,
+    document: usize,
+    report: DiffReport,
+}
+
+impl Differ {
+    /// `key` is the closest enclosing object key, as the pipeline tracks it.
+    fn compare(&mut self, original: &Value, anonymized: &Value, key: Option<&str>) {
+        match (original, anonymized) {
+            (Value::Object(a), Value::Object(b)) => {
+                for (child_key, value) in a {
+                    self.path.push(JsonPathSegment::Key(child_key.clone()));
+                    match b.get(child_key) {
+                        Some(other) => self.compare(value, other, Some(child_key)),
+                        None => self.flag(DifferenceKind::MissingKey),
+                    }
+                    self.path.pop();
+                }
+                for child_key in b.keys().filter(|child_key| !a.contains_key(*child_key)) {
+                    self.path.push(JsonPathSegment::Key(child_key.clone()));
+                    self.flag(DifferenceKind::AddedKey);
+                    self.path.pop();
+                }
+            }
+            (Value::Array(a), Value::Array(b)) => {
+                if a.len() != b.len() {
+                    self.flag(DifferenceKind::LengthChanged { original: a.len(), anonymized: b.len() });
+                }
+                for (index, (x, y)) in a.iter().zip(b).enumerate() {
+                    self.path.push(JsonPathSegment::Index(index));
+                    self.compare(x, y, key);
+                    self.path.pop();
+                }
+            }
+            (Value::String(a), Value::String(b)) if a != b => self.compare_text(a, b, key),
+            // A scanned number becomes a string when its fake has no digits
+            (Value::Number(_), Value::Number(_) | Value::String(_)) | (Value::Bool(_), Value::Bool(_))
+                if original != anonymized =>
+            {
+                let selected = match original {
+                    Value::Number(_) => self.scalar_rules.scans_number(&self.path),
+                    _ => self.scalar_rules.replaces_boolean(&self.path),
+                };
+                if selected {
+                    self.report.replaced_scalars += 1;
+                } else if matches!(anonymized, Value::String(_)) {
+                    self.flag(DifferenceKind::TypeChanged { original: type_name(original), anonymized: type_name(anonymized) });
+                } else {
+                    self.flag(DifferenceKind::ValueChanged);
+                }
+            }
+            (a, b) if type_name(a) != type_name(b) => {
+                self.flag(DifferenceKind::TypeChanged { original: type_name(a), anonymized: type_name(b) });
+            }
+            (a, b) if a != b => self.flag(DifferenceKind::ValueChanged),
+            _ => {}
+        }
+    }
+
+    fn compare_text(&mut self, original: &str, anonymized: &str, key: Option<&str>) {
+        let entities = self.engine.detect_for_key(key, original);
+        // The proxy replaces every occurrence of a detected value, not only
+        // the one detection reported
+        let mut spans: Vec<(usize, usize)> = entities.iter()
+            .filter(|entity| !entity.original_value.is_empty())
+            .flat_map(|entity| {
+                original.match_indices(entity.original_value.as_str())
+                    .map(|(start, value)| (start, start + value.len()))
+            })
+            .collect();
+        spans.sort_unstable();
+        let mut merged: Vec<(usize, usize)> = Vec::with_capacity(spans.len());
+        for (start, end) in spans {
+            match merged.last_mut() {
+                Some(last) if start <= last.1 => last.1 = last.1.max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+
+        match first_unexplained_change(original, anonymized, &merged) {
+            None => {
+                self.report.replaced_strings += 1;
+                self.report.replaced_entities += merged.len();
+            }
+            Some(offset) => self.flag(DifferenceKind::TextOutsideEntities { offset }),
+        }
+    }
+
+    fn flag(&mut self, kind: DifferenceKind) {
+        self.report.differences.push(Difference {
+            document: self.document,
+            path: render_path(&self.path),
+            kind,
+        });
+    }
+}
+
+/// Checks that `anonymized` is `original` with each span replaced by some
+/// text. Otherwise returns the offset in `original` of the first text
+/// outside the spans that could not be found in place.
+///
+/// The text between spans has to appear in order; matching each piece at its
+/// leftmost position leaves the most room for the ones after it.
+fn first_unexplained_change(original: &str, anonymized: &str, spans: &[(usize, usize)]) -> Option<usize> {
+    let Some((&(first_start, _), rest)) = spans.split_first() else {
+        return Some(original.bytes().zip(anonymized.bytes()).take_while(|(a, b)| a == b).count());
+    };
+    let prefix = &original[..first_start];
+    if !anonymized.starts_with(prefix) {
+        return Some(prefix.bytes().zip(anonymized.bytes()).take_while(|(a, b)| a == b).count());
+    }
+
+    let mut position = prefix.len();
+    let mut previous_end = spans[0].1;
+    for &(start, end) in rest {
+        let between = &original[previous_end..start];
+        // A replacement is never empty, so the text between starts at least one character later
+        let Some(next) = anonymized[position..].chars().next().map(|c| position + c.len_utf8()) else {
+            return Some(previous_end);
+        };
+        match anonymized[next..].find(between) {
+            Some(found) => position = next + found + between.len(),
+            None => return Some(previous_end),
+        }
+        previous_end = end;
+    }
+
+    let suffix = &original[previous_end..];
+    if anonymized.len() > position + suffix.len() && anonymized.ends_with(suffix) {
+        None
+    } else {
+        Some(previous_end)
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+fn render_path(path: &[JsonPathSegment]) -> String {
+    let mut rendered = "$".to_string();
+    for segment in path {
+        match segment {
+            JsonPathSegment::Key(key) => {
+                rendered.push('.');
+                rendered.push_str(key);
+            }
+            JsonPathSegment::Index(index) => rendered.push_str(&format!("[{}]", index)),
+        }
+    }
+    rendered
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGINAL: &str = r#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text","text":"Mail jane.roe@acme.com or bob@acme.com today"}],"isError":false}}"#;
+
+    #[test]
+    fn test_replaced_entities_are_the_only_change() {
+        let anonymized = r#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text","text":"Mail ella@example.org or kim.w@example.net today"}],"isError":false}}"#;
+
+        let report = diff(&Config::default(), ORIGINAL, anonymized).unwrap();
+
+        assert!(report.passed(), "{:?}", report.differences);
+        assert_eq!((report.documents, report.replaced_strings, report.replaced_entities), (1, 1, 2));
+    }
+
+    #[test]
+    fn test_structural_and_textual_changes_are_flagged() {
+        let anonymized = r#"{"jsonrpc":"2.0","id":"1","result":{"content":[{"type":"text","text":"Mail ella@example.org or kim.w@example.net tomorrow"},{"type":"text","text":"extra"}],"meta":{}}}"#;
+
+        let report = diff(&Config::default(), ORIGINAL, anonymized).unwrap();
+        let rendered: Vec<String> = report.differences.iter().map(ToString::to_string).collect();
+
+        assert_eq!(rendered, vec![
+            "document 1 $.id: number became string",
+            "document 1 $.result.content: array of 1 element(s) became 2",
+            "document 1 $.result.content[0].text: text changed outside detected entities, from byte 38",
+            "document 1 $.result.isError: key missing from the anonymized document",
+            "document 1 $.result.meta: key added by the anonymized document",
+        ]);
+        assert!(rendered.iter().all(|line| !line.contains("jane.roe")));
+    }
+
+    #[test]
+    fn test_documents_are_aligned_line_by_line() {
+        let original = "{\"email\":\"jane@acme.com\",\"age\":41}\n{\"note\":\"ok\"}\n";
+        let anonymized = "{\"email\":\"ella@example.org\",\"age\":42}\n";
+
+        let report = diff(&Config::default(), original, anonymized).unwrap();
+
+        assert_eq!(report.differences.len(), 2);
+        assert_eq!(report.differences[0].kind, DifferenceKind::DocumentCount { original: 2, anonymized: 1 });
+        assert_eq!(report.differences[1].kind, DifferenceKind::ValueChanged);
+        assert_eq!(report.differences[1].path, "$.age");
+        assert!(diff(&Config::default(), original, "not json").is_err());
+    }
+
+    #[test]
+    fn test_first_unexplained_change() {
+        let original = "a x@y.io b x@y.io c";
+        let spans = [(2, 8), (11, 17)];
+        assert_eq!(first_unexplained_change(original, "a AAA b BBBBBB c", &spans), None);
+        assert_eq!(first_unexplained_change(original, "a AAA B BBBBBB c", &spans), Some(8));
+        assert_eq!(first_unexplained_change(original, "a AAA b BBBBBB", &spans), Some(17));
+        assert_eq!(first_unexplained_change(original, "A AAA b BBBBBB c", &spans), Some(0));
+        assert_eq!(first_unexplained_change("same text", "same test", &[]), Some(7));
+    }
+}
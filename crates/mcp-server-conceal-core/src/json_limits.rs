@@ -0,0 +1,62 @@
+//! Bounds on the shape of JSON messages
+//!
+//! A hostile payload with millions of tiny values would hold the proxy up,
+//! and one nested deeper than a peer expects can break it. Every message is
+//! measured first, without recursion, against `[processing] max_json_depth`
+//! (by default 128, where JSON parsing stops anyway) and `max_json_nodes`.
+//! A message over either limit is not processed and never forwarded as is:
+//! every string in it is redacted, or it is dropped under
+//! `on_error = "block"`.
+
+use anyhow::Result;
+use serde_json::Value;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct JsonLimits {
+    /// Deepest nesting of arrays and objects; the message itself is level 1
+    pub max_depth: usize,
+    /// Most values, containers included
+    pub max_nodes: usize,
+}
+
+impl JsonLimits {
+    pub fn check(&self, value: &Value) -> Result<()> {
+        let mut stack = vec![(value, 0)];
+        let mut nodes = 0;
+        while let Some((value, depth)) = stack.pop() {
+            nodes += 1;
+            if nodes > self.max_nodes {
+                return Err(anyhow::anyhow!("JSON payload has more than {} values", self.max_nodes));
+            }
+            if (value.is_array() || value.is_object()) && depth >= self.max_depth {
+                return Err(anyhow::anyhow!("JSON payload is nested more than {} levels deep", self.max_depth));
+            }
+            match value {
+                Value::Array(items) => stack.extend(items.iter().map(|item| (item, depth + 1))),
+                Value::Object(fields) => stack.extend(fields.values().map(|field| (field, depth + 1))),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn nested(levels: usize) -> Value {
+        (1..levels).fold(json!([]), |inner, _| Value::Array(vec![inner]))
+    }
+
+    #[test]
+    fn test_limits_on_depth_and_size() {
+        let limits = JsonLimits { max_depth: 4, max_nodes: 10 };
+        assert!(limits.check(&json!({"a": [1, {"b": "c"}]})).is_ok());
+        assert!(limits.check(&nested(4)).is_ok());
+        assert!(limits.check(&nested(5)).unwrap_err().to_string().contains("4 levels"));
+        assert!(limits.check(&json!([1, 2, 3, 4, 5, 6, 7, 8, 9, 10])).unwrap_err().to_string().contains("10 values"));
+        assert!(limits.check(&json!("scalar")).is_ok());
+    }
+}
@@ -0,0 +1,175 @@
+//! JSON path rules selecting non-string fields for scanning
+//!
+//! Rules use a small JSONPath subset: `$` is the message root, `.name` a child
+//! key (case-insensitive, like key hints), `.*` any key, `[3]` an array index,
+//! `[*]` any index and `..` any number of levels in between. For example
+//! `$..ssn` selects an `ssn` key at any depth and `$.rows[*].phone` the phone
+//! of every row.
+
+use crate::config::ScalarFieldsConfig;
+use anyhow::Result;
+use std::fmt;
+
+/// One step from a parent value to a child.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JsonPathSegment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Key(String),
+    AnyKey,
+    Index(usize),
+    AnyIndex,
+    /// Zero or more segments
+    Descend,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    rule: String,
+    steps: Vec<Step>,
+}
+
+impl JsonPath {
+    pub fn parse(rule: &str) -> Result<Self> {
+        let invalid = |reason: &str| anyhow::anyhow!("Invalid JSON path rule '{}': {}", rule, reason);
+        let mut rest = rule.trim().strip_prefix('$').ok_or_else(|| invalid("must start with '$'"))?;
+        let mut steps = Vec::new();
+
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix("..") {
+                if after.is_empty() {
+                    return Err(invalid("trailing '..'"));
+                }
+                steps.push(Step::Descend);
+                rest = after;
+                if rest.starts_with('[') {
+                    continue;
+                }
+            } else if let Some(after) = rest.strip_prefix('.') {
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let (index, after) = after.split_once(']').ok_or_else(|| invalid("unclosed '['"))?;
+                steps.push(match index.trim() {
+                    "*" => Step::AnyIndex,
+                    index => Step::Index(index.parse().map_err(|_| invalid("array index must be a number or '*'"))?),
+                });
+                rest = after;
+                continue;
+            } else {
+                return Err(invalid("expected '.', '..' or '['"));
+            }
+
+            let end = rest.find(['.', '[']).unwrap_or(rest.len());
+            let name = &rest[..end];
+            if name.is_empty() {
+                return Err(invalid("empty key"));
+            }
+            steps.push(if name == "*" { Step::AnyKey } else { Step::Key(name.to_string()) });
+            rest = &rest[end..];
+        }
+
+        Ok(Self { rule: rule.trim().to_string(), steps })
+    }
+
+    pub fn matches(&self, path: &[JsonPathSegment]) -> bool {
+        matches_steps(&self.steps, path)
+    }
+}
+
+impl fmt::Display for JsonPath {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.rule)
+    }
+}
+
+fn matches_steps(steps: &[Step], path: &[JsonPathSegment]) -> bool {
+    let Some((step, rest)) = steps.split_first() else {
+        return path.is_empty();
+    };
+    if *step == Step::Descend {
+        return (0..=path.len()).any(|skip| matches_steps(rest, &path[skip..]));
+    }
+    let Some((segment, path_rest)) = path.split_first() else {
+        return false;
+    };
+    let segment_matches = match (step, segment) {
+        (Step::Key(name), JsonPathSegment::Key(key)) => name.eq_ignore_ascii_case(key),
+        (Step::AnyKey, JsonPathSegment::Key(_)) => true,
+        (Step::Index(index), JsonPathSegment::Index(i)) => index == i,
+        (Step::AnyIndex, JsonPathSegment::Index(_)) => true,
+        _ => false,
+    };
+    segment_matches && matches_steps(rest, path_rest)
+}
+
+/// Compiled `[detection.scalars]` rules.
+#[derive(Debug, Clone, Default)]
+pub struct ScalarRules {
+    numbers: Vec<JsonPath>,
+    booleans: Vec<JsonPath>,
+}
+
+impl ScalarRules {
+    pub fn new(config: &ScalarFieldsConfig) -> Result<Self> {
+        let compile = |rules: &[String]| rules.iter().map(|rule| JsonPath::parse(rule)).collect::<Result<Vec<_>>>();
+        Ok(Self {
+            numbers: compile(&config.numbers)?,
+            booleans: compile(&config.booleans)?,
+        })
+    }
+
+    /// Whether any rule exists, i.e. whether paths need to be tracked at all.
+    pub fn is_active(&self) -> bool {
+        !self.numbers.is_empty() || !self.booleans.is_empty()
+    }
+
+    pub fn scans_number(&self, path: &[JsonPathSegment]) -> bool {
+        self.numbers.iter().any(|rule| rule.matches(path))
+    }
+
+    pub fn replaces_boolean(&self, path: &[JsonPathSegment]) -> bool {
+        self.booleans.iter().any(|rule| rule.matches(path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(segments: &[&str]) -> Vec<JsonPathSegment> {
+        segments.iter()
+            .map(|s| match s.parse() {
+                Ok(index) => JsonPathSegment::Index(index),
+                Err(_) => JsonPathSegment::Key(s.to_string()),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_rules_match_paths() {
+        let anywhere = JsonPath::parse("$..ssn").unwrap();
+        assert!(anywhere.matches(&path(&["ssn"])));
+        assert!(anywhere.matches(&path(&["customer", "SSN"])));
+        assert!(!anywhere.matches(&path(&["customer", "ssn", "last4"])));
+
+        let rows = JsonPath::parse("$.rows[*].phone").unwrap();
+        assert!(rows.matches(&path(&["rows", "2", "phone"])));
+        assert!(!rows.matches(&path(&["rows", "phone"])));
+
+        let exact = JsonPath::parse("$.result.*[0]").unwrap();
+        assert!(exact.matches(&path(&["result", "ids", "0"])));
+        assert!(!exact.matches(&path(&["result", "ids", "1"])));
+    }
+
+    #[test]
+    fn test_invalid_rules_are_rejected() {
+        assert!(JsonPath::parse("ssn").is_err());
+        assert!(JsonPath::parse("$.rows[x]").is_err());
+        assert!(JsonPath::parse("$.rows[1").is_err());
+        assert!(JsonPath::parse("$.a..").is_err());
+    }
+}
@@ -0,0 +1,119 @@
+//! Language detection for prompts and faker locales
+//!
+//! Tool output is not always English. With `[language]` enabled, each text long
+//! enough to tell is run through a trigram language detector; French text is
+//! then faked with French names and sent to the LLM with the French prompt
+//! (built in for French, German and Spanish) instead of the default one. Texts whose
+//! language cannot be told reliably keep the configured prompt and locale.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LanguageConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Shorter texts are too short to tell reliably
+    #[serde(default = "default_min_chars")]
+    pub min_chars: usize,
+    /// Language code (`fr`, `de`, ...) → faker locale for texts in that language
+    #[serde(default = "default_locales")]
+    pub locales: HashMap<String, String>,
+    /// Language code → name of a prompt template in the prompts directory
+    #[serde(default = "default_prompts")]
+    pub prompts: HashMap<String, String>,
+}
+
+impl Default for LanguageConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_chars: default_min_chars(),
+            locales: default_locales(),
+            prompts: default_prompts(),
+        }
+    }
+}
+
+fn default_min_chars() -> usize {
+    40
+}
+
+fn default_locales() -> HashMap<String, String> {
+    [("en", "en_US"), ("fr", "fr_FR"), ("de", "de_DE"), ("ja", "ja_JP"), ("pt", "pt_BR"), ("zh", "zh_CN"), ("ar", "ar_SA")]
+        .iter()
+        .map(|(language, locale)| (language.to_string(), locale.to_string()))
+        .collect()
+}
+
+impl LanguageConfig {
+    /// Language of `text` as an ISO 639-1 code (ISO 639-3 where there is none),
+    /// when detection is enabled and the detector is confident.
+    pub fn detect(&self, text: &str) -> Option<&'static str> {
+        if !self.enabled || text.chars().count() < self.min_chars {
+            return None;
+        }
+        let info = whatlang::detect(text).filter(whatlang::Info::is_reliable)?;
+        Some(short_code(info.lang()))
+    }
+
+    /// Faker locale configured for the language of `text`.
+    pub fn locale_for(&self, text: &str) -> Option<&str> {
+        self.locales.get(self.detect(text)?).map(String::as_str)
+    }
+}
+
+fn default_prompts() -> HashMap<String, String> {
+    [("fr", "default_fr"), ("de", "default_de"), ("es", "default_es")]
+        .iter()
+        .map(|(language, prompt)| (language.to_string(), prompt.to_string()))
+        .collect()
+}
+
+fn short_code(lang: whatlang::Lang) -> &'static str {
+    use whatlang::Lang;
+    match lang {
+        Lang::Eng => "en",
+        Lang::Fra => "fr",
+        Lang::Deu => "de",
+        Lang::Spa => "es",
+        Lang::Ita => "it",
+        Lang::Por => "pt",
+        Lang::Nld => "nl",
+        Lang::Pol => "pl",
+        Lang::Rus => "ru",
+        Lang::Ukr => "uk",
+        Lang::Tur => "tr",
+        Lang::Swe => "sv",
+        Lang::Dan => "da",
+        Lang::Nob => "nb",
+        Lang::Fin => "fi",
+        Lang::Ces => "cs",
+        Lang::Jpn => "ja",
+        Lang::Cmn => "zh",
+        Lang::Kor => "ko",
+        Lang::Ara => "ar",
+        Lang::Heb => "he",
+        Lang::Hin => "hi",
+        other => other.code(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_languages_are_detected_with_enough_text() {
+        let config = LanguageConfig { enabled: true, ..LanguageConfig::default() };
+        let french = "Bonjour, je vous écris au sujet de la facture que nous avons reçue la semaine dernière.";
+        let german = "Sehr geehrte Damen und Herren, anbei erhalten Sie die Rechnung für den vergangenen Monat.";
+        assert_eq!(config.detect(french), Some("fr"));
+        assert_eq!(config.locale_for(french), Some("fr_FR"));
+        assert_eq!(config.locale_for(german), Some("de_DE"));
+        assert_eq!(config.detect("Bonjour Marie"), None);
+
+        let disabled = LanguageConfig::default();
+        assert_eq!(disabled.detect(french), None);
+    }
+}
@@ -0,0 +1,250 @@
+//! Check of processed messages for originals that survived replacement
+//!
+//! Replacement works string by string, so an original can still reach the
+//! other side: written with different case or separators in another field,
+//! cut short by an overlapping span, or in a string detection never looked
+//! at. With `[leak_check] enabled = true`, every processed message is
+//! scanned for the originals replaced in it before it is forwarded. Values
+//! are compared on their letters and digits, ignoring case, so
+//! `Jane.Roe@Acme.com` and `123 45 6789` are found as well. Matches start and
+//! end on word boundaries, so `Anna` is not found in `Savannah`. A value of
+//! several words is also looked for word by word, except for addresses
+//! (email, street, URL), whose words such as a company domain are not
+//! personal on their own. Values shorter than `min_length` letters and digits
+//! are not looked for, and text inside a fake is never a leak.
+//!
+//! A detected value that is itself a fake, of the same message or stored in
+//! the mapping store, is reported too: faking a fake again hides nothing
+//! and means a message went through detection twice.
+//!
+//! Findings are logged, recorded as a `leak_detected` audit event (entity
+//! types only, never values) and counted in the end-of-session summary;
+//! `on_leak` can drop or redact the message instead of forwarding it.
+
+use crate::config::{base_entity_type, AnonymizedEntity, OnError};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeSet;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LeakCheckConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// What reaches the other side when a leak is found
+    #[serde(default)]
+    pub on_leak: OnError,
+    /// Fewest letters and digits of a value, or of one of its words, looked for
+    #[serde(default = "default_min_length")]
+    pub min_length: usize,
+}
+
+impl Default for LeakCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            on_leak: OnError::default(),
+            min_length: default_min_length(),
+        }
+    }
+}
+
+fn default_min_length() -> usize {
+    4
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LeakKind {
+    /// A replaced value, in any case or with other separators
+    Original,
+    /// A word of a replaced value
+    Partial,
+    /// A fake detected and replaced again
+    DoubleAnonymized,
+}
+
+impl LeakKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LeakKind::Original => "original",
+            LeakKind::Partial => "partial",
+            LeakKind::DoubleAnonymized => "double_anonymized",
+        }
+    }
+}
+
+/// Entity types whose words are not looked for one by one.
+const WHOLE_ONLY_TYPES: &[&str] = &["email", "url", "hostname", "address", "street_address", "postal_address"];
+
+/// Letters and digits of `text`, lower-cased.
+fn normalize(text: &str) -> String {
+    text.chars().filter(|c| c.is_alphanumeric()).flat_map(char::to_lowercase).collect()
+}
+
+/// Words (runs of letters and digits) of `text`, lower-cased. Every
+/// occurrence of a fake becomes a `None` barrier no value can match across.
+fn words_outside(text: &str, fakes: &[&str]) -> Vec<Option<String>> {
+    let mut covered = vec![false; text.len()];
+    for fake in fakes.iter().filter(|fake| !fake.is_empty()) {
+        for (start, matched) in text.match_indices(fake) {
+            covered[start..start + matched.len()].iter_mut().for_each(|c| *c = true);
+        }
+    }
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for (index, c) in text.char_indices() {
+        if !covered[index] && c.is_alphanumeric() {
+            word.extend(c.to_lowercase());
+            continue;
+        }
+        if !word.is_empty() {
+            words.push(Some(std::mem::take(&mut word)));
+        }
+        if covered[index] && words.last().is_none_or(Option::is_some) {
+            words.push(None);
+        }
+    }
+    if !word.is_empty() {
+        words.push(Some(word));
+    }
+    words
+}
+
+/// Whether consecutive `words` spell `needle`, starting and ending on word
+/// boundaries; separators between them do not matter.
+fn spelled_by(words: &[Option<String>], needle: &str) -> bool {
+    (0..words.len()).any(|start| {
+        let mut rest = needle;
+        for word in &words[start..] {
+            let Some(word) = word else {
+                return false;
+            };
+            match rest.strip_prefix(word.as_str()) {
+                Some("") => return true,
+                Some(remaining) => rest = remaining,
+                None => return false,
+            }
+        }
+        false
+    })
+}
+
+#[derive(Debug, Clone)]
+pub struct LeakChecker {
+    pub on_leak: OnError,
+    min_length: usize,
+}
+
+impl LeakChecker {
+    pub fn new(config: &LeakCheckConfig) -> Option<Self> {
+        config.enabled.then_some(Self { on_leak: config.on_leak, min_length: config.min_length })
+    }
+
+    /// Kind and entity type of every value of `replaced` left in the strings
+    /// of `message`, object keys aside.
+    pub fn check(&self, message: &Value, replaced: &[AnonymizedEntity]) -> BTreeSet<(LeakKind, String)> {
+        let fakes: Vec<&str> = replaced.iter().map(|e| e.fake_value.as_str()).collect();
+        let mut needles = Vec::new();
+        for entity in replaced {
+            let whole = normalize(&entity.original_value);
+            let whole_only = WHOLE_ONLY_TYPES.contains(&base_entity_type(&entity.entity_type));
+            let words: Vec<String> = entity.original_value.split(|c: char| !c.is_alphanumeric())
+                .map(normalize)
+                .filter(|word| !whole_only && word.chars().count() >= self.min_length && *word != whole)
+                .collect();
+            if whole.chars().count() >= self.min_length {
+                needles.push((LeakKind::Original, whole, &entity.entity_type));
+            }
+            needles.extend(words.into_iter().map(|word| (LeakKind::Partial, word, &entity.entity_type)));
+        }
+
+        let mut found = BTreeSet::new();
+        if needles.is_empty() {
+            return found;
+        }
+        let mut stack = vec![message];
+        while let Some(value) = stack.pop() {
+            match value {
+                Value::String(text) => {
+                    let words = words_outside(text, &fakes);
+                    for (kind, needle, entity_type) in &needles {
+                        if spelled_by(&words, needle) {
+                            found.insert((*kind, base_entity_type(entity_type).to_string()));
+                        }
+                    }
+                }
+                Value::Array(items) => stack.extend(items),
+                Value::Object(fields) => stack.extend(fields.values()),
+                _ => {}
+            }
+        }
+        // A value found whole is not also reported word by word
+        let whole: BTreeSet<String> = found.iter()
+            .filter(|(kind, _)| *kind == LeakKind::Original)
+            .map(|(_, entity_type)| entity_type.clone())
+            .collect();
+        found.retain(|(kind, entity_type)| *kind != LeakKind::Partial || !whole.contains(entity_type));
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn replaced(entity_type: &str, original: &str, fake: &str) -> AnonymizedEntity {
+        AnonymizedEntity {
+            entity_type: entity_type.to_string(),
+            original_value: original.to_string(),
+            fake_value: fake.to_string(),
+            mapping_id: String::new(),
+        }
+    }
+
+    fn checker() -> LeakChecker {
+        LeakChecker::new(&LeakCheckConfig { enabled: true, ..LeakCheckConfig::default() }).unwrap()
+    }
+
+    #[test]
+    fn test_finds_originals_left_after_replacement() {
+        let replaced = [
+            replaced("email@contact", "jane.roe@acme.com", "mark.lee@example.org"),
+            replaced("person_name", "Jane Roberts", "Mark Lee"),
+            replaced("ssn", "123-45-6789", "987-65-4321"),
+        ];
+        let clean = json!({"contact": "mark.lee@example.org", "note": "Mark Lee, 987-65-4321"});
+        assert!(checker().check(&clean, &replaced).is_empty());
+
+        let leaky = json!({"cc": ["JANE.ROE@ACME.COM"], "note": "Mark Lee (née Roberts), SSN 123 45 6789"});
+        let found: Vec<_> = checker().check(&leaky, &replaced).into_iter()
+            .map(|(kind, entity_type)| format!("{}:{}", kind.as_str(), entity_type))
+            .collect();
+        assert_eq!(found, ["original:email", "original:ssn", "partial:person_name"]);
+    }
+
+    #[test]
+    fn test_values_are_matched_on_word_boundaries() {
+        let replaced = [
+            replaced("person_name", "Anna Kowalski", "Mark Lee"),
+            replaced("first_name", "Jane", "Kim"),
+            replaced("email", "jane.roe@acme.com", "mark.lee@example.org"),
+        ];
+        let message = json!({"note": "Kim from Savannah met Janet at Acme Corp, see janeroe@acme.community"});
+        assert!(checker().check(&message, &replaced).is_empty());
+
+        let leaky = json!({"note": "Kim wrote to anna, cc JaneRoe @ Acme . com"});
+        let found: Vec<_> = checker().check(&leaky, &replaced).into_iter()
+            .map(|(kind, entity_type)| format!("{}:{}", kind.as_str(), entity_type))
+            .collect();
+        assert_eq!(found, ["original:email", "partial:person_name"]);
+    }
+
+    #[test]
+    fn test_text_inside_fakes_and_short_values_are_not_leaks() {
+        // The fake keeps the original's domain
+        let replaced = [replaced("email", "acme.com", "user@acme.com"), replaced("age", "52", "50-59")];
+        let message = json!({"to": "user@acme.com", "age": "50-59, not 52"});
+        assert!(checker().check(&message, &replaced).is_empty());
+        assert!(LeakChecker::new(&LeakCheckConfig::default()).is_none());
+    }
+}
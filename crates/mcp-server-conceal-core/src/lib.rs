@@ -1,18 +1,99 @@
 pub mod proxy;
+pub mod admin;
+pub mod annotate;
+pub mod api_keys;
+pub mod approval;
+pub mod audit;
+pub mod bloom;
+pub mod bypass;
+pub mod canary;
+pub mod chaos;
+pub mod chunking;
+pub mod circuit_breaker;
 pub mod config;
+pub mod config_format;
+pub mod config_migration;
+pub mod context;
+pub mod crypto;
+pub mod dates;
+pub mod debug_diff;
+pub mod degradation;
 pub mod detection;
+pub mod discovery;
 pub mod faker;
+pub mod fingerprint;
+pub mod fpe;
+pub mod gateway;
+pub mod gazetteer;
+pub mod generalize;
+pub mod geo;
+pub mod hashing;
+pub mod i18n;
+pub mod identifiers;
+pub mod identity;
+pub mod ip;
+pub mod json_diff;
+pub mod json_limits;
+pub mod json_path;
+pub mod key_material;
+pub mod language;
+pub mod leak_check;
+pub mod listener;
+pub mod log_file;
+pub mod log_sanitizer;
+pub mod manifest;
 pub mod mapping;
+pub mod names;
+pub mod national_ids;
 pub mod ollama;
+pub mod outage_queue;
+pub mod overlap;
+pub mod pattern_packs;
+pub mod phone;
+#[cfg(feature = "bridge")]
+pub mod pipeline;
+#[cfg(not(feature = "bridge"))]
+pub(crate) mod pipeline;
+pub mod presets;
 pub mod prompt_loader;
+pub mod protocol;
+pub mod provider;
+pub mod quarantine;
+pub mod redis_cache;
+pub mod schema_hints;
+pub mod scheduler;
+pub mod secrets;
+pub mod session;
+pub mod stitching;
+pub mod telemetry;
+pub mod urls;
+pub mod validators;
+pub mod verification;
+pub mod verify;
 
 #[cfg(test)]
 pub mod integration_tests;
 
 pub use proxy::{IntegratedProxy, IntegratedProxyConfig};
-pub use config::{Config, DetectionConfig, FakerConfig, MappingConfig, LlmConfig, DetectedEntity, AnonymizedEntity};
+#[cfg(feature = "bridge")]
+pub use pipeline::{bridge, Direction, Pipeline};
+pub use config::{Config, DetectionConfig, FakerConfig, AnonymizationStrategy, MappingConfig, LlmConfig, LlmOutputFormat, AuditConfig, PassphraseSource, DetectedEntity, AnonymizedEntity};
 pub use detection::RegexDetectionEngine;
 pub use faker::FakerEngine;
-pub use mapping::{MappingStore, EntityMapping, LlmCacheEntry, MappingStatistics};
+pub use circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+pub use scheduler::{LlmConcurrencyConfig, LlmScheduler};
+pub use chunking::LlmChunkingConfig;
+pub use verification::LlmVerificationConfig;
+pub use overlap::OverlapResolver;
+pub use degradation::{Degradation, DegradationReport};
+pub use outage_queue::{BackfillReport, OutageQueue, OutageQueueConfig};
+pub use mapping::{MappingStore, EntityMapping, LlmCacheEntry, MappingStatistics, BundleFormat, BundledMapping, ImportReport, RuntimeCounters, RuntimeStatistics};
 pub use ollama::{OllamaClient, OllamaConfig, LlmResponse, LlmDetectedEntity};
 pub use prompt_loader::PromptLoader;
+pub use provider::LlmProvider;
+pub use secrets::{Passphrase, PassphraseStore};
+pub use audit::AuditLog;
+pub use i18n::{Catalog, I18nConfig};
+pub use bypass::{BypassRegistry, BypassScope, BypassToken, SignedBypassToken};
+pub use protocol::{RequestInfo, RequestTracker};
+pub use config_migration::{MigrationReport, CURRENT_CONFIG_VERSION};
@@ -0,0 +1,289 @@
+//! Socket transports for the client side of the proxy
+//!
+//! Instead of stdin/stdout, the proxy can listen on a unix domain socket or a
+//! TCP port for newline-delimited JSON-RPC, so it runs as a long-lived sidecar.
+//! Every connection is a session of its own, with its own target process.
+//!
+//! A unix socket is reachable only by the current user. TCP has no such
+//! check, so a TCP listener needs a token in `MCP_CONCEAL_LISTEN_TOKEN`, and
+//! every client must send `AUTH <token>` as its first line before any
+//! JSON-RPC; a loopback address may go without one.
+
+use anyhow::Result;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite};
+use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::net::UnixListener;
+use tracing::{info, warn};
+
+/// Environment variable holding the token TCP clients authenticate with.
+pub const LISTEN_TOKEN_ENV_VAR: &str = "MCP_CONCEAL_LISTEN_TOKEN";
+
+/// Longest `AUTH <token>` line read from a TCP client.
+const MAX_AUTH_LINE: usize = 512;
+
+/// How long a TCP client has to send its `AUTH` line.
+const AUTH_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub type ConnectionReader = Box<dyn AsyncRead + Send + Unpin>;
+pub type ConnectionWriter = Box<dyn AsyncWrite + Send + Unpin>;
+
+/// `unix:<path>` or `tcp:<host>:<port>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ListenAddress {
+    Unix(PathBuf),
+    Tcp(String),
+}
+
+impl FromStr for ListenAddress {
+    type Err = anyhow::Error;
+
+    fn from_str(address: &str) -> Result<Self> {
+        match address.split_once(':') {
+            Some(("unix", path)) if !path.is_empty() => Ok(ListenAddress::Unix(PathBuf::from(path))),
+            Some(("tcp", host_port)) if host_port.rsplit_once(':').is_some_and(|(_, port)| port.parse::<u16>().is_ok()) => {
+                Ok(ListenAddress::Tcp(host_port.to_string()))
+            }
+            _ => Err(anyhow::anyhow!("Listen address '{}' must be unix:<path> or tcp:<host>:<port>", address)),
+        }
+    }
+}
+
+impl fmt::Display for ListenAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListenAddress::Unix(path) => write!(f, "unix:{}", path.display()),
+            ListenAddress::Tcp(host_port) => write!(f, "tcp:{}", host_port),
+        }
+    }
+}
+
+pub enum Listener {
+    #[cfg(unix)]
+    Unix(UnixListener, PathBuf),
+    /// With the token clients must send first, if any
+    Tcp(TcpListener, Option<String>),
+}
+
+/// A client that connected and still has to authenticate, if the listener
+/// needs it.
+pub struct Connection {
+    pub reader: ConnectionReader,
+    pub writer: ConnectionWriter,
+    /// Description for logs
+    pub peer: String,
+    token: Option<String>,
+}
+
+impl Listener {
+    /// Binds `address`. A socket file another process still listens on is
+    /// an error; one left behind by an earlier run is replaced. The socket
+    /// is created in a directory only the current user can enter and moved
+    /// into place once it is private, so no one else can connect in between.
+    /// A TCP address needs `token` unless it is a loopback one.
+    pub async fn bind(address: &ListenAddress, token: Option<String>) -> Result<Self> {
+        match address {
+            #[cfg(unix)]
+            ListenAddress::Unix(path) => {
+                remove_stale_socket(path)?;
+                let listener = bind_private(path)
+                    .map_err(|e| anyhow::anyhow!("Failed to listen on {}: {}", path.display(), e))?;
+                Ok(Listener::Unix(listener, path.clone()))
+            }
+            #[cfg(not(unix))]
+            ListenAddress::Unix(_) => Err(anyhow::anyhow!("Unix domain sockets are not available on this platform")),
+            ListenAddress::Tcp(host_port) => {
+                let token = token.filter(|token| !token.is_empty());
+                let listener = TcpListener::bind(host_port).await
+                    .map_err(|e| anyhow::anyhow!("Failed to listen on {}: {}", host_port, e))?;
+                let loopback = listener.local_addr()?.ip().is_loopback();
+                match (&token, loopback) {
+                    (None, false) => return Err(anyhow::anyhow!(
+                        "Listening on non-loopback address {} needs a token in {}; anyone who can reach it could call the target's tools",
+                        host_port, LISTEN_TOKEN_ENV_VAR
+                    )),
+                    (None, true) => warn!("Listening on {} without {}; every local user can connect", host_port, LISTEN_TOKEN_ENV_VAR),
+                    (Some(_), _) => info!("TCP clients must send 'AUTH <token>' first"),
+                }
+                Ok(Listener::Tcp(listener, token))
+            }
+        }
+    }
+
+    /// Waits for the next client.
+    pub async fn accept(&self) -> Result<Connection> {
+        match self {
+            #[cfg(unix)]
+            Listener::Unix(listener, path) => {
+                let (stream, _) = listener.accept().await?;
+                let (reader, writer) = stream.into_split();
+                Ok(Connection { reader: Box::new(reader), writer: Box::new(writer), peer: path.display().to_string(), token: None })
+            }
+            Listener::Tcp(listener, token) => {
+                let (stream, peer) = listener.accept().await?;
+                let (reader, writer) = stream.into_split();
+                Ok(Connection { reader: Box::new(reader), writer: Box::new(writer), peer: peer.to_string(), token: token.clone() })
+            }
+        }
+    }
+}
+
+impl Connection {
+    /// Reads the client's `AUTH <token>` line when the listener needs one.
+    /// Nothing after that line is read, so the JSON-RPC stream starts intact.
+    pub async fn authenticate(mut self) -> Result<Self> {
+        let Some(token) = self.token.take() else {
+            return Ok(self);
+        };
+        let line = tokio::time::timeout(AUTH_TIMEOUT, read_auth_line(&mut self.reader)).await
+            .map_err(|_| anyhow::anyhow!("{} sent no AUTH line within {}s", self.peer, AUTH_TIMEOUT.as_secs()))??;
+        let sent = line.strip_prefix("AUTH ").unwrap_or_default().as_bytes();
+        // Compared without an early exit, so timing does not tell how much matched
+        let matches = sent.len() == token.len()
+            && sent.iter().zip(token.as_bytes()).fold(0u8, |diff, (a, b)| diff | (a ^ b)) == 0;
+        if !matches {
+            return Err(anyhow::anyhow!("{} sent a wrong token", self.peer));
+        }
+        Ok(self)
+    }
+}
+
+/// One line, a byte at a time so that nothing past it is consumed.
+async fn read_auth_line(reader: &mut ConnectionReader) -> Result<String> {
+    let mut line = Vec::new();
+    loop {
+        let byte = reader.read_u8().await?;
+        if byte == b'\n' {
+            break;
+        }
+        if line.len() == MAX_AUTH_LINE {
+            return Err(anyhow::anyhow!("AUTH line longer than {} bytes", MAX_AUTH_LINE));
+        }
+        line.push(byte);
+    }
+    Ok(String::from_utf8_lossy(&line).trim_end_matches('\r').to_string())
+}
+
+/// Removes a socket file no process listens on any more.
+#[cfg(unix)]
+fn remove_stale_socket(path: &std::path::Path) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    if !std::fs::symlink_metadata(path).is_ok_and(|metadata| metadata.file_type().is_socket()) {
+        return Ok(());
+    }
+    if std::os::unix::net::UnixStream::connect(path).is_ok() {
+        return Err(anyhow::anyhow!("{} is in use by another process", path.display()));
+    }
+    info!("Replacing stale socket {}", path.display());
+    std::fs::remove_file(path)?;
+    Ok(())
+}
+
+/// Binds in a fresh 0700 directory next to `path`, restricts the socket to
+/// 0600 and renames it to `path`.
+#[cfg(unix)]
+fn bind_private(path: &std::path::Path) -> std::io::Result<UnixListener> {
+    use std::os::unix::fs::{DirBuilderExt, PermissionsExt};
+
+    let parent = path.parent().filter(|parent| !parent.as_os_str().is_empty()).unwrap_or(std::path::Path::new("."));
+    let staging = parent.join(format!(".conceal-{}", uuid::Uuid::new_v4().simple()));
+    std::fs::DirBuilder::new().mode(0o700).create(&staging)?;
+    let staged = staging.join("socket");
+    let bound = UnixListener::bind(&staged).and_then(|listener| {
+        std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(0o600))?;
+        std::fs::rename(&staged, path)?;
+        Ok(listener)
+    });
+    std::fs::remove_file(&staged).ok();
+    std::fs::remove_dir(&staging).ok();
+    bound
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        if let Listener::Unix(_, path) = self {
+            std::fs::remove_file(path).ok();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_listen_addresses_are_parsed() {
+        assert_eq!("unix:/run/conceal.sock".parse::<ListenAddress>().unwrap(), ListenAddress::Unix(PathBuf::from("/run/conceal.sock")));
+        assert_eq!("tcp:127.0.0.1:9400".parse::<ListenAddress>().unwrap(), ListenAddress::Tcp("127.0.0.1:9400".to_string()));
+        assert_eq!("tcp:[::1]:9400".parse::<ListenAddress>().unwrap().to_string(), "tcp:[::1]:9400");
+        assert!("tcp:localhost".parse::<ListenAddress>().is_err());
+        assert!("unix:".parse::<ListenAddress>().is_err());
+        assert!("127.0.0.1:9400".parse::<ListenAddress>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_unix_listener_accepts_clients() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("conceal.sock");
+        let listener = Listener::bind(&ListenAddress::Unix(path.clone()), None).await.unwrap();
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        }
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+        let mut client = tokio::net::UnixStream::connect(&path).await.unwrap();
+        let Connection { mut reader, mut writer, .. } = listener.accept().await.unwrap().authenticate().await.unwrap();
+        client.write_all(b"ping\n").await.unwrap();
+        let mut received = [0u8; 5];
+        reader.read_exact(&mut received).await.unwrap();
+        assert_eq!(&received, b"ping\n");
+        writer.write_all(b"pong\n").await.unwrap();
+        client.read_exact(&mut received).await.unwrap();
+        assert_eq!(&received, b"pong\n");
+
+        // A second proxy cannot take over the socket of a running one
+        assert!(Listener::bind(&ListenAddress::Unix(path.clone()), None).await.is_err());
+
+        drop(listener);
+        assert!(!path.exists());
+
+        // A socket left behind by a process that is gone is replaced
+        drop(std::os::unix::net::UnixListener::bind(&path).unwrap());
+        assert!(Listener::bind(&ListenAddress::Unix(path.clone()), None).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_tcp_clients_authenticate_first() {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        assert!(Listener::bind(&"tcp:0.0.0.0:0".parse().unwrap(), None).await.is_err());
+        let listener = Listener::bind(&"tcp:127.0.0.1:0".parse().unwrap(), Some("s3cret".to_string())).await.unwrap();
+        let Listener::Tcp(tcp, _) = &listener else { unreachable!() };
+        let address = tcp.local_addr().unwrap();
+
+        let mut client = tokio::net::TcpStream::connect(address).await.unwrap();
+        client.write_all(b"AUTH s3cret\r\n{\"id\":1}\n").await.unwrap();
+        let mut connection = listener.accept().await.unwrap().authenticate().await.unwrap();
+        let mut received = [0u8; 9];
+        connection.reader.read_exact(&mut received).await.unwrap();
+        assert_eq!(&received, b"{\"id\":1}\n");
+
+        let mut client = tokio::net::TcpStream::connect(address).await.unwrap();
+        client.write_all(b"AUTH guess\n").await.unwrap();
+        assert!(listener.accept().await.unwrap().authenticate().await.is_err());
+
+        let mut client = tokio::net::TcpStream::connect(address).await.unwrap();
+        client.write_all(b"{\"jsonrpc\":\"2.0\"}\n").await.unwrap();
+        assert!(listener.accept().await.unwrap().authenticate().await.is_err());
+    }
+}
@@ -0,0 +1,329 @@
+//! Log file output with rotation
+//!
+//! A stdio MCP host often swallows the proxy's stderr or interleaves it with
+//! its own output. With `[logging] file` set, the proxy also writes its log to
+//! that file, rotated by size and/or by the hour or day: `conceal.log` moves to
+//! `conceal.log.1`, older files move up one number, and files beyond
+//! `max_files` are deleted.
+//!
+//! The CLI installs its log layers before the configuration is read, so they
+//! write through [`LogOutputs`], which [`LogOutputs::configure`] points at the
+//! file afterwards.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LoggingConfig {
+    /// Log file; relative paths live in the data directory. Unset, the log
+    /// only goes to stderr
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<PathBuf>,
+    /// Keep logging to stderr while a file is written
+    #[serde(default = "default_stderr")]
+    pub stderr: bool,
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// Size at which the file is rotated; 0 rotates by time only
+    #[serde(default = "default_max_size_mb")]
+    pub max_size_mb: u64,
+    /// Rotated files kept next to the current one
+    #[serde(default = "default_max_files")]
+    pub max_files: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            stderr: default_stderr(),
+            rotation: LogRotation::default(),
+            max_size_mb: default_max_size_mb(),
+            max_files: default_max_files(),
+        }
+    }
+}
+
+fn default_stderr() -> bool {
+    true
+}
+
+fn default_max_size_mb() -> u64 {
+    100
+}
+
+fn default_max_files() -> usize {
+    5
+}
+
+/// Time-based rotation, on UTC hour or day boundaries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogRotation {
+    Never,
+    Hourly,
+    #[default]
+    Daily,
+}
+
+impl LogRotation {
+    fn period_secs(&self) -> Option<u64> {
+        match self {
+            LogRotation::Never => None,
+            LogRotation::Hourly => Some(3600),
+            LogRotation::Daily => Some(86400),
+        }
+    }
+}
+
+pub struct RotatingFile {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_bytes: Option<u64>,
+    period_secs: Option<u64>,
+    /// Period the current file was started in
+    period: u64,
+    max_files: usize,
+}
+
+impl RotatingFile {
+    pub fn open(path: &Path, config: &LoggingConfig) -> Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent)?;
+        }
+        let file = open_append(path)
+            .map_err(|e| anyhow::anyhow!("Failed to open log file {}: {}", path.display(), e))?;
+        let metadata = file.metadata()?;
+        let period_secs = config.rotation.period_secs();
+        let started = metadata.modified().ok().filter(|_| metadata.len() > 0).unwrap_or_else(SystemTime::now);
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            size: metadata.len(),
+            max_bytes: (config.max_size_mb > 0).then(|| config.max_size_mb * 1024 * 1024),
+            period_secs,
+            period: period_of(started, period_secs),
+            max_files: config.max_files,
+        })
+    }
+
+    fn due(&self, incoming: usize) -> bool {
+        let too_large = self.max_bytes.is_some_and(|max| self.size > 0 && self.size + incoming as u64 > max);
+        too_large || period_of(SystemTime::now(), self.period_secs) != self.period
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+        let numbered = |n: usize| {
+            let mut name = self.path.clone().into_os_string();
+            name.push(format!(".{}", n));
+            PathBuf::from(name)
+        };
+        if self.max_files == 0 {
+            std::fs::remove_file(&self.path)?;
+        } else {
+            std::fs::remove_file(numbered(self.max_files)).ok();
+            for n in (1..self.max_files).rev() {
+                std::fs::rename(numbered(n), numbered(n + 1)).ok();
+            }
+            std::fs::rename(&self.path, numbered(1))?;
+        }
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        self.period = period_of(SystemTime::now(), self.period_secs);
+        Ok(())
+    }
+}
+
+impl Write for RotatingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.due(buf.len()) {
+            // A failed rotation keeps logging to the current file and is noted
+            // there rather than through tracing, whose writer this is. The
+            // next attempt waits for another period or max_size_mb
+            if let Err(e) = self.rotate() {
+                let note = format!("Failed to rotate log file {}: {}\n", self.path.display(), e);
+                self.file.write_all(note.as_bytes())?;
+                self.size = note.len() as u64;
+                self.period = period_of(SystemTime::now(), self.period_secs);
+            }
+        }
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn period_of(time: SystemTime, period_secs: Option<u64>) -> u64 {
+    let Some(period_secs) = period_secs else {
+        return 0;
+    };
+    time.duration_since(UNIX_EPOCH).map(|d| d.as_secs() / period_secs).unwrap_or(0)
+}
+
+/// Logs can hold message excerpts at debug level; only the owner reads them.
+fn open_append(path: &Path) -> io::Result<File> {
+    let mut options = OpenOptions::new();
+    options.create(true).append(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options.open(path)
+}
+
+/// Destinations of the CLI's log layers, settable once the configuration is known.
+#[derive(Clone)]
+pub struct LogOutputs {
+    stderr: Arc<AtomicBool>,
+    file: Arc<Mutex<Option<RotatingFile>>>,
+}
+
+impl Default for LogOutputs {
+    fn default() -> Self {
+        Self { stderr: Arc::new(AtomicBool::new(true)), file: Arc::new(Mutex::new(None)) }
+    }
+}
+
+impl LogOutputs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn configure(&self, config: &LoggingConfig) -> Result<()> {
+        let Some(path) = &config.file else {
+            return Ok(());
+        };
+        let file = RotatingFile::open(path, config)?;
+        *self.file.lock().unwrap_or_else(|e| e.into_inner()) = Some(file);
+        self.stderr.store(config.stderr, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Writer for the stderr layer; discards output once stderr is turned off.
+    pub fn stderr(&self) -> impl Fn() -> StderrOutput + Send + Sync + 'static {
+        let enabled = self.stderr.clone();
+        move || StderrOutput(enabled.load(Ordering::Relaxed))
+    }
+
+    /// Writer for the file layer; discards output while no file is configured.
+    pub fn file(&self) -> impl Fn() -> FileOutput + Send + Sync + 'static {
+        let file = self.file.clone();
+        move || FileOutput(file.clone())
+    }
+}
+
+pub struct StderrOutput(bool);
+
+impl Write for StderrOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.0 {
+            io::stderr().write(buf)
+        } else {
+            Ok(buf.len())
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+pub struct FileOutput(Arc<Mutex<Option<RotatingFile>>>);
+
+impl Write for FileOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.0.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            Some(file) => file.write(buf),
+            None => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.0.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            Some(file) => file.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_files_rotate_by_size_and_old_ones_are_dropped() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("logs").join("conceal.log");
+        let config = LoggingConfig {
+            file: Some(path.clone()),
+            rotation: LogRotation::Never,
+            max_size_mb: 1,
+            max_files: 2,
+            ..LoggingConfig::default()
+        };
+        let mut file = RotatingFile::open(&path, &config).unwrap();
+        let line = vec![b'x'; 400 * 1024];
+        for _ in 0..8 {
+            file.write_all(&line).unwrap();
+        }
+        file.flush().unwrap();
+
+        let size = |p: &Path| std::fs::metadata(p).map(|m| m.len()).unwrap_or(0);
+        assert_eq!(size(&path), 800 * 1024);
+        assert_eq!(size(&dir.path().join("logs/conceal.log.1")), 800 * 1024);
+        assert_eq!(size(&dir.path().join("logs/conceal.log.2")), 800 * 1024);
+        assert!(!dir.path().join("logs/conceal.log.3").exists());
+    }
+
+    #[test]
+    fn test_failed_rotation_is_noted_in_the_file_once() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("conceal.log");
+        // A directory in the way of the first rotated file
+        std::fs::create_dir_all(dir.path().join("conceal.log.1/busy")).unwrap();
+        let config = LoggingConfig {
+            file: Some(path.clone()),
+            rotation: LogRotation::Never,
+            max_size_mb: 1,
+            max_files: 1,
+            ..LoggingConfig::default()
+        };
+        let mut file = RotatingFile::open(&path, &config).unwrap();
+        let line = vec![b'x'; 400 * 1024];
+        for _ in 0..4 {
+            file.write_all(&line).unwrap();
+        }
+        file.flush().unwrap();
+
+        let written = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(written.matches("Failed to rotate log file").count(), 1);
+        assert!(written.len() > 4 * 400 * 1024);
+    }
+
+    #[test]
+    fn test_outputs_write_to_the_file_once_configured() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("conceal.log");
+        let outputs = LogOutputs::new();
+        let file = outputs.file();
+        file().write_all(b"before\n").unwrap();
+
+        outputs.configure(&LoggingConfig { file: Some(path.clone()), stderr: false, ..LoggingConfig::default() }).unwrap();
+        file().write_all(b"after\n").unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "after\n");
+        assert!(!outputs.stderr()().0);
+    }
+}
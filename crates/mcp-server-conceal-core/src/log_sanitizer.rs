@@ -0,0 +1,211 @@
+//! Anonymization of MCP client log files
+//!
+//! Clients log the traffic they exchange with MCP servers on their own side of
+//! the proxy, so raw values end up on disk even when the wire is protected.
+//! This module finds a client's MCP logs and rewrites them through the same
+//! detection and mapping store as the proxy: a value the proxy replaced gets
+//! the same fake in the sanitized log. JSON embedded in a log line is walked
+//! like a message; the surrounding text is scanned as plain text.
+
+use crate::config::Config;
+use crate::ollama::OllamaConfig;
+use crate::pipeline::{Direction, Pipeline};
+use anyhow::Result;
+use serde_json::Value;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use tracing::{debug, info};
+
+/// A client whose log layout is known.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogClient {
+    /// Claude Desktop: one `mcp*.log` file per server plus `mcp.log`
+    Claude,
+}
+
+impl FromStr for LogClient {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "claude" | "claude-desktop" => Ok(LogClient::Claude),
+            other => Err(anyhow::anyhow!("Unknown log client '{}' (supported: claude)", other)),
+        }
+    }
+}
+
+impl LogClient {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LogClient::Claude => "claude",
+        }
+    }
+
+    /// Where the client writes its logs on this platform.
+    pub fn default_log_dir(&self) -> Result<PathBuf> {
+        let dirs = directories::BaseDirs::new()
+            .ok_or_else(|| anyhow::anyhow!("Could not determine the home directory"))?;
+        Ok(match self {
+            LogClient::Claude if cfg!(target_os = "macos") => dirs.home_dir().join("Library/Logs/Claude"),
+            LogClient::Claude => dirs.config_dir().join("Claude").join("logs"),
+        })
+    }
+
+    pub fn is_log_file(&self, path: &Path) -> bool {
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            return false;
+        };
+        match self {
+            LogClient::Claude => name.starts_with("mcp") && name.ends_with(".log") && !name.ends_with(SANITIZED_SUFFIX),
+        }
+    }
+
+    /// Log files of this client in `dir`, sorted by name.
+    pub fn find_logs(&self, dir: &Path) -> Result<Vec<PathBuf>> {
+        let entries = std::fs::read_dir(dir)
+            .map_err(|e| anyhow::anyhow!("Failed to read log directory {}: {}", dir.display(), e))?;
+        let mut logs: Vec<PathBuf> = entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file() && self.is_log_file(path))
+            .collect();
+        logs.sort();
+        Ok(logs)
+    }
+}
+
+/// Appended to a log's name for the sanitized copy.
+pub const SANITIZED_SUFFIX: &str = ".sanitized.log";
+
+#[derive(Debug, Default)]
+pub struct SanitizeReport {
+    pub files: Vec<PathBuf>,
+    pub lines: usize,
+    pub changed_lines: usize,
+}
+
+/// Sanitizes every log of `client` in `dir`. Each log is written next to the
+/// original with [`SANITIZED_SUFFIX`], or replaces it when `in_place` is set.
+pub async fn sanitize_logs(
+    config: &Config,
+    ollama_config: OllamaConfig,
+    client: LogClient,
+    dir: &Path,
+    in_place: bool,
+) -> Result<SanitizeReport> {
+    let logs = client.find_logs(dir)?;
+    info!("Found {} {} log file(s) in {}", logs.len(), client.as_str(), dir.display());
+
+    let mut pipeline = Pipeline::new(config, ollama_config, Direction::Request)?;
+    let mut report = SanitizeReport::default();
+
+    for log in logs {
+        let contents = std::fs::read_to_string(&log)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", log.display(), e))?;
+        let mut sanitized = String::with_capacity(contents.len());
+        for line in contents.split_inclusive('\n') {
+            let (body, ending) = match line.strip_suffix('\n') {
+                Some(body) => (body, "\n"),
+                None => (line, ""),
+            };
+            let processed = sanitize_line(&mut pipeline, body).await?;
+            report.lines += 1;
+            if processed != body {
+                report.changed_lines += 1;
+            }
+            sanitized.push_str(&processed);
+            sanitized.push_str(ending);
+        }
+
+        let target = if in_place { log.clone() } else { sanitized_path(&log) };
+        // Write beside the target and rename, so an interrupted run never truncates a log
+        let temp = target.with_extension("conceal-tmp");
+        std::fs::write(&temp, sanitized)?;
+        std::fs::rename(&temp, &target)?;
+        debug!("Sanitized {} -> {}", log.display(), target.display());
+        report.files.push(target);
+    }
+
+    Ok(report)
+}
+
+pub fn sanitized_path(log: &Path) -> PathBuf {
+    let name = log.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let stem = name.strip_suffix(".log").unwrap_or(&name);
+    log.with_file_name(format!("{}{}", stem, SANITIZED_SUFFIX))
+}
+
+/// Sanitizes one log line: the first embedded JSON object or array, if any, is
+/// processed as a message and the text around it as plain text.
+pub(crate) async fn sanitize_line(pipeline: &mut Pipeline, line: &str) -> Result<String> {
+    let Some((start, end, mut value)) = find_embedded_json(line) else {
+        return Ok(pipeline.sanitize_text(line).await?.into_owned());
+    };
+
+    let mut sanitized = pipeline.sanitize_text(&line[..start]).await?.into_owned();
+    if pipeline.sanitize_value(&mut value).await? {
+        sanitized.push_str(&serde_json::to_string(&value)?);
+    } else {
+        sanitized.push_str(&line[start..end]);
+    }
+    sanitized.push_str(&pipeline.sanitize_text(&line[end..]).await?);
+    Ok(sanitized)
+}
+
+/// Byte range and value of the first JSON object or array that parses from a
+/// `{` or `[` in `line`.
+fn find_embedded_json(line: &str) -> Option<(usize, usize, Value)> {
+    line.char_indices()
+        .filter(|(_, c)| *c == '{' || *c == '[')
+        .find_map(|(start, _)| {
+            let mut values = serde_json::Deserializer::from_str(&line[start..]).into_iter::<Value>();
+            match values.next() {
+                Some(Ok(value)) if value.is_object() || value.is_array() => {
+                    Some((start, start + values.byte_offset(), value))
+                }
+                _ => None,
+            }
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DetectionMode;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_finds_json_inside_log_line() {
+        let line = r#"2025-06-01T10:00:00.000Z [files] [info] Message from client: {"method":"tools/call","id":4} { metadata: undefined }"#;
+        let (start, end, value) = find_embedded_json(line).unwrap();
+
+        assert_eq!(value["id"], 4);
+        assert!(line[..start].ends_with("client: "));
+        assert_eq!(&line[end..], " { metadata: undefined }");
+        assert!(find_embedded_json("[info] Server started").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_sanitizes_claude_logs_next_to_originals() {
+        let temp_dir = TempDir::new().unwrap();
+        let log = temp_dir.path().join("mcp-server-files.log");
+        std::fs::write(&log, concat!(
+            r#"2025-06-01T10:00:00.000Z [files] [info] Message from client: {"jsonrpc":"2.0","id":1,"method":"tools/call","params":{"name":"send","arguments":{"to":"jane.roe@acme.com"}}}"#, "\n",
+            "2025-06-01T10:00:01.000Z [files] [error] failed to reach jane.roe@acme.com\n",
+        )).unwrap();
+        std::fs::write(temp_dir.path().join("other.txt"), "jane.roe@acme.com").unwrap();
+
+        let mut config = Config::default();
+        config.detection.mode = DetectionMode::Regex;
+        config.mapping.database_path = temp_dir.path().join("mappings.db");
+
+        let report = sanitize_logs(&config, OllamaConfig::default(), LogClient::Claude, temp_dir.path(), false).await.unwrap();
+
+        assert_eq!(report.files, vec![temp_dir.path().join("mcp-server-files.sanitized.log")]);
+        assert_eq!((report.lines, report.changed_lines), (2, 2));
+        let sanitized = std::fs::read_to_string(&report.files[0]).unwrap();
+        assert!(!sanitized.contains("jane.roe@acme.com"));
+        assert!(sanitized.contains("[files] [error] failed to reach "));
+        // Originals are kept unless sanitizing in place
+        assert!(std::fs::read_to_string(&log).unwrap().contains("jane.roe@acme.com"));
+    }
+}
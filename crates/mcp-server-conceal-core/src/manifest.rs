@@ -0,0 +1,119 @@
+//! Per-session concealment manifest
+//!
+//! Every proxy session gets a manifest describing the ruleset it runs with:
+//! hashes of the whole configuration, of the pattern set, of the faking
+//! policy and of the LLM prompt, plus the model and detection mode. Its id is
+//! attached to every audit event of the session, so an anonymized artifact
+//! can be traced back to the exact rules that produced it. Manifests hold
+//! hashes and names only, never patterns, prompts or values.
+
+use crate::config::{Config, DetectionMode};
+use anyhow::Result;
+use serde::Serialize;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SessionManifest {
+    pub id: String,
+    pub created_at: u64,
+    pub proxy_version: String,
+    pub config_version: u32,
+    /// Hash of the complete configuration
+    pub config_hash: String,
+    /// Hash of the detection rules: patterns, key hints, name lists and scalar rules
+    pub pattern_set_hash: String,
+    pub pattern_count: usize,
+    /// Hash of how detections are replaced: faker settings and the mapping key algorithm
+    pub policy_hash: String,
+    pub detection_mode: String,
+    /// Unset in regex-only mode
+    pub model: Option<String>,
+    pub prompt_hash: Option<String>,
+}
+
+impl SessionManifest {
+    /// Describes a session running `config` with the resolved LLM prompt.
+    pub fn new(config: &Config, prompt_template: &str) -> Result<Self> {
+        let llm_in_use = !matches!(config.detection.mode, DetectionMode::Regex);
+        let model = config.llm.as_ref().filter(|llm| llm_in_use && llm.enabled).map(|llm| llm.model.clone());
+        let detection = &config.detection;
+
+        Ok(Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            created_at: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+            proxy_version: env!("CARGO_PKG_VERSION").to_string(),
+            config_version: config.config_version,
+            config_hash: hash_json(&serde_json::to_value(config)?),
+            pattern_set_hash: hash_json(&json!({
+                "patterns": detection.patterns,
+                "key_hints": detection.key_hints,
+                "names": detection.names,
+                "entropy": detection.entropy,
+                "key_material": detection.key_material,
+                "context": detection.context,
+                "phone": detection.phone,
+                "urls": detection.urls,
+                "identifiers": detection.identifiers,
+                "dictionaries": detection.dictionaries,
+                "scalars": detection.scalars,
+            })),
+            pattern_count: detection.patterns.len(),
+            policy_hash: hash_json(&json!({
+                "faker": config.faker,
+                "hash_algorithm": config.mapping.hash_algorithm,
+            })),
+            detection_mode: format!("{:?}", detection.mode),
+            prompt_hash: model.as_ref().map(|_| hex::encode(Sha256::digest(prompt_template.as_bytes()))),
+            model,
+        })
+    }
+
+    /// Writes the manifest to `<dir>/<id>.json` and returns its path.
+    pub fn write(&self, dir: &Path) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir)?;
+        let path = dir.join(format!("{}.json", self.id));
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .map_err(|e| anyhow::anyhow!("Failed to write session manifest {}: {}", path.display(), e))?;
+        Ok(path)
+    }
+}
+
+// serde_json objects are sorted by key, so equal settings hash equally
+// whatever the order of the config file or its hash maps
+fn hash_json(value: &serde_json::Value) -> String {
+    hex::encode(Sha256::digest(value.to_string().as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hashes_follow_the_ruleset() {
+        let config = Config::default();
+        let first = SessionManifest::new(&config, "prompt {text}").unwrap();
+        let second = SessionManifest::new(&config, "prompt {text}").unwrap();
+        assert_ne!(first.id, second.id);
+        assert_eq!(
+            (&first.config_hash, &first.pattern_set_hash, &first.policy_hash, &first.prompt_hash),
+            (&second.config_hash, &second.pattern_set_hash, &second.policy_hash, &second.prompt_hash)
+        );
+        assert_eq!(first.model.as_deref(), Some("llama3.2:3b"));
+
+        let mut changed = config.clone();
+        changed.detection.patterns.insert("employee_id".to_string(), r"\bEMP-\d{6}\b".into());
+        let manifest = SessionManifest::new(&changed, "prompt {text}").unwrap();
+        assert_ne!(manifest.pattern_set_hash, first.pattern_set_hash);
+        assert_ne!(manifest.config_hash, first.config_hash);
+        assert_eq!(manifest.policy_hash, first.policy_hash);
+        assert_ne!(SessionManifest::new(&config, "other {text}").unwrap().prompt_hash, first.prompt_hash);
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = first.write(temp_dir.path()).unwrap();
+        let written: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap();
+        assert_eq!(written["id"], first.id.as_str());
+    }
+}
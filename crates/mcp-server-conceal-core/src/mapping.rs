@@ -3,12 +3,25 @@
 //! This module provides persistent storage for PII entity mappings and LLM cache entries,
 //! ensuring consistency across sessions and supporting batch operations for performance.
 
-use crate::config::{AnonymizedEntity, DetectedEntity, MappingConfig};
+use crate::bloom::BloomFilter;
+use crate::crypto;
+use crate::config::{base_entity_type, AnonymizedEntity, DetectedEntity, MappingConfig};
+use crate::hashing::{ValueHasher, LEGACY_ALGORITHM};
+use crate::pipeline::Direction;
+use crate::redis_cache::RedisCache;
+use crate::secrets;
 use anyhow::Result;
+use lru::LruCache;
 use rusqlite::{params, Connection, OptionalExtension};
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::num::NonZeroUsize;
 use std::path::Path;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use tracing::{debug, info, warn};
 use uuid::Uuid;
 
@@ -34,6 +47,332 @@ pub struct LlmCacheEntry {
 pub struct MappingStore {
     conn: Connection,
     config: MappingConfig,
+    /// Rows of other namespaces in a shared database are never read or touched
+    namespace: String,
+    /// Per entity type, mappings removed by retention cleanup that had been used recently
+    expired_in_use: BTreeMap<String, u64>,
+    hasher: ValueHasher,
+    /// Algorithms of mappings keyed before the algorithm changed, rekeyed on use
+    previous_hashers: Vec<ValueHasher>,
+    /// Keys of stored mappings, so lookups of unseen values skip the database
+    known_values: Option<BloomFilter>,
+    /// With `encryption` on, originals are stored encrypted under this key so
+    /// fakes can be resolved back to them
+    pub(crate) original_key: Option<[u8; 32]>,
+    /// Encrypts exported bundles; derived when the passphrase is loaded anyway,
+    /// otherwise on first use
+    bundle_key: Option<[u8; 32]>,
+    /// Shared cache consulted before SQLite, when configured and reachable
+    redis: Option<RedisCache>,
+    /// Recently used mappings by `recent_mapping_key`, written through;
+    /// shared with the stores of the opposite direction and other sessions
+    recent_mappings: Option<SharedRecentMappings>,
+    /// Reused to build lookup keys of `recent_mappings` without allocating
+    recent_key_buffer: String,
+    /// Recently used LLM results by (model, text hash); lookups take `&self`
+    recent_llm_results: Option<RefCell<LruCache<(String, String), RecentLlmResult>>>,
+    counters: RuntimeCounters,
+}
+
+type SharedRecentMappings = Arc<Mutex<LruCache<String, RecentMapping>>>;
+
+struct RecentMapping {
+    fake_value: String,
+    /// When the row's `last_used_at` was last written
+    touched_at: u64,
+}
+
+struct RecentLlmResult {
+    entities: Vec<DetectedEntity>,
+    /// No later than the row's `expires_at`, so an expired entry is never served
+    valid_until: u64,
+}
+
+const ORIGINAL_KEY_CONTEXT: &[u8] = b"mcp-server-conceal mapping originals v1";
+const BUNDLE_KEY_CONTEXT: &[u8] = b"mcp-server-conceal mapping bundle v1";
+const BUNDLE_VERSION: u32 = 1;
+/// Hashed with a bundle's algorithm, so an import can tell whether its keyed
+/// hashes came from the same passphrase
+const BUNDLE_HASH_CHECK: &str = "mcp-server-conceal bundle hash check";
+
+/// Nonce and ciphertext of an original, both NULL without encryption.
+type EncryptedOriginal = (Option<Vec<u8>>, Option<Vec<u8>>);
+
+/// How [`MappingStore::export`] writes a bundle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BundleFormat {
+    /// Plain JSON with hashes and fakes only; originals are never written unencrypted
+    Json,
+    /// JSON encrypted with a key derived from the mapping passphrase; carries
+    /// the originals of stores with `encryption` on
+    EncryptedJson,
+}
+
+impl FromStr for BundleFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "json" => Ok(BundleFormat::Json),
+            "encrypted-json" | "encrypted" => Ok(BundleFormat::EncryptedJson),
+            other => Err(anyhow::anyhow!("Unknown bundle format '{}' (supported: json, encrypted-json)", other)),
+        }
+    }
+}
+
+/// One mapping in an exported bundle.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BundledMapping {
+    pub entity_type: String,
+    pub original_value_hash: String,
+    pub hash_algorithm: String,
+    pub fake_value: String,
+    pub created_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_used_at: Option<u64>,
+    /// Only in encrypted bundles
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct MappingBundle {
+    version: u32,
+    exported_at: u64,
+    namespace: String,
+    /// Algorithm of the exporting store, and its hash of `BUNDLE_HASH_CHECK`
+    hash_algorithm: String,
+    hash_check: String,
+    mappings: Vec<BundledMapping>,
+}
+
+/// File form of an encrypted bundle: a [`MappingBundle`] sealed with AES-256-GCM.
+#[derive(Debug, Serialize, Deserialize)]
+struct EncryptedBundle {
+    version: u32,
+    encryption: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+/// Outcome of [`MappingStore::import`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    pub imported: usize,
+    /// Values this store had already mapped; their fakes are kept
+    pub already_mapped: usize,
+    /// Keyed with another algorithm and without an original to rehash
+    pub unusable: usize,
+    /// Originals not kept because this store has `encryption` off
+    pub originals_dropped: usize,
+}
+
+/// A change to the mapping database schema, applied once per database in
+/// version order. Never edit a released migration; add a new one.
+struct Migration {
+    version: u32,
+    name: &'static str,
+    /// Table and column whose presence shows that a database from before
+    /// versioned migrations already has this change
+    marker: Option<(&'static str, &'static str)>,
+    sql: &'static str,
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial schema",
+        marker: None,
+        sql: "CREATE TABLE IF NOT EXISTS entity_mappings (
+                  id TEXT PRIMARY KEY,
+                  entity_type TEXT NOT NULL,
+                  original_value_hash TEXT NOT NULL,
+                  fake_value TEXT NOT NULL,
+                  created_at INTEGER NOT NULL,
+                  UNIQUE(entity_type, original_value_hash)
+              );
+              CREATE TABLE IF NOT EXISTS llm_cache (
+                  id TEXT PRIMARY KEY,
+                  text_hash TEXT NOT NULL,
+                  original_text TEXT NOT NULL,
+                  llm_result TEXT NOT NULL,
+                  model_name TEXT NOT NULL,
+                  created_at INTEGER NOT NULL,
+                  UNIQUE(text_hash, model_name)
+              );
+              -- Last placeholder number handed out per label, so `<<EMAIL_n>>` numbering
+              -- continues across restarts and both proxy directions share one sequence
+              CREATE TABLE IF NOT EXISTS placeholder_counters (
+                  label TEXT PRIMARY KEY,
+                  last_number INTEGER NOT NULL
+              );
+              -- Messages per combination of entity types replaced together, e.g.
+              -- address+dob+name; combinations drive re-identification risk
+              CREATE TABLE IF NOT EXISTS entity_cooccurrence (
+                  combination TEXT PRIMARY KEY,
+                  message_count INTEGER NOT NULL,
+                  last_seen_at INTEGER NOT NULL
+              );
+              CREATE TABLE IF NOT EXISTS store_metadata (
+                  key TEXT PRIMARY KEY,
+                  value TEXT NOT NULL
+              );
+              CREATE INDEX IF NOT EXISTS idx_entity_lookup ON entity_mappings(entity_type, original_value_hash);
+              CREATE INDEX IF NOT EXISTS idx_created_at ON entity_mappings(created_at);
+              CREATE INDEX IF NOT EXISTS idx_llm_cache_lookup ON llm_cache(text_hash, model_name);
+              CREATE INDEX IF NOT EXISTS idx_llm_cache_created_at ON llm_cache(created_at);",
+    },
+    Migration {
+        version: 2,
+        name: "mapping last use",
+        marker: Some(("entity_mappings", "last_used_at")),
+        sql: "ALTER TABLE entity_mappings ADD COLUMN last_used_at INTEGER;",
+    },
+    Migration {
+        version: 3,
+        name: "mapping hash algorithm",
+        // NULL until `prepare_rekeying` fills it in
+        marker: Some(("entity_mappings", "hash_algorithm")),
+        sql: "ALTER TABLE entity_mappings ADD COLUMN hash_algorithm TEXT;",
+    },
+    Migration {
+        version: 4,
+        name: "encrypted originals",
+        // Only written with encryption on, for reverse lookups
+        marker: Some(("entity_mappings", "original_ciphertext")),
+        sql: "ALTER TABLE entity_mappings ADD COLUMN original_nonce BLOB;
+              ALTER TABLE entity_mappings ADD COLUMN original_ciphertext BLOB;
+              CREATE INDEX IF NOT EXISTS idx_fake_lookup ON entity_mappings(entity_type, fake_value);",
+    },
+    Migration {
+        version: 5,
+        name: "adaptive llm cache ttl",
+        // Entries cached before expire with retention until their next hit
+        marker: Some(("llm_cache", "hit_count")),
+        sql: "ALTER TABLE llm_cache ADD COLUMN hit_count INTEGER NOT NULL DEFAULT 0;
+              ALTER TABLE llm_cache ADD COLUMN expires_at INTEGER;",
+    },
+    Migration {
+        version: 6,
+        name: "namespaces",
+        // SQLite cannot change a unique constraint in place, so the tables are
+        // rebuilt with their rows in the default namespace
+        marker: Some(("entity_mappings", "namespace")),
+        sql: "CREATE TABLE entity_mappings_namespaced (
+                  id TEXT PRIMARY KEY,
+                  namespace TEXT NOT NULL DEFAULT '',
+                  entity_type TEXT NOT NULL,
+                  original_value_hash TEXT NOT NULL,
+                  fake_value TEXT NOT NULL,
+                  created_at INTEGER NOT NULL,
+                  last_used_at INTEGER,
+                  hash_algorithm TEXT,
+                  original_nonce BLOB,
+                  original_ciphertext BLOB,
+                  UNIQUE(namespace, entity_type, original_value_hash)
+              );
+              INSERT INTO entity_mappings_namespaced
+                  (id, entity_type, original_value_hash, fake_value, created_at, last_used_at,
+                   hash_algorithm, original_nonce, original_ciphertext)
+              SELECT id, entity_type, original_value_hash, fake_value, created_at, last_used_at,
+                     hash_algorithm, original_nonce, original_ciphertext
+              FROM entity_mappings;
+              DROP TABLE entity_mappings;
+              ALTER TABLE entity_mappings_namespaced RENAME TO entity_mappings;
+
+              CREATE TABLE llm_cache_namespaced (
+                  id TEXT PRIMARY KEY,
+                  namespace TEXT NOT NULL DEFAULT '',
+                  text_hash TEXT NOT NULL,
+                  original_text TEXT NOT NULL,
+                  llm_result TEXT NOT NULL,
+                  model_name TEXT NOT NULL,
+                  created_at INTEGER NOT NULL,
+                  hit_count INTEGER NOT NULL DEFAULT 0,
+                  expires_at INTEGER,
+                  UNIQUE(namespace, text_hash, model_name)
+              );
+              INSERT INTO llm_cache_namespaced
+                  (id, text_hash, original_text, llm_result, model_name, created_at, hit_count, expires_at)
+              SELECT id, text_hash, original_text, llm_result, model_name, created_at, hit_count, expires_at
+              FROM llm_cache;
+              DROP TABLE llm_cache;
+              ALTER TABLE llm_cache_namespaced RENAME TO llm_cache;
+
+              CREATE TABLE placeholder_counters_namespaced (
+                  namespace TEXT NOT NULL DEFAULT '',
+                  label TEXT NOT NULL,
+                  last_number INTEGER NOT NULL,
+                  PRIMARY KEY(namespace, label)
+              );
+              INSERT INTO placeholder_counters_namespaced (label, last_number)
+              SELECT label, last_number FROM placeholder_counters;
+              DROP TABLE placeholder_counters;
+              ALTER TABLE placeholder_counters_namespaced RENAME TO placeholder_counters;
+
+              CREATE INDEX idx_entity_lookup ON entity_mappings(namespace, entity_type, original_value_hash);
+              CREATE INDEX idx_fake_lookup ON entity_mappings(namespace, entity_type, fake_value);
+              CREATE INDEX idx_created_at ON entity_mappings(created_at);
+              CREATE INDEX idx_llm_cache_lookup ON llm_cache(namespace, text_hash, model_name);
+              CREATE INDEX idx_llm_cache_created_at ON llm_cache(created_at);",
+    },
+    Migration {
+        version: 7,
+        name: "llm cache eviction",
+        // Size-bounded caches evict by last access; older entries count as
+        // accessed when created
+        marker: Some(("llm_cache", "last_accessed")),
+        sql: "ALTER TABLE llm_cache ADD COLUMN last_accessed INTEGER;
+              ALTER TABLE llm_cache ADD COLUMN size_bytes INTEGER NOT NULL DEFAULT 0;
+              UPDATE llm_cache SET last_accessed = created_at,
+                  size_bytes = LENGTH(CAST(original_text AS BLOB)) + LENGTH(CAST(llm_result AS BLOB));
+              CREATE INDEX IF NOT EXISTS idx_llm_cache_last_accessed ON llm_cache(namespace, last_accessed);",
+    },
+    Migration {
+        version: 8,
+        name: "identity clusters",
+        marker: None,
+        sql: "-- The fake person of each cluster of values describing one real person
+              CREATE TABLE IF NOT EXISTS identity_clusters (
+                  namespace TEXT NOT NULL DEFAULT '',
+                  cluster_id TEXT NOT NULL,
+                  fake_name TEXT NOT NULL,
+                  created_at INTEGER NOT NULL,
+                  PRIMARY KEY(namespace, cluster_id)
+              );
+              -- A value belongs to the first cluster it was linked to
+              CREATE TABLE IF NOT EXISTS identity_members (
+                  namespace TEXT NOT NULL DEFAULT '',
+                  entity_type TEXT NOT NULL,
+                  original_value_hash TEXT NOT NULL,
+                  cluster_id TEXT NOT NULL,
+                  PRIMARY KEY(namespace, entity_type, original_value_hash)
+              );",
+    },
+];
+
+fn applied_migrations(conn: &Connection) -> Result<BTreeSet<u32>> {
+    let mut stmt = conn.prepare("SELECT version FROM schema_migrations")?;
+    let versions = stmt.query_map([], |row| row.get(0))?.collect::<rusqlite::Result<_>>()?;
+    Ok(versions)
+}
+
+/// Text hashes per query in a batched LLM cache lookup, well under SQLite's
+/// bound-parameter limit.
+const LLM_CACHE_BATCH_SIZE: usize = 500;
+
+/// Entity type combinations listed in [`MappingStatistics`].
+const STATISTICS_COOCCURRENCE_LIMIT: usize = 10;
+
+/// Smallest capacity the known-value filter is built with.
+const MIN_FILTER_CAPACITY: usize = 10_000;
+
+/// How often a mapping served from memory writes its `last_used_at`. Expiry
+/// warnings count in days, so hourly is precise enough.
+const TOUCH_INTERVAL_SECS: u64 = 60 * 60;
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
 }
 
 impl MappingStore {
@@ -46,159 +385,722 @@ impl MappingStore {
             }
             Connection::open(&config.database_path)?
         };
+        configure_connection(&conn, &config)?;
 
-        let mut store = Self { conn, config };
-        store.initialize_schema()?;
+        let hasher = ValueHasher::from_config(&config)?;
+        let passphrase = if config.encryption {
+            Some(secrets::require_passphrase(&config)
+                .map_err(|e| anyhow::anyhow!("Mapping encryption needs the mapping passphrase: {}", e))?)
+        } else {
+            None
+        };
+        let original_key = passphrase.as_ref().map(|passphrase| crypto::derive_key(passphrase, ORIGINAL_KEY_CONTEXT));
+        let bundle_key = passphrase.as_ref().map(|passphrase| crypto::derive_key(passphrase, BUNDLE_KEY_CONTEXT));
+        // SQLite is the durable store, so an unreachable cache only costs speed
+        let redis = config.redis.as_ref().and_then(|redis_config| {
+            let mut redis_config = redis_config.clone();
+            if let Some(namespace) = config.namespace.as_ref() {
+                redis_config.key_prefix = format!("{}:{}", redis_config.key_prefix, namespace);
+            }
+            match RedisCache::connect(&redis_config, passphrase.as_ref()) {
+                Ok(cache) => {
+                    info!("Using Redis cache (LLM results cached: {})", cache.caches_llm_results());
+                    Some(cache)
+                }
+                Err(e) => {
+                    warn!("Redis cache unavailable, using SQLite only: {}", e);
+                    None
+                }
+            }
+        });
+        let recent_mappings = NonZeroUsize::new(config.memory_cache.mappings).map(|cap| Arc::new(Mutex::new(LruCache::new(cap))));
+        let recent_llm_results = NonZeroUsize::new(config.memory_cache.llm_results).map(|cap| RefCell::new(LruCache::new(cap)));
+        let mut store = Self {
+            conn,
+            namespace: config.namespace.clone().unwrap_or_default(),
+            config,
+            expired_in_use: BTreeMap::new(),
+            hasher,
+            previous_hashers: Vec::new(),
+            known_values: None,
+            original_key,
+            bundle_key,
+            redis,
+            recent_mappings,
+            recent_key_buffer: String::new(),
+            recent_llm_results,
+            counters: RuntimeCounters::default(),
+        };
+        store.migrate()?;
+        store.prepare_rekeying()?;
         store.cleanup_expired_mappings()?;
+        if store.config.bloom_filter {
+            store.rebuild_known_values()?;
+        }
         
-        info!("Initialized mapping store at {:?}", store.config.database_path);
+        info!("Initialized mapping store at {:?} ({} keys, namespace '{}')",
+              store.config.database_path, store.hasher.name(), store.namespace);
         Ok(store)
     }
 
-    fn initialize_schema(&mut self) -> Result<()> {
+    /// Brings the schema up to the latest migration. Each migration runs in
+    /// its own transaction, which also records it, so an interrupted upgrade
+    /// resumes where it stopped and concurrent connections apply it once.
+    fn migrate(&mut self) -> Result<()> {
         self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS entity_mappings (
-                id TEXT PRIMARY KEY,
-                entity_type TEXT NOT NULL,
-                original_value_hash TEXT NOT NULL,
-                fake_value TEXT NOT NULL,
-                created_at INTEGER NOT NULL,
-                UNIQUE(entity_type, original_value_hash)
+            "CREATE TABLE IF NOT EXISTS schema_migrations (
+                version INTEGER PRIMARY KEY,
+                name TEXT NOT NULL,
+                applied_at INTEGER NOT NULL
             )",
             [],
         )?;
+        self.adopt_unversioned_schema()?;
+
+        let latest = MIGRATIONS.last().map_or(0, |migration| migration.version);
+        let applied = applied_migrations(&self.conn)?;
+        if let Some(newer) = applied.iter().find(|version| **version > latest) {
+            return Err(anyhow::anyhow!(
+                "Mapping database {:?} has schema version {}, newer than this build supports ({}); upgrade mcp-server-conceal",
+                self.config.database_path, newer, latest
+            ));
+        }
+        if MIGRATIONS.iter().all(|migration| applied.contains(&migration.version)) {
+            return Ok(());
+        }
 
-        self.conn.execute(
-            "CREATE TABLE IF NOT EXISTS llm_cache (
-                id TEXT PRIMARY KEY,
-                text_hash TEXT NOT NULL,
-                original_text TEXT NOT NULL,
-                llm_result TEXT NOT NULL,
-                model_name TEXT NOT NULL,
-                created_at INTEGER NOT NULL,
-                UNIQUE(text_hash, model_name)
-            )",
+        let upgrading = !applied.is_empty();
+        for migration in MIGRATIONS {
+            let tx = self.conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+            // Another connection may have applied it while this one waited for the lock
+            if applied_migrations(&tx)?.contains(&migration.version) {
+                continue;
+            }
+            tx.execute_batch(migration.sql).map_err(|e| {
+                anyhow::anyhow!("Mapping database migration {} ({}) failed: {}", migration.version, migration.name, e)
+            })?;
+            tx.execute(
+                "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+                params![migration.version, migration.name, now_secs()],
+            )?;
+            tx.commit()?;
+            if upgrading {
+                info!("Upgraded mapping database to schema version {} ({})", migration.version, migration.name);
+            }
+        }
+
+        debug!("Database schema at version {}", latest);
+        Ok(())
+    }
+
+    /// Records the migrations a database created before versioned migrations
+    /// already has, judged by their marker columns. The initial migration only
+    /// creates what is missing, so it runs again on such databases.
+    fn adopt_unversioned_schema(&mut self) -> Result<()> {
+        let tx = self.conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        let versioned: bool = tx.query_row("SELECT COUNT(*) > 0 FROM schema_migrations", [], |row| row.get(0))?;
+        let has_mappings: bool = tx.query_row(
+            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'entity_mappings'",
             [],
+            |row| row.get(0),
         )?;
+        if versioned || !has_mappings {
+            return Ok(());
+        }
+
+        let mut adopted = Vec::new();
+        for migration in MIGRATIONS.iter().skip(1) {
+            let Some((table, column)) = migration.marker else {
+                break;
+            };
+            let present: bool = tx.query_row(
+                "SELECT COUNT(*) > 0 FROM pragma_table_info(?1) WHERE name = ?2",
+                params![table, column],
+                |row| row.get(0),
+            )?;
+            if !present {
+                break;
+            }
+            tx.execute(
+                "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+                params![migration.version, migration.name, now_secs()],
+            )?;
+            adopted.push(migration.version);
+        }
+        tx.commit()?;
+        info!("Mapping database predates schema versions; found migrations {:?} already applied", adopted);
+        Ok(())
+    }
 
+    /// Records the configured hash algorithm, and prepares hashers for the
+    /// mappings still keyed with an earlier one. Those are found by their old
+    /// hash and rekeyed the next time their value is looked up; the ones that
+    /// never come back expire with retention as usual.
+    fn prepare_rekeying(&mut self) -> Result<()> {
+        let recorded: Option<String> = self.conn
+            .query_row("SELECT value FROM store_metadata WHERE key = 'hash_algorithm'", [], |row| row.get(0))
+            .optional()?;
+        // Rows from before the algorithm was recorded were keyed with SipHash
+        let recorded = recorded.unwrap_or_else(|| LEGACY_ALGORITHM.to_string());
         self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_entity_lookup 
-             ON entity_mappings(entity_type, original_value_hash)",
-            [],
+            "UPDATE entity_mappings SET hash_algorithm = ?1 WHERE hash_algorithm IS NULL",
+            params![recorded],
         )?;
-
         self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_created_at 
-             ON entity_mappings(created_at)",
-            [],
+            "INSERT INTO store_metadata (key, value) VALUES ('hash_algorithm', ?1)
+             ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+            params![self.hasher.name()],
         )?;
 
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_llm_cache_lookup 
-             ON llm_cache(text_hash, model_name)",
-            [],
+        let mut stmt = self.conn.prepare(
+            "SELECT hash_algorithm, COUNT(*) FROM entity_mappings
+             WHERE namespace = ?2 AND hash_algorithm != ?1 GROUP BY hash_algorithm",
         )?;
+        let previous: Vec<(String, i64)> = stmt
+            .query_map(params![self.hasher.name(), self.namespace], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<rusqlite::Result<_>>()?;
+        drop(stmt);
+
+        for (name, count) in previous {
+            let hasher = match ValueHasher::from_recorded(&name, &self.config) {
+                Ok(hasher) if self.config.fips_mode && !hasher.is_fips_approved() => {
+                    return Err(anyhow::anyhow!(
+                        "Mapping database {:?} holds {} mapping(s) keyed with {}, which fips_mode does not allow; \
+                         start from a new database",
+                        self.config.database_path, count, name
+                    ));
+                }
+                Ok(hasher) => hasher,
+                Err(e) => {
+                    warn!("{} mapping(s) keyed with {} cannot be looked up and will get new fakes: {}", count, name, e);
+                    continue;
+                }
+            };
+            info!("{} mapping(s) keyed with {} are rekeyed to {} as their values reappear", count, name, self.hasher.name());
+            self.previous_hashers.push(hasher);
+        }
+        Ok(())
+    }
 
-        self.conn.execute(
-            "CREATE INDEX IF NOT EXISTS idx_llm_cache_created_at 
-             ON llm_cache(created_at)",
-            [],
+    /// Loads the keys of all stored mappings into a fresh filter with room to
+    /// grow. Mappings other connections store later are not in it; lookups of
+    /// those miss and the caller's insert-then-read keeps both sides consistent.
+    fn rebuild_known_values(&mut self) -> Result<()> {
+        let count: i64 = self.conn.query_row(
+            "SELECT COUNT(*) FROM entity_mappings WHERE namespace = ?1",
+            params![self.namespace],
+            |row| row.get(0),
         )?;
+        let mut filter = BloomFilter::with_capacity((count as usize * 2).max(MIN_FILTER_CAPACITY));
+        let mut stmt = self.conn.prepare("SELECT entity_type, original_value_hash FROM entity_mappings WHERE namespace = ?1")?;
+        let rows = stmt.query_map(params![self.namespace], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?;
+        for row in rows {
+            let (entity_type, original_hash) = row?;
+            filter.insert(&entity_type, &original_hash);
+        }
+        drop(stmt);
+
+        debug!("Known-value filter holds {} mapping(s), capacity {}", count, filter.capacity());
+        self.known_values = Some(filter);
+        Ok(())
+    }
 
-        debug!("Database schema initialized");
+    fn remember(&mut self, entity_type: &str, original_hash: &str) -> Result<()> {
+        let Some(filter) = self.known_values.as_mut() else {
+            return Ok(());
+        };
+        filter.insert(entity_type, original_hash);
+        if filter.is_saturated() {
+            self.rebuild_known_values()?;
+        }
         Ok(())
     }
 
+    /// False when the value has definitely never been mapped through this store.
+    fn may_be_mapped(&self, entity_type: &str, original_hash: &str) -> bool {
+        self.known_values.as_ref().is_none_or(|filter| filter.may_contain(entity_type, original_hash))
+    }
+
+    /// Algorithm that keys this database's mappings and cache entries.
+    pub fn hash_algorithm(&self) -> &'static str {
+        self.hasher.name()
+    }
+
     pub fn store_mapping(&mut self, anonymized: &AnonymizedEntity) -> Result<()> {
         let original_hash = self.hash_value(&anonymized.original_value);
+        // With a shared cache, the first proxy to map a value decides its fake
+        let fake_value = self.redis.as_ref()
+            .and_then(|redis| redis_result(redis.claim_mapping(&anonymized.entity_type, &original_hash, &anonymized.fake_value)))
+            .unwrap_or_else(|| anonymized.fake_value.clone());
+        self.insert_mapping(&anonymized.mapping_id, &anonymized.entity_type, &anonymized.original_value, &original_hash, &fake_value)
+    }
+
+    fn insert_mapping(
+        &mut self,
+        mapping_id: &str,
+        entity_type: &str,
+        original_value: &str,
+        original_hash: &str,
+        fake_value: &str,
+    ) -> Result<()> {
+        let (nonce, ciphertext) = self.encrypt_original(original_value)?;
         let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
 
-        self.conn.execute(
+        let inserted = self.conn.execute(
             "INSERT OR IGNORE INTO entity_mappings 
-             (id, entity_type, original_value_hash, fake_value, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5)",
+             (id, entity_type, original_value_hash, fake_value, created_at, last_used_at, hash_algorithm,
+              original_nonce, original_ciphertext, namespace)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?6, ?7, ?8, ?9)",
             params![
-                anonymized.mapping_id,
-                anonymized.entity_type,
+                mapping_id,
+                entity_type,
                 original_hash,
-                anonymized.fake_value,
-                now
+                fake_value,
+                now,
+                self.hasher.name(),
+                nonce,
+                ciphertext,
+                self.namespace
             ],
         )?;
+        self.remember(entity_type, original_hash)?;
+        // An existing row keeps its fake; the next lookup caches that one
+        if inserted > 0 {
+            self.remember_recent_mapping(entity_type, original_hash, fake_value, now);
+        }
 
         debug!("Stored mapping for entity type '{}': {} -> {}", 
-               anonymized.entity_type, original_hash, anonymized.fake_value);
+               entity_type, original_hash, fake_value);
         Ok(())
     }
 
-    pub fn get_mapping(&self, entity_type: &str, original_value: &str) -> Result<Option<String>> {
+    /// Reserves the next placeholder number for `label`, starting at 1.
+    pub fn next_placeholder_number(&mut self, label: &str) -> Result<u64> {
+        let number: i64 = self.conn.query_row(
+            "INSERT INTO placeholder_counters (namespace, label, last_number) VALUES (?2, ?1, 1)
+             ON CONFLICT(namespace, label) DO UPDATE SET last_number = last_number + 1
+             RETURNING last_number",
+            params![label, self.namespace],
+            |row| row.get(0),
+        )?;
+        Ok(number as u64)
+    }
+
+    pub fn get_mapping(&mut self, entity_type: &str, original_value: &str) -> Result<Option<String>> {
         let original_hash = self.hash_value(original_value);
-        
-        let fake_value: Option<String> = self.conn
+        if let Some(value) = self.recent_mapping(entity_type, &original_hash)? {
+            debug!("Retrieved mapping for '{}' from memory: {} -> {}", entity_type, original_hash, value);
+            return Ok(Some(value));
+        }
+
+        let cached = self.redis.as_ref()
+            .and_then(|redis| redis_result(redis.get_mapping(entity_type, &original_hash)))
+            .flatten();
+        if let Some(value) = cached {
+            self.keep_shared_mapping(entity_type, original_value, &original_hash, &value)?;
+            self.remember_recent_mapping(entity_type, &original_hash, &value, now_secs());
+            debug!("Retrieved mapping for '{}' from Redis: {} -> {}", entity_type, original_hash, value);
+            return Ok(Some(value));
+        }
+
+        if let Some(value) = self.find_mapping(entity_type, &original_hash)? {
+            self.touch(entity_type, &original_hash)?;
+            self.share_mapping(entity_type, &original_hash, &value);
+            self.remember_recent_mapping(entity_type, &original_hash, &value, now_secs());
+            debug!("Retrieved mapping for '{}': {} -> {}", entity_type, original_hash, value);
+            return Ok(Some(value));
+        }
+
+        for index in 0..self.previous_hashers.len() {
+            let old_hash = self.previous_hashers[index].hash(original_value);
+            if let Some(value) = self.find_mapping(entity_type, &old_hash)? {
+                self.rekey(entity_type, &old_hash, &original_hash)?;
+                self.share_mapping(entity_type, &original_hash, &value);
+                self.remember_recent_mapping(entity_type, &original_hash, &value, now_secs());
+                debug!("Rekeyed mapping for '{}' from {} to {}", entity_type, self.previous_hashers[index].name(), self.hasher.name());
+                return Ok(Some(value));
+            }
+        }
+        Ok(None)
+    }
+
+    /// A mapping held in memory. Its `last_used_at` is written at most once
+    /// per [`TOUCH_INTERVAL_SECS`], so repeated hits skip the database.
+    fn recent_mapping(&mut self, entity_type: &str, original_hash: &str) -> Result<Option<String>> {
+        let Some(recent) = self.recent_mappings.as_ref() else {
+            return Ok(None);
+        };
+        recent_mapping_key(&mut self.recent_key_buffer, &self.namespace, entity_type, original_hash);
+        let (value, stale) = {
+            let mut recent = lock_recent(recent);
+            let Some(entry) = recent.get_mut(self.recent_key_buffer.as_str()) else {
+                return Ok(None);
+            };
+            let now = now_secs();
+            let stale = now >= entry.touched_at + TOUCH_INTERVAL_SECS;
+            if stale {
+                entry.touched_at = now;
+            }
+            (entry.fake_value.clone(), stale)
+        };
+        if stale {
+            self.touch(entity_type, original_hash)?;
+        }
+        Ok(Some(value))
+    }
+
+    fn remember_recent_mapping(&mut self, entity_type: &str, original_hash: &str, fake_value: &str, touched_at: u64) {
+        if let Some(recent) = self.recent_mappings.as_ref() {
+            let entry = RecentMapping { fake_value: fake_value.to_string(), touched_at };
+            let mut key = String::new();
+            recent_mapping_key(&mut key, &self.namespace, entity_type, original_hash);
+            lock_recent(recent).put(key, entry);
+        }
+    }
+
+    /// Keeps a durable copy of a mapping another proxy made, in case the
+    /// shared cache entry expires. The database decides whether it is there
+    /// already: the Bloom filter is off or can answer "maybe" for a row that
+    /// does not exist.
+    fn keep_shared_mapping(&mut self, entity_type: &str, original_value: &str, original_hash: &str, fake_value: &str) -> Result<()> {
+        if self.find_mapping(entity_type, original_hash)?.is_none() {
+            self.insert_mapping(&Uuid::new_v4().to_string(), entity_type, original_value, original_hash, fake_value)?;
+        }
+        Ok(())
+    }
+
+    /// Puts a mapping found in SQLite into the shared cache.
+    fn share_mapping(&self, entity_type: &str, original_hash: &str, fake_value: &str) {
+        if let Some(redis) = self.redis.as_ref() {
+            redis_result(redis.claim_mapping(entity_type, original_hash, fake_value));
+        }
+    }
+
+    /// Whether `value` is a fake this namespace handed out for `entity_type`,
+    /// as found under any key path (`email@to`, `email@cc`, ...).
+    pub fn is_fake(&self, entity_type: &str, value: &str) -> Result<bool> {
+        let base_type = base_entity_type(entity_type);
+        // `type@path` sorts between `type@` and `typeA`, so the index is used
+        let found: Option<i64> = self.conn
             .query_row(
-                "SELECT fake_value FROM entity_mappings 
-                 WHERE entity_type = ?1 AND original_value_hash = ?2",
-                params![entity_type, original_hash],
+                "SELECT 1 FROM entity_mappings WHERE namespace = ?3 AND fake_value = ?2
+                 AND (entity_type = ?1 OR (entity_type >= ?1 || '@' AND entity_type < ?1 || 'A')) LIMIT 1",
+                params![base_type, value, self.namespace],
                 |row| row.get(0),
             )
             .optional()?;
+        Ok(found.is_some())
+    }
+
+    /// The original a fake replaced, for authorized de-anonymization, as
+    /// stored for `entity_type` under any key path. Needs `encryption`, since
+    /// only then are originals stored (encrypted); mappings stored before it
+    /// was turned on cannot be resolved. Fails when the fake was handed out
+    /// for more than one original, or for one whose value was not stored.
+    pub fn get_original(&self, entity_type: &str, fake_value: &str) -> Result<Option<String>> {
+        let key = self.original_key.as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Reverse lookups need mapping.encryption = true"))?;
+        let base_type = base_entity_type(entity_type);
+        let mut stmt = self.conn.prepare_cached(
+            "SELECT hash_algorithm, original_value_hash, original_nonce, original_ciphertext FROM entity_mappings
+             WHERE namespace = ?3 AND fake_value = ?2
+             AND (entity_type = ?1 OR (entity_type >= ?1 || '@' AND entity_type < ?1 || 'A'))",
+        )?;
+        let rows: Vec<((Option<String>, String), EncryptedOriginal)> = stmt
+            .query_map(params![base_type, fake_value, self.namespace], |row| {
+                Ok(((row.get(0)?, row.get(1)?), (row.get(2)?, row.get(3)?)))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+
+        let mut originals = HashSet::new();
+        let mut resolved_keys = HashSet::new();
+        for (hash_key, stored) in &rows {
+            if let (Some(nonce), Some(ciphertext)) = stored {
+                let original = crypto::decrypt(key, nonce, ciphertext)
+                    .map_err(|e| anyhow::anyhow!("Failed to decrypt original, was the passphrase changed? {}", e))?;
+                originals.insert(String::from_utf8(original)?);
+                resolved_keys.insert(hash_key);
+            }
+        }
+        // A row without its original may stand for another value, unless its key is one already resolved
+        let unresolved = rows.iter().any(|(hash_key, _)| !resolved_keys.contains(hash_key));
+        match originals.len() {
+            0 => {
+                if !rows.is_empty() {
+                    debug!("Mapping for '{}' was stored without its original", entity_type);
+                }
+                Ok(None)
+            }
+            1 if !unresolved => Ok(originals.into_iter().next()),
+            _ => Err(anyhow::anyhow!(
+                "The {} fake was handed out for {} different originals, so it cannot be resolved",
+                base_type,
+                if unresolved { "several".to_string() } else { originals.len().to_string() }
+            )),
+        }
+    }
 
-        if let Some(ref value) = fake_value {
-            debug!("Retrieved mapping for '{}': {} -> {}", 
-                   entity_type, original_hash, value);
+    /// Writes this namespace's mappings to `path`. Encrypted bundles carry the
+    /// originals when `encryption` is on, so they can be resolved elsewhere
+    /// with the same passphrase. Returns the number of mappings written.
+    pub fn export(&mut self, path: &Path, format: BundleFormat) -> Result<usize> {
+        let mut stmt = self.conn.prepare(
+            "SELECT entity_type, original_value_hash, hash_algorithm, fake_value, created_at, last_used_at,
+                    original_nonce, original_ciphertext
+             FROM entity_mappings WHERE namespace = ?1 ORDER BY created_at, id",
+        )?;
+        let rows: Vec<(BundledMapping, EncryptedOriginal)> = stmt
+            .query_map(params![self.namespace], |row| {
+                Ok((
+                    BundledMapping {
+                        entity_type: row.get(0)?,
+                        original_value_hash: row.get(1)?,
+                        hash_algorithm: row.get::<_, Option<String>>(2)?.unwrap_or_else(|| self.hasher.name().to_string()),
+                        fake_value: row.get(3)?,
+                        created_at: row.get(4)?,
+                        last_used_at: row.get(5)?,
+                        original: None,
+                    },
+                    (row.get(6)?, row.get(7)?),
+                ))
+            })?
+            .collect::<rusqlite::Result<_>>()?;
+        drop(stmt);
+
+        let mut mappings = Vec::with_capacity(rows.len());
+        for (mut mapping, encrypted) in rows {
+            if let (BundleFormat::EncryptedJson, Some(key), (Some(nonce), Some(ciphertext))) = (format, self.original_key.as_ref(), encrypted) {
+                let original = crypto::decrypt(key, &nonce, &ciphertext)
+                    .map_err(|e| anyhow::anyhow!("Failed to decrypt original, was the passphrase changed? {}", e))?;
+                mapping.original = Some(String::from_utf8(original)?);
+            }
+            mappings.push(mapping);
         }
 
-        Ok(fake_value)
+        let count = mappings.len();
+        let bundle = MappingBundle {
+            version: BUNDLE_VERSION,
+            exported_at: now_secs(),
+            namespace: self.namespace.clone(),
+            hash_algorithm: self.hasher.name().to_string(),
+            hash_check: self.hasher.hash(BUNDLE_HASH_CHECK),
+            mappings,
+        };
+        let contents = match format {
+            BundleFormat::Json => serde_json::to_string_pretty(&bundle)?,
+            BundleFormat::EncryptedJson => {
+                let (nonce, ciphertext) = crypto::encrypt(&self.bundle_key()?, &serde_json::to_vec(&bundle)?)?;
+                serde_json::to_string_pretty(&EncryptedBundle {
+                    version: BUNDLE_VERSION,
+                    encryption: "aes-256-gcm".to_string(),
+                    nonce: hex::encode(nonce),
+                    ciphertext: hex::encode(ciphertext),
+                })?
+            }
+        };
+        std::fs::write(path, contents)
+            .map_err(|e| anyhow::anyhow!("Failed to write mapping bundle {}: {}", path.display(), e))?;
+
+        info!("Exported {} mapping(s) of namespace '{}' to {}", count, self.namespace, path.display());
+        Ok(count)
+    }
+
+    /// Merges a bundle written by [`export`](Self::export), in either format,
+    /// into this namespace. Values already mapped here keep their fakes.
+    pub fn import(&mut self, path: &Path) -> Result<ImportReport> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read mapping bundle {}: {}", path.display(), e))?;
+        let value: serde_json::Value = serde_json::from_str(&contents)
+            .map_err(|e| anyhow::anyhow!("{} is not a mapping bundle: {}", path.display(), e))?;
+        let bundle: MappingBundle = if value.get("ciphertext").is_some() {
+            let sealed: EncryptedBundle = serde_json::from_value(value)?;
+            let plaintext = crypto::decrypt(&self.bundle_key()?, &hex::decode(&sealed.nonce)?, &hex::decode(&sealed.ciphertext)?)
+                .map_err(|e| anyhow::anyhow!("Cannot open mapping bundle, was it exported with another passphrase? {}", e))?;
+            serde_json::from_slice(&plaintext)?
+        } else {
+            serde_json::from_value(value)?
+        };
+        if bundle.version > BUNDLE_VERSION {
+            return Err(anyhow::anyhow!("Mapping bundle version {} is newer than this build supports", bundle.version));
+        }
+        let same_hashes = bundle.hash_algorithm == self.hasher.name();
+        if same_hashes && bundle.hash_check != self.hasher.hash(BUNDLE_HASH_CHECK) {
+            return Err(anyhow::anyhow!(
+                "Mapping bundle hashes were keyed with another passphrase or key file; configure the passphrase \
+                 of the exporting machine, or copy the .key file next to its database"
+            ));
+        }
+
+        let mut report = ImportReport::default();
+        let mut rows = Vec::with_capacity(bundle.mappings.len());
+        for mapping in &bundle.mappings {
+            // Rehashing with this store's algorithm works whatever the bundle used
+            let original_hash = match mapping.original.as_deref() {
+                Some(original) => self.hash_value(original),
+                None if same_hashes && mapping.hash_algorithm == self.hasher.name() => mapping.original_value_hash.clone(),
+                None => {
+                    report.unusable += 1;
+                    continue;
+                }
+            };
+            let encrypted = match mapping.original.as_deref() {
+                Some(original) if self.original_key.is_some() => self.encrypt_original(original)?,
+                Some(_) => {
+                    report.originals_dropped += 1;
+                    (None, None)
+                }
+                None => (None, None),
+            };
+            rows.push((mapping, original_hash, encrypted));
+        }
+
+        let tx = self.conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR IGNORE INTO entity_mappings
+                 (id, entity_type, original_value_hash, fake_value, created_at, last_used_at, hash_algorithm,
+                  original_nonce, original_ciphertext, namespace)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            )?;
+            for (mapping, original_hash, (nonce, ciphertext)) in &rows {
+                let inserted = stmt.execute(params![
+                    Uuid::new_v4().to_string(),
+                    mapping.entity_type,
+                    original_hash,
+                    mapping.fake_value,
+                    mapping.created_at,
+                    mapping.last_used_at,
+                    self.hasher.name(),
+                    nonce,
+                    ciphertext,
+                    self.namespace
+                ])?;
+                if inserted > 0 {
+                    report.imported += 1;
+                } else {
+                    report.already_mapped += 1;
+                }
+            }
+        }
+        tx.commit()?;
+        for (mapping, original_hash, _) in &rows {
+            self.remember(&mapping.entity_type, original_hash)?;
+        }
+
+        info!("Imported {} mapping(s) from {} into namespace '{}' ({} already mapped, {} unusable)",
+              report.imported, path.display(), self.namespace, report.already_mapped, report.unusable);
+        Ok(report)
+    }
+
+    fn bundle_key(&mut self) -> Result<[u8; 32]> {
+        if let Some(key) = self.bundle_key {
+            return Ok(key);
+        }
+        let passphrase = secrets::require_passphrase(&self.config)
+            .map_err(|e| anyhow::anyhow!("Encrypted mapping bundles need the mapping passphrase: {}", e))?;
+        let key = crypto::derive_key(&passphrase, BUNDLE_KEY_CONTEXT);
+        self.bundle_key = Some(key);
+        Ok(key)
+    }
+
+    fn encrypt_original(&self, original: &str) -> Result<EncryptedOriginal> {
+        match self.original_key.as_ref() {
+            Some(key) => {
+                let (nonce, ciphertext) = crypto::encrypt(key, original.as_bytes())?;
+                Ok((Some(nonce), Some(ciphertext)))
+            }
+            None => Ok((None, None)),
+        }
+    }
+
+    fn find_mapping(&self, entity_type: &str, original_hash: &str) -> Result<Option<String>> {
+        if !self.may_be_mapped(entity_type, original_hash) {
+            return Ok(None);
+        }
+        Ok(self.conn
+            .query_row(
+                "SELECT fake_value FROM entity_mappings 
+                 WHERE namespace = ?3 AND entity_type = ?1 AND original_value_hash = ?2",
+                params![entity_type, original_hash, self.namespace],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Moves a mapping found under an old hash to the current one. If the
+    /// other direction rekeyed it first, the old row is simply dropped.
+    fn rekey(&mut self, entity_type: &str, old_hash: &str, new_hash: &str) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.conn.execute(
+            "UPDATE OR IGNORE entity_mappings SET original_value_hash = ?3, hash_algorithm = ?4, last_used_at = ?5
+             WHERE namespace = ?6 AND entity_type = ?1 AND original_value_hash = ?2",
+            params![entity_type, old_hash, new_hash, self.hasher.name(), now, self.namespace],
+        )?;
+        self.conn.execute(
+            "DELETE FROM entity_mappings WHERE namespace = ?3 AND entity_type = ?1 AND original_value_hash = ?2",
+            params![entity_type, old_hash, self.namespace],
+        )?;
+        self.remember(entity_type, new_hash)
     }
 
     pub fn store_mappings_batch(&mut self, anonymized_entities: &[AnonymizedEntity]) -> Result<()> {
         let hashed_entities: Vec<_> = anonymized_entities.iter()
-            .map(|e| (e, self.hash_value(&e.original_value)))
-            .collect();
+            .map(|e| Ok((e, self.hash_value(&e.original_value), self.encrypt_original(&e.original_value)?)))
+            .collect::<Result<_>>()?;
         
-        let tx = self.conn.transaction()?;
+        // Take the write lock up front; a deferred transaction that has to
+        // upgrade it can fail with SQLITE_BUSY without waiting
+        let tx = self.conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
         let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let mut inserted_entities = Vec::new();
 
         {
             let mut stmt = tx.prepare(
                 "INSERT OR IGNORE INTO entity_mappings 
-                 (id, entity_type, original_value_hash, fake_value, created_at)
-                 VALUES (?1, ?2, ?3, ?4, ?5)"
+                 (id, entity_type, original_value_hash, fake_value, created_at, last_used_at, hash_algorithm,
+                  original_nonce, original_ciphertext, namespace)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?5, ?6, ?7, ?8, ?9)"
             )?;
 
-            for (anonymized, original_hash) in hashed_entities {
-                stmt.execute(params![
+            for (anonymized, original_hash, (nonce, ciphertext)) in &hashed_entities {
+                let inserted = stmt.execute(params![
                     anonymized.mapping_id,
                     anonymized.entity_type,
                     original_hash,
                     anonymized.fake_value,
-                    now
+                    now,
+                    self.hasher.name(),
+                    nonce,
+                    ciphertext,
+                    self.namespace
                 ])?;
+                if inserted > 0 {
+                    inserted_entities.push((anonymized, original_hash));
+                }
             }
         }
 
         tx.commit()?;
+        for (anonymized, original_hash, _) in &hashed_entities {
+            self.remember(&anonymized.entity_type, original_hash)?;
+        }
+        for (anonymized, original_hash) in inserted_entities {
+            self.remember_recent_mapping(&anonymized.entity_type, original_hash, &anonymized.fake_value, now);
+        }
         debug!("Stored batch of {} mappings", anonymized_entities.len());
         Ok(())
     }
 
-    pub fn get_mappings_batch(&self, requests: &[(String, String)]) -> Result<HashMap<String, String>> {
-        let mut results = HashMap::new();
-        
-        let mut stmt = self.conn.prepare(
-            "SELECT fake_value FROM entity_mappings 
-             WHERE entity_type = ?1 AND original_value_hash = ?2"
-        )?;
+    /// Takes the per-type counts of recently used mappings that retention
+    /// cleanup has removed since the last call.
+    pub fn take_expired_in_use(&mut self) -> BTreeMap<String, u64> {
+        std::mem::take(&mut self.expired_in_use)
+    }
 
+    pub fn get_mappings_batch(&mut self, requests: &[(String, String)]) -> Result<HashMap<String, String>> {
+        let mut results = HashMap::new();
         for (entity_type, original_value) in requests {
-            let original_hash = self.hash_value(original_value);
-            
-            if let Some(fake_value) = stmt
-                .query_row(params![entity_type, original_hash], |row| {
-                    row.get::<_, String>(0)
-                })
-                .optional()?
-            {
+            if let Some(fake_value) = self.get_mapping(entity_type, original_value)? {
                 results.insert(original_value.clone(), fake_value);
             }
         }
@@ -208,32 +1110,151 @@ impl MappingStore {
         Ok(results)
     }
 
+    /// Records that a mapping was just used to replace a value.
+    fn touch(&self, entity_type: &str, original_hash: &str) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.conn.execute(
+            "UPDATE entity_mappings SET last_used_at = ?3
+             WHERE namespace = ?4 AND entity_type = ?1 AND original_value_hash = ?2",
+            params![entity_type, original_hash, now, self.namespace],
+        )?;
+        Ok(())
+    }
+
     pub fn cleanup_expired_mappings(&mut self) -> Result<usize> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let outlived_cache = if self.config.llm_cache_ttl.adaptive {
+            self.conn.execute(
+                "DELETE FROM llm_cache WHERE namespace = ?2 AND expires_at <= ?1",
+                params![now, self.namespace],
+            )?
+        } else {
+            0
+        };
+        // Bulk deletes are not tracked per entry; the memory caches start over
+        if let Some(recent) = self.recent_llm_results.as_ref().filter(|_| outlived_cache > 0) {
+            recent.borrow_mut().clear();
+        }
+        if outlived_cache > 0 {
+            info!("Removed {} LLM cache entries past their adaptive TTL", outlived_cache);
+        }
+
         if let Some(retention_days) = self.config.retention_days {
-            let cutoff_time = SystemTime::now()
-                .duration_since(UNIX_EPOCH)?
-                .as_secs()
-                .saturating_sub(retention_days as u64 * 24 * 60 * 60);
+            let cutoff_time = now.saturating_sub(retention_days as u64 * 24 * 60 * 60);
+
+            if self.config.expiry_warning_days > 0 {
+                let recent_cutoff = now.saturating_sub(self.config.expiry_warning_days as u64 * 24 * 60 * 60);
+                let mut stmt = self.conn.prepare(
+                    "SELECT entity_type, COUNT(*) FROM entity_mappings
+                     WHERE namespace = ?3 AND created_at < ?1 AND last_used_at >= ?2
+                     GROUP BY entity_type"
+                )?;
+                let in_use = stmt
+                    .query_map(params![cutoff_time, recent_cutoff, self.namespace], |row| {
+                        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                for (entity_type, count) in in_use {
+                    warn!("Expiring {} '{}' mapping(s) used within the last {} days; \
+                           pseudonyms in earlier output can no longer be mapped back",
+                          count, entity_type, self.config.expiry_warning_days);
+                    *self.expired_in_use.entry(entity_type).or_default() += count as u64;
+                }
+            }
 
             let deleted_mappings = self.conn.execute(
-                "DELETE FROM entity_mappings WHERE created_at < ?1",
-                params![cutoff_time],
+                "DELETE FROM entity_mappings WHERE namespace = ?2 AND created_at < ?1",
+                params![cutoff_time, self.namespace],
             )?;
 
             let deleted_cache = self.conn.execute(
-                "DELETE FROM llm_cache WHERE created_at < ?1",
-                params![cutoff_time],
+                "DELETE FROM llm_cache WHERE namespace = ?2 AND created_at < ?1",
+                params![cutoff_time, self.namespace],
             )?;
 
+            if deleted_mappings > 0 {
+                self.forget_recent_mappings();
+            }
+            if let Some(recent) = self.recent_llm_results.as_ref().filter(|_| deleted_cache > 0) {
+                recent.borrow_mut().clear();
+            }
+
             let total_deleted = deleted_mappings + deleted_cache;
             if total_deleted > 0 {
                 info!("Cleaned up {} expired entries ({} mappings, {} cache) older than {} days", 
                       total_deleted, deleted_mappings, deleted_cache, retention_days);
             }
 
-            Ok(total_deleted)
+            Ok(total_deleted + outlived_cache)
         } else {
-            Ok(0)
+            Ok(outlived_cache)
+        }
+    }
+
+    /// Entries that expired before this time count as misses; 0 without
+    /// adaptive TTLs.
+    fn llm_cache_cutoff(&self) -> Result<u64> {
+        if !self.config.llm_cache_ttl.adaptive {
+            return Ok(0);
+        }
+        Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+    }
+
+    /// Counts a hit on each entry and, with adaptive TTLs, doubles its
+    /// lifetime from now, up to the maximum.
+    fn record_llm_cache_hits(&self, model_name: &str, text_hashes: &[&str]) -> Result<()> {
+        let ttl = &self.config.llm_cache_ttl;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs() as i64;
+        let min_ttl = ttl.min_ttl_secs().map(|secs| secs as i64);
+        for chunk in text_hashes.chunks(LLM_CACHE_BATCH_SIZE) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            // The shift is capped so it cannot overflow; MIN caps the lifetime
+            let sql = format!(
+                "UPDATE llm_cache SET hit_count = hit_count + 1, last_accessed = ?2,
+                 expires_at = CASE WHEN ?1 IS NULL THEN expires_at
+                                   ELSE ?2 + MIN(?3, ?1 * (1 << MIN(hit_count + 1, 20))) END
+                 WHERE model_name = ?4 AND namespace = ?5 AND text_hash IN ({})",
+                placeholders
+            );
+            let max_ttl = ttl.max_ttl_secs() as i64;
+            let params = [&min_ttl as &dyn rusqlite::ToSql, &now, &max_ttl, &model_name, &self.namespace]
+                .into_iter()
+                .chain(chunk.iter().map(|hash| hash as &dyn rusqlite::ToSql));
+            self.conn.execute(&sql, rusqlite::params_from_iter(params))?;
+        }
+
+        // A hit extends the entry at least to the initial TTL from now
+        if let Some(recent) = self.recent_llm_results.as_ref() {
+            let valid_until = self.llm_result_valid_until(now as u64);
+            let mut recent = recent.borrow_mut();
+            for hash in text_hashes {
+                if let Some(entry) = recent.peek_mut(&(model_name.to_string(), hash.to_string())) {
+                    entry.valid_until = valid_until;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn llm_result_valid_until(&self, now: u64) -> u64 {
+        self.config.llm_cache_ttl.min_ttl_secs().map_or(u64::MAX, |ttl| now + ttl)
+    }
+
+    /// An LLM result held in memory, unless it may have expired.
+    fn recent_llm_result(&self, model_name: &str, text_hash: &str) -> Option<Vec<DetectedEntity>> {
+        let mut recent = self.recent_llm_results.as_ref()?.borrow_mut();
+        let key = (model_name.to_string(), text_hash.to_string());
+        if recent.get(&key)?.valid_until > now_secs() {
+            return recent.get(&key).map(|entry| entry.entities.clone());
+        }
+        recent.pop(&key);
+        None
+    }
+
+    fn remember_llm_result(&self, model_name: &str, text_hash: &str, entities: &[DetectedEntity], valid_until: u64) {
+        if let Some(recent) = self.recent_llm_results.as_ref() {
+            let entry = RecentLlmResult { entities: entities.to_vec(), valid_until };
+            recent.borrow_mut().put((model_name.to_string(), text_hash.to_string()), entry);
         }
     }
 
@@ -242,33 +1263,126 @@ impl MappingStore {
         let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
         let id = Uuid::new_v4().to_string();
         let llm_result_json = serde_json::to_string(entities)?;
+        // New entries start with the short TTL of a one-off text
+        let expires_at = self.config.llm_cache_ttl.min_ttl_secs().map(|ttl| now + ttl);
+
+        let size_bytes = text.len() + llm_result_json.len();
 
         self.conn.execute(
             "INSERT OR REPLACE INTO llm_cache 
-             (id, text_hash, original_text, llm_result, model_name, created_at)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
-            params![id, text_hash, text, llm_result_json, model_name, now],
+             (id, text_hash, original_text, llm_result, model_name, created_at, hit_count, expires_at, namespace,
+              last_accessed, size_bytes)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 0, ?7, ?8, ?6, ?9)",
+            params![id, text_hash, text, llm_result_json, model_name, now, expires_at, self.namespace, size_bytes],
         )?;
+        self.evict_llm_cache()?;
+        if let Some(redis) = self.redis.as_ref() {
+            redis_result(redis.set_llm_result(model_name, &text_hash, entities));
+        }
+        self.remember_llm_result(model_name, &text_hash, entities, expires_at.unwrap_or(u64::MAX));
 
         debug!("Stored LLM cache entry for text hash '{}' with {} entities", 
                text_hash, entities.len());
         Ok(())
     }
 
+    /// Drops the least recently used entries of this namespace until it is
+    /// within `max_cache_entries` and `max_cache_bytes`.
+    fn evict_llm_cache(&self) -> Result<usize> {
+        let mut evicted = 0;
+        if let Some(max_entries) = self.config.max_cache_entries {
+            evicted += self.conn.execute(
+                "DELETE FROM llm_cache WHERE id IN (
+                     SELECT id FROM llm_cache WHERE namespace = ?1
+                     ORDER BY last_accessed DESC, created_at DESC, id LIMIT -1 OFFSET ?2)",
+                params![self.namespace, max_entries],
+            )?;
+        }
+        if let Some(max_bytes) = self.config.max_cache_bytes {
+            // Keeps the most recently used entries whose sizes add up to the limit
+            evicted += self.conn.execute(
+                "DELETE FROM llm_cache WHERE id IN (
+                     SELECT id FROM (
+                         SELECT id, SUM(size_bytes) OVER (
+                             ORDER BY last_accessed DESC, created_at DESC, id ROWS UNBOUNDED PRECEDING
+                         ) AS retained
+                         FROM llm_cache WHERE namespace = ?1)
+                     WHERE retained > ?2)",
+                params![self.namespace, max_bytes],
+            )?;
+        }
+        if evicted > 0 {
+            debug!("Evicted {} least recently used LLM cache entries", evicted);
+        }
+        Ok(evicted)
+    }
+
+    /// Shares `counters` with other stores, so a proxy's directions add up.
+    pub fn with_counters(mut self, counters: RuntimeCounters) -> Self {
+        self.counters = counters;
+        self
+    }
+
+    /// Shares the recently used mappings held in memory with `other`, so a
+    /// mapping either store deletes is gone for both. Entries are kept per
+    /// namespace, so stores of other namespaces may share them too.
+    pub fn sharing_recent_mappings(mut self, other: &MappingStore) -> Self {
+        if other.recent_mappings.is_some() {
+            self.recent_mappings = other.recent_mappings.clone();
+        }
+        self
+    }
+
+    /// Drops the recently used mappings held in memory, for every store
+    /// sharing them, after rows were deleted from under them.
+    pub fn forget_recent_mappings(&self) {
+        if let Some(recent) = self.recent_mappings.as_ref() {
+            lock_recent(recent).clear();
+        }
+    }
+
+    pub fn config(&self) -> &MappingConfig {
+        &self.config
+    }
+
+    pub fn counters(&self) -> &RuntimeCounters {
+        &self.counters
+    }
+
     pub fn get_llm_cache(&self, text: &str, model_name: &str) -> Result<Option<Vec<DetectedEntity>>> {
+        let cached = self.lookup_llm_cache(text, model_name)?;
+        let hit = cached.is_some() as usize;
+        self.counters.record_llm_cache_lookups(hit, 1 - hit);
+        Ok(cached)
+    }
+
+    fn lookup_llm_cache(&self, text: &str, model_name: &str) -> Result<Option<Vec<DetectedEntity>>> {
         let text_hash = self.hash_value(text);
+        if let Some(entities) = self.recent_llm_result(model_name, &text_hash) {
+            self.record_llm_cache_hits(model_name, &[text_hash.as_str()])?;
+            debug!("Retrieved LLM cache hit for text hash '{}' from memory", text_hash);
+            return Ok(Some(entities));
+        }
+        if let Some(entities) = self.cached_llm_results(model_name, &[text_hash.as_str()]).pop().flatten() {
+            self.remember_llm_result(model_name, &text_hash, &entities, self.llm_result_valid_until(now_secs()));
+            debug!("Retrieved LLM cache hit for text hash '{}' from Redis", text_hash);
+            return Ok(Some(entities));
+        }
         
         let cache_result: Option<String> = self.conn
             .query_row(
                 "SELECT llm_result FROM llm_cache 
-                 WHERE text_hash = ?1 AND model_name = ?2",
-                params![text_hash, model_name],
+                 WHERE namespace = ?4 AND text_hash = ?1 AND model_name = ?2 AND (expires_at IS NULL OR expires_at > ?3)",
+                params![text_hash, model_name, self.llm_cache_cutoff()?, self.namespace],
                 |row| row.get(0),
             )
             .optional()?;
 
         if let Some(llm_result_json) = cache_result {
             let entities: Vec<DetectedEntity> = serde_json::from_str(&llm_result_json)?;
+            self.record_llm_cache_hits(model_name, &[text_hash.as_str()])?;
+            self.share_llm_result(model_name, &text_hash, &entities);
+            self.remember_llm_result(model_name, &text_hash, &entities, self.llm_result_valid_until(now_secs()));
             debug!("Retrieved LLM cache hit for text hash '{}': {} entities", 
                    text_hash, entities.len());
             Ok(Some(entities))
@@ -278,31 +1392,122 @@ impl MappingStore {
         }
     }
 
+    /// Cached results for many texts at once, keyed by text. Texts without an
+    /// entry are absent from the map. Looks them up in as few queries as
+    /// SQLite's parameter limit allows.
+    pub fn get_llm_cache_batch(&self, texts: &[&str], model_name: &str) -> Result<HashMap<String, Vec<DetectedEntity>>> {
+        let mut by_hash: HashMap<String, &str> = HashMap::with_capacity(texts.len());
+        for text in texts {
+            by_hash.insert(self.hash_value(text), text);
+        }
+
+        let mut results = HashMap::new();
+        let mut hits = Vec::new();
+        let mut uncached_hashes = Vec::with_capacity(by_hash.len());
+        for (hash, text) in &by_hash {
+            match self.recent_llm_result(model_name, hash) {
+                Some(entities) => {
+                    results.insert(text.to_string(), entities);
+                    hits.push(hash.clone());
+                }
+                None => uncached_hashes.push(hash),
+            }
+        }
+
+        let valid_until = self.llm_result_valid_until(now_secs());
+        let mut hashes = Vec::with_capacity(uncached_hashes.len());
+        for chunk in uncached_hashes.chunks(LLM_CACHE_BATCH_SIZE) {
+            let chunk_hashes: Vec<&str> = chunk.iter().map(|hash| hash.as_str()).collect();
+            for (hash, cached) in chunk.iter().zip(self.cached_llm_results(model_name, &chunk_hashes)) {
+                match cached {
+                    Some(entities) => {
+                        self.remember_llm_result(model_name, hash, &entities, valid_until);
+                        results.insert(by_hash[*hash].to_string(), entities);
+                    }
+                    None => hashes.push(*hash),
+                }
+            }
+        }
+
+        let cutoff = self.llm_cache_cutoff()?;
+        for chunk in hashes.chunks(LLM_CACHE_BATCH_SIZE) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let mut stmt = self.conn.prepare(&format!(
+                "SELECT text_hash, llm_result FROM llm_cache
+                 WHERE model_name = ? AND namespace = ? AND (expires_at IS NULL OR expires_at > ?) AND text_hash IN ({})",
+                placeholders
+            ))?;
+            let params = [&model_name as &dyn rusqlite::ToSql, &self.namespace, &cutoff]
+                .into_iter()
+                .chain(chunk.iter().map(|hash| *hash as &dyn rusqlite::ToSql));
+            let rows = stmt.query_map(rusqlite::params_from_iter(params), |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?))
+            })?;
+            for row in rows {
+                let (text_hash, llm_result_json) = row?;
+                if let Some(text) = by_hash.get(&text_hash) {
+                    let entities: Vec<DetectedEntity> = serde_json::from_str(&llm_result_json)?;
+                    self.share_llm_result(model_name, &text_hash, &entities);
+                    self.remember_llm_result(model_name, &text_hash, &entities, valid_until);
+                    results.insert(text.to_string(), entities);
+                    hits.push(text_hash);
+                }
+            }
+        }
+        self.record_llm_cache_hits(model_name, &hits.iter().map(String::as_str).collect::<Vec<_>>())?;
+        self.counters.record_llm_cache_lookups(results.len(), by_hash.len() - results.len());
+
+        debug!("LLM cache batch lookup: {} of {} texts cached", results.len(), by_hash.len());
+        Ok(results)
+    }
+
+    /// Redis entries for each hash, in order; all misses without a cache
+    /// that holds LLM results.
+    fn cached_llm_results(&self, model_name: &str, text_hashes: &[&str]) -> Vec<Option<Vec<DetectedEntity>>> {
+        self.redis.as_ref()
+            .filter(|redis| redis.caches_llm_results())
+            .and_then(|redis| redis_result(redis.get_llm_results(model_name, text_hashes)))
+            .unwrap_or_else(|| vec![None; text_hashes.len()])
+    }
+
+    fn share_llm_result(&self, model_name: &str, text_hash: &str, entities: &[DetectedEntity]) {
+        if let Some(redis) = self.redis.as_ref().filter(|redis| redis.caches_llm_results()) {
+            redis_result(redis.set_llm_result(model_name, text_hash, entities));
+        }
+    }
+
     pub fn clear_llm_cache(&mut self) -> Result<usize> {
-        let deleted = self.conn.execute("DELETE FROM llm_cache", [])?;
+        let deleted = self.conn.execute("DELETE FROM llm_cache WHERE namespace = ?1", params![self.namespace])?;
+        if let Some(recent) = self.recent_llm_results.as_ref() {
+            recent.borrow_mut().clear();
+        }
         warn!("Cleared all {} LLM cache entries from database", deleted);
         Ok(deleted)
     }
 
     pub fn get_statistics(&self) -> Result<MappingStatistics> {
         let total_mappings: i64 = self.conn.query_row(
-            "SELECT COUNT(*) FROM entity_mappings",
-            [],
+            "SELECT COUNT(*) FROM entity_mappings WHERE namespace = ?1",
+            params![self.namespace],
             |row| row.get(0),
         )?;
 
-        let total_cache_entries: i64 = self.conn.query_row(
-            "SELECT COUNT(*) FROM llm_cache",
-            [],
-            |row| row.get(0),
-        )?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let (total_cache_entries, reused_cache_entries, cache_hits, mean_cache_ttl): (i64, i64, i64, Option<f64>) =
+            self.conn.query_row(
+                "SELECT COUNT(*), COUNT(CASE WHEN hit_count > 0 THEN 1 END), COALESCE(SUM(hit_count), 0),
+                        AVG(CASE WHEN expires_at > ?1 THEN expires_at - ?1 END)
+                 FROM llm_cache WHERE namespace = ?2",
+                params![now, self.namespace],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )?;
 
         let mut type_counts = HashMap::new();
         let mut stmt = self.conn.prepare(
-            "SELECT entity_type, COUNT(*) FROM entity_mappings GROUP BY entity_type"
+            "SELECT entity_type, COUNT(*) FROM entity_mappings WHERE namespace = ?1 GROUP BY entity_type"
         )?;
 
-        let rows = stmt.query_map([], |row| {
+        let rows = stmt.query_map(params![self.namespace], |row| {
             Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
         })?;
 
@@ -313,8 +1518,8 @@ impl MappingStore {
 
         let oldest_mapping: Option<u64> = self.conn
             .query_row(
-                "SELECT MIN(created_at) FROM entity_mappings WHERE created_at IS NOT NULL",
-                [],
+                "SELECT MIN(created_at) FROM entity_mappings WHERE namespace = ?1 AND created_at IS NOT NULL",
+                params![self.namespace],
                 |row| row.get::<_, Option<u64>>(0),
             )
             .optional()?
@@ -323,39 +1528,291 @@ impl MappingStore {
         Ok(MappingStatistics {
             total_mappings: total_mappings as usize,
             total_cache_entries: total_cache_entries as usize,
+            reused_cache_entries: reused_cache_entries as usize,
+            cache_hits: cache_hits as u64,
+            mean_cache_ttl_seconds: mean_cache_ttl.map(|secs| secs as u64),
             mappings_by_type: type_counts,
             oldest_mapping_age: oldest_mapping,
+            top_cooccurrences: self.top_cooccurrences(STATISTICS_COOCCURRENCE_LIMIT)?,
+            runtime: self.counters.snapshot(),
         })
     }
 
+    /// Counts one message in which all of `entity_types` were replaced. Single
+    /// types are not combinations and are skipped.
+    pub fn record_cooccurrence(&mut self, entity_types: &BTreeSet<String>) -> Result<()> {
+        if entity_types.len() < 2 {
+            return Ok(());
+        }
+        let combination = entity_types.iter().map(String::as_str).collect::<Vec<_>>().join("+");
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        self.conn.execute(
+            "INSERT INTO entity_cooccurrence (combination, message_count, last_seen_at) VALUES (?1, 1, ?2)
+             ON CONFLICT(combination) DO UPDATE SET message_count = message_count + 1, last_seen_at = excluded.last_seen_at",
+            params![combination, now],
+        )?;
+        Ok(())
+    }
+
+    /// Most frequent entity type combinations, with their message counts.
+    pub fn top_cooccurrences(&self, limit: usize) -> Result<Vec<(String, u64)>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT combination, message_count FROM entity_cooccurrence
+             ORDER BY message_count DESC, combination LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit as i64], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        Ok(rows.collect::<rusqlite::Result<_>>()?)
+    }
+
+    /// Cluster `(id, fake name)` a value was linked to, if any.
+    pub fn identity_cluster(&self, entity_type: &str, original_value: &str) -> Result<Option<(String, String)>> {
+        let original_hash = self.hash_value(original_value);
+        Ok(self.conn
+            .query_row(
+                "SELECT c.cluster_id, c.fake_name FROM identity_members m
+                 JOIN identity_clusters c ON c.namespace = m.namespace AND c.cluster_id = m.cluster_id
+                 WHERE m.namespace = ?3 AND m.entity_type = ?1 AND m.original_value_hash = ?2",
+                params![entity_type, original_hash, self.namespace],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?)
+    }
+
+    /// Adds `(entity type, value)` members to a cluster, creating it with
+    /// `fake_name` if it is new. Members of another cluster stay there.
+    pub fn link_identity(&mut self, cluster_id: &str, fake_name: &str, members: &[(String, String)]) -> Result<()> {
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let tx = self.conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        tx.execute(
+            "INSERT OR IGNORE INTO identity_clusters (namespace, cluster_id, fake_name, created_at) VALUES (?1, ?2, ?3, ?4)",
+            params![self.namespace, cluster_id, fake_name, now],
+        )?;
+        for (entity_type, value) in members {
+            tx.execute(
+                "INSERT OR IGNORE INTO identity_members (namespace, entity_type, original_value_hash, cluster_id)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![self.namespace, entity_type, self.hasher.hash(value), cluster_id],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Forgets the mapping of one value, so it counts as never seen again.
+    pub fn delete_mapping(&mut self, entity_type: &str, original_value: &str) -> Result<bool> {
+        let original_hash = self.hash_value(original_value);
+        if let Some(redis) = self.redis.as_ref() {
+            redis_result(redis.delete_mapping(entity_type, &original_hash));
+        }
+        if let Some(recent) = self.recent_mappings.as_ref() {
+            recent_mapping_key(&mut self.recent_key_buffer, &self.namespace, entity_type, &original_hash);
+            lock_recent(recent).pop(self.recent_key_buffer.as_str());
+        }
+        self.conn.execute(
+            "DELETE FROM identity_members WHERE namespace = ?3 AND entity_type = ?1 AND original_value_hash = ?2",
+            params![entity_type, original_hash, self.namespace],
+        )?;
+        let deleted = self.conn.execute(
+            "DELETE FROM entity_mappings WHERE namespace = ?3 AND entity_type = ?1 AND original_value_hash = ?2",
+            params![entity_type, original_hash, self.namespace],
+        )?;
+        Ok(deleted > 0)
+    }
+
+    /// Namespaces of the client sessions started below this store's
+    /// namespace that still hold mappings or cached results.
+    pub fn session_namespaces(&self) -> Result<Vec<String>> {
+        let prefix = crate::session::session_prefix(self.config.namespace.as_deref());
+        let mut stmt = self.conn.prepare(
+            "SELECT namespace FROM entity_mappings WHERE substr(namespace, 1, length(?1)) = ?1
+             UNION SELECT namespace FROM llm_cache WHERE substr(namespace, 1, length(?1)) = ?1
+             ORDER BY namespace",
+        )?;
+        let rows = stmt.query_map(params![prefix], |row| row.get(0))?;
+        rows.collect::<rusqlite::Result<Vec<String>>>().map_err(Into::into)
+    }
+
     pub fn clear_all_mappings(&mut self) -> Result<usize> {
-        let deleted = self.conn.execute("DELETE FROM entity_mappings", [])?;
+        let deleted = self.conn.execute("DELETE FROM entity_mappings WHERE namespace = ?1", params![self.namespace])?;
+        self.conn.execute("DELETE FROM placeholder_counters WHERE namespace = ?1", params![self.namespace])?;
+        self.conn.execute("DELETE FROM identity_members WHERE namespace = ?1", params![self.namespace])?;
+        self.conn.execute("DELETE FROM identity_clusters WHERE namespace = ?1", params![self.namespace])?;
+        self.forget_recent_mappings();
+        if self.known_values.is_some() {
+            self.rebuild_known_values()?;
+        }
         warn!("Cleared all {} mappings from database", deleted);
         Ok(deleted)
     }
 
     fn hash_value(&self, value: &str) -> String {
-        use std::collections::hash_map::DefaultHasher;
-        use std::hash::{Hash, Hasher};
-        
-        let mut hasher = DefaultHasher::new();
-        value.hash(&mut hasher);
-        format!("{:x}", hasher.finish())
+        self.hasher.hash(value)
     }
 }
 
+/// Writes the `recent_mappings` key of a mapping into `key`. Namespaces and
+/// entity types never contain NUL, so the key is unambiguous.
+fn recent_mapping_key(key: &mut String, namespace: &str, entity_type: &str, original_hash: &str) {
+    key.clear();
+    key.push_str(namespace);
+    key.push('\0');
+    key.push_str(entity_type);
+    key.push('\0');
+    key.push_str(original_hash);
+}
+
+/// A panic while the cache was held leaves nothing half-written worth keeping.
+fn lock_recent(recent: &SharedRecentMappings) -> std::sync::MutexGuard<'_, LruCache<String, RecentMapping>> {
+    recent.lock().unwrap_or_else(|poisoned| {
+        let mut recent = poisoned.into_inner();
+        recent.clear();
+        recent
+    })
+}
+
+/// Both proxy directions, CLI commands and other proxies on the same database
+/// each hold a connection. WAL lets readers run alongside the one writer, and
+/// the busy timeout makes a writer wait for the lock instead of failing with
+/// SQLITE_BUSY.
+fn configure_connection(conn: &Connection, config: &MappingConfig) -> Result<()> {
+    conn.busy_timeout(Duration::from_millis(config.busy_timeout_ms))?;
+    conn.pragma_update(None, "foreign_keys", true)?;
+    // In-memory databases keep their "memory" journal
+    let journal_mode: String = conn.pragma_update_and_check(None, "journal_mode", "WAL", |row| row.get(0))?;
+    if config.database_path != Path::new(":memory:") && !journal_mode.eq_ignore_ascii_case("wal") {
+        warn!("Mapping database does not support WAL (journal mode {}); concurrent access may block", journal_mode);
+    }
+    // Durable enough with WAL: a crash loses at most the last transactions, never consistency
+    conn.pragma_update(None, "synchronous", "NORMAL")?;
+    Ok(())
+}
+
+/// Redis failures fall back to SQLite, so they are logged rather than returned.
+fn redis_result<T>(result: Result<T>) -> Option<T> {
+    result.map_err(|e| warn!("Redis cache request failed, using SQLite: {}", e)).ok()
+}
+
+/// Counters of the running process, shared by the mapping stores of both
+/// proxy directions. They start at zero with every process.
+#[derive(Debug, Clone, Default)]
+pub struct RuntimeCounters(Arc<CounterValues>);
+
+#[derive(Debug, Default)]
+struct CounterValues {
+    mapping_hits: AtomicU64,
+    mapping_misses: AtomicU64,
+    llm_cache_hits: AtomicU64,
+    llm_cache_misses: AtomicU64,
+    request_entities: AtomicU64,
+    request_bytes: AtomicU64,
+    response_entities: AtomicU64,
+    response_bytes: AtomicU64,
+}
+
+impl RuntimeCounters {
+    /// A detected value that already had a fake, or one that needed a new one.
+    pub fn record_mapping_lookup(&self, hit: bool) {
+        let counter = if hit { &self.0.mapping_hits } else { &self.0.mapping_misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn record_llm_cache_lookups(&self, hits: usize, misses: usize) {
+        self.0.llm_cache_hits.fetch_add(hits as u64, Ordering::Relaxed);
+        self.0.llm_cache_misses.fetch_add(misses as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_bytes(&self, direction: Direction, bytes: usize) {
+        let counter = match direction {
+            Direction::Request => &self.0.request_bytes,
+            Direction::Response => &self.0.response_bytes,
+        };
+        counter.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    pub fn record_entities(&self, direction: Direction, entities: usize) {
+        let counter = match direction {
+            Direction::Request => &self.0.request_entities,
+            Direction::Response => &self.0.response_entities,
+        };
+        counter.fetch_add(entities as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> RuntimeStatistics {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        RuntimeStatistics {
+            mapping_hits: load(&self.0.mapping_hits),
+            mapping_misses: load(&self.0.mapping_misses),
+            llm_cache_hits: load(&self.0.llm_cache_hits),
+            llm_cache_misses: load(&self.0.llm_cache_misses),
+            request: DirectionStatistics {
+                entities_anonymized: load(&self.0.request_entities),
+                bytes_processed: load(&self.0.request_bytes),
+            },
+            response: DirectionStatistics {
+                entities_anonymized: load(&self.0.response_entities),
+                bytes_processed: load(&self.0.response_bytes),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct RuntimeStatistics {
+    /// Detected values replaced with their existing fake
+    pub mapping_hits: u64,
+    /// Detected values that got a new fake
+    pub mapping_misses: u64,
+    /// Texts whose LLM detection was served from the cache
+    pub llm_cache_hits: u64,
+    pub llm_cache_misses: u64,
+    pub request: DirectionStatistics,
+    pub response: DirectionStatistics,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct DirectionStatistics {
+    pub entities_anonymized: u64,
+    /// Message bytes read from this direction, whether or not they changed
+    pub bytes_processed: u64,
+}
+
+impl RuntimeStatistics {
+    pub fn mapping_hit_rate(&self) -> Option<f64> {
+        hit_rate(self.mapping_hits, self.mapping_misses)
+    }
+
+    pub fn llm_cache_hit_rate(&self) -> Option<f64> {
+        hit_rate(self.llm_cache_hits, self.llm_cache_misses)
+    }
+}
+
+fn hit_rate(hits: u64, misses: u64) -> Option<f64> {
+    let total = hits + misses;
+    (total > 0).then(|| hits as f64 / total as f64)
+}
+
 #[derive(Debug)]
 pub struct MappingStatistics {
     pub total_mappings: usize,
     pub total_cache_entries: usize,
+    /// LLM cache entries hit at least once, whose TTL has been extended
+    pub reused_cache_entries: usize,
+    pub cache_hits: u64,
+    /// Mean remaining lifetime of LLM cache entries with an adaptive TTL
+    pub mean_cache_ttl_seconds: Option<u64>,
     pub mappings_by_type: HashMap<String, usize>,
     pub oldest_mapping_age: Option<u64>,
+    /// Most frequent entity type combinations within one message
+    pub top_cooccurrences: Vec<(String, u64)>,
+    /// Counters of this process; zero in a store not shared with a proxy
+    pub runtime: RuntimeStatistics,
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::config::AnonymizedEntity;
+    use crate::hashing::{key_file_path, HashAlgorithm};
     use std::path::PathBuf;
     use tempfile::TempDir;
 
@@ -367,6 +1824,7 @@ mod tests {
             database_path: db_path,
             encryption: false,
             retention_days: Some(30),
+            ..MappingConfig::default()
         };
         
         (config, temp_dir)
@@ -394,6 +1852,7 @@ mod tests {
             database_path: PathBuf::from(":memory:"),
             encryption: false,
             retention_days: None,
+            ..MappingConfig::default()
         };
         
         let store = MappingStore::new(config).unwrap();
@@ -520,6 +1979,268 @@ mod tests {
         assert!(stats.oldest_mapping_age.is_some());
     }
 
+    #[test]
+    fn test_cooccurrence_counts_combinations() {
+        let (config, _temp_dir) = create_test_config();
+        let mut store = MappingStore::new(config).unwrap();
+        let types = |names: &[&str]| names.iter().map(|n| n.to_string()).collect::<BTreeSet<_>>();
+
+        store.record_cooccurrence(&types(&["name", "dob", "address"])).unwrap();
+        store.record_cooccurrence(&types(&["address", "name", "dob"])).unwrap();
+        store.record_cooccurrence(&types(&["email", "name"])).unwrap();
+        store.record_cooccurrence(&types(&["email"])).unwrap();
+
+        assert_eq!(store.top_cooccurrences(10).unwrap(), vec![
+            ("address+dob+name".to_string(), 2),
+            ("email+name".to_string(), 1),
+        ]);
+        assert_eq!(store.get_statistics().unwrap().top_cooccurrences.len(), 2);
+    }
+
+    #[test]
+    fn test_shared_mappings_are_kept_without_a_bloom_filter() {
+        let (config, _temp_dir) = create_test_config();
+        let mut store = MappingStore::new(MappingConfig { bloom_filter: false, ..config }).unwrap();
+        let hash = store.hash_value("john@example.com");
+        store.keep_shared_mapping("email", "john@example.com", &hash, "fake@company.com").unwrap();
+        store.keep_shared_mapping("email", "john@example.com", &hash, "fake@company.com").unwrap();
+        assert_eq!(store.find_mapping("email", &hash).unwrap().as_deref(), Some("fake@company.com"));
+        assert_eq!(store.get_statistics().unwrap().total_mappings, 1);
+    }
+
+    #[test]
+    fn test_unreachable_redis_falls_back_to_sqlite() {
+        let (config, _temp_dir) = create_test_config();
+        let config = MappingConfig {
+            redis: Some(crate::redis_cache::RedisCacheConfig {
+                url: "redis://127.0.0.1:1/".to_string(),
+                ttl_seconds: 60,
+                key_prefix: "test".to_string(),
+                timeout_ms: 50,
+            }),
+            ..config
+        };
+        let mut store = MappingStore::new(config).unwrap();
+        assert!(store.redis.is_none());
+
+        store.store_mapping(&AnonymizedEntity {
+            entity_type: "email".to_string(),
+            original_value: "jane@acme.com".to_string(),
+            fake_value: "kim@example.net".to_string(),
+            mapping_id: Uuid::new_v4().to_string(),
+        }).unwrap();
+        assert_eq!(store.get_mapping("email", "jane@acme.com").unwrap().as_deref(), Some("kim@example.net"));
+    }
+
+    #[test]
+    fn test_memory_cache_writes_through_and_is_invalidated() {
+        let (config, _temp_dir) = create_test_config();
+        let mut store = MappingStore::new(config).unwrap();
+        let entity = create_test_entity();
+        store.store_mapping(&entity).unwrap();
+        // The first fake stored for a value wins, in memory as in SQLite
+        store.store_mapping(&AnonymizedEntity { fake_value: "other@company.com".to_string(), ..entity.clone() }).unwrap();
+        store.store_llm_cache("cached text", &[], "test-model").unwrap();
+
+        // Served from memory once SQLite no longer has the rows
+        store.conn.execute("DELETE FROM entity_mappings", []).unwrap();
+        store.conn.execute("DELETE FROM llm_cache", []).unwrap();
+        assert_eq!(store.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("fake@company.com"));
+        assert!(store.get_llm_cache("cached text", "test-model").unwrap().is_some());
+        assert_eq!(store.get_llm_cache_batch(&["cached text"], "test-model").unwrap().len(), 1);
+
+        store.delete_mapping("email", "john@example.com").unwrap();
+        assert_eq!(store.get_mapping("email", "john@example.com").unwrap(), None);
+        store.clear_llm_cache().unwrap();
+        assert!(store.get_llm_cache("cached text", "test-model").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_fakes_are_recognized_under_any_key_path() {
+        let (config, _temp_dir) = create_test_config();
+        let mut store = MappingStore::new(config).unwrap();
+        store.store_mapping(&AnonymizedEntity { entity_type: "email@to".to_string(), ..create_test_entity() }).unwrap();
+
+        assert!(store.is_fake("email@cc", "fake@company.com").unwrap());
+        assert!(store.is_fake("email", "fake@company.com").unwrap());
+        assert!(!store.is_fake("email_domain", "fake@company.com").unwrap());
+        assert!(!store.is_fake("email", "john@example.com").unwrap());
+    }
+
+    #[test]
+    fn test_deletes_reach_stores_sharing_the_memory_cache() {
+        let (config, _temp_dir) = create_test_config();
+        let mut requests = MappingStore::new(config.clone()).unwrap();
+        let mut responses = MappingStore::new(config.clone()).unwrap().sharing_recent_mappings(&requests);
+        let mut other_namespace = MappingStore::new(MappingConfig { namespace: Some("other".to_string()), ..config })
+            .unwrap()
+            .sharing_recent_mappings(&requests);
+        let entity = create_test_entity();
+        requests.store_mapping(&entity).unwrap();
+        assert_eq!(responses.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("fake@company.com"));
+        // Entries of one namespace are not served to another
+        assert_eq!(other_namespace.get_mapping("email", "john@example.com").unwrap(), None);
+
+        // A mapping forgotten on one side is not served from memory on the other
+        requests.delete_mapping("email", "john@example.com").unwrap();
+        assert_eq!(responses.get_mapping("email", "john@example.com").unwrap(), None);
+
+        responses.store_mapping(&entity).unwrap();
+        requests.conn.execute("DELETE FROM entity_mappings", []).unwrap();
+        requests.forget_recent_mappings();
+        assert_eq!(responses.get_mapping("email", "john@example.com").unwrap(), None);
+    }
+
+    #[test]
+    fn test_concurrent_writers_and_readers_share_one_database() {
+        let (config, _temp_dir) = create_test_config();
+        let store = MappingStore::new(config.clone()).unwrap();
+        let journal_mode: String = store.conn.query_row("PRAGMA journal_mode", [], |row| row.get(0)).unwrap();
+        assert_eq!(journal_mode, "wal");
+
+        const THREADS: usize = 8;
+        const VALUES: usize = 100;
+        let barrier = std::sync::Barrier::new(THREADS + 2);
+        let seen: Vec<Vec<String>> = std::thread::scope(|scope| {
+            let writers: Vec<_> = (0..THREADS)
+                .map(|thread| {
+                    let (config, barrier) = (config.clone(), &barrier);
+                    scope.spawn(move || {
+                        let mut store = MappingStore::new(config).unwrap();
+                        barrier.wait();
+                        (0..VALUES)
+                            .map(|n| {
+                                let entity = AnonymizedEntity {
+                                    entity_type: "email".to_string(),
+                                    original_value: format!("user{}@example.com", n),
+                                    fake_value: format!("fake{}-{}@example.net", thread, n),
+                                    mapping_id: Uuid::new_v4().to_string(),
+                                };
+                                if n % 10 == 0 {
+                                    store.store_mappings_batch(std::slice::from_ref(&entity)).unwrap();
+                                } else {
+                                    store.store_mapping(&entity).unwrap();
+                                }
+                                store.store_llm_cache(&format!("text {} {}", thread, n), &[], "test-model").unwrap();
+                                store.get_mapping("email", &entity.original_value).unwrap().unwrap()
+                            })
+                            .collect()
+                    })
+                })
+                .collect();
+            for _ in 0..2 {
+                let (config, barrier) = (config.clone(), &barrier);
+                scope.spawn(move || {
+                    let store = MappingStore::new(config).unwrap();
+                    barrier.wait();
+                    for n in 0..VALUES {
+                        store.get_statistics().unwrap();
+                        store.get_llm_cache(&format!("text 0 {}", n), "test-model").unwrap();
+                    }
+                });
+            }
+            writers.into_iter().map(|writer| writer.join().unwrap()).collect()
+        });
+
+        // Every writer ended up with the one fake that won each value
+        for n in 0..VALUES {
+            assert!(seen.iter().all(|fakes| fakes[n] == seen[0][n]), "value {} diverged", n);
+        }
+        let stats = store.get_statistics().unwrap();
+        assert_eq!((stats.total_mappings, stats.total_cache_entries), (VALUES, THREADS * VALUES));
+    }
+
+    #[test]
+    fn test_placeholder_numbers_persist_per_label() {
+        let (config, _temp_dir) = create_test_config();
+        {
+            let mut store = MappingStore::new(config.clone()).unwrap();
+            assert_eq!(store.next_placeholder_number("EMAIL").unwrap(), 1);
+            assert_eq!(store.next_placeholder_number("EMAIL").unwrap(), 2);
+            assert_eq!(store.next_placeholder_number("PERSON").unwrap(), 1);
+        }
+
+        let mut reopened = MappingStore::new(config).unwrap();
+        assert_eq!(reopened.next_placeholder_number("EMAIL").unwrap(), 3);
+        reopened.clear_all_mappings().unwrap();
+        assert_eq!(reopened.next_placeholder_number("EMAIL").unwrap(), 1);
+    }
+
+    #[test]
+    fn test_namespaces_share_a_database_without_mixing() {
+        let (config, _temp_dir) = create_test_config();
+        let namespaced = |name: &str| MappingConfig { namespace: Some(name.to_string()), ..config.clone() };
+        let mut github = MappingStore::new(namespaced("github")).unwrap();
+        let mut crm = MappingStore::new(namespaced("crm")).unwrap();
+
+        github.store_mapping(&create_test_entity()).unwrap();
+        assert_eq!(crm.get_mapping("email", "john@example.com").unwrap(), None);
+        crm.store_mapping(&AnonymizedEntity { fake_value: "other@company.com".to_string(), ..create_test_entity() }).unwrap();
+        assert_eq!(crm.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("other@company.com"));
+        assert_eq!(github.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("fake@company.com"));
+
+        github.store_llm_cache("shared text", &[], "test-model").unwrap();
+        assert!(crm.get_llm_cache("shared text", "test-model").unwrap().is_none());
+        assert_eq!(github.next_placeholder_number("EMAIL").unwrap(), 1);
+        assert_eq!(crm.next_placeholder_number("EMAIL").unwrap(), 1);
+
+        crm.clear_all_mappings().unwrap();
+        assert_eq!(github.get_statistics().unwrap().total_mappings, 1);
+        assert_eq!(github.next_placeholder_number("EMAIL").unwrap(), 2);
+    }
+
+    #[test]
+    fn test_databases_from_before_namespaces_are_upgraded() {
+        let (config, _temp_dir) = create_test_config();
+        let conn = Connection::open(&config.database_path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE entity_mappings (
+                id TEXT PRIMARY KEY, entity_type TEXT NOT NULL, original_value_hash TEXT NOT NULL,
+                fake_value TEXT NOT NULL, created_at INTEGER NOT NULL, last_used_at INTEGER,
+                UNIQUE(entity_type, original_value_hash));
+             CREATE TABLE llm_cache (
+                id TEXT PRIMARY KEY, text_hash TEXT NOT NULL, original_text TEXT NOT NULL,
+                llm_result TEXT NOT NULL, model_name TEXT NOT NULL, created_at INTEGER NOT NULL,
+                UNIQUE(text_hash, model_name));
+             CREATE TABLE placeholder_counters (label TEXT PRIMARY KEY, last_number INTEGER NOT NULL);
+             INSERT INTO placeholder_counters VALUES ('EMAIL', 4);",
+        ).unwrap();
+        let hash = ValueHasher::from_config(&config).unwrap().hash("john@example.com");
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+        conn.execute(
+            "INSERT INTO entity_mappings VALUES ('m1', 'email', ?1, 'fake@company.com', ?2, ?2)",
+            params![hash, now],
+        ).unwrap();
+        drop(conn);
+
+        let mut store = MappingStore::new(config.clone()).unwrap();
+        assert_eq!(store.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("fake@company.com"));
+        assert_eq!(store.next_placeholder_number("EMAIL").unwrap(), 5);
+        let mut other = MappingStore::new(MappingConfig { namespace: Some("crm".to_string()), ..config }).unwrap();
+        assert_eq!(other.get_mapping("email", "john@example.com").unwrap(), None);
+        // Last use was already tracked, so migration 2 is adopted and the rest applied
+        assert_eq!(applied_migrations(&store.conn).unwrap().len(), MIGRATIONS.len());
+    }
+
+    #[test]
+    fn test_migrations_are_recorded_and_applied_once() {
+        let (config, _temp_dir) = create_test_config();
+        let mut store = MappingStore::new(config.clone()).unwrap();
+        store.store_mapping(&create_test_entity()).unwrap();
+        let versions: Vec<u32> = applied_migrations(&store.conn).unwrap().into_iter().collect();
+        assert_eq!(versions, MIGRATIONS.iter().map(|migration| migration.version).collect::<Vec<_>>());
+        drop(store);
+
+        let mut reopened = MappingStore::new(config.clone()).unwrap();
+        assert_eq!(reopened.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("fake@company.com"));
+
+        // A database upgraded by a newer release is not downgraded or misread
+        reopened.conn.execute("INSERT INTO schema_migrations VALUES (999, 'from the future', 0)", []).unwrap();
+        drop(reopened);
+        let error = MappingStore::new(config).err().unwrap().to_string();
+        assert!(error.contains("schema version 999"), "{}", error);
+    }
+
     #[test]
     fn test_clear_all_mappings() {
         let (config, _temp_dir) = create_test_config();
@@ -554,6 +2275,161 @@ mod tests {
         assert_ne!(hash1, hash3);
     }
 
+    #[test]
+    fn test_mappings_are_rekeyed_after_an_algorithm_change() {
+        let (config, _temp_dir) = create_test_config();
+        let config = MappingConfig { hash_algorithm: Some(HashAlgorithm::Sha256), ..config };
+        let mut store = MappingStore::new(config.clone()).unwrap();
+        store.store_mapping(&create_test_entity()).unwrap();
+        assert_eq!(store.hash_algorithm(), "sha256");
+        drop(store);
+
+        let key_algorithms = |path: &Path| -> Vec<String> {
+            let conn = Connection::open(path).unwrap();
+            let mut stmt = conn.prepare("SELECT hash_algorithm FROM entity_mappings").unwrap();
+            stmt.query_map([], |row| row.get(0)).unwrap().map(Result::unwrap).collect()
+        };
+        let sha512 = MappingConfig { hash_algorithm: Some(HashAlgorithm::Sha512), ..config.clone() };
+        let mut store = MappingStore::new(sha512.clone()).unwrap();
+        assert_eq!(store.hash_algorithm(), "sha512");
+        assert_eq!(key_algorithms(&config.database_path), ["sha256"]);
+        assert_eq!(store.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("fake@company.com"));
+        assert_eq!(key_algorithms(&config.database_path), ["sha512"]);
+        drop(store);
+        let mut store = MappingStore::new(sha512).unwrap();
+        assert_eq!(store.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("fake@company.com"));
+        drop(store);
+
+        // A database from before the metadata was keyed with SipHash
+        let conn = Connection::open(&config.database_path).unwrap();
+        conn.execute("DELETE FROM store_metadata", []).unwrap();
+        conn.execute("UPDATE entity_mappings SET hash_algorithm = NULL, original_value_hash = ?1",
+            params![ValueHasher::legacy().hash("john@example.com")]).unwrap();
+        drop(conn);
+        let err = MappingStore::new(MappingConfig { fips_mode: true, ..config.clone() }).err().unwrap().to_string();
+        assert!(err.contains("keyed with siphash"), "{}", err);
+        let mut store = MappingStore::new(config.clone()).unwrap();
+        assert_eq!(store.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("fake@company.com"));
+        assert_eq!(key_algorithms(&config.database_path), ["sha256"]);
+    }
+
+    #[test]
+    fn test_reverse_lookup_needs_encrypted_originals() {
+        let (config, _temp_dir) = create_test_config();
+        let mut store = MappingStore::new(config).unwrap();
+        store.store_mapping(&create_test_entity()).unwrap();
+        assert!(store.get_original("email", "fake@company.com").is_err());
+
+        // What `encryption = true` derives from the passphrase
+        store.original_key = Some([3u8; 32]);
+        assert_eq!(store.get_original("email", "fake@company.com").unwrap(), None);
+        let mut entity = create_test_entity();
+        entity.original_value = "jane@example.com".to_string();
+        entity.fake_value = "kim@example.net".to_string();
+        store.store_mappings_batch(&[entity]).unwrap();
+
+        assert_eq!(store.get_original("email", "kim@example.net").unwrap().as_deref(), Some("jane@example.com"));
+        assert_eq!(store.get_original("phone", "kim@example.net").unwrap(), None);
+        assert_eq!(store.get_original("email@params.to", "kim@example.net").unwrap().as_deref(), Some("jane@example.com"));
+
+        // The same fake for another original cannot be resolved to either
+        let mut other = create_test_entity();
+        other.entity_type = "email@cc".to_string();
+        other.original_value = "joe@example.com".to_string();
+        other.fake_value = "kim@example.net".to_string();
+        store.store_mapping(&other).unwrap();
+        let err = store.get_original("email", "kim@example.net").unwrap_err().to_string();
+        assert!(err.contains("2 different originals"), "{}", err);
+        let stored: Vec<u8> = store.conn
+            .query_row("SELECT original_ciphertext FROM entity_mappings WHERE fake_value = 'kim@example.net'", [], |row| row.get(0))
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&stored).contains("jane"));
+    }
+
+    #[test]
+    fn test_encrypted_bundle_round_trip_carries_originals() {
+        let (config, temp_dir) = create_test_config();
+        let mut source = MappingStore::new(config.clone()).unwrap();
+        source.original_key = Some([3u8; 32]);
+        source.bundle_key = Some([5u8; 32]);
+        source.store_mapping(&create_test_entity()).unwrap();
+
+        let bundle = temp_dir.path().join("mappings.bundle.json");
+        assert_eq!(source.export(&bundle, BundleFormat::EncryptedJson).unwrap(), 1);
+        assert!(!std::fs::read_to_string(&bundle).unwrap().contains("john@example.com"));
+
+        let target_config = MappingConfig { database_path: temp_dir.path().join("target.db"), ..config.clone() };
+        // Hashes match where the key file was copied along
+        std::fs::copy(key_file_path(&config.database_path).unwrap(), key_file_path(&target_config.database_path).unwrap()).unwrap();
+        let mut target = MappingStore::new(target_config).unwrap();
+        target.original_key = Some([4u8; 32]);
+        target.bundle_key = Some([5u8; 32]);
+        let report = target.import(&bundle).unwrap();
+        assert_eq!(report, ImportReport { imported: 1, ..ImportReport::default() });
+        assert_eq!(target.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("fake@company.com"));
+        assert_eq!(target.get_original("email", "fake@company.com").unwrap().as_deref(), Some("john@example.com"));
+
+        // Importing again leaves the existing mappings alone
+        assert_eq!(target.import(&bundle).unwrap().already_mapped, 1);
+
+        target.bundle_key = Some([6u8; 32]);
+        assert!(target.import(&bundle).is_err());
+    }
+
+    #[test]
+    fn test_plain_bundle_has_no_originals_and_keeps_existing_fakes() {
+        let (config, temp_dir) = create_test_config();
+        let mut source = MappingStore::new(config.clone()).unwrap();
+        source.original_key = Some([3u8; 32]);
+        source.store_mapping(&create_test_entity()).unwrap();
+        let bundle = temp_dir.path().join("mappings.json");
+        source.export(&bundle, BundleFormat::Json).unwrap();
+        let contents = std::fs::read_to_string(&bundle).unwrap();
+        assert!(contents.contains("fake@company.com"));
+        assert!(!contents.contains("john@example.com"));
+
+        let target_config = MappingConfig { database_path: temp_dir.path().join("target.db"), ..config.clone() };
+        // Hashes match where the key file was copied along
+        std::fs::copy(key_file_path(&config.database_path).unwrap(), key_file_path(&target_config.database_path).unwrap()).unwrap();
+        let mut target = MappingStore::new(target_config).unwrap();
+        let mut entity = create_test_entity();
+        entity.fake_value = "local@company.com".to_string();
+        target.store_mapping(&entity).unwrap();
+        let mut other = create_test_entity();
+        other.original_value = "jane@example.com".to_string();
+        other.fake_value = "kim@example.net".to_string();
+        source.store_mapping(&other).unwrap();
+        source.export(&bundle, BundleFormat::Json).unwrap();
+
+        let report = target.import(&bundle).unwrap();
+        assert_eq!((report.imported, report.already_mapped), (1, 1));
+        assert_eq!(target.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("local@company.com"));
+        assert_eq!(target.get_mapping("email", "jane@example.com").unwrap().as_deref(), Some("kim@example.net"));
+    }
+
+    #[test]
+    fn test_known_value_filter_skips_unseen_and_reloads_on_open() {
+        let (config, _temp_dir) = create_test_config();
+        let mut store = MappingStore::new(config.clone()).unwrap();
+        let mut other = MappingStore::new(config.clone()).unwrap();
+        store.store_mapping(&create_test_entity()).unwrap();
+
+        // Stored elsewhere after this store loaded its filter: a definite miss here
+        assert_eq!(other.get_mapping("email", "john@example.com").unwrap(), None);
+        assert!(!other.may_be_mapped("email", &other.hash_value("john@example.com")));
+        // Inserting through the filter's own store reads back the mapping that won
+        let mut late = create_test_entity();
+        late.fake_value = "late@company.com".to_string();
+        other.store_mapping(&late).unwrap();
+        assert_eq!(other.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("fake@company.com"));
+
+        let mut reopened = MappingStore::new(config.clone()).unwrap();
+        assert_eq!(reopened.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("fake@company.com"));
+        let mut unfiltered = MappingStore::new(MappingConfig { bloom_filter: false, ..config }).unwrap();
+        assert!(unfiltered.known_values.is_none());
+        assert_eq!(unfiltered.get_mapping("email", "john@example.com").unwrap().as_deref(), Some("fake@company.com"));
+    }
+
     #[test]
     fn test_different_entity_types() {
         let (config, _temp_dir) = create_test_config();
@@ -595,6 +2471,7 @@ mod tests {
                 start: 8,
                 end: 21,
                 confidence: 0.95,
+                ..Default::default()
             },
             DetectedEntity {
                 entity_type: "email".to_string(),
@@ -602,6 +2479,7 @@ mod tests {
                 start: 25,
                 end: 42,
                 confidence: 0.98,
+                ..Default::default()
             },
         ];
         let model_name = "llama3.2:3b";
@@ -620,6 +2498,42 @@ mod tests {
         assert_eq!(cached_entities[1].original_value, "sarah@company.com");
     }
 
+    fn cached_texts(store: &MappingStore) -> Vec<String> {
+        let mut stmt = store.conn.prepare("SELECT original_text FROM llm_cache ORDER BY original_text").unwrap();
+        stmt.query_map([], |row| row.get(0)).unwrap().collect::<rusqlite::Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn test_llm_cache_evicts_least_recently_used_entries() {
+        let (mut config, _temp_dir) = create_test_config();
+        config.max_cache_entries = Some(2);
+        let mut store = MappingStore::new(config).unwrap();
+        store.store_llm_cache("first", &[], "test-model").unwrap();
+        store.store_llm_cache("second", &[], "test-model").unwrap();
+        store.conn.execute("UPDATE llm_cache SET last_accessed = last_accessed - 100", []).unwrap();
+
+        // A hit makes the oldest entry the most recently used one
+        store.get_llm_cache("first", "test-model").unwrap().unwrap();
+        store.store_llm_cache("third", &[], "test-model").unwrap();
+        assert_eq!(cached_texts(&store), ["first", "third"]);
+    }
+
+    #[test]
+    fn test_llm_cache_is_bounded_by_size() {
+        let (mut config, _temp_dir) = create_test_config();
+        config.max_cache_bytes = Some(100);
+        let mut store = MappingStore::new(config).unwrap();
+        let text = "x".repeat(40);
+        store.store_llm_cache(&format!("a{}", text), &[], "test-model").unwrap();
+        store.conn.execute("UPDATE llm_cache SET last_accessed = last_accessed - 100", []).unwrap();
+        store.store_llm_cache(&format!("b{}", text), &[], "test-model").unwrap();
+        assert_eq!(cached_texts(&store).len(), 2);
+
+        // Three entries of 43 bytes do not fit in 100
+        store.store_llm_cache(&format!("c{}", text), &[], "test-model").unwrap();
+        assert_eq!(cached_texts(&store), [format!("b{}", text), format!("c{}", text)]);
+    }
+
     #[test]
     fn test_llm_cache_miss() {
         let (config, _temp_dir) = create_test_config();
@@ -641,6 +2555,7 @@ mod tests {
             start: 0,
             end: 8,
             confidence: 0.9,
+            ..Default::default()
         }];
         let entities2 = vec![DetectedEntity {
             entity_type: "organization".to_string(),
@@ -648,6 +2563,7 @@ mod tests {
             start: 19,
             end: 28,
             confidence: 0.85,
+            ..Default::default()
         }];
 
         store.store_llm_cache(text, &entities1, "model1").unwrap();
@@ -677,6 +2593,7 @@ mod tests {
             start: 0,
             end: 3,
             confidence: 0.5,
+            ..Default::default()
         }];
         
         let entities2 = vec![DetectedEntity {
@@ -685,6 +2602,7 @@ mod tests {
             start: 0,
             end: 3,
             confidence: 0.9,
+            ..Default::default()
         }];
 
         store.store_llm_cache(text, &entities1, model_name).unwrap();
@@ -696,6 +2614,50 @@ mod tests {
         assert_eq!(result[0].original_value, "new_value");
     }
 
+    #[test]
+    fn test_llm_cache_batch_lookup() {
+        let (config, _temp_dir) = create_test_config();
+        let mut store = MappingStore::new(config).unwrap();
+        let texts: Vec<String> = (0..LLM_CACHE_BATCH_SIZE + 20).map(|n| format!("text number {}", n)).collect();
+        let entity = |text: &str| vec![DetectedEntity {
+            entity_type: "name".to_string(),
+            original_value: text.to_string(),
+            start: 0,
+            end: text.len(),
+            confidence: 0.9,
+            ..Default::default()
+        }];
+        // Every third text is cached, one of them under another model
+        for text in texts.iter().step_by(3) {
+            store.store_llm_cache(text, &entity(text), "test-model").unwrap();
+        }
+        store.store_llm_cache(&texts[1], &entity(&texts[1]), "other-model").unwrap();
+
+        let lookup: Vec<&str> = texts.iter().map(String::as_str).collect();
+        let cached = store.get_llm_cache_batch(&lookup, "test-model").unwrap();
+        assert_eq!(cached.len(), texts.len().div_ceil(3));
+        assert_eq!(cached[&texts[LLM_CACHE_BATCH_SIZE + 1]][0].original_value, texts[LLM_CACHE_BATCH_SIZE + 1]);
+        assert!(!cached.contains_key(&texts[1]));
+        assert!(store.get_llm_cache_batch(&[], "test-model").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_llm_cache_lookups_are_counted_across_shared_stores() {
+        let (config, _temp_dir) = create_test_config();
+        let mut store = MappingStore::new(config.clone()).unwrap();
+        let other = MappingStore::new(config).unwrap().with_counters(store.counters().clone());
+        store.store_llm_cache("cached text", &[], "test-model").unwrap();
+
+        assert!(store.get_llm_cache("cached text", "test-model").unwrap().is_some());
+        assert!(other.get_llm_cache("unseen text", "test-model").unwrap().is_none());
+        other.get_llm_cache_batch(&["cached text", "unseen text", "other text"], "test-model").unwrap();
+
+        let runtime = store.get_statistics().unwrap().runtime;
+        assert_eq!((runtime.llm_cache_hits, runtime.llm_cache_misses), (2, 3));
+        assert_eq!(runtime.llm_cache_hit_rate(), Some(0.4));
+        assert_eq!(runtime.mapping_hit_rate(), None);
+    }
+
     #[test]
     fn test_llm_cache_statistics() {
         let (config, _temp_dir) = create_test_config();
@@ -710,6 +2672,7 @@ mod tests {
             start: 0,
             end: 4,
             confidence: 0.8,
+            ..Default::default()
         }];
         store.store_llm_cache(text, &entities, "test-model").unwrap();
 
@@ -718,6 +2681,47 @@ mod tests {
         assert_eq!(stats.total_cache_entries, 1);
     }
 
+    #[test]
+    fn test_llm_cache_ttl_adapts_to_hits() {
+        let (mut config, _temp_dir) = create_test_config();
+        // Expiry is faked in SQLite below, out of sight of the memory cache
+        config.memory_cache.llm_results = 0;
+        let mut store = MappingStore::new(config).unwrap();
+        let expires_at = |store: &MappingStore, text: &str| -> i64 {
+            store.conn.query_row(
+                "SELECT expires_at - created_at FROM llm_cache WHERE text_hash = ?1",
+                params![store.hash_value(text)],
+                |row| row.get(0),
+            ).unwrap()
+        };
+
+        store.store_llm_cache("recurring", &[], "test-model").unwrap();
+        store.store_llm_cache("one-off", &[], "test-model").unwrap();
+        assert_eq!(expires_at(&store, "recurring"), 24 * 60 * 60);
+
+        for _ in 0..3 {
+            assert!(store.get_llm_cache("recurring", "test-model").unwrap().is_some());
+        }
+        assert!(store.get_llm_cache_batch(&["recurring"], "test-model").unwrap().contains_key("recurring"));
+        // 24h doubled four times, capped at 30 days
+        let ttl = expires_at(&store, "recurring");
+        assert!((16 * 24 * 60 * 60..=16 * 24 * 60 * 60 + 5).contains(&ttl), "{}", ttl);
+        for _ in 0..3 {
+            store.get_llm_cache("recurring", "test-model").unwrap();
+        }
+        assert!(expires_at(&store, "recurring") <= 30 * 24 * 60 * 60 + 5);
+
+        let stats = store.get_statistics().unwrap();
+        assert_eq!((stats.total_cache_entries, stats.reused_cache_entries, stats.cache_hits), (2, 1, 7));
+        assert!(stats.mean_cache_ttl_seconds.unwrap() > 24 * 60 * 60);
+
+        // An outlived one-off text is a miss and goes with the next cleanup
+        store.conn.execute("UPDATE llm_cache SET expires_at = 1 WHERE hit_count = 0", []).unwrap();
+        assert!(store.get_llm_cache("one-off", "test-model").unwrap().is_none());
+        assert_eq!(store.cleanup_expired_mappings().unwrap(), 1);
+        assert_eq!(store.get_statistics().unwrap().total_cache_entries, 1);
+    }
+
     #[test]
     fn test_clear_llm_cache() {
         let (config, _temp_dir) = create_test_config();
@@ -730,6 +2734,7 @@ mod tests {
             start: 0,
             end: 4,
             confidence: 0.9,
+            ..Default::default()
         }];
         
         store.store_llm_cache(text, &entities, "test-model").unwrap();
@@ -765,6 +2770,7 @@ mod tests {
             start: 0,
             end: 4,
             confidence: 0.9,
+            ..Default::default()
         }];
         store.store_llm_cache(text, &entities, "test-model").unwrap();
         
@@ -780,6 +2786,31 @@ mod tests {
         assert!(cache_result.is_none());
     }
 
+    #[test]
+    fn test_cleanup_reports_recently_used_mappings() {
+        let (mut config, _temp_dir) = create_test_config();
+        config.retention_days = Some(0);
+        let mut store = MappingStore::new(config).unwrap();
+
+        let used = create_test_entity();
+        store.store_mapping(&used).unwrap();
+        let stale = AnonymizedEntity {
+            entity_type: "phone".to_string(),
+            original_value: "555-0100".to_string(),
+            fake_value: "555-0199".to_string(),
+            mapping_id: Uuid::new_v4().to_string(),
+        };
+        store.store_mapping(&stale).unwrap();
+        store.conn.execute("UPDATE entity_mappings SET last_used_at = 0 WHERE entity_type = 'phone'", []).unwrap();
+
+        std::thread::sleep(std::time::Duration::from_secs(1));
+        assert_eq!(store.cleanup_expired_mappings().unwrap(), 2);
+
+        let expired = store.take_expired_in_use();
+        assert_eq!(expired, BTreeMap::from([("email".to_string(), 1)]));
+        assert!(store.take_expired_in_use().is_empty());
+    }
+
     #[test]
     fn test_llm_cache_with_empty_entities() {
         let (config, _temp_dir) = create_test_config();
@@ -812,6 +2843,7 @@ mod tests {
                 start: 8,
                 end: 18,
                 confidence: 0.95,
+                ..Default::default()
             },
             DetectedEntity {
                 entity_type: "email".to_string(),
@@ -819,6 +2851,7 @@ mod tests {
                 start: 22,
                 end: 38,
                 confidence: 0.98,
+                ..Default::default()
             },
             DetectedEntity {
                 entity_type: "person_name".to_string(),
@@ -826,6 +2859,7 @@ mod tests {
                 start: 40,
                 end: 48,
                 confidence: 0.93,
+                ..Default::default()
             },
             DetectedEntity {
                 entity_type: "email".to_string(),
@@ -833,6 +2867,7 @@ mod tests {
                 start: 52,
                 end: 68,
                 confidence: 0.97,
+                ..Default::default()
             },
             DetectedEntity {
                 entity_type: "phone".to_string(),
@@ -840,6 +2875,7 @@ mod tests {
                 start: 150,
                 end: 164,
                 confidence: 0.99,
+                ..Default::default()
             },
         ];
         let model_name = "llama3.2:3b";
@@ -870,6 +2906,7 @@ mod tests {
                 start: 10,
                 end: 25,
                 confidence: 0.92,
+                ..Default::default()
             },
             DetectedEntity {
                 entity_type: "email".to_string(),
@@ -877,6 +2914,7 @@ mod tests {
                 start: 28,
                 end: 55,
                 confidence: 0.96,
+                ..Default::default()
             },
             DetectedEntity {
                 entity_type: "person_name".to_string(),
@@ -884,6 +2922,7 @@ mod tests {
                 start: 59,
                 end: 70,
                 confidence: 0.94,
+                ..Default::default()
             },
         ];
         let model_name = "llama3.2:3b";
@@ -912,6 +2951,7 @@ mod tests {
                 start: i * 5,
                 end: (i + 1) * 5,
                 confidence: 0.8 + (i as f64 * 0.02),
+                ..Default::default()
             }];
             
             store.store_llm_cache(base_text, &entities, model).unwrap();
@@ -942,6 +2982,7 @@ mod tests {
             start: 28,
             end: 36,
             confidence: 0.95,
+            ..Default::default()
         }];
         let model_name = "persistent-model";
 
@@ -0,0 +1,176 @@
+//! Heuristic person-name detection
+//!
+//! Names are the most common PII that regex patterns miss. Deployments that
+//! cannot run an LLM can enable this detector, which reports capitalized word
+//! sequences that follow an honorific ("Dr. Alvarez") or start with a known
+//! first name ("Maria Keller"). It favours precision: capitalized words that
+//! are neither titled nor in the gazetteer are never reported.
+
+use crate::config::{DetectedEntity, NameDetectionConfig};
+use anyhow::Result;
+use regex::Regex;
+use std::collections::HashSet;
+
+const HONORIFIC_CONFIDENCE: f64 = 0.9;
+const FULL_NAME_CONFIDENCE: f64 = 0.85;
+/// A known first name without a surname; below the default threshold on purpose.
+const FIRST_NAME_ONLY_CONFIDENCE: f64 = 0.6;
+
+/// Common given names used when no gazetteer is configured.
+const BUILTIN_FIRST_NAMES: &[&str] = &[
+    "aaron", "adam", "ahmed", "alex", "alexander", "alice", "amanda", "amy", "ana", "andrea",
+    "andrew", "angela", "anna", "anthony", "barbara", "ben", "benjamin", "brian", "carlos", "carol",
+    "catherine", "charles", "chris", "christopher", "claire", "daniel", "david", "deborah", "diana", "donald",
+    "edward", "elena", "elizabeth", "emily", "emma", "eric", "fatima", "francesca", "frank", "gary",
+    "george", "hannah", "helen", "ivan", "jack", "james", "jane", "jason", "jennifer", "jessica",
+    "john", "jonathan", "jose", "joseph", "josé", "juan", "julia", "karen", "kevin", "laura",
+    "linda", "lisa", "luca", "lucas", "maria", "mark", "martin", "mary", "matthew", "michael",
+    "michelle", "mohammed", "nancy", "nicole", "oliver", "olivia", "patricia", "paul", "peter", "rachel",
+    "rebecca", "richard", "robert", "sam", "samuel", "sandra", "sara", "sarah", "sophia", "stephen",
+    "steven", "susan", "thomas", "timothy", "victoria", "wei", "william", "yuki", "zoe",
+];
+
+/// Most capitalized words a detected name may span ("Mary Ann Evans").
+const MAX_NAME_WORDS: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct HeuristicNameDetector {
+    capitalized_word: Regex,
+    honorifics: HashSet<String>,
+    first_names: HashSet<String>,
+}
+
+impl HeuristicNameDetector {
+    pub fn new(config: &NameDetectionConfig) -> Result<Self> {
+        let capitalized_word = Regex::new(r"\p{Lu}[\p{Ll}'’-]+")?;
+        let honorifics = config.honorifics.iter()
+            .map(|h| h.trim_end_matches('.').to_lowercase())
+            .collect();
+        let first_names = BUILTIN_FIRST_NAMES.iter()
+            .map(|name| name.to_string())
+            .chain(config.first_names.iter().map(|name| name.to_lowercase()))
+            .collect();
+
+        Ok(Self { capitalized_word, honorifics, first_names })
+    }
+
+    pub fn detect(&self, text: &str) -> Vec<DetectedEntity> {
+        let words: Vec<_> = self.capitalized_word.find_iter(text)
+            .filter(|w| is_whole_word(text, w.start(), w.end()))
+            .collect();
+        let mut entities = Vec::new();
+
+        let mut i = 0;
+        while i < words.len() {
+            let is_title = self.is_honorific(words[i].as_str())
+                && words.get(i + 1).is_some_and(|next| is_title_gap(&text[words[i].end()..next.start()]));
+            let first = if is_title { i + 1 } else { i };
+
+            let mut last = first;
+            while last + 1 < words.len()
+                && last + 1 - first < MAX_NAME_WORDS
+                && is_word_gap(&text[words[last].end()..words[last + 1].start()])
+                && !self.is_honorific(words[last + 1].as_str())
+            {
+                last += 1;
+            }
+
+            let confidence = if is_title {
+                Some(HONORIFIC_CONFIDENCE)
+            } else if self.first_names.contains(&words[first].as_str().to_lowercase()) {
+                Some(if last > first { FULL_NAME_CONFIDENCE } else { FIRST_NAME_ONLY_CONFIDENCE })
+            } else {
+                None
+            };
+
+            match confidence {
+                Some(confidence) => {
+                    let (start, end) = (words[first].start(), words[last].end());
+                    entities.push(DetectedEntity {
+                        entity_type: "name".to_string(),
+                        original_value: text[start..end].to_string(),
+                        start,
+                        end,
+                        confidence,
+                        ..Default::default()
+                    });
+                    i = last + 1;
+                }
+                // A capitalized word that starts nothing may still precede a name ("Ask Maria Keller")
+                None => i += 1,
+            }
+        }
+
+        entities
+    }
+
+    fn is_honorific(&self, word: &str) -> bool {
+        self.honorifics.contains(&word.to_lowercase())
+    }
+}
+
+fn is_whole_word(text: &str, start: usize, end: usize) -> bool {
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+    !before.is_some_and(char::is_alphanumeric) && !after.is_some_and(char::is_alphanumeric)
+}
+
+fn is_word_gap(gap: &str) -> bool {
+    !gap.is_empty() && gap.chars().all(|c| c == ' ' || c == '\t')
+}
+
+fn is_title_gap(gap: &str) -> bool {
+    is_word_gap(gap.strip_prefix('.').unwrap_or(gap))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detector() -> HeuristicNameDetector {
+        HeuristicNameDetector::new(&NameDetectionConfig::default()).unwrap()
+    }
+
+    fn names(text: &str) -> Vec<(String, f64)> {
+        detector().detect(text).into_iter().map(|e| (e.original_value, e.confidence)).collect()
+    }
+
+    #[test]
+    fn test_honorific_marks_following_name() {
+        let found = names("Please forward this to Dr. Alvarez Quintana before noon.");
+        assert_eq!(found, vec![("Alvarez Quintana".to_string(), HONORIFIC_CONFIDENCE)]);
+
+        let found = names("Ms Okafor approved it");
+        assert_eq!(found[0].0, "Okafor");
+    }
+
+    #[test]
+    fn test_known_first_name_with_surname() {
+        let found = names("Ask Maria Keller and José García yesterday");
+        assert_eq!(found, vec![
+            ("Maria Keller".to_string(), FULL_NAME_CONFIDENCE),
+            ("José García".to_string(), FULL_NAME_CONFIDENCE),
+        ]);
+    }
+
+    #[test]
+    fn test_ordinary_capitalized_words_are_ignored() {
+        assert!(names("The Quarterly Report is due. Server Status: OK").is_empty());
+
+        let found = names("Thanks, Sarah");
+        assert_eq!(found, vec![("Sarah".to_string(), FIRST_NAME_ONLY_CONFIDENCE)]);
+    }
+
+    #[test]
+    fn test_configured_first_names_extend_gazetteer() {
+        let config = NameDetectionConfig {
+            first_names: vec!["Siobhan".to_string()],
+            ..NameDetectionConfig::default()
+        };
+        let detector = HeuristicNameDetector::new(&config).unwrap();
+
+        let found = detector.detect("Ticket owner: Siobhan Byrne");
+        assert_eq!(found[0].original_value, "Siobhan Byrne");
+        assert_eq!(&"Ticket owner: Siobhan Byrne"[found[0].start..found[0].end], "Siobhan Byrne");
+    }
+}
@@ -0,0 +1,189 @@
+//! Check digits of national identifiers
+//!
+//! The `uk`, `ca`, `br`, `in` and `eu` pattern packs find numbers shaped like
+//! national identifiers; these checks drop the ones whose check digits are
+//! wrong, as `validators` does for card numbers. Indian PANs have no check
+//! digit, and EU VAT numbers are checked for Austria, Belgium, Germany,
+//! France, Italy, the Netherlands and Poland; other countries' numbers are
+//! accepted on their format.
+
+use crate::validators::luhn_checks;
+
+fn digits(value: &str) -> Vec<u32> {
+    value.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+/// UK National Insurance number: not one of the prefixes that are never issued.
+pub fn nino(value: &str) -> bool {
+    let letters: String = value.chars().filter(char::is_ascii_alphabetic).collect();
+    if letters.len() != 3 || digits(value).len() != 6 {
+        return false;
+    }
+    let prefix = letters[..2].to_ascii_uppercase();
+    !["BG", "GB", "NK", "KN", "TN", "NT", "ZZ"].contains(&prefix.as_str())
+}
+
+/// Canadian Social Insurance Number: 9 digits, Luhn.
+pub fn sin(value: &str) -> bool {
+    let digits = digits(value);
+    digits.len() == 9 && luhn_checks(&digits)
+}
+
+/// Brazilian CPF: 11 digits, two mod-11 check digits.
+pub fn cpf(value: &str) -> bool {
+    let digits = digits(value);
+    if digits.len() != 11 || digits.iter().all(|d| *d == digits[0]) {
+        return false;
+    }
+    (9..11).all(|n| {
+        let sum: u32 = digits[..n].iter().enumerate().map(|(i, d)| d * (n as u32 + 1 - i as u32)).sum();
+        sum * 10 % 11 % 10 == digits[n]
+    })
+}
+
+/// Brazilian CNPJ: 14 digits, two weighted mod-11 check digits.
+pub fn cnpj(value: &str) -> bool {
+    let digits = digits(value);
+    if digits.len() != 14 || digits.iter().all(|d| *d == digits[0]) {
+        return false;
+    }
+    const WEIGHTS: [u32; 13] = [6, 5, 4, 3, 2, 9, 8, 7, 6, 5, 4, 3, 2];
+    (12..14).all(|n| {
+        let sum: u32 = digits[..n].iter().zip(&WEIGHTS[14 - n - 1..]).map(|(d, w)| d * w).sum();
+        let check = if sum % 11 < 2 { 0 } else { 11 - sum % 11 };
+        check == digits[n]
+    })
+}
+
+/// Indian Aadhaar number: 12 digits, Verhoeff.
+pub fn aadhaar(value: &str) -> bool {
+    let digits = digits(value);
+    digits.len() == 12 && verhoeff_checks(&digits)
+}
+
+fn verhoeff_checks(digits: &[u32]) -> bool {
+    const D: [[u32; 10]; 10] = [
+        [0, 1, 2, 3, 4, 5, 6, 7, 8, 9], [1, 2, 3, 4, 0, 6, 7, 8, 9, 5], [2, 3, 4, 0, 1, 7, 8, 9, 5, 6],
+        [3, 4, 0, 1, 2, 8, 9, 5, 6, 7], [4, 0, 1, 2, 3, 9, 5, 6, 7, 8], [5, 9, 8, 7, 6, 0, 4, 3, 2, 1],
+        [6, 5, 9, 8, 7, 1, 0, 4, 3, 2], [7, 6, 5, 9, 8, 2, 1, 0, 4, 3], [8, 7, 6, 5, 9, 3, 2, 1, 0, 4],
+        [9, 8, 7, 6, 5, 4, 3, 2, 1, 0],
+    ];
+    const P: [[u32; 10]; 8] = [
+        [0, 1, 2, 3, 4, 5, 6, 7, 8, 9], [1, 5, 7, 6, 2, 8, 3, 0, 9, 4], [5, 8, 0, 3, 7, 9, 6, 1, 4, 2],
+        [8, 9, 1, 6, 0, 4, 3, 5, 2, 7], [9, 4, 5, 3, 1, 2, 6, 8, 7, 0], [4, 2, 8, 6, 5, 7, 3, 9, 0, 1],
+        [2, 7, 9, 3, 8, 0, 6, 4, 1, 5], [7, 0, 4, 6, 9, 1, 3, 2, 5, 8],
+    ];
+    digits.iter().rev().enumerate()
+        .fold(0, |check, (i, &d)| D[check as usize][P[i % 8][d as usize] as usize]) == 0
+}
+
+/// EU VAT number, with its country prefix.
+pub fn eu_vat(value: &str) -> bool {
+    let compact: String = value.chars().filter(|c| !c.is_whitespace() && *c != '.' && *c != '-').collect();
+    let Some((country, number)) = compact.split_at_checked(2) else {
+        return false;
+    };
+    let digits = digits(number);
+    match country.to_ascii_uppercase().as_str() {
+        "AT" => digits.len() == 8 && austria(&digits),
+        "BE" => digits.len() == 10 && belgium(&digits),
+        "DE" => digits.len() == 9 && germany(&digits),
+        "FR" => france(number),
+        "IT" => digits.len() == 11 && luhn_checks(&digits),
+        "NL" => netherlands(number, &digits),
+        "PL" => digits.len() == 10 && weighted_mod_11(&digits, &[6, 5, 7, 2, 3, 4, 5, 6, 7]),
+        _ => true,
+    }
+}
+
+fn to_number(digits: &[u32]) -> u64 {
+    digits.iter().fold(0, |n, d| n * 10 + u64::from(*d))
+}
+
+fn austria(digits: &[u32]) -> bool {
+    let sum: u32 = digits[..7].iter().enumerate()
+        .map(|(i, &d)| if i % 2 == 1 { d * 2 / 10 + d * 2 % 10 } else { d })
+        .sum();
+    (10 - (sum + 4) % 10) % 10 == digits[7]
+}
+
+fn belgium(digits: &[u32]) -> bool {
+    97 - to_number(&digits[..8]) % 97 == to_number(&digits[8..])
+}
+
+// ISO 7064 MOD 11,10
+fn germany(digits: &[u32]) -> bool {
+    let product = digits[..8].iter().fold(10, |product, d| {
+        let sum = match (d + product) % 10 {
+            0 => 10,
+            sum => sum,
+        };
+        2 * sum % 11
+    });
+    (11 - product) % 10 == digits[8]
+}
+
+/// Two key characters, then the 9-digit SIREN; only numeric keys can be checked.
+fn france(number: &str) -> bool {
+    let Some((key, siren)) = number.split_at_checked(2) else {
+        return false;
+    };
+    if siren.len() != 9 || !siren.bytes().all(|b| b.is_ascii_digit()) {
+        return false;
+    }
+    match key.parse::<u64>() {
+        Ok(key) => key == (12 + 3 * (to_number(&digits(siren)) % 97)) % 97,
+        Err(_) => key.chars().all(|c| c.is_ascii_alphanumeric()),
+    }
+}
+
+/// `NL` + 9 digits + `B` + 2 digits; older numbers use weighted mod 11, newer
+/// ones mod 97 over the whole number.
+fn netherlands(number: &str, digits: &[u32]) -> bool {
+    if digits.len() != 11 {
+        return false;
+    }
+    let weighted = weighted_mod_11(&digits[..9], &[9, 8, 7, 6, 5, 4, 3, 2]);
+    let whole: String = format!("NL{}", number).chars()
+        .map(|c| c.to_digit(36).map_or(String::new(), |d| d.to_string()))
+        .collect();
+    let mod_97 = whole.chars().filter_map(|c| c.to_digit(10)).fold(0u64, |r, d| (r * 10 + u64::from(d)) % 97) == 1;
+    weighted || mod_97
+}
+
+/// The last digit is the weighted sum of the others mod 11.
+fn weighted_mod_11(digits: &[u32], weights: &[u32]) -> bool {
+    let sum: u32 = digits.iter().zip(weights).map(|(d, w)| d * w).sum();
+    sum % 11 != 10 && sum % 11 == digits[weights.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_digits() {
+        assert!(nino("AB 12 34 56 C"));
+        assert!(!nino("GB 12 34 56 C"));
+        assert!(sin("046 454 286"));
+        assert!(!sin("046 454 287"));
+        assert!(cpf("529.982.247-25"));
+        assert!(!cpf("529.982.247-26"));
+        assert!(!cpf("111.111.111-11"));
+        assert!(cnpj("11.222.333/0001-81"));
+        assert!(!cnpj("11.222.333/0001-82"));
+        assert!(aadhaar("2345 6789 0124"));
+        assert!(!aadhaar("2345 6789 0125"));
+    }
+
+    #[test]
+    fn test_eu_vat_numbers() {
+        for valid in ["ATU10223006", "BE0411905847", "DE136695976", "FR40303265045", "IT00743110157",
+                      "NL004495445B01", "PL5260250274", "SE556036079301"] {
+            assert!(eu_vat(valid), "{}", valid);
+        }
+        for invalid in ["DE136695977", "FR41303265045", "IT00743110158", "PL5260250275", "BE0411905848"] {
+            assert!(!eu_vat(invalid), "{}", invalid);
+        }
+    }
+}
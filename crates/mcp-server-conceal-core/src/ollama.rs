@@ -6,10 +6,21 @@
 use anyhow::Result;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tracing::{debug, error, info, warn};
-use crate::config::DetectedEntity;
+use crate::chaos;
+use crate::chunking::{self, LlmChunkingConfig};
+use crate::circuit_breaker::{CircuitBreaker, CircuitBreakerConfig, CircuitState};
+use crate::discovery;
+use crate::language::LanguageConfig;
+use crate::config::{DetectedEntity, LlmBatchConfig, LlmOutputFormat};
 use crate::prompt_loader::PromptLoader;
+use crate::provider::{self, LlmProvider};
+use crate::scheduler::{LlmConcurrencyConfig, LlmScheduler};
+use crate::secrets::Passphrase;
+use crate::verification::{self, LlmVerificationConfig};
 
 #[derive(Debug, Clone)]
 pub struct OllamaConfig {
@@ -17,6 +28,18 @@ pub struct OllamaConfig {
     pub model: String,
     pub timeout_seconds: u64,
     pub enabled: bool,
+    pub circuit_breaker: CircuitBreakerConfig,
+    pub concurrency: LlmConcurrencyConfig,
+    pub batching: LlmBatchConfig,
+    pub chunking: LlmChunkingConfig,
+    pub verification: LlmVerificationConfig,
+    pub output_format: LlmOutputFormat,
+    /// Pull missing models at startup instead of failing
+    pub auto_pull: bool,
+    pub provider: LlmProvider,
+    /// Allow non-local endpoints; raw text leaves this host
+    pub allow_remote: bool,
+    pub api_key_env: Option<String>,
 }
 
 impl Default for OllamaConfig {
@@ -26,6 +49,16 @@ impl Default for OllamaConfig {
             model: "llama3.2:3b".to_string(),
             timeout_seconds: 30,
             enabled: false,
+            circuit_breaker: CircuitBreakerConfig::default(),
+            concurrency: LlmConcurrencyConfig::default(),
+            batching: LlmBatchConfig::default(),
+            chunking: LlmChunkingConfig::default(),
+            verification: LlmVerificationConfig::default(),
+            output_format: LlmOutputFormat::default(),
+            auto_pull: false,
+            provider: LlmProvider::default(),
+            allow_remote: false,
+            api_key_env: None,
         }
     }
 }
@@ -35,6 +68,9 @@ struct OllamaRequest {
     model: String,
     prompt: String,
     stream: bool,
+    /// `"json"` or a JSON schema constraining the reply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<serde_json::Value>,
     options: OllamaOptions,
 }
 
@@ -51,6 +87,40 @@ struct OllamaResponse {
     done: bool,
 }
 
+#[derive(Debug, Deserialize)]
+struct TagsResponse {
+    #[serde(default)]
+    models: Vec<ModelTag>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModelTag {
+    name: String,
+}
+
+/// One line of the streamed `/api/pull` progress.
+#[derive(Debug, Deserialize)]
+struct PullProgress {
+    #[serde(default)]
+    status: String,
+    #[serde(default)]
+    total: Option<u64>,
+    #[serde(default)]
+    completed: Option<u64>,
+    #[serde(default)]
+    error: Option<String>,
+}
+
+/// Pulls can take far longer than a generation.
+const PULL_TIMEOUT: Duration = Duration::from_secs(3600);
+
+/// Whether a configured model name refers to an installed tag; an untagged
+/// name means `:latest`, as in the Ollama CLI.
+fn model_matches(configured: &str, installed: &str) -> bool {
+    let normalize = |name: &str| if name.contains(':') { name.to_string() } else { format!("{}:latest", name) };
+    normalize(configured) == normalize(installed)
+}
+
 #[derive(Debug, Deserialize)]
 pub struct LlmResponse {
     pub entities: Vec<LlmDetectedEntity>,
@@ -67,18 +137,110 @@ pub struct LlmDetectedEntity {
     pub end: usize,
     #[serde(default = "default_confidence")]
     pub confidence: f64,
+    /// Item number in a batched request
+    #[serde(default)]
+    pub item: Option<usize>,
+}
+
+/// JSON schema of an entity detection reply; batched replies also carry `item`.
+fn entities_schema(batched: bool) -> serde_json::Value {
+    let mut entity = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "type": {"type": "string"},
+            "value": {"type": "string"},
+            "start": {"type": "integer"},
+            "end": {"type": "integer"},
+            "confidence": {"type": "number"}
+        },
+        "required": ["type", "value", "confidence"]
+    });
+    if batched {
+        entity["properties"]["item"] = serde_json::json!({"type": "integer"});
+        entity["required"] = serde_json::json!(["type", "value", "confidence", "item"]);
+    }
+    serde_json::json!({
+        "type": "object",
+        "properties": {"entities": {"type": "array", "items": entity}},
+        "required": ["entities"]
+    })
+}
+
+/// Appended to the prompt when several texts are sent in one request.
+const BATCH_INSTRUCTIONS: &str = "\n\nBATCH_MODE: TEXT contains numbered items, each starting with a marker like [[1]]. \
+Treat every item as a separate text. Add an \"item\" field with the item number to every entity, \
+e.g. {\"type\": \"email\", \"value\": \"a@b.com\", \"item\": 2, \"confidence\": 0.9}. \
+Never include the [[n]] markers in values.";
+
+fn batch_marker(index: usize) -> String {
+    format!("[[{}]]", index + 1)
+}
+
+/// Joins texts into a single numbered block for one prompt.
+pub fn format_batch(texts: &[&str]) -> String {
+    texts.iter()
+        .enumerate()
+        .map(|(index, text)| format!("{} {}", batch_marker(index), text))
+        .collect::<Vec<_>>()
+        .join("\n")
 }
 
 fn default_confidence() -> f64 {
     0.8
 }
 
+/// Maps an LLM-reported span onto byte offsets into `text`.
+///
+/// Models report offsets inconsistently: sometimes bytes, often characters,
+/// frequently neither. The reported span is accepted when it selects `value`
+/// as either byte or character offsets; otherwise the occurrence of `value`
+/// closest to the reported start is used. Never slices on a non-char boundary.
+pub fn resolve_entity_span(text: &str, value: &str, start: usize, end: usize) -> Option<(usize, usize)> {
+    if value.is_empty() {
+        return None;
+    }
+
+    if start < end {
+        if text.get(start..end) == Some(value) {
+            return Some((start, end));
+        }
+        if let (Some(byte_start), Some(byte_end)) = (char_to_byte_offset(text, start), char_to_byte_offset(text, end)) {
+            if text.get(byte_start..byte_end) == Some(value) {
+                return Some((byte_start, byte_end));
+            }
+        }
+        debug!("Reported span {}-{} does not match '{}', searching text", start, end, value);
+    }
+
+    // Multiple occurrences: prefer the one nearest the reported position
+    text.match_indices(value)
+        .map(|(found, _)| found)
+        .min_by_key(|found| found.abs_diff(start))
+        .map(|found| (found, found + value.len()))
+}
+
+fn char_to_byte_offset(text: &str, char_offset: usize) -> Option<usize> {
+    text.char_indices()
+        .map(|(byte, _)| byte)
+        .chain(std::iter::once(text.len()))
+        .nth(char_offset)
+}
+
 #[derive(Clone)]
 pub struct OllamaClient {
     client: Client,
     config: OllamaConfig,
     prompt_loader: PromptLoader,
     prompt_template: String,
+    /// Language detection choosing among `language_prompts`
+    language: LanguageConfig,
+    /// Language code → prompt template for texts in that language
+    language_prompts: HashMap<String, String>,
+    /// Shared by all clones, so both proxy directions see the same backend state
+    breaker: Arc<Mutex<CircuitBreaker>>,
+    scheduler: LlmScheduler,
+    /// API key of a hosted provider
+    api_key: Option<Passphrase>,
 }
 
 impl OllamaClient {
@@ -90,34 +252,333 @@ impl OllamaClient {
 
         let prompt_loader = PromptLoader::new()?;
         let template = prompt_loader.load_prompt(prompt_template)?;
+        let breaker = Arc::new(Mutex::new(CircuitBreaker::new(config.circuit_breaker.clone())));
+        let scheduler = LlmScheduler::new(&config.concurrency);
+
+        let mut api_key = None;
+        if config.enabled {
+            provider::ensure_endpoint_allowed(config.provider, &config.endpoint, config.allow_remote)?;
+            if config.allow_remote && !provider::is_local_endpoint(&config.endpoint) {
+                warn!("Sending text to remote {} endpoint {}: raw values leave this host", config.provider.as_str(), config.endpoint);
+            }
+            if let Some(var) = config.api_key_env.as_deref().or(config.provider.default_api_key_env()) {
+                let key = std::env::var(var).ok().filter(|key| !key.is_empty());
+                if key.is_none() && config.provider.is_hosted() {
+                    return Err(anyhow::anyhow!("No API key for the {} provider found in {}", config.provider.as_str(), var));
+                }
+                api_key = key.map(Passphrase::new);
+            }
+        }
 
         Ok(Self { 
             client, 
             config, 
             prompt_loader,
             prompt_template: template,
+            language: LanguageConfig::default(),
+            language_prompts: HashMap::new(),
+            breaker,
+            scheduler,
+            api_key,
         })
     }
 
+    /// The detection prompt template in use, after resolving named templates.
+    pub fn prompt_template(&self) -> &str {
+        &self.prompt_template
+    }
+
+    /// Loads the prompt templates configured per language in `[language]`;
+    /// texts in other languages keep the default template.
+    pub fn with_language_prompts(mut self, language: &LanguageConfig) -> Result<Self> {
+        if !language.enabled {
+            return Ok(self);
+        }
+        for (code, name) in &language.prompts {
+            let template = self.prompt_loader.load_prompt(Some(name))?;
+            self.language_prompts.insert(code.clone(), template);
+        }
+        self.language = language.clone();
+        Ok(self)
+    }
+
+    /// Detection prompt template for the language `text` is written in.
+    fn template_for(&self, text: &str) -> &str {
+        if self.language_prompts.is_empty() {
+            return &self.prompt_template;
+        }
+        self.language.detect(text)
+            .and_then(|code| self.language_prompts.get(code))
+            .unwrap_or(&self.prompt_template)
+    }
+
+    /// Adds the provider's authentication headers, if it has a key.
+    fn authorize(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.api_key {
+            Some(key) => self.config.provider.auth_headers(key.expose())
+                .into_iter()
+                .fold(request, |request, (name, value)| request.header(name, value)),
+            None => request,
+        }
+    }
+
+    pub fn circuit_state(&self) -> CircuitState {
+        self.breaker.lock().map(|b| b.state()).unwrap_or(CircuitState::Closed)
+    }
+
+    fn circuit_allows_request(&self) -> bool {
+        self.breaker.lock().map(|mut b| b.allow_request()).unwrap_or(true)
+    }
+
+    fn record_outcome(&self, success: bool) {
+        if let Ok(mut breaker) = self.breaker.lock() {
+            if success {
+                breaker.record_success();
+            } else {
+                breaker.record_failure();
+            }
+        }
+    }
+
+    pub fn batching(&self) -> &LlmBatchConfig {
+        &self.config.batching
+    }
+
+    pub fn concurrency(&self) -> &LlmConcurrencyConfig {
+        &self.config.concurrency
+    }
+
     pub async fn extract_entities(&self, text: &str) -> Result<Vec<DetectedEntity>> {
         if !self.config.enabled {
             debug!("Ollama client is disabled, returning empty entities");
             return Ok(vec![]);
         }
 
+        let chunking = &self.config.chunking;
+        if chunking.enabled && text.len() > chunking.max_chars {
+            let chunks = chunking::split(text, chunking.max_chars, chunking.overlap_chars);
+            debug!("Splitting {} characters into {} chunks for LLM detection", text.len(), chunks.len());
+
+            let mut per_chunk = Vec::with_capacity(chunks.len());
+            for chunk in chunks {
+                per_chunk.push((chunk, self.extract_entities_unchunked(chunk.text).await?));
+            }
+            return Ok(chunking::merge(per_chunk));
+        }
+
+        self.extract_entities_unchunked(text).await
+    }
+
+    /// Asks which identifier-like values survived anonymization of `text`.
+    pub async fn discover_unreplaced(&self, text: &str) -> Result<Vec<DetectedEntity>> {
+        if !self.config.enabled {
+            return Ok(Vec::new());
+        }
+        let prompt = self.prompt_loader.format_prompt(discovery::DISCOVERY_PROMPT, text);
+        let response = self.generate(&prompt, entities_schema(false)).await?;
+        self.parse_llm_response(&response, text)
+    }
+
+    async fn extract_entities_unchunked(&self, text: &str) -> Result<Vec<DetectedEntity>> {
         debug!("Sending text to Ollama for LLM detection: {} characters", text.len());
 
-        let prompt = self.prompt_loader.format_prompt(&self.prompt_template, text);
-        let response = self.call_ollama(&prompt).await?;
+        let prompt = self.prompt_loader.format_prompt(self.template_for(text), text);
+        let response = self.generate(&prompt, entities_schema(false)).await?;
         
-        self.parse_llm_response(&response, text)
+        let entities = self.parse_llm_response(&response, text)?;
+        if self.config.verification.enabled {
+            return Ok(self.verify_critical_entities(text, entities).await);
+        }
+        Ok(entities)
+    }
+
+    /// Keeps critical-type entities only if the verifier confirms them. When the
+    /// verifier cannot answer, candidates are kept: over-anonymizing is the safer failure.
+    async fn verify_critical_entities(&self, text: &str, entities: Vec<DetectedEntity>) -> Vec<DetectedEntity> {
+        let (critical, mut accepted): (Vec<_>, Vec<_>) = entities.into_iter()
+            .partition(|e| self.config.verification.is_critical(&e.entity_type));
+        if critical.is_empty() {
+            return accepted;
+        }
+
+        let candidates: Vec<&DetectedEntity> = critical.iter().collect();
+        let prompt = verification::verification_prompt(text, &candidates);
+        let model = self.config.verification.model.as_deref().unwrap_or(&self.config.model);
+        let verdicts = match self.generate_with_model(model, &prompt, verification::verdicts_schema()).await {
+            Ok(response) => self.extract_json_from_response(&response)
+                .and_then(|json| verification::parse_verdicts(&json, critical.len())),
+            Err(e) => Err(e),
+        };
+
+        match verdicts {
+            Ok(verdicts) => {
+                let rejected = verdicts.iter().filter(|v| !**v).count();
+                if rejected > 0 {
+                    debug!("Verifier rejected {} of {} critical entities", rejected, critical.len());
+                }
+                accepted.extend(critical.into_iter().zip(verdicts).filter(|(_, ok)| *ok).map(|(e, _)| e));
+            }
+            Err(e) => {
+                warn!("Verification of critical entities failed, keeping candidates: {}", e);
+                accepted.extend(critical);
+            }
+        }
+        accepted.sort_by_key(|e| e.start);
+        accepted
+    }
+
+    /// Detects entities in several texts with a single request. Results are
+    /// returned in the order of `texts`.
+    pub async fn extract_entities_batch(&self, texts: &[&str]) -> Result<Vec<Vec<DetectedEntity>>> {
+        if !self.config.enabled {
+            return Ok(vec![Vec::new(); texts.len()]);
+        }
+        if let [text] = texts {
+            return Ok(vec![self.extract_entities(text).await?]);
+        }
+
+        // Texts are batched with others of the same language only, each
+        // group sent with the prompt for its language
+        let mut groups: Vec<(&str, Vec<usize>)> = Vec::new();
+        for (index, text) in texts.iter().enumerate() {
+            let template = self.template_for(text);
+            match groups.iter_mut().find(|(group, _)| std::ptr::eq(*group, template)) {
+                Some((_, indices)) => indices.push(index),
+                None => groups.push((template, vec![index])),
+            }
+        }
+        if let [(template, _)] = groups[..] {
+            return self.extract_entities_batch_with(template, texts).await;
+        }
+        let mut results = vec![Vec::new(); texts.len()];
+        for (template, indices) in groups {
+            let group: Vec<&str> = indices.iter().map(|&index| texts[index]).collect();
+            let found = match group[..] {
+                [text] => vec![self.extract_entities(text).await?],
+                _ => self.extract_entities_batch_with(template, &group).await?,
+            };
+            for (index, entities) in indices.into_iter().zip(found) {
+                results[index] = entities;
+            }
+        }
+        Ok(results)
+    }
+
+    /// Detects entities in several texts with a single request sent with `template`.
+    async fn extract_entities_batch_with(&self, template: &str, texts: &[&str]) -> Result<Vec<Vec<DetectedEntity>>> {
+        debug!("Sending batch of {} texts to Ollama for LLM detection", texts.len());
+
+        let batch = format_batch(texts);
+        let mut prompt = self.prompt_loader.format_prompt(template, &batch);
+        prompt.push_str(BATCH_INSTRUCTIONS);
+        let response = self.generate(&prompt, entities_schema(true)).await?;
+
+        let results = self.parse_batch_response(&response, texts)?;
+        if !self.config.verification.enabled {
+            return Ok(results);
+        }
+        let mut verified = Vec::with_capacity(results.len());
+        for (text, entities) in texts.iter().zip(results) {
+            verified.push(self.verify_critical_entities(text, entities).await);
+        }
+        Ok(verified)
+    }
+
+    /// Sends a prompt through the circuit breaker and concurrency limits.
+    async fn generate(&self, prompt: &str, schema: serde_json::Value) -> Result<String> {
+        self.generate_with_model(&self.config.model, prompt, schema).await
+    }
+
+    /// `schema` describes the expected reply and is sent when `output_format = "schema"`.
+    async fn generate_with_model(&self, model: &str, prompt: &str, schema: serde_json::Value) -> Result<String> {
+        if !self.circuit_allows_request() {
+            return Err(anyhow::anyhow!("LLM circuit is open, skipping Ollama request"));
+        }
+
+        let _slot = self.scheduler.acquire().await?;
+        if let Some(chaos) = chaos::injector() {
+            chaos.delay_llm_call().await;
+        }
+        // Only transport failures count against the backend; unparseable output does not
+        if self.config.provider.is_hosted() {
+            let response = self.call_hosted(model, prompt, schema).await;
+            self.record_outcome(response.is_ok());
+            return response;
+        }
+        let format = match self.config.output_format {
+            LlmOutputFormat::None => None,
+            LlmOutputFormat::Json => Some(serde_json::Value::from("json")),
+            LlmOutputFormat::Schema => Some(schema),
+        };
+        let response = self.call_ollama(model, prompt, format).await;
+        self.record_outcome(response.is_ok());
+        response
+    }
+
+    /// Generation through a hosted provider's API.
+    async fn call_hosted(&self, model: &str, prompt: &str, schema: serde_json::Value) -> Result<String> {
+        let provider = self.config.provider;
+        let url = provider.generate_url(&self.config.endpoint);
+        debug!("Making request to {}: {}", provider.as_str(), url);
+
+        let body = provider.request_body(model, prompt, self.config.output_format, schema);
+        let response = self.authorize(self.client.post(&url).json(&body)).send().await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let error_text = response.text().await.unwrap_or_default();
+            error!("{} request failed with status {}: {}", provider.as_str(), status, error_text);
+            return Err(anyhow::anyhow!("{} request failed: {} - {}", provider.as_str(), status, error_text));
+        }
+
+        let reply: serde_json::Value = response.json().await?;
+        let text = provider.response_text(&reply)?;
+        debug!("Received response from {}: {} characters", provider.as_str(), text.len());
+        Ok(text)
     }
 
-    async fn call_ollama(&self, prompt: &str) -> Result<String> {
+    fn parse_batch_response(&self, response: &str, texts: &[&str]) -> Result<Vec<Vec<DetectedEntity>>> {
+        let json_str = self.extract_json_from_response(response)?;
+        let llm_response: LlmResponse = serde_json::from_str(&json_str)
+            .map_err(|e| anyhow::anyhow!("Failed to parse LLM JSON response: {}", e))?;
+
+        let mut results = vec![Vec::new(); texts.len()];
+        for llm_entity in llm_response.entities {
+            // Trust the item number when it points at a text containing the value;
+            // otherwise attribute the value to every text that contains it
+            let targets: Vec<usize> = match llm_entity.item {
+                Some(item) if (1..=texts.len()).contains(&item) && texts[item - 1].contains(llm_entity.value.as_str()) => vec![item - 1],
+                _ => (0..texts.len()).filter(|&i| texts[i].contains(llm_entity.value.as_str())).collect(),
+            };
+            if targets.is_empty() {
+                warn!("Could not find batched entity '{}' in any text", llm_entity.value);
+            }
+
+            for index in targets {
+                let Some((start, end)) = resolve_entity_span(texts[index], &llm_entity.value, llm_entity.start, llm_entity.end) else {
+                    continue;
+                };
+                results[index].push(DetectedEntity {
+                    entity_type: llm_entity.entity_type.clone(),
+                    original_value: llm_entity.value.clone(),
+                    start,
+                    end,
+                    confidence: llm_entity.confidence,
+                    ..Default::default()
+                });
+            }
+        }
+
+        info!("Ollama extracted {} entities from a batch of {} texts", results.iter().map(Vec::len).sum::<usize>(), texts.len());
+        Ok(results)
+    }
+
+    async fn call_ollama(&self, model: &str, prompt: &str, format: Option<serde_json::Value>) -> Result<String> {
         let request = OllamaRequest {
-            model: self.config.model.clone(),
+            model: model.to_string(),
             prompt: prompt.to_string(),
             stream: false,
+            format,
             options: OllamaOptions {
                 temperature: 0.0,  // Set to 0 for deterministic JSON output
                 top_p: 0.1,        // Very low for strict adherence to format
@@ -128,7 +589,7 @@ impl OllamaClient {
         debug!("Making request to Ollama: {}/api/generate", self.config.endpoint);
         
         let response = self.client
-            .post(&format!("{}/api/generate", self.config.endpoint))
+            .post(format!("{}/api/generate", self.config.endpoint))
             .json(&request)
             .send()
             .await?;
@@ -163,36 +624,9 @@ impl OllamaClient {
         let mut entities = Vec::new();
         
         for llm_entity in llm_response.entities {
-            let (start, end) = if llm_entity.start == 0 && llm_entity.end == 0 {
-                if let Some((found_start, found_end)) = self.find_entity_position(original_text, &llm_entity.value) {
-                    (found_start, found_end)
-                } else {
-                    warn!("Could not find entity '{}' in text", llm_entity.value);
-                    continue;
-                }
-            } else {
-                if llm_entity.start >= llm_entity.end || llm_entity.end > original_text.len() {
-                    warn!("Invalid entity positions for '{}': {}-{}", 
-                          llm_entity.value, llm_entity.start, llm_entity.end);
-                    if let Some((found_start, found_end)) = self.find_entity_position(original_text, &llm_entity.value) {
-                        (found_start, found_end)
-                    } else {
-                        continue;
-                    }
-                } else {
-                    let actual_text = &original_text[llm_entity.start..llm_entity.end];
-                    if actual_text != llm_entity.value {
-                        warn!("Entity value mismatch: expected '{}', found '{}'", 
-                              llm_entity.value, actual_text);
-                            if let Some((found_start, found_end)) = self.find_entity_position(original_text, &llm_entity.value) {
-                            (found_start, found_end)
-                        } else {
-                            continue;
-                        }
-                    } else {
-                        (llm_entity.start, llm_entity.end)
-                    }
-                }
+            let Some((start, end)) = resolve_entity_span(original_text, &llm_entity.value, llm_entity.start, llm_entity.end) else {
+                warn!("Could not find entity '{}' in text", llm_entity.value);
+                continue;
             };
 
             entities.push(DetectedEntity {
@@ -201,6 +635,7 @@ impl OllamaClient {
                 start,
                 end,
                 confidence: llm_entity.confidence,
+                ..Default::default()
             });
         }
 
@@ -209,16 +644,22 @@ impl OllamaClient {
     }
 
     fn extract_json_from_response(&self, response: &str) -> Result<String> {
-        // First, fix double braces that Ollama might return due to template parsing
+        // Structured output arrives as plain JSON; the repairs below are for free-form replies
+        let trimmed = response.trim();
+        if trimmed.starts_with('{') && serde_json::from_str::<serde_json::Value>(trimmed).is_ok() {
+            return Ok(trimmed.to_string());
+        }
+
+        // Fix double braces that Ollama might return due to template parsing
         let fixed_response = response.replace("{{", "{").replace("}}", "}");
         
         // Try to find and extract the first complete JSON object
         if let Some(start) = fixed_response.find('{') {
             let mut brace_count = 0;
             let mut end_pos = start;
-            let chars: Vec<char> = fixed_response.chars().collect();
             
-            for (i, &ch) in chars.iter().enumerate().skip(start) {
+            // Byte offsets throughout, so text before the JSON may contain any UTF-8
+            for (i, ch) in fixed_response.char_indices().skip_while(|(i, _)| *i < start) {
                 match ch {
                     '{' => brace_count += 1,
                     '}' => {
@@ -243,21 +684,108 @@ impl OllamaClient {
 
         // Fallback: if the entire response looks like JSON
         let trimmed = fixed_response.trim();
-        if trimmed.starts_with('{') && trimmed.ends_with('}') {
-            if serde_json::from_str::<serde_json::Value>(trimmed).is_ok() {
-                return Ok(trimmed.to_string());
-            }
+        if trimmed.starts_with('{')
+            && trimmed.ends_with('}')
+            && serde_json::from_str::<serde_json::Value>(trimmed).is_ok()
+        {
+            return Ok(trimmed.to_string());
         }
 
         Err(anyhow::anyhow!("No valid JSON found in Ollama response: {}", response))
     }
 
-    fn find_entity_position(&self, text: &str, entity_value: &str) -> Option<(usize, usize)> {
-        if let Some(start) = text.find(entity_value) {
-            Some((start, start + entity_value.len()))
-        } else {
-            None
+    /// Names of the models installed in Ollama.
+    pub async fn list_models(&self) -> Result<Vec<String>> {
+        let response = self.client
+            .get(format!("{}/api/tags", self.config.endpoint))
+            .send()
+            .await?
+            .error_for_status()?;
+        let tags: TagsResponse = response.json().await?;
+        Ok(tags.models.into_iter().map(|m| m.name).collect())
+    }
+
+    /// Downloads a model, logging progress as it streams in.
+    pub async fn pull_model(&self, model: &str) -> Result<()> {
+        info!("Pulling Ollama model '{}'", model);
+        let mut response = self.client
+            .post(format!("{}/api/pull", self.config.endpoint))
+            .json(&serde_json::json!({ "model": model, "stream": true }))
+            .timeout(PULL_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let mut buffer = Vec::new();
+        let mut last_status = String::new();
+        let mut last_percent = None;
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(newline) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=newline).collect();
+                let Ok(progress) = serde_json::from_slice::<PullProgress>(&line) else {
+                    continue;
+                };
+                if let Some(error) = progress.error {
+                    return Err(anyhow::anyhow!("Pulling model '{}' failed: {}", model, error));
+                }
+                let percent = match (progress.completed, progress.total) {
+                    (Some(done), Some(total)) if total > 0 => Some(done * 100 / total / 10 * 10),
+                    _ => None,
+                };
+                if progress.status != last_status || percent.is_some() && percent != last_percent {
+                    match percent {
+                        Some(percent) => info!("  {}: {}%", progress.status, percent),
+                        None => info!("  {}", progress.status),
+                    }
+                    last_status = progress.status;
+                    last_percent = percent;
+                }
+            }
         }
+
+        info!("Model '{}' is ready", model);
+        Ok(())
+    }
+
+    /// Fails with a clear message when a configured model is not installed,
+    /// or pulls it when `auto_pull` is set. An unreachable Ollama is only
+    /// logged: the circuit breaker and regex fallback handle that at runtime.
+    pub async fn ensure_models_available(&self) -> Result<()> {
+        if !self.config.enabled || self.config.provider.is_hosted() {
+            return Ok(());
+        }
+
+        let installed = match self.list_models().await {
+            Ok(installed) => installed,
+            Err(e) => {
+                warn!("Could not list Ollama models at {}: {}", self.config.endpoint, e);
+                return Ok(());
+            }
+        };
+
+        let mut required = vec![self.config.model.clone()];
+        if let Some(verifier) = self.config.verification.model.clone().filter(|_| self.config.verification.enabled) {
+            required.push(verifier);
+        }
+
+        for model in required {
+            if installed.iter().any(|name| model_matches(&model, name)) {
+                continue;
+            }
+            if self.config.auto_pull {
+                self.pull_model(&model).await?;
+            } else {
+                return Err(anyhow::anyhow!(
+                    "Ollama model '{}' is not installed at {} (installed: {}). Run `ollama pull {}` or set llm.auto_pull = true",
+                    model,
+                    self.config.endpoint,
+                    if installed.is_empty() { "none".to_string() } else { installed.join(", ") },
+                    model
+                ));
+            }
+        }
+        Ok(())
     }
 
     pub async fn health_check(&self) -> Result<bool> {
@@ -265,14 +793,26 @@ impl OllamaClient {
             return Ok(false);
         }
 
+        if !self.circuit_allows_request() {
+            debug!("LLM circuit is open, skipping health check");
+            return Ok(false);
+        }
+
         debug!("Performing Ollama health check");
         
-        let response = self.client
-            .get(&format!("{}/api/tags", self.config.endpoint))
+        let response = self.authorize(self.client.get(self.config.provider.models_url(&self.config.endpoint)))
             .send()
-            .await?;
+            .await;
+        let response = match response {
+            Ok(response) => response,
+            Err(e) => {
+                self.record_outcome(false);
+                return Err(e.into());
+            }
+        };
 
         let is_healthy = response.status().is_success();
+        self.record_outcome(is_healthy);
         
         if is_healthy {
             info!("Ollama health check passed");
@@ -294,6 +834,16 @@ mod tests {
             model: "llama3.2:3b".to_string(),
             timeout_seconds: 30,
             enabled: true,
+            circuit_breaker: CircuitBreakerConfig::default(),
+            concurrency: LlmConcurrencyConfig::default(),
+            batching: LlmBatchConfig::default(),
+            chunking: LlmChunkingConfig::default(),
+            verification: LlmVerificationConfig::default(),
+            output_format: LlmOutputFormat::default(),
+            auto_pull: false,
+            provider: LlmProvider::default(),
+            allow_remote: false,
+            api_key_env: None,
         }
     }
 
@@ -375,20 +925,97 @@ mod tests {
         assert!(client.extract_json_from_response(response5).is_err());
     }
 
+    #[test]
+    fn test_model_names_match_ollama_tags() {
+        assert!(model_matches("llama3.2", "llama3.2:latest"));
+        assert!(model_matches("llama3.2:3b", "llama3.2:3b"));
+        assert!(!model_matches("llama3.2:3b", "llama3.2:latest"));
+
+        let tags: TagsResponse = serde_json::from_str(r#"{"models": [{"name": "llama3.2:3b", "size": 2019393189}]}"#).unwrap();
+        assert_eq!(tags.models[0].name, "llama3.2:3b");
+    }
+
+    #[test]
+    fn test_structured_output_is_parsed_verbatim() {
+        let client = OllamaClient::new(create_test_config(), None).unwrap();
+
+        // Valid JSON is not subjected to the double-brace repair
+        let response = r#"{"entities": [{"type": "api_key", "value": "tok{{abc}}", "confidence": 0.9}]}"#;
+        assert_eq!(client.extract_json_from_response(response).unwrap(), response);
+
+        let request = OllamaRequest {
+            model: "m".to_string(),
+            prompt: "p".to_string(),
+            stream: false,
+            format: Some(entities_schema(true)),
+            options: OllamaOptions { temperature: 0.0, top_p: 0.1, max_tokens: 500 },
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["format"]["properties"]["entities"]["items"]["properties"]["item"]["type"], "integer");
+
+        let request = OllamaRequest { format: None, ..request };
+        assert!(serde_json::to_value(&request).unwrap().get("format").is_none());
+    }
+
     #[test]
     fn test_find_entity_position() {
-        let config = create_test_config();
-        let client = OllamaClient::new(config, None).unwrap();
-        
         let text = "Contact Sarah Johnson at sarah@company.com";
         
-        let pos = client.find_entity_position(text, "Sarah Johnson");
+        let pos = resolve_entity_span(text, "Sarah Johnson", 0, 0);
         assert_eq!(pos, Some((8, 21)));
         
-        let pos2 = client.find_entity_position(text, "Not Found");
+        let pos2 = resolve_entity_span(text, "Not Found", 0, 0);
         assert_eq!(pos2, None);
     }
 
+    #[test]
+    fn test_resolve_entity_span_accepts_char_offsets() {
+        let text = "Contactez François Müller à françois.müller@société.com";
+        let byte_start = text.find("François Müller").unwrap();
+        
+        // Character offsets, as most models count them
+        let char_start = text[..byte_start].chars().count();
+        let span = resolve_entity_span(text, "François Müller", char_start, char_start + 15);
+        assert_eq!(span, Some((byte_start, byte_start + "François Müller".len())));
+        
+        // Correct byte offsets are used as-is
+        let email_start = text.find("françois.müller@société.com").unwrap();
+        let email_end = email_start + "françois.müller@société.com".len();
+        assert_eq!(resolve_entity_span(text, "françois.müller@société.com", email_start, email_end), Some((email_start, email_end)));
+    }
+
+    #[test]
+    fn test_resolve_entity_span_never_slices_inside_a_char() {
+        let text = "José García à josé@españa.es";
+        
+        // 'é' is two bytes; offset 3 falls inside it
+        let span = resolve_entity_span(text, "José García", 3, 14).unwrap();
+        assert_eq!(&text[span.0..span.1], "José García");
+        
+        // Out-of-range offsets fall back to searching
+        assert_eq!(resolve_entity_span(text, "josé@españa.es", 100, 200).map(|(s, e)| &text[s..e]), Some("josé@españa.es"));
+    }
+
+    #[test]
+    fn test_resolve_entity_span_prefers_nearest_occurrence() {
+        let text = "Ana met Ana";
+        assert_eq!(resolve_entity_span(text, "Ana", 7, 9), Some((8, 11)));
+    }
+
+    #[test]
+    fn test_parse_llm_response_with_accented_text() {
+        let config = create_test_config();
+        let client = OllamaClient::new(config, None).unwrap();
+        
+        let original_text = "Écrire à José García";
+        let response = r#"Résultat: {"entities": [{"type": "person_name", "value": "José García", "start": 9, "end": 20, "confidence": 0.9}]}"#;
+        
+        let entities = client.parse_llm_response(response, original_text).unwrap();
+        
+        assert_eq!(entities.len(), 1);
+        assert_eq!(&original_text[entities[0].start..entities[0].end], "José García");
+    }
+
     #[test]
     fn test_parse_valid_llm_response() {
         let config = create_test_config();
@@ -422,6 +1049,108 @@ mod tests {
         assert!(entities.is_empty() || entities[0].original_value == "Sarah");
     }
 
+    #[tokio::test]
+    async fn test_circuit_opens_when_backend_unreachable() {
+        let mut config = create_test_config();
+        // Nothing listens on port 9 (discard), so connections fail fast
+        config.endpoint = "http://127.0.0.1:9".to_string();
+        config.circuit_breaker = CircuitBreakerConfig { failure_threshold: 2, cooldown_seconds: 60 };
+        let client = OllamaClient::new(config, None).unwrap();
+        let shared = client.clone();
+        
+        assert!(client.health_check().await.is_err());
+        assert_eq!(client.circuit_state(), CircuitState::Closed);
+        assert!(client.extract_entities("Contact Sarah").await.is_err());
+        assert_eq!(shared.circuit_state(), CircuitState::Open);
+        
+        // While open, no network round trip is attempted
+        assert!(!shared.health_check().await.unwrap());
+        assert!(shared.extract_entities("Contact Sarah").await.unwrap_err().to_string().contains("circuit is open"));
+    }
+
+    /// An Ollama endpoint that finds nothing and keeps the prompts it was sent.
+    async fn spawn_recording_llm() -> (String, Arc<Mutex<Vec<String>>>) {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let prompts = Arc::new(Mutex::new(Vec::new()));
+        let recorded = prompts.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let prompts = prompts.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    while let Ok(read @ 1..) = socket.read(&mut chunk).await {
+                        request.extend_from_slice(&chunk[..read]);
+                        let text = String::from_utf8_lossy(&request).to_string();
+                        let Some(header_end) = text.find("\r\n\r\n") else { continue };
+                        let length = text[..header_end].lines()
+                            .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|n| n.trim().parse::<usize>().unwrap()))
+                            .unwrap_or(0);
+                        if request.len() < header_end + 4 + length {
+                            continue;
+                        }
+                        let body: serde_json::Value = serde_json::from_slice(&request[header_end + 4..]).unwrap();
+                        prompts.lock().unwrap().push(body["prompt"].as_str().unwrap_or_default().to_string());
+                        request.clear();
+                        let reply = serde_json::json!({ "response": r#"{"entities":[]}"#, "done": true }).to_string();
+                        let response = format!("HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}", reply.len(), reply);
+                        if socket.write_all(response.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (endpoint, recorded)
+    }
+
+    #[tokio::test]
+    async fn test_batches_are_split_by_the_language_of_each_text() {
+        let (endpoint, prompts) = spawn_recording_llm().await;
+        let language = LanguageConfig { enabled: true, ..LanguageConfig::default() };
+        let client = OllamaClient::new(OllamaConfig { endpoint, ..create_test_config() }, None).unwrap()
+            .with_language_prompts(&language).unwrap();
+        let texts = [
+            "Bonjour, je vous écris au sujet de la facture que nous avons reçue la semaine dernière.",
+            "Hello, I am writing about the invoice we received from your office last week.",
+            "Merci de rappeler Marie au sujet de la réunion prévue pour le mois prochain à Lyon.",
+        ];
+
+        assert_eq!(client.extract_entities_batch(&texts).await.unwrap().len(), 3);
+        let prompts = prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 2);
+        let french = prompts.iter().find(|prompt| prompt.contains("français")).unwrap();
+        assert!(french.contains("la facture") && french.contains("Marie") && !french.contains("Hello"));
+        let english = prompts.iter().find(|prompt| !prompt.contains("français")).unwrap();
+        assert!(english.contains("Hello") && english.contains("Built-in PII Detection Prompt\n"));
+    }
+
+    #[test]
+    fn test_parse_batch_response_fans_out_by_item() {
+        let client = OllamaClient::new(create_test_config(), None).unwrap();
+        let texts = ["Contact Sarah Johnson", "no pii here", "mail sarah@company.com or Sarah Johnson"];
+        
+        assert_eq!(format_batch(&texts[..2]), "[[1]] Contact Sarah Johnson\n[[2]] no pii here");
+        
+        let response = r#"{"entities": [
+            {"type": "person_name", "value": "Sarah Johnson", "item": 1, "confidence": 0.9},
+            {"type": "email", "value": "sarah@company.com", "item": 2, "confidence": 0.95},
+            {"type": "person_name", "value": "Sarah Johnson", "confidence": 0.9}
+        ]}"#;
+        let results = client.parse_batch_response(response, &texts).unwrap();
+        
+        assert_eq!(results.len(), 3);
+        // Item 1 is reported twice (explicitly and by search), item 3 gets both
+        assert!(results[0].iter().all(|e| e.original_value == "Sarah Johnson" && e.start == 8));
+        assert!(results[1].is_empty());
+        // Wrong item number: the email is attributed to the text that contains it
+        let third: Vec<_> = results[2].iter().map(|e| e.original_value.as_str()).collect();
+        assert_eq!(third, vec!["sarah@company.com", "Sarah Johnson"]);
+    }
+
     #[test]
     fn test_disabled_client() {
         let mut config = create_test_config();
@@ -0,0 +1,324 @@
+//! Backlog of texts that missed LLM detection during an outage
+//!
+//! While the LLM backend is down (or its circuit is open, or its queue is
+//! full) strings are anonymized with regex detection only. With the outage
+//! queue enabled, each such string is kept in an encrypted backlog so that
+//! `mcp-server-conceal backfill` can run it through the LLM once the backend
+//! is back. The backfill fills the LLM cache and records, per entity type, what
+//! the LLM found that regex detection did not, for post-incident review.
+//!
+//! Texts are encrypted with AES-256-GCM and deduplicated by a keyed hash, both
+//! derived from the mapping passphrase. The backlog lives in its own database
+//! because the mapping database is recreated on every proxy start.
+
+use crate::config::{Config, DetectedEntity, DetectionMode};
+use crate::crypto;
+use crate::detection::RegexDetectionEngine;
+use crate::mapping::MappingStore;
+use crate::ollama::{OllamaClient, OllamaConfig};
+use crate::secrets::{self, Passphrase};
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{debug, info, warn};
+
+const ENCRYPTION_KEY_CONTEXT: &[u8] = b"mcp-server-conceal llm backlog encryption v1";
+const HASH_KEY_CONTEXT: &[u8] = b"mcp-server-conceal llm backlog hashing v1";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutageQueueConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Backlog database; relative paths live in the data directory
+    #[serde(default = "default_queue_path")]
+    pub path: PathBuf,
+    /// Texts beyond this many are not queued
+    #[serde(default = "default_max_entries")]
+    pub max_entries: usize,
+}
+
+impl Default for OutageQueueConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: default_queue_path(),
+            max_entries: default_max_entries(),
+        }
+    }
+}
+
+fn default_queue_path() -> PathBuf {
+    PathBuf::from("llm_backlog.db")
+}
+
+fn default_max_entries() -> usize {
+    10_000
+}
+
+/// A decrypted backlog entry.
+#[derive(Debug, Clone)]
+pub struct QueuedText {
+    pub id: i64,
+    pub text: String,
+    /// Model that was configured when the text was queued
+    pub model: String,
+    pub queued_at: u64,
+}
+
+pub struct OutageQueue {
+    conn: Connection,
+    path: PathBuf,
+    encryption_key: [u8; 32],
+    hash_key: [u8; 32],
+    max_entries: usize,
+}
+
+impl OutageQueue {
+    pub fn open(config: &OutageQueueConfig, passphrase: &Passphrase) -> Result<Self> {
+        let conn = if config.path == Path::new(":memory:") {
+            Connection::open_in_memory()?
+        } else {
+            if let Some(parent) = config.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            Connection::open(&config.path)
+                .map_err(|e| anyhow::anyhow!("Failed to open LLM backlog {}: {}", config.path.display(), e))?
+        };
+        // Both proxy directions write to the same file
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS llm_backlog (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                text_hash TEXT NOT NULL UNIQUE,
+                nonce BLOB NOT NULL,
+                ciphertext BLOB NOT NULL,
+                model TEXT NOT NULL,
+                queued_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        Ok(Self {
+            conn,
+            path: config.path.clone(),
+            encryption_key: crypto::derive_key(passphrase, ENCRYPTION_KEY_CONTEXT),
+            hash_key: crypto::derive_key(passphrase, HASH_KEY_CONTEXT),
+            max_entries: config.max_entries,
+        })
+    }
+
+    /// Opens the configured queue, if it is enabled and LLM detection is in use.
+    /// The mapping passphrase is required.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        let Some(queue_config) = config.llm.as_ref()
+            .filter(|llm| llm.enabled && llm.outage_queue.enabled)
+            .map(|llm| &llm.outage_queue)
+        else {
+            return Ok(None);
+        };
+        if matches!(config.detection.mode, DetectionMode::Regex) {
+            return Ok(None);
+        }
+
+        let passphrase = secrets::require_passphrase(&config.mapping)
+            .map_err(|e| anyhow::anyhow!("The LLM outage queue needs the mapping passphrase: {}", e))?;
+        Self::open(queue_config, &passphrase).map(Some)
+    }
+
+    /// Another connection to the same backlog, for use from a second task.
+    pub fn try_clone(&self) -> Result<Self> {
+        if self.path == Path::new(":memory:") {
+            return Err(anyhow::anyhow!("An in-memory LLM backlog cannot be shared"));
+        }
+        let conn = Connection::open(&self.path)?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        Ok(Self {
+            conn,
+            path: self.path.clone(),
+            encryption_key: self.encryption_key,
+            hash_key: self.hash_key,
+            max_entries: self.max_entries,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Queues a text for later LLM detection. Returns false if it was already
+    /// queued or the queue is full.
+    pub fn enqueue(&mut self, text: &str, model: &str) -> Result<bool> {
+        if self.len()? >= self.max_entries {
+            debug!("LLM backlog is full ({} entries), not queueing text", self.max_entries);
+            return Ok(false);
+        }
+
+        let text_hash = crypto::keyed_hash(&self.hash_key, text.as_bytes());
+        let (nonce, ciphertext) = crypto::encrypt(&self.encryption_key, text.as_bytes())?;
+        let inserted = self.conn.execute(
+            "INSERT OR IGNORE INTO llm_backlog (text_hash, nonce, ciphertext, model, queued_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![text_hash, nonce, ciphertext, model, now_secs()],
+        )?;
+        Ok(inserted > 0)
+    }
+
+    pub fn len(&self) -> Result<usize> {
+        let count: i64 = self.conn.query_row("SELECT COUNT(*) FROM llm_backlog", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Oldest entries first, decrypted.
+    pub fn pending(&self, limit: usize) -> Result<Vec<QueuedText>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, nonce, ciphertext, model, queued_at FROM llm_backlog ORDER BY id LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit as i64], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, Vec<u8>>(1)?,
+                row.get::<_, Vec<u8>>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, u64>(4)?,
+            ))
+        })?;
+
+        let mut entries = Vec::new();
+        for row in rows {
+            let (id, nonce, ciphertext, model, queued_at) = row?;
+            let text = crypto::decrypt(&self.encryption_key, &nonce, &ciphertext)?;
+            entries.push(QueuedText {
+                id,
+                text: String::from_utf8(text)?,
+                model,
+                queued_at,
+            });
+        }
+        Ok(entries)
+    }
+
+    pub fn remove(&mut self, id: i64) -> Result<()> {
+        self.conn.execute("DELETE FROM llm_backlog WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+}
+
+/// Outcome of a backfill run. Holds counts only, never the texts or values.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct BackfillReport {
+    pub processed: usize,
+    pub remaining: usize,
+    pub llm_entities: usize,
+    /// LLM findings that regex detection did not cover, by entity type
+    pub missed_by_regex: BTreeMap<String, usize>,
+}
+
+impl BackfillReport {
+    pub fn missed_total(&self) -> usize {
+        self.missed_by_regex.values().sum()
+    }
+}
+
+/// Runs up to `limit` queued texts through the LLM, caches the results in the
+/// mapping database and removes them from the backlog. Stops early, leaving
+/// the rest queued, if the LLM fails again.
+pub async fn backfill(config: &Config, ollama_config: OllamaConfig, limit: usize) -> Result<BackfillReport> {
+    let mut queue = OutageQueue::from_config(config)?
+        .ok_or_else(|| anyhow::anyhow!("The LLM outage queue is not enabled in this configuration"))?;
+    let prompt_template = config.llm.as_ref().and_then(|llm| llm.prompt_template.as_ref());
+    let model_name = ollama_config.model.clone();
+    let client = OllamaClient::new(ollama_config, prompt_template)?.with_language_prompts(&config.language)?;
+    if !client.health_check().await.unwrap_or(false) {
+        return Err(anyhow::anyhow!("LLM backend is still unavailable; try again once it has recovered"));
+    }
+
+    let detection_engine = RegexDetectionEngine::new(&config.detection)?;
+    let mut mapping_store = MappingStore::new(config.mapping.clone())?;
+    let mut report = BackfillReport::default();
+
+    for entry in queue.pending(limit)? {
+        let llm_entities = match client.extract_entities(&entry.text).await {
+            Ok(entities) => entities,
+            Err(e) => {
+                warn!("LLM detection failed during backfill, stopping: {}", e);
+                break;
+            }
+        };
+
+        let regex_entities = detection_engine.detect_in_text(&entry.text);
+        for entity in &llm_entities {
+            if !is_covered(entity, &regex_entities) {
+                *report.missed_by_regex.entry(entity.entity_type.clone()).or_default() += 1;
+            }
+        }
+        report.llm_entities += llm_entities.len();
+
+        if let Err(e) = mapping_store.store_llm_cache(&entry.text, &llm_entities, &model_name) {
+            warn!("Failed to cache backfilled LLM result: {}", e);
+        }
+        queue.remove(entry.id)?;
+        report.processed += 1;
+    }
+
+    report.remaining = queue.len()?;
+    info!("Backfilled {} queued text(s), {} remaining", report.processed, report.remaining);
+    Ok(report)
+}
+
+fn is_covered(entity: &DetectedEntity, regex_entities: &[DetectedEntity]) -> bool {
+    regex_entities.iter().any(|r| r.start < entity.end && entity.start < r.end)
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_queue(max_entries: usize) -> OutageQueue {
+        let config = OutageQueueConfig {
+            enabled: true,
+            path: PathBuf::from(":memory:"),
+            max_entries,
+        };
+        OutageQueue::open(&config, &Passphrase::new("test passphrase")).unwrap()
+    }
+
+    #[test]
+    fn test_enqueue_dedupes_and_respects_limit() {
+        let mut queue = memory_queue(2);
+
+        assert!(queue.enqueue("Call Maria Keller", "llama3.2:3b").unwrap());
+        assert!(!queue.enqueue("Call Maria Keller", "llama3.2:3b").unwrap());
+        assert!(queue.enqueue("Owner: Dr. Okafor", "llama3.2:3b").unwrap());
+        assert!(!queue.enqueue("a third text", "llama3.2:3b").unwrap());
+
+        let pending = queue.pending(10).unwrap();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0].text, "Call Maria Keller");
+
+        queue.remove(pending[0].id).unwrap();
+        assert_eq!(queue.len().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_texts_are_stored_encrypted() {
+        let mut queue = memory_queue(10);
+        queue.enqueue("jane.roe@acme.com", "llama3.2:3b").unwrap();
+
+        let (hash, ciphertext): (String, Vec<u8>) = queue.conn
+            .query_row("SELECT text_hash, ciphertext FROM llm_backlog", [], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap();
+        assert!(!hash.contains("jane"));
+        assert!(!String::from_utf8_lossy(&ciphertext).contains("jane.roe"));
+    }
+}
@@ -0,0 +1,147 @@
+//! Overlap resolution for detected entity spans
+//!
+//! Regex patterns and the LLM frequently report overlapping spans for the same
+//! text (a name inside an email address, a phone number that also looks like an
+//! SSN). Replacing both garbles the output, so before replacement every set of
+//! overlapping candidates is reduced to a single winner using these rules, in order:
+//!
+//! 1. configured type precedence (types listed earlier win, unlisted types rank last)
+//! 2. longer span wins
+//! 3. higher confidence wins
+//! 4. earlier start wins (keeps the result deterministic)
+
+use crate::config::{base_entity_type, DetectedEntity, OverlapConfig};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use tracing::debug;
+
+#[derive(Debug, Clone, Default)]
+pub struct OverlapResolver {
+    precedence: HashMap<String, usize>,
+}
+
+impl OverlapResolver {
+    pub fn new(config: &OverlapConfig) -> Self {
+        let precedence = config.type_precedence.iter()
+            .enumerate()
+            .map(|(rank, entity_type)| (entity_type.clone(), rank))
+            .collect();
+        Self { precedence }
+    }
+
+    /// Returns a non-overlapping subset of `entities`, sorted by start offset.
+    pub fn resolve(&self, mut entities: Vec<DetectedEntity>) -> Vec<DetectedEntity> {
+        if entities.len() < 2 {
+            return entities;
+        }
+
+        entities.sort_by(|a, b| self.compare_priority(a, b));
+
+        let mut accepted: Vec<DetectedEntity> = Vec::with_capacity(entities.len());
+        for candidate in entities {
+            match accepted.iter().find(|kept| spans_overlap(kept, &candidate)) {
+                Some(winner) => debug!(
+                    "Dropping '{}' at {}-{} in favour of overlapping '{}' at {}-{}",
+                    candidate.entity_type, candidate.start, candidate.end,
+                    winner.entity_type, winner.start, winner.end
+                ),
+                None => accepted.push(candidate),
+            }
+        }
+
+        accepted.sort_by_key(|e| e.start);
+        accepted
+    }
+
+    fn rank(&self, entity_type: &str) -> usize {
+        let base_type = base_entity_type(entity_type);
+        self.precedence.get(base_type).copied().unwrap_or(usize::MAX)
+    }
+
+    /// Orders entities from most to least preferred.
+    fn compare_priority(&self, a: &DetectedEntity, b: &DetectedEntity) -> Ordering {
+        self.rank(&a.entity_type).cmp(&self.rank(&b.entity_type))
+            .then_with(|| (b.end - b.start).cmp(&(a.end - a.start)))
+            .then_with(|| b.confidence.partial_cmp(&a.confidence).unwrap_or(Ordering::Equal))
+            .then_with(|| a.start.cmp(&b.start))
+    }
+}
+
+fn spans_overlap(a: &DetectedEntity, b: &DetectedEntity) -> bool {
+    a.start < b.end && b.start < a.end
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entity(entity_type: &str, value: &str, start: usize, confidence: f64) -> DetectedEntity {
+        DetectedEntity {
+            entity_type: entity_type.to_string(),
+            original_value: value.to_string(),
+            start,
+            end: start + value.len(),
+            confidence,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_longer_span_wins() {
+        let resolver = OverlapResolver::default();
+        let entities = vec![
+            entity("person_name", "john.smith", 8, 0.95),
+            entity("email", "john.smith@acme.com", 8, 0.9),
+        ];
+
+        let resolved = resolver.resolve(entities);
+
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].entity_type, "email");
+    }
+
+    #[test]
+    fn test_higher_confidence_breaks_length_ties() {
+        let resolver = OverlapResolver::default();
+        let entities = vec![
+            entity("phone", "123-45-6789", 0, 0.6),
+            entity("ssn", "123-45-6789", 0, 0.95),
+        ];
+
+        let resolved = resolver.resolve(entities);
+
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].entity_type, "ssn");
+    }
+
+    #[test]
+    fn test_type_precedence_overrides_length() {
+        let resolver = OverlapResolver::new(&OverlapConfig {
+            type_precedence: vec!["ssn".to_string()],
+        });
+        let entities = vec![
+            entity("account_number", "ID 123-45-6789", 0, 0.9),
+            entity("ssn@customer.id", "123-45-6789", 3, 0.8),
+        ];
+
+        let resolved = resolver.resolve(entities);
+
+        assert_eq!(resolved.len(), 1);
+        assert_eq!(resolved[0].entity_type, "ssn@customer.id");
+    }
+
+    #[test]
+    fn test_disjoint_spans_are_kept_in_order() {
+        let resolver = OverlapResolver::default();
+        let entities = vec![
+            entity("phone", "555-123-4567", 30, 0.9),
+            entity("email", "a@b.com", 0, 0.95),
+            entity("name", "Bob", 7, 0.8),
+        ];
+
+        let resolved = resolver.resolve(entities);
+
+        assert_eq!(resolved.len(), 3);
+        assert_eq!(resolved.iter().map(|e| e.start).collect::<Vec<_>>(), vec![0, 7, 30]);
+    }
+}
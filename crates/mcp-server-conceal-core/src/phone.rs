@@ -0,0 +1,159 @@
+//! International phone number detection and same-region fakes
+//!
+//! The `phone` pattern only knows North American numbers. With
+//! `[detection.phone]` enabled, anything written like a phone number, in
+//! national or international format ("+44 20 7946 0958", "06 12 34 56 78",
+//! "+1 (415) 555-2671 ext. 12"), is parsed and validated against the
+//! libphonenumber numbering plans; numbers without a country code are read as
+//! numbers of `default_region`. Valid numbers are reported as `phone` with
+//! their region, and replace overlapping matches of the `phone` pattern.
+//! The faker then generates a valid number of the same region and type,
+//! written in the same national or international style.
+
+use crate::config::DetectedEntity;
+use anyhow::Result;
+use phonenumber::metadata::DATABASE;
+use phonenumber::{country, Mode, PhoneNumber, Type};
+use rand::Rng;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+pub const PHONE_TYPE: &str = "phone";
+/// Confidence of a number the numbering plan of its region accepts.
+pub const PHONE_CONFIDENCE: f64 = 0.95;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PhoneConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Region (ISO 3166 code) of numbers written without a country code
+    #[serde(default = "default_region")]
+    pub default_region: String,
+}
+
+impl Default for PhoneConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            default_region: default_region(),
+        }
+    }
+}
+
+fn default_region() -> String {
+    "US".to_string()
+}
+
+#[derive(Debug, Clone)]
+pub struct PhoneDetector {
+    candidates: Regex,
+    default_region: country::Id,
+}
+
+impl PhoneDetector {
+    pub fn new(config: &PhoneConfig) -> Result<Self> {
+        let default_region = config.default_region.to_ascii_uppercase().parse()
+            .map_err(|_| anyhow::anyhow!("Unknown phone region '{}'", config.default_region))?;
+        // Digits with the separators people write, then an optional extension
+        let candidates = Regex::new(
+            r"(?:\+|\(|\b)\d[\d \t().\-/]{5,22}\d(?:[ \t]*(?:ext\.?|x|#)[ \t]*\d{1,6})?",
+        )?;
+        Ok(Self { candidates, default_region })
+    }
+
+    pub fn detect(&self, text: &str) -> Vec<DetectedEntity> {
+        self.candidates.find_iter(text)
+            .filter(|candidate| !text[candidate.end()..].starts_with(|c: char| c.is_alphanumeric()))
+            .filter_map(|candidate| {
+                let digits = candidate.as_str().chars().filter(char::is_ascii_digit).count();
+                if !(7..=21).contains(&digits) {
+                    return None;
+                }
+                let number = phonenumber::parse(Some(self.default_region), candidate.as_str()).ok()?;
+                if !phonenumber::is_valid(&number) {
+                    return None;
+                }
+                Some(DetectedEntity {
+                    entity_type: PHONE_TYPE.to_string(),
+                    original_value: candidate.as_str().to_string(),
+                    start: candidate.start(),
+                    end: candidate.end(),
+                    confidence: PHONE_CONFIDENCE,
+                    region: region_of(&number),
+                })
+            })
+            .collect()
+    }
+}
+
+fn region_of(number: &PhoneNumber) -> Option<String> {
+    number.country().id().map(|id| id.as_ref().to_string())
+}
+
+/// A valid number of `region`, of the same type as `original` (mobile,
+/// landline, ...) and written the same way: internationally when `original`
+/// starts with `+` or `00`, nationally otherwise. `None` when the region has
+/// no example number to build from.
+pub fn fake_number(region: &str, original: &str, rng: &mut impl Rng) -> Option<String> {
+    let id: country::Id = region.parse().ok()?;
+    let metadata = DATABASE.by_id(region)?;
+    let kind = phonenumber::parse(Some(id), original).map(|n| n.number_type(&DATABASE)).unwrap_or(Type::Mobile);
+    let descriptors = metadata.descriptors();
+    let example = [descriptors.get(kind), descriptors.mobile(), descriptors.fixed_line()].into_iter()
+        .flatten()
+        .find_map(|descriptor| descriptor.example())?;
+
+    let international = original.trim_start().starts_with('+') || original.trim_start().starts_with("00");
+    let mode = if international { Mode::International } else { Mode::National };
+    // The example's leading digits pick the number range; the rest is random
+    let keep = (example.len() / 2).max(1);
+    for _ in 0..20 {
+        let national: String = example[..keep].chars()
+            .chain((keep..example.len()).map(|_| char::from(b'0' + rng.gen_range(0..10))))
+            .collect();
+        if let Ok(number) = phonenumber::parse(Some(id), &national) {
+            if phonenumber::is_valid(&number) {
+                return Some(number.format().mode(mode).to_string());
+            }
+        }
+    }
+    let number = phonenumber::parse(Some(id), example).ok()?;
+    Some(number.format().mode(mode).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn test_international_numbers_are_found_with_their_region() {
+        let detector = PhoneDetector::new(&PhoneConfig { enabled: true, default_region: "FR".to_string() }).unwrap();
+        let text = "London +44 20 7946 0958, Paris 06 12 34 56 78, SF +1 (415) 555-2671 ext. 12, order 2024-01-15.";
+        let found: Vec<_> = detector.detect(text).into_iter()
+            .map(|e| (e.original_value, e.region.unwrap()))
+            .collect();
+        assert_eq!(found, vec![
+            ("+44 20 7946 0958".to_string(), "GB".to_string()),
+            ("06 12 34 56 78".to_string(), "FR".to_string()),
+            ("+1 (415) 555-2671 ext. 12".to_string(), "US".to_string()),
+        ]);
+        assert!(PhoneDetector::new(&PhoneConfig { enabled: true, default_region: "XX".to_string() }).is_err());
+    }
+
+    #[test]
+    fn test_fakes_stay_in_region_and_style() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let fake = fake_number("GB", "+44 7400 123456", &mut rng).unwrap();
+        assert!(fake.starts_with("+44 7"), "{}", fake);
+        assert_ne!(fake, "+44 7400 123456");
+        let number = phonenumber::parse(None, &fake).unwrap();
+        assert!(phonenumber::is_valid(&number));
+
+        let fake = fake_number("FR", "06 12 34 56 78", &mut rng).unwrap();
+        assert!(fake.starts_with('0'), "{}", fake);
+        assert!(phonenumber::is_valid(&phonenumber::parse(Some(country::Id::FR), &fake).unwrap()));
+        assert!(fake_number("XX", "123", &mut rng).is_none());
+    }
+}
@@ -0,0 +1,3472 @@
+//! Per-direction message processing
+//!
+//! A [`Pipeline`] owns the detection, faking and mapping state for one direction
+//! of MCP traffic, and [`bridge`] is the line-forwarding loop the proxy runs
+//! over the child's stdio. With the `bridge` cargo feature both are public, so
+//! other transports (SSH tunnels, QUIC streams, test harnesses) can reuse the
+//! exact processing the proxy applies.
+
+use anyhow::Result;
+use base64::Engine;
+use base64::engine::general_purpose::{self, GeneralPurpose, STANDARD as BASE64};
+use serde_json::Value;
+use std::borrow::Cow;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::future::Future;
+use std::ops::Range;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::task::JoinSet;
+use tokio::time::Instant;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tracing::{debug, info, info_span, warn, Instrument, Span};
+
+use crate::admin::RuntimeControl;
+use crate::annotate;
+use crate::approval::{ApprovalDecision, ApprovalGate, PendingApproval, Resolution};
+use crate::audit::AuditLog;
+use crate::bypass::BypassRegistry;
+use crate::canary::Canary;
+use crate::chaos;
+use crate::circuit_breaker::CircuitState;
+use crate::config::{base_entity_type, AnonymizationStrategy, Base64Config, Config, DetectedEntity, AnonymizedEntity, DetectionMode, Framing, NestedJsonConfig, OnError, OversizedLine, StitchingConfig};
+use crate::debug_diff::DebugDiff;
+use crate::degradation::{Degradation, DegradationReport};
+use crate::discovery::Discovery;
+use crate::detection::RegexDetectionEngine;
+use crate::i18n::Catalog;
+use crate::identity::{self, Persona};
+use crate::faker::{self, FakerEngine, REDACTED_TOKEN};
+use crate::json_limits::JsonLimits;
+use crate::json_path::{JsonPathSegment, ScalarRules};
+use crate::leak_check::{LeakChecker, LeakKind};
+use crate::mapping::MappingStore;
+use crate::ollama::{OllamaClient, OllamaConfig};
+use crate::outage_queue::OutageQueue;
+use crate::overlap::OverlapResolver;
+use crate::protocol::{self, RequestInfo, RequestTracker};
+use crate::schema_hints::{SchemaHints, ToolHints};
+use crate::quarantine::QuarantineStore;
+use crate::session::SessionScope;
+use crate::stitching::{self, StitchedText};
+
+/// Entity type under which the `ip` strategy stores real-to-fake subnets.
+const IP_SUBNET_ENTITY_TYPE: &str = "ip_subnet";
+/// Entity type under which mapped email domains are stored.
+const EMAIL_DOMAIN_ENTITY_TYPE: &str = "email_domain";
+/// Entity type under which the `date_shift` offset is stored.
+const DATE_SHIFT_ENTITY_TYPE: &str = "date_shift";
+/// JSON-RPC error code of a response withheld by an operator.
+const WITHHELD_ERROR_CODE: i64 = -32001;
+/// Keys whose values `on_error = "redact_all"` keeps, so clients can still
+/// tell content blocks apart.
+const REDACT_ALL_KEPT_KEYS: &[&str] = &["type", "mimeType"];
+
+/// Which way traffic flows through a pipeline, relative to the MCP server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Direction {
+    /// Client to server
+    Request,
+    /// Server to client
+    Response,
+}
+
+impl Direction {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Direction::Request => "request",
+            Direction::Response => "response",
+        }
+    }
+
+    pub fn opposite(&self) -> Direction {
+        match self {
+            Direction::Request => Direction::Response,
+            Direction::Response => Direction::Request,
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Detection, faking and mapping state used to process one direction of traffic.
+pub struct Pipeline {
+    pub(crate) detection_engine: RegexDetectionEngine,
+    pub(crate) ollama_client: OllamaClient,
+    pub(crate) faker_engine: FakerEngine,
+    pub(crate) mapping_store: MappingStore,
+    pub(crate) overlap_resolver: OverlapResolver,
+    pub(crate) stitching: StitchingConfig,
+    pub(crate) nested_json: NestedJsonConfig,
+    /// Documents-in-strings enclosing the value being processed
+    pub(crate) nested_depth: usize,
+    pub(crate) base64: Base64Config,
+    pub(crate) model_name: String,
+    pub(crate) detection_mode: DetectionMode,
+    /// Shared between directions so responses can be attributed to their request
+    pub(crate) request_tracker: RequestTracker,
+    /// Key hints learned from `tools/list`; `None` when disabled
+    pub(crate) schema_hints: Option<SchemaHints>,
+    /// Schema hints of the tool whose result is being processed
+    pub(crate) tool_hints: Option<Arc<ToolHints>>,
+    pub(crate) audit_log: AuditLog,
+    pub(crate) bypass_registry: Option<BypassRegistry>,
+    /// Texts that missed LLM detection, kept for a later backfill
+    pub(crate) outage_queue: Option<OutageQueue>,
+    /// Encrypted samples of messages with critical detections, for QA review
+    pub(crate) quarantine: Option<QuarantineStore>,
+    /// Entity types (base type, lower case) replaced in the current message
+    pub(crate) message_types: BTreeSet<String>,
+    /// Fake person of the one person the current message names, with identity linking
+    pub(crate) message_persona: Option<Persona>,
+    /// Holds messages with first-seen critical entities for an operator
+    pub(crate) approval: Option<ApprovalGate>,
+    /// Type and original of each critical value first mapped in the current message
+    pub(crate) first_seen_critical: Vec<(String, String)>,
+    /// Looks for originals left in processed messages; `None` when disabled
+    pub(crate) leak_checker: Option<LeakChecker>,
+    /// Values replaced in the current message, kept for the leak check
+    pub(crate) message_replacements: Vec<AnonymizedEntity>,
+    /// Fakes detected again in the current message
+    pub(crate) message_leaks: BTreeSet<(LeakKind, String)>,
+    /// Canary of the proxy session, added to changed responses; `None` outside the proxy
+    pub(crate) canary: Option<Canary>,
+    /// Masked per-message diffs, when enabled
+    pub(crate) debug_diff: Option<DebugDiff>,
+    /// Wrap replacements in review markers instead of writing bare fakes
+    pub(crate) annotate: bool,
+    /// Sampled checks for values the rules missed
+    pub(crate) discovery: Option<Discovery>,
+    /// Shared between directions for the end-of-session summary
+    pub(crate) degradation: DegradationReport,
+    /// Fallbacks taken while processing the current message
+    pub(crate) message_degradations: BTreeSet<Degradation>,
+    /// LLM cache entries of the current message's strings, looked up in one
+    /// batch; `None` marks a text known to be uncached
+    pub(crate) llm_cache_snapshot: HashMap<String, Option<Vec<DetectedEntity>>>,
+    /// Non-string fields selected for scanning
+    pub(crate) scalar_rules: ScalarRules,
+    /// Path of the value being processed, tracked only when scalar rules exist
+    pub(crate) json_path: Vec<JsonPathSegment>,
+    /// Scratch space of `apply_replacements`, kept to avoid an allocation per string
+    pub(crate) replacement_spans: Vec<ReplacementSpan>,
+    /// Longest one message may take before it is forwarded unprocessed
+    pub(crate) message_timeout: Option<Duration>,
+    /// Largest message shape processed; see `json_limits`
+    pub(crate) json_limits: JsonLimits,
+    /// Time LLM detection may add to one message before regex takes over
+    pub(crate) latency_budget: Option<Duration>,
+    /// When the current message's latency budget runs out
+    pub(crate) llm_deadline: Option<Instant>,
+    /// Whether a message that failed processing is forwarded, dropped or redacted
+    pub(crate) on_error: OnError,
+    /// Error message of a response dropped under `on_error = "block"`
+    pub(crate) error_withheld_message: String,
+    /// Error message answering a request dropped under `on_error = "block"`
+    pub(crate) error_withheld_request_message: String,
+    /// Longest line `bridge` buffers, and what it does with longer ones
+    pub(crate) max_line_bytes: usize,
+    pub(crate) on_oversized_line: OversizedLine,
+    /// Framing `bridge` reads; `Auto` decides from the first bytes
+    pub(crate) framing: Framing,
+    /// Framing `bridge` writes; `None` keeps the one read
+    pub(crate) output_framing: Option<Framing>,
+    /// Mapping namespace per client session, shared with the opposite direction
+    pub(crate) session_scope: Option<SessionScope>,
+    /// Session `mapping_store` belongs to
+    pub(crate) session_generation: u64,
+    /// Commands of the admin channel; `None` outside the proxy
+    pub(crate) control: Option<RuntimeControl>,
+    /// Store and configuration generations of `control` last applied
+    pub(crate) control_seen: (u64, u64),
+    /// Replies to the peer this direction reads from, such as the error for a
+    /// withheld request; the opposite direction's `bridge` writes them
+    pub(crate) replies: Option<mpsc::UnboundedSender<String>>,
+    /// Replies of the opposite direction, written by `bridge` to the peer
+    /// this direction writes to
+    pub(crate) peer_replies: Option<mpsc::UnboundedReceiver<String>>,
+    /// Messages `bridge` holds for an operator while it goes on forwarding;
+    /// `None` outside it, where processing waits for the decision
+    pub(crate) held: Option<Vec<HeldMessage>>,
+    pub(crate) direction: Direction,
+}
+
+/// Header of a `Content-Length` frame; its name is case-insensitive.
+const CONTENT_LENGTH_HEADER: &str = "content-length";
+
+/// What is left of a message `bridge` stopped buffering.
+#[derive(Debug, Clone, Copy)]
+enum Remainder {
+    /// The rest of the line, up to and including its newline
+    Line,
+    /// This many more bytes of a `Content-Length` body
+    Bytes(u64),
+}
+
+/// Forwards messages from `reader` to `writer`, processing each one through
+/// `pipeline`. Returns when `reader` reaches EOF.
+///
+/// Messages that fail processing are forwarded unchanged unless the pipeline's
+/// `on_error` policy says otherwise, as the proxy does; read and write errors
+/// end the loop. Messages longer than the pipeline's `max_line_bytes` are never
+/// buffered whole; `on_oversized_line` decides what becomes of them. Messages
+/// are newline-delimited or `Content-Length` framed, as the pipeline's
+/// `framing` says or, with `auto`, as the first bytes of `reader` show.
+pub async fn bridge<R, W>(reader: R, writer: &mut W, pipeline: &mut Pipeline) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    pipeline.held = Some(Vec::new());
+    let result = bridge_messages(reader, writer, pipeline).await;
+    pipeline.held = None;
+    result
+}
+
+async fn bridge_messages<R, W>(reader: R, writer: &mut W, pipeline: &mut Pipeline) -> Result<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut reader = BufReader::new(reader);
+    let mut buffer = Vec::new();
+    let mut output = String::new();
+    let mut framing = pipeline.framing;
+    let mut peer_replies = pipeline.peer_replies.take();
+    // Messages waiting for an operator, decided while the stream goes on
+    let mut held = JoinSet::new();
+
+    loop {
+        // Until a message shows the framing, replies go out newline-delimited
+        let output_framing = pipeline.output_framing
+            .unwrap_or(if framing == Framing::Auto { Framing::Newline } else { framing });
+        buffer.clear();
+        let read = {
+            let read = read_message(&mut reader, &mut buffer, &mut framing, pipeline.max_line_bytes, pipeline.direction);
+            tokio::pin!(read);
+            loop {
+                tokio::select! {
+                    read = &mut read => break read,
+                    Some(reply) = next_reply(&mut peer_replies) => {
+                        write_message(writer, output_framing, &reply).await
+                            .map_err(|e| anyhow::anyhow!("Failed to forward {}: {}", pipeline.direction, e))?;
+                    }
+                    Some(decided) = held.join_next() => {
+                        let (message, resolution) = decided?;
+                        write_released(writer, output_framing, pipeline, message, resolution).await?;
+                    }
+                }
+            }
+        };
+        let remainder = match read.map_err(|e| anyhow::anyhow!("Failed to read {}: {}", pipeline.direction, e))? {
+            Some(remainder) => remainder,
+            None => {
+                debug!("EOF on {} stream", pipeline.direction);
+                while let Some(decided) = held.join_next().await {
+                    let (message, resolution) = decided?;
+                    write_released(writer, output_framing, pipeline, message, resolution).await?;
+                }
+                return Ok(());
+            }
+        };
+        let output_framing = pipeline.output_framing.unwrap_or(framing);
+        if let Some(remainder) = remainder {
+            forward_oversized_line(&buffer, remainder, &mut reader, writer, output_framing, pipeline).await
+                .map_err(|e| anyhow::anyhow!("Failed to forward {}: {}", pipeline.direction, e))?;
+            continue;
+        }
+        let mut line = std::str::from_utf8(&buffer)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", pipeline.direction, e))?;
+        let corrupted;
+        if let Some(chaos) = chaos::injector() {
+            if pipeline.direction == Direction::Response && chaos.drop_stdout_line() {
+                warn!("Fault injection: dropped a line from the server");
+                continue;
+            }
+            if let Some(text) = chaos.corrupt(line) {
+                warn!("Fault injection: corrupted a {} message", pipeline.direction);
+                corrupted = text;
+                line = &corrupted;
+            }
+        }
+        process_and_forward_line(line, &mut output, writer, output_framing, pipeline).await
+            .map_err(|e| anyhow::anyhow!("Failed to forward {}: {}", pipeline.direction, e))?;
+        for message in pipeline.held.iter_mut().flat_map(std::mem::take) {
+            held.spawn(message.wait());
+        }
+    }
+}
+
+/// Reads the next message into `buffer`, telling the framing from its first
+/// bytes while `framing` is `Auto`. Returns `None` at EOF, else what of the
+/// message is still unread when it is longer than `limit`.
+async fn read_message<R: AsyncBufRead + Unpin>(
+    reader: &mut R,
+    buffer: &mut Vec<u8>,
+    framing: &mut Framing,
+    limit: usize,
+    direction: Direction,
+) -> std::io::Result<Option<Option<Remainder>>> {
+    if *framing == Framing::Auto {
+        match detect_framing(reader).await? {
+            Some(detected) => {
+                debug!("Detected {} framing on {} stream", detected.as_str(), direction);
+                *framing = detected;
+            }
+            None => return Ok(None),
+        }
+    }
+    match framing {
+        Framing::ContentLength => read_frame_bounded(reader, buffer, limit).await,
+        _ => read_line_bounded(reader, buffer, limit).await
+            .map(|(read, oversized)| (read > 0).then_some(oversized.then_some(Remainder::Line))),
+    }
+}
+
+/// Reads whole messages with the framing and size limit `bridge` applies, for
+/// streams that are routed before any pipeline sees them, as the gateway's
+/// client side is. A message longer than the limit cannot be routed and is
+/// skipped without being buffered.
+pub struct MessageReader<R> {
+    reader: BufReader<R>,
+    buffer: Vec<u8>,
+    framing: Framing,
+    limit: usize,
+}
+
+impl<R: AsyncRead + Unpin> MessageReader<R> {
+    pub fn new(reader: R, framing: Framing, limit: usize) -> Self {
+        Self { reader: BufReader::new(reader), buffer: Vec::new(), framing, limit }
+    }
+
+    /// Framing of the stream, once the first message showed it.
+    pub fn framing(&self) -> Framing {
+        self.framing
+    }
+
+    /// The next non-empty message, trimmed; `None` at EOF.
+    pub async fn next(&mut self) -> Result<Option<String>> {
+        loop {
+            self.buffer.clear();
+            match read_message(&mut self.reader, &mut self.buffer, &mut self.framing, self.limit, Direction::Request).await? {
+                None => return Ok(None),
+                Some(Some(remainder)) => {
+                    let bytes = self.buffer.len() + copy_remainder::<_, tokio::io::Sink>(&mut self.reader, remainder, None).await?;
+                    warn!("Dropped a client message of {} bytes, over max_line_bytes ({})", bytes, self.limit);
+                }
+                Some(None) => {
+                    let message = std::str::from_utf8(&self.buffer)?.trim();
+                    if !message.is_empty() {
+                        return Ok(Some(message.to_string()));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// The next reply of the opposite direction; never ready without a channel.
+async fn next_reply(peer_replies: &mut Option<mpsc::UnboundedReceiver<String>>) -> Option<String> {
+    match peer_replies {
+        Some(peer_replies) => peer_replies.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Forwards what is left of a held message once an operator decided on it.
+async fn write_released<W: AsyncWrite + Unpin>(
+    writer: &mut W,
+    framing: Framing,
+    pipeline: &mut Pipeline,
+    message: HeldMessage,
+    resolution: Resolution,
+) -> Result<()> {
+    let released = pipeline.release(message, resolution);
+    if !released.is_empty() {
+        write_message(writer, framing, &released).await
+            .map_err(|e| anyhow::anyhow!("Failed to forward {}: {}", pipeline.direction, e))?;
+    }
+    Ok(())
+}
+
+/// Skips leading whitespace and tells the framing of a stream from its first
+/// byte: JSON starts with a bracket or brace, a `Content-Length` header with
+/// a letter. Returns `None` at EOF.
+async fn detect_framing<R: AsyncBufRead + Unpin>(reader: &mut R) -> std::io::Result<Option<Framing>> {
+    loop {
+        let available = reader.fill_buf().await?;
+        let Some(&first) = available.first() else {
+            return Ok(None);
+        };
+        if first.is_ascii_whitespace() {
+            reader.consume(1);
+            continue;
+        }
+        return Ok(Some(if first.is_ascii_alphabetic() { Framing::ContentLength } else { Framing::Newline }));
+    }
+}
+
+/// Longest header line of a `Content-Length` frame; real ones are a few dozen bytes.
+const MAX_FRAME_HEADER_BYTES: usize = 8 * 1024;
+
+/// Reads the headers of a `Content-Length` frame and up to `limit` bytes of
+/// its body into `buffer`. Returns `None` at EOF, else what of the body is
+/// still unread when it is longer than `limit`.
+async fn read_frame_bounded<R: AsyncBufRead + Unpin>(reader: &mut R, buffer: &mut Vec<u8>, limit: usize) -> std::io::Result<Option<Option<Remainder>>> {
+    let mut length = None;
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        let (read, oversized) = read_line_bounded(reader, &mut line, MAX_FRAME_HEADER_BYTES).await?;
+        if oversized {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData,
+                format!("frame header longer than {} bytes", MAX_FRAME_HEADER_BYTES)));
+        }
+        if read == 0 {
+            return match length {
+                None => Ok(None),
+                Some(_) => Err(std::io::Error::new(std::io::ErrorKind::UnexpectedEof, "stream ended inside frame headers")),
+            };
+        }
+        let header = String::from_utf8_lossy(&line);
+        let header = header.trim();
+        if header.is_empty() {
+            // Blank lines between frames
+            if length.is_some() {
+                break;
+            }
+            continue;
+        }
+        let (name, value) = header.split_once(':')
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid frame header '{}'", header)))?;
+        if name.trim().eq_ignore_ascii_case(CONTENT_LENGTH_HEADER) {
+            length = Some(value.trim().parse::<u64>()
+                .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, format!("invalid Content-Length '{}'", value.trim())))?);
+        } else if length.is_none() && !name.trim().to_ascii_lowercase().starts_with("content-") {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, format!("expected a Content-Length header, got '{}'", header)));
+        }
+    }
+    let length = length.unwrap_or_default();
+    let buffered = length.min(limit as u64);
+    buffer.resize(buffered as usize, 0);
+    tokio::io::AsyncReadExt::read_exact(reader, buffer).await?;
+    Ok(Some((length > buffered).then_some(Remainder::Bytes(length - buffered))))
+}
+
+/// Writes one message in `framing`; a newline-delimited one gets a newline
+/// unless it already ends with one, and is put on one line if a framed body
+/// spread it over several.
+pub(crate) async fn write_message<W: AsyncWrite + Unpin>(writer: &mut W, framing: Framing, message: &str) -> std::io::Result<()> {
+    let compacted;
+    let mut message = message;
+    if framing != Framing::ContentLength && message.trim_end().contains('\n') {
+        if let Ok(value) = serde_json::from_str::<Value>(message) {
+            compacted = value.to_string();
+            message = &compacted;
+        }
+    }
+    match framing {
+        Framing::ContentLength => {
+            let header = format!("Content-Length: {}\r\n\r\n", message.len());
+            writer.write_all(header.as_bytes()).await?;
+            writer.write_all(message.as_bytes()).await?;
+        }
+        _ => {
+            writer.write_all(message.as_bytes()).await?;
+            if !message.ends_with('\n') {
+                writer.write_all(b"\n").await?;
+            }
+        }
+    }
+    writer.flush().await
+}
+
+/// Reads one line into `buffer`, newline included, stopping once it would
+/// exceed `limit` bytes without its newline. Returns the bytes read (0 at EOF)
+/// and whether the line was cut off, with the rest of it still unread.
+async fn read_line_bounded<R: AsyncBufRead + Unpin>(reader: &mut R, buffer: &mut Vec<u8>, limit: usize) -> std::io::Result<(usize, bool)> {
+    let mut read = 0;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok((read, false));
+        }
+        let newline = available.iter().position(|&byte| byte == b'\n');
+        let content = newline.unwrap_or(available.len());
+        let room = limit.saturating_sub(buffer.len());
+        if content > room {
+            buffer.extend_from_slice(&available[..room]);
+            reader.consume(room);
+            return Ok((read + room, true));
+        }
+        let used = newline.map_or(content, |at| at + 1);
+        buffer.extend_from_slice(&available[..used]);
+        reader.consume(used);
+        read += used;
+        if newline.is_some() {
+            return Ok((read, false));
+        }
+    }
+}
+
+/// Passes the unread rest of a message to `writer` chunk by chunk, or
+/// discards it without one. Returns the number of bytes.
+async fn copy_remainder<R, W>(reader: &mut R, remainder: Remainder, writer: Option<&mut W>) -> Result<usize>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    match remainder {
+        Remainder::Line => copy_rest_of_line(reader, writer).await,
+        Remainder::Bytes(length) => {
+            let mut body = tokio::io::AsyncReadExt::take(reader, length);
+            let copied = match writer {
+                Some(writer) => tokio::io::copy(&mut body, writer).await?,
+                None => tokio::io::copy(&mut body, &mut tokio::io::sink()).await?,
+            };
+            if copied < length {
+                return Err(anyhow::anyhow!("stream ended inside a {} byte frame", length));
+            }
+            Ok(copied as usize)
+        }
+    }
+}
+
+/// Passes the unread rest of a line to `writer` chunk by chunk, or discards
+/// it without one. Returns the number of bytes.
+async fn copy_rest_of_line<R, W>(reader: &mut R, mut writer: Option<&mut W>) -> Result<usize>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut copied = 0;
+    loop {
+        let available = reader.fill_buf().await?;
+        if available.is_empty() {
+            return Ok(copied);
+        }
+        let newline = available.iter().position(|&byte| byte == b'\n');
+        let used = newline.map_or(available.len(), |at| at + 1);
+        if let Some(writer) = writer.as_mut() {
+            writer.write_all(&available[..used]).await?;
+        }
+        reader.consume(used);
+        copied += used;
+        if newline.is_some() {
+            return Ok(copied);
+        }
+    }
+}
+
+async fn forward_oversized_line<R, W>(
+    prefix: &[u8],
+    remainder: Remainder,
+    reader: &mut R,
+    writer: &mut W,
+    framing: Framing,
+    pipeline: &mut Pipeline,
+) -> Result<()>
+where
+    R: AsyncBufRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let direction = pipeline.direction;
+    let mut policy = pipeline.on_oversized_line;
+    if policy == OversizedLine::Forward && framing == Framing::ContentLength && matches!(remainder, Remainder::Line) {
+        // A frame header needs the length of a line not read yet
+        warn!("Cannot stream an oversized line into a Content-Length frame, dropping it");
+        policy = OversizedLine::Drop;
+    }
+    let rest = match policy {
+        OversizedLine::Drop => copy_remainder::<_, W>(reader, remainder, None).await?,
+        OversizedLine::Truncate => {
+            let rest = copy_remainder::<_, W>(reader, remainder, None).await?;
+            // The cut may fall inside a character; the text up to it is kept
+            let text = match std::str::from_utf8(prefix) {
+                Ok(text) => text,
+                Err(e) => std::str::from_utf8(&prefix[..e.valid_up_to()]).unwrap_or_default(),
+            };
+            pipeline.llm_deadline = pipeline.latency_budget.map(|budget| Instant::now() + budget);
+            match pipeline.sanitize_text(text).await {
+                Ok(truncated) => write_message(writer, framing, &truncated).await?,
+                // One failed line must not end the stream, nor go out unlooked-at unless allowed
+                Err(e) if pipeline.on_error == OnError::Forward => {
+                    warn!("Error processing truncated {} for PII, forwarding it: {}", direction, e);
+                    pipeline.degrade(Degradation::FailOpen);
+                    write_message(writer, framing, text).await?;
+                }
+                Err(e) => {
+                    warn!("Error processing truncated {} for PII, dropping it: {}", direction, e);
+                    pipeline.degrade(Degradation::Withheld);
+                }
+            }
+            rest
+        }
+        OversizedLine::Forward => {
+            if let (Framing::ContentLength, Remainder::Bytes(length)) = (framing, remainder) {
+                let header = format!("Content-Length: {}\r\n\r\n", prefix.len() as u64 + length);
+                writer.write_all(header.as_bytes()).await?;
+            }
+            writer.write_all(prefix).await?;
+            let rest = copy_remainder(reader, remainder, Some(&mut *writer)).await?;
+            if framing == Framing::Newline && matches!(remainder, Remainder::Bytes(_)) {
+                writer.write_all(b"\n").await?;
+            }
+            writer.flush().await?;
+            rest
+        }
+    };
+    let bytes = prefix.len() + rest;
+    warn!("{} of {} bytes exceeds max_line_bytes ({}), applied '{}'",
+          direction, bytes, pipeline.max_line_bytes, pipeline.on_oversized_line.as_str());
+    pipeline.mapping_store.counters().record_bytes(direction, bytes);
+    let mut degradations = std::mem::take(&mut pipeline.message_degradations);
+    degradations.insert(Degradation::Oversized);
+    pipeline.degradation.record_message(direction, &degradations);
+    Ok(())
+}
+
+#[tracing::instrument(name = "message", skip_all, fields(direction = %pipeline.direction, bytes = line.len(), changed))]
+async fn process_and_forward_line<W: AsyncWrite + Unpin>(
+    line: &str,
+    output: &mut String,
+    writer: &mut W,
+    framing: Framing,
+    pipeline: &mut Pipeline,
+) -> Result<()> {
+    let direction = pipeline.direction;
+    let original_line = line.trim();
+    debug!("Processing {}: {}", direction, original_line);
+
+    let processed = pipeline.process_line(original_line).await;
+    if let Ok(Cow::Owned(_)) = processed {
+        Span::current().record("changed", true);
+    }
+    write_processed(processed, line, output, writer, framing, direction)
+        .instrument(info_span!("write"))
+        .await
+}
+
+async fn write_processed<W: AsyncWrite + Unpin>(
+    processed: Result<Cow<'_, str>>,
+    line: &str,
+    output: &mut String,
+    writer: &mut W,
+    framing: Framing,
+    direction: Direction,
+) -> Result<()> {
+    match processed {
+        Ok(processed_line) => {
+            if processed_line.is_empty() {
+                // Withheld, with no reply to send in its place
+                return Ok(());
+            }
+            if let Cow::Borrowed(_) = processed_line {
+                // Unchanged: the exact bytes received, whitespace and line ending included
+                write_message(writer, framing, line).await?;
+                return Ok(());
+            }
+            info!("PII detected and anonymized in {}", direction);
+            debug!("Original: {}", line.trim());
+            debug!("Processed: {}", processed_line);
+
+            if framing == Framing::ContentLength {
+                write_message(writer, framing, &processed_line).await?;
+                return Ok(());
+            }
+            // One write per message, from a buffer reused across messages
+            output.clear();
+            output.push_str(&processed_line);
+            output.push('\n');
+            writer.write_all(output.as_bytes()).await?;
+            writer.flush().await?;
+        }
+        Err(e) => {
+            warn!("Error processing {} for PII, forwarding original: {}", direction, e);
+            write_message(writer, framing, line).await?;
+        }
+    }
+    Ok(())
+}
+
+impl Pipeline {
+    /// Builds a standalone pipeline with its own mapping store and request tracker.
+    /// Bypass tokens and operator approvals only apply inside the proxy.
+    pub fn new(config: &Config, ollama_config: OllamaConfig, direction: Direction) -> Result<Self> {
+        let prompt_template = config.llm.as_ref().and_then(|llm| llm.prompt_template.as_ref());
+        let catalog = Catalog::new(&config.i18n)?;
+
+        Ok(Self {
+            detection_engine: RegexDetectionEngine::new(&config.detection)?,
+            ollama_client: OllamaClient::new(ollama_config.clone(), prompt_template)?.with_language_prompts(&config.language)?,
+            faker_engine: FakerEngine::from_config(config)?,
+            mapping_store: MappingStore::new(config.mapping.clone())?,
+            overlap_resolver: OverlapResolver::new(&config.detection.overlap),
+            stitching: config.detection.stitching.clone(),
+            nested_json: config.detection.nested_json.clone(),
+            nested_depth: 0,
+            base64: config.detection.base64.clone(),
+            model_name: ollama_config.model,
+            detection_mode: config.detection.mode.clone(),
+            request_tracker: RequestTracker::new(),
+            schema_hints: config.detection.schema_hints.enabled.then(SchemaHints::new),
+            tool_hints: None,
+            audit_log: AuditLog::new(&config.audit)?,
+            bypass_registry: None,
+            outage_queue: OutageQueue::from_config(config)?,
+            quarantine: QuarantineStore::from_config(config)?,
+            message_types: BTreeSet::new(),
+            message_persona: None,
+            approval: None,
+            first_seen_critical: Vec::new(),
+            leak_checker: LeakChecker::new(&config.leak_check),
+            message_replacements: Vec::new(),
+            message_leaks: BTreeSet::new(),
+            canary: None,
+            debug_diff: None,
+            annotate: false,
+            discovery: Discovery::new(&config.discovery),
+            degradation: DegradationReport::new(),
+            message_degradations: BTreeSet::new(),
+            llm_cache_snapshot: HashMap::new(),
+            scalar_rules: ScalarRules::new(&config.detection.scalars)?,
+            json_path: Vec::new(),
+            replacement_spans: Vec::new(),
+            message_timeout: config.processing.message_timeout(),
+            json_limits: config.processing.json_limits(),
+            latency_budget: config.processing.latency_budget(),
+            llm_deadline: None,
+            on_error: config.processing.on_error,
+            error_withheld_message: catalog.text("error_withheld").to_string(),
+            error_withheld_request_message: catalog.text("error_withheld_request").to_string(),
+            max_line_bytes: config.processing.max_line_bytes,
+            on_oversized_line: config.processing.on_oversized_line,
+            framing: config.processing.framing,
+            output_framing: None,
+            session_scope: config.mapping.reset_on_initialize.then(|| SessionScope::new(config.mapping.clone())),
+            session_generation: 0,
+            control: None,
+            control_seen: (0, 0),
+            replies: None,
+            peer_replies: None,
+            held: None,
+            direction,
+        })
+    }
+
+    #[cfg(any(feature = "bridge", test))]
+    /// Shares a request tracker with the pipeline for the opposite direction,
+    /// so responses can be attributed to the requests that caused them.
+    pub fn with_request_tracker(mut self, request_tracker: RequestTracker) -> Self {
+        self.request_tracker = request_tracker;
+        self
+    }
+
+    /// Marks every replacement for review, as in `⟦email→fake@example.com⟧`.
+    pub fn with_annotations(mut self, enabled: bool) -> Self {
+        self.annotate = enabled;
+        self
+    }
+
+    /// Shares mapping sessions with the pipeline for the opposite direction,
+    /// so both move to a new namespace at `initialize`.
+    pub fn with_session_scope(mut self, session_scope: Option<SessionScope>) -> Self {
+        self.session_scope = session_scope;
+        self
+    }
+
+    /// Lets this pipeline and the one for the opposite direction reply to the
+    /// peer each reads from through the other's `bridge`, so a withheld
+    /// request is answered with an error instead of left waiting.
+    pub fn connect_replies(&mut self, opposite: &mut Pipeline) {
+        let (replies, peer_replies) = mpsc::unbounded_channel();
+        self.replies = Some(replies);
+        opposite.peer_replies = Some(peer_replies);
+        let (replies, peer_replies) = mpsc::unbounded_channel();
+        opposite.replies = Some(replies);
+        self.peer_replies = Some(peer_replies);
+    }
+
+    #[cfg(feature = "bridge")]
+    pub fn request_tracker(&self) -> &RequestTracker {
+        &self.request_tracker
+    }
+
+    #[cfg(any(feature = "bridge", test))]
+    pub fn degradation_report(&self) -> &DegradationReport {
+        &self.degradation
+    }
+
+    /// Processes one JSON-RPC message, returning the line to forward; it
+    /// borrows `line` when nothing was replaced. With `on_error = "forward"`,
+    /// an error is returned and counted as the message being forwarded
+    /// unprocessed, as `bridge` does; otherwise the message is dropped or
+    /// redacted in its place. An empty line means nothing is to be forwarded.
+    pub async fn process_line<'l>(&mut self, line: &'l str) -> Result<Cow<'l, str>> {
+        self.follow_control()?;
+        self.mapping_store.counters().record_bytes(self.direction, line.len());
+        self.llm_deadline = self.latency_budget.map(|budget| Instant::now() + budget);
+        let result = match self.message_timeout {
+            // Only interrupts at await points: LLM calls, not a single long regex scan
+            Some(limit) => match tokio::time::timeout(limit, self.process_message(line)).await {
+                Ok(result) => result,
+                Err(_) => {
+                    self.degrade(Degradation::TimedOut);
+                    Err(anyhow::anyhow!("Processing {} took longer than {}s", self.direction, limit.as_secs_f64()))
+                }
+            },
+            None => self.process_message(line).await,
+        };
+        let first_seen = std::mem::take(&mut self.first_seen_critical);
+        if result.is_err() && !self.message_degradations.contains(&Degradation::TimedOut) {
+            self.degrade(Degradation::FailOpen);
+        }
+        let unprocessed = self.message_degradations.iter().any(Degradation::leaves_data_unprocessed);
+        let result = match result {
+            Err(e) if self.on_error != OnError::Forward => {
+                warn!("Error processing {} for PII: {}", self.direction, e);
+                Err(Cow::Owned(self.fail_closed(line, self.on_error)))
+            }
+            Ok(_) if unprocessed && self.on_error != OnError::Forward => Err(Cow::Owned(self.fail_closed(line, self.on_error))),
+            Ok(processed) => Ok(Ok(processed)),
+            Err(e) => Ok(Err(e)),
+        };
+        let degradations = std::mem::take(&mut self.message_degradations);
+        self.degradation.record_message(self.direction, &degradations);
+        match result {
+            // Failed closed: nothing of the message is forwarded to hold
+            Err(replacement) => Ok(replacement),
+            Ok(Ok(Cow::Owned(_))) if self.control.as_ref().is_some_and(RuntimeControl::detect_only) => {
+                info!("Detect-only mode: forwarding the {} unchanged", self.direction);
+                Ok(Cow::Borrowed(line))
+            }
+            // Outside the message timeout, which would forward a held message unprocessed
+            Ok(Ok(processed)) => Ok(self.hold_for_approval(processed, first_seen).await),
+            Ok(Err(e)) => Err(e),
+        }
+    }
+
+    /// Applies what the admin channel changed since the last message: a
+    /// reloaded configuration, or rows deleted under the mapping store.
+    fn follow_control(&mut self) -> Result<()> {
+        let Some(control) = self.control.clone() else {
+            return Ok(());
+        };
+        let (config_generation, config) = control.config();
+        if config_generation != self.control_seen.1 {
+            self.control_seen.1 = config_generation;
+            if let Some(config) = config {
+                self.apply_config(&config)?;
+                info!("Applied reloaded configuration to {} processing", self.direction);
+            }
+        }
+        let store_generation = control.store_generation();
+        if store_generation != self.control_seen.0 {
+            self.control_seen.0 = store_generation;
+            self.mapping_store.forget_recent_mappings();
+            self.reopen_mapping_store()?;
+        }
+        Ok(())
+    }
+
+    /// Takes over the detection, faking and processing settings of `config`.
+    /// The mapping database, LLM client, audit log, canary and framing stay
+    /// as they were started.
+    pub(crate) fn apply_config(&mut self, config: &Config) -> Result<()> {
+        self.detection_engine = RegexDetectionEngine::new(&config.detection)?;
+        self.faker_engine = FakerEngine::from_config(config)?;
+        self.overlap_resolver = OverlapResolver::new(&config.detection.overlap);
+        self.stitching = config.detection.stitching.clone();
+        self.nested_json = config.detection.nested_json.clone();
+        self.base64 = config.detection.base64.clone();
+        self.detection_mode = config.detection.mode.clone();
+        self.scalar_rules = ScalarRules::new(&config.detection.scalars)?;
+        self.message_timeout = config.processing.message_timeout();
+        self.json_limits = config.processing.json_limits();
+        self.latency_budget = config.processing.latency_budget();
+        self.on_error = config.processing.on_error;
+        self.leak_checker = LeakChecker::new(&config.leak_check);
+        self.max_line_bytes = config.processing.max_line_bytes;
+        self.on_oversized_line = config.processing.on_oversized_line;
+        Ok(())
+    }
+
+    /// Opens the mapping store again, on the current session's namespace if
+    /// sessions are kept apart. The recently used mappings shared with the
+    /// opposite direction stay; everything else it held in memory is dropped.
+    fn reopen_mapping_store(&mut self) -> Result<()> {
+        let store = match &self.session_scope {
+            Some(scope) => {
+                let (store, generation) = scope.open_store()?;
+                self.session_generation = generation;
+                store
+            }
+            None => MappingStore::new(self.mapping_store.config().clone())?,
+        };
+        self.mapping_store = store
+            .with_counters(self.mapping_store.counters().clone())
+            .sharing_recent_mappings(&self.mapping_store);
+        Ok(())
+    }
+
+    /// Starts a new mapping session at a client's `initialize`, and moves to
+    /// the namespace of the current session once either direction started one.
+    fn follow_session(&mut self, message: &Value) -> Result<()> {
+        let Some(scope) = self.session_scope.clone() else {
+            return Ok(());
+        };
+        if self.direction == Direction::Request && message.get("method").and_then(Value::as_str) == Some("initialize") {
+            let namespace = scope.start();
+            info!("New client session, mappings kept in namespace '{}'", namespace);
+            self.audit_log.record("session_started", serde_json::json!({ "namespace": namespace }));
+            if let Some(canary) = &self.canary {
+                let canary = canary.for_session(&namespace);
+                info!("  Canary: {}", canary.token());
+                self.audit_log.record("canary_issued", serde_json::json!({ "canary": canary.token(), "namespace": namespace }));
+            }
+        }
+        if scope.generation() != self.session_generation {
+            self.reopen_mapping_store()?;
+            if let (Some(canary), Some(namespace)) = (&self.canary, scope.namespace()) {
+                self.canary = Some(canary.for_session(&namespace));
+            }
+        }
+        Ok(())
+    }
+
+    fn degrade(&mut self, degradation: Degradation) {
+        self.message_degradations.insert(degradation);
+    }
+
+    fn over_latency_budget(&self) -> bool {
+        self.llm_deadline.is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    /// Leaves the rest of the message to regex, reporting it once per message.
+    fn exceed_latency_budget(&mut self) {
+        if self.message_degradations.insert(Degradation::LatencyBudget) {
+            let budget_ms = self.latency_budget.unwrap_or_default().as_millis() as u64;
+            warn!("LLM detection of a {} ran over the {}ms latency budget, using regex-only detection for the rest of it",
+                  self.direction, budget_ms);
+            self.audit_log.record("latency_budget_exceeded", serde_json::json!({
+                "direction": self.direction.as_str(),
+                "budget_ms": budget_ms,
+            }));
+        }
+    }
+
+    /// Replaces a message that was not fully processed according to `policy`,
+    /// `on_error` or `on_leak`. Unparseable lines are dropped either way.
+    fn fail_closed(&mut self, line: &str, policy: OnError) -> String {
+        self.message_degradations.retain(|degradation| !degradation.leaves_data_unprocessed());
+        let message = serde_json::from_str::<Value>(line).ok();
+        match (policy, message) {
+            (OnError::RedactAll, Some(mut message)) => {
+                warn!("Redacting every string of the {} after an error", self.direction);
+                self.degrade(Degradation::RedactedAll);
+                if let Some(fields) = message.as_object_mut() {
+                    for (key, value) in fields {
+                        if !matches!(key.as_str(), "jsonrpc" | "id" | "method") {
+                            redact_all(value);
+                        }
+                    }
+                }
+                message.to_string()
+            }
+            (_, message) => {
+                warn!("Dropping the {} after an error", self.direction);
+                self.degrade(Degradation::Withheld);
+                message.map(|message| self.withhold(&message, &self.error_withheld_message, &self.error_withheld_request_message))
+                    .unwrap_or_default()
+            }
+        }
+    }
+
+    /// Reports what the leak check found in a processed message and returns
+    /// what is forwarded instead of it under `on_leak`, if anything.
+    fn handle_leaks(&mut self, line: &str, leaks: &BTreeSet<(LeakKind, String)>, on_leak: OnError) -> Option<String> {
+        if leaks.is_empty() {
+            return None;
+        }
+        let findings: Vec<String> = leaks.iter().map(|(kind, entity_type)| format!("{} {}", kind.as_str(), entity_type)).collect();
+        warn!("Leak check of the processed {} found: {}", self.direction, findings.join(", "));
+        for (kind, _) in leaks {
+            self.degrade(match kind {
+                LeakKind::DoubleAnonymized => Degradation::DoubleAnonymized,
+                LeakKind::Original | LeakKind::Partial => Degradation::Leaked,
+            });
+        }
+        self.audit_log.record("leak_detected", serde_json::json!({
+            "direction": self.direction.as_str(),
+            "findings": leaks.iter()
+                .map(|(kind, entity_type)| serde_json::json!({ "kind": kind.as_str(), "entity_type": entity_type }))
+                .collect::<Vec<_>>(),
+            "action": on_leak.as_str(),
+        }));
+        if on_leak == OnError::Forward {
+            return None;
+        }
+        // Nothing is left of the message for an operator to approve
+        self.first_seen_critical.clear();
+        Some(self.fail_closed(line, on_leak))
+    }
+
+    async fn process_message<'l>(&mut self, line: &'l str) -> Result<Cow<'l, str>> {
+        let json_value: Value = info_span!("read").in_scope(|| serde_json::from_str(line))?;
+        if let Err(e) = self.json_limits.check(&json_value) {
+            // Never forwarded as is, whatever on_error says: nothing in it was looked at
+            warn!("Not processing the {}: {}", self.direction, e);
+            let policy = if self.on_error == OnError::Block { OnError::Block } else { OnError::RedactAll };
+            return Ok(Cow::Owned(self.fail_closed(line, policy)));
+        }
+        self.follow_session(&json_value)?;
+        
+        let request = self.request_tracker.classify(self.direction, &json_value);
+        if self.bypass_applies(request.as_ref()) {
+            return Ok(Cow::Borrowed(line));
+        }
+        
+        if self.direction == Direction::Request
+            && self.mapping_store.config().restore_in_requests
+            && request.as_ref().is_some_and(|request| request.method == "tools/call")
+        {
+            return self.restore_tool_arguments(line, json_value);
+        }
+
+        // Sampling requests carry conversation content despite being requests
+        let sampling = request.as_ref().is_some_and(RequestInfo::is_sampling);
+        self.learn_schema_hints(request.as_ref(), &json_value);
+        
+        // Check if this is a JSON-RPC/MCP protocol message - if so, skip PII processing
+        if !sampling && is_jsonrpc_protocol_message(&json_value) {
+            debug!("Skipping PII processing for JSON-RPC/MCP protocol message");
+            return Ok(Cow::Borrowed(line));
+        }
+        
+        let original = self.debug_diff.is_some().then(|| json_value.clone());
+        let mut json_value = json_value;
+        self.json_path.clear();
+        self.message_types.clear();
+        self.first_seen_critical.clear();
+        self.message_replacements.clear();
+        self.message_leaks.clear();
+        self.load_llm_cache_snapshot(&json_value);
+        self.prefetch_llm_entities(&json_value).instrument(info_span!("detect_llm", prefetch = true)).await;
+        self.message_persona = self.link_message_identity(&json_value).unwrap_or_else(|e| {
+            warn!("Failed to link identities: {}", e);
+            None
+        });
+        self.tool_hints = request.as_ref()
+            .and_then(|request| request.tool.as_deref())
+            .and_then(|tool| self.schema_hints.as_ref()?.for_tool(tool));
+        let processed = if sampling {
+            self.process_sampling_content(&mut json_value).await
+        } else {
+            self.process_json(&mut json_value, None).await
+        };
+        self.tool_hints = None;
+        let any_changes = match processed {
+            Ok(any_changes) => any_changes,
+            Err(e) => {
+                warn!("Failed to process {}, forwarding it unchanged: {}", self.direction, e);
+                self.degrade(Degradation::FailOpen);
+                false
+            }
+        };
+
+        if let (Some(diff), Some(original)) = (self.debug_diff.as_mut(), original) {
+            if any_changes {
+                let label = format!("{} {}", self.direction, protocol::message_id(&original).unwrap_or_else(|| "-".to_string()));
+                info!(target: "debug_diff", "\n{}", diff.render(&label, &original, &json_value));
+            } else {
+                diff.reset();
+            }
+        }
+        
+        self.sample_for_discovery(&json_value);
+
+        if any_changes {
+            if let Some(checker) = &self.leak_checker {
+                let on_leak = checker.on_leak;
+                let mut leaks = checker.check(&json_value, &self.message_replacements);
+                leaks.append(&mut self.message_leaks);
+                if let Some(replacement) = self.handle_leaks(line, &leaks, on_leak) {
+                    return Ok(Cow::Owned(replacement));
+                }
+            }
+            if let Some(canary) = &self.canary {
+                canary.inject(&mut json_value);
+            }
+            let processed = serde_json::to_string(&json_value)
+                .map_err(|e| anyhow::anyhow!("Failed to serialize modified JSON: {}", e))?;
+            self.sample_for_quarantine(line, &processed);
+            if let Err(e) = self.mapping_store.record_cooccurrence(&self.message_types) {
+                warn!("Failed to record entity co-occurrence: {}", e);
+            }
+            Ok(Cow::Owned(processed))
+        } else {
+            Ok(Cow::Borrowed(line))
+        }
+    }
+
+    /// Puts the originals back for the fakes detected in a tool call's
+    /// arguments. A fake that cannot be resolved, such as one handed out for
+    /// two originals, stays as it is.
+    fn restore_tool_arguments<'l>(&mut self, line: &'l str, mut message: Value) -> Result<Cow<'l, str>> {
+        let Some(arguments) = message.pointer_mut("/params/arguments") else {
+            return Ok(Cow::Borrowed(line));
+        };
+        let mut restored = 0;
+        let mut stack = vec![(arguments, None::<String>)];
+        while let Some((value, key)) = stack.pop() {
+            match value {
+                Value::String(text) => {
+                    let mut entities = self.detection_engine.detect_for_key(key.as_deref(), text);
+                    entities.sort_by_key(|entity| std::cmp::Reverse(entity.start));
+                    let mut end = text.len();
+                    for entity in entities {
+                        if entity.end > end {
+                            continue;
+                        }
+                        match self.mapping_store.get_original(&entity.entity_type, &entity.original_value) {
+                            Ok(Some(original)) => {
+                                text.replace_range(entity.start..entity.end, &original);
+                                end = entity.start;
+                                restored += 1;
+                            }
+                            Ok(None) => {}
+                            Err(e) => warn!("Leaving a {} value in a tool call as it is: {}", entity.entity_type, e),
+                        }
+                    }
+                }
+                Value::Object(map) => {
+                    for (key, value) in map.iter_mut() {
+                        stack.push((value, Some(key.clone())));
+                    }
+                }
+                Value::Array(items) => {
+                    for item in items.iter_mut() {
+                        stack.push((item, key.clone()));
+                    }
+                }
+                _ => {}
+            }
+        }
+        if restored == 0 {
+            return Ok(Cow::Borrowed(line));
+        }
+        self.audit_log.record("originals_restored", serde_json::json!({
+            "tool": message.pointer("/params/name"),
+            "count": restored,
+        }));
+        Ok(Cow::Owned(serde_json::to_string(&message)?))
+    }
+
+    /// Infers key hints from the tool schemas in a `tools/list` response.
+    fn learn_schema_hints(&self, request: Option<&RequestInfo>, message: &Value) {
+        let (Some(hints), Some(result)) = (self.schema_hints.as_ref(), message.get("result")) else {
+            return;
+        };
+        if request.is_some_and(|request| request.method == "tools/list") {
+            let hinted = hints.learn(result);
+            if hinted > 0 {
+                info!("Inferred sensitive fields for {} tool(s) from their schemas", hinted);
+            }
+        }
+    }
+
+    /// Entity type implied by a key, from the configured key hints or else
+    /// the schema of the tool whose result is being processed.
+    fn key_entity_type(&self, key: Option<&str>) -> Option<&str> {
+        self.detection_engine.key_entity_type(key).or_else(|| {
+            let hints = self.tool_hints.as_ref()?;
+            hints.get(&key?.to_lowercase()).map(String::as_str)
+        })
+    }
+
+    fn detect_for_key(&self, key: Option<&str>, text: &str) -> Vec<DetectedEntity> {
+        let _span = info_span!("detect_regex", chars = text.len()).entered();
+        let entities = self.detection_engine.detect_in_text(text);
+        self.apply_key_hint(key, text, entities)
+    }
+
+    fn apply_key_hint(&self, key: Option<&str>, text: &str, entities: Vec<DetectedEntity>) -> Vec<DetectedEntity> {
+        self.detection_engine.apply_entity_hint(key, self.key_entity_type(key), text, entities)
+    }
+
+    /// Processes only the conversation content of a sampling request or response.
+    async fn process_sampling_content(&mut self, message: &mut Value) -> Result<bool> {
+        let mut any_changes = false;
+        for pointer in protocol::SAMPLING_CONTENT {
+            if let Some(content) = message.pointer_mut(pointer) {
+                any_changes |= self.process_json(content, None).await?;
+            }
+        }
+        Ok(any_changes)
+    }
+
+    /// Anonymizes every string in `value`, protocol messages included. Used for
+    /// offline content such as client logs, where nothing must be skipped.
+    pub(crate) async fn sanitize_value(&mut self, value: &mut Value) -> Result<bool> {
+        self.json_limits.check(value)?;
+        self.json_path.clear();
+        self.llm_cache_snapshot.clear();
+        self.process_json(value, None).await
+    }
+
+    /// Anonymizes free text outside any JSON structure.
+    pub(crate) async fn sanitize_text<'t>(&mut self, text: &'t str) -> Result<Cow<'t, str>> {
+        if text.trim().is_empty() {
+            return Ok(Cow::Borrowed(text));
+        }
+        self.process_text(text, None).await
+    }
+
+    /// Occasionally hands a processed message to discovery, in the background.
+    fn sample_for_discovery(&mut self, processed: &Value) {
+        if !self.discovery.as_ref().is_some_and(Discovery::should_sample) {
+            if let Some(discovery) = self.discovery.as_mut() {
+                discovery.fakes.clear();
+            }
+            return;
+        }
+
+        let mut texts = Vec::new();
+        self.collect_llm_texts(processed, None, &mut texts);
+        let texts: Vec<String> = texts.into_iter().map(str::to_string).collect();
+        let (client, engine) = (self.ollama_client.clone(), self.detection_engine.clone());
+        if let Some(discovery) = self.discovery.as_mut() {
+            discovery.spawn(client, engine, texts);
+        }
+    }
+
+    /// Occasionally keeps an encrypted original/processed pair of a message in
+    /// which a critical entity type was replaced.
+    fn sample_for_quarantine(&mut self, original: &str, processed: &str) {
+        let direction = self.direction;
+        let Some(quarantine) = self.quarantine.as_mut() else {
+            return;
+        };
+        let critical_types: Vec<String> = self.message_types.iter()
+            .filter(|entity_type| quarantine.config().is_critical(entity_type))
+            .cloned()
+            .collect();
+        if critical_types.is_empty() || !quarantine.should_sample() {
+            return;
+        }
+        if let Err(e) = quarantine.record(direction, &critical_types, original, processed) {
+            warn!("Failed to store QA sample: {}", e);
+        }
+    }
+
+    /// Holds a message in which a critical value was mapped for the first time
+    /// until an operator decides on it. Inside `bridge` the message is left to
+    /// it and nothing is forwarded for now; elsewhere this waits for the
+    /// decision. A denied message is withheld and its new mappings are
+    /// forgotten, so the values are held again next time.
+    async fn hold_for_approval<'l>(&mut self, processed: Cow<'l, str>, first_seen: Vec<(String, String)>) -> Cow<'l, str> {
+        let Some(gate) = self.approval.clone() else {
+            return processed;
+        };
+        if first_seen.is_empty() {
+            return processed;
+        }
+
+        let message: Value = serde_json::from_str(&processed).unwrap_or_default();
+        let method = message.get("method").and_then(Value::as_str).map(str::to_string);
+        let entity_types: Vec<String> = first_seen.iter()
+            .map(|(entity_type, _)| base_entity_type(entity_type).to_ascii_lowercase())
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+
+        let request = match gate.submit(self.direction, entity_types.clone(), method.clone()) {
+            Ok(request) => {
+                warn!(
+                    "Holding {} with first-seen {} until an operator runs `mcp-server-conceal ctl approve {}` (or deny), at most {}s",
+                    self.direction, entity_types.join(", "), request.id, gate.config().timeout_seconds
+                );
+                self.audit_log.record("approval_requested", serde_json::json!({
+                    "request_id": request.id,
+                    "direction": self.direction.as_str(),
+                    "method": method,
+                    "entity_types": entity_types,
+                }));
+                Some(request)
+            }
+            Err(e) => {
+                warn!("Could not request operator approval, applying the default: {}", e);
+                None
+            }
+        };
+
+        let held = HeldMessage { gate, request, processed: processed.into_owned(), message, first_seen };
+        match self.held.as_mut() {
+            Some(held_messages) => {
+                held_messages.push(held);
+                Cow::Owned(String::new())
+            }
+            None => {
+                let (held, resolution) = held.wait().await;
+                Cow::Owned(self.release(held, resolution))
+            }
+        }
+    }
+
+    /// Returns what is forwarded of a held message once it is decided on.
+    pub(crate) fn release(&mut self, held: HeldMessage, resolution: Resolution) -> String {
+        if let Some(request) = &held.request {
+            self.audit_log.record("approval_resolved", serde_json::json!({
+                "request_id": request.id,
+                "decision": resolution.decision.as_str(),
+                "timed_out": resolution.timed_out,
+            }));
+        }
+        match resolution.decision {
+            ApprovalDecision::Approve => held.processed,
+            ApprovalDecision::Deny => {
+                for (entity_type, original) in &held.first_seen {
+                    if let Err(e) = self.mapping_store.delete_mapping(entity_type, original) {
+                        warn!("Failed to forget mapping of a withheld {}: {}", entity_type, e);
+                    }
+                }
+                warn!("Withholding {} denied by the approval policy", self.direction);
+                self.withhold(&held.message, held.gate.withheld_message(), held.gate.withheld_request_message())
+            }
+        }
+    }
+
+    /// What is forwarded in place of a withheld message: an error for a
+    /// response, so the client is not left waiting, and nothing for anything
+    /// else. A withheld request is answered with an error of its own, sent
+    /// back to its sender through the opposite direction.
+    fn withhold(&self, message: &Value, response_error: &str, request_error: &str) -> String {
+        match (message.get("id"), message.get("method")) {
+            (Some(id), None) => error_response(id, response_error),
+            (Some(id), Some(_)) => {
+                let answered = self.replies.as_ref()
+                    .is_some_and(|replies| replies.send(error_response(id, request_error)).is_ok());
+                if !answered {
+                    warn!("No way to answer the withheld {}, its sender gets no reply", self.direction);
+                }
+                String::new()
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// Looks up the LLM cache for every string of a message in one batch, so
+    /// the per-string pass does not query the database for each field.
+    fn load_llm_cache_snapshot(&mut self, message: &Value) {
+        self.llm_cache_snapshot.clear();
+        if matches!(self.detection_mode, DetectionMode::Regex) {
+            return;
+        }
+
+        let mut texts = Vec::new();
+        self.collect_llm_texts(message, None, &mut texts);
+        if texts.len() < 2 {
+            return;
+        }
+        texts.sort_unstable();
+        texts.dedup();
+        match self.mapping_store.get_llm_cache_batch(&texts, &self.model_name) {
+            Ok(mut cached) => {
+                for text in texts {
+                    let entry = cached.remove(text);
+                    self.llm_cache_snapshot.insert(text.to_string(), entry);
+                }
+            }
+            Err(e) => debug!("Batched LLM cache lookup failed, falling back to per-string lookups: {}", e),
+        }
+    }
+
+    fn cached_llm_entities(&self, text: &str) -> Result<Option<Vec<DetectedEntity>>> {
+        match self.llm_cache_snapshot.get(text) {
+            Some(entry) => Ok(entry.clone()),
+            None => self.mapping_store.get_llm_cache(text, &self.model_name),
+        }
+    }
+
+    fn cache_llm_entities(&mut self, text: &str, entities: &[DetectedEntity]) -> Result<()> {
+        self.mapping_store.store_llm_cache(text, entities, &self.model_name)?;
+        if let Some(entry) = self.llm_cache_snapshot.get_mut(text) {
+            *entry = Some(entities.to_vec());
+        }
+        Ok(())
+    }
+
+    /// Detects all uncached strings of a message up front and fills the LLM
+    /// cache, so the per-string pass that follows is served from cache. With
+    /// batching enabled the strings go out in as few LLM requests as possible;
+    /// otherwise up to `max_in_flight` requests run at once.
+    async fn prefetch_llm_entities(&mut self, message: &Value) {
+        let batching = self.ollama_client.batching().clone();
+        let max_in_flight = self.ollama_client.concurrency().max_in_flight;
+        if !(batching.enabled || max_in_flight > 1)
+            || matches!(self.detection_mode, DetectionMode::Regex)
+            || self.ollama_client.circuit_state() == CircuitState::Open
+            || self.over_latency_budget()
+        {
+            return;
+        }
+
+        let mut texts = Vec::new();
+        self.collect_llm_texts(message, None, &mut texts);
+        texts.sort_unstable();
+        texts.dedup();
+        texts.retain(|text| (!batching.enabled || text.len() <= batching.max_chars)
+            && !matches!(self.cached_llm_entities(text), Ok(Some(_))));
+        if texts.len() < 2 {
+            return;
+        }
+        match within_budget(self.llm_deadline, self.ollama_client.health_check()).await {
+            Some(Ok(true)) => {}
+            Some(_) => return,
+            None => return self.exceed_latency_budget(),
+        }
+        if !batching.enabled {
+            return self.detect_concurrently(&texts, max_in_flight).await;
+        }
+
+        for batch in pack_batches(&texts, batching.max_items, batching.max_chars) {
+            let Some(result) = within_budget(self.llm_deadline, self.ollama_client.extract_entities_batch(&batch)).await else {
+                return self.exceed_latency_budget();
+            };
+            match result {
+                Ok(results) => {
+                    for (text, entities) in batch.iter().zip(results) {
+                        if let Err(e) = self.cache_llm_entities(text, &entities) {
+                            warn!("Failed to cache batched LLM result: {}", e);
+                        }
+                    }
+                }
+                Err(e) => {
+                    debug!("Batched LLM detection failed, falling back to per-string requests: {}", e);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Runs LLM detection of `texts` with up to `limit` requests in flight,
+    /// caching each result. Stops at the first failure; the per-string pass
+    /// then handles what is left, degrading as it would without this.
+    async fn detect_concurrently(&mut self, texts: &[&str], limit: usize) {
+        let mut pending = texts.iter();
+        let mut in_flight = JoinSet::new();
+        loop {
+            while in_flight.len() < limit {
+                let Some(text) = pending.next() else {
+                    break;
+                };
+                let (client, text) = (self.ollama_client.clone(), text.to_string());
+                in_flight.spawn(async move {
+                    let entities = client.extract_entities(&text).await;
+                    (text, entities)
+                });
+            }
+            // Dropping the set on return aborts the requests still in flight
+            let joined = match within_budget(self.llm_deadline, in_flight.join_next()).await {
+                Some(Some(joined)) => joined,
+                Some(None) => return,
+                None => return self.exceed_latency_budget(),
+            };
+            match joined {
+                Ok((text, Ok(entities))) => {
+                    if let Err(e) = self.cache_llm_entities(&text, &entities) {
+                        warn!("Failed to cache LLM result: {}", e);
+                    }
+                }
+                Ok((_, Err(e))) => {
+                    debug!("Concurrent LLM detection failed, falling back to per-string requests: {}", e);
+                    return;
+                }
+                Err(e) => {
+                    warn!("Concurrent LLM detection task failed: {}", e);
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Strings `process_json` would send to the LLM, skipping stitched runs.
+    fn collect_llm_texts<'v>(&self, value: &'v Value, key: Option<&str>, texts: &mut Vec<&'v str>) {
+        match value {
+            Value::String(text) if text.trim().len() > 3 || self.key_entity_type(key).is_some() => {
+                texts.push(text);
+            }
+            Value::Array(arr) => {
+                let runs = if self.stitching.enabled && self.key_entity_type(key).is_none() {
+                    stitching::stitchable_runs(arr)
+                } else {
+                    Vec::new()
+                };
+                for (index, item) in arr.iter().enumerate() {
+                    if !runs.iter().any(|run| run.contains(&index)) {
+                        self.collect_llm_texts(item, key, texts);
+                    }
+                }
+            }
+            Value::Object(obj) => {
+                for (child_key, val) in obj {
+                    self.collect_llm_texts(val, Some(child_key.as_str()), texts);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    /// Checks the request a message belongs to against active bypass tokens.
+    fn bypass_applies(&mut self, request: Option<&RequestInfo>) -> bool {
+        let Some(request) = request else {
+            return false;
+        };
+        let Some(token) = self.bypass_registry.as_mut().and_then(|registry| registry.find(request)) else {
+            return false;
+        };
+
+        warn!("Bypass token {} active, forwarding {} for '{}' unprocessed", token.id, self.direction, request.method);
+        self.degrade(Degradation::Bypassed);
+        self.audit_log.record("bypass_applied", serde_json::json!({
+            "token_id": token.id,
+            "direction": self.direction.as_str(),
+            "method": request.method,
+            "tool": request.tool,
+            "expires_at": token.expires_at,
+        }));
+        true
+    }
+
+    /// Walks a JSON value with an explicit work stack, anonymizing string
+    /// leaves. `key` is the name of the closest enclosing object key, which
+    /// array elements inherit.
+    fn process_json<'a>(
+        &'a mut self,
+        value: &'a mut Value,
+        key: Option<&'a str>,
+    ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<bool>> + Send + 'a>> {
+        Box::pin(async move {
+            let tracked = self.scalar_rules.is_active();
+            let base = self.json_path.len();
+            let mut any_changes = false;
+            // Children are pushed in reverse so they are processed in document order
+            let mut stack = vec![JsonWork { value, key, depth: base, segments: Vec::new() }];
+
+            while let Some(JsonWork { value, key, depth, segments }) = stack.pop() {
+                if tracked {
+                    self.json_path.truncate(depth);
+                    self.json_path.extend(segments);
+                }
+                let depth = self.json_path.len();
+                match value {
+                    // Only bother with non-trivial strings, unless the key names the value
+                    Value::String(text) if text.trim().len() > 3 || self.key_entity_type(key).is_some() => {
+                        any_changes |= self.process_string(text, key).await?;
+                    }
+                    Value::Number(number) if self.scalar_rules.scans_number(&self.json_path) => {
+                        let number = number.clone();
+                        match self.process_number(&number, key).await {
+                            Ok(Some(replacement)) => {
+                                *value = replacement;
+                                any_changes = true;
+                            }
+                            Ok(None) => {}
+                            Err(e) => {
+                                debug!("Leaving number unprocessed after error: {}", e);
+                                self.degrade(Degradation::FieldSkipped);
+                            }
+                        }
+                    }
+                    Value::Bool(flag) if self.scalar_rules.replaces_boolean(&self.json_path) => {
+                        // A stored mapping would be trivially invertible with only two values
+                        *flag = rand::random();
+                        any_changes = true;
+                    }
+                    Value::Array(arr) => {
+                        // Key-hinted arrays keep per-element handling so the hint applies to each value
+                        let runs = if self.stitching.enabled && self.key_entity_type(key).is_none() {
+                            let (changed, runs) = self.process_stitched_runs(arr, key).await?;
+                            any_changes |= changed;
+                            runs
+                        } else {
+                            Vec::new()
+                        };
+                        for (index, item) in arr.iter_mut().enumerate().rev() {
+                            if !runs.iter().any(|run| run.contains(&index)) {
+                                stack.push(JsonWork::child(item, key, depth, tracked, || vec![JsonPathSegment::Index(index)]));
+                            } else if let Value::Object(obj) = item {
+                                // Content blocks may carry other fields (annotations, metadata) worth scanning
+                                stack.extend(obj.iter_mut().rev().filter(|(k, _)| k.as_str() != "text").map(|(child_key, val)| {
+                                    JsonWork::child(val, Some(child_key.as_str()), depth, tracked, || {
+                                        vec![JsonPathSegment::Index(index), JsonPathSegment::Key(child_key.clone())]
+                                    })
+                                }));
+                            }
+                        }
+                    }
+                    Value::Object(obj) => {
+                        stack.extend(obj.iter_mut().rev().map(|(child_key, val)| {
+                            JsonWork::child(val, Some(child_key.as_str()), depth, tracked, || vec![JsonPathSegment::Key(child_key.clone())])
+                        }));
+                    }
+                    _ => {}
+                }
+            }
+
+            if tracked {
+                self.json_path.truncate(base);
+            }
+            Ok(any_changes)
+        })
+    }
+
+    /// Processes a string value, decoding it first if it is base64-encoded
+    /// text under one of the configured keys. Binary payloads are left alone.
+    async fn process_string(&mut self, text: &mut String, key: Option<&str>) -> Result<bool> {
+        let Some((mut decoded, engine)) = self.base64_text(text, key) else {
+            return self.process_string_content(text, key).await;
+        };
+        if !self.process_string_content(&mut decoded, key).await? {
+            return Ok(false);
+        }
+        *text = engine.encode(decoded);
+        Ok(true)
+    }
+
+    /// The text a base64 string under a configured key decodes to, if it is
+    /// small enough and reads as text rather than binary data, with the
+    /// alphabet and padding it was written in.
+    fn base64_text(&self, text: &str, key: Option<&str>) -> Option<(String, &'static GeneralPurpose)> {
+        let key = key?;
+        if !self.base64.enabled
+            || text.len() / 4 * 3 > self.base64.max_decoded_bytes
+            || !self.base64.keys.iter().any(|name| name.eq_ignore_ascii_case(key))
+        {
+            return None;
+        }
+        // A length that is a multiple of four reads as padded, needing none
+        let url_safe = text.contains(['-', '_']);
+        let padded = text.len().is_multiple_of(4);
+        let engine = match (url_safe, padded) {
+            (false, true) => &BASE64,
+            (true, true) => &general_purpose::URL_SAFE,
+            (false, false) => &general_purpose::STANDARD_NO_PAD,
+            (true, false) => &general_purpose::URL_SAFE_NO_PAD,
+        };
+        let decoded = String::from_utf8(engine.decode(text).ok()?).ok()?;
+        let binary = decoded.chars().any(|c| c.is_control() && !matches!(c, '\n' | '\r' | '\t'));
+        (!binary).then_some((decoded, engine))
+    }
+
+    /// Processes a string, or the JSON document serialized in it when nested
+    /// JSON scanning is enabled. A changed document is serialized back,
+    /// pretty-printed if it was written over several lines.
+    async fn process_string_content(&mut self, text: &mut String, key: Option<&str>) -> Result<bool> {
+        if let Some(mut document) = self.nested_document(text) {
+            self.nested_depth += 1;
+            let result = self.process_json(&mut document, key).await;
+            self.nested_depth -= 1;
+            if !result? {
+                return Ok(false);
+            }
+            *text = if text.contains('\n') {
+                serde_json::to_string_pretty(&document)?
+            } else {
+                serde_json::to_string(&document)?
+            };
+            return Ok(true);
+        }
+
+        match self.process_text(text, key).await {
+            Ok(Cow::Owned(processed_text)) if processed_text != *text => {
+                *text = processed_text;
+                Ok(true)
+            }
+            Ok(_) => Ok(false),
+            Err(e) => {
+                debug!("Leaving string unprocessed after error: {}", e);
+                self.degrade(Degradation::FieldSkipped);
+                Ok(false)
+            }
+        }
+    }
+
+    /// The object or array `text` holds, if nested JSON is followed this deep.
+    fn nested_document(&self, text: &str) -> Option<Value> {
+        if !self.nested_json.enabled || self.nested_depth >= self.nested_json.max_depth {
+            return None;
+        }
+        if !matches!(text.trim_start().as_bytes().first(), Some(b'{' | b'[')) {
+            return None;
+        }
+        let document = serde_json::from_str::<Value>(text).ok().filter(|document| document.is_object() || document.is_array())?;
+        match self.json_limits.check(&document) {
+            Ok(()) => Some(document),
+            Err(e) => {
+                debug!("Scanning nested document as text: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Scans an integer as text. The replacement stays a number when the fake
+    /// contains digits (e.g. `912-34-5678` becomes `912345678`), and becomes a
+    /// string otherwise, since keeping the original would leak it.
+    async fn process_number(&mut self, number: &serde_json::Number, key: Option<&str>) -> Result<Option<Value>> {
+        if !number.is_i64() && !number.is_u64() {
+            return Ok(None);
+        }
+        let text = number.to_string();
+        let processed = match self.process_text(&text, key).await? {
+            Cow::Owned(processed) if processed != text => processed,
+            _ => return Ok(None),
+        };
+        if self.annotate {
+            return Ok(Some(Value::String(processed)));
+        }
+
+        let digits: String = processed.chars().filter(char::is_ascii_digit).collect();
+        let negative = number.as_i64().is_some_and(|n| n < 0);
+        Ok(Some(match digits.parse::<i64>() {
+            Ok(n) if negative => Value::from(-n),
+            Ok(n) => Value::from(n),
+            Err(_) => Value::String(processed),
+        }))
+    }
+
+    /// Detects across runs of adjacent text elements, then writes replacements
+    /// back per element. Returns the runs, whose elements' other fields are
+    /// left to the caller.
+    async fn process_stitched_runs(&mut self, arr: &mut [Value], key: Option<&str>) -> Result<(bool, Vec<Range<usize>>)> {
+        let runs = stitching::stitchable_runs(arr);
+        let mut any_changes = false;
+
+        for run in &runs {
+            let parts: Vec<String> = arr[run.clone()].iter()
+                .filter_map(stitching::text_of)
+                .map(str::to_string)
+                .collect();
+            let stitched = StitchedText::join(&parts, &self.stitching.separator);
+
+            let mut entities = Vec::new();
+            for window in stitched.windows(self.stitching.window) {
+                let window_entities = self.detect_entities(&stitched.text[window.clone()], key).await?;
+                entities.extend(window_entities.into_iter().map(|mut entity| {
+                    entity.start += window.start;
+                    entity.end += window.start;
+                    entity
+                }));
+            }
+            let entities = self.overlap_resolver.resolve(entities);
+
+            for ((item, part), part_entities) in arr[run.clone()].iter_mut().zip(&parts).zip(stitched.split_entities(&entities)) {
+                if part_entities.is_empty() {
+                    continue;
+                }
+                let anonymized_entities = self.create_anonymized_entities(part_entities)?;
+                if let Cow::Owned(processed) = apply_replacements(part, &anonymized_entities, self.annotate, &mut self.replacement_spans) {
+                    if processed != *part {
+                        stitching::set_text(item, processed);
+                        any_changes = true;
+                    }
+                }
+            }
+        }
+
+        Ok((any_changes, runs))
+    }
+
+    /// Anonymizes one string, borrowing it back when nothing was detected.
+    async fn process_text<'t>(&mut self, text: &'t str, key: Option<&str>) -> Result<Cow<'t, str>> {
+        let combined_entities = self.detect_entities(text, key).await?;
+        
+        if combined_entities.is_empty() {
+            return Ok(Cow::Borrowed(text));
+        }
+        
+        let combined_entities = self.overlap_resolver.resolve(combined_entities);
+        self.faker_engine.adapt_to(text);
+        let anonymized_entities = self.create_anonymized_entities(combined_entities)?;
+        Ok(apply_replacements(text, &anonymized_entities, self.annotate, &mut self.replacement_spans))
+    }
+
+    async fn detect_entities(&mut self, text: &str, key: Option<&str>) -> Result<Vec<DetectedEntity>> {
+        let combined_entities = match self.detection_mode {
+            DetectionMode::Regex => {
+                // Regex-only detection
+                self.detect_for_key(key, text)
+            }
+            DetectionMode::Llm | DetectionMode::RegexLlm
+                if self.over_latency_budget() && !matches!(self.cached_llm_entities(text), Ok(Some(_))) =>
+            {
+                // The message has waited on the LLM long enough; keep the conversation moving
+                self.exceed_latency_budget();
+                self.queue_for_backfill(text);
+                self.detect_for_key(key, text)
+            }
+            DetectionMode::Llm if self.ollama_client.circuit_state() == CircuitState::Open => {
+                // LLM backend is failing; regex keeps the worst leaks covered until it recovers
+                self.degrade(Degradation::LlmCircuitOpen);
+                self.queue_for_backfill(text);
+                self.detect_for_key(key, text)
+            }
+            DetectionMode::Llm => {
+                // LLM-only detection, with key hints still applied
+                let llm_entities = self.get_llm_entities(text).instrument(info_span!("detect_llm")).await?;
+                if llm_entities.is_empty() && self.over_latency_budget() {
+                    // The LLM call was cut short by the latency budget
+                    self.detect_for_key(key, text)
+                } else {
+                    // Dictionary terms are caught whatever the LLM makes of them
+                    let entities = combine_entities(self.detection_engine.dictionary_terms(text), llm_entities);
+                    self.apply_key_hint(key, text, entities)
+                }
+            }
+            DetectionMode::RegexLlm => {
+                // Hybrid approach: regex first, then LLM
+                let regex_entities = self.detect_for_key(key, text);
+                let llm_entities = self.get_llm_entities(text).instrument(info_span!("detect_llm")).await?;
+                combine_entities(regex_entities, llm_entities)
+            }
+        };
+        // The LLM knows nothing of the skipped identifier formats
+        Ok(self.detection_engine.drop_identifiers(combined_entities))
+    }
+
+    async fn get_llm_entities(&mut self, text: &str) -> Result<Vec<DetectedEntity>> {
+        // Check cache first
+        if let Some(cached) = self.cached_llm_entities(text)? {
+            return Ok(cached);
+        }
+        
+        // Try LLM if available
+        let available = match within_budget(self.llm_deadline, self.ollama_client.health_check()).await {
+            Some(available) => available.unwrap_or(false),
+            None => return Ok(self.skip_llm_over_budget(text)),
+        };
+        if available {
+            let Some(result) = within_budget(self.llm_deadline, self.ollama_client.extract_entities(text)).await else {
+                return Ok(self.skip_llm_over_budget(text));
+            };
+            match result {
+                Ok(entities) => {
+                    self.cache_llm_entities(text, &entities)?;
+                    Ok(entities)
+                }
+                Err(e) => {
+                    debug!("Ollama extraction failed, using regex-only: {}", e);
+                    self.degrade(Degradation::LlmFailed);
+                    self.queue_for_backfill(text);
+                    Ok(Vec::new())
+                }
+            }
+        } else {
+            debug!("Ollama not available, using regex-only detection");
+            self.degrade(if self.ollama_client.circuit_state() == CircuitState::Open {
+                Degradation::LlmCircuitOpen
+            } else {
+                Degradation::LlmUnavailable
+            });
+            self.queue_for_backfill(text);
+            Ok(Vec::new())
+        }
+    }
+
+    fn skip_llm_over_budget(&mut self, text: &str) -> Vec<DetectedEntity> {
+        self.exceed_latency_budget();
+        self.queue_for_backfill(text);
+        Vec::new()
+    }
+
+    fn queue_for_backfill(&mut self, text: &str) {
+        if let Some(queue) = self.outage_queue.as_mut() {
+            if let Err(e) = queue.enqueue(text, &self.model_name) {
+                warn!("Failed to queue text for LLM backfill: {}", e);
+            }
+        }
+    }
+
+    fn create_anonymized_entities(&mut self, entities: Vec<DetectedEntity>) -> Result<Vec<AnonymizedEntity>> {
+        let _span = info_span!("fake", entities = entities.len()).entered();
+        let mut anonymized_entities = Vec::new();
+        let persona = self.message_persona.clone();
+        
+        for entity in entities {
+            if let Some(diff) = self.debug_diff.as_mut() {
+                diff.record_rule(&entity.entity_type);
+            }
+            if self.leak_checker.is_some() && self.is_fake(&entity)? {
+                let base_type = base_entity_type(&entity.entity_type);
+                self.message_leaks.insert((LeakKind::DoubleAnonymized, base_type.to_string()));
+            }
+            let strategy = self.faker_engine.strategy_for(&entity.entity_type);
+            // Values FPE cannot take get a random fake, stored like any other
+            let encrypted = strategy == AnonymizationStrategy::Fpe && self.faker_engine.can_encrypt(&entity.original_value);
+            // FPE and date shift fakes are not stored, so they are never first seen
+            let unstored = encrypted || strategy == AnonymizationStrategy::DateShift;
+            let existing_fake = if unstored {
+                None
+            } else {
+                self.mapping_store.get_mapping(&entity.entity_type, &entity.original_value)?
+            };
+            let first_seen = !unstored && existing_fake.is_none();
+            if !unstored {
+                self.mapping_store.counters().record_mapping_lookup(existing_fake.is_some());
+            }
+            let anonymized = if encrypted {
+                // Reversible with the key alone, so never stored
+                self.faker_engine.anonymize_entity(&entity)?
+            } else if strategy == AnonymizationStrategy::DateShift {
+                // Only the offset is stored; each date follows from it
+                let offset = self.shared_fake(DATE_SHIFT_ENTITY_TYPE, "offset", |faker| {
+                    Ok(faker.generate_date_offset().to_string())
+                })?;
+                let offset = offset.parse()
+                    .map_err(|e| anyhow::anyhow!("Corrupt date shift offset '{}' in mapping store: {}", offset, e))?;
+                self.faker_engine.anonymize_date_shifted(&entity, offset)
+            } else if let Some(existing_fake) = existing_fake {
+                AnonymizedEntity {
+                    entity_type: entity.entity_type,
+                    original_value: entity.original_value,
+                    fake_value: existing_fake,
+                    mapping_id: format!("existing-{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos()),
+                }
+            } else if let Some(persona) = persona.as_ref().filter(|p| strategy == AnonymizationStrategy::Fake && p.covers(&entity)) {
+                let fake_domain = match self.faker_engine.email_domain_to_map(&entity.entity_type, &entity.original_value) {
+                    Some(domain) => Some(self.shared_fake(EMAIL_DOMAIN_ENTITY_TYPE, &domain, |faker| Ok(faker.generate_fake_domain()))?),
+                    None => None,
+                };
+                // Every address of the person gets its own fake, numbered if need be
+                let mut attempt = 0;
+                let mut anonymized = self.faker_engine.anonymize_as_persona(&entity, persona, fake_domain.as_deref(), attempt);
+                while anonymized.entity_type == identity::EMAIL_TYPE
+                    && self.mapping_store.is_fake(&anonymized.entity_type, &anonymized.fake_value)?
+                {
+                    attempt += 1;
+                    anonymized = self.faker_engine.anonymize_as_persona(&entity, persona, fake_domain.as_deref(), attempt);
+                }
+                self.store_first_mapping(anonymized)?
+            } else if strategy == AnonymizationStrategy::Tokenize {
+                let number = self.mapping_store.next_placeholder_number(&faker::placeholder_label(&entity.entity_type))?;
+                let anonymized = self.faker_engine.anonymize_as_placeholder(&entity, number);
+                self.store_first_mapping(anonymized)?
+            } else if strategy == AnonymizationStrategy::Ip {
+                let anonymized = match self.faker_engine.ip_subnet(&entity.original_value) {
+                    Some(subnet) => {
+                        let fake_subnet = self.shared_fake(IP_SUBNET_ENTITY_TYPE, &subnet, |faker| faker.generate_fake_subnet(&subnet))?;
+                        self.faker_engine.anonymize_ip_in_subnet(&entity, &fake_subnet)
+                    }
+                    None => self.faker_engine.anonymize_entity(&entity)?,
+                };
+                self.store_first_mapping(anonymized)?
+            } else if let Some(domain) = self.faker_engine.email_domain_to_map(&entity.entity_type, &entity.original_value) {
+                let fake_domain = self.shared_fake(EMAIL_DOMAIN_ENTITY_TYPE, &domain, |faker| Ok(faker.generate_fake_domain()))?;
+                let anonymized = self.faker_engine.anonymize_email_in_domain(&entity, &fake_domain);
+                self.store_first_mapping(anonymized)?
+            } else {
+                let anonymized = self.faker_engine.anonymize_entity(&entity)?;
+                self.store_first_mapping(anonymized)?
+            };
+            if let Some(discovery) = self.discovery.as_mut() {
+                discovery.fakes.push(anonymized.fake_value.clone());
+            }
+            let base_type = base_entity_type(&anonymized.entity_type);
+            if !self.message_types.contains(base_type) {
+                self.message_types.insert(base_type.to_ascii_lowercase());
+            }
+            if self.leak_checker.is_some() {
+                self.message_replacements.push(anonymized.clone());
+            }
+            if first_seen && self.approval.as_ref().is_some_and(|gate| gate.config().requires_approval(&anonymized.entity_type)) {
+                self.first_seen_critical.push((anonymized.entity_type.clone(), anonymized.original_value.clone()));
+            }
+            anonymized_entities.push(anonymized);
+        }
+        self.mapping_store.counters().record_entities(self.direction, anonymized_entities.len());
+        
+        Ok(anonymized_entities)
+    }
+
+    /// Whether a detected value is a fake, of the current message or stored.
+    fn is_fake(&self, entity: &DetectedEntity) -> Result<bool> {
+        let of_message = self.message_replacements.iter()
+            .any(|replaced| replaced.fake_value == entity.original_value && replaced.original_value != replaced.fake_value);
+        Ok(of_message || self.mapping_store.is_fake(&entity.entity_type, &entity.original_value)?)
+    }
+
+    /// With identity linking on, detects the people a message mentions before
+    /// any of its strings is faked, so a name and an email in different fields
+    /// end up in one cluster. LLM results are used only when already cached.
+    fn link_message_identity(&mut self, message: &Value) -> Result<Option<Persona>> {
+        if self.faker_engine.identity().is_none() {
+            return Ok(None);
+        }
+        let mut texts = Vec::new();
+        collect_keyed_texts(message, None, &mut texts);
+        let mut entities = Vec::new();
+        for &(key, text) in &texts {
+            entities.extend(self.detect_for_key(key, text));
+            if !matches!(self.detection_mode, DetectionMode::Regex) {
+                entities.extend(self.cached_llm_entities(text)?.unwrap_or_default());
+            }
+        }
+        let texts: Vec<&str> = texts.into_iter().map(|(_, text)| text).collect();
+        self.identity_persona(&entities, &texts)
+    }
+
+    /// Links the entities of a message that names one person into an identity
+    /// cluster, and returns the fake person its names and emails map to. An
+    /// existing cluster of any member keeps its fake name; a new one takes the
+    /// name's existing fake, or a fresh one in the locale of the text naming
+    /// the person.
+    fn identity_persona(&mut self, entities: &[DetectedEntity], texts: &[&str]) -> Result<Option<Persona>> {
+        let Some(cluster) = self.faker_engine.identity().and_then(|config| identity::cluster(entities, config)) else {
+            return Ok(None);
+        };
+        let mut known = None;
+        for (entity_type, value) in &cluster.members {
+            known = self.mapping_store.identity_cluster(entity_type, value)?;
+            if known.is_some() {
+                break;
+            }
+        }
+        let (cluster_id, fake_name) = match known {
+            Some(known) => known,
+            None => {
+                let fake_name = match self.mapping_store.get_mapping(identity::NAME_TYPE, &cluster.name)? {
+                    Some(fake_name) => fake_name,
+                    None => {
+                        if let Some(text) = texts.iter().find(|text| text.contains(cluster.name.as_str())) {
+                            self.faker_engine.adapt_to(text);
+                        }
+                        self.faker_engine.generate_fake_name()
+                    }
+                };
+                (uuid::Uuid::new_v4().to_string(), fake_name)
+            }
+        };
+        self.mapping_store.link_identity(&cluster_id, &fake_name, &cluster.members)?;
+        Ok(Some(Persona { name: cluster.name, fake_name }))
+    }
+
+    /// Stores a mapping and returns the one actually stored: the other direction
+    /// may have mapped the same value first, and its mapping wins.
+    fn store_first_mapping(&mut self, mut anonymized: AnonymizedEntity) -> Result<AnonymizedEntity> {
+        self.mapping_store.store_mapping(&anonymized)?;
+        if let Some(stored) = self.mapping_store.get_mapping(&anonymized.entity_type, &anonymized.original_value)? {
+            anonymized.fake_value = stored;
+        }
+        Ok(anonymized)
+    }
+
+    /// Fake for a value shared by many entities (a subnet, an email domain),
+    /// kept in the mapping store so every entity maps consistently across both
+    /// directions.
+    fn shared_fake(
+        &mut self,
+        entity_type: &str,
+        original: &str,
+        generate: impl FnOnce(&mut FakerEngine) -> Result<String>,
+    ) -> Result<String> {
+        if let Some(fake) = self.mapping_store.get_mapping(entity_type, original)? {
+            return Ok(fake);
+        }
+        let mapping = AnonymizedEntity {
+            entity_type: entity_type.to_string(),
+            original_value: original.to_string(),
+            fake_value: generate(&mut self.faker_engine)?,
+            mapping_id: uuid::Uuid::new_v4().to_string(),
+        };
+        Ok(self.store_first_mapping(mapping)?.fake_value)
+    }
+}
+
+/// A value `process_json` has yet to visit: its key, and the path segments
+/// it adds below the first `depth` segments of the path, when tracked.
+struct JsonWork<'a> {
+    value: &'a mut Value,
+    key: Option<&'a str>,
+    depth: usize,
+    segments: Vec<JsonPathSegment>,
+}
+
+impl<'a> JsonWork<'a> {
+    fn child(
+        value: &'a mut Value,
+        key: Option<&'a str>,
+        depth: usize,
+        tracked: bool,
+        segments: impl FnOnce() -> Vec<JsonPathSegment>,
+    ) -> Self {
+        Self { value, key, depth, segments: if tracked { segments() } else { Vec::new() } }
+    }
+}
+
+/// A JSON-RPC error answering the message with `id`.
+fn error_response(id: &Value, error_message: &str) -> String {
+    serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {
+            "code": WITHHELD_ERROR_CODE,
+            "message": error_message,
+        },
+    }).to_string()
+}
+
+/// A message held for an operator decision.
+pub(crate) struct HeldMessage {
+    gate: ApprovalGate,
+    /// `None` when the request could not be written; the default applies
+    request: Option<PendingApproval>,
+    processed: String,
+    message: Value,
+    first_seen: Vec<(String, String)>,
+}
+
+impl HeldMessage {
+    async fn wait(self) -> (Self, Resolution) {
+        let resolution = match &self.request {
+            Some(request) => self.gate.wait(request).await,
+            None => Resolution { decision: self.gate.config().on_timeout, timed_out: false },
+        };
+        (self, resolution)
+    }
+}
+
+/// Runs an LLM call until `deadline`, returning `None` if it is cut short.
+async fn within_budget<T>(deadline: Option<Instant>, call: impl Future<Output = T>) -> Option<T> {
+    match deadline {
+        Some(deadline) => tokio::time::timeout_at(deadline, call).await.ok(),
+        None => Some(call.await),
+    }
+}
+
+/// Replaces every string in `value` with `[REDACTED]`, except the values of
+/// `REDACT_ALL_KEPT_KEYS`. Iterative, since it also runs on messages that
+/// failed the depth limit.
+fn redact_all(value: &mut Value) {
+    let mut stack = vec![value];
+    while let Some(value) = stack.pop() {
+        match value {
+            Value::String(text) => *text = REDACTED_TOKEN.to_string(),
+            Value::Array(items) => stack.extend(items.iter_mut()),
+            Value::Object(fields) => stack.extend(fields.iter_mut()
+                .filter(|(key, _)| !REDACT_ALL_KEPT_KEYS.contains(&key.as_str()))
+                .map(|(_, value)| value)),
+            _ => {}
+        }
+    }
+}
+
+/// Groups texts into batches bounded by item count and total length.
+fn pack_batches<'a>(texts: &[&'a str], max_items: usize, max_chars: usize) -> Vec<Vec<&'a str>> {
+    let mut batches: Vec<Vec<&str>> = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+    let mut current_chars = 0;
+
+    for &text in texts {
+        if !current.is_empty() && (current.len() >= max_items.max(1) || current_chars + text.len() > max_chars) {
+            batches.push(std::mem::take(&mut current));
+            current_chars = 0;
+        }
+        current_chars += text.len();
+        current.push(text);
+    }
+    if !current.is_empty() {
+        batches.push(current);
+    }
+    batches
+}
+
+fn is_jsonrpc_protocol_message(json_value: &Value) -> bool {
+    if let Some(obj) = json_value.as_object() {
+        // MCP protocol control messages - skip PII processing
+        if obj.contains_key("protocolVersion") ||
+           obj.contains_key("capabilities") ||
+           obj.contains_key("serverInfo") ||
+           obj.contains_key("clientInfo") {
+            return true;
+        }
+        
+        // JSON-RPC requests - skip PII processing
+        if obj.contains_key("method") && obj.contains_key("id") {
+            return true;
+        }
+        
+        // JSON-RPC error responses - skip PII processing
+        if obj.contains_key("error") && obj.contains_key("id") {
+            return true;
+        }
+        
+        // JSON-RPC responses with results - check if they contain user data
+        if obj.contains_key("result") && obj.contains_key("id") {
+            // If the result contains a "content" field, this is likely tool response data that should be processed
+            if let Some(result) = obj.get("result") {
+                if let Some(result_obj) = result.as_object() {
+                    if result_obj.contains_key("content") {
+                        return false; // Process this for PII - it contains user data
+                    }
+                }
+            }
+            // Otherwise, it's a protocol control response (initialize, tools/list, etc.)
+            return true;
+        }
+        
+        // Non-JSON-RPC messages should be processed
+        false
+    } else {
+        false
+    }
+}
+
+// Collapses exact duplicates (same type and span); partial overlaps are left to OverlapResolver
+fn combine_entities(regex_entities: Vec<DetectedEntity>, llm_entities: Vec<DetectedEntity>) -> Vec<DetectedEntity> {
+    let mut combined = HashMap::new();
+    
+    // Add regex entities first (lower priority)
+    for entity in regex_entities {
+        let key = format!("{}:{}:{}", entity.entity_type, entity.start, entity.end);
+        combined.insert(key, entity);
+    }
+    
+    // LLM entities override regex ones
+    for entity in llm_entities {
+        let key = format!("{}:{}:{}", entity.entity_type, entity.start, entity.end);
+        combined.insert(key, entity);
+    }
+    
+    combined.into_values().collect()
+}
+
+/// Every string of a value with the key it is found under.
+fn collect_keyed_texts<'v>(value: &'v Value, key: Option<&'v str>, texts: &mut Vec<(Option<&'v str>, &'v str)>) {
+    match value {
+        Value::String(text) => texts.push((key, text)),
+        Value::Array(arr) => arr.iter().for_each(|item| collect_keyed_texts(item, key, texts)),
+        Value::Object(obj) => obj.iter().for_each(|(child_key, val)| collect_keyed_texts(val, Some(child_key), texts)),
+        _ => {}
+    }
+}
+
+/// Byte range of an occurrence and the index of the entity it belongs to.
+pub(crate) type ReplacementSpan = (usize, usize, usize);
+
+// Replaces every occurrence of each original value. Occurrences can still
+// overlap (one value may be a substring of another elsewhere in the text), so
+// longer occurrences claim their bytes first and everything is spliced in one pass.
+// With `annotate`, each replacement is wrapped in a review marker. `spans` is
+// scratch space; the result borrows `text` when there is nothing to replace.
+fn apply_replacements<'t>(text: &'t str, entities: &[AnonymizedEntity], annotate: bool, spans: &mut Vec<ReplacementSpan>) -> Cow<'t, str> {
+    let _span = info_span!("replace", entities = entities.len()).entered();
+    spans.clear();
+    for (index, entity) in entities.iter().enumerate().filter(|(_, e)| !e.original_value.is_empty()) {
+        spans.extend(text.match_indices(entity.original_value.as_str())
+            .map(|(start, matched)| (start, start + matched.len(), index)));
+    }
+    if spans.is_empty() {
+        return Cow::Borrowed(text);
+    }
+    spans.sort_unstable_by(|a, b| (b.1 - b.0).cmp(&(a.1 - a.0)).then(a.0.cmp(&b.0)).then(a.2.cmp(&b.2)));
+    
+    // Keep the first of overlapping occurrences, compacting in place
+    let mut kept = 0;
+    for i in 0..spans.len() {
+        let (start, end, _) = spans[i];
+        if !spans[..kept].iter().any(|s| start < s.1 && s.0 < end) {
+            spans[kept] = spans[i];
+            kept += 1;
+        }
+    }
+    spans.truncate(kept);
+    spans.sort_unstable_by_key(|span| span.0);
+    
+    let mut result = String::with_capacity(text.len());
+    let mut last_end = 0;
+    for &(start, end, index) in spans.iter() {
+        let entity = &entities[index];
+        result.push_str(&text[last_end..start]);
+        if annotate {
+            result.push_str(&annotate::marker(&entity.entity_type, &entity.fake_value));
+        } else {
+            result.push_str(&entity.fake_value);
+        }
+        last_end = end;
+    }
+    result.push_str(&text[last_end..]);
+    
+    Cow::Owned(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::approval::{self, ApprovalConfig};
+    use crate::bypass;
+    use crate::config::{DictionaryConfig, MappingConfig};
+    use crate::leak_check::LeakCheckConfig;
+    use crate::mapping::DirectionStatistics;
+    use crate::scheduler::LlmConcurrencyConfig;
+    use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+    use std::sync::Arc;
+    use crate::dates;
+    use crate::fpe::FpeCipher;
+    use std::path::PathBuf;
+
+    fn create_test_pipeline() -> Pipeline {
+        let mut config = Config::default();
+        config.detection.mode = DetectionMode::Regex;
+
+        Pipeline {
+            detection_engine: RegexDetectionEngine::new(&config.detection).unwrap(),
+            ollama_client: OllamaClient::new(OllamaConfig::default(), None).unwrap(),
+            faker_engine: FakerEngine::new(&config.faker),
+            mapping_store: MappingStore::new(MappingConfig {
+                database_path: PathBuf::from(":memory:"),
+                ..MappingConfig::default()
+            }).unwrap(),
+            overlap_resolver: OverlapResolver::new(&config.detection.overlap),
+            stitching: config.detection.stitching.clone(),
+            nested_json: config.detection.nested_json.clone(),
+            nested_depth: 0,
+            base64: config.detection.base64.clone(),
+            model_name: "test-model".to_string(),
+            detection_mode: config.detection.mode,
+            request_tracker: RequestTracker::new(),
+            schema_hints: config.detection.schema_hints.enabled.then(SchemaHints::new),
+            tool_hints: None,
+            audit_log: AuditLog::disabled(),
+            bypass_registry: None,
+            outage_queue: None,
+            quarantine: None,
+            message_types: BTreeSet::new(),
+            message_persona: None,
+            approval: None,
+            first_seen_critical: Vec::new(),
+            leak_checker: LeakChecker::new(&config.leak_check),
+            message_replacements: Vec::new(),
+            message_leaks: BTreeSet::new(),
+            canary: None,
+            debug_diff: None,
+            annotate: false,
+            discovery: None,
+            degradation: DegradationReport::new(),
+            message_degradations: BTreeSet::new(),
+            llm_cache_snapshot: HashMap::new(),
+            scalar_rules: ScalarRules::default(),
+            json_path: Vec::new(),
+            replacement_spans: Vec::new(),
+            message_timeout: None,
+            json_limits: config.processing.json_limits(),
+            latency_budget: None,
+            llm_deadline: None,
+            on_error: OnError::Forward,
+            error_withheld_message: Catalog::default().text("error_withheld").to_string(),
+            error_withheld_request_message: Catalog::default().text("error_withheld_request").to_string(),
+            max_line_bytes: usize::MAX,
+            on_oversized_line: OversizedLine::Drop,
+            framing: Framing::Auto,
+            output_framing: None,
+            session_scope: None,
+            session_generation: 0,
+            control: None,
+            control_seen: (0, 0),
+            replies: None,
+            peer_replies: None,
+            held: None,
+            direction: Direction::Response,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_key_hints_anonymize_unmatched_values() {
+        let mut pipeline = create_test_pipeline();
+        let line = r#"{"customer":{"name":"Jane Roe","ssn":"987654321","note":"call back"},"contacts":[{"Name":"Jane Roe"}]}"#;
+
+        let processed = pipeline.process_line(line).await.unwrap();
+        let value: Value = serde_json::from_str(&processed).unwrap();
+
+        assert_ne!(value["customer"]["name"], "Jane Roe");
+        assert_ne!(value["customer"]["ssn"], "987654321");
+        assert_eq!(value["customer"]["note"], "call back");
+        // Same original under the same type maps to the same fake
+        assert_eq!(value["contacts"][0]["Name"], value["customer"]["name"]);
+    }
+
+    #[tokio::test]
+    async fn test_scalar_rules_scan_numbers_and_booleans() {
+        let mut pipeline = create_test_pipeline();
+        pipeline.scalar_rules = ScalarRules::new(&crate::config::ScalarFieldsConfig {
+            numbers: vec!["$..ssn".to_string(), "$.rows[*].phone".to_string()],
+            booleans: vec!["$..flagged".to_string()],
+        }).unwrap();
+        let line = r#"{"customer":{"ssn":123456789,"age":41},"rows":[{"phone":5551234567,"flagged":true}],"phone":5551234567}"#;
+
+        let processed = pipeline.process_line(line).await.unwrap();
+        let value: Value = serde_json::from_str(&processed).unwrap();
+
+        assert!(value["customer"]["ssn"].is_u64() || value["customer"]["ssn"].is_i64());
+        assert_ne!(value["customer"]["ssn"], 123456789);
+        assert_eq!(value["customer"]["age"], 41);
+        assert!(value["rows"][0]["phone"].is_number());
+        assert_ne!(value["rows"][0]["phone"], 5551234567u64);
+        assert!(value["rows"][0]["flagged"].is_boolean());
+        // Outside the rules, numbers pass through
+        assert_eq!(value["phone"], 5551234567u64);
+    }
+
+    #[tokio::test]
+    async fn test_placeholders_are_numbered_from_the_mapping_store() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.detection.mode = DetectionMode::Regex;
+        config.mapping.database_path = temp_dir.path().join("mappings.db");
+        config.faker.strategies.insert("email".to_string(), AnonymizationStrategy::Tokenize);
+        let line = |email: &str| format!(r#"{{"note":"write to {}"}}"#, email);
+
+        let mut first = Pipeline::new(&config, OllamaConfig::default(), Direction::Request).unwrap();
+        assert!(first.process_line(&line("a@acme.com")).await.unwrap().contains("<<EMAIL_1>>"));
+        assert!(first.process_line(&line("b@acme.com")).await.unwrap().contains("<<EMAIL_2>>"));
+
+        // A second pipeline on the same store continues the sequence and reuses mappings
+        let mut second = Pipeline::new(&config, OllamaConfig::default(), Direction::Response).unwrap();
+        assert!(second.process_line(&line("c@acme.com")).await.unwrap().contains("<<EMAIL_3>>"));
+        assert!(second.process_line(&line("a@acme.com")).await.unwrap().contains("<<EMAIL_1>>"));
+    }
+
+    #[tokio::test]
+    async fn test_fpe_values_are_reversible_and_not_stored() {
+        let mut faker_config = Config::default().faker;
+        faker_config.strategies.insert("ssn".to_string(), AnonymizationStrategy::Fpe);
+        let passphrase = crate::secrets::Passphrase::new("correct horse");
+        let mut pipeline = create_test_pipeline();
+        pipeline.faker_engine = FakerEngine::new(&faker_config).with_fpe(FpeCipher::from_passphrase(&passphrase));
+
+        // Key hints detect the SSN field even though no SSN pattern is configured
+        let processed = pipeline.process_line(r#"{"customer":{"ssn":"123-45-6789"}}"#).await.unwrap();
+        let value: Value = serde_json::from_str(&processed).unwrap();
+        let encrypted = value["customer"]["ssn"].as_str().unwrap();
+
+        assert_ne!(encrypted, "123-45-6789");
+        assert_eq!(FpeCipher::from_passphrase(&passphrase).decrypt_value("ssn", encrypted).unwrap(), "123-45-6789");
+        assert_eq!(pipeline.mapping_store.get_statistics().unwrap().total_mappings, 0);
+
+        // Too short to encrypt: a random fake, stored so it stays the same
+        let line = r#"{"customer":{"ssn":"12-345"}}"#;
+        let first: Value = serde_json::from_str(&pipeline.process_line(line).await.unwrap()).unwrap();
+        let second: Value = serde_json::from_str(&pipeline.process_line(line).await.unwrap()).unwrap();
+        assert_ne!(first["customer"]["ssn"], "12-345");
+        assert_eq!(first["customer"]["ssn"], second["customer"]["ssn"]);
+        assert_eq!(pipeline.mapping_store.get_statistics().unwrap().total_mappings, 1);
+    }
+
+    #[tokio::test]
+    async fn test_message_timeout_forwards_stalled_message() {
+        // An LLM endpoint that accepts connections and never answers
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+
+        let mut pipeline = create_test_pipeline();
+        pipeline.ollama_client = OllamaClient::new(OllamaConfig { enabled: true, endpoint, ..OllamaConfig::default() }, None).unwrap();
+        pipeline.detection_mode = DetectionMode::RegexLlm;
+        pipeline.message_timeout = Some(Duration::from_millis(200));
+
+        assert!(pipeline.process_line(r#"{"note":"call jane.roe@acme.com"}"#).await.is_err());
+        assert_eq!(pipeline.degradation.to_json()["by_kind"]["timed_out"]["response"], 1);
+        assert!(pipeline.degradation.to_json()["by_kind"].get("fail_open").is_none());
+
+        // The next message is processed normally
+        pipeline.detection_mode = DetectionMode::Regex;
+        assert!(!pipeline.process_line(r#"{"note":"call jane.roe@acme.com"}"#).await.unwrap().contains("jane.roe"));
+    }
+
+    #[tokio::test]
+    async fn test_on_error_blocks_or_redacts_unprocessed_messages() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+
+        let mut pipeline = create_test_pipeline();
+        pipeline.ollama_client = OllamaClient::new(OllamaConfig { enabled: true, endpoint, ..OllamaConfig::default() }, None).unwrap();
+        pipeline.detection_mode = DetectionMode::RegexLlm;
+        pipeline.message_timeout = Some(Duration::from_millis(100));
+        let response = r#"{"jsonrpc":"2.0","id":7,"result":{"content":[{"type":"text","text":"call jane.roe@acme.com"}]}}"#;
+
+        pipeline.on_error = OnError::Block;
+        let blocked: Value = serde_json::from_str(&pipeline.process_line(response).await.unwrap()).unwrap();
+        assert_eq!(blocked["id"], 7);
+        assert_eq!(blocked["error"]["code"], WITHHELD_ERROR_CODE);
+        assert_eq!(pipeline.process_line("{not json").await.unwrap(), "");
+
+        pipeline.on_error = OnError::RedactAll;
+        let redacted: Value = serde_json::from_str(&pipeline.process_line(response).await.unwrap()).unwrap();
+        assert_eq!(redacted["jsonrpc"], "2.0");
+        assert_eq!(redacted["result"]["content"][0], serde_json::json!({ "type": "text", "text": REDACTED_TOKEN }));
+
+        let by_kind = &pipeline.degradation.to_json()["by_kind"];
+        assert_eq!(by_kind["withheld"]["response"], 2);
+        assert_eq!(by_kind["redacted_all"]["response"], 1);
+        assert!(by_kind.get("timed_out").is_none() && by_kind.get("fail_open").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_blocked_requests_are_answered_with_an_error() {
+        let mut requests = create_test_pipeline();
+        requests.direction = Direction::Request;
+        requests.on_error = OnError::Block;
+        requests.json_limits = JsonLimits { max_depth: 3, max_nodes: 100 };
+        let mut responses = create_test_pipeline();
+        requests.connect_replies(&mut responses);
+        let request = r#"{"jsonrpc":"2.0","id":11,"method":"tools/call","params":{"name":"send","arguments":{"to":{"email":"jane.roe@acme.com"}}}}"#;
+
+        // Not sent to the server; the client gets an error instead of waiting
+        assert_eq!(requests.process_line(request).await.unwrap(), "");
+        let reply: Value = serde_json::from_str(&responses.peer_replies.as_mut().unwrap().try_recv().unwrap()).unwrap();
+        assert_eq!(reply["id"], 11);
+        assert_eq!(reply["error"]["code"], WITHHELD_ERROR_CODE);
+        assert!(reply["error"]["message"].as_str().unwrap().starts_with("Request withheld"));
+
+        // Notifications expect no reply
+        let notification = r#"{"jsonrpc":"2.0","method":"notifications/message","params":{"data":{"to":{"email":"jane.roe@acme.com"}}}}"#;
+        assert_eq!(requests.process_line(notification).await.unwrap(), "");
+        assert!(responses.peer_replies.as_mut().unwrap().try_recv().is_err());
+        assert_eq!(requests.degradation.to_json()["by_kind"]["withheld"]["request"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_fakes_in_tool_arguments_are_restored() {
+        let mut requests = create_test_pipeline();
+        requests.direction = Direction::Request;
+        requests.mapping_store = MappingStore::new(MappingConfig {
+            database_path: PathBuf::from(":memory:"),
+            restore_in_requests: true,
+            ..MappingConfig::default()
+        }).unwrap();
+        requests.mapping_store.original_key = Some([3u8; 32]);
+        requests.mapping_store.store_mapping(&AnonymizedEntity {
+            entity_type: "email@result.content[0].text".to_string(),
+            original_value: "jane.roe@acme.com".to_string(),
+            fake_value: "kim.lee@example.net".to_string(),
+            mapping_id: "m1".to_string(),
+        }).unwrap();
+
+        let request = r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"send","arguments":{"to":["kim.lee@example.net"],"body":"Hi kim.lee@example.net, cc ann@example.org"}}}"#;
+        let restored: Value = serde_json::from_str(&requests.process_line(request).await.unwrap()).unwrap();
+        assert_eq!(restored["params"]["arguments"], serde_json::json!({
+            "to": ["jane.roe@acme.com"],
+            "body": "Hi jane.roe@acme.com, cc ann@example.org",
+        }));
+
+        // Other requests, and tool calls without known fakes, pass unchanged
+        let list = r#"{"jsonrpc":"2.0","id":5,"method":"tools/list"}"#;
+        assert_eq!(requests.process_line(list).await.unwrap(), list);
+        let unknown = r#"{"jsonrpc":"2.0","id":6,"method":"tools/call","params":{"name":"send","arguments":{"to":"ann@example.org"}}}"#;
+        assert_eq!(requests.process_line(unknown).await.unwrap(), unknown);
+    }
+
+    #[tokio::test]
+    async fn test_messages_over_json_limits_are_never_forwarded_as_is() {
+        let mut pipeline = create_test_pipeline();
+        pipeline.json_limits = JsonLimits { max_depth: 3, max_nodes: 100 };
+        let response = r#"{"jsonrpc":"2.0","id":3,"result":{"content":[{"type":"text","text":"call jane.roe@acme.com"}]}}"#;
+
+        let redacted: Value = serde_json::from_str(&pipeline.process_line(response).await.unwrap()).unwrap();
+        assert_eq!(redacted["result"]["content"][0], serde_json::json!({"type": "text", "text": REDACTED_TOKEN}));
+
+        pipeline.on_error = OnError::Block;
+        let blocked: Value = serde_json::from_str(&pipeline.process_line(response).await.unwrap()).unwrap();
+        assert_eq!(blocked["error"]["code"], WITHHELD_ERROR_CODE);
+
+        pipeline.on_error = OnError::RedactAll;
+        let redacted = pipeline.process_line(response).await.unwrap();
+        assert!(!redacted.contains("jane.roe"), "{}", redacted);
+
+        // Within the limits, the message is processed as usual
+        pipeline.json_limits = JsonLimits { max_depth: 8, max_nodes: 100 };
+        let processed = pipeline.process_line(response).await.unwrap();
+        assert!(!processed.contains("jane.roe") && processed.contains("@"), "{}", processed);
+
+        // As deep as JSON parses, processed without recursing per level
+        pipeline.json_limits = crate::config::ProcessingConfig::default().json_limits();
+        let deep = format!(r#"{{"jsonrpc":"2.0","id":4,"result":{{"content":{}"mail jane.roe@acme.com"{}}}}}"#, "[".repeat(120), "]".repeat(120));
+        let processed = pipeline.process_line(&deep).await.unwrap();
+        assert!(!processed.contains("jane.roe") && processed.contains("@"), "{}", processed);
+    }
+
+    #[tokio::test]
+    async fn test_leak_check_reports_originals_and_refaked_fakes() {
+        let mut pipeline = create_test_pipeline();
+        pipeline.leak_checker = LeakChecker::new(&LeakCheckConfig { enabled: true, ..LeakCheckConfig::default() });
+        // The second address escapes the email pattern but not the leak check
+        let response = r#"{"jsonrpc":"2.0","id":5,"result":{"content":[{"type":"text","text":"mail jane.roe@acme.com or jane.roe@acme .com"}]}}"#;
+
+        let forwarded: Value = serde_json::from_str(&pipeline.process_line(response).await.unwrap()).unwrap();
+        let text = forwarded["result"]["content"][0]["text"].as_str().unwrap();
+        assert!(!text.starts_with("mail jane.roe@acme.com") && text.ends_with("jane.roe@acme .com"), "{}", text);
+        assert_eq!(pipeline.degradation.to_json()["by_kind"]["leaked"]["response"], 1);
+
+        pipeline.leak_checker = LeakChecker::new(&LeakCheckConfig { enabled: true, on_leak: OnError::Block, ..LeakCheckConfig::default() });
+        let blocked: Value = serde_json::from_str(&pipeline.process_line(response).await.unwrap()).unwrap();
+        assert_eq!(blocked["error"]["code"], WITHHELD_ERROR_CODE);
+
+        // A fake that comes through again is faked again, and handled as on_leak says
+        let fake = text.split_whitespace().nth(1).unwrap();
+        let echo = format!(r#"{{"jsonrpc":"2.0","id":6,"result":{{"content":[{{"type":"text","text":"sent to {}"}}]}}}}"#, fake);
+        let blocked: Value = serde_json::from_str(&pipeline.process_line(&echo).await.unwrap()).unwrap();
+        assert_eq!(blocked["error"]["code"], WITHHELD_ERROR_CODE);
+        let by_kind = &pipeline.degradation.to_json()["by_kind"];
+        assert_eq!(by_kind["double_anonymized"]["response"], 1);
+        assert_eq!(by_kind["withheld"]["response"], 2);
+    }
+
+    #[tokio::test]
+    async fn test_canary_is_added_to_changed_responses_only() {
+        use crate::canary::{self, CanaryConfig};
+
+        let mut pipeline = create_test_pipeline();
+        let key = canary::signing_key(&crate::secrets::Passphrase::new("correct horse"));
+        pipeline.canary = Some(Canary::issue(&CanaryConfig::default(), &key));
+        let token = pipeline.canary.as_ref().unwrap().token().to_string();
+
+        let changed = r#"{"jsonrpc":"2.0","id":7,"result":{"content":[{"type":"text","text":"mail jane.roe@acme.com"}]}}"#;
+        let forwarded: Value = serde_json::from_str(&pipeline.process_line(changed).await.unwrap()).unwrap();
+        assert_eq!(forwarded["result"]["_meta"]["trace_id"], token.as_str());
+        assert_eq!(canary::find(&forwarded.to_string(), "ref-", &key), [token]);
+
+        let unchanged = r#"{"jsonrpc":"2.0","id":8,"result":{"content":[{"type":"text","text":"nothing to hide"}]}}"#;
+        assert!(!pipeline.process_line(unchanged).await.unwrap().contains("ref-"));
+    }
+
+    #[tokio::test]
+    async fn test_client_sessions_get_canaries_of_their_own() {
+        use crate::canary::CanaryConfig;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let scope = SessionScope::new(MappingConfig { database_path: dir.path().join("mappings.db"), ..MappingConfig::default() });
+        let canary = Canary::issue(&CanaryConfig::default(), &crate::canary::signing_key(&crate::secrets::Passphrase::new("correct horse")));
+        let mut requests = create_test_pipeline().with_session_scope(Some(scope.clone()));
+        requests.direction = Direction::Request;
+        requests.canary = Some(canary.clone());
+        let mut responses = create_test_pipeline().with_session_scope(Some(scope));
+        responses.canary = Some(canary.clone());
+
+        let initialize = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#;
+        let response = r#"{"jsonrpc":"2.0","id":2,"result":{"content":[{"type":"text","text":"mail jane@acme.com"}]}}"#;
+        let mut tokens = Vec::new();
+        for _ in 0..2 {
+            requests.process_line(initialize).await.unwrap();
+            let forwarded: Value = serde_json::from_str(&responses.process_line(response).await.unwrap()).unwrap();
+            tokens.push(forwarded["result"]["_meta"]["trace_id"].as_str().unwrap().to_string());
+        }
+        assert_ne!(tokens[0], tokens[1]);
+        assert!(!tokens.contains(&canary.token().to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_latency_budget_falls_back_to_regex_for_the_message() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut held = Vec::new();
+            while let Ok((socket, _)) = listener.accept().await {
+                held.push(socket);
+            }
+        });
+
+        let mut pipeline = create_test_pipeline();
+        pipeline.ollama_client = OllamaClient::new(OllamaConfig { enabled: true, endpoint, ..OllamaConfig::default() }, None).unwrap();
+        pipeline.detection_mode = DetectionMode::Llm;
+        pipeline.latency_budget = Some(Duration::from_millis(100));
+
+        let started = std::time::Instant::now();
+        let line = r#"{"first":"call jane.roe@acme.com","second":"or john.doe@acme.com"}"#;
+        let processed = pipeline.process_line(line).await.unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2));
+        assert!(!processed.contains("jane.roe") && !processed.contains("john.doe"));
+
+        let by_kind = &pipeline.degradation.to_json()["by_kind"];
+        assert_eq!(by_kind["latency_budget"]["response"], 1);
+        assert!(by_kind.get("fail_open").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_ip_strategy_maps_subnets_consistently() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.detection.mode = DetectionMode::Regex;
+        config.detection.patterns.insert("ip_address".to_string(), r"\b(?:\d{1,3}\.){3}\d{1,3}\b".into());
+        config.mapping.database_path = temp_dir.path().join("mappings.db");
+        config.faker.strategies.insert("ip_address".to_string(), AnonymizationStrategy::Ip);
+
+        let mut request = Pipeline::new(&config, OllamaConfig::default(), Direction::Request).unwrap();
+        let processed = request.process_line(r#"{"log":"10.1.2.5 -> 10.1.2.9"}"#).await.unwrap();
+        let log = serde_json::from_str::<Value>(&processed).unwrap()["log"].as_str().unwrap().to_string();
+        let (first, second) = log.split_once(" -> ").unwrap();
+        assert!(first.starts_with("10.") && first.ends_with(".5") && first != "10.1.2.5", "{}", log);
+        assert_eq!(first.trim_end_matches('5'), second.trim_end_matches('9'));
+
+        // The other direction reuses the stored subnet
+        let mut response = Pipeline::new(&config, OllamaConfig::default(), Direction::Response).unwrap();
+        let processed = response.process_line(r#"{"log":"10.1.2.77"}"#).await.unwrap();
+        assert!(processed.contains(&format!("{}77", first.trim_end_matches('5'))), "{}", processed);
+    }
+
+    #[tokio::test]
+    async fn test_date_shift_uses_one_offset_for_both_directions() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut config = Config::default();
+        config.detection.mode = DetectionMode::Regex;
+        config.detection.patterns.insert("date".to_string(), r"\b\d{4}-\d{2}-\d{2}\b".into());
+        config.mapping.database_path = temp_dir.path().join("mappings.db");
+        config.faker.strategies.insert("date".to_string(), AnonymizationStrategy::DateShift);
+
+        let mut request = Pipeline::new(&config, OllamaConfig::default(), Direction::Request).unwrap();
+        let processed = request.process_line(r#"{"admitted":"2024-03-15"}"#).await.unwrap();
+        let admitted = serde_json::from_str::<Value>(&processed).unwrap()["admitted"].as_str().unwrap().to_string();
+        assert_ne!(admitted, "2024-03-15");
+
+        let mut response = Pipeline::new(&config, OllamaConfig::default(), Direction::Response).unwrap();
+        let processed = response.process_line(r#"{"discharged":"2024-03-18"}"#).await.unwrap();
+        let discharged = serde_json::from_str::<Value>(&processed).unwrap()["discharged"].as_str().unwrap().to_string();
+        assert_eq!(dates::shift(&admitted, 3).unwrap(), discharged);
+    }
+
+    #[tokio::test]
+    async fn test_llm_cache_is_read_once_per_message() {
+        let mut pipeline = create_test_pipeline();
+        pipeline.detection_mode = DetectionMode::Llm;
+        for name in ["Maria Keller", "Jonas Weber"] {
+            let text = format!("Ticket from {}", name);
+            let entities = vec![DetectedEntity {
+                entity_type: "name".to_string(),
+                original_value: name.to_string(),
+                start: 12,
+                end: text.len(),
+                confidence: 0.9,
+                ..Default::default()
+            }];
+            pipeline.mapping_store.store_llm_cache(&text, &entities, "test-model").unwrap();
+        }
+
+        let line = r#"{"a":"Ticket from Maria Keller","b":["Ticket from Jonas Weber","Ticket from Maria Keller"]}"#;
+        let processed = pipeline.process_line(line).await.unwrap();
+        assert!(!processed.contains("Maria Keller") && !processed.contains("Jonas Weber"), "{}", processed);
+        assert_eq!(pipeline.llm_cache_snapshot.len(), 2);
+        assert!(pipeline.llm_cache_snapshot.values().all(Option::is_some));
+    }
+
+    #[tokio::test]
+    async fn test_dictionary_terms_are_caught_when_the_llm_misses_them() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let roster = dir.path().join("codenames.txt");
+        std::fs::write(&roster, "Bluejay\n").unwrap();
+        let mut config = Config::default();
+        config.detection.dictionaries.push(DictionaryConfig { path: roster, entity_type: "codename".to_string(), case_sensitive: false });
+
+        let mut pipeline = create_test_pipeline();
+        pipeline.detection_engine = RegexDetectionEngine::new(&config.detection).unwrap();
+        pipeline.detection_mode = DetectionMode::Llm;
+        let text = "Status of project bluejay";
+        pipeline.mapping_store.store_llm_cache(text, &[], "test-model").unwrap();
+
+        let line = format!(r#"{{"text":"{}"}}"#, text);
+        let processed = pipeline.process_line(&line).await.unwrap();
+        assert!(!processed.contains("bluejay"), "{}", processed);
+        assert!(processed.contains("REDACTED_CODENAME"), "{}", processed);
+    }
+
+    #[tokio::test]
+    async fn test_identity_clusters_keep_names_and_emails_coherent() {
+        let mut config = Config::default();
+        config.faker.identity.enabled = true;
+        let mut pipeline = create_test_pipeline();
+        pipeline.faker_engine = FakerEngine::new(&config.faker);
+
+        let fakes = |processed: &str| {
+            let value: Value = serde_json::from_str(processed).unwrap();
+            (value["name"].as_str().unwrap().to_string(), value["email"].as_str().unwrap().to_string())
+        };
+        let first = pipeline.process_line(r#"{"email":"maria.k@corp.com","name":"Maria Keller"}"#).await.unwrap();
+        let (fake_name, fake_email) = fakes(&first);
+        assert_ne!(fake_name, "Maria Keller");
+        let user = fake_name.to_lowercase().replace(' ', ".");
+        assert!(fake_email.starts_with(&format!("{}@", user)), "{} / {}", fake_name, fake_email);
+
+        // A new address of the same person follows the cluster's fake name
+        let second = pipeline.process_line(r#"{"name":"Maria Keller","email":"mk@home.net"}"#).await.unwrap();
+        let (same_name, other_email) = fakes(&second);
+        assert_eq!(same_name, fake_name);
+        assert!(other_email.starts_with(&user) && other_email != fake_email, "{}", other_email);
+        assert!(pipeline.mapping_store.identity_cluster("email", "mk@home.net").unwrap().is_some());
+
+        // Several addresses in one message never collapse onto one fake
+        let many = pipeline.process_line(r#"{"name":"Maria Keller","cc":"m1@a.com m2@a.com m3@a.com m4@a.com m5@a.com"}"#).await.unwrap();
+        let value: Value = serde_json::from_str(&many).unwrap();
+        let addresses: std::collections::HashSet<&str> = value["cc"].as_str().unwrap().split(' ').collect();
+        assert_eq!(addresses.len(), 5, "{}", many);
+    }
+
+    /// An LLM endpoint that takes `delay` to find no entities, reporting the
+    /// most generation requests it ever had in flight.
+    async fn spawn_slow_llm(delay: Duration) -> (String, Arc<AtomicUsize>) {
+        use tokio::io::AsyncReadExt;
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let (in_flight, peak) = (Arc::new(AtomicUsize::new(0)), Arc::new(AtomicUsize::new(0)));
+        let reported = peak.clone();
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let (in_flight, peak) = (in_flight.clone(), peak.clone());
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut chunk = [0u8; 4096];
+                    loop {
+                        let Ok(read) = socket.read(&mut chunk).await else { return };
+                        if read == 0 {
+                            return;
+                        }
+                        request.extend_from_slice(&chunk[..read]);
+                        let text = String::from_utf8_lossy(&request).to_string();
+                        let Some(header_end) = text.find("\r\n\r\n") else { continue };
+                        let length = text[..header_end].lines()
+                            .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|n| n.trim().parse::<usize>().unwrap()))
+                            .unwrap_or(0);
+                        if request.len() < header_end + 4 + length {
+                            continue;
+                        }
+                        request.clear();
+                        let body = if text.starts_with("POST") {
+                            let now = in_flight.fetch_add(1, AtomicOrdering::SeqCst) + 1;
+                            peak.fetch_max(now, AtomicOrdering::SeqCst);
+                            tokio::time::sleep(delay).await;
+                            in_flight.fetch_sub(1, AtomicOrdering::SeqCst);
+                            serde_json::json!({ "response": r#"{"entities":[]}"#, "done": true }).to_string()
+                        } else {
+                            r#"{"models":[]}"#.to_string()
+                        };
+                        let response = format!("HTTP/1.1 200 OK\r\ncontent-type: application/json\r\ncontent-length: {}\r\n\r\n{}", body.len(), body);
+                        if socket.write_all(response.as_bytes()).await.is_err() {
+                            return;
+                        }
+                    }
+                });
+            }
+        });
+        (endpoint, reported)
+    }
+
+    #[tokio::test]
+    async fn test_llm_detection_of_independent_strings_runs_concurrently() {
+        let (endpoint, peak) = spawn_slow_llm(Duration::from_millis(200)).await;
+        let mut pipeline = create_test_pipeline();
+        let concurrency = LlmConcurrencyConfig { max_in_flight: 2, ..LlmConcurrencyConfig::default() };
+        pipeline.ollama_client = OllamaClient::new(OllamaConfig { enabled: true, endpoint, concurrency, ..OllamaConfig::default() }, None).unwrap();
+        pipeline.detection_mode = DetectionMode::RegexLlm;
+
+        let line = r#"{"a":"first note","b":"second note","c":["third note","fourth note"]}"#;
+        pipeline.process_line(line).await.unwrap();
+
+        assert_eq!(peak.load(AtomicOrdering::SeqCst), 2);
+        for text in ["first note", "second note", "third note", "fourth note"] {
+            assert!(pipeline.mapping_store.get_llm_cache(text, "test-model").unwrap().is_some(), "{}", text);
+        }
+        assert_eq!(pipeline.degradation.degraded_messages(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_quarantine_samples_only_critical_detections() {
+        let mut pipeline = create_test_pipeline();
+        let config = crate::quarantine::QuarantineConfig {
+            enabled: true,
+            sample_rate: 1.0,
+            path: PathBuf::from(":memory:"),
+            ..Default::default()
+        };
+        pipeline.quarantine = Some(QuarantineStore::open(&config, &crate::secrets::Passphrase::new("test")).unwrap());
+
+        pipeline.process_line(r#"{"note":"mail jane@example.com"}"#).await.unwrap();
+        let critical = r#"{"customer":{"ssn":"987654321"}}"#;
+        let processed = pipeline.process_line(critical).await.unwrap();
+
+        let samples = pipeline.quarantine.as_ref().unwrap().samples(10).unwrap();
+        assert_eq!(samples.len(), 1);
+        assert_eq!((samples[0].original.as_str(), samples[0].processed.as_str()), (critical, processed.as_ref()));
+        assert_eq!(samples[0].entity_types, "ssn");
+    }
+
+    #[tokio::test]
+    async fn test_first_seen_critical_values_wait_for_approval() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut pipeline = create_test_pipeline();
+        let config = ApprovalConfig {
+            enabled: true,
+            timeout_seconds: 2,
+            dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        pipeline.approval = Some(ApprovalGate::new(&config, b"test-key".to_vec()));
+        let response = r#"{"jsonrpc":"2.0","id":7,"result":{"content":[{"type":"text","text":"found"}],"customer":{"ssn":"987654321"}}}"#;
+
+        // Nobody decides: the default withholds it and the value stays unseen
+        let withheld: Value = serde_json::from_str(&pipeline.process_line(response).await.unwrap()).unwrap();
+        assert_eq!((withheld["id"].clone(), withheld["error"]["code"].clone()), (serde_json::json!(7), serde_json::json!(WITHHELD_ERROR_CODE)));
+        assert!(pipeline.mapping_store.get_mapping("ssn", "987654321").unwrap().is_none());
+
+        let dir = temp_dir.path().to_path_buf();
+        let operator = tokio::spawn(async move {
+            loop {
+                if let Some(request) = approval::list_pending(&dir).pop() {
+                    assert_eq!(request.entity_types, vec!["ssn".to_string()]);
+                    return approval::decide(&dir, b"test-key", &request.id, ApprovalDecision::Approve).unwrap();
+                }
+                tokio::time::sleep(Duration::from_millis(20)).await;
+            }
+        });
+        let approved = pipeline.process_line(response).await.unwrap();
+        assert!(operator.await.unwrap());
+        assert!(approved.contains("content") && !approved.contains("987654321"), "{}", approved);
+
+        // Known from now on, so it is not held again
+        assert_eq!(pipeline.process_line(response).await.unwrap(), approved);
+        assert!(approval::list_pending(temp_dir.path()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_withheld_requests_are_answered_through_the_opposite_direction() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let config = ApprovalConfig {
+            enabled: true,
+            timeout_seconds: 1,
+            dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        let mut detection = Config::default().detection;
+        detection.patterns.insert("ssn".to_string(), r"\b\d{3}-\d{2}-\d{4}\b".into());
+        let mut responses = create_test_pipeline();
+        responses.detection_engine = RegexDetectionEngine::new(&detection).unwrap();
+        responses.approval = Some(ApprovalGate::new(&config, b"test-key".to_vec()));
+        let mut requests = create_test_pipeline();
+        requests.direction = Direction::Request;
+        responses.connect_replies(&mut requests);
+        let sampling = serde_json::json!({"jsonrpc": "2.0", "id": "sample-3", "method": "sampling/createMessage", "params": {
+            "messages": [{"role": "user", "content": {"type": "text", "text": "Check the customer with SSN 536-22-1847"}}],
+            "maxTokens": 100,
+        }}).to_string();
+
+        // Nothing reaches the client, and the server gets an error for its request
+        assert_eq!(responses.process_line(&sampling).await.unwrap(), "");
+        let reply: Value = serde_json::from_str(&requests.peer_replies.as_mut().unwrap().try_recv().unwrap()).unwrap();
+        assert_eq!(reply["id"], "sample-3");
+        assert_eq!(reply["error"]["code"], WITHHELD_ERROR_CODE);
+        assert!(reply["error"]["message"].as_str().unwrap().starts_with("Request withheld"));
+    }
+
+    #[tokio::test]
+    async fn test_held_messages_do_not_stall_the_stream() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let mut pipeline = create_test_pipeline();
+        let config = ApprovalConfig {
+            enabled: true,
+            timeout_seconds: 10,
+            dir: temp_dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        pipeline.approval = Some(ApprovalGate::new(&config, b"test-key".to_vec()));
+        let (mut input, reader) = tokio::io::duplex(4096);
+        let (mut writer, output) = tokio::io::duplex(4096);
+        let bridged = tokio::spawn(async move { bridge(reader, &mut writer, &mut pipeline).await });
+        let mut output = BufReader::new(output).lines();
+
+        input.write_all(concat!(
+            r#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text","text":"found"}],"customer":{"ssn":"987654321"}}}"#, "\n",
+            r#"{"jsonrpc":"2.0","id":2,"result":{"content":[{"type":"text","text":"nothing to hide"}]}}"#, "\n",
+        ).as_bytes()).await.unwrap();
+
+        // The second response goes out while the first waits for an operator
+        let first: Value = serde_json::from_str(&output.next_line().await.unwrap().unwrap()).unwrap();
+        assert_eq!(first["id"], 2);
+        let request = loop {
+            if let Some(request) = approval::list_pending(temp_dir.path()).pop() {
+                break request;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        };
+        approval::decide(temp_dir.path(), b"test-key", &request.id, ApprovalDecision::Approve).unwrap();
+        let released = output.next_line().await.unwrap().unwrap();
+        assert!(released.contains(r#""id":1"#) && !released.contains("987654321"), "{}", released);
+
+        drop(input);
+        bridged.await.unwrap().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_bypass_token_skips_processing_for_matching_tool() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let key = b"test-key".to_vec();
+        let token = bypass::SignedBypassToken::issue(
+            &key,
+            bypass::BypassScope::Tool("query_db".to_string()),
+            std::time::Duration::from_secs(600),
+            None,
+        ).unwrap();
+        bypass::write_token(temp_dir.path(), &token).unwrap();
+
+        let mut pipeline = create_test_pipeline();
+        pipeline.bypass_registry = Some(BypassRegistry::new(temp_dir.path().to_path_buf(), key));
+        let tracker = pipeline.request_tracker.clone();
+
+        tracker.classify(Direction::Request, &serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "tools/call", "params": {"name": "query_db"}}));
+        tracker.classify(Direction::Request, &serde_json::json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "other"}}));
+
+        let bypassed = r#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text","text":"mail jane@example.com"}]}}"#;
+        assert_eq!(pipeline.process_line(bypassed).await.unwrap(), bypassed);
+
+        let processed = r#"{"jsonrpc":"2.0","id":2,"result":{"content":[{"type":"text","text":"mail jane@example.com"}]}}"#;
+        assert!(!pipeline.process_line(processed).await.unwrap().contains("jane@example.com"));
+    }
+
+    #[tokio::test]
+    async fn test_sampling_messages_are_processed() {
+        let mut responses = create_test_pipeline();
+        let mut requests = create_test_pipeline().with_request_tracker(responses.request_tracker.clone());
+        requests.direction = Direction::Request;
+        let request = serde_json::json!({"jsonrpc": "2.0", "id": 9, "method": "sampling/createMessage", "params": {
+            "messages": [{"role": "user", "content": {"type": "text", "text": "Summarize the ticket from jane@acme.com"}}],
+            "systemPrompt": "Escalate to ops@acme.com if unsure",
+            "modelPreferences": {"hints": [{"name": "claude-3-sonnet"}]},
+            "maxTokens": 100,
+        }}).to_string();
+
+        let processed: Value = serde_json::from_str(&responses.process_line(&request).await.unwrap()).unwrap();
+        let text = processed["params"]["messages"][0]["content"]["text"].as_str().unwrap();
+        assert!(text.starts_with("Summarize the ticket from ") && !text.contains("jane@acme.com"));
+        assert!(!processed["params"]["systemPrompt"].as_str().unwrap().contains("ops@acme.com"));
+        // Model hints are under a key-hinted name but are not user data
+        assert_eq!(processed["params"]["modelPreferences"]["hints"][0]["name"], "claude-3-sonnet");
+
+        // The client numbers its own requests, and may reuse the server's id
+        let call = r#"{"jsonrpc":"2.0","id":9,"method":"tools/call","params":{"name":"lookup"}}"#;
+        assert_eq!(requests.process_line(call).await.unwrap(), call);
+
+        let response = r#"{"jsonrpc":"2.0","id":9,"result":{"role":"assistant","content":{"type":"text","text":"Reply to jane@acme.com"},"model":"claude-3-sonnet","stopReason":"endTurn"}}"#;
+        let processed: Value = serde_json::from_str(&requests.process_line(response).await.unwrap()).unwrap();
+        assert!(!processed["result"]["content"]["text"].as_str().unwrap().contains("jane@acme.com"));
+        assert_eq!(processed["result"]["model"], "claude-3-sonnet");
+        let tool_result = serde_json::json!({"jsonrpc": "2.0", "id": 9, "result": {"content": []}});
+        assert_eq!(responses.request_tracker.classify(Direction::Response, &tool_result).unwrap().tool.as_deref(), Some("lookup"));
+    }
+
+    #[tokio::test]
+    async fn test_tool_schemas_mark_fields_of_their_results() {
+        let mut pipeline = create_test_pipeline();
+        pipeline.schema_hints = Some(SchemaHints::new());
+        let tracker = pipeline.request_tracker.clone();
+        tracker.classify(Direction::Request, &serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "tools/list"}));
+        let listing = serde_json::json!({"jsonrpc": "2.0", "id": 1, "result": {"tools": [{
+            "name": "get_account",
+            "inputSchema": {"type": "object", "properties": {"account_id": {"type": "string"}}},
+            "outputSchema": {"type": "object", "properties": {"ownerName": {"type": "string"}}},
+        }]}}).to_string();
+        assert_eq!(pipeline.process_line(&listing).await.unwrap(), listing);
+
+        let result = |id: u64| serde_json::json!({"jsonrpc": "2.0", "id": id, "result": {
+            "content": [], "structuredContent": {"account_id": "A-1", "ownerName": "Jane Roe"},
+        }}).to_string();
+        tracker.classify(Direction::Request, &serde_json::json!({"jsonrpc": "2.0", "id": 2, "method": "tools/call", "params": {"name": "get_account"}}));
+        let processed: Value = serde_json::from_str(&pipeline.process_line(&result(2)).await.unwrap()).unwrap();
+        assert_ne!(processed["result"]["structuredContent"]["ownerName"], "Jane Roe");
+        assert_eq!(processed["result"]["structuredContent"]["account_id"], "A-1");
+
+        // Hints only apply to results of the tool that declared them
+        tracker.classify(Direction::Request, &serde_json::json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "other"}}));
+        assert_eq!(pipeline.process_line(&result(3)).await.unwrap(), result(3));
+    }
+
+    #[tokio::test]
+    async fn test_nested_json_strings_are_scanned_and_reserialized() {
+        let document = serde_json::json!({ "customer": { "name": "Jane Roe", "email": "jane@acme.com" }, "total": 12 });
+        let line = serde_json::json!({ "content": [{ "type": "text", "text": document.to_string() }] }).to_string();
+
+        // As plain text, the key hint for the name does not apply
+        let mut pipeline = create_test_pipeline();
+        let processed = pipeline.process_line(&line).await.unwrap();
+        assert!(processed.contains("Jane Roe") && !processed.contains("jane@acme.com"));
+
+        let mut pipeline = create_test_pipeline();
+        pipeline.nested_json.enabled = true;
+        let processed: Value = serde_json::from_str(&pipeline.process_line(&line).await.unwrap()).unwrap();
+        let nested: Value = serde_json::from_str(processed["content"][0]["text"].as_str().unwrap()).unwrap();
+        assert_ne!(nested["customer"]["name"], "Jane Roe");
+        assert_ne!(nested["customer"]["email"], "jane@acme.com");
+        assert_eq!(nested["total"], 12);
+
+        // Beyond max_depth, a document is scanned as text
+        pipeline.nested_json.max_depth = 1;
+        let twice = serde_json::json!({ "text": serde_json::json!({ "inner": document.to_string() }).to_string() }).to_string();
+        let processed = pipeline.process_line(&twice).await.unwrap();
+        assert!(processed.contains("Jane Roe") && !processed.contains("jane@acme.com"));
+    }
+
+    #[tokio::test]
+    async fn test_base64_text_blobs_are_decoded_scanned_and_reencoded() {
+        let text_blob = BASE64.encode("Contact jane@acme.com for access");
+        let image_blob = BASE64.encode([0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a, 0x00]);
+        let line = serde_json::json!({ "result": { "contents": [
+            { "uri": "file:///notes.txt", "mimeType": "text/plain", "blob": text_blob },
+            { "type": "image", "mimeType": "image/png", "data": image_blob },
+        ] } })
+        .to_string();
+
+        let mut pipeline = create_test_pipeline();
+        assert_eq!(pipeline.process_line(&line).await.unwrap(), line);
+
+        pipeline.base64.enabled = true;
+        let processed: Value = serde_json::from_str(&pipeline.process_line(&line).await.unwrap()).unwrap();
+        let decoded = BASE64.decode(processed["result"]["contents"][0]["blob"].as_str().unwrap()).unwrap();
+        let decoded = String::from_utf8(decoded).unwrap();
+        assert!(decoded.starts_with("Contact ") && !decoded.contains("jane@acme.com"));
+        assert_eq!(processed["result"]["contents"][1]["data"], image_blob);
+    }
+
+    #[tokio::test]
+    async fn test_url_safe_and_unpadded_base64_is_decoded_and_kept_as_written() {
+        // Encoded, it has '-' or '_', and would need padding
+        let text = "Contact jane@acme.com about ~~~?>!";
+        let mut pipeline = create_test_pipeline();
+        pipeline.base64.enabled = true;
+        for engine in [&general_purpose::URL_SAFE, &general_purpose::URL_SAFE_NO_PAD, &general_purpose::STANDARD_NO_PAD] {
+            let blob = engine.encode(text);
+            let line = serde_json::json!({ "result": { "contents": [{ "uri": "file:///notes.txt", "blob": blob }] } }).to_string();
+            let processed: Value = serde_json::from_str(&pipeline.process_line(&line).await.unwrap()).unwrap();
+            let processed = processed["result"]["contents"][0]["blob"].as_str().unwrap();
+            let decoded = String::from_utf8(engine.decode(processed).unwrap()).unwrap();
+            assert!(decoded.starts_with("Contact ") && !decoded.contains("jane@acme.com"), "{}", blob);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_stitching_detects_values_split_across_content_blocks() {
+        let mut pipeline = create_test_pipeline();
+        pipeline.stitching.enabled = true;
+        pipeline.stitching.separator = String::new();
+        let line = r#"{"jsonrpc":"2.0","id":3,"result":{"content":[{"type":"text","text":"Reach jane.roe@exa"},{"type":"text","text":"mple.com today"}]}}"#;
+
+        // Unstitched, neither half matches the email pattern
+        let mut unstitched = create_test_pipeline();
+        assert_eq!(unstitched.process_line(line).await.unwrap(), line);
+
+        let processed = pipeline.process_line(line).await.unwrap();
+        let value: Value = serde_json::from_str(&processed).unwrap();
+        let first = value["result"]["content"][0]["text"].as_str().unwrap();
+        let second = value["result"]["content"][1]["text"].as_str().unwrap();
+
+        assert!(first.starts_with("Reach ") && !first.contains("jane.roe"));
+        assert!(second.ends_with(" today") && !second.contains("mple.com"));
+        assert_eq!(value["result"]["content"][1]["type"], "text");
+    }
+
+    #[tokio::test]
+    async fn test_bridge_processes_each_line() {
+        let mut config = Config::default();
+        config.detection.mode = DetectionMode::Regex;
+        config.mapping.database_path = PathBuf::from(":memory:");
+        let mut pipeline = Pipeline::new(&config, OllamaConfig::default(), Direction::Response).unwrap();
+
+        let input = concat!(
+            r#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text","text":"mail jane@example.com"}]}}"#, "\n",
+            "not json\n",
+        );
+        let mut output = Vec::new();
+
+        bridge(input.as_bytes(), &mut output, &mut pipeline).await.unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(!lines[0].contains("jane@example.com"));
+        assert_eq!(lines[1], "not json");
+        // The unparseable line went through unprocessed and is reported as such
+        assert_eq!(pipeline.degradation_report().messages(), 2);
+        assert_eq!(pipeline.degradation_report().degraded_messages(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_bridge_bounds_line_length() {
+        let short = r#"{"note":"mail jane@example.com"}"#;
+        let long = format!(r#"{{"note":"mail john@example.com {}"}}"#, "x".repeat(200));
+        let run = |policy| {
+            let (short, long) = (short.to_string(), long.clone());
+            async move {
+                let mut pipeline = create_test_pipeline();
+                pipeline.max_line_bytes = 64;
+                pipeline.on_oversized_line = policy;
+                // The long line arrives in pieces, as from a pipe
+                let mut reader = tokio_test::io::Builder::new()
+                    .read(format!("{}\n{}", short, &long[..100]).as_bytes())
+                    .read(format!("{}\n{}\n", &long[100..], short).as_bytes())
+                    .build();
+                let mut output = Vec::new();
+                bridge(&mut reader, &mut output, &mut pipeline).await.unwrap();
+                assert_eq!(pipeline.degradation_report().to_json()["by_kind"]["oversized"]["response"], 1);
+                String::from_utf8(output).unwrap()
+            }
+        };
+
+        let dropped = run(OversizedLine::Drop).await;
+        assert_eq!(dropped.lines().count(), 2);
+        assert!(!dropped.contains("example.com"));
+
+        let truncated = run(OversizedLine::Truncate).await;
+        let lines: Vec<&str> = truncated.lines().collect();
+        assert_eq!(lines.len(), 3);
+        // The first 64 bytes, with the email in them concealed
+        assert!(lines[1].starts_with(r#"{"note":"mail "#) && lines[1].ends_with(&"x".repeat(33)));
+        assert!(!lines[1].contains("john@example.com") && lines[1].len() < 100);
+
+        let forwarded = run(OversizedLine::Forward).await;
+        assert_eq!(forwarded.lines().nth(1), Some(long.as_str()));
+    }
+
+    #[tokio::test]
+    async fn test_failed_truncation_does_not_end_the_bridge() {
+        let long = format!(r#"{{"note":"mail john@example.com {}"}}"#, "x".repeat(200));
+        let input = format!("{}\n{{\"id\":1}}\n", long);
+        let run = |on_error| {
+            let input = input.clone();
+            async move {
+                let dir = tempfile::TempDir::new().unwrap();
+                let database_path = dir.path().join("mappings.db");
+                let mut pipeline = create_test_pipeline();
+                pipeline.mapping_store = MappingStore::new(MappingConfig { database_path: database_path.clone(), ..MappingConfig::default() }).unwrap();
+                // Storing the email's mapping fails
+                rusqlite::Connection::open(&database_path).unwrap().execute_batch("DROP TABLE entity_mappings").unwrap();
+                pipeline.max_line_bytes = 64;
+                pipeline.on_oversized_line = OversizedLine::Truncate;
+                pipeline.on_error = on_error;
+                let mut output = Vec::new();
+                bridge(input.as_bytes(), &mut output, &mut pipeline).await.unwrap();
+                String::from_utf8(output).unwrap()
+            }
+        };
+
+        let forwarded = run(OnError::Forward).await;
+        assert_eq!(forwarded.lines().collect::<Vec<_>>(), [&long[..64], r#"{"id":1}"#]);
+        assert_eq!(run(OnError::Block).await, "{\"id\":1}\n");
+    }
+
+    #[tokio::test]
+    async fn test_initialize_starts_a_fresh_mapping_session() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mapping = MappingConfig {
+            database_path: dir.path().join("mappings.db"),
+            namespace: Some("server".to_string()),
+            ..MappingConfig::default()
+        };
+        let scope = SessionScope::new(mapping.clone());
+        let mut requests = create_test_pipeline().with_session_scope(Some(scope.clone()));
+        requests.direction = Direction::Request;
+        let mut responses = create_test_pipeline().with_session_scope(Some(scope));
+
+        let message = r#"{"jsonrpc":"2.0","id":2,"result":{"content":[{"type":"text","text":"mail jane@acme.com"}]}}"#;
+        let initialize = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#;
+        requests.process_line(initialize).await.unwrap();
+        responses.process_line(message).await.unwrap();
+        assert_eq!(responses.mapping_store.get_statistics().unwrap().total_mappings, 1);
+
+        requests.process_line(initialize).await.unwrap();
+        responses.process_line(message).await.unwrap();
+        // The second session mapped the value again, in a namespace of its own
+        assert_eq!(responses.session_generation, 2);
+        assert_eq!(responses.mapping_store.get_statistics().unwrap().total_mappings, 1);
+        assert_eq!(MappingStore::new(mapping).unwrap().get_statistics().unwrap().total_mappings, 0);
+    }
+
+    #[tokio::test]
+    async fn test_runtime_control_applies_between_messages() {
+        let control = RuntimeControl::new();
+        let mut pipeline = create_test_pipeline();
+        pipeline.control = Some(control.clone());
+        let message = r#"{"note":"mail jane@acme.com, badge EMP-0042"}"#;
+
+        control.set_detect_only(true);
+        assert_eq!(pipeline.process_line(message).await.unwrap(), message);
+        assert_eq!(pipeline.mapping_store.counters().snapshot().response.entities_anonymized, 1);
+
+        control.set_detect_only(false);
+        let mut config = Config::default();
+        config.detection.mode = DetectionMode::Regex;
+        config.detection.patterns.insert("employee_id".to_string(), r"\bEMP-\d{4}\b".into());
+        control.publish_config(config);
+        let processed = pipeline.process_line(message).await.unwrap();
+        assert!(!processed.contains("jane@acme.com") && !processed.contains("EMP-0042"));
+    }
+
+    #[tokio::test]
+    async fn test_bridge_detects_content_length_framing() {
+        let unchanged = r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#;
+        let changed = "{\n  \"note\": \"mail jane@acme.com\"\n}";
+        let input = format!(
+            "Content-Length: {}\r\nContent-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n{}content-length: {}\r\n\r\n{}",
+            unchanged.len(), unchanged, changed.len(), changed,
+        );
+
+        let mut pipeline = create_test_pipeline();
+        let mut output = Vec::new();
+        bridge(input.as_bytes(), &mut output, &mut pipeline).await.unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        let (first, rest) = output.split_once("\r\n\r\n").unwrap();
+        assert_eq!(first, format!("Content-Length: {}", unchanged.len()));
+        assert!(rest.starts_with(unchanged));
+        let (header, body) = rest[unchanged.len()..].split_once("\r\n\r\n").unwrap();
+        assert_eq!(header, format!("Content-Length: {}", body.len()));
+        assert!(!body.contains("jane@acme.com"));
+        assert!(serde_json::from_str::<Value>(body).is_ok());
+
+        // Written newline-delimited, the pretty-printed body goes on one line
+        let mut pipeline = create_test_pipeline();
+        pipeline.output_framing = Some(Framing::Newline);
+        let mut output = Vec::new();
+        bridge(input.as_bytes(), &mut output, &mut pipeline).await.unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0], unchanged);
+        assert!(lines[1].contains(r#""note":"#) && !lines[1].contains("jane@acme.com"));
+    }
+
+    #[tokio::test]
+    async fn test_bridge_bounds_content_length_frames() {
+        let long = format!(r#"{{"note":"mail john@example.com {}"}}"#, "x".repeat(200));
+        let input = format!("Content-Length: {}\r\n\r\n{}Content-Length: 2\r\n\r\n{{}}", long.len(), long);
+
+        let mut pipeline = create_test_pipeline();
+        pipeline.max_line_bytes = 64;
+        pipeline.on_oversized_line = OversizedLine::Forward;
+        let mut output = Vec::new();
+        bridge(input.as_bytes(), &mut output, &mut pipeline).await.unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), input);
+
+        pipeline.on_oversized_line = OversizedLine::Drop;
+        let mut output = Vec::new();
+        bridge(input.as_bytes(), &mut output, &mut pipeline).await.unwrap();
+        assert_eq!(String::from_utf8(output).unwrap(), "Content-Length: 2\r\n\r\n{}");
+    }
+
+    #[tokio::test]
+    async fn test_endless_frame_headers_are_refused() {
+        let input = format!("Content-Length: 2\r\nContent-Type: {}", "x".repeat(4 * MAX_FRAME_HEADER_BYTES));
+        let mut pipeline = create_test_pipeline();
+        let mut output = Vec::new();
+        let error = bridge(input.as_bytes(), &mut output, &mut pipeline).await.unwrap_err();
+        assert!(error.to_string().contains("frame header longer than"), "{}", error);
+        assert!(output.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_bridge_forwards_unchanged_messages_byte_for_byte() {
+        let mut pipeline = create_test_pipeline();
+        // Key order, number spelling, escapes and spacing a reserialization would normalize
+        let unchanged = concat!(
+            r#"{ "z": 1.50, "a": [1e3, -0.0, 12345678901234567890123], "s": "caf\u00e9 \/ ok" }"#, "\r\n",
+            r#"{"jsonrpc":"2.0","id":1,"method":"tools/list"}"#, "\n",
+            "   \n",
+        );
+        let input = format!("{}{}", unchanged, r#"{"note":"mail jane@acme.com","n":1.50}"#);
+
+        let mut output = Vec::new();
+        bridge(input.as_bytes(), &mut output, &mut pipeline).await.unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.starts_with(unchanged), "{:?}", output);
+        assert!(!output[unchanged.len()..].contains("jane@acme.com"));
+    }
+
+    #[test]
+    fn test_collect_and_pack_llm_texts() {
+        let pipeline = create_test_pipeline();
+        let message = serde_json::json!({
+            "rows": [{"name": "Al", "note": "call Jane Roe"}, {"name": "Bo", "note": "call Jane Roe"}],
+            "id": "abc"
+        });
+
+        let mut texts = Vec::new();
+        pipeline.collect_llm_texts(&message, None, &mut texts);
+        // Short values only qualify under a key hint; "abc" does not
+        assert_eq!(texts, vec!["Al", "call Jane Roe", "Bo", "call Jane Roe"]);
+
+        let batches = pack_batches(&["aaaa", "bbbb", "cccc", "dddddddddd"], 2, 9);
+        assert_eq!(batches, vec![vec!["aaaa", "bbbb"], vec!["cccc"], vec!["dddddddddd"]]);
+    }
+
+    #[test]
+    fn test_apply_replacements_does_not_garble_nested_values() {
+        let entities = vec![
+            AnonymizedEntity {
+                entity_type: "name".to_string(),
+                original_value: "john".to_string(),
+                fake_value: "Mike".to_string(),
+                mapping_id: "1".to_string(),
+            },
+            AnonymizedEntity {
+                entity_type: "email".to_string(),
+                original_value: "john@acme.com".to_string(),
+                fake_value: "mike.w@example.net".to_string(),
+                mapping_id: "2".to_string(),
+            },
+        ];
+
+        let mut spans = Vec::new();
+        let result = apply_replacements("Ask john at john@acme.com", &entities, false, &mut spans);
+
+        assert_eq!(result, "Ask Mike at mike.w@example.net");
+        assert!(matches!(apply_replacements("Ask jane", &entities, false, &mut spans), Cow::Borrowed("Ask jane")));
+    }
+
+    #[tokio::test]
+    async fn test_unchanged_messages_are_forwarded_without_copying() {
+        let mut pipeline = create_test_pipeline();
+
+        let clean = r#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text","text":"All checks passed"}]}}"#;
+        assert!(matches!(pipeline.process_line(clean).await.unwrap(), Cow::Borrowed(line) if line == clean));
+        let protocol = r#"{"jsonrpc":"2.0","id":2,"method":"tools/list"}"#;
+        assert!(matches!(pipeline.process_line(protocol).await.unwrap(), Cow::Borrowed(_)));
+
+        let with_pii = r#"{"jsonrpc":"2.0","id":3,"result":{"content":[{"type":"text","text":"mail jane@acme.com"}]}}"#;
+        assert!(matches!(pipeline.process_line(with_pii).await.unwrap(), Cow::Owned(line) if !line.contains("jane@acme.com")));
+    }
+
+    #[tokio::test]
+    async fn test_runtime_counters_track_mapping_reuse_and_bytes() {
+        let mut pipeline = create_test_pipeline();
+        let line = r#"{"note":"mail jane@acme.com"}"#;
+        pipeline.process_line(line).await.unwrap();
+        pipeline.process_line(line).await.unwrap();
+
+        let runtime = pipeline.mapping_store.counters().snapshot();
+        assert_eq!((runtime.mapping_hits, runtime.mapping_misses), (1, 1));
+        assert_eq!(runtime.mapping_hit_rate(), Some(0.5));
+        assert_eq!(runtime.response.entities_anonymized, 2);
+        assert_eq!(runtime.response.bytes_processed, 2 * line.len() as u64);
+        assert_eq!(runtime.request, DirectionStatistics::default());
+    }
+}
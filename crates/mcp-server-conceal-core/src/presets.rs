@@ -0,0 +1,166 @@
+//! Policy presets
+//!
+//! A preset is a complete configuration to start from: `preset = "strict"` in
+//! the config file, or `--preset strict`, fills in every setting the file
+//! leaves out. What the file does set wins, table by table, so a file can hold
+//! just the preset and the few settings that differ from it.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::config::{merge_tables, AnonymizationStrategy, Config, DetectionMode, OnError};
+use crate::pattern_packs;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    /// Every pattern pack, LLM detection, names and encoded payloads scanned,
+    /// secrets redacted, and messages that fail processing blocked
+    Strict,
+    /// Personal and financial patterns with LLM detection; messages that fail
+    /// processing are forwarded fully redacted
+    Balanced,
+    /// Personal patterns only, regex detection without an LLM
+    Minimal,
+}
+
+impl Preset {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Preset::Strict => "strict",
+            Preset::Balanced => "balanced",
+            Preset::Minimal => "minimal",
+        }
+    }
+
+    pub fn config(&self) -> Config {
+        let mut config = Config {
+            preset: Some(*self),
+            ..Config::default()
+        };
+        let detection = &mut config.detection;
+        match self {
+            Preset::Strict => {
+                detection.mode = DetectionMode::RegexLlm;
+                detection.pattern_packs = packs(&["personal", "secrets", "financial"]);
+                detection.confidence_threshold = 0.6;
+                detection.names.enabled = true;
+                detection.nested_json.enabled = true;
+                detection.base64.enabled = true;
+                detection.entropy.enabled = true;
+                let secrets = pattern_packs::pack("secrets").unwrap_or_default();
+                config.faker.strategies.extend(secrets.iter().map(|(name, _)| (name.to_string(), AnonymizationStrategy::Redact)));
+                config.faker.strategies.insert(detection.entropy.entity_type.clone(), AnonymizationStrategy::Redact);
+                config.processing.on_error = OnError::Block;
+                set_llm(&mut config, true);
+            }
+            Preset::Balanced => {
+                detection.mode = DetectionMode::RegexLlm;
+                detection.pattern_packs = packs(&["personal", "financial"]);
+                detection.confidence_threshold = 0.8;
+                config.processing.on_error = OnError::RedactAll;
+                set_llm(&mut config, true);
+            }
+            Preset::Minimal => {
+                detection.mode = DetectionMode::Regex;
+                detection.pattern_packs = packs(&["personal"]);
+                detection.confidence_threshold = 0.9;
+                config.processing.on_error = OnError::Forward;
+                set_llm(&mut config, false);
+            }
+        }
+        config
+    }
+}
+
+/// Lays a configuration document over `preset`, or else over the preset it
+/// names, if any.
+pub fn apply(value: &mut toml::Value, preset: Option<Preset>) -> Result<()> {
+    let preset = match preset {
+        Some(preset) => preset,
+        None => match value.get("preset") {
+            Some(name) => name.as_str().unwrap_or_default().parse()?,
+            None => return Ok(()),
+        },
+    };
+    let mut base = toml::Value::try_from(preset.config())?;
+    merge_tables(&mut base, std::mem::replace(value, toml::Value::Boolean(false)));
+    *value = base;
+    if let Some(table) = value.as_table_mut() {
+        table.insert("preset".to_string(), toml::Value::String(preset.as_str().to_string()));
+    }
+    Ok(())
+}
+
+fn packs(names: &[&str]) -> Vec<String> {
+    names.iter().map(|name| name.to_string()).collect()
+}
+
+fn set_llm(config: &mut Config, enabled: bool) {
+    if let Some(llm) = config.llm.as_mut() {
+        llm.enabled = enabled;
+    }
+}
+
+impl FromStr for Preset {
+    type Err = anyhow::Error;
+
+    fn from_str(name: &str) -> Result<Self> {
+        match name {
+            "strict" => Ok(Preset::Strict),
+            "balanced" => Ok(Preset::Balanced),
+            "minimal" => Ok(Preset::Minimal),
+            _ => Err(anyhow::anyhow!("Unknown preset '{}' (available: strict, balanced, minimal)", name)),
+        }
+    }
+}
+
+impl fmt::Display for Preset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_presets_are_valid_configurations() {
+        for preset in [Preset::Strict, Preset::Balanced, Preset::Minimal] {
+            let mut config = preset.config();
+            config.mapping.database_path = std::env::temp_dir().join("mappings.db");
+            config.validate().unwrap();
+            assert_eq!(preset.as_str().parse::<Preset>().unwrap(), preset);
+        }
+        let strict = Preset::Strict.config();
+        assert_eq!(strict.processing.on_error, OnError::Block);
+        assert!(strict.detection.pattern_packs.contains(&"secrets".to_string()));
+        assert!(strict.detection.entropy.enabled);
+        assert_eq!(strict.faker.strategies["aws_access_key"], AnonymizationStrategy::Redact);
+        assert!(!Preset::Minimal.config().llm.unwrap().enabled);
+    }
+
+    #[test]
+    fn test_file_settings_win_over_the_preset() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = dir.path().join("config.toml");
+        std::fs::write(&path, format!(
+            "preset = \"strict\"\n[processing]\non_error = \"redact_all\"\n[detection.patterns]\nticket = 'TKT-\\d+'\n[mapping]\ndatabase_path = '{}'\n",
+            dir.path().join("mappings.db").display()
+        )).unwrap();
+
+        let config = Config::load(&path, None, None).unwrap();
+        assert_eq!(config.preset, Some(Preset::Strict));
+        assert_eq!(config.processing.on_error, OnError::RedactAll);
+        assert_eq!(config.detection.confidence_threshold, 0.6);
+        assert!(config.detection.patterns.contains_key("ticket"));
+        assert!(config.detection.patterns.contains_key("email"));
+
+        let minimal = Config::load(&path, None, Some(Preset::Minimal)).unwrap();
+        assert_eq!(minimal.preset, Some(Preset::Minimal));
+        assert!(matches!(minimal.detection.mode, DetectionMode::Regex));
+    }
+}
@@ -9,6 +9,12 @@ use tracing::warn;
 use crate::config::Config;
 
 const BUILTIN_PROMPT: &str = include_str!("templates/builtin_prompt.md");
+/// Templates for texts in other languages, named as in `language.prompts`
+const BUILTIN_LANGUAGE_PROMPTS: [(&str, &str); 3] = [
+    ("default_fr", include_str!("templates/builtin_prompt_fr.md")),
+    ("default_de", include_str!("templates/builtin_prompt_de.md")),
+    ("default_es", include_str!("templates/builtin_prompt_es.md")),
+];
 
 #[derive(Clone)]
 pub struct PromptLoader {
@@ -27,6 +33,12 @@ impl PromptLoader {
         if !default_prompt_path.exists() {
             std::fs::write(&default_prompt_path, BUILTIN_PROMPT)?;
         }
+        for (name, prompt) in BUILTIN_LANGUAGE_PROMPTS {
+            let path = prompts_dir.join(format!("{}.md", name));
+            if !path.exists() {
+                std::fs::write(&path, prompt)?;
+            }
+        }
         
         Ok(Self { prompts_dir })
     }
@@ -39,6 +51,9 @@ impl PromptLoader {
                 match std::fs::read_to_string(&prompt_path) {
                     Ok(content) => Ok(content),
                     Err(_) => {
+                        if let Some((_, prompt)) = BUILTIN_LANGUAGE_PROMPTS.iter().find(|(builtin, _)| builtin == name) {
+                            return Ok(prompt.to_string());
+                        }
                         warn!("Prompt template '{}' not found, using built-in", name);
                         Ok(BUILTIN_PROMPT.to_string())
                     }
@@ -93,6 +108,17 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_language_prompts_are_built_in() {
+        let loader = PromptLoader::new().unwrap();
+        for name in ["default_fr", "default_de", "default_es"] {
+            let prompt = loader.load_prompt(Some(&name.to_string())).unwrap();
+            assert!(prompt.contains("{text}") && prompt.contains("person_name"), "{}", name);
+            assert!(!prompt.contains("Built-in PII Detection Prompt\n"), "{}", name);
+        }
+        assert!(loader.load_prompt(Some(&"default_fr".to_string())).unwrap().contains("français"));
+    }
+
     #[test]
     fn test_nonexistent_prompt_fallback() {
         let loader = PromptLoader::new().unwrap();
@@ -0,0 +1,143 @@
+//! MCP/JSON-RPC message inspection shared by both proxy directions
+//!
+//! Responses only carry the request `id`, so the side a request passes records
+//! what it was (method, tool name) and the opposite side looks it up when the
+//! matching response arrives. Client and server number their requests
+//! independently, so requests are told apart by the direction they took.
+
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::pipeline::Direction;
+
+/// Upper bound on outstanding requests, so ids that never get a response
+/// (notifications misdetected as requests, crashed servers) cannot grow the map forever.
+const MAX_PENDING_REQUESTS: usize = 10_000;
+
+/// Server-initiated request asking the client's LLM for a completion.
+pub const SAMPLING_METHOD: &str = "sampling/createMessage";
+
+/// Parts of sampling requests and their responses that carry conversation
+/// content. The rest (model preferences, token limits, stop reason) is
+/// protocol control data and is forwarded as is.
+pub const SAMPLING_CONTENT: &[&str] = &["/params/messages", "/params/systemPrompt", "/result/content"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestInfo {
+    pub method: String,
+    /// Tool name for `tools/call` requests.
+    pub tool: Option<String>,
+}
+
+impl RequestInfo {
+    pub fn is_sampling(&self) -> bool {
+        self.method == SAMPLING_METHOD
+    }
+
+    pub fn from_request(message: &Value) -> Option<Self> {
+        let method = message.get("method")?.as_str()?.to_string();
+        let tool = if method == "tools/call" {
+            message.pointer("/params/name").and_then(Value::as_str).map(str::to_string)
+        } else {
+            None
+        };
+        Some(Self { method, tool })
+    }
+}
+
+/// JSON-RPC ids may be strings or numbers; normalize to one key space.
+pub fn message_id(message: &Value) -> Option<String> {
+    match message.get("id")? {
+        Value::String(id) => Some(format!("s:{}", id)),
+        Value::Number(id) => Some(format!("n:{}", id)),
+        _ => None,
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct RequestTracker {
+    /// Keyed by the direction the request took and its id
+    pending: Arc<Mutex<HashMap<(Direction, String), RequestInfo>>>,
+}
+
+impl RequestTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Remembers a request seen in `direction` so its response can be
+    /// attributed later.
+    pub fn record_request(&self, direction: Direction, message: &Value) -> Option<RequestInfo> {
+        let id = (direction, message_id(message)?);
+        let info = RequestInfo::from_request(message)?;
+        if let Ok(mut pending) = self.pending.lock() {
+            if pending.len() >= MAX_PENDING_REQUESTS {
+                pending.clear();
+            }
+            pending.insert(id, info.clone());
+        }
+        Some(info)
+    }
+
+    /// Returns (and forgets) the request a response seen in `direction`
+    /// belongs to, which went the opposite way.
+    pub fn take_response(&self, direction: Direction, message: &Value) -> Option<RequestInfo> {
+        if message.get("method").is_some() {
+            return None;
+        }
+        let id = (direction.opposite(), message_id(message)?);
+        self.pending.lock().ok()?.remove(&id)
+    }
+
+    /// Attributes any message: requests describe themselves, responses are looked up.
+    pub fn classify(&self, direction: Direction, message: &Value) -> Option<RequestInfo> {
+        if message.get("method").is_some() {
+            self.record_request(direction, message)
+                .or_else(|| RequestInfo::from_request(message))
+        } else {
+            self.take_response(direction, message)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_response_is_attributed_to_request() {
+        let tracker = RequestTracker::new();
+        let request = json!({"jsonrpc": "2.0", "id": 7, "method": "tools/call", "params": {"name": "query_db"}});
+        let response = json!({"jsonrpc": "2.0", "id": 7, "result": {"content": []}});
+
+        let info = tracker.classify(Direction::Request, &request).unwrap();
+        assert_eq!(info.tool.as_deref(), Some("query_db"));
+
+        let attributed = tracker.classify(Direction::Response, &response).unwrap();
+        assert_eq!(attributed, info);
+        assert!(tracker.classify(Direction::Response, &response).is_none());
+    }
+
+    #[test]
+    fn test_string_and_numeric_ids_do_not_collide() {
+        let tracker = RequestTracker::new();
+        tracker.record_request(Direction::Request, &json!({"id": 1, "method": "tools/list"}));
+        tracker.record_request(Direction::Request, &json!({"id": "1", "method": "resources/list"}));
+
+        assert_eq!(tracker.take_response(Direction::Response, &json!({"id": "1", "result": {}})).unwrap().method, "resources/list");
+        assert_eq!(tracker.take_response(Direction::Response, &json!({"id": 1, "result": {}})).unwrap().method, "tools/list");
+    }
+
+    #[test]
+    fn test_client_and_server_ids_do_not_collide() {
+        let tracker = RequestTracker::new();
+        tracker.record_request(Direction::Request, &json!({"id": 1, "method": "tools/call", "params": {"name": "query_db"}}));
+        tracker.record_request(Direction::Response, &json!({"id": 1, "method": SAMPLING_METHOD}));
+
+        // Each response is matched with the request that went the other way
+        assert!(tracker.take_response(Direction::Request, &json!({"id": 1, "result": {}})).unwrap().is_sampling());
+        assert_eq!(tracker.take_response(Direction::Response, &json!({"id": 1, "result": {}})).unwrap().tool.as_deref(), Some("query_db"));
+    }
+}
@@ -0,0 +1,192 @@
+//! LLM backends and the remote-endpoint guard
+//!
+//! Detection normally runs against a local Ollama, so raw text never leaves the
+//! machine. Hosted APIs (Anthropic, OpenAI) are supported for deployments
+//! without a GPU, but sending unredacted PII to a third party changes the
+//! threat model: any endpoint that is not on this host is refused unless
+//! `llm.allow_remote = true`. API keys are read from the environment, never
+//! from the config file.
+
+use crate::config::LlmOutputFormat;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::net::IpAddr;
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+const MAX_OUTPUT_TOKENS: u32 = 1024;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LlmProvider {
+    #[default]
+    Ollama,
+    Anthropic,
+    #[serde(rename = "openai")]
+    OpenAi,
+}
+
+impl LlmProvider {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LlmProvider::Ollama => "ollama",
+            LlmProvider::Anthropic => "anthropic",
+            LlmProvider::OpenAi => "openai",
+        }
+    }
+
+    pub fn is_hosted(&self) -> bool {
+        !matches!(self, LlmProvider::Ollama)
+    }
+
+    /// Environment variable holding the API key when `llm.api_key_env` is unset.
+    pub fn default_api_key_env(&self) -> Option<&'static str> {
+        match self {
+            LlmProvider::Ollama => None,
+            LlmProvider::Anthropic => Some("ANTHROPIC_API_KEY"),
+            LlmProvider::OpenAi => Some("OPENAI_API_KEY"),
+        }
+    }
+
+    pub fn generate_url(&self, endpoint: &str) -> String {
+        let endpoint = endpoint.trim_end_matches('/');
+        match self {
+            LlmProvider::Ollama => format!("{}/api/generate", endpoint),
+            LlmProvider::Anthropic => format!("{}/v1/messages", endpoint),
+            LlmProvider::OpenAi => format!("{}/v1/chat/completions", endpoint),
+        }
+    }
+
+    pub fn models_url(&self, endpoint: &str) -> String {
+        let endpoint = endpoint.trim_end_matches('/');
+        match self {
+            LlmProvider::Ollama => format!("{}/api/tags", endpoint),
+            LlmProvider::Anthropic | LlmProvider::OpenAi => format!("{}/v1/models", endpoint),
+        }
+    }
+
+    pub fn auth_headers(&self, api_key: &str) -> Vec<(&'static str, String)> {
+        match self {
+            LlmProvider::Ollama => Vec::new(),
+            LlmProvider::Anthropic => vec![
+                ("x-api-key", api_key.to_string()),
+                ("anthropic-version", ANTHROPIC_VERSION.to_string()),
+            ],
+            LlmProvider::OpenAi => vec![("authorization", format!("Bearer {}", api_key))],
+        }
+    }
+
+    /// Request body for a hosted provider. `schema` is used by OpenAI when
+    /// `output_format = "schema"`; Anthropic relies on the prompt alone.
+    pub fn request_body(&self, model: &str, prompt: &str, format: LlmOutputFormat, schema: Value) -> Value {
+        let messages = json!([{ "role": "user", "content": prompt }]);
+        match self {
+            LlmProvider::Ollama => json!({ "model": model, "prompt": prompt, "stream": false }),
+            LlmProvider::Anthropic => json!({
+                "model": model,
+                "max_tokens": MAX_OUTPUT_TOKENS,
+                "temperature": 0.0,
+                "messages": messages,
+            }),
+            LlmProvider::OpenAi => {
+                let mut body = json!({
+                    "model": model,
+                    "max_tokens": MAX_OUTPUT_TOKENS,
+                    "temperature": 0.0,
+                    "messages": messages,
+                });
+                match format {
+                    LlmOutputFormat::None => {}
+                    LlmOutputFormat::Json => body["response_format"] = json!({ "type": "json_object" }),
+                    LlmOutputFormat::Schema => {
+                        body["response_format"] = json!({
+                            "type": "json_schema",
+                            "json_schema": { "name": "detection", "schema": schema },
+                        });
+                    }
+                }
+                body
+            }
+        }
+    }
+
+    /// Extracts the generated text from a hosted provider's reply.
+    pub fn response_text(&self, response: &Value) -> Result<String> {
+        let text = match self {
+            LlmProvider::Ollama => response["response"].as_str().map(str::to_string),
+            LlmProvider::Anthropic => response["content"].as_array().map(|blocks| {
+                blocks.iter()
+                    .filter(|block| block["type"] == "text")
+                    .filter_map(|block| block["text"].as_str())
+                    .collect::<String>()
+            }),
+            LlmProvider::OpenAi => response["choices"][0]["message"]["content"].as_str().map(str::to_string),
+        };
+        text.ok_or_else(|| anyhow::anyhow!("Unexpected {} response: no generated text", self.as_str()))
+    }
+}
+
+/// Whether `endpoint` resolves to this machine: `localhost` or a loopback address.
+/// LAN addresses count as remote, since the text leaves the host.
+pub fn is_local_endpoint(endpoint: &str) -> bool {
+    let without_scheme = endpoint.split_once("://").map_or(endpoint, |(_, rest)| rest);
+    let authority = without_scheme.split(['/', '?', '#']).next().unwrap_or_default();
+    let host_port = authority.rsplit_once('@').map_or(authority, |(_, host)| host);
+
+    let host = if let Some(bracketed) = host_port.strip_prefix('[') {
+        bracketed.split(']').next().unwrap_or_default()
+    } else {
+        host_port.split(':').next().unwrap_or_default()
+    };
+
+    let host = host.to_ascii_lowercase();
+    host == "localhost"
+        || host.ends_with(".localhost")
+        || host.parse::<IpAddr>().is_ok_and(|ip| ip.is_loopback())
+}
+
+/// Refuses non-local endpoints unless remote use was explicitly allowed.
+pub fn ensure_endpoint_allowed(provider: LlmProvider, endpoint: &str, allow_remote: bool) -> Result<()> {
+    if allow_remote || is_local_endpoint(endpoint) {
+        return Ok(());
+    }
+    Err(anyhow::anyhow!(
+        "Refusing to send text to non-local {} endpoint {}: raw PII would leave this host. \
+         Set llm.allow_remote = true to accept this",
+        provider.as_str(),
+        endpoint
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_local_endpoints() {
+        assert!(is_local_endpoint("http://localhost:11434"));
+        assert!(is_local_endpoint("http://127.0.0.1:11434/"));
+        assert!(is_local_endpoint("http://[::1]:11434"));
+        assert!(!is_local_endpoint("https://api.anthropic.com"));
+        assert!(!is_local_endpoint("http://192.168.1.20:11434"));
+        assert!(!is_local_endpoint("http://localhost.evil.com"));
+        assert!(!is_local_endpoint("http://localhost@evil.com"));
+
+        assert!(ensure_endpoint_allowed(LlmProvider::OpenAi, "https://api.openai.com", false).is_err());
+        assert!(ensure_endpoint_allowed(LlmProvider::OpenAi, "https://api.openai.com", true).is_ok());
+    }
+
+    #[test]
+    fn test_hosted_request_and_response_shapes() {
+        let body = LlmProvider::OpenAi.request_body("gpt-4o-mini", "find PII", LlmOutputFormat::Json, json!({}));
+        assert_eq!(body["response_format"]["type"], "json_object");
+        assert_eq!(body["messages"][0]["content"], "find PII");
+
+        let reply = json!({"content": [{"type": "text", "text": "{\"entities\": []}"}]});
+        assert_eq!(LlmProvider::Anthropic.response_text(&reply).unwrap(), "{\"entities\": []}");
+
+        let reply = json!({"choices": [{"message": {"content": "{}"}}]});
+        assert_eq!(LlmProvider::OpenAi.response_text(&reply).unwrap(), "{}");
+        assert!(LlmProvider::OpenAi.response_text(&json!({})).is_err());
+    }
+}
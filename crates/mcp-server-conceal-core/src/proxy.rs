@@ -1,19 +1,42 @@
 //! Integrated MCP Privacy Proxy implementation
 
 use anyhow::Result;
-use serde_json::Value;
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::process::ExitStatus;
 use tokio::io::{stdin, stdout, AsyncBufReadExt, AsyncWriteExt, BufReader};
 use tokio::process::{Child, Command};
 use tokio::sync::mpsc;
 use tracing::{debug, error, info, warn};
 
-use crate::config::{Config, DetectedEntity, AnonymizedEntity, DetectionMode};
+use crate::admin::{self, AdminContext, RuntimeControl};
+use crate::audit::AuditLog;
+use crate::approval::ApprovalGate;
+use crate::bypass::{self, BypassRegistry};
+use crate::canary::{self, Canary};
+use crate::config::{Config, ConfigSource, DetectionMode, Framing, MappingConfig};
+use crate::debug_diff::DebugDiff;
+use crate::degradation::DegradationReport;
+use crate::discovery::Discovery;
 use crate::detection::RegexDetectionEngine;
 use crate::faker::FakerEngine;
+use crate::i18n::Catalog;
+use crate::gateway::{Route, Router, TargetConfig};
+use crate::fingerprint::{FingerprintPolicy, FingerprintStore, TargetFingerprint, TrustCheck};
+use crate::json_path::ScalarRules;
+use crate::leak_check::LeakChecker;
+use crate::listener::{ConnectionReader, ConnectionWriter, ListenAddress, Listener, LISTEN_TOKEN_ENV_VAR};
 use crate::mapping::MappingStore;
 use crate::ollama::{OllamaClient, OllamaConfig};
+use crate::manifest::SessionManifest;
+use crate::outage_queue::OutageQueue;
+use crate::quarantine::QuarantineStore;
+use crate::overlap::OverlapResolver;
+use crate::pipeline::{bridge, write_message, Direction, MessageReader, Pipeline};
+use crate::protocol::RequestTracker;
+use crate::schema_hints::SchemaHints;
+use crate::secrets;
+use crate::session::SessionScope;
 
 #[derive(Debug, Clone)]
 pub struct IntegratedProxyConfig {
@@ -23,6 +46,12 @@ pub struct IntegratedProxyConfig {
     pub target_cwd: Option<PathBuf>,
     pub config: Config,
     pub ollama_config: OllamaConfig,
+    /// Log a masked diff of every changed message
+    pub debug_diff: bool,
+    /// Serve clients on a socket instead of stdin/stdout
+    pub listen: Option<ListenAddress>,
+    /// File, profile and preset the configuration was read with, for reloads
+    pub config_source: Option<ConfigSource>,
 }
 
 pub struct IntegratedProxy {
@@ -31,14 +60,71 @@ pub struct IntegratedProxy {
     faker_engine: FakerEngine,
     mapping_store: MappingStore,
     ollama_client: OllamaClient,
+    request_tracker: RequestTracker,
+    schema_hints: SchemaHints,
+    audit_log: AuditLog,
+    bypass_registry: Option<BypassRegistry>,
+    /// Added to every response the proxy changes
+    canary: Option<Canary>,
+    outage_queue: Option<OutageQueue>,
+    quarantine: Option<QuarantineStore>,
+    approval: Option<ApprovalGate>,
+    degradation: DegradationReport,
+    catalog: Catalog,
+    /// Mapping sessions of the stdio pipelines, with `reset_on_initialize`
+    session_scope: Option<SessionScope>,
+    /// Shared with every pipeline, changed through the admin channel
+    control: RuntimeControl,
 }
 
 impl IntegratedProxy {
     pub fn new(config: IntegratedProxyConfig) -> Result<Self> {
         let detection_engine = RegexDetectionEngine::new(&config.config.detection)?;
-        let faker_engine = FakerEngine::new(&config.config.faker);
-        let mapping_store = MappingStore::new(config.config.mapping.clone())?;
-        let ollama_client = OllamaClient::new(config.ollama_config.clone(), config.config.llm.as_ref().and_then(|llm| llm.prompt_template.as_ref()))?;
+        let faker_engine = FakerEngine::from_config(&config.config)?;
+        let mut mapping_store = MappingStore::new(config.config.mapping.clone())?;
+        let ollama_client = OllamaClient::new(config.ollama_config.clone(), config.config.llm.as_ref().and_then(|llm| llm.prompt_template.as_ref()))?
+            .with_language_prompts(&config.config.language)?;
+        let manifest = SessionManifest::new(&config.config, ollama_client.prompt_template())?;
+        let audit_log = AuditLog::new(&config.config.audit)?.with_session_id(&manifest.id);
+        if let Some(dir) = &config.config.audit.manifest_dir {
+            info!("  Session manifest: {}", manifest.write(dir)?.display());
+        }
+        audit_log.record("session_started", serde_json::to_value(&manifest)?);
+        let expired_in_use = mapping_store.take_expired_in_use();
+        if !expired_in_use.is_empty() {
+            audit_log.record("mappings_expired_in_use", serde_json::json!({
+                "retention_days": config.config.mapping.retention_days,
+                "used_within_days": config.config.mapping.expiry_warning_days,
+                "entity_types": expired_in_use,
+            }));
+        }
+        let bypass_registry = Self::create_bypass_registry(&config.config);
+        let canary = match &config.config.canary {
+            canary_config if canary_config.enabled => {
+                let key = canary::signing_key(&secrets::require_passphrase(&config.config.mapping)?);
+                let canary = Canary::issue(canary_config, &key);
+                info!("  Canary: {}", canary.token());
+                audit_log.record("canary_issued", serde_json::json!({ "canary": canary.token() }));
+                Some(canary)
+            }
+            _ => None,
+        };
+        let outage_queue = OutageQueue::from_config(&config.config)?;
+        if let Some(queue) = &outage_queue {
+            info!("  LLM outage queue: {}", queue.path().display());
+        }
+        let quarantine = QuarantineStore::from_config(&config.config)?;
+        if let Some(quarantine) = &quarantine {
+            info!("  QA quarantine: {} ({}% of critical detections)", quarantine.path().display(), quarantine.config().sample_rate * 100.0);
+        }
+        let approval = ApprovalGate::from_config(&config.config)?;
+        let catalog = Catalog::new(&config.config.i18n)?;
+        let control = RuntimeControl::started_with(&config.config, config.ollama_config.enabled);
+        let session_scope = session_scope(&config.config.mapping);
+        if let Some(gate) = &approval {
+            info!("  Operator approval of first-seen {:?}: {} ({}s timeout, then {})",
+                gate.config().entity_types, gate.config().dir.display(), gate.config().timeout_seconds, gate.config().on_timeout.as_str());
+        }
 
         Ok(Self {
             config,
@@ -46,58 +132,539 @@ impl IntegratedProxy {
             faker_engine,
             mapping_store,
             ollama_client,
+            request_tracker: RequestTracker::new(),
+            schema_hints: SchemaHints::new(),
+            audit_log,
+            bypass_registry,
+            canary,
+            outage_queue,
+            quarantine,
+            approval,
+            degradation: DegradationReport::new(),
+            catalog,
+            session_scope,
+            control,
         })
     }
 
-    pub async fn run(&mut self) -> Result<()> {
+    // Bypass tokens are signed with the mapping passphrase; without one they cannot be honoured
+    fn create_bypass_registry(config: &Config) -> Option<BypassRegistry> {
+        if !config.mapping.bypass_tokens {
+            return None;
+        }
+        let passphrase = match secrets::resolve_passphrase(&config.mapping) {
+            Ok(Some(passphrase)) => passphrase,
+            Ok(None) => {
+                debug!("No mapping passphrase available, bypass tokens disabled");
+                return None;
+            }
+            Err(e) => {
+                warn!("Could not read mapping passphrase, bypass tokens disabled: {}", e);
+                return None;
+            }
+        };
+
+        match bypass::default_bypass_dir() {
+            Ok(dir) => {
+                info!("  Bypass token directory: {}", dir.display());
+                Some(BypassRegistry::new(dir, bypass::signing_key(&passphrase)))
+            }
+            Err(e) => {
+                warn!("Could not determine bypass token directory: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Runs the proxy until the client or the target closes its stream, or a
+    /// SIGINT or SIGTERM arrives. Returns the exit code to leave with: the
+    /// target's, or 128 plus the number of the signal that ended it.
+    pub async fn run(&mut self) -> Result<i32> {
         info!("Starting Integrated MCP Privacy Proxy");
         info!("  Regex patterns: {}", self.config.config.detection.patterns.len());
         info!("  Ollama enabled: {}", self.config.ollama_config.enabled);
         info!("  Database path: {}", self.config.config.mapping.database_path.display());
+        // Announces injected faults up front rather than at the first one
+        crate::chaos::injector();
+
+        if !matches!(self.config.config.detection.mode, DetectionMode::Regex) {
+            self.ollama_client.ensure_models_available().await?;
+        }
+        let _admin: Vec<AbortOnDrop> = self.start_admin().await?.into_iter().map(AbortOnDrop).collect();
+        let _config_watcher = self.config.config_source.clone().map(|source| {
+            info!("  Configuration reloads on change{}: {}", if cfg!(unix) { " or SIGHUP" } else { "" }, source.path.display());
+            AbortOnDrop(admin::watch_config(source, &self.config.config.reload, self.control.clone(), self.audit_log.clone()))
+        });
+
+        if !self.config.config.targets.is_empty() {
+            if self.config.listen.is_some() {
+                return Err(anyhow::anyhow!("Listening on a socket is not available in gateway mode"));
+            }
+            return self.run_gateway().await;
+        }
+        if let Some(address) = self.config.listen.clone() {
+            return self.run_listener(&address).await;
+        }
 
-        let mut child = self.spawn_child_process().await?;
+        let target = self.single_target();
+        self.verify_target(&target)?;
+        let mut child = self.spawn_child_process(&target).await?;
         let io_handles = self.setup_io_handles(&mut child)?;
         
         let (shutdown_tx, mut shutdown_rx) = mpsc::unbounded_channel();
         let tasks = self.spawn_processing_tasks(io_handles, shutdown_tx.clone()).await;
 
-        shutdown_rx.recv().await;
+        let signal = tokio::select! {
+            _ = shutdown_rx.recv() => None,
+            signal = shutdown_signal() => Some(signal?),
+        };
         info!("Shutting down proxy");
 
+        let code = stop_child(&mut child, signal, self.config.config.processing.shutdown_grace()).await;
         self.cleanup_tasks(tasks).await;
         self.print_final_stats();
 
-        info!("Integrated MCP Privacy Proxy shut down");
+        info!("Integrated MCP Privacy Proxy shut down (exit code {})", code);
+        Ok(code)
+    }
+
+    /// Serves the admin socket and the metrics endpoint, those configured,
+    /// until the returned tasks are aborted.
+    async fn start_admin(&self) -> Result<Vec<tokio::task::JoinHandle<()>>> {
+        let admin = &self.config.config.admin;
+        if admin.socket.is_none() && admin.metrics.is_none() {
+            return Ok(Vec::new());
+        }
+        let mapping = &self.config.config.mapping;
+        let namespaces = match self.config.config.targets.as_slice() {
+            [] => vec![mapping.clone()],
+            targets => targets.iter()
+                .map(|target| MappingConfig { namespace: Some(target.namespace(mapping.namespace.as_deref())), ..mapping.clone() })
+                .collect(),
+        };
+        let context = std::sync::Arc::new(AdminContext {
+            control: self.control.clone(),
+            namespaces,
+            counters: self.mapping_store.counters().clone(),
+            degradation: self.degradation.clone(),
+            config_source: self.config.config_source.clone(),
+            audit_log: self.audit_log.clone(),
+        });
+        let mut tasks = Vec::new();
+        if let Some(socket) = &admin.socket {
+            tasks.push(admin::serve(socket, context.clone()).await?);
+        }
+        if let Some(address) = &admin.metrics {
+            tasks.push(admin::serve_metrics(address, context).await?);
+        }
+        Ok(tasks)
+    }
+
+    /// The target given on the command line, in the shape of a `[[targets]]` block.
+    fn single_target(&self) -> TargetConfig {
+        TargetConfig {
+            name: String::new(),
+            command: self.config.target_command.clone(),
+            args: self.config.target_args.clone(),
+            env: self.config.target_env.clone(),
+            cwd: self.config.target_cwd.clone(),
+        }
+    }
+
+    /// Gateway mode: runs every configured target behind one aggregated
+    /// server. Each target's traffic goes through its own pair of pipelines,
+    /// with mappings in its own namespace; the `Router` in between merges and
+    /// routes messages. Returns the first non-zero exit code of the targets.
+    async fn run_gateway(&mut self) -> Result<i32> {
+        let targets = self.config.config.targets.clone();
+        info!("  Gateway targets: {}", targets.iter().map(|t| t.name.as_str()).collect::<Vec<_>>().join(", "));
+
+        let (events_tx, mut events) = mpsc::unbounded_channel();
+        let mut children = Vec::new();
+        let mut senders = Vec::new();
+        let mut tasks = Vec::new();
+        for (index, target) in targets.iter().enumerate() {
+            self.verify_target(target)?;
+            let mut child = self.spawn_child_process(target).await?;
+            let handles = self.setup_io_handles(&mut child)?;
+            let (sender, target_tasks) = self.spawn_gateway_target(index, target, handles, events_tx.clone())?;
+            senders.push(sender);
+            tasks.extend(target_tasks);
+            children.push(child);
+        }
+        let processing = &self.config.config.processing;
+        tasks.push(spawn_client_reader(events_tx, processing.framing, processing.max_line_bytes));
+
+        let mut router = Router::new(targets.iter().map(|target| target.name.clone()).collect(), processing.fan_out_timeout());
+        let mut our_stdout = stdout();
+        // Replies go out in the framing the client's first message arrived in
+        let mut client_framing = match processing.framing {
+            Framing::Auto => Framing::Newline,
+            framing => framing,
+        };
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
+        let signal = loop {
+            let deadline = router.next_deadline();
+            let event = tokio::select! {
+                event = events.recv() => event,
+                signal = &mut shutdown => break Some(signal?),
+                _ = tokio::time::sleep_until(deadline.unwrap_or_else(tokio::time::Instant::now)), if deadline.is_some() => {
+                    Some(GatewayEvent::Expired)
+                }
+            };
+            let routes = match event {
+                Some(GatewayEvent::Client(line, framing)) => {
+                    client_framing = framing;
+                    router.from_client(&line)
+                }
+                Some(GatewayEvent::Expired) => router.expire(tokio::time::Instant::now()),
+                Some(GatewayEvent::Target(index, line)) => router.from_target(index, &line),
+                Some(GatewayEvent::TargetClosed(index)) => router.target_closed(index),
+                Some(GatewayEvent::ClientClosed) | None => {
+                    info!("EOF on stdin, shutting down");
+                    break None;
+                }
+            };
+            for route in routes {
+                match route {
+                    Route::Client(line) => write_message(&mut our_stdout, client_framing, &line).await?,
+                    Route::Target(index, line) => {
+                        senders[index].send(line).ok();
+                    }
+                }
+            }
+            if router.all_closed() {
+                info!("Every gateway target closed its output, shutting down");
+                break None;
+            }
+        };
+        info!("Shutting down gateway");
+
+        // Closing the request streams tells the targets the client is gone;
+        // they get their grace period side by side
+        drop(senders);
+        let grace = self.config.config.processing.shutdown_grace();
+        let mut stopping = tokio::task::JoinSet::new();
+        for (index, mut child) in children.into_iter().enumerate() {
+            stopping.spawn(async move { (index, stop_child(&mut child, signal, grace).await) });
+        }
+        let mut exits = stopping.join_all().await;
+        exits.sort_by_key(|(index, _)| *index);
+        let code = exits.into_iter().map(|(_, code)| code).find(|&code| code != 0).unwrap_or(0);
+        for task in tasks {
+            task.abort();
+        }
+        self.print_final_stats();
+        for target in &targets {
+            let mut mapping = self.config.config.mapping.clone();
+            mapping.namespace = Some(target.namespace(self.config.config.mapping.namespace.as_deref()));
+            match MappingStore::new(mapping).and_then(|store| store.get_statistics()) {
+                Ok(stats) => info!("  Target '{}': {} mappings", target.name, stats.total_mappings),
+                Err(e) => warn!("Failed to get statistics of target '{}': {}", target.name, e),
+            }
+        }
+
+        info!("Integrated MCP Privacy Proxy shut down (exit code {})", code);
+        Ok(code)
+    }
+
+    /// Serves clients connecting to `address` until a SIGINT or SIGTERM
+    /// arrives. Each connection gets its own target process and pipelines;
+    /// mappings are shared, so a value gets the same fake in every session.
+    /// Returns the first non-zero exit code of a target that ended on its
+    /// own, or 1 if a session could not be started.
+    async fn run_listener(&mut self, address: &ListenAddress) -> Result<i32> {
+        let target = self.single_target();
+        self.verify_target(&target)?;
+        let token = std::env::var(LISTEN_TOKEN_ENV_VAR).ok();
+        let listener = Listener::bind(address, token).await?;
+        info!("Listening for MCP clients on {}", address);
+
+        let shutdown = shutdown_signal();
+        tokio::pin!(shutdown);
+        // Clients still sending their AUTH line, kept off the accept loop
+        let mut handshakes = tokio::task::JoinSet::new();
+        let mut sessions = tokio::task::JoinSet::new();
+        let mut next_session = 0u64;
+        let mut code = 0;
+        let signal = loop {
+            let connection = tokio::select! {
+                accepted = listener.accept() => {
+                    match accepted {
+                        Ok(connection) => {
+                            handshakes.spawn(connection.authenticate());
+                        }
+                        Err(e) => warn!("Failed to accept a client: {}", e),
+                    }
+                    continue;
+                }
+                Some(authenticated) = handshakes.join_next(), if !handshakes.is_empty() => match authenticated {
+                    Ok(Ok(connection)) => connection,
+                    Ok(Err(e)) => {
+                        warn!("Rejected a client: {}", e);
+                        continue;
+                    }
+                    Err(e) => {
+                        warn!("Client handshake failed: {}", e);
+                        continue;
+                    }
+                },
+                signal = &mut shutdown => break signal?,
+                Some(ended) = sessions.join_next(), if !sessions.is_empty() => {
+                    if let (Ok(exited), 0) = (ended, code) {
+                        code = exited;
+                    }
+                    continue;
+                }
+            };
+            next_session += 1;
+            info!("Session {} started for {}", next_session, connection.peer);
+            match self.start_session(&target, connection.reader, connection.writer).await {
+                Ok(session) => {
+                    let id = next_session;
+                    sessions.spawn(async move {
+                        let code = session.await;
+                        info!("Session {} ended (target exit code {})", id, code);
+                        code
+                    });
+                }
+                Err(e) => {
+                    error!("Failed to start session {}: {}", next_session, e);
+                    if code == 0 {
+                        code = 1;
+                    }
+                }
+            }
+        };
+        info!("Received signal {}, closing {} sessions", signal, sessions.len());
+        handshakes.shutdown().await;
+        // Dropping the sessions kills their targets
+        sessions.shutdown().await;
+        drop(listener);
+        self.print_final_stats();
+        info!("Integrated MCP Privacy Proxy shut down (exit code {})", code);
+        Ok(code)
+    }
+
+    /// Spawns a target for one client connection and returns the session,
+    /// which resolves to the target's exit code once either side is done.
+    async fn start_session(
+        &self,
+        target: &TargetConfig,
+        client_reader: ConnectionReader,
+        mut client_writer: ConnectionWriter,
+    ) -> Result<impl std::future::Future<Output = i32>> {
+        let mut child = self.spawn_child_process(target).await?;
+        let handles = self.setup_io_handles(&mut child)?;
+        let request_tracker = RequestTracker::new();
+        let mapping = &self.config.config.mapping;
+        // Sessions of concurrent clients are kept apart
+        let sessions = session_scope(mapping);
+        let mut request_pipeline = self.build_pipeline(MappingStore::new(mapping.clone())?, Direction::Request, &request_tracker, &self.schema_hints)?
+            .with_session_scope(sessions.clone());
+        let mut response_pipeline = self.build_pipeline(MappingStore::new(mapping.clone())?, Direction::Response, &request_tracker, &self.schema_hints)?
+            .with_session_scope(sessions);
+        request_pipeline.connect_replies(&mut response_pipeline);
+        let grace = self.config.config.processing.shutdown_grace();
+
+        Ok(async move {
+            let mut child_stdin = handles.child_stdin;
+            let (stderr_shutdown, _) = mpsc::unbounded_channel();
+            let stderr_task = spawn_stderr_task(handles.child_stderr, stderr_shutdown);
+            let mut requests = Box::pin(bridge(client_reader, &mut child_stdin, &mut request_pipeline));
+            let mut responses = Box::pin(bridge(handles.child_stdout, &mut client_writer, &mut response_pipeline));
+            tokio::select! {
+                result = &mut requests => {
+                    if let Err(e) = result {
+                        warn!("Session requests failed: {}", e);
+                    }
+                    // The target sees EOF; what it still writes reaches the client
+                    drop(requests);
+                    drop(child_stdin);
+                    if tokio::time::timeout(grace, &mut responses).await.is_err() {
+                        warn!("Target output still open after {}s, dropping the rest", grace.as_secs());
+                    }
+                }
+                result = &mut responses => {
+                    if let Err(e) = result {
+                        warn!("Session responses failed: {}", e);
+                    }
+                }
+            }
+            let code = stop_child(&mut child, None, grace).await;
+            stderr_task.abort();
+            code
+        })
+    }
+
+    /// Connects one gateway target to the router: lines sent to the returned
+    /// channel go through the target's request pipeline into its stdin, and
+    /// its stdout comes back through its response pipeline as events.
+    fn spawn_gateway_target(
+        &self,
+        index: usize,
+        target: &TargetConfig,
+        handles: IoHandles,
+        events: mpsc::UnboundedSender<GatewayEvent>,
+    ) -> Result<(mpsc::UnboundedSender<String>, Vec<tokio::task::JoinHandle<()>>)> {
+        let mut mapping = self.config.config.mapping.clone();
+        mapping.namespace = Some(target.namespace(self.config.config.mapping.namespace.as_deref()));
+        info!("  Target '{}' mapping namespace: {}", target.name, mapping.namespace.as_deref().unwrap_or_default());
+        let request_tracker = RequestTracker::new();
+        let schema_hints = SchemaHints::new();
+        let sessions = session_scope(&mapping);
+        let mut request_pipeline = self.build_pipeline(MappingStore::new(mapping.clone())?, Direction::Request, &request_tracker, &schema_hints)?
+            .with_session_scope(sessions.clone());
+        let mut response_pipeline = self.build_pipeline(MappingStore::new(mapping)?, Direction::Response, &request_tracker, &schema_hints)?
+            .with_session_scope(sessions);
+        request_pipeline.connect_replies(&mut response_pipeline);
+        // The router's side of the pipes is newline-delimited; the target's is
+        // as configured, and newline-delimited under `auto`
+        let target_framing = self.config.config.processing.framing;
+        request_pipeline.framing = Framing::Newline;
+        request_pipeline.output_framing = Some(match target_framing {
+            Framing::ContentLength => Framing::ContentLength,
+            _ => Framing::Newline,
+        });
+        response_pipeline.output_framing = Some(Framing::Newline);
+
+        let (sender, mut lines) = mpsc::unbounded_channel::<String>();
+        let (mut requests_in, requests_out) = tokio::io::duplex(GATEWAY_PIPE_BYTES);
+        let (mut responses_in, responses_out) = tokio::io::duplex(GATEWAY_PIPE_BYTES);
+        let mut child_stdin = handles.child_stdin;
+        let child_stdout = handles.child_stdout;
+        let name = target.name.clone();
+
+        let feed_task = tokio::spawn(async move {
+            while let Some(mut line) = lines.recv().await {
+                line.push('\n');
+                if requests_in.write_all(line.as_bytes()).await.is_err() {
+                    break;
+                }
+            }
+        });
+        let request_name = name.clone();
+        let request_task = tokio::spawn(async move {
+            if let Err(e) = bridge(requests_out, &mut child_stdin, &mut request_pipeline).await {
+                error!("Request processing for target '{}' failed: {}", request_name, e);
+            }
+        });
+        let response_task = tokio::spawn(async move {
+            match bridge(child_stdout, &mut responses_in, &mut response_pipeline).await {
+                Ok(()) => info!("EOF on stdout of target '{}'", name),
+                Err(e) => error!("Response processing for target '{}' failed: {}", name, e),
+            }
+        });
+        let events_task = tokio::spawn(async move {
+            let mut reader = BufReader::new(responses_out);
+            let mut line = String::new();
+            loop {
+                line.clear();
+                match reader.read_line(&mut line).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {
+                        if events.send(GatewayEvent::Target(index, line.clone())).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+            events.send(GatewayEvent::TargetClosed(index)).ok();
+        });
+        let (stderr_shutdown, _) = mpsc::unbounded_channel();
+        let stderr_task = spawn_stderr_task(handles.child_stderr, stderr_shutdown);
+
+        Ok((sender, vec![feed_task, request_task, response_task, events_task, stderr_task]))
+    }
+
+    /// Compares the target's files with the fingerprint recorded on first use.
+    fn verify_target(&self, target: &TargetConfig) -> Result<()> {
+        let policy = self.config.config.target_trust.policy;
+        if policy == FingerprintPolicy::Off {
+            return Ok(());
+        }
+
+        // Only `block` refuses a target it could not fingerprint; `warn` starts it anyway
+        let checked = TargetFingerprint::compute(&target.command, &target.args, target.cwd.as_deref(), &target.env)
+            .and_then(|fingerprint| {
+                let store = FingerprintStore::from_config(&self.config.config.target_trust)?;
+                let check = store.check(&fingerprint)?;
+                Ok((fingerprint, store, check))
+            });
+        let (fingerprint, store, check) = match checked {
+            Ok(checked) => checked,
+            Err(e) if policy == FingerprintPolicy::Block => {
+                return Err(anyhow::anyhow!("Cannot verify the target MCP server, refusing to start it: {}", e));
+            }
+            Err(e) => {
+                warn!("Could not fingerprint the target MCP server, starting it unverified: {}", e);
+                self.audit_log.record("target_fingerprint_failed", serde_json::json!({
+                    "command": target.command,
+                    "error": e.to_string(),
+                }));
+                return Ok(());
+            }
+        };
+        match check {
+            TrustCheck::Unchanged => debug!("Target fingerprint matches {}", store.path().display()),
+            TrustCheck::FirstUse => {
+                info!("  Recorded target fingerprint in {}", store.path().display());
+                self.audit_log.record("target_trusted", serde_json::json!({
+                    "command_line": fingerprint.command_line,
+                    "files": fingerprint.files,
+                }));
+            }
+            TrustCheck::Changed(paths) => {
+                self.audit_log.record("target_fingerprint_changed", serde_json::json!({
+                    "command_line": fingerprint.command_line,
+                    "changed": paths,
+                    "policy": policy,
+                }));
+                let changed: Vec<String> = paths.iter().map(|p| p.display().to_string()).collect();
+                let message = format!(
+                    "Target MCP server changed since it was trusted: {}. Run `mcp-server-conceal trust-target` with the same target options if this is expected",
+                    changed.join(", ")
+                );
+                if policy == FingerprintPolicy::Block {
+                    return Err(anyhow::anyhow!(message));
+                }
+                warn!("{}", message);
+            }
+        }
         Ok(())
     }
 
-    async fn spawn_child_process(&self) -> Result<Child> {
+    async fn spawn_child_process(&self, target: &TargetConfig) -> Result<Child> {
         info!(
             "Spawning child process: {} {:?}",
-            self.config.target_command, self.config.target_args
+            target.command, target.args
         );
 
-        let mut command = Command::new(&self.config.target_command);
+        let mut command = Command::new(&target.command);
         command
-            .args(&self.config.target_args)
+            .args(&target.args)
             .stdin(std::process::Stdio::piped())
             .stdout(std::process::Stdio::piped())
             .stderr(std::process::Stdio::piped())
             .kill_on_drop(true);
+        // Out of the terminal's reach, so a Ctrl-C arrives once, forwarded
+        #[cfg(unix)]
+        command.process_group(0);
 
-        for (key, value) in &self.config.target_env {
+        for (key, value) in &target.env {
             command.env(key, value);
             debug!("Setting env var: {}={}", key, value);
         }
 
-        if let Some(ref cwd) = self.config.target_cwd {
+        if let Some(ref cwd) = target.cwd {
             command.current_dir(cwd);
             debug!("Setting working directory: {}", cwd.display());
         }
 
         let child = command.spawn()
             .map_err(|e| anyhow::anyhow!("Failed to spawn child process '{}': {}", 
-                                       self.config.target_command, e))?;
+                                       target.command, e))?;
 
         info!("Child process started with PID: {:?}", child.id());
         Ok(child)
@@ -121,8 +688,15 @@ impl IntegratedProxy {
     }
 
     async fn spawn_processing_tasks(&self, handles: IoHandles, shutdown_tx: mpsc::UnboundedSender<()>) -> ProxyTasks {
-        let stdin_task = self.spawn_stdin_task(handles.our_stdin, handles.child_stdin, shutdown_tx.clone()).await;
-        let stdout_task = self.spawn_stdout_task(handles.child_stdout, handles.our_stdout, shutdown_tx.clone()).await;
+        let mut request_pipeline = MappingStore::new(self.config.config.mapping.clone())
+            .and_then(|store| self.build_pipeline(store, Direction::Request, &self.request_tracker, &self.schema_hints));
+        let mut response_pipeline = MappingStore::new(self.config.config.mapping.clone())
+            .and_then(|store| self.build_pipeline(store, Direction::Response, &self.request_tracker, &self.schema_hints));
+        if let (Ok(request_pipeline), Ok(response_pipeline)) = (&mut request_pipeline, &mut response_pipeline) {
+            request_pipeline.connect_replies(response_pipeline);
+        }
+        let stdin_task = self.spawn_stdin_task(handles.our_stdin, handles.child_stdin, request_pipeline, shutdown_tx.clone()).await;
+        let stdout_task = self.spawn_stdout_task(handles.child_stdout, handles.our_stdout, response_pipeline, shutdown_tx.clone()).await;
         let stderr_task = spawn_stderr_task(handles.child_stderr, shutdown_tx.clone());
         // Temporarily disable child monitor task that was causing immediate shutdown
         let child_task = tokio::spawn(async move {
@@ -138,81 +712,123 @@ impl IntegratedProxy {
         }
     }
 
-    async fn spawn_stdin_task(&self, our_stdin: tokio::io::Stdin, mut child_stdin: tokio::process::ChildStdin, shutdown_tx: mpsc::UnboundedSender<()>) -> tokio::task::JoinHandle<()> {
-        let mut detection_engine = self.detection_engine.clone();
-        let mut faker_engine = self.faker_engine.clone();
-        let mapping_config = self.config.config.mapping.clone();
-        let ollama_client = self.ollama_client.clone();
-        let ollama_config = self.config.ollama_config.clone();
-        let detection_mode = self.config.config.detection.mode.clone();
+    fn build_pipeline(
+        &self,
+        mapping_store: MappingStore,
+        direction: Direction,
+        request_tracker: &RequestTracker,
+        schema_hints: &SchemaHints,
+    ) -> Result<Pipeline> {
+        let mapping_store = mapping_store
+            .with_counters(self.mapping_store.counters().clone())
+            .sharing_recent_mappings(&self.mapping_store);
+        Ok(Pipeline {
+            detection_engine: self.detection_engine.clone(),
+            ollama_client: self.ollama_client.clone(),
+            faker_engine: self.faker_engine.clone(),
+            mapping_store,
+            overlap_resolver: OverlapResolver::new(&self.config.config.detection.overlap),
+            stitching: self.config.config.detection.stitching.clone(),
+            nested_json: self.config.config.detection.nested_json.clone(),
+            nested_depth: 0,
+            base64: self.config.config.detection.base64.clone(),
+            model_name: self.config.ollama_config.model.clone(),
+            detection_mode: self.config.config.detection.mode.clone(),
+            request_tracker: request_tracker.clone(),
+            schema_hints: self.config.config.detection.schema_hints.enabled.then(|| schema_hints.clone()),
+            tool_hints: None,
+            audit_log: self.audit_log.clone(),
+            bypass_registry: self.bypass_registry.clone(),
+            outage_queue: self.outage_queue.as_ref().map(OutageQueue::try_clone).transpose()?,
+            quarantine: self.quarantine.as_ref().map(QuarantineStore::try_clone).transpose()?,
+            message_types: Default::default(),
+            message_persona: None,
+            approval: self.approval.clone(),
+            first_seen_critical: Vec::new(),
+            leak_checker: LeakChecker::new(&self.config.config.leak_check),
+            message_replacements: Vec::new(),
+            message_leaks: Default::default(),
+            canary: self.canary.clone(),
+            debug_diff: self.config.debug_diff.then(DebugDiff::new),
+            annotate: false,
+            discovery: Discovery::new(&self.config.config.discovery),
+            degradation: self.degradation.clone(),
+            message_degradations: Default::default(),
+            llm_cache_snapshot: Default::default(),
+            scalar_rules: ScalarRules::new(&self.config.config.detection.scalars)?,
+            message_timeout: self.config.config.processing.message_timeout(),
+            json_limits: self.config.config.processing.json_limits(),
+            latency_budget: self.config.config.processing.latency_budget(),
+            llm_deadline: None,
+            on_error: self.config.config.processing.on_error,
+            error_withheld_message: self.catalog.text("error_withheld").to_string(),
+            error_withheld_request_message: self.catalog.text("error_withheld_request").to_string(),
+            max_line_bytes: self.config.config.processing.max_line_bytes,
+            on_oversized_line: self.config.config.processing.on_oversized_line,
+            framing: self.config.config.processing.framing,
+            output_framing: None,
+            session_scope: self.session_scope.clone(),
+            session_generation: 0,
+            control: Some(self.control.clone()),
+            control_seen: (self.control.store_generation(), self.control.config_generation()),
+            replies: None,
+            peer_replies: None,
+            held: None,
+            json_path: Vec::new(),
+            replacement_spans: Vec::new(),
+            direction,
+        })
+    }
 
+    async fn spawn_stdin_task(&self, our_stdin: tokio::io::Stdin, mut child_stdin: tokio::process::ChildStdin, pipeline: Result<Pipeline>, shutdown_tx: mpsc::UnboundedSender<()>) -> tokio::task::JoinHandle<()> {
         tokio::spawn(async move {
-            let mut mapping_store = match MappingStore::new(mapping_config) {
-                Ok(store) => store,
+            let mut pipeline = match pipeline {
+                Ok(pipeline) => pipeline,
                 Err(e) => {
-                    error!("Failed to create mapping store in stdin task: {}", e);
+                    error!("Failed to create pipeline in stdin task: {}", e);
                     shutdown_tx.send(()).ok();
                     return;
                 }
             };
 
-            if let Err(e) = process_stdin_loop(
-                our_stdin, 
-                &mut child_stdin,
-                &mut detection_engine,
-                &ollama_client,
-                &mut faker_engine,
-                &mut mapping_store,
-                &ollama_config.model,
-                &detection_mode,
-                &shutdown_tx
-            ).await {
-                error!("Stdin processing failed: {}", e);
+            match bridge(our_stdin, &mut child_stdin, &mut pipeline).await {
+                Ok(()) => info!("EOF on stdin, shutting down"),
+                Err(e) => error!("Stdin processing failed: {}", e),
             }
+            shutdown_tx.send(()).ok();
         })
     }
 
-    async fn spawn_stdout_task(&self, child_stdout: tokio::process::ChildStdout, mut our_stdout: tokio::io::Stdout, shutdown_tx: mpsc::UnboundedSender<()>) -> tokio::task::JoinHandle<()> {
-        let mut detection_engine = self.detection_engine.clone();
-        let mut faker_engine = self.faker_engine.clone();
-        let mapping_config = self.config.config.mapping.clone();
-        let ollama_client = self.ollama_client.clone();
-        let ollama_config = self.config.ollama_config.clone();
-        let detection_mode = self.config.config.detection.mode.clone();
-
+    async fn spawn_stdout_task(&self, child_stdout: tokio::process::ChildStdout, mut our_stdout: tokio::io::Stdout, pipeline: Result<Pipeline>, shutdown_tx: mpsc::UnboundedSender<()>) -> tokio::task::JoinHandle<()> {
         tokio::spawn(async move {
-            let mut mapping_store = match MappingStore::new(mapping_config) {
-                Ok(store) => store,
+            let mut pipeline = match pipeline {
+                Ok(pipeline) => pipeline,
                 Err(e) => {
-                    error!("Failed to create mapping store in stdout task: {}", e);
+                    error!("Failed to create pipeline in stdout task: {}", e);
                     shutdown_tx.send(()).ok();
                     return;
                 }
             };
 
-            if let Err(e) = process_stdout_loop(
-                child_stdout,
-                &mut our_stdout,
-                &mut detection_engine,
-                &ollama_client,
-                &mut faker_engine,
-                &mut mapping_store,
-                &ollama_config.model,
-                &detection_mode,
-                &shutdown_tx
-            ).await {
-                error!("Stdout processing failed: {}", e);
+            match bridge(child_stdout, &mut our_stdout, &mut pipeline).await {
+                Ok(()) => info!("EOF on child stdout"),
+                Err(e) => error!("Stdout processing failed: {}", e),
             }
+            shutdown_tx.send(()).ok();
         })
     }
 
-    async fn cleanup_tasks(&self, tasks: ProxyTasks) {
+    async fn cleanup_tasks(&self, mut tasks: ProxyTasks) {
+        // Output the child wrote before exiting is still concealed and forwarded
+        let grace = self.config.config.processing.shutdown_grace();
+        if tokio::time::timeout(grace, &mut tasks.stdout_task).await.is_err() {
+            warn!("Child output still open after {}s, dropping the rest", grace.as_secs());
+        }
+
         tasks.stdin_task.abort();
         tasks.stdout_task.abort();
         tasks.stderr_task.abort();
         tasks.child_task.abort();
-
-        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
     }
 
     fn print_final_stats(&self) {
@@ -220,167 +836,202 @@ impl IntegratedProxy {
             Ok(stats) => {
                 info!("Final processing statistics:");
                 info!("  Total mappings created: {}", stats.total_mappings);
-                info!("  Cache entries: {}", stats.total_cache_entries);
+                info!("  Cache entries: {} ({} reused, {} hits)",
+                      stats.total_cache_entries, stats.reused_cache_entries, stats.cache_hits);
                 info!("  Entity types processed: {:?}", stats.mappings_by_type);
+                if !stats.top_cooccurrences.is_empty() {
+                    info!("  Entity types replaced together (messages):");
+                    for (combination, messages) in &stats.top_cooccurrences {
+                        info!("    {}: {}", combination, messages);
+                    }
+                }
+                let runtime = &stats.runtime;
+                info!("  Mapping lookups: {} hits, {} misses{}",
+                      runtime.mapping_hits, runtime.mapping_misses, format_rate(runtime.mapping_hit_rate()));
+                info!("  LLM cache lookups: {} hits, {} misses{}",
+                      runtime.llm_cache_hits, runtime.llm_cache_misses, format_rate(runtime.llm_cache_hit_rate()));
+                for (direction, counters) in [("Requests", &runtime.request), ("Responses", &runtime.response)] {
+                    info!("  {}: {} entities anonymized in {} bytes",
+                          direction, counters.entities_anonymized, counters.bytes_processed);
+                }
             }
             Err(e) => warn!("Failed to get final statistics: {}", e),
         }
+        if let Some(queue) = &self.outage_queue {
+            match queue.len() {
+                Ok(0) => {}
+                Ok(queued) => info!("  Texts queued for LLM backfill: {}", queued),
+                Err(e) => warn!("Failed to read LLM backlog size: {}", e),
+            }
+        }
+
+        self.degradation.log_summary(&self.catalog);
+        if self.degradation.degraded_messages() > 0 {
+            self.audit_log.record("session_degraded", self.degradation.to_json());
+        }
     }
 }
 
-struct IoHandles {
-    child_stdin: tokio::process::ChildStdin,
-    child_stdout: tokio::process::ChildStdout,
-    child_stderr: tokio::process::ChildStderr,
-    our_stdin: tokio::io::Stdin,
-    our_stdout: tokio::io::Stdout,
+/// Gives the target the grace period to exit, after forwarding `signal`
+/// to it; kills it if it is still running then. Returns the exit code the
+/// proxy reports for it.
+async fn stop_child(child: &mut Child, signal: Option<i32>, grace: std::time::Duration) -> i32 {
+    if let Some(signal) = signal {
+        info!("Received signal {}, forwarding it to the child process", signal);
+        forward_signal(child, signal);
+    }
+
+    match tokio::time::timeout(grace, child.wait()).await {
+        Ok(Ok(status)) => {
+            info!("Child process exited: {}", status);
+            exit_code(Some(status))
+        }
+        Ok(Err(e)) => {
+            warn!("Failed to wait for child process: {}", e);
+            exit_code(None)
+        }
+        Err(_) => {
+            warn!("Child process still running after {}s, killing it", grace.as_secs());
+            if let Err(e) = child.kill().await {
+                warn!("Failed to kill child process: {}", e);
+            }
+            let _ = child.wait().await;
+            // The kill is the proxy's doing, not how the target failed: the
+            // shutdown is reported as the signal that asked for it, if any
+            killed_exit_code(signal)
+        }
+    }
 }
 
-struct ProxyTasks {
-    stdin_task: tokio::task::JoinHandle<()>,
-    stdout_task: tokio::task::JoinHandle<()>,
-    stderr_task: tokio::task::JoinHandle<()>,
-    child_task: tokio::task::JoinHandle<()>,
+fn killed_exit_code(signal: Option<i32>) -> i32 {
+    signal.map_or(0, |signal| 128 + signal)
 }
 
-async fn process_stdin_loop(
-    our_stdin: tokio::io::Stdin,
-    child_stdin: &mut tokio::process::ChildStdin,
-    detection_engine: &mut RegexDetectionEngine,
-    ollama_client: &OllamaClient,
-    faker_engine: &mut FakerEngine,
-    mapping_store: &mut MappingStore,
-    model_name: &str,
-    detection_mode: &DetectionMode,
-    shutdown_tx: &mpsc::UnboundedSender<()>,
-) -> Result<()> {
-    let mut reader = BufReader::new(our_stdin);
-    let mut line = String::new();
-
-    loop {
-        line.clear();
-        match reader.read_line(&mut line).await {
-            Ok(0) => {
-                info!("EOF on stdin, shutting down");
-                shutdown_tx.send(()).ok();
-                break;
-            }
-            Ok(_) => {
-                if let Err(e) = process_and_forward_line(
-                    &line,
-                    child_stdin,
-                    detection_engine,
-                    ollama_client,
-                    faker_engine,
-                    mapping_store,
-                    model_name,
-                    detection_mode,
-                    "request"
-                ).await {
-                    error!("Failed to process stdin line: {}", e);
-                    shutdown_tx.send(()).ok();
-                    break;
-                }
-            }
-            Err(e) => {
-                error!("Failed to read from stdin: {}", e);
-                shutdown_tx.send(()).ok();
-                break;
-            }
+/// Waits for SIGINT or SIGTERM and returns its number.
+#[cfg(unix)]
+async fn shutdown_signal() -> Result<i32> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut interrupt = signal(SignalKind::interrupt())?;
+    let mut terminate = signal(SignalKind::terminate())?;
+    tokio::select! {
+        _ = interrupt.recv() => Ok(libc::SIGINT),
+        _ = terminate.recv() => Ok(libc::SIGTERM),
+    }
+}
+
+#[cfg(not(unix))]
+async fn shutdown_signal() -> Result<i32> {
+    tokio::signal::ctrl_c().await?;
+    Ok(2)
+}
+
+/// Forwards `signal` to the target, unless it shares the proxy's process
+/// group: a signal from the terminal then reached it already.
+#[cfg(unix)]
+fn forward_signal(child: &Child, signal: i32) {
+    let Some(pid) = child.id() else {
+        return;
+    };
+    // SAFETY: getpgid() and getpgrp() have no memory-safety preconditions
+    if unsafe { libc::getpgid(pid as libc::pid_t) == libc::getpgrp() } {
+        debug!("Child process shares the proxy's process group, not forwarding signal {}", signal);
+        return;
+    }
+    // SAFETY: kill() has no memory-safety preconditions; the pid is our own child
+    if unsafe { libc::kill(pid as libc::pid_t, signal) } != 0 {
+        warn!("Failed to forward signal {} to child process: {}", signal, std::io::Error::last_os_error());
+    }
+}
+
+// Without signals to forward, the child is stopped the only way available
+#[cfg(not(unix))]
+fn forward_signal(child: &mut Child, _signal: i32) {
+    if let Err(e) = child.start_kill() {
+        warn!("Failed to stop child process: {}", e);
+    }
+}
+
+fn exit_code(status: Option<ExitStatus>) -> i32 {
+    let Some(status) = status else {
+        return 1;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(signal) = status.signal() {
+            return 128 + signal;
         }
     }
-    Ok(())
+    status.code().unwrap_or(1)
 }
 
-async fn process_stdout_loop(
-    child_stdout: tokio::process::ChildStdout,
-    our_stdout: &mut tokio::io::Stdout,
-    detection_engine: &mut RegexDetectionEngine,
-    ollama_client: &OllamaClient,
-    faker_engine: &mut FakerEngine,
-    mapping_store: &mut MappingStore,
-    model_name: &str,
-    detection_mode: &DetectionMode,
-    shutdown_tx: &mpsc::UnboundedSender<()>,
-) -> Result<()> {
-    let mut reader = BufReader::new(child_stdout);
-    let mut line = String::new();
-
-    loop {
-        line.clear();
-        match reader.read_line(&mut line).await {
-            Ok(0) => {
-                info!("EOF on child stdout");
-                shutdown_tx.send(()).ok();
-                break;
-            }
-            Ok(_) => {
-                if let Err(e) = process_and_forward_line(
-                    &line,
-                    our_stdout,
-                    detection_engine,
-                    ollama_client,
-                    faker_engine,
-                    mapping_store,
-                    model_name,
-                    detection_mode,
-                    "response"
-                ).await {
-                    error!("Failed to process stdout line: {}", e);
-                    shutdown_tx.send(()).ok();
+fn format_rate(rate: Option<f64>) -> String {
+    rate.map(|rate| format!(" ({:.1}% hit rate)", rate * 100.0)).unwrap_or_default()
+}
+
+/// Aborts a background task when the proxy stops, however it stops.
+struct AbortOnDrop(tokio::task::JoinHandle<()>);
+
+impl Drop for AbortOnDrop {
+    fn drop(&mut self) {
+        self.0.abort();
+    }
+}
+
+fn session_scope(mapping: &MappingConfig) -> Option<SessionScope> {
+    mapping.reset_on_initialize.then(|| SessionScope::new(mapping.clone()))
+}
+
+/// Buffer of the in-memory pipes between a gateway target's pipelines and the router
+const GATEWAY_PIPE_BYTES: usize = 64 * 1024;
+
+enum GatewayEvent {
+    /// A client message, and the framing it came in
+    Client(String, Framing),
+    Target(usize, String),
+    TargetClosed(usize),
+    ClientClosed,
+    /// A fan-out ran out of time
+    Expired,
+}
+
+/// Reads the client's messages with the framing and size limit of a single
+/// target's proxy; the router needs them whole.
+fn spawn_client_reader(events: mpsc::UnboundedSender<GatewayEvent>, framing: Framing, max_line_bytes: usize) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut reader = MessageReader::new(stdin(), framing, max_line_bytes);
+        loop {
+            match reader.next().await {
+                Ok(Some(message)) => {
+                    if events.send(GatewayEvent::Client(message, reader.framing())).is_err() {
+                        return;
+                    }
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    error!("Failed to read from stdin: {}", e);
                     break;
                 }
             }
-            Err(e) => {
-                error!("Failed to read from child stdout: {}", e);
-                shutdown_tx.send(()).ok();
-                break;
-            }
         }
-    }
-    Ok(())
+        events.send(GatewayEvent::ClientClosed).ok();
+    })
 }
 
-async fn process_and_forward_line<W: AsyncWriteExt + Unpin>(
-    line: &str,
-    writer: &mut W,
-    detection_engine: &mut RegexDetectionEngine,
-    ollama_client: &OllamaClient,
-    faker_engine: &mut FakerEngine,
-    mapping_store: &mut MappingStore,
-    model_name: &str,
-    detection_mode: &DetectionMode,
-    direction: &str,
-) -> Result<()> {
-    let original_line = line.trim();
-    debug!("Processing {}: {}", direction, original_line);
-
-    match process_request_with_pii_detection(
-        original_line,
-        detection_engine,
-        ollama_client,
-        faker_engine,
-        mapping_store,
-        model_name,
-        detection_mode,
-    ).await {
-        Ok(processed_line) => {
-            if processed_line != original_line {
-                info!("PII detected and anonymized in {}", direction);
-                debug!("Original: {}", original_line);
-                debug!("Processed: {}", processed_line);
-            }
-            
-            writer.write_all((processed_line + "\n").as_bytes()).await?;
-            writer.flush().await?;
-        }
-        Err(e) => {
-            warn!("Error processing {} for PII, forwarding original: {}", direction, e);
-            writer.write_all(line.as_bytes()).await?;
-            writer.flush().await?;
-        }
-    }
-    Ok(())
+struct IoHandles {
+    child_stdin: tokio::process::ChildStdin,
+    child_stdout: tokio::process::ChildStdout,
+    child_stderr: tokio::process::ChildStderr,
+    our_stdin: tokio::io::Stdin,
+    our_stdout: tokio::io::Stdout,
+}
+
+struct ProxyTasks {
+    stdin_task: tokio::task::JoinHandle<()>,
+    stdout_task: tokio::task::JoinHandle<()>,
+    stderr_task: tokio::task::JoinHandle<()>,
+    child_task: tokio::task::JoinHandle<()>,
 }
 
 fn spawn_stderr_task(child_stderr: tokio::process::ChildStderr, _shutdown_tx: mpsc::UnboundedSender<()>) -> tokio::task::JoinHandle<()> {
@@ -407,257 +1058,35 @@ fn spawn_stderr_task(child_stderr: tokio::process::ChildStderr, _shutdown_tx: mp
     })
 }
 
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
 
-async fn process_request_with_pii_detection(
-    line: &str,
-    detection_engine: &mut RegexDetectionEngine,
-    ollama_client: &OllamaClient,
-    faker_engine: &mut FakerEngine,
-    mapping_store: &mut MappingStore,
-    model_name: &str,
-    detection_mode: &DetectionMode,
-) -> Result<String> {
-    let json_value: Value = serde_json::from_str(line)?;
-    
-    // Check if this is a JSON-RPC/MCP protocol message - if so, skip PII processing
-    if is_jsonrpc_protocol_message(&json_value) {
-        debug!("Skipping PII processing for JSON-RPC/MCP protocol message");
-        return Ok(line.to_string());
-    }
-    
-    let mut json_value = json_value;
-    let any_changes = process_json_for_pii(
-        &mut json_value, 
-        detection_engine, 
-        ollama_client, 
-        faker_engine, 
-        mapping_store, 
-        model_name,
-        detection_mode
-    ).await.unwrap_or(false);
-    
-    if any_changes {
-        serde_json::to_string(&json_value)
-            .map_err(|e| anyhow::anyhow!("Failed to serialize modified JSON: {}", e))
-    } else {
-        Ok(line.to_string())
-    }
-}
-
-fn is_jsonrpc_protocol_message(json_value: &Value) -> bool {
-    if let Some(obj) = json_value.as_object() {
-        // MCP protocol control messages - skip PII processing
-        if obj.contains_key("protocolVersion") ||
-           obj.contains_key("capabilities") ||
-           obj.contains_key("serverInfo") ||
-           obj.contains_key("clientInfo") {
-            return true;
-        }
-        
-        // JSON-RPC requests - skip PII processing
-        if obj.contains_key("method") && obj.contains_key("id") {
-            return true;
-        }
-        
-        // JSON-RPC error responses - skip PII processing
-        if obj.contains_key("error") && obj.contains_key("id") {
-            return true;
-        }
-        
-        // JSON-RPC responses with results - check if they contain user data
-        if obj.contains_key("result") && obj.contains_key("id") {
-            // If the result contains a "content" field, this is likely tool response data that should be processed
-            if let Some(result) = obj.get("result") {
-                if let Some(result_obj) = result.as_object() {
-                    if result_obj.contains_key("content") {
-                        return false; // Process this for PII - it contains user data
-                    }
-                }
-            }
-            // Otherwise, it's a protocol control response (initialize, tools/list, etc.)
-            return true;
-        }
-        
-        // Non-JSON-RPC messages should be processed
-        false
-    } else {
-        false
-    }
-}
-
-fn process_json_for_pii<'a>(
-    value: &'a mut Value,
-    detection_engine: &'a mut RegexDetectionEngine,
-    ollama_client: &'a OllamaClient,
-    faker_engine: &'a mut FakerEngine,
-    mapping_store: &'a mut MappingStore,
-    model_name: &'a str,
-    detection_mode: &'a DetectionMode,
-) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<bool>> + Send + 'a>> {
-    Box::pin(async move {
-        let mut any_changes = false;
-        
-        match value {
-            Value::String(text) => {
-                // Only bother with non-trivial strings
-                if text.trim().len() > 3 {
-                    if let Ok(processed_text) = process_text_through_pipeline(
-                        text,
-                        detection_engine,
-                        ollama_client,
-                        faker_engine,
-                        mapping_store,
-                        model_name,
-                        detection_mode,
-                    ).await {
-                        if processed_text != *text {
-                            *text = processed_text;
-                            any_changes = true;
-                        }
-                    }
-                }
-            }
-            Value::Array(arr) => {
-                for item in arr.iter_mut() {
-                    if process_json_for_pii(item, detection_engine, ollama_client, faker_engine, mapping_store, model_name, detection_mode).await? {
-                        any_changes = true;
-                    }
-                }
-            }
-            Value::Object(obj) => {
-                for (_, val) in obj.iter_mut() {
-                    if process_json_for_pii(val, detection_engine, ollama_client, faker_engine, mapping_store, model_name, detection_mode).await? {
-                        any_changes = true;
-                    }
-                }
-            }
-            _ => {}
-        }
-        
-        Ok(any_changes)
-    })
-}
-
-async fn process_text_through_pipeline(
-    text: &str,
-    detection_engine: &mut RegexDetectionEngine,
-    ollama_client: &OllamaClient,
-    faker_engine: &mut FakerEngine,
-    mapping_store: &mut MappingStore,
-    model_name: &str,
-    detection_mode: &DetectionMode,
-) -> Result<String> {
-    let combined_entities = match detection_mode {
-        DetectionMode::Regex => {
-            // Regex-only detection
-            detection_engine.detect_in_text(text)
-        }
-        DetectionMode::Llm => {
-            // LLM-only detection
-            let llm_entities = get_llm_entities(text, ollama_client, mapping_store, model_name).await?;
-            llm_entities
-        }
-        DetectionMode::RegexLlm => {
-            // Hybrid approach: regex first, then LLM
-            let regex_entities = detection_engine.detect_in_text(text);
-            let llm_entities = get_llm_entities(text, ollama_client, mapping_store, model_name).await?;
-            combine_entities(regex_entities, llm_entities)
-        }
-    };
-    
-    if combined_entities.is_empty() {
-        return Ok(text.to_string());
+    #[test]
+    fn test_exit_code_follows_the_child() {
+        assert_eq!(exit_code(Some(ExitStatus::from_raw(0))), 0);
+        assert_eq!(exit_code(Some(ExitStatus::from_raw(3 << 8))), 3);
+        assert_eq!(exit_code(Some(ExitStatus::from_raw(libc::SIGTERM))), 128 + libc::SIGTERM);
+        assert_eq!(exit_code(None), 1);
+        assert_eq!(killed_exit_code(None), 0);
+        assert_eq!(killed_exit_code(Some(libc::SIGINT)), 128 + libc::SIGINT);
     }
-    
-    let anonymized_entities = create_anonymized_entities(combined_entities, faker_engine, mapping_store).await?;
-    apply_replacements(text, &anonymized_entities)
-}
 
-async fn get_llm_entities(
-    text: &str,
-    ollama_client: &OllamaClient,
-    mapping_store: &mut MappingStore,
-    model_name: &str,
-) -> Result<Vec<DetectedEntity>> {
-    // Check cache first
-    if let Some(cached) = mapping_store.get_llm_cache(text, model_name)? {
-        return Ok(cached);
-    }
-    
-    // Try LLM if available
-    if ollama_client.health_check().await.unwrap_or(false) {
-        match ollama_client.extract_entities(text).await {
-            Ok(entities) => {
-                mapping_store.store_llm_cache(text, &entities, model_name)?;
-                Ok(entities)
-            }
-            Err(e) => {
-                debug!("Ollama extraction failed, using regex-only: {}", e);
-                Ok(Vec::new())
-            }
-        }
-    } else {
-        debug!("Ollama not available, using regex-only detection");
-        Ok(Vec::new())
-    }
-}
+    #[tokio::test]
+    async fn test_signals_reach_the_target_once() {
+        use std::time::Duration;
 
-async fn create_anonymized_entities(
-    entities: Vec<DetectedEntity>,
-    faker_engine: &mut FakerEngine,
-    mapping_store: &mut MappingStore,
-) -> Result<Vec<AnonymizedEntity>> {
-    let mut anonymized_entities = Vec::new();
-    
-    for entity in entities {
-        let anonymized = if let Some(existing_fake) = mapping_store.get_mapping(&entity.entity_type, &entity.original_value)? {
-            AnonymizedEntity {
-                entity_type: entity.entity_type,
-                original_value: entity.original_value,
-                fake_value: existing_fake,
-                mapping_id: format!("existing-{}", std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_nanos()),
-            }
-        } else {
-            let anonymized = faker_engine.anonymize_entity(&entity)?;
-            mapping_store.store_mapping(&anonymized)?;
-            anonymized
-        };
-        anonymized_entities.push(anonymized);
-    }
-    
-    Ok(anonymized_entities)
-}
+        // A target in the proxy's process group got a terminal's signal already,
+        // so it is left to the grace period
+        let mut shared = Command::new("sleep").arg("30").kill_on_drop(true).spawn().unwrap();
+        let started = std::time::Instant::now();
+        assert_eq!(stop_child(&mut shared, Some(libc::SIGTERM), Duration::from_millis(300)).await, 128 + libc::SIGTERM);
+        assert!(started.elapsed() >= Duration::from_millis(300));
 
-// Prefer deterministic deduplication over complex overlap detection
-fn combine_entities(regex_entities: Vec<DetectedEntity>, llm_entities: Vec<DetectedEntity>) -> Vec<DetectedEntity> {
-    let mut combined = HashMap::new();
-    
-    // Add regex entities first (lower priority)
-    for entity in regex_entities {
-        let key = format!("{}:{}:{}", entity.entity_type, entity.start, entity.end);
-        combined.insert(key, entity);
-    }
-    
-    // LLM entities override regex ones
-    for entity in llm_entities {
-        let key = format!("{}:{}:{}", entity.entity_type, entity.start, entity.end);
-        combined.insert(key, entity);
+        let mut own_group = Command::new("sleep").arg("30").process_group(0).kill_on_drop(true).spawn().unwrap();
+        let started = std::time::Instant::now();
+        assert_eq!(stop_child(&mut own_group, Some(libc::SIGTERM), Duration::from_secs(5)).await, 128 + libc::SIGTERM);
+        assert!(started.elapsed() < Duration::from_secs(5));
     }
-    
-    combined.into_values().collect()
 }
-
-// Simple text replacement - good enough for most cases
-fn apply_replacements(text: &str, entities: &[AnonymizedEntity]) -> Result<String> {
-    let mut result = text.to_string();
-    
-    // Sort by position to avoid messing up indices during replacement
-    let mut sorted_entities: Vec<_> = entities.iter().collect();
-    sorted_entities.sort_by_key(|e| text.find(&e.original_value).unwrap_or(0));
-    
-    for entity in sorted_entities {
-        result = result.replace(&entity.original_value, &entity.fake_value);
-    }
-    
-    Ok(result)
-}
\ No newline at end of file
@@ -0,0 +1,303 @@
+//! Encrypted samples of critical detections for quality review
+//!
+//! Detection accuracy can only be judged against the plaintext, but logging
+//! every message defeats the purpose of the proxy. With the quarantine
+//! enabled, a small fraction of the messages in which a critical entity type
+//! (SSNs, card numbers, ...) was replaced is kept as an original/processed
+//! pair, so a reviewer can periodically check what was caught and what
+//! slipped through with `mcp-server-conceal qa-samples`.
+//!
+//! Samples are encrypted with AES-256-GCM under a key derived from the mapping
+//! passphrase and deleted once they are older than the retention period, or
+//! when the table is full, oldest first. Viewing them is recorded in the audit
+//! log.
+
+use crate::config::{base_entity_type, Config};
+use crate::crypto;
+use crate::pipeline::Direction;
+use crate::secrets::{self, Passphrase};
+use anyhow::Result;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+const ENCRYPTION_KEY_CONTEXT: &[u8] = b"mcp-server-conceal qa quarantine encryption v1";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuarantineConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Fraction of qualifying messages kept, between 0.0 and 1.0
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    /// Only messages in which one of these types was replaced qualify
+    #[serde(default = "default_entity_types")]
+    pub entity_types: Vec<String>,
+    /// Samples older than this are deleted
+    #[serde(default = "default_retention_days")]
+    pub retention_days: u32,
+    /// Oldest samples are deleted beyond this many
+    #[serde(default = "default_max_samples")]
+    pub max_samples: usize,
+    /// Sample database; relative paths live in the data directory
+    #[serde(default = "default_quarantine_path")]
+    pub path: PathBuf,
+}
+
+impl Default for QuarantineConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            sample_rate: default_sample_rate(),
+            entity_types: default_entity_types(),
+            retention_days: default_retention_days(),
+            max_samples: default_max_samples(),
+            path: default_quarantine_path(),
+        }
+    }
+}
+
+fn default_sample_rate() -> f64 {
+    0.01
+}
+
+fn default_entity_types() -> Vec<String> {
+    ["ssn", "credit_card", "passport", "bank_account", "iban"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+fn default_retention_days() -> u32 {
+    7
+}
+
+fn default_max_samples() -> usize {
+    1000
+}
+
+fn default_quarantine_path() -> PathBuf {
+    PathBuf::from("qa_samples.db")
+}
+
+impl QuarantineConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.sample_rate) {
+            return Err(anyhow::anyhow!("Quarantine sample rate must be between 0.0 and 1.0"));
+        }
+        if self.retention_days == 0 {
+            return Err(anyhow::anyhow!("quarantine.retention_days must be at least 1"));
+        }
+        Ok(())
+    }
+
+    /// Whether a replaced entity of this type makes its message a candidate.
+    pub fn is_critical(&self, entity_type: &str) -> bool {
+        let base_type = base_entity_type(entity_type);
+        self.entity_types.iter().any(|t| t.eq_ignore_ascii_case(base_type))
+    }
+}
+
+/// A decrypted sample.
+#[derive(Debug, Clone)]
+pub struct QaSample {
+    pub id: i64,
+    pub direction: String,
+    /// Critical types replaced in the message, comma separated
+    pub entity_types: String,
+    pub original: String,
+    pub processed: String,
+    pub sampled_at: u64,
+}
+
+pub struct QuarantineStore {
+    conn: Connection,
+    path: PathBuf,
+    encryption_key: [u8; 32],
+    config: QuarantineConfig,
+}
+
+impl QuarantineStore {
+    pub fn open(config: &QuarantineConfig, passphrase: &Passphrase) -> Result<Self> {
+        let conn = if config.path == Path::new(":memory:") {
+            Connection::open_in_memory()?
+        } else {
+            if let Some(parent) = config.path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            Connection::open(&config.path)
+                .map_err(|e| anyhow::anyhow!("Failed to open QA quarantine {}: {}", config.path.display(), e))?
+        };
+        // Both proxy directions write to the same file
+        conn.busy_timeout(Duration::from_secs(5))?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS qa_samples (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                direction TEXT NOT NULL,
+                entity_types TEXT NOT NULL,
+                nonce BLOB NOT NULL,
+                ciphertext BLOB NOT NULL,
+                sampled_at INTEGER NOT NULL
+            )",
+            [],
+        )?;
+
+        let mut store = Self {
+            conn,
+            path: config.path.clone(),
+            encryption_key: crypto::derive_key(passphrase, ENCRYPTION_KEY_CONTEXT),
+            config: config.clone(),
+        };
+        store.enforce_retention()?;
+        Ok(store)
+    }
+
+    /// Opens the configured quarantine, if it is enabled. The mapping
+    /// passphrase is required.
+    pub fn from_config(config: &Config) -> Result<Option<Self>> {
+        if !config.quarantine.enabled {
+            return Ok(None);
+        }
+        let passphrase = secrets::require_passphrase(&config.mapping)
+            .map_err(|e| anyhow::anyhow!("The QA quarantine needs the mapping passphrase: {}", e))?;
+        Self::open(&config.quarantine, &passphrase).map(Some)
+    }
+
+    /// Another connection to the same quarantine, for use from a second task.
+    pub fn try_clone(&self) -> Result<Self> {
+        if self.path == Path::new(":memory:") {
+            return Err(anyhow::anyhow!("An in-memory QA quarantine cannot be shared"));
+        }
+        let conn = Connection::open(&self.path)?;
+        conn.busy_timeout(Duration::from_secs(5))?;
+        Ok(Self {
+            conn,
+            path: self.path.clone(),
+            encryption_key: self.encryption_key,
+            config: self.config.clone(),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn config(&self) -> &QuarantineConfig {
+        &self.config
+    }
+
+    /// Draws whether a qualifying message is kept.
+    pub fn should_sample(&self) -> bool {
+        rand::random::<f64>() < self.config.sample_rate
+    }
+
+    /// Stores an original/processed pair. `entity_types` are the critical
+    /// types replaced in it.
+    pub fn record(&mut self, direction: Direction, entity_types: &[String], original: &str, processed: &str) -> Result<()> {
+        let pair = serde_json::to_vec(&(original, processed))?;
+        let (nonce, ciphertext) = crypto::encrypt(&self.encryption_key, &pair)?;
+        self.conn.execute(
+            "INSERT INTO qa_samples (direction, entity_types, nonce, ciphertext, sampled_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![direction.as_str(), entity_types.join(","), nonce, ciphertext, now_secs()],
+        )?;
+        debug!("Quarantined a {} sample for QA ({})", direction, entity_types.join(", "));
+        self.enforce_retention()
+    }
+
+    /// Deletes samples past the retention period and beyond the size limit.
+    pub fn enforce_retention(&mut self) -> Result<()> {
+        let cutoff = now_secs().saturating_sub(u64::from(self.config.retention_days) * 86_400);
+        self.conn.execute("DELETE FROM qa_samples WHERE sampled_at < ?1", params![cutoff])?;
+        self.conn.execute(
+            "DELETE FROM qa_samples WHERE id NOT IN (SELECT id FROM qa_samples ORDER BY id DESC LIMIT ?1)",
+            params![self.config.max_samples as i64],
+        )?;
+        Ok(())
+    }
+
+    pub fn len(&self) -> Result<usize> {
+        let count: i64 = self.conn.query_row("SELECT COUNT(*) FROM qa_samples", [], |row| row.get(0))?;
+        Ok(count as usize)
+    }
+
+    pub fn is_empty(&self) -> Result<bool> {
+        Ok(self.len()? == 0)
+    }
+
+    /// Oldest samples first, decrypted.
+    pub fn samples(&self, limit: usize) -> Result<Vec<QaSample>> {
+        let mut stmt = self.conn.prepare(
+            "SELECT id, direction, entity_types, nonce, ciphertext, sampled_at FROM qa_samples ORDER BY id LIMIT ?1",
+        )?;
+        let rows = stmt.query_map(params![limit as i64], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Vec<u8>>(3)?,
+                row.get::<_, Vec<u8>>(4)?,
+                row.get::<_, u64>(5)?,
+            ))
+        })?;
+
+        let mut samples = Vec::new();
+        for row in rows {
+            let (id, direction, entity_types, nonce, ciphertext, sampled_at) = row?;
+            let pair = crypto::decrypt(&self.encryption_key, &nonce, &ciphertext)?;
+            let (original, processed): (String, String) = serde_json::from_slice(&pair)?;
+            samples.push(QaSample { id, direction, entity_types, original, processed, sampled_at });
+        }
+        Ok(samples)
+    }
+
+    /// Deletes every sample, typically once they have been reviewed.
+    pub fn clear(&mut self) -> Result<usize> {
+        Ok(self.conn.execute("DELETE FROM qa_samples", [])?)
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_samples_round_trip_and_respect_limits() {
+        let config = QuarantineConfig {
+            enabled: true,
+            max_samples: 2,
+            path: PathBuf::from(":memory:"),
+            ..QuarantineConfig::default()
+        };
+        let mut store = QuarantineStore::open(&config, &Passphrase::new("test passphrase")).unwrap();
+        let types = vec!["ssn".to_string()];
+
+        for n in 1..=3 {
+            store.record(Direction::Response, &types, &format!("SSN 123-45-678{}", n), "SSN 987-65-4321").unwrap();
+        }
+        let samples = store.samples(10).unwrap();
+        assert_eq!(samples.len(), 2);
+        assert_eq!(samples[0].original, "SSN 123-45-6782");
+        assert_eq!((samples[1].direction.as_str(), samples[1].entity_types.as_str()), ("response", "ssn"));
+
+        // Nothing readable is stored
+        let ciphertext: Vec<u8> = store.conn
+            .query_row("SELECT ciphertext FROM qa_samples LIMIT 1", [], |row| row.get(0))
+            .unwrap();
+        assert!(!String::from_utf8_lossy(&ciphertext).contains("123-45"));
+
+        store.conn.execute("UPDATE qa_samples SET sampled_at = 0 WHERE id = ?1", params![samples[0].id]).unwrap();
+        store.enforce_retention().unwrap();
+        assert_eq!(store.len().unwrap(), 1);
+        assert_eq!(store.clear().unwrap(), 1);
+
+        assert!(config.is_critical("SSN") && config.is_critical("credit_card@finance"));
+        assert!(!config.is_critical("email"));
+    }
+}
@@ -0,0 +1,240 @@
+//! Shared Redis cache in front of the mapping store
+//!
+//! Several concealed MCP servers can share one Redis instance, so a value one
+//! proxy has mapped gets the same fake everywhere and an LLM result computed
+//! once is reused by all. SQLite stays the durable store of each proxy; Redis
+//! only holds copies with a TTL, and every Redis failure falls back to SQLite.
+//!
+//! Keys are the same hashes SQLite uses, never values. LLM results contain
+//! the detected values, so they are only cached in Redis encrypted, which
+//! needs `mapping.encryption` (and with it the mapping passphrase).
+//!
+//! The client is compiled in with the `redis` cargo feature.
+
+use crate::config::DetectedEntity;
+use crate::secrets::Passphrase;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "redis")]
+use crate::crypto;
+#[cfg(feature = "redis")]
+use std::sync::Mutex;
+#[cfg(feature = "redis")]
+use std::time::Duration;
+
+#[cfg(feature = "redis")]
+const LLM_KEY_CONTEXT: &[u8] = b"mcp-server-conceal redis llm cache v1";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RedisCacheConfig {
+    /// e.g. `redis://cache.internal:6379/0`
+    pub url: String,
+    /// Lifetime of cached entries; SQLite keeps the durable copy
+    #[serde(default = "default_ttl_seconds")]
+    pub ttl_seconds: u64,
+    /// Prepended to every key, so proxies with different rules can share an instance
+    #[serde(default = "default_key_prefix")]
+    pub key_prefix: String,
+    /// Connect, read and write timeout; a slow cache must not stall the proxy
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+fn default_ttl_seconds() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_key_prefix() -> String {
+    "mcp-conceal".to_string()
+}
+
+fn default_timeout_ms() -> u64 {
+    200
+}
+
+#[cfg(any(feature = "redis", test))]
+fn mapping_key(prefix: &str, entity_type: &str, original_hash: &str) -> String {
+    format!("{}:map:{}:{}", prefix, entity_type, original_hash)
+}
+
+#[cfg(any(feature = "redis", test))]
+fn llm_key(prefix: &str, model_name: &str, text_hash: &str) -> String {
+    format!("{}:llm:{}:{}", prefix, model_name, text_hash)
+}
+
+#[cfg(feature = "redis")]
+pub struct RedisCache {
+    conn: Mutex<redis::Connection>,
+    config: RedisCacheConfig,
+    /// Without it LLM results are not cached in Redis
+    llm_key: Option<[u8; 32]>,
+}
+
+#[cfg(feature = "redis")]
+impl RedisCache {
+    pub fn connect(config: &RedisCacheConfig, passphrase: Option<&Passphrase>) -> Result<Self> {
+        let timeout = Duration::from_millis(config.timeout_ms.max(1));
+        let client = redis::Client::open(config.url.as_str())
+            .map_err(|e| anyhow::anyhow!("Invalid Redis URL: {}", e))?;
+        let conn = client.get_connection_with_timeout(timeout)
+            .map_err(|e| anyhow::anyhow!("Failed to connect to Redis: {}", e))?;
+        conn.set_read_timeout(Some(timeout))?;
+        conn.set_write_timeout(Some(timeout))?;
+
+        Ok(Self {
+            conn: Mutex::new(conn),
+            config: config.clone(),
+            llm_key: passphrase.map(|passphrase| crypto::derive_key(passphrase, LLM_KEY_CONTEXT)),
+        })
+    }
+
+    fn query<T: redis::FromRedisValue>(&self, cmd: &redis::Cmd) -> Result<T> {
+        let mut conn = self.conn.lock().map_err(|_| anyhow::anyhow!("Redis connection poisoned"))?;
+        Ok(cmd.query(&mut *conn)?)
+    }
+
+    pub fn get_mapping(&self, entity_type: &str, original_hash: &str) -> Result<Option<String>> {
+        self.query(redis::cmd("GET").arg(mapping_key(&self.config.key_prefix, entity_type, original_hash)))
+    }
+
+    /// Stores `fake_value` unless another proxy cached a fake for the value
+    /// first. Returns the fake that won.
+    pub fn claim_mapping(&self, entity_type: &str, original_hash: &str, fake_value: &str) -> Result<String> {
+        let key = mapping_key(&self.config.key_prefix, entity_type, original_hash);
+        let stored: Option<String> = self.query(
+            redis::cmd("SET").arg(&key).arg(fake_value).arg("NX").arg("EX").arg(self.config.ttl_seconds),
+        )?;
+        if stored.is_some() {
+            return Ok(fake_value.to_string());
+        }
+        // Lost the race; an entry that expired in between is simply ours
+        Ok(self.get_mapping(entity_type, original_hash)?.unwrap_or_else(|| fake_value.to_string()))
+    }
+
+    pub fn delete_mapping(&self, entity_type: &str, original_hash: &str) -> Result<()> {
+        self.query::<()>(redis::cmd("DEL").arg(mapping_key(&self.config.key_prefix, entity_type, original_hash)))
+    }
+
+    pub fn caches_llm_results(&self) -> bool {
+        self.llm_key.is_some()
+    }
+
+    /// Cached LLM results for each hash, in order. Entries that cannot be
+    /// decrypted count as misses.
+    pub fn get_llm_results(&self, model_name: &str, text_hashes: &[&str]) -> Result<Vec<Option<Vec<DetectedEntity>>>> {
+        let Some(key) = self.llm_key.as_ref().filter(|_| !text_hashes.is_empty()) else {
+            return Ok(vec![None; text_hashes.len()]);
+        };
+        let keys: Vec<String> = text_hashes.iter()
+            .map(|hash| llm_key(&self.config.key_prefix, model_name, hash))
+            .collect();
+        let values: Vec<Option<Vec<u8>>> = self.query(redis::cmd("MGET").arg(&keys))?;
+
+        Ok(values.into_iter()
+            .map(|value| {
+                let value = value.filter(|v| v.len() > 12)?;
+                let plaintext = crypto::decrypt(key, &value[..12], &value[12..]).ok()?;
+                serde_json::from_slice(&plaintext).ok()
+            })
+            .collect())
+    }
+
+    pub fn set_llm_result(&self, model_name: &str, text_hash: &str, entities: &[DetectedEntity]) -> Result<()> {
+        let Some(key) = self.llm_key.as_ref() else {
+            return Ok(());
+        };
+        let (mut value, ciphertext) = crypto::encrypt(key, &serde_json::to_vec(entities)?)?;
+        value.extend_from_slice(&ciphertext);
+        self.query::<()>(
+            redis::cmd("SET")
+                .arg(llm_key(&self.config.key_prefix, model_name, text_hash))
+                .arg(value)
+                .arg("EX")
+                .arg(self.config.ttl_seconds),
+        )
+    }
+}
+
+/// Stand-in when the `redis` feature is off: connecting always fails, so no
+/// value of it ever exists.
+#[cfg(not(feature = "redis"))]
+pub struct RedisCache {
+    never: std::convert::Infallible,
+}
+
+#[cfg(not(feature = "redis"))]
+impl RedisCache {
+    pub fn connect(_config: &RedisCacheConfig, _passphrase: Option<&Passphrase>) -> Result<Self> {
+        Err(anyhow::anyhow!("This build has no Redis support; rebuild with `--features redis`"))
+    }
+
+    pub fn get_mapping(&self, _entity_type: &str, _original_hash: &str) -> Result<Option<String>> {
+        match self.never {}
+    }
+
+    pub fn claim_mapping(&self, _entity_type: &str, _original_hash: &str, _fake_value: &str) -> Result<String> {
+        match self.never {}
+    }
+
+    pub fn delete_mapping(&self, _entity_type: &str, _original_hash: &str) -> Result<()> {
+        match self.never {}
+    }
+
+    pub fn caches_llm_results(&self) -> bool {
+        match self.never {}
+    }
+
+    pub fn get_llm_results(&self, _model_name: &str, _text_hashes: &[&str]) -> Result<Vec<Option<Vec<DetectedEntity>>>> {
+        match self.never {}
+    }
+
+    pub fn set_llm_result(&self, _model_name: &str, _text_hash: &str, _entities: &[DetectedEntity]) -> Result<()> {
+        match self.never {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_hold_hashes_under_the_prefix() {
+        let config: RedisCacheConfig = toml::from_str(r#"url = "redis://127.0.0.1/""#).unwrap();
+        assert_eq!((config.ttl_seconds, config.timeout_ms), (86_400, 200));
+        assert_eq!(mapping_key(&config.key_prefix, "email", "ab12"), "mcp-conceal:map:email:ab12");
+        assert_eq!(llm_key("team-a", "llama3.2:3b", "cd34"), "team-a:llm:llama3.2:3b:cd34");
+    }
+
+    // Needs a Redis server: REDIS_URL=redis://127.0.0.1/ cargo test --features redis -- --ignored
+    #[cfg(feature = "redis")]
+    #[test]
+    #[ignore]
+    fn test_claims_and_encrypted_llm_results_against_a_live_server() {
+        let config = RedisCacheConfig {
+            url: std::env::var("REDIS_URL").unwrap_or_else(|_| "redis://127.0.0.1/".to_string()),
+            ttl_seconds: 60,
+            key_prefix: format!("mcp-conceal-test-{}", uuid::Uuid::new_v4()),
+            timeout_ms: 1000,
+        };
+        let cache = RedisCache::connect(&config, Some(&Passphrase::new("test"))).unwrap();
+
+        assert_eq!(cache.claim_mapping("email", "h1", "first@example.net").unwrap(), "first@example.net");
+        assert_eq!(cache.claim_mapping("email", "h1", "second@example.net").unwrap(), "first@example.net");
+        cache.delete_mapping("email", "h1").unwrap();
+        assert_eq!(cache.get_mapping("email", "h1").unwrap(), None);
+
+        let entities = vec![DetectedEntity {
+            entity_type: "email".to_string(),
+            original_value: "jane@acme.com".to_string(),
+            start: 0,
+            end: 13,
+            confidence: 0.9,
+            ..Default::default()
+        }];
+        cache.set_llm_result("model", "t1", &entities).unwrap();
+        let results = cache.get_llm_results("model", &["t1", "t2"]).unwrap();
+        assert_eq!(results[0].as_ref().unwrap()[0].original_value, "jane@acme.com");
+        assert!(results[1].is_none());
+    }
+}
@@ -0,0 +1,138 @@
+//! Admission control for LLM requests
+//!
+//! A small local model handles one or two generations at a time; anything more
+//! just queues inside Ollama until requests time out. The scheduler caps the
+//! number of in-flight requests and the number waiting for a slot. When the
+//! queue is full the caller is turned away immediately and falls back to
+//! regex-only detection for that string instead of stalling the proxy.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmConcurrencyConfig {
+    #[serde(default = "default_max_in_flight")]
+    pub max_in_flight: usize,
+    #[serde(default = "default_max_queue")]
+    pub max_queue: usize,
+}
+
+impl Default for LlmConcurrencyConfig {
+    fn default() -> Self {
+        Self {
+            max_in_flight: default_max_in_flight(),
+            max_queue: default_max_queue(),
+        }
+    }
+}
+
+fn default_max_in_flight() -> usize {
+    1
+}
+
+fn default_max_queue() -> usize {
+    32
+}
+
+/// Shared by all clones of a client.
+#[derive(Debug, Clone)]
+pub struct LlmScheduler {
+    slots: Arc<Semaphore>,
+    waiting: Arc<AtomicUsize>,
+    max_queue: usize,
+}
+
+/// Holds an in-flight slot until dropped.
+pub struct LlmSlot {
+    _permit: OwnedSemaphorePermit,
+}
+
+/// Counts a caller as waiting until dropped, so a wait that is cancelled
+/// (message timeout, latency budget, aborted task) leaves the queue.
+struct QueuePlace(Arc<AtomicUsize>);
+
+impl Drop for QueuePlace {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl LlmScheduler {
+    pub fn new(config: &LlmConcurrencyConfig) -> Self {
+        Self {
+            slots: Arc::new(Semaphore::new(config.max_in_flight.max(1))),
+            waiting: Arc::new(AtomicUsize::new(0)),
+            max_queue: config.max_queue,
+        }
+    }
+
+    /// Waits for an in-flight slot, or fails at once if the queue is full.
+    pub async fn acquire(&self) -> anyhow::Result<LlmSlot> {
+        if let Ok(permit) = self.slots.clone().try_acquire_owned() {
+            return Ok(LlmSlot { _permit: permit });
+        }
+
+        let queued = self.waiting.fetch_add(1, Ordering::SeqCst);
+        let _place = QueuePlace(self.waiting.clone());
+        if queued >= self.max_queue {
+            return Err(anyhow::anyhow!("LLM request queue is full ({} waiting)", queued));
+        }
+
+        let permit = self.slots.clone().acquire_owned().await?;
+        Ok(LlmSlot { _permit: permit })
+    }
+
+    /// Requests currently waiting for a slot.
+    pub fn queued(&self) -> usize {
+        self.waiting.load(Ordering::SeqCst)
+    }
+
+    pub fn available_slots(&self) -> usize {
+        self.slots.available_permits()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_limits_in_flight_and_rejects_when_queue_full() {
+        let scheduler = LlmScheduler::new(&LlmConcurrencyConfig { max_in_flight: 1, max_queue: 1 });
+
+        let slot = scheduler.acquire().await.unwrap();
+        assert_eq!(scheduler.available_slots(), 0);
+
+        let waiter = {
+            let scheduler = scheduler.clone();
+            tokio::spawn(async move { scheduler.acquire().await.map(|_| ()) })
+        };
+        while scheduler.queued() == 0 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+
+        // One in flight and one waiting: the next caller is turned away
+        assert!(scheduler.acquire().await.is_err());
+
+        drop(slot);
+        waiter.await.unwrap().unwrap();
+        assert_eq!(scheduler.queued(), 0);
+        assert_eq!(scheduler.available_slots(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waits_leave_the_queue() {
+        let scheduler = LlmScheduler::new(&LlmConcurrencyConfig { max_in_flight: 1, max_queue: 1 });
+        let slot = scheduler.acquire().await.unwrap();
+
+        for _ in 0..3 {
+            assert!(tokio::time::timeout(Duration::from_millis(5), scheduler.acquire()).await.is_err());
+            assert_eq!(scheduler.queued(), 0);
+        }
+        drop(slot);
+        assert!(scheduler.acquire().await.is_ok());
+    }
+}
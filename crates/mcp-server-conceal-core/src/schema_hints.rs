@@ -0,0 +1,210 @@
+//! Key hints inferred from the tool schemas a server advertises
+//!
+//! A `tools/list` response describes each tool's input and output with JSON
+//! Schema. Property names such as `customer_email` or `phoneNumber` and string
+//! formats such as `email` or `ipv4` say what a field holds, so results of that
+//! tool get key hints for those fields without any manual configuration.
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use tracing::debug;
+
+/// Lowercased property name → entity type, for one tool.
+pub type ToolHints = HashMap<String, String>;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SchemaHintsConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+impl Default for SchemaHintsConfig {
+    fn default() -> Self {
+        Self { enabled: default_enabled() }
+    }
+}
+
+fn default_enabled() -> bool {
+    false
+}
+
+/// Hints learned from `tools/list`, shared by both proxy directions.
+#[derive(Clone, Default)]
+pub struct SchemaHints {
+    tools: Arc<RwLock<HashMap<String, Arc<ToolHints>>>>,
+}
+
+impl SchemaHints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the hints of every tool in a `tools/list` result, replacing
+    /// what an earlier listing said about the same tool. Returns the number
+    /// of tools with at least one sensitive field.
+    pub fn learn(&self, result: &Value) -> usize {
+        let Some(tools) = result.get("tools").and_then(Value::as_array) else {
+            return 0;
+        };
+        let Ok(mut known) = self.tools.write() else {
+            return 0;
+        };
+
+        let mut hinted = 0;
+        for tool in tools {
+            let Some(name) = tool.get("name").and_then(Value::as_str) else {
+                continue;
+            };
+            let mut seen = HashMap::new();
+            for schema in ["inputSchema", "outputSchema"].iter().filter_map(|key| tool.get(*key)) {
+                collect_hints(schema, &mut seen);
+            }
+            let hints: ToolHints = seen.into_iter()
+                .filter_map(|(property, entity_type)| Some((property, entity_type?.to_string())))
+                .collect();
+            if hints.is_empty() {
+                known.remove(name);
+            } else {
+                debug!("Tool '{}' schema marks {} field(s) as sensitive", name, hints.len());
+                hinted += 1;
+                known.insert(name.to_string(), Arc::new(hints));
+            }
+        }
+        hinted
+    }
+
+    pub fn for_tool(&self, tool: &str) -> Option<Arc<ToolHints>> {
+        self.tools.read().ok()?.get(tool).cloned()
+    }
+}
+
+/// Walks a schema's properties, including those of nested objects, array
+/// items and `anyOf`/`oneOf`/`allOf` alternatives. Hints are looked up by
+/// property name alone, so a name that reads differently at two places,
+/// such as `contact` holding an email in one object and a company in
+/// another, is recorded as `None` and gives no hint at all.
+fn collect_hints(schema: &Value, seen: &mut HashMap<String, Option<&'static str>>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+    if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+        for (property, subschema) in properties {
+            let entity_type = infer_entity_type(property, subschema);
+            seen.entry(property.to_lowercase())
+                .and_modify(|known| if *known != entity_type { *known = None })
+                .or_insert(entity_type);
+            collect_hints(subschema, seen);
+        }
+    }
+    if let Some(items) = schema.get("items") {
+        collect_hints(items, seen);
+    }
+    for key in ["anyOf", "oneOf", "allOf"] {
+        for alternative in schema.get(key).and_then(Value::as_array).into_iter().flatten() {
+            collect_hints(alternative, seen);
+        }
+    }
+}
+
+/// Entity type of a property, from its string format or else its name.
+fn infer_entity_type(property: &str, schema: &Value) -> Option<&'static str> {
+    let format = schema.get("format").and_then(Value::as_str).unwrap_or_default();
+    match format {
+        "email" | "idn-email" => return Some("email"),
+        "ipv4" | "ipv6" => return Some("ip_address"),
+        "hostname" | "idn-hostname" => return Some("hostname"),
+        "phone" | "tel" => return Some("phone"),
+        _ => {}
+    }
+
+    // `customer_email`, `customerEmail` and `Customer-Email` all read the same
+    let name: String = property.chars().filter(char::is_ascii_alphanumeric).collect::<String>().to_ascii_lowercase();
+    if name.contains("email") {
+        Some("email")
+    } else if ["phone", "mobile", "telephone"].iter().any(|word| name.contains(word)) {
+        Some("phone")
+    } else if name == "ssn" || name.contains("socialsecurity") {
+        Some("ssn")
+    } else if matches!(name.as_str(), "dob" | "dateofbirth" | "birthdate" | "birthday") {
+        Some("date_of_birth")
+    } else if name.ends_with("ipaddress") {
+        Some("ip_address")
+    } else if name.ends_with("address") && !name.ends_with("macaddress") {
+        Some("address")
+    } else if is_person_name(&name) {
+        Some("name")
+    } else {
+        None
+    }
+}
+
+/// Names of people, as opposed to `filename`, `hostname`, `username` and the
+/// like. A bare `name` is as often a tool, file or project as a person, so
+/// it needs a qualifier.
+fn is_person_name(name: &str) -> bool {
+    const QUALIFIERS: &[&str] = &[
+        "full", "first", "last", "middle", "given", "family", "sur", "maiden",
+        "customer", "contact", "patient", "employee", "person", "owner", "author",
+    ];
+    name.strip_suffix("name").is_some_and(|qualifier| QUALIFIERS.contains(&qualifier))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_hints_from_property_names_and_formats() {
+        let hints = SchemaHints::new();
+        let listed = hints.learn(&json!({ "tools": [
+            { "name": "lookup_customer", "inputSchema": { "type": "object", "properties": {
+                "customer_id": { "type": "string" },
+                "contact": { "type": "string", "format": "email" },
+            } }, "outputSchema": { "type": "object", "properties": {
+                "customers": { "type": "array", "items": { "type": "object", "properties": {
+                    "fullName": { "type": "string" },
+                    "phoneNumber": { "type": "string" },
+                    "billing_address": { "type": "string" },
+                    "filename": { "type": "string" },
+                } } },
+            } } },
+            { "name": "read_file", "inputSchema": { "type": "object", "properties": { "path": { "type": "string" } } } },
+        ] }));
+
+        assert_eq!(listed, 1);
+        assert!(hints.for_tool("read_file").is_none());
+        let customer = hints.for_tool("lookup_customer").unwrap();
+        assert_eq!(customer.get("contact").map(String::as_str), Some("email"));
+        assert_eq!(customer.get("fullname").map(String::as_str), Some("name"));
+        assert_eq!(customer.get("phonenumber").map(String::as_str), Some("phone"));
+        assert_eq!(customer.get("billing_address").map(String::as_str), Some("address"));
+        assert!(!customer.contains_key("customer_id") && !customer.contains_key("filename"));
+    }
+
+    #[test]
+    fn test_ambiguous_names_give_no_hint() {
+        let hints = SchemaHints::new();
+        hints.learn(&json!({ "tools": [{ "name": "t", "outputSchema": { "properties": {
+            "name": { "type": "string" },
+            "contact": { "type": "string", "format": "email" },
+            "owner": { "type": "object", "properties": {
+                "contact": { "type": "string" },
+                "last_name": { "type": "string" },
+            } },
+        } } }] }));
+        let tool = hints.for_tool("t").unwrap();
+        assert_eq!(tool.get("last_name").map(String::as_str), Some("name"));
+        assert!(!tool.contains_key("name") && !tool.contains_key("contact"));
+    }
+
+    #[test]
+    fn test_relisting_replaces_hints() {
+        let hints = SchemaHints::new();
+        hints.learn(&json!({ "tools": [{ "name": "t", "inputSchema": { "properties": { "email": {} } } }] }));
+        hints.learn(&json!({ "tools": [{ "name": "t", "inputSchema": { "properties": { "query": {} } } }] }));
+        assert!(hints.for_tool("t").is_none());
+    }
+}
@@ -0,0 +1,183 @@
+//! Mapping passphrase storage backed by the OS credential store
+//!
+//! The passphrase protecting the mapping database (encryption and keyed hashing)
+//! lives in the macOS Keychain, the Secret Service on Linux, or the Windows
+//! Credential Manager, so it never has to appear in the config file or the
+//! process environment.
+
+use crate::config::{MappingConfig, PassphraseSource};
+use anyhow::Result;
+use rand::RngCore;
+use std::fmt;
+use tracing::{debug, warn};
+
+pub const KEYRING_SERVICE: &str = "mcp-server-conceal";
+pub const PASSPHRASE_ENV_VAR: &str = "MCP_CONCEAL_PASSPHRASE";
+
+/// A secret value whose `Debug`/`Display` output never reveals its contents.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Passphrase(String);
+
+impl Passphrase {
+    pub fn new(value: impl Into<String>) -> Self {
+        Self(value.into())
+    }
+
+    /// Generates a random 256-bit passphrase encoded as hex.
+    pub fn generate() -> Self {
+        let mut bytes = [0u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Self(bytes.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Debug for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Passphrase(<redacted>)")
+    }
+}
+
+impl fmt::Display for Passphrase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("<redacted>")
+    }
+}
+
+/// Keyring entry holding the mapping passphrase for one account name.
+pub struct PassphraseStore {
+    account: String,
+}
+
+impl PassphraseStore {
+    pub fn new(account: &str) -> Self {
+        Self {
+            account: account.to_string(),
+        }
+    }
+
+    pub fn from_config(config: &MappingConfig) -> Self {
+        Self::new(&config.keyring_account)
+    }
+
+    pub fn account(&self) -> &str {
+        &self.account
+    }
+
+    fn entry(&self) -> Result<keyring::Entry> {
+        keyring::Entry::new(KEYRING_SERVICE, &self.account)
+            .map_err(|e| anyhow::anyhow!("Failed to open credential store entry '{}': {}", self.account, e))
+    }
+
+    pub fn get(&self) -> Result<Option<Passphrase>> {
+        match self.entry()?.get_password() {
+            Ok(value) => Ok(Some(Passphrase(value))),
+            Err(keyring::Error::NoEntry) => Ok(None),
+            Err(e) => Err(anyhow::anyhow!(
+                "Failed to read passphrase '{}' from the OS credential store: {}",
+                self.account,
+                e
+            )),
+        }
+    }
+
+    pub fn set(&self, passphrase: &Passphrase) -> Result<()> {
+        self.entry()?
+            .set_password(passphrase.expose())
+            .map_err(|e| anyhow::anyhow!("Failed to store passphrase '{}' in the OS credential store: {}", self.account, e))?;
+        debug!("Stored mapping passphrase '{}' in the OS credential store", self.account);
+        Ok(())
+    }
+
+    pub fn delete(&self) -> Result<bool> {
+        match self.entry()?.delete_credential() {
+            Ok(()) => Ok(true),
+            Err(keyring::Error::NoEntry) => Ok(false),
+            Err(e) => Err(anyhow::anyhow!(
+                "Failed to delete passphrase '{}' from the OS credential store: {}",
+                self.account,
+                e
+            )),
+        }
+    }
+}
+
+/// Resolves the mapping passphrase from the configured source.
+///
+/// Returns `Ok(None)` when the source has no passphrase; callers that need one
+/// should use [`require_passphrase`] to get an actionable error instead.
+pub fn resolve_passphrase(config: &MappingConfig) -> Result<Option<Passphrase>> {
+    match config.passphrase_source {
+        PassphraseSource::Keyring => PassphraseStore::from_config(config).get(),
+        PassphraseSource::Env => Ok(env_passphrase(std::env::var(PASSPHRASE_ENV_VAR).ok())),
+    }
+}
+
+/// The passphrase held by the environment variable's value, if any.
+fn env_passphrase(value: Option<String>) -> Option<Passphrase> {
+    let value = value.filter(|value| !value.is_empty())?;
+    warn!(
+        "Reading mapping passphrase from {}; prefer passphrase_source = \"keyring\" outside of CI",
+        PASSPHRASE_ENV_VAR
+    );
+    Some(Passphrase(value))
+}
+
+pub fn require_passphrase(config: &MappingConfig) -> Result<Passphrase> {
+    resolve_passphrase(config)?.ok_or_else(|| match config.passphrase_source {
+        PassphraseSource::Keyring => anyhow::anyhow!(
+            "No mapping passphrase '{}' found in the OS credential store. Run `mcp-server-conceal passphrase init` first",
+            config.keyring_account
+        ),
+        PassphraseSource::Env => anyhow::anyhow!(
+            "No mapping passphrase found in {}",
+            PASSPHRASE_ENV_VAR
+        ),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_passphrases_are_unique_hex() {
+        let a = Passphrase::generate();
+        let b = Passphrase::generate();
+
+        assert_eq!(a.expose().len(), 64);
+        assert!(a.expose().chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_passphrase_is_redacted_in_output() {
+        let passphrase = Passphrase::new("super-secret");
+
+        assert!(!format!("{:?}", passphrase).contains("super-secret"));
+        assert!(!format!("{}", passphrase).contains("super-secret"));
+    }
+
+    // Reads values rather than setting the variable, which would race with
+    // every other test that resolves a passphrase
+    #[test]
+    fn test_resolve_from_env() {
+        assert_eq!(MappingConfig::default().passphrase_source, PassphraseSource::Keyring);
+        assert_eq!(env_passphrase(Some("from-env".to_string())), Some(Passphrase::new("from-env")));
+        assert_eq!(env_passphrase(Some(String::new())), None);
+        assert_eq!(env_passphrase(None), None);
+    }
+
+    #[test]
+    fn test_store_uses_configured_account() {
+        let config = MappingConfig {
+            keyring_account: "crm-proxy".to_string(),
+            ..MappingConfig::default()
+        };
+
+        assert_eq!(PassphraseStore::from_config(&config).account(), "crm-proxy");
+    }
+}
@@ -0,0 +1,172 @@
+//! Mapping namespaces per MCP client session
+//!
+//! With `mapping.reset_on_initialize`, every `initialize` handshake the
+//! request pipeline sees starts a new namespace below the configured one.
+//! Both pipelines of a proxy share one `SessionScope` and reopen their mapping
+//! stores when its generation moves on, so a long-running proxy gives each
+//! client session pseudonyms of its own.
+//!
+//! A session ends when the next one starts or the proxy stops, and unless
+//! `mapping.keep_sessions` is set its namespace is cleared then, so sessions
+//! do not pile up in the database.
+
+use anyhow::Result;
+use std::sync::{Arc, Mutex};
+use tracing::{info, warn};
+
+use crate::config::MappingConfig;
+use crate::mapping::MappingStore;
+
+#[derive(Clone)]
+pub struct SessionScope {
+    /// Mapping configuration with the namespace sessions are started below
+    base: MappingConfig,
+    current: Arc<Mutex<Session>>,
+}
+
+struct Session {
+    base: MappingConfig,
+    /// 0 until the first `initialize`
+    generation: u64,
+    namespace: Option<String>,
+}
+
+impl SessionScope {
+    pub fn new(base: MappingConfig) -> Self {
+        let session = Session { base: base.clone(), generation: 0, namespace: None };
+        Self { base, current: Arc::new(Mutex::new(session)) }
+    }
+
+    /// Starts a new session and returns its namespace, ending the previous one.
+    pub fn start(&self) -> String {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        let namespace = format!("{}{}", session_prefix(self.base.namespace.as_deref()), &id[..12]);
+        let mut current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+        current.generation += 1;
+        if let Some(ended) = current.namespace.replace(namespace.clone()) {
+            current.end(ended);
+        }
+        namespace
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.current.lock().unwrap_or_else(|e| e.into_inner()).generation
+    }
+
+    /// Namespace of the current session, `None` before the first one.
+    pub fn namespace(&self) -> Option<String> {
+        self.current.lock().unwrap_or_else(|e| e.into_inner()).namespace.clone()
+    }
+
+    /// Opens a mapping store on the current session's namespace, or the
+    /// configured one before the first session; returns it with the session's
+    /// generation.
+    pub fn open_store(&self) -> Result<(MappingStore, u64)> {
+        let (generation, namespace) = {
+            let current = self.current.lock().unwrap_or_else(|e| e.into_inner());
+            (current.generation, current.namespace.clone())
+        };
+        let mut config = self.base.clone();
+        if namespace.is_some() {
+            config.namespace = namespace;
+        }
+        Ok((MappingStore::new(config)?, generation))
+    }
+}
+
+/// What the namespaces of sessions started below `base` begin with.
+pub fn session_prefix(base: Option<&str>) -> String {
+    match base {
+        Some(base) => format!("{}/session-", base),
+        None => "session-".to_string(),
+    }
+}
+
+impl Session {
+    /// Clears the mappings of a session that is over.
+    fn end(&self, namespace: String) {
+        if self.base.keep_sessions {
+            return;
+        }
+        let config = MappingConfig { namespace: Some(namespace.clone()), ..self.base.clone() };
+        match MappingStore::new(config).and_then(|mut store| store.clear_all_mappings()) {
+            Ok(cleared) => info!("Session '{}' ended, cleared its {} mapping(s)", namespace, cleared),
+            Err(e) => warn!("Failed to clear the mappings of ended session '{}': {}", namespace, e),
+        }
+    }
+}
+
+/// The last pipeline holding the scope is gone: the proxy stopped, however
+/// it stopped, and the current session ends with it.
+impl Drop for Session {
+    fn drop(&mut self) {
+        if let Some(namespace) = self.namespace.take() {
+            self.end(namespace);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sessions_get_fresh_namespaces_below_the_base() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let base = MappingConfig {
+            database_path: dir.path().join("mappings.db"),
+            namespace: Some("github".to_string()),
+            ..MappingConfig::default()
+        };
+        let scope = SessionScope::new(base);
+        assert_eq!(scope.generation(), 0);
+
+        let first = scope.start();
+        let second = scope.start();
+        assert!(first.starts_with("github/session-"));
+        assert_ne!(first, second);
+        assert_eq!(scope.generation(), 2);
+
+        let (_, generation) = scope.open_store().unwrap();
+        assert_eq!(generation, 2);
+    }
+
+    fn mapping_count(base: &MappingConfig, namespace: &str) -> usize {
+        let config = MappingConfig { namespace: Some(namespace.to_string()), ..base.clone() };
+        MappingStore::new(config).unwrap().get_statistics().unwrap().total_mappings
+    }
+
+    #[test]
+    fn test_ended_sessions_are_cleared() {
+        use crate::config::AnonymizedEntity;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let base = MappingConfig { database_path: dir.path().join("mappings.db"), ..MappingConfig::default() };
+        let remember = |scope: &SessionScope| {
+            let (mut store, _) = scope.open_store().unwrap();
+            store.store_mapping(&AnonymizedEntity {
+                entity_type: "email".to_string(),
+                original_value: "jane@acme.com".to_string(),
+                fake_value: "user1@example.com".to_string(),
+                mapping_id: uuid::Uuid::new_v4().to_string(),
+            }).unwrap();
+        };
+
+        let scope = SessionScope::new(base.clone());
+        let first = scope.start();
+        remember(&scope);
+        assert_eq!(mapping_count(&base, &first), 1);
+        let second = scope.start();
+        remember(&scope);
+        assert_eq!(mapping_count(&base, &first), 0);
+        drop(scope);
+        assert_eq!(mapping_count(&base, &second), 0);
+
+        let kept = MappingConfig { keep_sessions: true, ..base.clone() };
+        let scope = SessionScope::new(kept);
+        let third = scope.start();
+        remember(&scope);
+        drop(scope);
+        assert_eq!(mapping_count(&base, &third), 1);
+    }
+}
@@ -0,0 +1,196 @@
+//! Cross-field entity stitching
+//!
+//! Tool output often arrives as several adjacent text chunks (array elements,
+//! MCP content blocks). Detecting each chunk on its own loses context: a name in
+//! one block and the email that confirms it in the next are judged separately,
+//! and a value broken across two chunks is missed entirely. When stitching is
+//! enabled, adjacent chunks are joined into windows for detection and the
+//! resulting spans are mapped back onto the chunks they came from.
+
+use crate::config::DetectedEntity;
+use serde_json::Value;
+use std::ops::Range;
+
+/// Text of a stitchable array element: a plain string or an MCP `{"type": "text"}` content block.
+pub fn text_of(value: &Value) -> Option<&str> {
+    match value {
+        Value::String(text) => Some(text),
+        Value::Object(obj) if obj.get("type").and_then(Value::as_str) == Some("text") => {
+            obj.get("text").and_then(Value::as_str)
+        }
+        _ => None,
+    }
+}
+
+/// Replaces the text of an element accepted by [`text_of`].
+pub fn set_text(value: &mut Value, text: String) {
+    match value {
+        Value::String(existing) => *existing = text,
+        Value::Object(obj) => {
+            obj.insert("text".to_string(), Value::String(text));
+        }
+        _ => {}
+    }
+}
+
+/// Runs of at least two consecutive stitchable elements, as index ranges into `items`.
+pub fn stitchable_runs(items: &[Value]) -> Vec<Range<usize>> {
+    let mut runs = Vec::new();
+    let mut run_start = None;
+
+    for (index, item) in items.iter().enumerate() {
+        match (text_of(item).is_some(), run_start) {
+            (true, None) => run_start = Some(index),
+            (false, Some(start)) => {
+                if index - start >= 2 {
+                    runs.push(start..index);
+                }
+                run_start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(start) = run_start.filter(|start| items.len() - start >= 2) {
+        runs.push(start..items.len());
+    }
+    runs
+}
+
+#[derive(Debug, Clone)]
+pub struct StitchedText {
+    pub text: String,
+    segments: Vec<Range<usize>>,
+}
+
+impl StitchedText {
+    pub fn join<S: AsRef<str>>(parts: &[S], separator: &str) -> Self {
+        let mut text = String::new();
+        let mut segments = Vec::with_capacity(parts.len());
+
+        for (index, part) in parts.iter().enumerate() {
+            if index > 0 {
+                text.push_str(separator);
+            }
+            let start = text.len();
+            text.push_str(part.as_ref());
+            segments.push(start..text.len());
+        }
+        Self { text, segments }
+    }
+
+    /// Byte ranges of the joined text covering `window` consecutive segments each.
+    /// Windows overlap by one segment, so every adjacent pair is seen together.
+    pub fn windows(&self, window: usize) -> Vec<Range<usize>> {
+        let window = window.max(2);
+        if self.segments.len() <= window {
+            return std::iter::once(0..self.text.len()).collect();
+        }
+
+        let mut ranges = Vec::new();
+        let mut first = 0;
+        loop {
+            let last = (first + window).min(self.segments.len()) - 1;
+            ranges.push(self.segments[first].start..self.segments[last].end);
+            if last == self.segments.len() - 1 {
+                break;
+            }
+            first += window - 1;
+        }
+        ranges
+    }
+
+    /// Splits entities found in the joined text into per-segment entities with
+    /// segment-relative offsets. An entity spanning several segments contributes
+    /// its trimmed fragment in each of them, under the same entity type.
+    pub fn split_entities(&self, entities: &[DetectedEntity]) -> Vec<Vec<DetectedEntity>> {
+        let mut per_segment = vec![Vec::new(); self.segments.len()];
+
+        for entity in entities {
+            for (index, segment) in self.segments.iter().enumerate() {
+                let start = entity.start.max(segment.start);
+                let end = entity.end.min(segment.end);
+                if start >= end {
+                    continue;
+                }
+                let Some(fragment) = self.text.get(start..end) else {
+                    continue;
+                };
+                let trimmed = fragment.trim();
+                if trimmed.is_empty() {
+                    continue;
+                }
+                let local_start = start - segment.start + (fragment.len() - fragment.trim_start().len());
+                per_segment[index].push(DetectedEntity {
+                    entity_type: entity.entity_type.clone(),
+                    original_value: trimmed.to_string(),
+                    start: local_start,
+                    end: local_start + trimmed.len(),
+                    confidence: entity.confidence,
+                    ..Default::default()
+                });
+            }
+        }
+        per_segment
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn entity(entity_type: &str, text: &str, value: &str) -> DetectedEntity {
+        let start = text.find(value).unwrap();
+        DetectedEntity {
+            entity_type: entity_type.to_string(),
+            original_value: value.to_string(),
+            start,
+            end: start + value.len(),
+            confidence: 0.9,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_stitchable_runs() {
+        let items = vec![
+            json!({"type": "text", "text": "Contact: Jane"}),
+            json!("Roe, jane@example.com"),
+            json!({"type": "image", "data": "..."}),
+            json!("lonely"),
+            json!(42),
+            json!("a"),
+            json!("b"),
+        ];
+
+        assert_eq!(stitchable_runs(&items), vec![0..2, 5..7]);
+    }
+
+    #[test]
+    fn test_split_entities_maps_back_to_segments() {
+        let stitched = StitchedText::join(&["Contact: Jane", "Roe <jane@example.com>"], "\n");
+        let entities = vec![
+            entity("person_name", &stitched.text, "Jane\nRoe"),
+            entity("email", &stitched.text, "jane@example.com"),
+        ];
+
+        let split = stitched.split_entities(&entities);
+
+        assert_eq!(split[0].len(), 1);
+        assert_eq!(split[0][0].original_value, "Jane");
+        assert_eq!(&"Contact: Jane"[split[0][0].start..split[0][0].end], "Jane");
+        let values: Vec<_> = split[1].iter().map(|e| e.original_value.as_str()).collect();
+        assert_eq!(values, vec!["Roe", "jane@example.com"]);
+        assert_eq!(split[1][1].start, 5);
+    }
+
+    #[test]
+    fn test_windows_overlap_by_one_segment() {
+        let stitched = StitchedText::join(&["a", "bb", "c", "dd", "e"], "\n");
+
+        let windows: Vec<_> = stitched.windows(2).iter().map(|r| stitched.text[r.clone()].to_string()).collect();
+        assert_eq!(windows, vec!["a\nbb", "bb\nc", "c\ndd", "dd\ne"]);
+
+        assert_eq!(stitched.windows(8), std::iter::once(0..stitched.text.len()).collect::<Vec<_>>());
+    }
+}
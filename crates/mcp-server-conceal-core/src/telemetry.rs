@@ -0,0 +1,131 @@
+//! Tracing spans per MCP message, optionally exported over OTLP
+//!
+//! The pipeline opens a `message` span for every message it forwards, with
+//! `read`, `detect_regex`, `detect_llm`, `fake`, `replace` and `write` spans
+//! below it, so a slow conversation shows where its time goes. Span fields
+//! carry directions, sizes and counts, never message content.
+//!
+//! With `[telemetry] otlp_endpoint` set and the `otel` feature built in, the
+//! spans are sent to an OpenTelemetry collector. The exporter is started after
+//! the configuration is read, through a layer the CLI installs empty at startup.
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::{reload, Layer, Registry};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TelemetryConfig {
+    /// OTLP/HTTP collector, e.g. `http://localhost:4318`; unset, spans are not exported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+    #[serde(default = "default_service_name")]
+    pub service_name: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: default_service_name(),
+        }
+    }
+}
+
+fn default_service_name() -> String {
+    "mcp-server-conceal".to_string()
+}
+
+pub type TelemetryLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Placeholder for the exporter layer, to be filled in by [`TelemetryHandle::start`].
+/// Only the proxy's own spans pass, so the exporter's HTTP client does not
+/// trace itself.
+pub fn reloadable() -> (impl Layer<Registry> + Send + Sync, TelemetryHandle) {
+    let (layer, handle) = reload::Layer::new(None);
+    let spans = Targets::new().with_target("mcp_server_conceal_core", tracing::Level::INFO);
+    (layer.with_filter(spans), TelemetryHandle(handle))
+}
+
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+pub struct TelemetryHandle(reload::Handle<Option<TelemetryLayer>, Registry>);
+
+impl TelemetryHandle {
+    /// Starts exporting spans when an endpoint is configured. Dropping the
+    /// returned guard flushes the spans not yet sent.
+    pub fn start(&self, config: &TelemetryConfig) -> Result<Option<TelemetryGuard>> {
+        let Some(endpoint) = config.otlp_endpoint.as_deref() else {
+            return Ok(None);
+        };
+        self.start_exporter(endpoint, &config.service_name)
+    }
+
+    #[cfg(feature = "otel")]
+    fn start_exporter(&self, endpoint: &str, service_name: &str) -> Result<Option<TelemetryGuard>> {
+        use opentelemetry::trace::TracerProvider as _;
+        use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+        use opentelemetry_sdk::trace::SdkTracerProvider;
+        use opentelemetry_sdk::Resource;
+
+        let endpoint = traces_endpoint(endpoint);
+        let exporter = SpanExporter::builder()
+            .with_http()
+            .with_endpoint(&endpoint)
+            .build()
+            .map_err(|e| anyhow::anyhow!("Failed to create OTLP exporter for {}: {}", endpoint, e))?;
+        let provider = SdkTracerProvider::builder()
+            .with_batch_exporter(exporter)
+            .with_resource(Resource::builder().with_service_name(service_name.to_string()).build())
+            .build();
+        let layer = tracing_opentelemetry::layer().with_tracer(provider.tracer("mcp-server-conceal"));
+        self.0.reload(Some(Box::new(layer) as TelemetryLayer))
+            .map_err(|e| anyhow::anyhow!("Failed to install the OTLP exporter: {}", e))?;
+        tracing::info!("Exporting tracing spans to {}", endpoint);
+        Ok(Some(TelemetryGuard(provider)))
+    }
+
+    #[cfg(not(feature = "otel"))]
+    fn start_exporter(&self, endpoint: &str, _service_name: &str) -> Result<Option<TelemetryGuard>> {
+        warn!("telemetry.otlp_endpoint is set to {}, but this build has no OTLP exporter; rebuild with --features otel", endpoint);
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "otel")]
+pub struct TelemetryGuard(opentelemetry_sdk::trace::SdkTracerProvider);
+
+#[cfg(not(feature = "otel"))]
+pub struct TelemetryGuard;
+
+#[cfg(feature = "otel")]
+impl Drop for TelemetryGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.0.shutdown() {
+            warn!("Failed to flush tracing spans: {}", e);
+        }
+    }
+}
+
+/// Collectors take spans at `/v1/traces`; a bare base URL is completed.
+#[cfg_attr(not(feature = "otel"), allow(dead_code))]
+fn traces_endpoint(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traces_endpoint_is_completed() {
+        assert_eq!(traces_endpoint("http://localhost:4318"), "http://localhost:4318/v1/traces");
+        assert_eq!(traces_endpoint("http://localhost:4318/"), "http://localhost:4318/v1/traces");
+        assert_eq!(traces_endpoint("https://otel.example.com/v1/traces"), "https://otel.example.com/v1/traces");
+    }
+}
@@ -0,0 +1,177 @@
+//! Checks that regex matches are what their pattern claims to be
+//!
+//! A regex finds things shaped like a card number; a checksum tells whether
+//! it is one. A pattern names a validator with `validator = "luhn"` in its
+//! structured form, and matches the validator rejects are dropped. Patterns
+//! named after the built-in entity types (`email`, `phone`, `ssn`,
+//! `credit_card`, `ip_address`, `url`, `mac_address`, `coordinates` and the
+//! national identifiers of the locale packs) get that type's validator and
+//! confidence unless they set their own; `validator = "none"` turns the
+//! check off. A dropped match is a value left in the clear, so types whose
+//! checks would drop real identifiers check nothing by default: an SSN
+//! pattern also finds ITINs, and a card pattern also finds numbers a typo
+//! broke the checksum of. `validator = "luhn"` opts in.
+
+use crate::geo::Point;
+use crate::national_ids;
+use serde::{Deserialize, Serialize};
+use std::net::Ipv4Addr;
+
+/// Confidence of a pattern match when neither the pattern nor its entity
+/// type sets one.
+pub const DEFAULT_CONFIDENCE: f64 = 0.8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Validator {
+    /// Accepts every match, overriding an entity type's default validator
+    None,
+    /// A local part, `@` and a dotted domain
+    Email,
+    /// At least 10 digits
+    Phone,
+    /// Area, group and serial numbers that are ever issued, ITINs (area 9xx) included
+    Ssn,
+    /// Payment card checksum over 12 to 19 digits
+    Luhn,
+    /// Four dotted decimal octets
+    Ipv4,
+    /// An `http://` or `https://` scheme
+    Url,
+    /// 12 hex digits, not all zeros or all ones
+    Mac,
+    /// A latitude and a longitude in range, separated by a comma
+    Coordinates,
+    /// UK National Insurance number prefix
+    Nino,
+    /// Canadian SIN checksum (Luhn over 9 digits)
+    Sin,
+    /// Brazilian CPF check digits
+    Cpf,
+    /// Brazilian CNPJ check digits
+    Cnpj,
+    /// Indian Aadhaar checksum (Verhoeff)
+    Aadhaar,
+    /// EU VAT number check digits, for the countries that have them
+    EuVat,
+}
+
+impl Validator {
+    pub fn check(self, value: &str) -> bool {
+        match self {
+            Validator::None => true,
+            Validator::Email => value.split_once('@')
+                .is_some_and(|(local, domain)| !local.is_empty() && domain.contains('.')),
+            Validator::Phone => digits(value).len() >= 10,
+            Validator::Ssn => valid_ssn(&digits(value)),
+            Validator::Luhn => luhn_valid(&digits(value)),
+            Validator::Ipv4 => value.parse::<Ipv4Addr>().is_ok(),
+            Validator::Url => value.starts_with("http://") || value.starts_with("https://"),
+            Validator::Mac => valid_mac(value),
+            Validator::Coordinates => Point::parse(value).is_some(),
+            Validator::Nino => national_ids::nino(value),
+            Validator::Sin => national_ids::sin(value),
+            Validator::Cpf => national_ids::cpf(value),
+            Validator::Cnpj => national_ids::cnpj(value),
+            Validator::Aadhaar => national_ids::aadhaar(value),
+            Validator::EuVat => national_ids::eu_vat(value),
+        }
+    }
+}
+
+/// Built-in entity types with their confidence and validator.
+const BUILTIN_TYPES: &[(&str, f64, Option<Validator>)] = &[
+    ("email", 0.95, Some(Validator::Email)),
+    ("phone", 0.9, Some(Validator::Phone)),
+    ("ssn", 0.95, Some(Validator::Ssn)),
+    // Luhn is opt-in: a mistyped card number is still a card number
+    ("credit_card", 0.9, None),
+    ("ip_address", 0.95, Some(Validator::Ipv4)),
+    ("url", 0.9, Some(Validator::Url)),
+    ("mac_address", 0.9, Some(Validator::Mac)),
+    ("coordinates", 0.85, Some(Validator::Coordinates)),
+    ("uk_nino", 0.9, Some(Validator::Nino)),
+    ("ca_sin", 0.85, Some(Validator::Sin)),
+    ("br_cpf", 0.9, Some(Validator::Cpf)),
+    ("br_cnpj", 0.9, Some(Validator::Cnpj)),
+    ("in_aadhaar", 0.9, Some(Validator::Aadhaar)),
+    ("in_pan", 0.85, None),
+    ("eu_vat", 0.85, Some(Validator::EuVat)),
+];
+
+/// Confidence and validator of a built-in entity type.
+pub fn defaults_for(entity_type: &str) -> (f64, Option<Validator>) {
+    BUILTIN_TYPES.iter()
+        .find(|(name, _, _)| *name == entity_type)
+        .map_or((DEFAULT_CONFIDENCE, None), |&(_, confidence, validator)| (confidence, validator))
+}
+
+/// Whether `entity_type` is one of the built-in types rather than a custom one.
+pub fn is_builtin(entity_type: &str) -> bool {
+    BUILTIN_TYPES.iter().any(|(name, _, _)| *name == entity_type)
+}
+
+fn digits(value: &str) -> Vec<u32> {
+    value.chars().filter_map(|c| c.to_digit(10)).collect()
+}
+
+fn valid_ssn(digits: &[u32]) -> bool {
+    if digits.len() != 9 {
+        return false;
+    }
+    let number = |range: std::ops::Range<usize>| digits[range].iter().fold(0, |n, d| n * 10 + d);
+    let (area, group, serial) = (number(0..3), number(3..5), number(5..9));
+    // Area 9xx is never an SSN but is an ITIN, which is just as personal
+    area != 0 && area != 666 && group != 0 && serial != 0
+}
+
+fn valid_mac(value: &str) -> bool {
+    let hex: String = value.chars().filter(char::is_ascii_hexdigit).collect();
+    hex.len() == 12 && hex.chars().any(|c| c != '0') && hex.chars().any(|c| !c.eq_ignore_ascii_case(&'f'))
+}
+
+fn luhn_valid(digits: &[u32]) -> bool {
+    (12..=19).contains(&digits.len()) && luhn_checks(digits)
+}
+
+/// Luhn checksum over any number of digits.
+pub(crate) fn luhn_checks(digits: &[u32]) -> bool {
+    let sum: u32 = digits.iter().rev().enumerate()
+        .map(|(i, &d)| if i % 2 == 1 { if d * 2 > 9 { d * 2 - 9 } else { d * 2 } } else { d })
+        .sum();
+    sum.is_multiple_of(10)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validators() {
+        assert!(Validator::Luhn.check("4111 1111 1111 1111"));
+        assert!(!Validator::Luhn.check("4111 1111 1111 1112"));
+        assert!(Validator::Ssn.check("123-45-6789"));
+        assert!(!Validator::Ssn.check("666-45-6789"));
+        assert!(!Validator::Ssn.check("123-00-6789"));
+        assert!(Validator::Ssn.check("912-70-1234"));
+        assert!(Validator::Ipv4.check("192.168.1.1"));
+        assert!(!Validator::Ipv4.check("999.1.1.1"));
+        assert!(Validator::Email.check("a@b.co"));
+        assert!(!Validator::Email.check("@b.co"));
+        assert!(!Validator::Phone.check("555-1234"));
+        assert!(Validator::None.check("anything"));
+        assert!(Validator::Mac.check("00:1a:2b:3c:4d:5e"));
+        assert!(!Validator::Mac.check("ff:ff:ff:ff:ff:ff"));
+        assert!(!Validator::Mac.check("00-00-00-00-00-00"));
+        assert!(Validator::Coordinates.check("-33.868820, 151.209296"));
+        assert!(!Validator::Coordinates.check("123.456, 10.000"));
+    }
+
+    #[test]
+    fn test_entity_types_have_defaults() {
+        assert_eq!(defaults_for("ssn"), (0.95, Some(Validator::Ssn)));
+        assert_eq!(defaults_for("credit_card"), (0.9, None));
+        assert_eq!(defaults_for("employee_id"), (DEFAULT_CONFIDENCE, None));
+        assert!(is_builtin("credit_card") && !is_builtin("employee_id"));
+    }
+}
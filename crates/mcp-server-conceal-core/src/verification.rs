@@ -0,0 +1,120 @@
+//! Second-opinion checks for critical LLM detections
+//!
+//! A small model both misses and invents entities. For the entity types where
+//! a mistake matters most (government IDs, card numbers), detections can be
+//! put to a second model, or to the same model with a verification prompt.
+//! Only candidates the verifier confirms are kept; entities of other types
+//! pass through unchanged.
+
+use crate::config::DetectedEntity;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LlmVerificationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Entity types whose detections must be confirmed
+    #[serde(default = "default_critical_types")]
+    pub entity_types: Vec<String>,
+    /// Verifier model; the detection model is used when unset
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+impl Default for LlmVerificationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            entity_types: default_critical_types(),
+            model: None,
+        }
+    }
+}
+
+fn default_critical_types() -> Vec<String> {
+    ["ssn", "credit_card", "passport", "bank_account"]
+        .into_iter()
+        .map(String::from)
+        .collect()
+}
+
+impl LlmVerificationConfig {
+    pub fn is_critical(&self, entity_type: &str) -> bool {
+        self.entity_types.iter().any(|t| t.eq_ignore_ascii_case(entity_type))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct VerificationResponse {
+    #[serde(default)]
+    confirmed: Vec<usize>,
+}
+
+/// JSON schema of the verifier's reply.
+pub fn verdicts_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {"confirmed": {"type": "array", "items": {"type": "integer"}}},
+        "required": ["confirmed"]
+    })
+}
+
+/// Asks the verifier which numbered candidates are real values of their type.
+pub fn verification_prompt(text: &str, candidates: &[&DetectedEntity]) -> String {
+    let mut prompt = String::from(
+        "You are checking the output of a PII detector. For each numbered candidate, decide whether \
+         the value appears in the TEXT and really is an entity of the stated type.\n\n",
+    );
+    prompt.push_str(&format!("TEXT: \"{}\"\n\nCANDIDATES:\n", text.replace('"', r#"\""#)));
+    for (index, candidate) in candidates.iter().enumerate() {
+        prompt.push_str(&format!("{}. type={} value=\"{}\"\n", index + 1, candidate.entity_type, candidate.original_value));
+    }
+    prompt.push_str(
+        "\nReturn ONLY JSON listing the numbers of the candidates you confirm, for example \
+         {\"confirmed\": [1, 3]}. Return {\"confirmed\": []} if none are correct.",
+    );
+    prompt
+}
+
+/// Parses the verifier's JSON into one verdict per candidate.
+pub fn parse_verdicts(json: &str, candidates: usize) -> Result<Vec<bool>> {
+    let response: VerificationResponse = serde_json::from_str(json)
+        .map_err(|e| anyhow::anyhow!("Failed to parse verification response: {}", e))?;
+    let mut verdicts = vec![false; candidates];
+    for number in response.confirmed {
+        if let Some(verdict) = number.checked_sub(1).and_then(|i| verdicts.get_mut(i)) {
+            *verdict = true;
+        }
+    }
+    Ok(verdicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_verdicts_ignores_out_of_range_numbers() {
+        assert_eq!(parse_verdicts(r#"{"confirmed": [2, 0, 7]}"#, 3).unwrap(), vec![false, true, false]);
+        assert_eq!(parse_verdicts(r#"{"confirmed": []}"#, 2).unwrap(), vec![false, false]);
+        assert!(parse_verdicts("not json", 1).is_err());
+    }
+
+    #[test]
+    fn test_prompt_numbers_candidates() {
+        let ssn = DetectedEntity {
+            entity_type: "ssn".to_string(),
+            original_value: "123-45-6789".to_string(),
+            start: 4,
+            end: 15,
+            confidence: 0.9,
+            ..Default::default()
+        };
+        let prompt = verification_prompt("SSN 123-45-6789", &[&ssn]);
+
+        assert!(prompt.contains("1. type=ssn value=\"123-45-6789\""));
+        assert!(LlmVerificationConfig::default().is_critical("SSN"));
+        assert!(!LlmVerificationConfig::default().is_critical("email"));
+    }
+}
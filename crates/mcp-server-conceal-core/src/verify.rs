@@ -0,0 +1,191 @@
+//! Offline leak verification for recorded traffic
+//!
+//! Given a capture of raw messages and the proxy's processed output for the
+//! same traffic (one JSON-RPC message per line, in the same order), checks that
+//! no entity detected in the raw side survives into the processed side. Both
+//! sides are scanned with the configured regex engine, so a report is
+//! reproducible for a given config version without an LLM.
+
+use crate::config::{base_entity_type, Config, DetectedEntity};
+use crate::detection::RegexDetectionEngine;
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LeakKind {
+    /// A value detected in the raw line appears verbatim in the processed line.
+    RawValueRetained,
+    /// Detection on the processed output found a value seen anywhere in the raw input.
+    DetectedInOutput,
+}
+
+#[derive(Debug, Clone)]
+pub struct Leak {
+    /// 1-based line number in the processed file
+    pub line: usize,
+    pub entity_type: String,
+    pub value: String,
+    /// Byte offset of the value in the processed line
+    pub offset: usize,
+    pub kind: LeakKind,
+}
+
+impl fmt::Display for Leak {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let kind = match self.kind {
+            LeakKind::RawValueRetained => "raw value retained",
+            LeakKind::DetectedInOutput => "detected in output",
+        };
+        write!(f, "line {}:{} [{}] {} ({})", self.line, self.offset, self.entity_type, mask_value(&self.value), kind)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct VerifyReport {
+    pub config_version: u32,
+    pub raw_lines: usize,
+    pub processed_lines: usize,
+    pub raw_entities: usize,
+    pub leaks: Vec<Leak>,
+}
+
+impl VerifyReport {
+    pub fn passed(&self) -> bool {
+        self.leaks.is_empty() && self.raw_lines == self.processed_lines
+    }
+}
+
+/// Keeps leak reports from re-leaking: only the first and last characters are shown.
+pub fn mask_value(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    match chars.len() {
+        0..=2 => "*".repeat(chars.len()),
+        len => format!("{}{}{}", chars[0], "*".repeat(len - 2), chars[len - 1]),
+    }
+}
+
+pub fn verify_files<P: AsRef<Path>>(config: &Config, raw: P, processed: P) -> Result<VerifyReport> {
+    let raw = std::fs::read_to_string(raw.as_ref())
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", raw.as_ref().display(), e))?;
+    let processed = std::fs::read_to_string(processed.as_ref())
+        .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", processed.as_ref().display(), e))?;
+    verify(config, &raw, &processed)
+}
+
+pub fn verify(config: &Config, raw: &str, processed: &str) -> Result<VerifyReport> {
+    let engine = RegexDetectionEngine::new(&config.detection)?;
+    let raw_lines: Vec<&str> = raw.lines().filter(|l| !l.trim().is_empty()).collect();
+    let processed_lines: Vec<&str> = processed.lines().filter(|l| !l.trim().is_empty()).collect();
+
+    let raw_entities: Vec<Vec<DetectedEntity>> = raw_lines.iter().map(|line| detect_line(&engine, line)).collect();
+    let mut raw_values: HashMap<&str, &str> = HashMap::new();
+    for entity in raw_entities.iter().flatten() {
+        raw_values.insert(entity.original_value.as_str(), base_entity_type(&entity.entity_type));
+    }
+
+    let mut report = VerifyReport {
+        config_version: config.config_version,
+        raw_lines: raw_lines.len(),
+        processed_lines: processed_lines.len(),
+        raw_entities: raw_entities.iter().map(Vec::len).sum(),
+        leaks: Vec::new(),
+    };
+
+    for (index, processed_line) in processed_lines.iter().enumerate() {
+        let line_number = index + 1;
+        let mut reported: Vec<&str> = Vec::new();
+
+        for entity in raw_entities.get(index).into_iter().flatten() {
+            let value = entity.original_value.as_str();
+            if let Some(offset) = processed_line.find(value).filter(|_| !reported.contains(&value)) {
+                reported.push(value);
+                report.leaks.push(Leak {
+                    line: line_number,
+                    entity_type: base_entity_type(&entity.entity_type).to_string(),
+                    value: value.to_string(),
+                    offset,
+                    kind: LeakKind::RawValueRetained,
+                });
+            }
+        }
+
+        for entity in detect_line(&engine, processed_line) {
+            let Some((&value, &entity_type)) = raw_values.get_key_value(entity.original_value.as_str()) else {
+                continue;
+            };
+            if reported.contains(&value) {
+                continue;
+            }
+            reported.push(value);
+            report.leaks.push(Leak {
+                line: line_number,
+                entity_type: entity_type.to_string(),
+                value: value.to_string(),
+                offset: processed_line.find(value).unwrap_or_default(),
+                kind: LeakKind::DetectedInOutput,
+            });
+        }
+    }
+
+    Ok(report)
+}
+
+fn detect_line(engine: &RegexDetectionEngine, line: &str) -> Vec<DetectedEntity> {
+    match serde_json::from_str::<Value>(line) {
+        Ok(value) => engine.detect_in_json(&value),
+        Err(_) => engine.detect_in_text(line),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RAW: &str = r#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text","text":"Mail jane.roe@acme.com"}]}}
+{"jsonrpc":"2.0","id":2,"result":{"content":[{"type":"text","text":"Owner: bob@acme.com"}]}}
+"#;
+
+    #[test]
+    fn test_clean_output_passes() {
+        let processed = r#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text","text":"Mail ella@example.org"}]}}
+{"jsonrpc":"2.0","id":2,"result":{"content":[{"type":"text","text":"Owner: kim@example.net"}]}}
+"#;
+
+        let report = verify(&Config::default(), RAW, processed).unwrap();
+
+        assert!(report.passed());
+        assert_eq!(report.raw_entities, 2);
+    }
+
+    #[test]
+    fn test_retained_and_misplaced_values_are_reported() {
+        // Line 1 kept its email; line 2 was faked, but as line 1's original value
+        let processed = r#"{"jsonrpc":"2.0","id":1,"result":{"content":[{"type":"text","text":"Mail jane.roe@acme.com"}]}}
+{"jsonrpc":"2.0","id":2,"result":{"content":[{"type":"text","text":"Owner: jane.roe@acme.com"}]}}
+"#;
+
+        let report = verify(&Config::default(), RAW, processed).unwrap();
+
+        assert!(!report.passed());
+        assert_eq!(report.leaks.len(), 2);
+        assert_eq!(report.leaks[0].kind, LeakKind::RawValueRetained);
+        assert_eq!(report.leaks[1].kind, LeakKind::DetectedInOutput);
+        assert_eq!(report.leaks[1].line, 2);
+        assert!(!report.leaks[1].to_string().contains("jane.roe@acme.com"));
+    }
+
+    #[test]
+    fn test_line_count_mismatch_fails() {
+        let report = verify(&Config::default(), RAW, "").unwrap();
+        assert!(!report.passed());
+    }
+
+    #[test]
+    fn test_mask_value() {
+        assert_eq!(mask_value("jane@acme.com"), "j***********m");
+        assert_eq!(mask_value("ab"), "**");
+    }
+}
@@ -17,6 +17,9 @@ async fn test_integrated_proxy_config() {
         target_cwd: Some(PathBuf::from("/tmp")),
         config: Config::default(),
         ollama_config: mcp_server_conceal_core::OllamaConfig::default(),
+        debug_diff: false,
+        listen: None,
+        config_source: None,
     };
     
     assert_eq!(config.target_command, "echo");
@@ -36,7 +39,9 @@ fn test_module_exports() {
     use mcp_server_conceal_core::{RegexDetectionEngine, FakerEngine, MappingStore};
     
     // These should all compile and be accessible
-    let _config = Config::default();
+    let mut _config = Config::default();
+    // Keeps the store and its key file out of the working directory
+    _config.mapping.database_path = PathBuf::from(":memory:");
     let _ollama_config = OllamaConfig::default();
     
     // Test that we can create the core engines (these were preserved in refactoring)
@@ -1,16 +1,24 @@
 //! MCP Server Conceal command-line interface
 
 use anyhow::Result;
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use mcp_server_conceal_core::log_file::LogOutputs;
+use mcp_server_conceal_core::telemetry::TelemetryHandle;
+use mcp_server_conceal_core::Catalog;
 use std::collections::HashMap;
 use std::path::PathBuf;
 use tracing::{info, warn};
+use tracing_subscriber::filter::LevelFilter;
+use tracing_subscriber::prelude::*;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(author, version, about, long_about = None, subcommand_negates_reqs = true)]
 pub struct Args {
-    #[arg(long, help = "Command to execute for the target MCP server")]
-    pub target_command: String,
+    #[command(subcommand)]
+    pub command: Option<Commands>,
+
+    #[arg(long, required_unless_present = "config", help = "Command to execute for the target MCP server (or [[targets]] in --config)")]
+    pub target_command: Option<String>,
 
     #[arg(long, help = "Arguments for the target MCP server (space-separated)")]
     pub target_args: Option<String>,
@@ -27,11 +35,210 @@ pub struct Args {
     #[arg(long, help = "Path to configuration file")]
     pub config: Option<PathBuf>,
 
+    #[arg(long, help = "Name of this proxied server: applies [profile.<name>] from the config and keeps its mappings apart in a shared database")]
+    pub profile: Option<String>,
+
+    #[arg(long, help = "Policy preset the configuration starts from: strict, balanced or minimal")]
+    pub preset: Option<mcp_server_conceal_core::presets::Preset>,
+
     #[arg(long, help = "Keep existing database mappings (by default, database is cleared on each run)")]
     pub keep_database: bool,
+
+    #[arg(long, help = "Log a masked diff (values hashed) of every message changed by the proxy")]
+    pub debug_diff: bool,
+
+    #[arg(long, value_name = "ADDRESS", help = "Serve clients on unix:<path> or tcp:<host>:<port> instead of stdin/stdout; each connection gets its own target")]
+    pub listen: Option<mcp_server_conceal_core::listener::ListenAddress>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Commands {
+    /// Manage the mapping passphrase stored in the OS credential store
+    Passphrase {
+        #[command(subcommand)]
+        action: PassphraseAction,
+    },
+    /// Check processed traffic against the raw capture for leaked entities
+    Verify {
+        #[arg(long, help = "Raw messages, one JSON-RPC message per line")]
+        raw: PathBuf,
+        #[arg(long, help = "Proxy output for the same messages, in the same order")]
+        processed: PathBuf,
+    },
+    /// Check that a proxied JSON document differs from the original only in detected entities
+    Diff {
+        #[arg(long, help = "Original JSON document, or one message per line")]
+        original: PathBuf,
+        #[arg(long, help = "The same document as the proxy forwarded it")]
+        anonymized: PathBuf,
+    },
+    /// Run texts queued during an LLM outage through the LLM and report what regex detection missed
+    Backfill {
+        #[arg(long, default_value_t = 1000, help = "Most queued texts to process in this run")]
+        limit: usize,
+    },
+    /// List entity patterns suggested by discovery, and approve or dismiss them
+    SuggestPatterns {
+        #[arg(long, default_value_t = 2, help = "Only list suggestions seen at least this many times")]
+        min_count: u64,
+        #[arg(long, conflicts_with = "dismiss", help = "Add the suggestion with this id to the config file")]
+        approve: Option<i64>,
+        #[arg(long, requires = "approve", help = "Pattern name for an approved suggestion (default: its entity type)")]
+        name: Option<String>,
+        #[arg(long, help = "Delete the suggestion with this id")]
+        dismiss: Option<i64>,
+    },
+    /// Anonymize an MCP client's own log files with the proxy's detection and mapping store
+    SanitizeLogs {
+        #[arg(long, default_value = "claude", help = "Client whose log format and location to use (claude)")]
+        client: String,
+        #[arg(long, help = "Log directory (default: the client's standard log location)")]
+        dir: Option<PathBuf>,
+        #[arg(long, help = "Replace the logs instead of writing <name>.sanitized.log copies")]
+        in_place: bool,
+    },
+    /// Show what the proxy would change in captured messages, without enforcing anything
+    Scan {
+        #[arg(help = "Messages or text to scan, one per line (default: stdin)")]
+        input: Option<PathBuf>,
+        #[arg(long, default_value = "annotated", help = "Output format: annotated (inline markers) or html (side by side)")]
+        format: String,
+        #[arg(long, help = "Write the result to this file instead of stdout")]
+        output: Option<PathBuf>,
+    },
+    /// Show the encrypted QA samples of critical detections, for manual review
+    QaSamples {
+        #[arg(long, default_value_t = 50, help = "Most samples to show, oldest first")]
+        limit: usize,
+        #[arg(long, help = "Delete all samples instead of showing them")]
+        clear: bool,
+    },
+    /// Recover the original of a value replaced with the fpe strategy
+    FpeDecrypt {
+        #[arg(long, help = "Entity type the value was detected as (e.g. ssn)")]
+        entity_type: String,
+        #[arg(help = "Encrypted value as it appeared in the output")]
+        value: String,
+    },
+    /// Look up the original a fake replaced (needs mapping.encryption and --keep-database)
+    Reveal {
+        #[arg(long, help = "Entity type the value was detected as (e.g. email)")]
+        entity_type: String,
+        #[arg(help = "Fake value as it appeared in the output")]
+        value: String,
+    },
+    /// Write this namespace's mappings to a bundle for offline de-anonymization or another machine
+    ExportMappings {
+        #[arg(help = "Bundle file to write")]
+        path: PathBuf,
+        #[arg(long, default_value = "encrypted-json", help = "Bundle format: encrypted-json (with originals, needs the passphrase) or json (hashes and fakes only)")]
+        format: String,
+    },
+    /// Merge a bundle written by export-mappings into this namespace (use with --keep-database)
+    ImportMappings {
+        #[arg(help = "Bundle file to read")]
+        path: PathBuf,
+    },
+    /// Show mapping statistics and which entity types are replaced together (needs --keep-database)
+    Stats {
+        #[arg(long, default_value = "20", help = "Number of entity type combinations to list")]
+        top: usize,
+    },
+    /// Record the current files of the target server as trusted (use with the --target-* options)
+    TrustTarget,
+    /// Find canaries of this proxy's passphrase in leaked text
+    CanaryCheck {
+        #[arg(help = "Text to search (default: stdin)")]
+        input: Option<PathBuf>,
+    },
+    /// Control a running proxy
+    Ctl {
+        #[command(subcommand)]
+        action: CtlAction,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum PassphraseAction {
+    /// Generate a random passphrase and store it in the credential store
+    Init {
+        #[arg(long, help = "Replace an existing passphrase")]
+        force: bool,
+    },
+    /// Store a passphrase read from stdin (never pass secrets as arguments)
+    Set,
+    /// Report whether a passphrase is stored, without printing it
+    Status,
+    /// Remove the passphrase from the credential store
+    Delete,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CtlAction {
+    /// Issue a signed, expiring token that skips PII processing for one tool or method
+    Bypass {
+        #[arg(long, conflicts_with = "method", required_unless_present = "method", help = "Tool name (tools/call) to bypass")]
+        tool: Option<String>,
+        #[arg(long, help = "JSON-RPC method to bypass")]
+        method: Option<String>,
+        #[arg(long, default_value = "10m", help = "How long the bypass stays active (e.g. 30s, 10m, 2h; max 24h)")]
+        ttl: String,
+        #[arg(long, help = "Why the bypass is needed (recorded in the audit trail)")]
+        reason: Option<String>,
+    },
+    /// List active bypass tokens
+    List,
+    /// Revoke a bypass token before it expires
+    Revoke {
+        id: String,
+    },
+    /// List messages held for operator approval
+    Pending,
+    /// Release a held message
+    Approve {
+        id: String,
+    },
+    /// Withhold a held message and forget its new mappings
+    Deny {
+        id: String,
+    },
+    /// Show live statistics of the running proxy (needs [admin] socket)
+    Stats,
+    /// Delete the running proxy's cached LLM detections
+    ClearCache,
+    /// Delete the running proxy's mappings; values seen again get new fakes
+    FlushMappings,
+    /// Make the running proxy re-read its configuration file
+    Reload,
+    /// Forward messages unchanged while still detecting and counting entities
+    DetectOnly {
+        #[arg(value_parser = ["on", "off"])]
+        state: String,
+    },
+}
+
+impl CtlAction {
+    /// The command for the running proxy's admin socket, for actions that are one.
+    fn admin_command(&self) -> Option<mcp_server_conceal_core::admin::AdminCommand> {
+        use mcp_server_conceal_core::admin::AdminCommand;
+
+        match self {
+            CtlAction::Stats => Some(AdminCommand::Stats),
+            CtlAction::ClearCache => Some(AdminCommand::ClearCache),
+            CtlAction::FlushMappings => Some(AdminCommand::FlushMappings),
+            CtlAction::Reload => Some(AdminCommand::Reload),
+            CtlAction::DetectOnly { state } => Some(AdminCommand::DetectOnly { enabled: state == "on" }),
+            CtlAction::Bypass { .. } | CtlAction::List | CtlAction::Revoke { .. } | CtlAction::Pending
+            | CtlAction::Approve { .. } | CtlAction::Deny { .. } => None,
+        }
+    }
 }
 
 impl Args {
+    pub fn target_command(&self) -> &str {
+        self.target_command.as_deref().unwrap_or_default()
+    }
+
     pub fn parse_target_args(&self) -> Vec<String> {
         self.target_args.as_ref()
             .and_then(|args| shell_words::split(args).ok())
@@ -68,45 +275,77 @@ async fn main() -> Result<()> {
             tracing::Level::INFO
         });
     
-    tracing_subscriber::fmt()
-        .with_max_level(log_level)
-        .with_target(false)
-        .with_writer(std::io::stderr)
+    let (telemetry_layer, telemetry) = mcp_server_conceal_core::telemetry::reloadable();
+    let log_outputs = LogOutputs::new();
+    tracing_subscriber::registry()
+        .with(telemetry_layer)
+        .with(tracing_subscriber::fmt::layer()
+            .with_target(false)
+            .with_writer(log_outputs.stderr())
+            .with_filter(LevelFilter::from_level(log_level)))
+        .with(tracing_subscriber::fmt::layer()
+            .with_target(false)
+            .with_ansi(false)
+            .with_writer(log_outputs.file())
+            .with_filter(LevelFilter::from_level(log_level)))
         .init();
 
-    info!("Starting mcp-server-conceal proxy");
-    info!("Target command: {}", args.target_command);
-    info!("Target args: {:?}", args.parse_target_args());
-    
-    let target_env = args.parse_target_env()?;
-    info!("Target environment variables: {} entries", target_env.len());
-    
-    if let Some(ref cwd) = args.target_cwd {
-        info!("Target working directory: {}", cwd.display());
+    match args.command {
+        Some(Commands::Passphrase { ref action }) => run_passphrase_command(&args, action),
+        Some(Commands::Ctl { ref action }) => match action.admin_command() {
+            Some(command) => run_admin_command(&args, &command).await,
+            None => run_ctl_command(&args, action),
+        },
+        Some(Commands::Verify { ref raw, ref processed }) => run_verify_command(&args, raw, processed),
+        Some(Commands::Diff { ref original, ref anonymized }) => run_diff_command(&args, original, anonymized),
+        Some(Commands::Backfill { limit }) => run_backfill_command(&args, limit).await,
+        Some(Commands::SuggestPatterns { min_count, approve, ref name, dismiss }) => {
+            run_suggest_patterns_command(&args, min_count, approve, name.as_deref(), dismiss)
+        }
+        Some(Commands::SanitizeLogs { ref client, ref dir, in_place }) => {
+            run_sanitize_logs_command(&args, client, dir.as_deref(), in_place).await
+        }
+        Some(Commands::Scan { ref input, ref format, ref output }) => {
+            run_scan_command(&args, input.as_deref(), format, output.as_deref()).await
+        }
+        Some(Commands::QaSamples { limit, clear }) => run_qa_samples_command(&args, limit, clear),
+        Some(Commands::FpeDecrypt { ref entity_type, ref value }) => run_fpe_decrypt_command(&args, entity_type, value),
+        Some(Commands::Reveal { ref entity_type, ref value }) => run_reveal_command(&args, entity_type, value),
+        Some(Commands::ExportMappings { ref path, ref format }) => run_export_mappings_command(&args, path, format),
+        Some(Commands::ImportMappings { ref path }) => run_import_mappings_command(&args, path),
+        Some(Commands::Stats { top }) => run_stats_command(&args, top),
+        Some(Commands::TrustTarget) => run_trust_target_command(&args),
+        Some(Commands::CanaryCheck { ref input }) => run_canary_check_command(&args, input.as_deref()),
+        None => match run_proxy(args, &telemetry, &log_outputs).await? {
+            0 => Ok(()),
+            code => std::process::exit(code),
+        },
     }
+}
 
+fn load_config(args: &Args) -> Result<mcp_server_conceal_core::Config> {
     let config = match args.config.as_ref() {
         Some(config_path) => {
             info!("Loading configuration from: {}", config_path.display());
-            mcp_server_conceal_core::Config::from_file(config_path)?
+            mcp_server_conceal_core::Config::load(config_path, args.profile.as_deref(), args.preset)?
         }
         None => {
             // Try to load from default location, fallback to default config
             match mcp_server_conceal_core::Config::get_default_config_path() {
                 Ok(default_path) if default_path.exists() => {
                     info!("Loading configuration from default location: {}", default_path.display());
-                    mcp_server_conceal_core::Config::from_file(&default_path)?
+                    mcp_server_conceal_core::Config::load(&default_path, args.profile.as_deref(), args.preset)?
                 }
                 Ok(default_path) => {
                     info!("Creating default configuration at: {}", default_path.display());
-                    let mut config = mcp_server_conceal_core::Config::default();
+                    let mut config = args.preset.map(|preset| preset.config()).unwrap_or_default();
                     config.resolve_paths()?;
                     config.to_file(&default_path)?;
                     config
                 }
                 Err(_) => {
                     info!("Using default configuration (could not determine config directory)");
-                    let mut config = mcp_server_conceal_core::Config::default();
+                    let mut config = args.preset.map(|preset| preset.config()).unwrap_or_default();
                     config.resolve_paths()?;
                     config
                 }
@@ -114,41 +353,647 @@ async fn main() -> Result<()> {
         }
     };
 
+    let mut config = config;
+    if let Some(profile) = args.profile.as_ref() {
+        config.mapping.namespace = Some(profile.clone());
+    }
+
     config.validate()?;
     info!("Configuration validated successfully");
+    Ok(config)
+}
 
-    // Remove database by default unless --keep-database is specified
-    if !args.keep_database {
-        if config.mapping.database_path.exists() {
-            info!("Removing existing database to start fresh (use --keep-database to preserve mappings)");
-            std::fs::remove_file(&config.mapping.database_path)?;
+fn run_passphrase_command(args: &Args, action: &PassphraseAction) -> Result<()> {
+    use mcp_server_conceal_core::{Passphrase, PassphraseStore};
+
+    let config = load_config(args)?;
+    let catalog = Catalog::new(&config.i18n)?;
+    let store = PassphraseStore::from_config(&config.mapping);
+    let account = store.account();
+
+    match action {
+        PassphraseAction::Init { force } => {
+            if !force && store.get()?.is_some() {
+                return Err(anyhow::anyhow!(
+                    "A passphrase '{}' already exists; use --force to replace it (existing mappings become unreadable)",
+                    store.account()
+                ));
+            }
+            store.set(&Passphrase::generate())?;
+            println!("{}", catalog.format("passphrase_generated", &[("account", &account)]));
+        }
+        PassphraseAction::Set => {
+            let mut input = String::new();
+            std::io::stdin().read_line(&mut input)?;
+            let value = input.trim_end_matches(['\r', '\n']);
+            if value.is_empty() {
+                return Err(anyhow::anyhow!("Refusing to store an empty passphrase"));
+            }
+            store.set(&Passphrase::new(value))?;
+            println!("{}", catalog.format("passphrase_stored", &[("account", &account)]));
+        }
+        PassphraseAction::Status => match store.get()? {
+            Some(_) => println!("{}", catalog.format("passphrase_present", &[("account", &account)])),
+            None => println!("{}", catalog.format("passphrase_not_set", &[("account", &account)])),
+        },
+        PassphraseAction::Delete => {
+            if store.delete()? {
+                println!("{}", catalog.format("passphrase_deleted", &[("account", &account)]));
+            } else {
+                println!("{}", catalog.format("passphrase_was_not_set", &[("account", &account)]));
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn run_admin_command(args: &Args, command: &mcp_server_conceal_core::admin::AdminCommand) -> Result<()> {
+    let config = load_config(args)?;
+    let socket = config.admin.socket.as_ref()
+        .ok_or_else(|| anyhow::anyhow!("No [admin] socket is configured"))?;
+    let result = mcp_server_conceal_core::admin::send(socket, command).await?;
+    println!("{}", serde_json::to_string_pretty(&result)?);
+    Ok(())
+}
+
+fn run_ctl_command(args: &Args, action: &CtlAction) -> Result<()> {
+    use mcp_server_conceal_core::approval::{self, ApprovalDecision};
+    use mcp_server_conceal_core::bypass::{self, BypassScope, SignedBypassToken};
+    use mcp_server_conceal_core::AuditLog;
+
+    let config = load_config(args)?;
+    let catalog = Catalog::new(&config.i18n)?;
+    let passphrase = mcp_server_conceal_core::secrets::require_passphrase(&config.mapping)?;
+    let key = bypass::signing_key(&passphrase);
+    let dir = bypass::default_bypass_dir()?;
+    let audit = AuditLog::new(&config.audit)?;
+
+    match action {
+        CtlAction::Bypass { tool, method, ttl, reason } => {
+            if !config.mapping.bypass_tokens {
+                return Err(anyhow::anyhow!("The proxy ignores bypass tokens unless [mapping] bypass_tokens = true"));
+            }
+            let scope = match (tool, method) {
+                (Some(tool), _) => BypassScope::Tool(tool.clone()),
+                (None, Some(method)) => BypassScope::Method(method.clone()),
+                (None, None) => return Err(anyhow::anyhow!("Either --tool or --method is required")),
+            };
+            let ttl = bypass::parse_ttl(ttl)?;
+            let signed = SignedBypassToken::issue(&key, scope, ttl, reason.clone())?;
+            bypass::write_token(&dir, &signed)?;
+
+            audit.record("bypass_issued", serde_json::json!({
+                "token_id": signed.token.id,
+                "scope": signed.token.scope,
+                "expires_at": signed.token.expires_at,
+                "reason": signed.token.reason,
+            }));
+            println!("{}", catalog.format("ctl_bypass_issued", &[
+                ("id", &signed.token.id),
+                ("scope", &format!("{:?}", signed.token.scope)),
+                ("seconds", &ttl.as_secs()),
+            ]));
+        }
+        CtlAction::List => {
+            let tokens = bypass::load_tokens(&dir, &key);
+            if tokens.is_empty() {
+                println!("{}", catalog.text("ctl_no_bypass_tokens"));
+            }
+            for token in tokens {
+                println!("{}", catalog.format("ctl_bypass_entry", &[
+                    ("id", &token.id),
+                    ("scope", &format!("{:?}", token.scope)),
+                    ("expires_at", &token.expires_at),
+                    ("reason", &token.reason.as_deref().unwrap_or("-")),
+                ]));
+            }
+        }
+        CtlAction::Revoke { id } => {
+            if bypass::revoke_token(&dir, id)? {
+                audit.record("bypass_revoked", serde_json::json!({ "token_id": id }));
+                println!("{}", catalog.format("ctl_bypass_revoked", &[("id", id)]));
+            } else {
+                println!("{}", catalog.format("ctl_no_bypass_token", &[("id", id)]));
+            }
+        }
+        CtlAction::Pending => {
+            let pending = approval::list_pending(&config.approval.dir);
+            if pending.is_empty() {
+                println!("{}", catalog.text("ctl_no_pending"));
+            }
+            for request in pending {
+                println!("{}", catalog.format("ctl_pending_entry", &[
+                    ("id", &request.id),
+                    ("direction", &request.direction),
+                    ("types", &request.entity_types.join(",")),
+                    ("method", &request.method.as_deref().unwrap_or("-")),
+                    ("expires_at", &request.expires_at),
+                ]));
+            }
         }
+        CtlAction::Stats | CtlAction::ClearCache | CtlAction::FlushMappings | CtlAction::Reload | CtlAction::DetectOnly { .. } => {
+            return Err(anyhow::anyhow!("This command is sent to a running proxy's admin socket"));
+        }
+        CtlAction::Approve { id } | CtlAction::Deny { id } => {
+            let decision = if matches!(action, CtlAction::Approve { .. }) {
+                ApprovalDecision::Approve
+            } else {
+                ApprovalDecision::Deny
+            };
+            if approval::decide(&config.approval.dir, &approval::signing_key(&passphrase), id, decision)? {
+                audit.record("approval_decided", serde_json::json!({ "request_id": id, "decision": decision.as_str() }));
+                println!("{}", catalog.format("ctl_decision_recorded", &[("decision", &decision.as_str()), ("id", id)]));
+            } else {
+                println!("{}", catalog.format("ctl_no_held_message", &[("id", id)]));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn run_qa_samples_command(args: &Args, limit: usize, clear: bool) -> Result<()> {
+    use mcp_server_conceal_core::quarantine::QuarantineStore;
+    use mcp_server_conceal_core::AuditLog;
+
+    let config = load_config(args)?;
+    let mut store = QuarantineStore::from_config(&config)?
+        .ok_or_else(|| anyhow::anyhow!("The QA quarantine is not enabled in this configuration"))?;
+    let audit_log = AuditLog::new(&config.audit)?;
+    let catalog = Catalog::new(&config.i18n)?;
+
+    if clear {
+        let deleted = store.clear()?;
+        audit_log.record("qa_samples_cleared", serde_json::json!({ "count": deleted }));
+        println!("{}", catalog.format("qa_deleted", &[("count", &deleted)]));
+        return Ok(());
+    }
+
+    let samples = store.samples(limit)?;
+    audit_log.record("qa_samples_viewed", serde_json::json!({
+        "count": samples.len(),
+        "ids": samples.iter().map(|sample| sample.id).collect::<Vec<_>>(),
+    }));
+    for sample in &samples {
+        println!("#{} {} [{}] at {}", sample.id, sample.direction, sample.entity_types, sample.sampled_at);
+        println!("  original:  {}", sample.original);
+        println!("  processed: {}", sample.processed);
+    }
+    println!("{}", catalog.format("qa_shown", &[("shown", &samples.len()), ("total", &store.len()?)]));
+    Ok(())
+}
+
+fn run_fpe_decrypt_command(args: &Args, entity_type: &str, value: &str) -> Result<()> {
+    use mcp_server_conceal_core::fpe::FpeCipher;
+    use mcp_server_conceal_core::AuditLog;
+
+    let config = load_config(args)?;
+    let cipher = FpeCipher::from_passphrase(&mcp_server_conceal_core::secrets::require_passphrase(&config.mapping)?);
+    let original = cipher.decrypt_value(entity_type, value)?;
+
+    AuditLog::new(&config.audit)?.record("fpe_decrypted", serde_json::json!({ "entity_type": entity_type }));
+    println!("{}", original);
+    Ok(())
+}
+
+fn run_reveal_command(args: &Args, entity_type: &str, value: &str) -> Result<()> {
+    use mcp_server_conceal_core::{AuditLog, MappingStore};
+
+    let config = load_config(args)?;
+    let store = MappingStore::new(config.mapping.clone())?;
+    let original = store.get_original(entity_type, value)?;
+
+    AuditLog::new(&config.audit)?.record("mapping_revealed", serde_json::json!({
+        "entity_type": entity_type,
+        "found": original.is_some(),
+    }));
+    let original = original
+        .ok_or_else(|| anyhow::anyhow!("No {} mapping with that fake value and a stored original", entity_type))?;
+    println!("{}", original);
+    Ok(())
+}
+
+fn run_export_mappings_command(args: &Args, path: &std::path::Path, format: &str) -> Result<()> {
+    use mcp_server_conceal_core::{AuditLog, BundleFormat, MappingStore};
+
+    let format: BundleFormat = format.parse()?;
+    let config = load_config(args)?;
+    let mut store = MappingStore::new(config.mapping.clone())?;
+    let count = store.export(path, format)?;
+
+    AuditLog::new(&config.audit)?.record("mappings_exported", serde_json::json!({
+        "mappings": count,
+        "encrypted": format == BundleFormat::EncryptedJson,
+        "originals": format == BundleFormat::EncryptedJson && config.mapping.encryption,
+    }));
+    println!("{}", Catalog::new(&config.i18n)?.format("export_done", &[("count", &count), ("path", &path.display())]));
+    Ok(())
+}
+
+fn run_import_mappings_command(args: &Args, path: &std::path::Path) -> Result<()> {
+    use mcp_server_conceal_core::{AuditLog, MappingStore};
+
+    let config = load_config(args)?;
+    let mut store = MappingStore::new(config.mapping.clone())?;
+    let report = store.import(path)?;
+
+    AuditLog::new(&config.audit)?.record("mappings_imported", serde_json::json!({
+        "imported": report.imported,
+        "already_mapped": report.already_mapped,
+        "unusable": report.unusable,
+    }));
+    let catalog = Catalog::new(&config.i18n)?;
+    println!("{}", catalog.format("import_done", &[("imported", &report.imported), ("already_mapped", &report.already_mapped)]));
+    if report.unusable > 0 {
+        println!("{}", catalog.format("import_unusable", &[("count", &report.unusable)]));
+    }
+    if report.originals_dropped > 0 {
+        println!("{}", catalog.format("import_originals_dropped", &[("count", &report.originals_dropped)]));
+    }
+    Ok(())
+}
+
+fn run_stats_command(args: &Args, top: usize) -> Result<()> {
+    use mcp_server_conceal_core::MappingStore;
+
+    let config = load_config(args)?;
+    let store = MappingStore::new(config.mapping.clone())?;
+    let stats = store.get_statistics()?;
+    let catalog = Catalog::new(&config.i18n)?;
+
+    println!("{}", catalog.format("stats_mappings", &[("count", &stats.total_mappings)]));
+    println!("{}", catalog.format("stats_cache_entries", &[("count", &stats.total_cache_entries)]));
+    println!("  {}", catalog.format("stats_cache_reused", &[("reused", &stats.reused_cache_entries), ("hits", &stats.cache_hits)]));
+    if let Some(ttl) = stats.mean_cache_ttl_seconds {
+        println!("  {}", catalog.format("stats_cache_ttl", &[("hours", &format!("{:.1}", ttl as f64 / 3600.0))]));
+    }
+    let mut by_type: Vec<_> = stats.mappings_by_type.into_iter().collect();
+    by_type.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    for (entity_type, count) in by_type {
+        println!("  {:<24} {}", entity_type, count);
+    }
+
+    let combinations = store.top_cooccurrences(top)?;
+    if combinations.is_empty() {
+        println!("{}", catalog.text("stats_no_combinations"));
+        return Ok(());
+    }
+    // Combinations drive re-identification risk more than any single type
+    println!("{}", catalog.text("stats_combinations"));
+    for (combination, messages) in combinations {
+        println!("  {:<40} {}", combination, messages);
+    }
+    Ok(())
+}
+
+fn run_trust_target_command(args: &Args) -> Result<()> {
+    use mcp_server_conceal_core::fingerprint::{FingerprintStore, TargetFingerprint};
+    use mcp_server_conceal_core::AuditLog;
+
+    let target_command = args.target_command.as_deref()
+        .ok_or_else(|| anyhow::anyhow!("--target-command is required"))?;
+    let config = load_config(args)?;
+    let fingerprint = TargetFingerprint::compute(
+        target_command,
+        &args.parse_target_args(),
+        args.target_cwd.as_deref(),
+        &args.parse_target_env()?,
+    )?;
+    let store = FingerprintStore::from_config(&config.target_trust)?;
+    store.trust(&fingerprint)?;
+
+    AuditLog::new(&config.audit)?.record("target_trusted", serde_json::json!({
+        "command_line": fingerprint.command_line,
+        "files": fingerprint.files,
+    }));
+    for file in &fingerprint.files {
+        println!("{}  {}", file.sha256, file.path.display());
+    }
+    println!("{}", Catalog::new(&config.i18n)?.format("trust_done", &[
+        ("command", &fingerprint.command_line),
+        ("path", &store.path().display()),
+    ]));
+    Ok(())
+}
+
+fn run_canary_check_command(args: &Args, input: Option<&std::path::Path>) -> Result<()> {
+    use mcp_server_conceal_core::canary;
+    use mcp_server_conceal_core::AuditLog;
+    use std::io::Read;
+
+    let config = load_config(args)?;
+    let key = canary::signing_key(&mcp_server_conceal_core::secrets::require_passphrase(&config.mapping)?);
+    let text = match input {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?,
+        None => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
+            text
+        }
+    };
+
+    let found = canary::find(&text, &config.canary.prefix, &key);
+    AuditLog::new(&config.audit)?.record("canary_checked", serde_json::json!({ "found": found.len() }));
+    for token in &found {
+        // Match the token against canary_issued audit events to find the session
+        println!("CANARY {}", token);
+    }
+    if found.is_empty() {
+        Err(anyhow::anyhow!("No canary of this proxy found (check canary.prefix and the passphrase)"))
     } else {
-        info!("Keeping existing database mappings");
+        Ok(())
+    }
+}
+
+fn run_verify_command(args: &Args, raw: &std::path::Path, processed: &std::path::Path) -> Result<()> {
+    let config = load_config(args)?;
+    let report = mcp_server_conceal_core::verify::verify_files(&config, raw, processed)?;
+    let catalog = Catalog::new(&config.i18n)?;
+
+    println!("{}", catalog.format("verify_config_version", &[("version", &report.config_version)]));
+    println!("{}", catalog.format("verify_counts", &[
+        ("raw", &report.raw_lines), ("processed", &report.processed_lines), ("entities", &report.raw_entities),
+    ]));
+    if report.raw_lines != report.processed_lines {
+        println!("{}", catalog.text("verify_line_counts_differ"));
+    }
+    for leak in &report.leaks {
+        println!("LEAK {}", leak);
+    }
+
+    if report.passed() {
+        // PASS, LEAK and UNEXPECTED stay untranslated for scripts to match
+        println!("PASS: {}", catalog.text("verify_pass"));
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Verification failed with {} leak(s)", report.leaks.len()))
+    }
+}
+
+fn run_diff_command(args: &Args, original: &std::path::Path, anonymized: &std::path::Path) -> Result<()> {
+    let config = load_config(args)?;
+    let report = mcp_server_conceal_core::json_diff::diff_files(&config, original, anonymized)?;
+    let catalog = Catalog::new(&config.i18n)?;
+
+    println!("{}", catalog.format("diff_counts", &[
+        ("documents", &report.documents),
+        ("strings", &report.replaced_strings),
+        ("entities", &report.replaced_entities),
+        ("scalars", &report.replaced_scalars),
+    ]));
+    for difference in &report.differences {
+        println!("UNEXPECTED {}", difference);
+    }
+
+    if report.passed() {
+        println!("PASS: {}", catalog.text("diff_pass"));
+        Ok(())
+    } else {
+        Err(anyhow::anyhow!("Found {} unexpected difference(s)", report.differences.len()))
+    }
+}
+
+async fn run_backfill_command(args: &Args, limit: usize) -> Result<()> {
+    use mcp_server_conceal_core::AuditLog;
+
+    let config = load_config(args)?;
+    let report = mcp_server_conceal_core::outage_queue::backfill(&config, ollama_config(&config), limit).await?;
+
+    AuditLog::new(&config.audit)?.record("backfill_result", serde_json::to_value(&report)?);
+
+    let catalog = Catalog::new(&config.i18n)?;
+    println!("{}", catalog.format("backfill_processed", &[("processed", &report.processed), ("remaining", &report.remaining)]));
+    println!("{}", catalog.format("backfill_entities", &[("found", &report.llm_entities), ("missed", &report.missed_total())]));
+    for (entity_type, count) in &report.missed_by_regex {
+        println!("  {}: {}", entity_type, count);
+    }
+    Ok(())
+}
+
+async fn run_sanitize_logs_command(args: &Args, client: &str, dir: Option<&std::path::Path>, in_place: bool) -> Result<()> {
+    use mcp_server_conceal_core::log_sanitizer::{self, LogClient};
+
+    let config = load_config(args)?;
+    let client: LogClient = client.parse()?;
+    let dir = match dir {
+        Some(dir) => dir.to_path_buf(),
+        None => client.default_log_dir()?,
+    };
+
+    let report = log_sanitizer::sanitize_logs(&config, ollama_config(&config), client, &dir, in_place).await?;
+    let catalog = Catalog::new(&config.i18n)?;
+    if report.files.is_empty() {
+        println!("{}", catalog.format("sanitize_no_logs", &[("client", &client.as_str()), ("dir", &dir.display())]));
+    }
+    for file in &report.files {
+        println!("{}", catalog.format("wrote_file", &[("path", &file.display())]));
+    }
+    println!("{}", catalog.format("sanitize_summary", &[("lines", &report.lines), ("changed", &report.changed_lines)]));
+    Ok(())
+}
+
+async fn run_scan_command(
+    args: &Args,
+    input: Option<&std::path::Path>,
+    format: &str,
+    output: Option<&std::path::Path>,
+) -> Result<()> {
+    use mcp_server_conceal_core::annotate::{self, ScanFormat};
+    use std::io::Read;
+
+    let config = load_config(args)?;
+    let format: ScanFormat = format.parse()?;
+    let text = match input {
+        Some(path) => std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read {}: {}", path.display(), e))?,
+        None => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text)?;
+            text
+        }
+    };
+
+    let report = annotate::scan(&config, ollama_config(&config), &text, format).await?;
+    let catalog = Catalog::new(&config.i18n)?;
+    match output {
+        Some(path) => {
+            std::fs::write(path, &report.output)?;
+            eprintln!("{}", catalog.format("wrote_file", &[("path", &path.display())]));
+        }
+        None => print!("{}", report.output),
     }
+    eprintln!("{}", catalog.format("scan_summary", &[("lines", &report.lines), ("changed", &report.changed_lines)]));
+    Ok(())
+}
+
+fn run_suggest_patterns_command(
+    args: &Args,
+    min_count: u64,
+    approve: Option<i64>,
+    name: Option<&str>,
+    dismiss: Option<i64>,
+) -> Result<()> {
+    use mcp_server_conceal_core::discovery::{self, SuggestionStore};
+    use mcp_server_conceal_core::{AuditLog, Config};
 
-    // Default to enabled for backward compatibility if no LLM config
-    let ollama_config = config.llm.as_ref()
+    let config = load_config(args)?;
+    let catalog = Catalog::new(&config.i18n)?;
+    let mut store = SuggestionStore::open(&config.discovery.path)?;
+
+    if let Some(id) = dismiss {
+        if !store.remove(id)? {
+            return Err(anyhow::anyhow!("No suggestion with id {}", id));
+        }
+        println!("{}", catalog.format("suggest_dismissed", &[("id", &id)]));
+        return Ok(());
+    }
+
+    if let Some(id) = approve {
+        let suggestion = store.get(id)?.ok_or_else(|| anyhow::anyhow!("No suggestion with id {}", id))?;
+        let config_path = match args.config.clone() {
+            Some(path) => path,
+            None => Config::get_default_config_path()?,
+        };
+        let added = discovery::approve_in_file(&config_path, &config, &suggestion, name)?;
+        store.remove(id)?;
+
+        AuditLog::new(&config.audit)?.record("suggestion_approved", serde_json::json!({
+            "kind": suggestion.kind.as_str(),
+            "entity_type": suggestion.entity_type,
+            "occurrences": suggestion.occurrences,
+        }));
+        let key = match suggestion.kind {
+            discovery::SuggestionKind::Pattern => "suggest_approved_pattern",
+            discovery::SuggestionKind::Dictionary => "suggest_approved_name",
+        };
+        println!("{}", catalog.format(key, &[("id", &id), ("name", &added), ("path", &config_path.display())]));
+        return Ok(());
+    }
+
+    let suggestions = store.list(min_count)?;
+    if suggestions.is_empty() {
+        println!("{}", catalog.format("suggest_none", &[("count", &min_count)]));
+    }
+    for suggestion in suggestions {
+        println!("{}", catalog.format("suggest_entry", &[
+            ("id", &format!("{:>4}", suggestion.id)),
+            ("kind", &format!("{:<10}", suggestion.kind.as_str())),
+            ("entity_type", &format!("{:<16}", suggestion.entity_type)),
+            ("count", &format!("{:>4}", suggestion.occurrences)),
+            ("candidate", &suggestion.candidate),
+        ]));
+    }
+    Ok(())
+}
+
+// Default to enabled for backward compatibility if no LLM config
+fn ollama_config(config: &mcp_server_conceal_core::Config) -> mcp_server_conceal_core::OllamaConfig {
+    config.llm.as_ref()
         .map(|llm| mcp_server_conceal_core::OllamaConfig {
             enabled: llm.enabled,
             endpoint: llm.endpoint.clone(),
             model: llm.model.clone(),
             timeout_seconds: llm.timeout_seconds,
+            circuit_breaker: llm.circuit_breaker.clone(),
+            concurrency: llm.concurrency.clone(),
+            batching: llm.batching.clone(),
+            chunking: llm.chunking.clone(),
+            verification: llm.verification.clone(),
+            output_format: llm.output_format,
+            auto_pull: llm.auto_pull,
+            provider: llm.provider,
+            allow_remote: llm.allow_remote,
+            api_key_env: llm.api_key_env.clone(),
         })
         .unwrap_or_else(|| mcp_server_conceal_core::OllamaConfig {
             enabled: true,
             endpoint: "http://localhost:11434".to_string(),
             model: "llama3.2:3b".to_string(),
             timeout_seconds: 30,
-        });
+            circuit_breaker: Default::default(),
+            concurrency: Default::default(),
+            batching: Default::default(),
+            chunking: Default::default(),
+            verification: Default::default(),
+            output_format: Default::default(),
+            auto_pull: false,
+            provider: Default::default(),
+            allow_remote: false,
+            api_key_env: None,
+        })
+}
+
+/// Runs the proxy and returns the exit code; everything it holds, such as the
+/// span exporter, is dropped before the caller exits.
+async fn run_proxy(args: Args, telemetry: &TelemetryHandle, log_outputs: &LogOutputs) -> Result<i32> {
+    let config = load_config(&args)?;
+    log_outputs.configure(&config.logging)?;
+    info!("Starting mcp-server-conceal proxy");
+    let _telemetry = telemetry.start(&config.telemetry)?;
+    match (args.target_command.is_some(), config.targets.is_empty()) {
+        (true, false) => return Err(anyhow::anyhow!("--target-command cannot be combined with [[targets]] in the configuration")),
+        (false, true) => return Err(anyhow::anyhow!("Either --target-command or [[targets]] in the configuration is required")),
+        (true, true) => {
+            info!("Target command: {}", args.target_command());
+            info!("Target args: {:?}", args.parse_target_args());
+        }
+        (false, false) => info!("Gateway mode with {} targets", config.targets.len()),
+    }
+
+    let target_env = args.parse_target_env()?;
+    info!("Target environment variables: {} entries", target_env.len());
+
+    if let Some(ref cwd) = args.target_cwd {
+        info!("Target working directory: {}", cwd.display());
+    }
+
+    if config.mapping.encryption {
+        // Fail at startup rather than on the first message if the key is missing
+        mcp_server_conceal_core::secrets::require_passphrase(&config.mapping)?;
+        info!("Mapping passphrase resolved from {:?}", config.mapping.passphrase_source);
+    }
+
+    // Clear this server's mappings by default unless --keep-database is specified;
+    // other namespaces in a shared database are left alone
+    if !args.keep_database {
+        if config.mapping.database_path.exists() {
+            let namespaces: Vec<Option<String>> = if config.targets.is_empty() {
+                vec![config.mapping.namespace.clone()]
+            } else {
+                config.targets.iter().map(|target| Some(target.namespace(config.mapping.namespace.as_deref()))).collect()
+            };
+            for namespace in namespaces {
+                info!("Clearing mappings of namespace '{}' to start fresh (use --keep-database to preserve mappings)",
+                      namespace.as_deref().unwrap_or_default());
+                let mut mapping = config.mapping.clone();
+                mapping.namespace = namespace;
+                let mut store = mcp_server_conceal_core::MappingStore::new(mapping)?;
+                store.clear_all_mappings()?;
+                store.clear_llm_cache()?;
+            }
+        }
+    } else {
+        info!("Keeping existing database mappings");
+    }
+
+    let ollama_config = ollama_config(&config);
 
     let proxy_config = mcp_server_conceal_core::IntegratedProxyConfig {
-        target_command: args.target_command.clone(),
+        target_command: args.target_command().to_string(),
         target_args: args.parse_target_args(),
         target_env,
         target_cwd: args.target_cwd.clone(),
         config,
         ollama_config,
+        debug_diff: args.debug_diff,
+        listen: args.listen.clone(),
+        config_source: args.config.clone()
+            .or_else(|| mcp_server_conceal_core::Config::get_default_config_path().ok().filter(|path| path.exists()))
+            .map(|path| mcp_server_conceal_core::config::ConfigSource {
+                path,
+                profile: args.profile.clone(),
+                preset: args.preset,
+            }),
     };
 
     let mut proxy = mcp_server_conceal_core::IntegratedProxy::new(proxy_config)?;
@@ -161,13 +1006,18 @@ mod tests {
 
     fn create_test_args() -> Args {
         Args {
-            target_command: "python".to_string(),
+            command: None,
+            target_command: Some("python".to_string()),
             target_args: None,
             target_env: vec![],
             target_cwd: None,
             log_level: "info".to_string(),
             config: None,
+            profile: None,
+            preset: None,
             keep_database: false,
+            debug_diff: false,
+            listen: None,
         }
     }
 
@@ -219,4 +1069,70 @@ mod tests {
         
         assert!(args.parse_target_env().is_err());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_subcommand_does_not_require_target() {
+        let args = Args::try_parse_from(["mcp-server-conceal", "passphrase", "status"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Commands::Passphrase { action: PassphraseAction::Status })
+        ));
+
+        let args = Args::try_parse_from(["mcp-server-conceal", "backfill", "--limit", "50"]).unwrap();
+        assert!(matches!(args.command, Some(Commands::Backfill { limit: 50 })));
+
+        let args = Args::try_parse_from(["mcp-server-conceal", "suggest-patterns", "--approve", "3", "--name", "employee_id"]).unwrap();
+        assert!(matches!(args.command, Some(Commands::SuggestPatterns { approve: Some(3), .. })));
+        assert!(Args::try_parse_from(["mcp-server-conceal", "suggest-patterns", "--name", "x"]).is_err());
+
+        let args = Args::try_parse_from(["mcp-server-conceal", "scan", "capture.jsonl", "--format", "html"]).unwrap();
+        assert!(matches!(args.command, Some(Commands::Scan { ref format, .. }) if format == "html"));
+
+        let args = Args::try_parse_from(["mcp-server-conceal", "reveal", "--entity-type", "email", "kim@example.net"]).unwrap();
+        assert!(matches!(args.command, Some(Commands::Reveal { ref value, .. }) if value == "kim@example.net"));
+
+        let args = Args::try_parse_from(["mcp-server-conceal", "export-mappings", "bundle.json"]).unwrap();
+        assert!(matches!(args.command, Some(Commands::ExportMappings { ref format, .. }) if format == "encrypted-json"));
+
+        let args = Args::try_parse_from(["mcp-server-conceal", "import-mappings", "bundle.json"]).unwrap();
+        assert!(matches!(args.command, Some(Commands::ImportMappings { ref path }) if path == std::path::Path::new("bundle.json")));
+
+        let args = Args::try_parse_from(["mcp-server-conceal", "stats", "--top", "5"]).unwrap();
+        assert!(matches!(args.command, Some(Commands::Stats { top: 5 })));
+
+        let args = Args::try_parse_from(["mcp-server-conceal", "--profile", "github", "stats"]).unwrap();
+        assert_eq!(args.profile.as_deref(), Some("github"));
+
+        let args = Args::try_parse_from(["mcp-server-conceal", "--preset", "strict", "--target-command", "python"]).unwrap();
+        assert_eq!(args.preset, Some(mcp_server_conceal_core::presets::Preset::Strict));
+        assert!(Args::try_parse_from(["mcp-server-conceal", "--preset", "paranoid", "--target-command", "python"]).is_err());
+
+        let args = Args::try_parse_from(["mcp-server-conceal", "qa-samples", "--clear"]).unwrap();
+        assert!(matches!(args.command, Some(Commands::QaSamples { limit: 50, clear: true })));
+
+        let args = Args::try_parse_from(["mcp-server-conceal", "canary-check", "leaked.txt"]).unwrap();
+        assert!(matches!(args.command, Some(Commands::CanaryCheck { input: Some(_) })));
+
+        assert!(Args::try_parse_from(["mcp-server-conceal"]).is_err());
+        assert!(Args::try_parse_from(["mcp-server-conceal", "--config", "gateway.toml"]).is_ok());
+    }
+
+    #[test]
+    fn test_ctl_bypass_requires_single_scope() {
+        let args = Args::try_parse_from(["mcp-server-conceal", "ctl", "bypass", "--tool", "query_db", "--ttl", "5m"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Commands::Ctl { action: CtlAction::Bypass { ref tool, .. } }) if tool.as_deref() == Some("query_db")
+        ));
+
+        assert!(Args::try_parse_from(["mcp-server-conceal", "ctl", "bypass"]).is_err());
+
+        let args = Args::try_parse_from(["mcp-server-conceal", "ctl", "detect-only", "on"]).unwrap();
+        assert!(matches!(
+            args.command,
+            Some(Commands::Ctl { ref action }) if action.admin_command() == Some(mcp_server_conceal_core::admin::AdminCommand::DetectOnly { enabled: true })
+        ));
+        assert!(Args::try_parse_from(["mcp-server-conceal", "ctl", "detect-only", "maybe"]).is_err());
+        assert!(Args::try_parse_from(["mcp-server-conceal", "ctl", "bypass", "--tool", "a", "--method", "b"]).is_err());
+    }
+}
@@ -0,0 +1,229 @@
+//! Runs every example under `examples/` through the real binary.
+//!
+//! Each example directory holds a `config.toml`, a scripted `server.py` and a
+//! `scenario.json` listing messages to send and what the client must (not)
+//! see in the responses. See `examples/README.md` for the scenario format.
+
+use regex::Regex;
+use serde_json::Value;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc;
+use std::time::Duration;
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn examples_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("../../examples")
+}
+
+fn python_available() -> bool {
+    Command::new("python3").arg("--version").output().is_ok_and(|out| out.status.success())
+}
+
+/// The value at a JSON pointer as text, without quotes for strings.
+fn text_at(response: &Value, pointer: &str) -> String {
+    match response.pointer(pointer) {
+        Some(Value::String(s)) => s.clone(),
+        Some(other) => other.to_string(),
+        None => panic!("no value at {} in {}", pointer, response),
+    }
+}
+
+fn check_response(example: &str, step: &Value, response: &Value) {
+    let line = response.to_string();
+    for absent in step["absent"].as_array().into_iter().flatten() {
+        let absent = absent.as_str().unwrap();
+        assert!(!line.contains(absent), "{}: '{}' leaked in {}", example, absent, line);
+    }
+    for present in step["present"].as_array().into_iter().flatten() {
+        let present = present.as_str().unwrap();
+        assert!(line.contains(present), "{}: '{}' missing from {}", example, present, line);
+    }
+    for (pointer, pattern) in step["matches"].as_object().into_iter().flatten() {
+        let value = text_at(response, pointer);
+        let regex = Regex::new(pattern.as_str().unwrap()).unwrap();
+        assert!(regex.is_match(&value), "{}: {} = '{}' does not match {}", example, pointer, value, regex);
+    }
+    for group in step["same"].as_array().into_iter().flatten() {
+        let captures: Vec<String> = group.as_array().unwrap().iter()
+            .map(|pair| {
+                let (pointer, pattern) = (pair[0].as_str().unwrap(), pair[1].as_str().unwrap());
+                let value = text_at(response, pointer);
+                Regex::new(pattern).unwrap().captures(&value)
+                    .unwrap_or_else(|| panic!("{}: {} = '{}' does not match {}", example, pointer, value, pattern))[1]
+                    .to_string()
+            })
+            .collect();
+        assert!(captures.windows(2).all(|w| w[0] == w[1]), "{}: expected equal parts, got {:?}", example, captures);
+    }
+}
+
+fn run_example(dir: &Path) {
+    let example = dir.file_name().unwrap().to_string_lossy().to_string();
+    let scenario: Value = serde_json::from_str(&std::fs::read_to_string(dir.join("scenario.json")).unwrap()).unwrap();
+    // Keep the mapping database, audit log and fingerprints out of the real data directory
+    let home = tempfile::TempDir::new().unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mcp-server-conceal"))
+        .arg("--target-command").arg("python3")
+        .arg("--target-args").arg("server.py")
+        .arg("--target-cwd").arg(dir)
+        .arg("--config").arg(dir.join("config.toml"))
+        .arg("--log-level").arg("warn")
+        .env("HOME", home.path())
+        .env("XDG_DATA_HOME", home.path().join("data"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .unwrap();
+
+    let mut stdin = child.stdin.take().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let stdout = BufReader::new(child.stdout.take().unwrap());
+    std::thread::spawn(move || {
+        for line in stdout.lines().map_while(Result::ok) {
+            if tx.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    for step in scenario["steps"].as_array().unwrap() {
+        writeln!(stdin, "{}", step["send"]).unwrap();
+        stdin.flush().unwrap();
+        if step["send"].get("id").is_none() {
+            continue;
+        }
+
+        let line = rx.recv_timeout(RESPONSE_TIMEOUT)
+            .unwrap_or_else(|_| panic!("{}: no response to {}", example, step["send"]));
+        let response: Value = serde_json::from_str(&line).unwrap();
+        assert_eq!(response["id"], step["send"]["id"], "{}: unexpected response {}", example, line);
+        check_response(&example, step, &response);
+    }
+
+    drop(stdin);
+    child.kill().ok();
+    child.wait().ok();
+}
+
+#[test]
+fn test_examples() {
+    if !python_available() {
+        eprintln!("python3 not found, skipping examples");
+        return;
+    }
+
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(examples_dir()).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.join("scenario.json").is_file())
+        .collect();
+    dirs.sort();
+    assert!(!dirs.is_empty(), "no examples found");
+
+    for dir in dirs {
+        run_example(&dir);
+    }
+}
+
+/// Reads `Content-Length` framed messages until the stream ends.
+fn read_frames(stdout: impl Read, tx: mpsc::Sender<Value>) {
+    let mut reader = BufReader::new(stdout);
+    loop {
+        let mut length = None;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).unwrap_or(0) == 0 {
+                return;
+            }
+            match header.trim().split_once(':') {
+                Some((name, value)) if name.eq_ignore_ascii_case("content-length") => length = value.trim().parse().ok(),
+                _ if header.trim().is_empty() && length.is_some() => break,
+                _ => {}
+            }
+        }
+        let mut body = vec![0; length.unwrap_or_default()];
+        if reader.read_exact(&mut body).is_err() || tx.send(serde_json::from_slice(&body).unwrap()).is_err() {
+            return;
+        }
+    }
+}
+
+#[test]
+fn test_gateway() {
+    if !python_available() {
+        eprintln!("python3 not found, skipping the gateway");
+        return;
+    }
+
+    let home = tempfile::TempDir::new().unwrap();
+    let config = home.path().join("gateway.toml");
+    let target = |name: &str| format!(
+        "[[targets]]\nname = \"{}\"\ncommand = \"python3\"\nargs = [\"server.py\"]\ncwd = '{}'\n",
+        name, examples_dir().join(name).display(),
+    );
+    std::fs::write(&config, format!(
+        "config_version = 2\n[processing]\nframing = \"auto\"\n\
+         [detection]\nmode = \"regex\"\nenabled = true\nconfidence_threshold = 0.8\n\
+         [detection.patterns]\nemail = '\\b[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\\.[A-Za-z]{{2,}}\\b'\n\
+         [faker]\nlocale = \"en_US\"\nseed = 12345\nconsistency = true\n\
+         [mapping]\ndatabase_path = '{}'\nencryption = false\n{}{}",
+        home.path().join("gateway.db").display(), target("github"), target("database"),
+    )).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_mcp-server-conceal"))
+        .arg("--config").arg(&config)
+        .arg("--log-level").arg("warn")
+        .env("HOME", home.path())
+        .env("XDG_DATA_HOME", home.path().join("data"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let (tx, rx) = mpsc::channel();
+    let stdout = child.stdout.take().unwrap();
+    std::thread::spawn(move || read_frames(stdout, tx));
+
+    let mut call = |message: Value| -> Value {
+        let body = message.to_string();
+        write!(stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+        stdin.flush().unwrap();
+        if message.get("id").is_none() {
+            return Value::Null;
+        }
+        let response = rx.recv_timeout(RESPONSE_TIMEOUT).unwrap_or_else(|_| panic!("gateway: no response to {}", message));
+        assert_eq!(response["id"], message["id"], "gateway: unexpected response {}", response);
+        response
+    };
+
+    let initialized = call(serde_json::json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+        "protocolVersion": "2024-11-05", "capabilities": {}, "clientInfo": {"name": "example", "version": "1.0.0"},
+    }}));
+    assert_eq!(initialized["result"]["serverInfo"]["name"], "mcp-server-conceal");
+    call(serde_json::json!({"jsonrpc": "2.0", "method": "notifications/initialized"}));
+
+    let listed = call(serde_json::json!({"jsonrpc": "2.0", "id": 2, "method": "tools/list"}));
+    let tools: Vec<&str> = listed["result"]["tools"].as_array().unwrap().iter().filter_map(|tool| tool["name"].as_str()).collect();
+    assert!(tools.contains(&"github__get_issue") && tools.contains(&"database__query"), "gateway: tools {:?}", tools);
+
+    let issue = call(serde_json::json!({"jsonrpc": "2.0", "id": 3, "method": "tools/call", "params": {"name": "github__get_issue", "arguments": {"number": 42}}}));
+    let text = issue.to_string();
+    assert!(text.contains("Login fails with SSO") && !text.contains("jane.roe@acme.com"), "gateway: {}", text);
+
+    // Closing the client's input stops both targets and the proxy
+    drop(stdin);
+    let deadline = std::time::Instant::now() + RESPONSE_TIMEOUT;
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        assert!(std::time::Instant::now() < deadline, "gateway: still running after its client left");
+        std::thread::sleep(Duration::from_millis(50));
+    };
+    assert!(status.success(), "gateway: exited with {}", status);
+}
@@ -0,0 +1,39 @@
+#!/usr/bin/env python3
+"""Scripted stand-in for a database MCP server.
+
+Answers `query` with rows of customer records as structured content, mixing
+string and integer columns.
+"""
+
+import os
+import sys
+
+sys.path.insert(0, os.path.join(os.path.dirname(os.path.abspath(__file__)), ".."))
+from stub_server import serve  # noqa: E402
+
+ROWS = [
+    {"id": 1, "name": "Jane Roe", "email": "jane.roe@acme.com", "ssn": 123456789, "last_login_ip": "10.1.2.5", "active": True},
+    {"id": 2, "name": "Bob Smith", "email": "bob.smith@globex.com", "ssn": 987654321, "last_login_ip": "10.1.2.9", "active": False},
+]
+
+TOOLS = [
+    {
+        "name": "query",
+        "description": "Run a read-only SQL query",
+        "inputSchema": {"type": "object", "properties": {"sql": {"type": "string"}}, "required": ["sql"]},
+    },
+]
+
+
+def call(name, arguments):
+    if name == "query":
+        sql = arguments.get("sql", "")
+        rows = [row for row in ROWS if "'" not in sql or f"'{row['email']}'" in sql]
+        return {
+            "content": [{"type": "text", "text": f"{len(rows)} rows"}],
+            "structuredContent": {"rows": rows},
+        }
+    return None
+
+
+serve("database", TOOLS, call)
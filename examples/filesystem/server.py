@@ -0,0 +1,49 @@
+#!/usr/bin/env python3
+"""Scripted stand-in for a filesystem MCP server.
+
+Serves a few in-memory files containing contact details, so the proxy has
+free text to anonymize.
+"""
+
+import os
+import sys
+
+sys.path.insert(0, os.path.join(os.path.dirname(os.path.abspath(__file__)), ".."))
+from stub_server import serve, text  # noqa: E402
+
+FILES = {
+    "/home/jane/notes.txt": (
+        "Call Jane Roe at 555-867-5309 or write to jane.roe@acme.com.\n"
+        "SSN on file: 123-45-6789."
+    ),
+    "/home/jane/todo.md": "- [ ] send the contract to bob.smith@globex.com",
+}
+
+TOOLS = [
+    {
+        "name": "read_file",
+        "description": "Read a text file",
+        "inputSchema": {"type": "object", "properties": {"path": {"type": "string"}}, "required": ["path"]},
+    },
+    {
+        "name": "write_file",
+        "description": "Write a text file",
+        "inputSchema": {
+            "type": "object",
+            "properties": {"path": {"type": "string"}, "content": {"type": "string"}},
+            "required": ["path", "content"],
+        },
+    },
+]
+
+
+def call(name, arguments):
+    if name == "read_file":
+        return text(FILES.get(arguments.get("path"), "No such file"))
+    if name == "write_file":
+        FILES[arguments["path"]] = arguments["content"]
+        return text(f"Wrote {len(arguments['content'])} characters to {arguments['path']}")
+    return None
+
+
+serve("filesystem", TOOLS, call)
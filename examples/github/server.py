@@ -0,0 +1,57 @@
+#!/usr/bin/env python3
+"""Scripted stand-in for a GitHub MCP server.
+
+Returns issues as structured JSON, where author details sit under keys such
+as `email` and `name` that the proxy's key hints recognise.
+"""
+
+import os
+import sys
+
+sys.path.insert(0, os.path.join(os.path.dirname(os.path.abspath(__file__)), ".."))
+from stub_server import serve  # noqa: E402
+
+ISSUES = {
+    42: {
+        "number": 42,
+        "title": "Login fails with SSO",
+        "state": "open",
+        "user": {"login": "jroe", "name": "Jane Roe", "email": "jane.roe@acme.com"},
+        "assignee": {"login": "bsmith", "name": "Bob Smith", "email": "bob.smith@acme.com"},
+        "body": "Reproduced from the office network, please ping jane.roe@acme.com for logs.",
+    },
+}
+
+TOOLS = [
+    {
+        "name": "get_issue",
+        "description": "Fetch an issue by number",
+        "inputSchema": {"type": "object", "properties": {"number": {"type": "integer"}}, "required": ["number"]},
+    },
+    {
+        "name": "create_comment",
+        "description": "Comment on an issue",
+        "inputSchema": {
+            "type": "object",
+            "properties": {"number": {"type": "integer"}, "body": {"type": "string"}},
+            "required": ["number", "body"],
+        },
+    },
+]
+
+
+def call(name, arguments):
+    if name == "get_issue":
+        issue = ISSUES.get(arguments.get("number"))
+        if issue is None:
+            return {"content": [{"type": "text", "text": "Issue not found"}], "isError": True}
+        return {
+            "content": [{"type": "text", "text": f"#{issue['number']} {issue['title']}"}],
+            "structuredContent": issue,
+        }
+    if name == "create_comment":
+        return {"content": [{"type": "text", "text": f"Commented: {arguments['body']}"}]}
+    return None
+
+
+serve("github", TOOLS, call)
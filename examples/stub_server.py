@@ -0,0 +1,43 @@
+"""Shared loop of the scripted example servers.
+
+Answers `initialize` and `tools/list`, hands `tools/call` to the example's
+`call(name, arguments)`, and logs every message received to stderr, which
+shows what the server actually got through the proxy.
+"""
+
+import json
+import sys
+
+
+def text(content):
+    return {"content": [{"type": "text", "text": content}]}
+
+
+def serve(name, tools, call):
+    def handle(request):
+        method = request.get("method")
+        params = request.get("params", {})
+        if method == "initialize":
+            return {
+                "protocolVersion": "2024-11-05",
+                "capabilities": {"tools": {}},
+                "serverInfo": {"name": f"example-{name}", "version": "1.0.0"},
+            }
+        if method == "tools/list":
+            return {"tools": tools}
+        if method == "tools/call":
+            return call(params.get("name"), params.get("arguments", {}))
+        return None
+
+    for line in sys.stdin:
+        request = json.loads(line)
+        print(f"[{name}] received {line.strip()}", file=sys.stderr, flush=True)
+        if "id" not in request:
+            continue
+        result = handle(request)
+        response = {"jsonrpc": "2.0", "id": request["id"]}
+        if result is None:
+            response["error"] = {"code": -32601, "message": "Method not found"}
+        else:
+            response["result"] = result
+        print(json.dumps(response), flush=True)